@@ -0,0 +1,162 @@
+// audio - los bloques de metadata como sonido, sin camara (feature "audio")
+// audio.qrfs export <qr_folder|disco.img> <salida.wav> [--blocks 0,1,...] [--config qrfs.toml]
+// audio.qrfs import <qr_folder> <grabacion.wav>... [--config qrfs.toml]
+// export modula el superblock y el bitmap (u otros bloques con --blocks) en
+// un wav para pasarlo por un parlante; import lee la grabacion del otro lado
+// y guarda los bloques como si se hubieran escaneado sus qr
+use std::env;
+use std::process;
+
+use qrfs_core::errors::QrfsError;
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("audio.qrfs: error: {e}");
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), QrfsError> {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 4 {
+        eprintln!("Uso: audio.qrfs export <qr_folder|disco.img> <salida.wav> [--blocks 0,1,...] [--config qrfs.toml]");
+        eprintln!("     audio.qrfs import <qr_folder> <grabacion.wav>... [--config qrfs.toml]");
+        return Ok(());
+    }
+
+    match args[1].as_str() {
+        "export" => channel::export(&args[2], &args[3], &args),
+        "import" => channel::import(&args[2], &args),
+        other => Err(QrfsError::Other(format!("comando desconocido '{}' (export o import)", other))),
+    }
+}
+
+#[cfg(feature = "audio")]
+mod channel {
+    use std::path::Path;
+
+    use qrfs_core::audio::{decode_texts, encode_texts, read_wav, write_wav, BAUD, SAMPLE_RATE};
+    use qrfs_core::camera::ingest_text;
+    use qrfs_core::codec::{BinaryCodec, BlockCodec};
+    use qrfs_core::config::{config_arg, QrfsConfig};
+    use qrfs_core::disk::BlockId;
+    use qrfs_core::errors::QrfsError;
+    use qrfs_core::missing::{scan_order, BlockRole};
+    use qrfs_core::storage::{BlockStorage, ContainerStorage, ScanDecision};
+
+    pub fn export(disk: &str, out: &str, args: &[String]) -> Result<(), QrfsError> {
+        let config = QrfsConfig::for_disk(disk, config_arg(args))?;
+        let blocks = match args.iter().position(|a| a == "--blocks") {
+            Some(i) => args
+                .get(i + 1)
+                .ok_or_else(|| QrfsError::Other("--blocks espera una lista de bloques".into()))?
+                .split(',')
+                .map(|b| b.trim().parse().map_err(|_| QrfsError::Other(format!("bloque invalido '{}'", b))))
+                .collect::<Result<Vec<BlockId>, _>>()?,
+            None => Vec::new(),
+        };
+
+        // un contenedor no guarda generaciones: van en 0
+        let texts = if Path::new(disk).is_file() {
+            encode_blocks(&ContainerStorage::open_disk(disk)?, blocks, |_| 0)?
+        } else {
+            let storage = config.qr_storage(disk);
+            encode_blocks(&storage, blocks, |id| storage.generation(id).unwrap_or(0))?
+        };
+        let samples = encode_texts(&texts)?;
+        write_wav(out, &samples)?;
+        println!(
+            "audio.qrfs: {} bloques en {} ({:.1}s a {} baudios)",
+            texts.len(),
+            out,
+            samples.len() as f32 / SAMPLE_RATE as f32,
+            BAUD
+        );
+        Ok(())
+    }
+
+    // sin lista: el superblock y el bitmap, lo que hace falta para montar
+    fn encode_blocks<S: BlockStorage>(
+        storage: &S,
+        mut blocks: Vec<BlockId>,
+        generation: impl Fn(BlockId) -> u64,
+    ) -> Result<Vec<String>, QrfsError> {
+        if blocks.is_empty() {
+            blocks = scan_order(storage)
+                .into_iter()
+                .filter(|&(_, role)| matches!(role, BlockRole::Superblock | BlockRole::Bitmap))
+                .map(|(block, _)| block)
+                .collect();
+        }
+        blocks
+            .into_iter()
+            .map(|id| Ok(BinaryCodec.encode(id, generation(id), &storage.read_block(id)?)))
+            .collect()
+    }
+
+    pub fn import(disk: &str, args: &[String]) -> Result<(), QrfsError> {
+        let config = QrfsConfig::for_disk(disk, config_arg(args))?;
+        let storage = config.qr_storage(disk);
+        let mut recordings = Vec::new();
+        let mut i = 3;
+        while i < args.len() {
+            if args[i] == "--config" {
+                i += 1;
+            } else {
+                recordings.push(&args[i]);
+            }
+            i += 1;
+        }
+
+        let mut saved = 0;
+        for recording in recordings {
+            let (samples, rate) = read_wav(recording)?;
+            let texts = decode_texts(&samples, rate);
+            if texts.is_empty() {
+                eprintln!("audio.qrfs: {}: no se entendio ningun bloque", recording);
+            }
+            for text in texts {
+                let scanned = ingest_text(&storage, &text);
+                match (scanned.block, scanned.result) {
+                    (Some(block), Ok(ScanDecision::Saved(gen))) => {
+                        saved += 1;
+                        println!("audio.qrfs: bloque {} guardado (gen {})", block, gen);
+                    }
+                    (Some(block), Ok(ScanDecision::Duplicate)) => println!("audio.qrfs: bloque {} ya estaba", block),
+                    (Some(block), Ok(ScanDecision::Stale { scanned, stored })) => println!(
+                        "audio.qrfs: bloque {} obsoleto (gen {} < {} guardada), se omite",
+                        block, scanned, stored
+                    ),
+                    (Some(block), Ok(ScanDecision::Conflict(gen))) => println!(
+                        "audio.qrfs: bloque {} con gen {} ya guardada con otro contenido, se omite",
+                        block, gen
+                    ),
+                    (_, Err(e)) => eprintln!("audio.qrfs: bloque descartado: {}", e),
+                    (None, Ok(_)) => {}
+                }
+            }
+        }
+        println!("audio.qrfs: {} bloques guardados", saved);
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+mod channel {
+    use qrfs_core::errors::QrfsError;
+
+    fn unavailable() -> Result<(), QrfsError> {
+        Err(QrfsError::Unimplemented(
+            "el canal de audio necesita compilar con --features audio".into(),
+        ))
+    }
+
+    pub fn export(_disk: &str, _out: &str, _args: &[String]) -> Result<(), QrfsError> {
+        unavailable()
+    }
+
+    pub fn import(_disk: &str, _args: &[String]) -> Result<(), QrfsError> {
+        unavailable()
+    }
+}
@@ -0,0 +1,48 @@
+// audit - lee el registro de auditoria que deja mount --audit-log
+// dump:   muestra cada operacion anotada
+// verify: comprueba la cadena de hashes (entradas borradas o editadas)
+
+use std::env;
+use std::path::Path;
+use std::process;
+
+use qrfs_core::audit::{read_entries, verify_chain, AUDIT_FILE};
+use qrfs_core::errors::QrfsError;
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("audit.qrfs: error: {e}");
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), QrfsError> {
+    let args: Vec<String> = env::args().collect();
+
+    // sintaxis: audit.qrfs <dump|verify> <carpeta_auditoria>
+    if args.len() < 3 {
+        eprintln!("Uso: audit.qrfs <dump|verify> <carpeta_auditoria>");
+        return Ok(());
+    }
+
+    let path = Path::new(&args[2]).join(AUDIT_FILE);
+    match args[1].as_str() {
+        "dump" => {
+            for entry in read_entries(&path)? {
+                println!(
+                    "{:>6}  t={}  uid={:<5} {:<8} inodo {:<4} {} {}",
+                    entry.seq, entry.time, entry.uid, entry.op, entry.inode, entry.name, entry.detail
+                );
+            }
+        }
+        "verify" => {
+            let count = verify_chain(&path)?;
+            println!("audit.qrfs: cadena integra ({} entradas).", count);
+        }
+        other => {
+            eprintln!("audit.qrfs: comando '{}' desconocido (dump o verify)", other);
+            process::exit(2);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,109 @@
+// blocks - cliente del servicio de bloques del servidor (ver proto/blocks.proto)
+// blocks.qrfs push <qr_folder|disco.img> <http://servidor:8080> [--config qrfs.toml]
+// blocks.qrfs pull <http://servidor:8080> <qr_folder> [--first N] [--count N] [--config qrfs.toml]
+// push sube los bloques que tiene el disco (lo que haria una app de celular
+// despues de escanear); pull baja al disco los que el servidor ya tiene
+use std::env;
+use std::path::Path;
+use std::process;
+
+use qrfs_core::blockrpc::{Block, BlockClient, MAX_DOWNLOAD};
+use qrfs_core::config::{config_arg, QrfsConfig};
+use qrfs_core::errors::QrfsError;
+use qrfs_core::storage::{BlockStorage, ContainerStorage};
+
+// bloques por pedido al subir
+const UPLOAD_BATCH: usize = 256;
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("blocks.qrfs: error: {e}");
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), QrfsError> {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 4 {
+        eprintln!("Uso: blocks.qrfs push <qr_folder|disco.img> <http://servidor:8080> [--config qrfs.toml]");
+        eprintln!("     blocks.qrfs pull <http://servidor:8080> <qr_folder> [--first N] [--count N] [--config qrfs.toml]");
+        return Ok(());
+    }
+
+    match args[1].as_str() {
+        "push" => push(&args[2], &args[3], &args),
+        "pull" => pull(&args[2], &args[3], &args),
+        other => Err(QrfsError::Other(format!("comando desconocido '{}' (push o pull)", other))),
+    }
+}
+
+fn push(disk: &str, server: &str, args: &[String]) -> Result<(), QrfsError> {
+    let config = QrfsConfig::for_disk(disk, config_arg(args))?;
+    let client = BlockClient::new(server);
+
+    // un contenedor no guarda generaciones: van en 0
+    let blocks: Vec<Block> = if Path::new(disk).is_file() {
+        let storage = ContainerStorage::open_disk(disk)?;
+        (0..storage.total_blocks())
+            .map(|id| Ok(Block { id, gen: 0, data: storage.read_block(id)? }))
+            .collect::<Result<_, QrfsError>>()?
+    } else {
+        let storage = config.qr_storage(disk);
+        (0..storage.total_blocks())
+            .filter_map(|id| storage.generation(id).map(|gen| (id, gen)))
+            .map(|(id, gen)| Ok(Block { id, gen, data: storage.read_verified(id)? }))
+            .collect::<Result<_, QrfsError>>()?
+    };
+
+    let (mut stored, mut rejected) = (0, 0);
+    for batch in blocks.chunks(UPLOAD_BATCH) {
+        for ack in client.upload(batch)? {
+            if ack.ok {
+                stored += 1;
+            } else {
+                rejected += 1;
+                eprintln!("blocks.qrfs: bloque {} rechazado: {}", ack.id, ack.error);
+            }
+        }
+    }
+    println!("blocks.qrfs: {} bloques subidos, {} rechazados", stored, rejected);
+    Ok(())
+}
+
+fn pull(server: &str, disk: &str, args: &[String]) -> Result<(), QrfsError> {
+    let config = QrfsConfig::for_disk(disk, config_arg(args))?;
+    let storage = config.qr_storage(disk);
+    let client = BlockClient::new(server);
+
+    let number = |flag: &str| -> Result<Option<u32>, QrfsError> {
+        match args.iter().position(|a| a == flag) {
+            None => Ok(None),
+            Some(i) => args
+                .get(i + 1)
+                .and_then(|n| n.parse().ok())
+                .map(Some)
+                .ok_or_else(|| QrfsError::Other(format!("{} espera un numero", flag))),
+        }
+    };
+    let first = number("--first")?.unwrap_or(0);
+    let count = number("--count")?.unwrap_or(storage.total_blocks().saturating_sub(first));
+    let end = first.saturating_add(count);
+
+    let mut pulled = 0;
+    let mut next = first;
+    while next < end {
+        let n = (end - next).min(MAX_DOWNLOAD);
+        for block in client.download(next, n)? {
+            if block.gen > 0 {
+                storage.write_block_with_gen(block.id, &block.data, block.gen)?;
+            } else {
+                storage.write_block(block.id, &block.data)?;
+            }
+            pulled += 1;
+        }
+        next += n;
+    }
+    println!("blocks.qrfs: {} bloques bajados a {}", pulled, disk);
+    Ok(())
+}
@@ -0,0 +1,435 @@
+// browse - explorador de un disco qrfs en la terminal, sin montar
+// muestra los archivos, sus bloques y si cada qr se puede leer; permite ver
+// el contenido y extraer archivos. sirve cuando no hay fuse (o no hay root)
+// con --list imprime lo mismo sin interfaz (para scripts o una terminal tonta)
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+
+use qrfs_core::api::{FileInfo, QrfsApi};
+use qrfs_core::errors::QrfsError;
+use qrfs_core::fs_format::read_inode_table;
+use qrfs_core::storage::{BlockStorage, ContainerStorage, QrStorageManager};
+use qrfs_core::BlockId;
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("browse.qrfs: error: {e}");
+        process::exit(1);
+    }
+}
+
+fn print_usage() {
+    eprintln!("Uso: browse.qrfs <qr_folder|disco.img> [--list]");
+    eprintln!("  teclas: j/k o flechas para moverse, enter para ver, x para extraer, q para salir");
+}
+
+fn run() -> Result<(), QrfsError> {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 2 {
+        print_usage();
+        return Ok(());
+    }
+
+    let disk = &args[1];
+    let list_only = args[2..].iter().any(|a| a == "--list");
+
+    if Path::new(disk).is_file() {
+        browse(ContainerStorage::open_disk(disk)?, disk, list_only)
+    } else {
+        let sb = QrStorageManager::probe_superblock(disk.as_str())?.ok_or_else(|| {
+            QrfsError::Other(format!("'{}' no tiene superblock, corra mkfs primero", disk))
+        })?;
+        let storage = QrStorageManager::new(disk, sb.block_size as usize, sb.total_blocks);
+        browse(storage, disk, list_only)
+    }
+}
+
+fn browse<B: BlockStorage + 'static>(storage: B, disk: &str, list_only: bool) -> Result<(), QrfsError> {
+    let storage = Arc::new(storage);
+    let mut state = Browser::open(storage, disk)?;
+
+    // sin terminal no hay interfaz: se cae al listado
+    if list_only || unsafe { libc::isatty(libc::STDIN_FILENO) } == 0 {
+        state.print_listing();
+        return Ok(());
+    }
+
+    let _raw = RawTerminal::enter()?;
+    state.main_loop()
+}
+
+// estado de cada bloque de un archivo
+#[derive(Clone, Copy, PartialEq)]
+enum BlockHealth {
+    Ok,
+    Unreadable,
+}
+
+struct Browser<B: BlockStorage + 'static> {
+    storage: Arc<B>,
+    api: QrfsApi<B>,
+    disk: String,
+    files: Vec<FileInfo>,
+    blocks: HashMap<u32, Vec<BlockId>>,
+    // se decodifica al seleccionar el archivo, no todo al abrir
+    health: HashMap<u32, Vec<BlockHealth>>,
+    selected: usize,
+    message: String,
+}
+
+impl<B: BlockStorage + 'static> Browser<B> {
+    fn open(storage: Arc<B>, disk: &str) -> Result<Self, QrfsError> {
+        let api = QrfsApi::open(storage.clone())?;
+        let sb = api.superblock();
+        let blocks = read_inode_table(storage.as_ref(), &sb)
+            .filter_map(|slot| slot.ok())
+            .map(|inode| (inode.id, inode.blocks))
+            .collect();
+
+        let mut files = api.list("/")?;
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(Self {
+            storage,
+            api,
+            disk: disk.to_string(),
+            files,
+            blocks,
+            health: HashMap::new(),
+            selected: 0,
+            message: String::new(),
+        })
+    }
+
+    fn file_blocks(&self, file: &FileInfo) -> &[BlockId] {
+        self.blocks
+            .get(&file.inode_id)
+            .map(|b| b.as_slice())
+            .unwrap_or(&[])
+    }
+
+    // lee (decodifica y verifica) cada bloque del archivo una sola vez
+    fn check_health(&mut self, index: usize) -> &[BlockHealth] {
+        let id = self.files[index].inode_id;
+        if !self.health.contains_key(&id) {
+            let result = self
+                .file_blocks(&self.files[index])
+                .iter()
+                .map(|&block| match self.storage.read_verified(block) {
+                    Ok(_) => BlockHealth::Ok,
+                    Err(_) => BlockHealth::Unreadable,
+                })
+                .collect();
+            self.health.insert(id, result);
+        }
+        &self.health[&id]
+    }
+
+    fn health_summary(&self, file: &FileInfo) -> String {
+        match self.health.get(&file.inode_id) {
+            None => "-".to_string(),
+            Some(health) => {
+                let bad = health.iter().filter(|&&h| h == BlockHealth::Unreadable).count();
+                if bad == 0 {
+                    "ok".to_string()
+                } else {
+                    format!("{} ilegibles", bad)
+                }
+            }
+        }
+    }
+
+    fn header(&self) -> String {
+        let usage = self.api.usage();
+        format!(
+            "qrfs browse: {} ({} archivos, {}/{} bloques libres)",
+            self.disk,
+            self.files.len(),
+            usage.free_blocks,
+            usage.data_blocks
+        )
+    }
+
+    fn file_line(&self, file: &FileInfo) -> String {
+        let name = if file.is_dir() {
+            format!("{}/", file.name)
+        } else {
+            file.name.clone()
+        };
+        format!(
+            "{:<32} {:>10} B {:>5} bloques  {}",
+            name,
+            file.size,
+            self.file_blocks(file).len(),
+            self.health_summary(file)
+        )
+    }
+
+    fn block_map(&self, index: usize) -> Vec<String> {
+        let file = &self.files[index];
+        let health = self.health.get(&file.inode_id);
+        self.file_blocks(file)
+            .iter()
+            .enumerate()
+            .map(|(i, block)| {
+                let mark = match health.map(|h| h[i]) {
+                    Some(BlockHealth::Ok) => "ok",
+                    Some(BlockHealth::Unreadable) => "MAL",
+                    None => "?",
+                };
+                format!("{}:{}", block, mark)
+            })
+            .collect()
+    }
+
+    // listado completo sin interfaz (decodifica todo)
+    fn print_listing(&mut self) {
+        println!("{}", self.header());
+        for index in 0..self.files.len() {
+            self.check_health(index);
+            println!("{}", self.file_line(&self.files[index]));
+            let map = self.block_map(index);
+            if !map.is_empty() {
+                println!("    bloques: {}", map.join(" "));
+            }
+        }
+    }
+
+    fn main_loop(&mut self) -> Result<(), QrfsError> {
+        loop {
+            if !self.files.is_empty() {
+                self.check_health(self.selected);
+            }
+            self.draw_list()?;
+
+            match read_key()? {
+                Key::Up if self.selected > 0 => self.selected -= 1,
+                Key::Down if self.selected + 1 < self.files.len() => self.selected += 1,
+                Key::Enter if !self.files.is_empty() => self.preview()?,
+                Key::Char('x') if !self.files.is_empty() => self.extract(),
+                Key::Char('q') | Key::Escape => break,
+                _ => {}
+            }
+        }
+        // dejar la pantalla limpia al salir
+        print!("\x1b[2J\x1b[H");
+        io::stdout().flush()?;
+        Ok(())
+    }
+
+    fn draw_list(&self) -> Result<(), QrfsError> {
+        let (cols, rows) = terminal_size();
+        let mut out = String::from("\x1b[2J\x1b[H");
+        let rule = "-".repeat(cols.min(100));
+
+        out.push_str(&format!("\x1b[1m{}\x1b[0m\r\n{}\r\n", clip(&self.header(), cols), rule));
+
+        // la lista ocupa lo que sobra despues del mapa de bloques y la ayuda
+        let list_rows = rows.saturating_sub(10).max(3);
+        let first = self.selected.saturating_sub(list_rows - 1);
+        if self.files.is_empty() {
+            out.push_str("  (disco vacio)\r\n");
+        }
+        for (index, file) in self.files.iter().enumerate().skip(first).take(list_rows) {
+            let line = clip(&self.file_line(file), cols.saturating_sub(2));
+            if index == self.selected {
+                out.push_str(&format!("\x1b[7m> {}\x1b[0m\r\n", line));
+            } else {
+                out.push_str(&format!("  {}\r\n", line));
+            }
+        }
+        out.push_str(&format!("{}\r\n", rule));
+
+        if let Some(file) = self.files.get(self.selected) {
+            out.push_str(&format!("bloques de {}:\r\n", file.name));
+            let mut line = String::new();
+            let mut lines = 0;
+            for entry in self.block_map(self.selected) {
+                if line.len() + entry.len() + 1 > cols && lines < 3 {
+                    out.push_str(&format!("  {}\r\n", line));
+                    line.clear();
+                    lines += 1;
+                }
+                if lines == 3 {
+                    line = "...".to_string();
+                    break;
+                }
+                line.push_str(&entry);
+                line.push(' ');
+            }
+            out.push_str(&format!("  {}\r\n{}\r\n", line, rule));
+        }
+
+        out.push_str(&format!("{}\r\n", clip(&self.message, cols)));
+        out.push_str("[j/k] mover  [enter] ver  [x] extraer  [q] salir");
+
+        print!("{}", out);
+        io::stdout().flush()?;
+        Ok(())
+    }
+
+    // muestra el contenido del archivo seleccionado (texto o hexa), con scroll
+    fn preview(&mut self) -> Result<(), QrfsError> {
+        let file = self.files[self.selected].clone();
+        let data = match self.api.read(&format!("/{}", file.name)) {
+            Ok(data) => data,
+            Err(e) => {
+                self.message = format!("no se pudo leer '{}': {}", file.name, e);
+                return Ok(());
+            }
+        };
+        let lines = preview_lines(&data);
+        let mut top = 0;
+
+        loop {
+            let (cols, rows) = terminal_size();
+            let page = rows.saturating_sub(2).max(1);
+            let mut out = format!(
+                "\x1b[2J\x1b[H\x1b[1m{} ({} bytes)\x1b[0m\r\n",
+                clip(&file.name, cols),
+                data.len()
+            );
+            for line in lines.iter().skip(top).take(page) {
+                out.push_str(&clip(line, cols));
+                out.push_str("\r\n");
+            }
+            out.push_str("[j/k] scroll  [q] volver");
+            print!("{}", out);
+            io::stdout().flush()?;
+
+            match read_key()? {
+                Key::Up => top = top.saturating_sub(1),
+                Key::Down if top + page < lines.len() => top += 1,
+                Key::Char(' ') => top = (top + page).min(lines.len().saturating_sub(1)),
+                Key::Char('q') | Key::Escape | Key::Enter => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+
+    // copia el archivo seleccionado a la carpeta actual
+    fn extract(&mut self) {
+        let file = &self.files[self.selected];
+        self.message = match self.api.read(&format!("/{}", file.name)) {
+            Ok(data) => match fs::write(&file.name, &data) {
+                Ok(()) => format!("'{}' extraido ({} bytes)", file.name, data.len()),
+                Err(e) => format!("no se pudo escribir '{}': {}", file.name, e),
+            },
+            Err(e) => format!("no se pudo leer '{}': {}", file.name, e),
+        };
+    }
+}
+
+// texto si es utf-8 sin caracteres de control raros, sino volcado hexa
+fn preview_lines(data: &[u8]) -> Vec<String> {
+    if let Ok(text) = std::str::from_utf8(data) {
+        if !text.chars().any(|c| c.is_control() && c != '\n' && c != '\t' && c != '\r') {
+            return text.lines().map(|l| l.replace('\t', "    ")).collect();
+        }
+    }
+    data.chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+                .collect();
+            format!("{:08x}  {:<48} {}", i * 16, hex.join(" "), ascii)
+        })
+        .collect()
+}
+
+fn clip(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+enum Key {
+    Up,
+    Down,
+    Enter,
+    Escape,
+    Char(char),
+}
+
+fn read_key() -> Result<Key, QrfsError> {
+    let mut stdin = io::stdin();
+    let mut byte = [0u8; 1];
+    stdin.read_exact(&mut byte)?;
+    Ok(match byte[0] {
+        b'\r' | b'\n' => Key::Enter,
+        b'k' => Key::Up,
+        b'j' => Key::Down,
+        0x1b => {
+            // flechas: ESC [ A / ESC [ B; un ESC solo (nada mas llega enseguida) es salir
+            let mut pending = libc::pollfd {
+                fd: libc::STDIN_FILENO,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            if unsafe { libc::poll(&mut pending, 1, 50) } <= 0 {
+                return Ok(Key::Escape);
+            }
+            let mut seq = [0u8; 2];
+            stdin.read_exact(&mut seq)?;
+            match &seq {
+                b"[A" => Key::Up,
+                b"[B" => Key::Down,
+                _ => Key::Escape,
+            }
+        }
+        other => Key::Char(other as char),
+    })
+}
+
+fn terminal_size() -> (usize, usize) {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    if ok && size.ws_col > 0 && size.ws_row > 0 {
+        (size.ws_col as usize, size.ws_row as usize)
+    } else {
+        (80, 24)
+    }
+}
+
+// terminal en modo crudo (sin eco ni buffer de lineas) mientras vive; al
+// soltarla vuelve como estaba, aunque se salga por un error
+struct RawTerminal {
+    original: libc::termios,
+}
+
+impl RawTerminal {
+    fn enter() -> Result<Self, QrfsError> {
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let mut raw = original;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        // ocultar el cursor
+        print!("\x1b[?25l");
+        Ok(Self { original })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        print!("\x1b[?25h");
+        let _ = io::stdout().flush();
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
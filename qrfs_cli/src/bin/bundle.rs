@@ -0,0 +1,134 @@
+// bundle - un disco qrfs entero en un solo archivo .qrb (y de vuelta)
+// pack:   qrfs (carpeta de qr, contenedor o http://) -> disco.qrb
+// unpack: disco.qrb (o http://host/disco.qrb) -> carpeta de qr o disco.img
+// el .qrb lleva los datos de los bloques, el manifiesto de archivos y,
+// con --parity n, un bloque de paridad cada n que arregla uno dañado por
+// grupo (ver qrfs_core::bundle). unpack redibuja los qr y compara cada
+// archivo con el sha-256 del manifiesto
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::bundle::{create_bundle, open_bundle, Bundle};
+use qrfs_core::config::{config_arg, QrfsConfig};
+use qrfs_core::errors::QrfsError;
+use qrfs_core::fs_format::read_superblock;
+use qrfs_core::http::{fetch_url, is_remote, HttpStorage};
+use qrfs_core::sha256::sha256_hex;
+use qrfs_core::storage::{BlockStorage, ContainerStorage, QrStorageManager};
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("bundle.qrfs: error: {e}");
+        process::exit(1);
+    }
+}
+
+fn print_usage() {
+    eprintln!("Uso: bundle.qrfs pack <qr_folder|disco.img|http://...> <salida.qrb> [--parity N] [--config f]");
+    eprintln!("     bundle.qrfs unpack <entrada.qrb|http://.../disco.qrb> <qr_folder|disco.img> [--config f]");
+}
+
+fn run() -> Result<(), QrfsError> {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 4 {
+        print_usage();
+        return Ok(());
+    }
+
+    let source = &args[2];
+    let dest = &args[3];
+
+    match args[1].as_str() {
+        "pack" => {
+            let mut parity = 0;
+            if let Some(i) = args.iter().position(|a| a == "--parity") {
+                parity = args
+                    .get(i + 1)
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(|| QrfsError::Other("--parity espera la cantidad de bloques por grupo".into()))?;
+            }
+            let bytes = if is_remote(source) {
+                create_bundle(Arc::new(HttpStorage::open_remote(source)?), parity)?
+            } else if Path::new(source).is_file() {
+                create_bundle(Arc::new(ContainerStorage::open_disk(source)?), parity)?
+            } else {
+                let config = QrfsConfig::for_disk(source, config_arg(&args))?;
+                create_bundle(Arc::new(config.qr_storage(source)), parity)?
+            };
+            fs::write(dest, &bytes)?;
+            let bundle = open_bundle(&bytes)?;
+            println!(
+                "bundle.qrfs: {} bloques, {} archivos{} en '{}' ({} bytes)",
+                bundle.blocks.len(),
+                bundle.manifest.files.len(),
+                if parity > 0 { format!(", paridad cada {}", parity) } else { String::new() },
+                dest,
+                bytes.len()
+            );
+            Ok(())
+        }
+        "unpack" => {
+            let bytes = if is_remote(source) {
+                fetch_url(source)?.ok_or_else(|| QrfsError::NotFound(source.to_string()))?
+            } else {
+                fs::read(source)?
+            };
+            let bundle = open_bundle(&bytes)?;
+            if !bundle.repaired.is_empty() {
+                println!("bundle.qrfs: bloques rearmados con la paridad: {:?}", bundle.repaired);
+            }
+            let sb = read_superblock(&bundle.to_storage()?)?;
+            let (block_size, total_blocks) = (bundle.manifest.block_size, bundle.manifest.total_blocks);
+            if dest.ends_with(".img") {
+                unpack(&bundle, ContainerStorage::create(dest, block_size, total_blocks)?)
+            } else {
+                let config = QrfsConfig::for_disk(dest, config_arg(&args))?;
+                let storage = config.configure(
+                    QrStorageManager::new(dest, block_size, total_blocks)
+                        .with_shard_size(sb.shard_size)
+                        .with_codec(sb.codec),
+                );
+                // vaciar lo que hubiera en la carpeta antes de copiar
+                storage.init_empty_blocks()?;
+                unpack(&bundle, storage)
+            }
+        }
+        _ => {
+            print_usage();
+            Ok(())
+        }
+    }
+}
+
+fn unpack<B: BlockStorage + 'static>(bundle: &Bundle, storage: B) -> Result<(), QrfsError> {
+    bundle.restore(&storage)?;
+    let api = QrfsApi::open(Arc::new(storage))?;
+    api.flush()?;
+
+    let mut failed = 0;
+    for file in &bundle.manifest.files {
+        let Some(expected) = &file.sha256 else {
+            continue;
+        };
+        let actual = api.read_inode(file.inode_id).map(|data| sha256_hex(&data));
+        if actual.as_ref().ok() != Some(expected) {
+            failed += 1;
+            println!("FALLA  {}: no coincide con el manifiesto", file.name);
+        }
+    }
+    if failed > 0 {
+        return Err(QrfsError::Other(format!("{} archivos no coinciden con el manifiesto", failed)));
+    }
+    println!(
+        "bundle.qrfs: listo ({} bloques, {} archivos verificados).",
+        bundle.blocks.len(),
+        bundle.manifest.files.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,498 @@
+// capture - escanea qr con la camara de la compu directo al disco
+// capture.qrfs <qr_folder> [--device /dev/video0] [--size 1280x720] [--frames carpeta] [--once] [--config qrfs.toml]
+// capture.qrfs <qr_folder> --screen [--region ANCHOxALTO+X+Y] [--screen-cmd "programa args"] [--idle segundos]
+// cada cuadro se busca entero (puede haber varias hojas a la vista) y los
+// bloques nuevos se guardan como los del servidor. sin --frames lee la
+// webcam (v4l2); con --frames toma los cuadros que otro programa deja en
+// una carpeta, y con --once termina cuando se acaban en vez de esperar mas.
+// con --screen mira la pantalla mientras otra compu pasa la secuencia de qr
+// y termina cuando el disco esta completo (o tras --idle segundos sin
+// bloques nuevos, 30 por defecto)
+use std::env;
+use std::process;
+use std::time::{Duration, Instant};
+
+use qrfs_core::camera::{ingest_frame, FolderFrames, FrameSource, Region, ScreenFrames};
+use qrfs_core::config::{config_arg, QrfsConfig};
+use qrfs_core::errors::QrfsError;
+use qrfs_core::missing::scan_next;
+use qrfs_core::storage::{BlockStorage, ScanDecision};
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("capture.qrfs: error: {e}");
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), QrfsError> {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 2 {
+        eprintln!("Uso: capture.qrfs <qr_folder> [--device /dev/video0] [--size 1280x720] [--frames <carpeta>] [--once] [--config qrfs.toml]");
+        eprintln!("     capture.qrfs <qr_folder> --screen [--region ANCHOxALTO+X+Y] [--screen-cmd \"programa args\"] [--idle segundos]");
+        return Ok(());
+    }
+
+    let disk = &args[1];
+    let config = QrfsConfig::for_disk(disk, config_arg(&args))?;
+    let mut device = "/dev/video0".to_string();
+    let (mut width, mut height) = (1280, 720);
+    let mut frames: Option<String> = None;
+    let mut once = false;
+    let mut screen = false;
+    let mut region: Option<Region> = None;
+    let mut screen_cmd: Option<String> = None;
+    let mut idle: Option<u64> = None;
+    let mut i = 2;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--device" if i + 1 < args.len() => {
+                device = args[i + 1].clone();
+                i += 1;
+            }
+            "--size" if i + 1 < args.len() => {
+                (width, height) = args[i + 1]
+                    .split_once('x')
+                    .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                    .ok_or_else(|| QrfsError::Other(format!("--size espera ANCHOxALTO, no '{}'", args[i + 1])))?;
+                i += 1;
+            }
+            "--frames" if i + 1 < args.len() => {
+                frames = Some(args[i + 1].clone());
+                i += 1;
+            }
+            "--once" => once = true,
+            "--screen" => screen = true,
+            "--region" if i + 1 < args.len() => {
+                region = Some(Region::parse(&args[i + 1])?);
+                i += 1;
+            }
+            "--screen-cmd" if i + 1 < args.len() => {
+                screen_cmd = Some(args[i + 1].clone());
+                i += 1;
+            }
+            "--idle" if i + 1 < args.len() => {
+                idle = Some(
+                    args[i + 1]
+                        .parse()
+                        .map_err(|_| QrfsError::Other("--idle espera segundos".into()))?,
+                );
+                i += 1;
+            }
+            "--config" => i += 1,
+            other => return Err(QrfsError::Other(format!("argumento desconocido '{}'", other))),
+        }
+        i += 1;
+    }
+
+    let mut source: Box<dyn FrameSource> = match (&frames, screen) {
+        (Some(_), true) => return Err(QrfsError::Other("--frames y --screen no van juntos".into())),
+        (Some(dir), false) => {
+            println!("capture.qrfs: leyendo cuadros de '{}'", dir);
+            Box::new(FolderFrames::new(dir, !once))
+        }
+        (None, true) => {
+            println!("capture.qrfs: mirando la pantalla, se termina con el disco completo");
+            match &screen_cmd {
+                Some(command) => Box::new(ScreenFrames::with_command(command)?),
+                None => Box::new(ScreenFrames::new(region)?),
+            }
+        }
+        (None, false) => {
+            println!("capture.qrfs: camara {} ({}x{}), Ctrl+C para terminar", device, width, height);
+            open_camera(&device, width, height)?
+        }
+    };
+    let until = Until {
+        complete: screen,
+        idle: idle.or(screen.then_some(30)).map(Duration::from_secs),
+    };
+    capture(&config, disk, source.as_mut(), until)
+}
+
+// cuando cortar ademas de que se acabe la fuente
+struct Until {
+    // todos los bloques en uso del disco escaneados
+    complete: bool,
+    // tanto tiempo sin bloques nuevos
+    idle: Option<Duration>,
+}
+
+fn capture(config: &QrfsConfig, disk: &str, source: &mut dyn FrameSource, until: Until) -> Result<(), QrfsError> {
+    // sin superblock todavia (el bloque 0 se escanea como cualquier otro) la
+    // geometria sale del perfil
+    let mut storage = config.qr_storage(disk);
+    let mut saved = 0;
+    let mut last_new = Instant::now();
+    while let Some(frame) = source.next_frame()? {
+        let mut superblock = false;
+        for qr in ingest_frame(&storage, &frame) {
+            match (qr.block, qr.result) {
+                (Some(block), Ok(ScanDecision::Saved(gen))) => {
+                    saved += 1;
+                    last_new = Instant::now();
+                    superblock |= block == 0;
+                    let have = (0..storage.total_blocks()).filter(|&id| storage.has_block(id)).count();
+                    println!(
+                        "capture.qrfs: bloque {} guardado (gen {}), {} de {} con imagen",
+                        block,
+                        gen,
+                        have,
+                        storage.total_blocks()
+                    );
+                }
+                (Some(_), Ok(ScanDecision::Duplicate)) => {}
+                (Some(block), Ok(ScanDecision::Stale { scanned, stored })) => {
+                    println!(
+                        "capture.qrfs: bloque {} obsoleto (gen {} < {} guardada), se omite",
+                        block, scanned, stored
+                    );
+                }
+                (Some(block), Ok(ScanDecision::Conflict(gen))) => {
+                    println!(
+                        "capture.qrfs: bloque {} con gen {} ya guardada con otro contenido, se omite",
+                        block, gen
+                    );
+                }
+                (_, Err(e)) => eprintln!("capture.qrfs: qr descartado: {}", e),
+                (None, Ok(_)) => {}
+            }
+        }
+        // con el superblock ya se sabe la geometria real del disco
+        if superblock {
+            storage = config.qr_storage(disk);
+        }
+        if until.complete && storage.has_block(0) && scan_next(&storage, 1).is_empty() {
+            println!("capture.qrfs: disco completo");
+            break;
+        }
+        if until.idle.is_some_and(|idle| last_new.elapsed() >= idle) {
+            println!("capture.qrfs: sin bloques nuevos hace {}s, se corta", last_new.elapsed().as_secs());
+            break;
+        }
+    }
+    println!("capture.qrfs: {} bloques guardados", saved);
+    Ok(())
+}
+
+// la webcam: /dev/videoN en linux
+#[cfg(target_os = "linux")]
+fn open_camera(device: &str, width: u32, height: u32) -> Result<Box<dyn FrameSource>, QrfsError> {
+    Ok(Box::new(v4l2::Camera::open(device, width, height)?))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_camera(device: &str, _width: u32, _height: u32) -> Result<Box<dyn FrameSource>, QrfsError> {
+    Err(QrfsError::Unimplemented(format!(
+        "{}: la camara solo se abre por v4l2 (linux); en otros sistemas use --frames con cuadros de ffmpeg",
+        device
+    )))
+}
+
+// captura por v4l2 con buffers mapeados (lo que soportan las webcams usb);
+// las estructuras y los ioctl son los de linux/videodev2.h
+#[cfg(target_os = "linux")]
+mod v4l2 {
+    use std::fs::{File, OpenOptions};
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+
+    use image::{DynamicImage, GrayImage};
+
+    use qrfs_core::camera::FrameSource;
+    use qrfs_core::errors::QrfsError;
+
+    const fn fourcc(code: &[u8; 4]) -> u32 {
+        code[0] as u32 | (code[1] as u32) << 8 | (code[2] as u32) << 16 | (code[3] as u32) << 24
+    }
+
+    const PIX_YUYV: u32 = fourcc(b"YUYV");
+    const PIX_GREY: u32 = fourcc(b"GREY");
+    const PIX_MJPEG: u32 = fourcc(b"MJPG");
+
+    const BUF_TYPE_VIDEO_CAPTURE: u32 = 1;
+    const MEMORY_MMAP: u32 = 1;
+    const CAP_VIDEO_CAPTURE: u32 = 0x0000_0001;
+    const CAP_STREAMING: u32 = 0x0400_0000;
+    const CAP_DEVICE_CAPS: u32 = 0x8000_0000;
+    const BUFFERS: u32 = 4;
+
+    #[repr(C)]
+    struct Capability {
+        driver: [u8; 16],
+        card: [u8; 32],
+        bus_info: [u8; 32],
+        version: u32,
+        capabilities: u32,
+        device_caps: u32,
+        reserved: [u32; 3],
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct PixFormat {
+        width: u32,
+        height: u32,
+        pixelformat: u32,
+        field: u32,
+        bytesperline: u32,
+        sizeimage: u32,
+        colorspace: u32,
+        priv_: u32,
+        flags: u32,
+        ycbcr_enc: u32,
+        quantization: u32,
+        xfer_func: u32,
+    }
+
+    #[repr(C)]
+    union FormatUnion {
+        pix: PixFormat,
+        // el union del kernel mide 200 bytes y tiene punteros (alineado a 8)
+        raw: [u64; 25],
+    }
+
+    #[repr(C)]
+    struct Format {
+        type_: u32,
+        fmt: FormatUnion,
+    }
+
+    #[repr(C)]
+    struct RequestBuffers {
+        count: u32,
+        type_: u32,
+        memory: u32,
+        capabilities: u32,
+        flags: u8,
+        reserved: [u8; 3],
+    }
+
+    #[repr(C)]
+    struct Timecode {
+        type_: u32,
+        flags: u32,
+        frames: u8,
+        seconds: u8,
+        minutes: u8,
+        hours: u8,
+        userbits: [u8; 4],
+    }
+
+    #[repr(C)]
+    union BufferLocation {
+        offset: u32,
+        userptr: libc::c_ulong,
+    }
+
+    #[repr(C)]
+    struct Buffer {
+        index: u32,
+        type_: u32,
+        bytesused: u32,
+        flags: u32,
+        field: u32,
+        timestamp: libc::timeval,
+        timecode: Timecode,
+        sequence: u32,
+        memory: u32,
+        m: BufferLocation,
+        length: u32,
+        reserved2: u32,
+        request_fd: u32,
+    }
+
+    // _IOR/_IOW/_IOWR('V', nr, T)
+    const fn ioc(dir: u32, nr: u32, size: usize) -> libc::c_ulong {
+        ((dir << 30) | ((size as u32) << 16) | ((b'V' as u32) << 8) | nr) as libc::c_ulong
+    }
+    const READ: u32 = 2;
+    const WRITE: u32 = 1;
+    const QUERYCAP: libc::c_ulong = ioc(READ, 0, std::mem::size_of::<Capability>());
+    const S_FMT: libc::c_ulong = ioc(READ | WRITE, 5, std::mem::size_of::<Format>());
+    const REQBUFS: libc::c_ulong = ioc(READ | WRITE, 8, std::mem::size_of::<RequestBuffers>());
+    const QUERYBUF: libc::c_ulong = ioc(READ | WRITE, 9, std::mem::size_of::<Buffer>());
+    const QBUF: libc::c_ulong = ioc(READ | WRITE, 15, std::mem::size_of::<Buffer>());
+    const DQBUF: libc::c_ulong = ioc(READ | WRITE, 17, std::mem::size_of::<Buffer>());
+    const STREAMON: libc::c_ulong = ioc(WRITE, 18, std::mem::size_of::<libc::c_int>());
+    const STREAMOFF: libc::c_ulong = ioc(WRITE, 19, std::mem::size_of::<libc::c_int>());
+
+    fn ioctl<T>(file: &File, request: libc::c_ulong, arg: &mut T) -> io::Result<()> {
+        loop {
+            // SAFETY: arg es la estructura que el kernel espera para request
+            let ret = unsafe { libc::ioctl(file.as_raw_fd(), request as _, arg as *mut T) };
+            if ret != -1 {
+                return Ok(());
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+
+    fn zeroed<T>() -> T {
+        // SAFETY: solo se usa con las estructuras de arriba, validas en cero
+        unsafe { std::mem::zeroed() }
+    }
+
+    fn new_buffer(index: u32) -> Buffer {
+        let mut buf: Buffer = zeroed();
+        buf.index = index;
+        buf.type_ = BUF_TYPE_VIDEO_CAPTURE;
+        buf.memory = MEMORY_MMAP;
+        buf
+    }
+
+    pub struct Camera {
+        file: File,
+        device: String,
+        format: PixFormat,
+        // (puntero, largo) de cada buffer mapeado
+        buffers: Vec<(*mut libc::c_void, usize)>,
+    }
+
+    // los buffers solo los toca el hilo que tiene la camara
+    unsafe impl Send for Camera {}
+
+    impl Camera {
+        pub fn open(device: &str, width: u32, height: u32) -> Result<Self, QrfsError> {
+            let err = |what: &str, e: io::Error| QrfsError::Other(format!("{}: {}: {}", device, what, e));
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(device)
+                .map_err(|e| err("no se pudo abrir", e))?;
+
+            let mut cap: Capability = zeroed();
+            ioctl(&file, QUERYCAP, &mut cap).map_err(|e| err("no es un dispositivo v4l2", e))?;
+            let caps = if cap.capabilities & CAP_DEVICE_CAPS != 0 {
+                cap.device_caps
+            } else {
+                cap.capabilities
+            };
+            if caps & CAP_VIDEO_CAPTURE == 0 || caps & CAP_STREAMING == 0 {
+                return Err(QrfsError::Unimplemented(format!("{}: no captura video por streaming", device)));
+            }
+
+            // yuyv si se puede; el driver contesta con lo que eligio
+            let mut format = None;
+            for pixelformat in [PIX_YUYV, PIX_MJPEG, PIX_GREY] {
+                let mut fmt: Format = zeroed();
+                fmt.type_ = BUF_TYPE_VIDEO_CAPTURE;
+                let mut pix: PixFormat = zeroed();
+                pix.width = width;
+                pix.height = height;
+                pix.pixelformat = pixelformat;
+                fmt.fmt.pix = pix;
+                ioctl(&file, S_FMT, &mut fmt).map_err(|e| err("formato rechazado", e))?;
+                // SAFETY: con type_ = VIDEO_CAPTURE el kernel llena pix
+                let pix = unsafe { fmt.fmt.pix };
+                if [PIX_YUYV, PIX_MJPEG, PIX_GREY].contains(&pix.pixelformat) {
+                    format = Some(pix);
+                    break;
+                }
+            }
+            let format = format.ok_or_else(|| {
+                QrfsError::Unimplemented(format!("{}: la camara no da yuyv, mjpeg ni gris", device))
+            })?;
+
+            let mut req = RequestBuffers {
+                count: BUFFERS,
+                type_: BUF_TYPE_VIDEO_CAPTURE,
+                memory: MEMORY_MMAP,
+                capabilities: 0,
+                flags: 0,
+                reserved: [0; 3],
+            };
+            ioctl(&file, REQBUFS, &mut req).map_err(|e| err("sin buffers", e))?;
+
+            let mut camera = Self {
+                file,
+                device: device.to_string(),
+                format,
+                buffers: Vec::new(),
+            };
+            for index in 0..req.count {
+                let mut buf = new_buffer(index);
+                ioctl(&camera.file, QUERYBUF, &mut buf).map_err(|e| err("buffer", e))?;
+                // SAFETY: offset y length son los que dio QUERYBUF para este fd
+                let ptr = unsafe {
+                    libc::mmap(
+                        std::ptr::null_mut(),
+                        buf.length as usize,
+                        libc::PROT_READ,
+                        libc::MAP_SHARED,
+                        camera.file.as_raw_fd(),
+                        buf.m.offset as libc::off_t,
+                    )
+                };
+                if ptr == libc::MAP_FAILED {
+                    return Err(err("mmap", io::Error::last_os_error()));
+                }
+                camera.buffers.push((ptr, buf.length as usize));
+                ioctl(&camera.file, QBUF, &mut buf).map_err(|e| err("buffer", e))?;
+            }
+            let mut kind = BUF_TYPE_VIDEO_CAPTURE as libc::c_int;
+            ioctl(&camera.file, STREAMON, &mut kind).map_err(|e| err("no arranca la captura", e))?;
+            Ok(camera)
+        }
+
+        // el cuadro en gris (a los qr les alcanza con el brillo)
+        fn to_image(&self, data: &[u8]) -> Result<DynamicImage, QrfsError> {
+            let (width, height) = (self.format.width, self.format.height);
+            let stride = self.format.bytesperline as usize;
+            let luma = match self.format.pixelformat {
+                PIX_MJPEG => {
+                    return image::load_from_memory(data)
+                        .map_err(|e| QrfsError::Other(format!("{}: cuadro mjpeg: {}", self.device, e)))
+                }
+                // y0 u y1 v: el brillo es un byte si y otro no
+                PIX_YUYV => GrayImage::from_fn(width, height, |x, y| {
+                    image::Luma([data.get(y as usize * stride + x as usize * 2).copied().unwrap_or(0)])
+                }),
+                _ => GrayImage::from_fn(width, height, |x, y| {
+                    image::Luma([data.get(y as usize * stride + x as usize).copied().unwrap_or(0)])
+                }),
+            };
+            Ok(DynamicImage::ImageLuma8(luma))
+        }
+    }
+
+    impl FrameSource for Camera {
+        fn next_frame(&mut self) -> Result<Option<DynamicImage>, QrfsError> {
+            let mut buf = new_buffer(0);
+            ioctl(&self.file, DQBUF, &mut buf)?;
+            let (ptr, len) = self.buffers[buf.index as usize];
+            let used = (buf.bytesused as usize).min(len);
+            // SAFETY: el buffer esta mapeado y el kernel no lo toca hasta el QBUF
+            let frame = unsafe { std::slice::from_raw_parts(ptr as *const u8, used) }.to_vec();
+            ioctl(&self.file, QBUF, &mut buf)?;
+            self.to_image(&frame).map(Some)
+        }
+    }
+
+    impl Drop for Camera {
+        fn drop(&mut self) {
+            let mut kind = BUF_TYPE_VIDEO_CAPTURE as libc::c_int;
+            let _ = ioctl(&self.file, STREAMOFF, &mut kind);
+            for &(ptr, len) in &self.buffers {
+                // SAFETY: mapeados en open con este largo
+                unsafe { libc::munmap(ptr, len) };
+            }
+        }
+    }
+
+    // los mismos tamanos y numeros que videodev2.h en 64 bits
+    #[cfg(target_pointer_width = "64")]
+    const _: () = {
+        assert!(std::mem::size_of::<Format>() == 208);
+        assert!(std::mem::size_of::<Buffer>() == 88);
+        assert!(QUERYCAP == 0x8068_5600);
+        assert!(S_FMT == 0xc0d0_5605);
+        assert!(REQBUFS == 0xc014_5608);
+        assert!(DQBUF == 0xc058_5611);
+        assert!(STREAMON == 0x4004_5612);
+    };
+}
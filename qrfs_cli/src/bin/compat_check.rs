@@ -0,0 +1,56 @@
+// compat_check - el codigo de hoy todavia lee los discos de cada formato viejo
+// compat_check.qrfs [carpeta] [--write]
+// abre cada disco de referencia (fixtures/compat por defecto) y compara lo
+// que lee con lo que se grabo. --write arma los que falten; los que ya estan
+// no se tocan nunca
+
+use std::env;
+use std::path::Path;
+use std::process;
+
+use qrfs_core::compat::{build_fixture, check_fixture, FIXTURES, FIXTURES_DIR};
+use qrfs_core::errors::QrfsError;
+
+fn main() {
+    match run() {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("compat_check.qrfs: error: {e}");
+            process::exit(1);
+        }
+    }
+}
+
+// true si todos los discos se leen
+fn run() -> Result<bool, QrfsError> {
+    let args: Vec<String> = env::args().collect();
+    let write = args.iter().any(|a| a == "--write");
+    let root = args[1..].iter().find(|a| !a.starts_with("--")).map_or(FIXTURES_DIR, String::as_str);
+
+    let mut failed = 0;
+    for fixture in FIXTURES {
+        let dir = Path::new(root).join(fixture.name);
+        if write && !dir.exists() {
+            build_fixture(&dir, fixture)?;
+            println!("nuevo {} ({})", fixture.name, fixture.describe());
+            continue;
+        }
+        let problems = check_fixture(&dir, fixture)?;
+        if problems.is_empty() {
+            println!("ok    {} ({})", fixture.name, fixture.describe());
+        } else {
+            failed += 1;
+            println!("FALLA {} ({})", fixture.name, fixture.describe());
+            for problem in problems {
+                println!("        {}", problem);
+            }
+        }
+    }
+    println!(
+        "compat_check.qrfs: {} de {} discos de referencia se leen",
+        FIXTURES.len() - failed,
+        FIXTURES.len()
+    );
+    Ok(failed == 0)
+}
@@ -0,0 +1,253 @@
+// contact_sheet - hoja de contactos de una carpeta de qr
+// dibuja cada bloque como miniatura con su id, a quien pertenece y su estado
+// (ok / falta / corrupto), para comparar un archivo impreso con el disco.
+// la salida es un png (una sola hoja larga) o un pdf (una pagina cada --rows filas)
+
+use std::collections::HashMap;
+use std::env;
+use std::process;
+use std::sync::Arc;
+
+use image::imageops::{self, FilterType};
+use image::{Rgb, RgbImage};
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::errors::QrfsError;
+use qrfs_core::fs_format::{inode_ext_chain, read_inode_table};
+use qrfs_core::sheet::{draw_rect, draw_text, fill_rect, write_pdf, GLYPH_ADVANCE};
+use qrfs_core::storage::{BlockStorage, QrStorageManager};
+use qrfs_core::BlockId;
+
+// medidas de cada celda en pixeles
+const THUMB: u32 = 120;
+const PAD: u32 = 6;
+const LINE: u32 = 10;
+const CELL_W: u32 = THUMB + 2 * PAD;
+const CELL_H: u32 = THUMB + 3 * LINE + 3 * PAD;
+const MARGIN: u32 = 20;
+const TITLE_H: u32 = 30;
+
+const WHITE: Rgb<u8> = Rgb([255, 255, 255]);
+const BLACK: Rgb<u8> = Rgb([0, 0, 0]);
+const GRAY: Rgb<u8> = Rgb([200, 200, 200]);
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("contact_sheet.qrfs: error: {e}");
+        process::exit(1);
+    }
+}
+
+fn print_usage() {
+    eprintln!("Uso: contact_sheet.qrfs <qr_folder> <salida.png|salida.pdf> [--cols N] [--rows N]");
+    eprintln!("  --rows es la cantidad de filas por pagina del pdf (el png es una sola hoja)");
+}
+
+fn run() -> Result<(), QrfsError> {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 3 {
+        print_usage();
+        return Ok(());
+    }
+
+    let qrfolder = &args[1];
+    let output = &args[2];
+
+    let mut cols = 6;
+    let mut rows = 7;
+    let mut i = 3;
+    while i < args.len() {
+        if args[i] == "--cols" && i + 1 < args.len() {
+            if let Ok(n) = args[i + 1].parse::<u32>() {
+                cols = n.max(1);
+            }
+            i += 1;
+        } else if args[i] == "--rows" && i + 1 < args.len() {
+            if let Ok(n) = args[i + 1].parse::<u32>() {
+                rows = n.max(1);
+            }
+            i += 1;
+        }
+        i += 1;
+    }
+
+    let sb = QrStorageManager::probe_superblock(qrfolder.as_str())?.ok_or_else(|| {
+        QrfsError::Other(format!(
+            "'{}' no es una carpeta de qr con superblock (los contenedores no tienen imagenes)",
+            qrfolder
+        ))
+    })?;
+    let storage = Arc::new(QrStorageManager::new(qrfolder, sb.block_size as usize, sb.total_blocks));
+
+    let cells = collect_cells(&storage)?;
+    println!(
+        "contact_sheet.qrfs: {} bloques ({} ok, {} faltan, {} corruptos)",
+        cells.len(),
+        cells.iter().filter(|c| c.status == Status::Ok).count(),
+        cells.iter().filter(|c| c.status == Status::Missing).count(),
+        cells.iter().filter(|c| c.status == Status::Corrupt).count()
+    );
+
+    let title = format!("qrfs {}", qrfolder);
+    if output.to_lowercase().ends_with(".pdf") {
+        let per_page = (cols * rows) as usize;
+        let chunks: Vec<&[Cell]> = cells.chunks(per_page.max(1)).collect();
+        let pages: Vec<RgbImage> = chunks
+            .iter()
+            .enumerate()
+            .map(|(n, chunk)| {
+                let title = format!("{} - pagina {}/{}", title, n + 1, chunks.len());
+                render_sheet(&storage, chunk, cols, &title)
+            })
+            .collect();
+        write_pdf(output, &pages)?;
+        println!("contact_sheet.qrfs: '{}' ({} paginas)", output, pages.len());
+    } else {
+        let sheet = render_sheet(&storage, &cells, cols, &title);
+        sheet
+            .save(output)
+            .map_err(|e| QrfsError::Other(format!("no se pudo guardar '{}': {}", output, e)))?;
+        println!("contact_sheet.qrfs: '{}' ({}x{})", output, sheet.width(), sheet.height());
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Status {
+    Ok,
+    Missing,
+    Corrupt,
+}
+
+impl Status {
+    fn label(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Missing => "falta",
+            Status::Corrupt => "corrupto",
+        }
+    }
+
+    fn color(self) -> Rgb<u8> {
+        match self {
+            Status::Ok => Rgb([0, 140, 0]),
+            Status::Missing => Rgb([200, 0, 0]),
+            Status::Corrupt => Rgb([220, 120, 0]),
+        }
+    }
+}
+
+struct Cell {
+    id: BlockId,
+    owner: String,
+    status: Status,
+}
+
+// un bloque entra en la hoja si tiene png o si es de un archivo (y entonces falta)
+fn collect_cells(storage: &Arc<QrStorageManager>) -> Result<Vec<Cell>, QrfsError> {
+    let api = QrfsApi::open(storage.clone())?;
+    let sb = api.superblock();
+
+    let mut owners: HashMap<BlockId, String> = HashMap::new();
+    owners.insert(0, "superblock".to_string());
+    for id in sb.free_map_start..sb.free_map_start + sb.free_map_blocks {
+        owners.insert(id, "bitmap".to_string());
+    }
+    for id in sb.inode_table_start..sb.inode_table_start + sb.inode_table_blocks {
+        owners.insert(id, "inodos".to_string());
+    }
+    for id in inode_ext_chain(storage.as_ref(), &sb).unwrap_or_default() {
+        owners.insert(id, "inodos (ext)".to_string());
+    }
+
+    let names: HashMap<u32, String> = api
+        .list("/")?
+        .into_iter()
+        .map(|info| (info.inode_id, info.name))
+        .collect();
+    let mut file_blocks = Vec::new();
+    for inode in read_inode_table(storage.as_ref(), &sb).filter_map(|slot| slot.ok()) {
+        if inode.mode == 0 && inode.id != sb.root_inode {
+            continue;
+        }
+        let owner = if inode.id == sb.root_inode {
+            "directorio /".to_string()
+        } else {
+            names
+                .get(&inode.id)
+                .cloned()
+                .unwrap_or_else(|| format!("inodo {}", inode.id))
+        };
+        for &block in &inode.blocks {
+            owners.insert(block, owner.clone());
+            file_blocks.push(block);
+        }
+    }
+
+    let mut cells = Vec::new();
+    for id in 0..sb.total_blocks {
+        let has_image = storage.block_path(id).exists();
+        if !has_image && !file_blocks.contains(&id) {
+            continue;
+        }
+        let status = if !has_image {
+            Status::Missing
+        } else if storage.read_verified(id).is_ok() {
+            Status::Ok
+        } else {
+            Status::Corrupt
+        };
+        cells.push(Cell {
+            id,
+            owner: owners.get(&id).cloned().unwrap_or_else(|| "libre".to_string()),
+            status,
+        });
+    }
+    Ok(cells)
+}
+
+fn render_sheet(storage: &QrStorageManager, cells: &[Cell], cols: u32, title: &str) -> RgbImage {
+    let rows = (cells.len() as u32).div_ceil(cols).max(1);
+    let width = 2 * MARGIN + cols * CELL_W;
+    let height = 2 * MARGIN + TITLE_H + rows * CELL_H;
+    let mut sheet = RgbImage::from_pixel(width, height, WHITE);
+
+    draw_text(&mut sheet, MARGIN, MARGIN, title, 2, BLACK);
+
+    for (n, cell) in cells.iter().enumerate() {
+        let x = MARGIN + (n as u32 % cols) * CELL_W;
+        let y = MARGIN + TITLE_H + (n as u32 / cols) * CELL_H;
+
+        // marco del color del estado
+        draw_rect(&mut sheet, x + 1, y + 1, CELL_W - 2, CELL_H - 2, cell.status.color());
+
+        let thumb_x = x + PAD;
+        let thumb_y = y + PAD;
+        match image::open(storage.block_path(cell.id)) {
+            Ok(img) => {
+                // nearest para que los modulos del qr queden nitidos
+                let thumb = imageops::resize(&img.to_rgb8(), THUMB, THUMB, FilterType::Nearest);
+                imageops::overlay(&mut sheet, &thumb, thumb_x as i64, thumb_y as i64);
+            }
+            Err(_) => {
+                // sin imagen: cuadro gris tachado
+                fill_rect(&mut sheet, thumb_x, thumb_y, THUMB, THUMB, GRAY);
+                for d in 0..THUMB {
+                    sheet.put_pixel(thumb_x + d, thumb_y + d, cell.status.color());
+                    sheet.put_pixel(thumb_x + THUMB - 1 - d, thumb_y + d, cell.status.color());
+                }
+            }
+        }
+
+        let text_x = x + PAD;
+        let text_y = y + 2 * PAD + THUMB;
+        let max_chars = (THUMB / GLYPH_ADVANCE) as usize;
+        draw_text(&mut sheet, text_x, text_y, &format!("#{:06}", cell.id), 1, BLACK);
+        let owner: String = cell.owner.chars().take(max_chars).collect();
+        draw_text(&mut sheet, text_x, text_y + LINE, &owner, 1, BLACK);
+        draw_text(&mut sheet, text_x, text_y + 2 * LINE, cell.status.label(), 1, cell.status.color());
+    }
+
+    sheet
+}
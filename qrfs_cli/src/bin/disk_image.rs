@@ -0,0 +1,228 @@
+// disk_image - convierte entre un disco qrfs y una imagen cruda de bloques
+// to:   qrfs (carpeta de qr o contenedor) -> disco.img + disco.img.json
+// from: disco.img -> carpeta de qr
+// la imagen es un volcado bloque a bloque (el bloque n esta en n * block_size),
+// asi que se puede montar como contenedor o recuperar archivos con dd usando
+// la lista de bloques del json. scan_order del json dice en que orden
+// escanear los qr impresos para que un escaneo a medias sirva cuanto antes
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::errors::QrfsError;
+use qrfs_core::fs_format::{self, read_inode_table};
+use qrfs_core::missing::scan_order;
+use qrfs_core::sha256::sha256_hex;
+use qrfs_core::storage::{BlockStorage, ContainerStorage, QrStorageManager};
+use qrfs_core::Superblock;
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("disk_image.qrfs: error: {e}");
+        process::exit(1);
+    }
+}
+
+fn print_usage() {
+    eprintln!("Uso: disk_image.qrfs to <qr_folder|disco.img> <salida.img>");
+    eprintln!("     disk_image.qrfs from <entrada.img> <qr_folder> [--shard N]");
+}
+
+fn run() -> Result<(), QrfsError> {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 4 {
+        print_usage();
+        return Ok(());
+    }
+
+    let source = &args[2];
+    let dest = &args[3];
+
+    match args[1].as_str() {
+        "to" => {
+            if Path::new(source).is_file() {
+                to_image(ContainerStorage::open_disk(source)?, dest)
+            } else {
+                let sb = QrStorageManager::probe_superblock(source.as_str())?.ok_or_else(|| {
+                    QrfsError::Other(format!("'{}' no tiene superblock, corra mkfs primero", source))
+                })?;
+                let storage = QrStorageManager::new(source, sb.block_size as usize, sb.total_blocks);
+                to_image(storage, dest)
+            }
+        }
+        "from" => {
+            let mut shard_size = None;
+            let mut i = 4;
+            while i < args.len() {
+                if args[i] == "--shard" && i + 1 < args.len() {
+                    if let Ok(n) = args[i + 1].parse::<u32>() {
+                        shard_size = Some(n);
+                    }
+                    i += 1;
+                }
+                i += 1;
+            }
+            from_image(source, dest, shard_size)
+        }
+        _ => {
+            print_usage();
+            Ok(())
+        }
+    }
+}
+
+// metadata que acompaña a la imagen (disco.img.json)
+#[derive(Serialize)]
+struct ImageManifest {
+    block_size: usize,
+    total_blocks: u32,
+    superblock: Superblock,
+    files: Vec<ImageFile>,
+    // bloques en uso del mas importante al menos: superblock, bitmap, tabla
+    // de inodos, directorios y datos (los de archivos fijos primero)
+    scan_order: Vec<ScanEntry>,
+}
+
+#[derive(Serialize)]
+struct ScanEntry {
+    block: u32,
+    role: &'static str,
+}
+
+#[derive(Serialize)]
+struct ImageFile {
+    name: String,
+    inode_id: u32,
+    size: u64,
+    mode: u16,
+    // bloques en orden; cada uno empieza en block * block_size dentro de la imagen
+    blocks: Vec<u32>,
+    // del contenido, para verificar una copia reingestada (verify.qrfs --manifest)
+    sha256: String,
+}
+
+fn read_superblock(storage: &dyn BlockStorage) -> Result<Superblock, QrfsError> {
+    let sb = fs_format::read_superblock(storage)?;
+    if !sb.is_valid() {
+        return Err(QrfsError::Other("firma invalida en bloque 0".into()));
+    }
+    Ok(sb)
+}
+
+fn to_image<B: BlockStorage + 'static>(storage: B, dest: &str) -> Result<(), QrfsError> {
+    let sb = read_superblock(&storage)?;
+    let block_size = storage.block_size();
+
+    println!(
+        "disk_image.qrfs: volcando {} bloques de {} bytes a '{}'...",
+        sb.total_blocks, block_size, dest
+    );
+
+    let image = ContainerStorage::create(dest, block_size, sb.total_blocks)?;
+    for id in 0..sb.total_blocks {
+        let data = storage.read_block(id)?;
+        // el contenedor nace en cero, esos bloques no hace falta escribirlos
+        if data.iter().any(|&b| b != 0) {
+            image.write_block(id, &data)?;
+        }
+    }
+
+    // nombres desde el directorio, bloques desde la tabla de inodos
+    let inodes: Vec<_> = read_inode_table(&storage, &sb)
+        .filter_map(|slot| slot.ok())
+        .collect();
+    let scan_order: Vec<ScanEntry> = scan_order(&storage)
+        .into_iter()
+        .map(|(block, role)| ScanEntry {
+            block,
+            role: role.name(),
+        })
+        .collect();
+    let api = QrfsApi::open(Arc::new(storage))?;
+    let mut files = Vec::new();
+    for info in api.list("/")? {
+        let blocks = inodes
+            .iter()
+            .find(|inode| inode.id == info.inode_id)
+            .map(|inode| inode.blocks.clone())
+            .unwrap_or_default();
+        let sha256 = sha256_hex(&api.read(&format!("/{}", info.name))?);
+        files.push(ImageFile {
+            name: info.name,
+            inode_id: info.inode_id,
+            size: info.size,
+            mode: info.mode,
+            blocks,
+            sha256,
+        });
+    }
+
+    let manifest = ImageManifest {
+        block_size,
+        total_blocks: sb.total_blocks,
+        superblock: sb,
+        files,
+        scan_order,
+    };
+    let json = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| QrfsError::Other(format!("error serializando metadata: {}", e)))?;
+    let json_path = format!("{}.json", dest);
+    fs::write(&json_path, json)?;
+
+    println!(
+        "disk_image.qrfs: listo ({} archivos, metadata en '{}').",
+        manifest.files.len(),
+        json_path
+    );
+    Ok(())
+}
+
+fn from_image(source: &str, dest: &str, shard_size: Option<u32>) -> Result<(), QrfsError> {
+    // el superblock dice el tamaño de bloque real de la imagen
+    let image = ContainerStorage::open_disk(source)?;
+    let mut sb = read_superblock(&image)?;
+    if image.total_blocks() < sb.total_blocks {
+        return Err(QrfsError::Other(format!(
+            "la imagen tiene {} bloques y el superblock dice {}",
+            image.total_blocks(),
+            sb.total_blocks
+        )));
+    }
+    if let Some(n) = shard_size {
+        sb.shard_size = n;
+    }
+
+    println!(
+        "disk_image.qrfs: codificando {} bloques de '{}' en '{}'...",
+        sb.total_blocks, source, dest
+    );
+
+    let storage = QrStorageManager::new(dest, sb.block_size as usize, sb.total_blocks)
+        .with_shard_size(sb.shard_size);
+    // vaciar lo que hubiera en la carpeta antes de copiar
+    storage.init_empty_blocks()?;
+
+    let region = fs_format::encode_superblock(&sb)?;
+    for (id, block) in region.iter().enumerate() {
+        storage.write_block(id as u32, block)?;
+    }
+
+    let mut written = region.len();
+    for id in region.len() as u32..sb.total_blocks {
+        let data = image.block(id)?;
+        if data.iter().any(|&b| b != 0) {
+            storage.write_block(id, data)?;
+            written += 1;
+        }
+    }
+
+    println!("disk_image.qrfs: listo ({} bloques con datos).", written);
+    Ok(())
+}
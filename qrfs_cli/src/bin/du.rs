@@ -0,0 +1,197 @@
+// du - cuanto ocupa cada archivo y directorio de un disco qrfs
+// du.qrfs <qr_folder|disco.img> [--dirs] [--config qrfs.toml]
+// por cada entrada: tamaño logico, bloques asignados, bytes asignados, bytes
+// guardados de verdad, bytes de las imagenes qr en el host y la ampliacion
+// (qr / tamaño). los bloques son chicos (128 bytes) y cada uno es un png
+// entero: un archivo de pocos bytes ocupa un bloque y un qr completo, salvo
+// que su cola este empaquetada con otras (mkfs.qrfs --pack-tails), y ahi
+// guarda solo su pedazo. los directorios suman todo lo que tienen adentro.
+// con --dirs solo se muestran los directorios
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+
+use qrfs_core::api::{QrfsApi, TreeEntry};
+use qrfs_core::config::{config_arg, QrfsConfig};
+use qrfs_core::disk::BlockId;
+use qrfs_core::errors::QrfsError;
+use qrfs_core::storage::{BlockStorage, ContainerStorage};
+
+// una entrada con mas de la mitad de lo guardado en relleno se marca con '*'
+const SLACK_MARK: f64 = 0.5;
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("du.qrfs: error: {e}");
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), QrfsError> {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 2 {
+        eprintln!("Uso: du.qrfs <qr_folder|disco.img> [--dirs] [--config qrfs.toml]");
+        return Ok(());
+    }
+
+    let disk = &args[1];
+    let config = QrfsConfig::for_disk(disk, config_arg(&args))?;
+    let dirs_only = args[2..].iter().any(|a| a == "--dirs");
+
+    if Path::new(disk).is_file() {
+        show(ContainerStorage::open_disk(disk)?, dirs_only)
+    } else {
+        show(config.qr_storage(disk), dirs_only)
+    }
+}
+
+// lo que ocupa una entrada (o todo lo que hay bajo un directorio)
+#[derive(Default, Clone, Copy)]
+struct Usage {
+    size: u64,
+    blocks: u64,
+    stored: u64,
+    qr_bytes: u64,
+}
+
+impl Usage {
+    fn add(&mut self, other: &Usage) {
+        self.size += other.size;
+        self.blocks += other.blocks;
+        self.stored += other.stored;
+        self.qr_bytes += other.qr_bytes;
+    }
+}
+
+fn show<B: BlockStorage + 'static>(storage: B, dirs_only: bool) -> Result<(), QrfsError> {
+    let storage = Arc::new(storage);
+    let api = QrfsApi::open(storage.clone())?;
+    let block_size = api.usage().block_size as u64;
+    let entries = api.walk()?;
+
+    let own: Vec<Usage> = entries
+        .iter()
+        .map(|entry| Usage {
+            // un directorio no tiene tamaño logico propio, solo sus bloques
+            size: if entry.info.is_dir() { 0 } else { entry.info.size },
+            blocks: entry.blocks.len() as u64,
+            stored: entry.stored,
+            qr_bytes: entry.blocks.iter().map(|&b| storage.host_bytes(b)).sum(),
+        })
+        .collect();
+    let totals = directory_totals(&entries, &own, |b| storage.host_bytes(b));
+
+    println!(
+        "{:>10} {:>7} {:>10} {:>10} {:>10} {:>7}  ruta",
+        "tamaño", "bloques", "asignado", "guardado", "qr", "ampl."
+    );
+    for (entry, usage) in entries.iter().zip(&own) {
+        let is_dir = entry.info.is_dir();
+        if dirs_only && !is_dir {
+            continue;
+        }
+        let usage = if is_dir { totals[&entry.path] } else { *usage };
+        print_row(&usage, block_size, &entry.path, is_dir);
+    }
+
+    let total = totals["/"];
+    let allocated = total.blocks * block_size;
+    println!();
+    println!(
+        "Total: {} bytes en {} bloques de {} bytes ({} de relleno)",
+        total.size,
+        total.blocks,
+        block_size,
+        allocated.saturating_sub(total.size)
+    );
+    if total.stored < allocated {
+        println!(
+            "Guardado: {} bytes ({:.1}x menos que lo asignado por las colas compartidas)",
+            total.stored,
+            allocated as f64 / total.stored.max(1) as f64
+        );
+    }
+    if let Some(per_block) = total.qr_bytes.checked_div(total.blocks) {
+        println!(
+            "Imagenes qr: {} bytes, {} por bloque en promedio ({:.1}x el bloque)",
+            total.qr_bytes,
+            per_block,
+            per_block as f64 / block_size as f64
+        );
+    }
+    if total.size > 0 {
+        println!(
+            "Ampliacion: {:.1}x (bytes de qr por byte de datos)",
+            total.qr_bytes as f64 / total.size as f64
+        );
+    }
+    println!("(* = mas de la mitad de lo guardado es relleno del ultimo bloque)");
+    Ok(())
+}
+
+// suma cada entrada en su directorio y en todos los de arriba (un directorio
+// tambien en si mismo, por sus bloques); un inodo con dos nombres bajo el
+// mismo directorio cuenta una vez, y un bloque compartido por varias colas
+// tambien
+fn directory_totals(
+    entries: &[TreeEntry],
+    own: &[Usage],
+    host_bytes: impl Fn(BlockId) -> u64,
+) -> HashMap<String, Usage> {
+    let mut totals: HashMap<String, Usage> = HashMap::new();
+    let mut counted: HashSet<(String, u32)> = HashSet::new();
+    let mut blocks: HashMap<String, HashSet<BlockId>> = HashMap::new();
+    for (entry, usage) in entries.iter().zip(own) {
+        let mut dir = if entry.info.is_dir() {
+            Some(entry.path.clone())
+        } else {
+            parent(&entry.path)
+        };
+        while let Some(path) = dir {
+            if counted.insert((path.clone(), entry.info.inode_id)) {
+                totals.entry(path.clone()).or_default().add(usage);
+                blocks.entry(path.clone()).or_default().extend(&entry.blocks);
+            }
+            dir = parent(&path);
+        }
+    }
+    for (path, total) in totals.iter_mut() {
+        let unique = &blocks[path];
+        total.blocks = unique.len() as u64;
+        total.qr_bytes = unique.iter().map(|&b| host_bytes(b)).sum();
+    }
+    totals
+}
+
+// "/a/b" -> "/a", "/a" -> "/", "/" -> None
+fn parent(path: &str) -> Option<String> {
+    match path.rfind('/') {
+        _ if path == "/" => None,
+        Some(0) | None => Some("/".to_string()),
+        Some(i) => Some(path[..i].to_string()),
+    }
+}
+
+fn print_row(usage: &Usage, block_size: u64, path: &str, is_dir: bool) {
+    let allocated = usage.blocks * block_size;
+    let ratio = if usage.size > 0 {
+        format!("{:.1}x", usage.qr_bytes as f64 / usage.size as f64)
+    } else {
+        "-".to_string()
+    };
+    let slack = usage.stored.saturating_sub(usage.size);
+    let mark = if usage.stored > 0 && slack as f64 > usage.stored as f64 * SLACK_MARK && !is_dir {
+        "*"
+    } else {
+        " "
+    };
+    let suffix = if is_dir && path != "/" { "/" } else { "" };
+    println!(
+        "{:>10} {:>7} {:>10} {:>10} {:>10} {:>7} {}{}{}",
+        usage.size, usage.blocks, allocated, usage.stored, usage.qr_bytes, ratio, mark, path, suffix
+    );
+}
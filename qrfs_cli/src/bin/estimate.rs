@@ -0,0 +1,80 @@
+// estimate - cuantos bloques, qr y hojas necesita un conjunto de datos
+// estimate.qrfs --size 25MB [--block-size 128] [--ec L|M|Q|H] [--pinned N] [--mirror]
+// no toca ningun disco: sirve para elegir --blocks, --inodes y --ec-level
+// antes de correr mkfs.qrfs
+
+use std::env;
+use std::process;
+
+use qrfs_core::config::{parse_ec_level, MIN_BLOCK_SIZE};
+use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::errors::QrfsError;
+use qrfs_core::estimate::{estimate, parse_size, QRS_PER_PAGE};
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("estimate.qrfs: error: {e}");
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), QrfsError> {
+    let args: Vec<String> = env::args().collect();
+    let value = |flag: &str| args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1));
+
+    let Some(size) = value("--size") else {
+        eprintln!("Uso: estimate.qrfs --size <bytes|20K|25MB> [--block-size 128] [--ec L|M|Q|H] [--pinned N] [--mirror]");
+        return Ok(());
+    };
+    let data_bytes = parse_size(size)
+        .ok_or_else(|| QrfsError::Other(format!("--size '{}' no es un tamaño (ej. 500, 20K, 25MB)", size)))?;
+    let block_size = match value("--block-size") {
+        Some(n) => n
+            .parse::<usize>()
+            .ok()
+            .filter(|&n| n >= MIN_BLOCK_SIZE)
+            .ok_or_else(|| QrfsError::Other(format!("--block-size espera un numero desde {}", MIN_BLOCK_SIZE)))?,
+        None => BLOCK_SIZE,
+    };
+    if args.iter().any(|a| a == "--parity") {
+        return Err(QrfsError::Unimplemented(
+            "qrfs no tiene bloques de paridad; la copia completa es --mirror".into(),
+        ));
+    }
+    let ec_level = parse_ec_level(value("--ec").map_or("M", String::as_str)).map_err(QrfsError::Other)?;
+    let pinned_blocks = match value("--pinned") {
+        Some(n) => n
+            .parse::<u32>()
+            .map_err(|_| QrfsError::Other("--pinned espera un numero".into()))?,
+        None => 0,
+    };
+    let copies = if args.iter().any(|a| a == "--mirror") { 2 } else { 1 };
+
+    let plan = estimate(data_bytes, block_size, ec_level, pinned_blocks, copies);
+    // un qr por bloque: el bloque lleno tiene que entrar en uno
+    if plan.qr_modules == 0 {
+        return Err(QrfsError::Other(format!(
+            "--block-size {}: un bloque lleno no entra en un qr con correccion {:?}",
+            block_size, ec_level
+        )));
+    }
+    println!("Datos:          {} bytes en {} bloques de {}", data_bytes, plan.data_blocks, block_size);
+    println!("Disco:          {} bloques, {} inodos", plan.total_blocks, plan.inode_count);
+    if copies > 1 {
+        println!("Imagenes qr:    {} ({} por copia)", plan.qr_images, plan.total_blocks);
+    } else {
+        println!("Imagenes qr:    {}", plan.qr_images);
+    }
+    println!("Hojas impresas: {} ({} qr por hoja)", plan.pages, QRS_PER_PAGE);
+    println!("Qr por bloque:  {0}x{0} modulos con correccion {1:?}", plan.qr_modules, ec_level);
+    println!(
+        "mkfs.qrfs <carpeta> --blocks {}{} --inodes {} --ec-level {:?}{}{}",
+        plan.total_blocks,
+        if block_size != BLOCK_SIZE { format!(" --block-size {}", block_size) } else { String::new() },
+        plan.inode_count,
+        ec_level,
+        if pinned_blocks > 0 { format!(" --pinned {}", pinned_blocks) } else { String::new() },
+        if copies > 1 { " --mirror <espejo>" } else { "" }
+    );
+    Ok(())
+}
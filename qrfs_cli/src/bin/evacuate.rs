@@ -0,0 +1,80 @@
+// evacuate - saca los datos de uno o mas bloques fisicos de un disco qrfs
+// evacuate.qrfs <qr_folder|disco.img> <bloque>... [--to <bloque>] [--config qrfs.toml]
+// cada bloque se copia a uno libre (o al de --to, si se evacua uno solo) y el
+// archivo que lo usaba pasa a apuntar ahi en una transaccion: sirve para
+// dejar libre un qr dañado o mal impreso, o el final del disco antes de
+// achicarlo. el bloque viejo queda libre
+use std::env;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::config::{config_arg, QrfsConfig};
+use qrfs_core::disk::BlockId;
+use qrfs_core::errors::QrfsError;
+use qrfs_core::storage::{BlockStorage, ContainerStorage};
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("evacuate.qrfs: error: {e}");
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), QrfsError> {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 3 {
+        eprintln!("Uso: evacuate.qrfs <qr_folder|disco.img> <bloque>... [--to <bloque>] [--config qrfs.toml]");
+        return Ok(());
+    }
+
+    let disk = &args[1];
+    let config = QrfsConfig::for_disk(disk, config_arg(&args))?;
+    let mut blocks: Vec<BlockId> = Vec::new();
+    let mut to = None;
+    let mut i = 2;
+    while i < args.len() {
+        if args[i] == "--to" && i + 1 < args.len() {
+            to = Some(parse_block(&args[i + 1])?);
+            i += 1;
+        } else if args[i] == "--config" {
+            i += 1;
+        } else {
+            blocks.push(parse_block(&args[i])?);
+        }
+        i += 1;
+    }
+    if blocks.is_empty() {
+        return Err(QrfsError::Other("falta el bloque a evacuar".into()));
+    }
+    if to.is_some() && blocks.len() > 1 {
+        return Err(QrfsError::Other("--to solo sirve para evacuar un bloque".into()));
+    }
+
+    if Path::new(disk).is_file() {
+        evacuate(ContainerStorage::open_disk(disk)?, &blocks, to)
+    } else {
+        evacuate(config.qr_storage(disk), &blocks, to)
+    }
+}
+
+fn parse_block(text: &str) -> Result<BlockId, QrfsError> {
+    text.parse()
+        .map_err(|_| QrfsError::Other(format!("numero de bloque invalido '{}'", text)))
+}
+
+fn evacuate<B: BlockStorage + 'static>(
+    storage: B,
+    blocks: &[BlockId],
+    to: Option<BlockId>,
+) -> Result<(), QrfsError> {
+    let api = QrfsApi::open(Arc::new(storage))?;
+    for &from in blocks {
+        let moved = api.evacuate_block(from, to)?;
+        println!("evacuate.qrfs: bloque {} -> {}", from, moved);
+    }
+    println!("evacuate.qrfs: {} bloques liberados", blocks.len());
+    Ok(())
+}
@@ -1,10 +1,17 @@
 use std::env;
 use std::process;
+use std::sync::Arc;
 
-use qrfs_core::disk::{Inode, Superblock, QRFS_MAGIC, QRFS_VERSION};
+use qrfs_core::api::QrfsApi;
+use qrfs_core::config::{config_arg, QrfsConfig};
 use qrfs_core::errors::QrfsError;
-use qrfs_core::storage::{BlockStorage, QrStorageManager};
-use std::collections::HashSet;
+use qrfs_core::fsck::{self, FsckOptions};
+use qrfs_core::keyring::{read_passphrase_from, NEW_PASSPHRASE_ENV};
+use qrfs_core::reprint::{PaperStatus, PrintLog, PrintManifest};
+use qrfs_core::shamir::{key_shards_arg, read_shards, KeyShard};
+use qrfs_core::storage::{BlockStorage, ContainerStorage};
+use qrfs_core::volume::{check_volumes, probe_volumes, volume_dirs_arg, volume_paths, VolumeStorage};
+use std::path::Path;
 
 fn main() {
     if let Err(e) = run() {
@@ -16,126 +23,149 @@ fn main() {
 fn run() -> Result<(), QrfsError> {
     let args: Vec<String> = env::args().collect();
     
-    // sintaxis: fsck.qrfs <qrfolder>
-    if args.len() != 2 {
-        eprintln!("Uso: fsck.qrfs <qrfolder/>");
+    // sintaxis: fsck.qrfs <qrfolder> [--no-cache] [--strict] [--repair] [--key-shards img...] [--volume-dirs carpeta...] [--config f]
+    if args.len() < 2 {
+        eprintln!("Uso: fsck.qrfs <qrfolder/|disco.img> [--no-cache] [--strict] [--repair] [--key-shards <img>...] [--volume-dirs <carpeta>...] [--config qrfs.toml]");
+        eprintln!("     --repair: reescribe los '.' y '..' mal apuntados y renombra los nombres repetidos (queda el más nuevo)");
+        eprintln!("     --key-shards: frase olvidada, rearma la clave con las tarjetas y pide una frase nueva");
+        eprintln!("     --volume-dirs: las carpetas de los volumenes 2 en adelante (sin esto, disco.vol2, disco.vol3...)");
         return Ok(());
     }
 
     let qrfolder = &args[1];
+    let config = QrfsConfig::for_disk(qrfolder, config_arg(&args))?;
+
+    // --no-cache: decodificar todos los png aunque esten en la cache
+    // --strict: un qr con un largo distinto al declarado es corrupcion (sin
+    // esto se corta o se completa con ceros) y se leen todos los bloques de datos
+    // --repair: corrige los '.' y '..' mal apuntados y los nombres repetidos
+    // (lo demas solo se informa)
+    let mut use_cache = config.decode_cache;
+    let mut strict = false;
+    let mut repair = false;
+    for arg in &args[2..] {
+        if arg == "--no-cache" {
+            use_cache = false;
+        } else if arg == "--strict" {
+            strict = true;
+        } else if arg == "--repair" {
+            repair = true;
+        }
+    }
+    let steps = if strict { 8 } else { 7 };
 
     println!("fsck.qrfs: Iniciando verificación de '{}'", qrfolder);
     println!("--------------------------------------------------");
 
-    let block_size = 128;
-    let total_blocks = 400;
-
-    let storage = QrStorageManager::new(qrfolder, block_size, total_blocks);
-
-    // verificar superblock (firma)
-    print!("[1/5] Verificando Superblock (Firma)... ");
-    let superblock = check_superblock(&storage)?;
-    println!("OK (Magic: {:X})", superblock.magic);
-
-    // verificar limites del disco
-    print!("[2/5] Verificando límites del disco... ");
-    check_disk_layout(&superblock)?;
-    println!("OK");
-
-    // analizar bitmap de espacio
-    print!("[3/5] Analizando Bitmap de espacio... ");
-    let bitmap = load_bitmap(&storage, &superblock)?;
-    println!("OK");
-
-    // analizar tabla de inodos
-    print!("[4/5] Analizando Tabla de Inodos... ");
-    let inodes = load_inodes(&storage, &superblock)?;
-    println!("OK ({} inodos activos)", inodes.len());
-
-    // verificar consistencia bitmap vs inodos
-    print!("[5/5] Verificando consistencia Bitmap vs Inodos... ");
-    check_consistency(&bitmap, &inodes, &superblock)?;
-    println!("OK");
+    // un superblock ilegible lo reporta el paso 1
+    let volumes = match Path::new(qrfolder).is_file() {
+        true => None,
+        false => probe_volumes(qrfolder).ok().flatten(),
+    };
+    let volume_paths = volumes
+        .as_ref()
+        .map(|sb| volume_paths(qrfolder, sb, volume_dirs_arg(&args)))
+        .unwrap_or_default();
+
+    // una carpeta de qr, un contenedor (volcado crudo de bloques en un archivo)
+    // o las carpetas de un disco repartido en volumenes
+    let storage: Box<dyn BlockStorage> = if Path::new(qrfolder).is_file() {
+        Box::new(ContainerStorage::open_disk(qrfolder)?)
+    } else if let Some(sb) = &volumes {
+        // marcas, orden de las carpetas y qr guardados en otro volumen
+        print!("Verificando juego de volúmenes... ");
+        let problems = check_volumes(sb, &volume_paths);
+        if !problems.is_empty() {
+            return Err(QrfsError::Other(format!("CORRUPCIÓN: {}", problems.join("; "))));
+        }
+        println!("OK ({} volúmenes)", volume_paths.len());
+        Box::new(VolumeStorage::open(sb, &volume_paths, |s| {
+            config.configure(s).with_decode_cache(use_cache).with_strict_size(strict)
+        })?)
+    } else {
+        Box::new(
+            config
+                .qr_storage(qrfolder)
+                .with_decode_cache(use_cache)
+                .with_strict_size(strict),
+        )
+    };
+    let storage = storage.as_ref();
+
+    // los pasos estan en qrfs_core::fsck; aca se muestran en orden y se corta
+    // en el primero con problemas
+    let report = fsck::check(storage, &FsckOptions { strict, repair });
+    for (i, check) in report.checks.iter().enumerate() {
+        print!("[{}/{}] {}... ", i + 1, steps, step_label(&check.name));
+        if !check.ok {
+            println!();
+            return Err(QrfsError::Other(format!("CORRUPCIÓN: {}", check.details.join("; "))));
+        }
+        if !check.repaired.is_empty() {
+            println!("REPARADO ({} cambios)", check.repaired.len());
+            for change in &check.repaired {
+                println!("    {}", change);
+            }
+        } else if check.notes.is_empty() {
+            println!("OK");
+        } else {
+            println!("OK ({})", check.notes.join(", "));
+        }
+    }
 
     println!("--------------------------------------------------");
     println!("fsck.qrfs: El sistema de archivos está LIMPIO.");
 
-    Ok(())
-}
-
-// funciones auxiliares de fsck 
-
-fn check_superblock(storage: &QrStorageManager) -> Result<Superblock, QrfsError> {
-    let data = storage.read_block(0)?;
-    let sb: Superblock = bincode::deserialize(&data)
-        .map_err(|_| QrfsError::Other("No se pudo leer el Superblock (Bloque 0)".into()))?;
-
-    if sb.magic != QRFS_MAGIC {
-        return Err(QrfsError::Other("Firma inválida (Magic Number incorrecto)".into()));
-    }
-    if sb.version != QRFS_VERSION {
-        return Err(QrfsError::Other("Versión de QRFS no soportada".into()));
+    // a que impresion en papel corresponde (reprint_plan.qrfs --record); el
+    // registro es de una sola carpeta
+    if !Path::new(qrfolder).is_file() && volumes.is_none() {
+        let (current, _) = PrintManifest::capture(&config.qr_storage(qrfolder));
+        match PrintLog::load(qrfolder)?.status(&current) {
+            PaperStatus::NeverPrinted => {}
+            status @ PaperStatus::Current { .. } => println!("fsck.qrfs: papel: {}", status),
+            status @ PaperStatus::Diverged { .. } => {
+                println!("fsck.qrfs: warning: {}; reprint_plan.qrfs dice cuales", status)
+            }
+        }
     }
-    Ok(sb)
-}
 
-fn check_disk_layout(sb: &Superblock) -> Result<(), QrfsError> {
-    if sb.data_block_start >= sb.total_blocks {
-        return Err(QrfsError::Other("Layout corrupto: Inicio de datos fuera de rango".into()));
+    // recuperacion de un disco cifrado con la frase olvidada
+    let shard_paths = key_shards_arg(&args);
+    if !shard_paths.is_empty() {
+        let shards = read_shards(&shard_paths)?;
+        if Path::new(qrfolder).is_file() {
+            recover_key(ContainerStorage::open_disk(qrfolder)?, &shards)?;
+        } else if let Some(sb) = &volumes {
+            recover_key(VolumeStorage::open(sb, &volume_paths, |s| config.configure(s))?, &shards)?;
+        } else {
+            recover_key(config.qr_storage(qrfolder), &shards)?;
+        }
     }
+
     Ok(())
 }
 
-fn load_bitmap(storage: &QrStorageManager, sb: &Superblock) -> Result<Vec<u8>, QrfsError> {
-    let mut bitmap = Vec::new();
-    for i in 0..sb.free_map_blocks {
-        let data = storage.read_block(sb.free_map_start + i)?;
-        bitmap.extend_from_slice(&data);
-    }
-    Ok(bitmap)
+// rearma la maestra con las tarjetas y la vuelve a sellar con una frase nueva
+fn recover_key<B: BlockStorage + 'static>(storage: B, shards: &[KeyShard]) -> Result<(), QrfsError> {
+    let api = QrfsApi::open(Arc::new(storage))?;
+    api.unlock_with_shards(shards)?;
+    println!("fsck.qrfs: Clave maestra rearmada con {} tarjetas.", shards.len());
+    let passphrase = read_passphrase_from(NEW_PASSPHRASE_ENV, "fsck.qrfs: frase nueva: ")?;
+    api.recover_passphrase(shards, &passphrase)?;
+    println!("fsck.qrfs: Frase nueva guardada; las tarjetas siguen sirviendo.");
+    Ok(())
 }
 
-fn load_inodes(storage: &QrStorageManager, sb: &Superblock) -> Result<Vec<Inode>, QrfsError> {
-    let mut inodes = Vec::new();
-    let mut buf = Vec::new();
-    for i in 0..sb.inode_table_blocks {
-        let data = storage.read_block(sb.inode_table_start + i)?;
-        buf.extend_from_slice(&data);
+fn step_label(name: &str) -> &str {
+    match name {
+        fsck::CHECK_SUPERBLOCK => "Verificando Superblock (Firma)",
+        fsck::CHECK_LAYOUT => "Verificando límites del disco",
+        fsck::CHECK_BITMAP => "Analizando Bitmap de espacio",
+        fsck::CHECK_INODES => "Analizando Tabla de Inodos",
+        fsck::CHECK_LINKS => "Verificando consistencia Bitmap vs Inodos",
+        fsck::CHECK_DIRECTORIES => "Verificando directorios",
+        fsck::CHECK_TREE => "Verificando árbol de directorios",
+        fsck::CHECK_DATA => "Verificando bloques de datos",
+        other => other,
     }
-    let mut cursor = std::io::Cursor::new(buf);
-    for _ in 0..sb.inode_count {
-        if let Ok(inode) = bincode::deserialize_from::<_, Inode>(&mut cursor) {
-            if inode.mode != 0 { inodes.push(inode); }
-        }
-    }
-    Ok(inodes)
 }
-
-fn check_consistency(bitmap: &[u8], inodes: &[Inode], sb: &Superblock) -> Result<(), QrfsError> {
-    let mut claimed_blocks = HashSet::new();
-    
-    // recolectar bloques reclamados por inodos
-    for inode in inodes {
-        for &blk in &inode.blocks {
-            if blk >= sb.total_blocks {
-                return Err(QrfsError::Other(format!("Inodo {} apunta a bloque fuera de rango {}", inode.id, blk)));
-            }
-            claimed_blocks.insert(blk);
-        }
-    }
-
-    // verificar contra bitmap
-    for blk in sb.data_block_start..sb.total_blocks {
-        let byte = (blk / 8) as usize;
-        let bit = (blk % 8) as u8;
-        if byte >= bitmap.len() { break; }
-        
-        let is_used = (bitmap[byte] & (1 << bit)) != 0;
-        let is_claimed = claimed_blocks.contains(&blk);
-
-        if is_claimed && !is_used {
-            return Err(QrfsError::Other(format!("CORRUPCIÓN: Bloque {} tiene datos pero está marcado como libre", blk)));
-        }
-    }
-    Ok(())
-}
\ No newline at end of file
@@ -0,0 +1,459 @@
+// ftp - expone un disco qrfs por ftp (modo pasivo)
+// pensado para celulares y herramientas viejas que solo saben put/get
+// es un adaptador delgado sobre QrfsApi, no necesita fuse
+
+use std::env;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, TcpListener, TcpStream};
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use qrfs_core::api::{FileInfo, QrfsApi};
+use qrfs_core::errors::QrfsError;
+use qrfs_core::storage::QrStorageManager;
+
+// puerto por defecto (el 21 necesita root)
+const DEFAULT_PORT: u16 = 2121;
+
+// tiempo maximo esperando la conexion de datos en modo pasivo
+const DATA_TIMEOUT: Duration = Duration::from_secs(30);
+
+type FtpApi = QrfsApi<QrStorageManager>;
+
+// credenciales; None = acceso anonimo
+struct Credentials {
+    user: Option<String>,
+    pass: Option<String>,
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("ftp.qrfs: error: {e}");
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), QrfsError> {
+    let args: Vec<String> = env::args().collect();
+
+    // sintaxis: ftp <qrfolder> [--port n] [--bind addr] [--user u --pass p]
+    if args.len() < 2 {
+        eprintln!("Uso: ftp.qrfs <qrfolder/> [--port N] [--bind ADDR] [--user U --pass P]");
+        return Ok(());
+    }
+
+    let qrfolder = &args[1];
+    let mut port = DEFAULT_PORT;
+    let mut bind = "0.0.0.0".to_string();
+    let mut creds = Credentials {
+        user: None,
+        pass: None,
+    };
+
+    let mut i = 2;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--port" if i + 1 < args.len() => {
+                port = args[i + 1]
+                    .parse()
+                    .map_err(|_| QrfsError::Other(format!("puerto invalido: {}", args[i + 1])))?;
+                i += 1;
+            }
+            "--bind" if i + 1 < args.len() => {
+                bind = args[i + 1].clone();
+                i += 1;
+            }
+            "--user" if i + 1 < args.len() => {
+                creds.user = Some(args[i + 1].clone());
+                i += 1;
+            }
+            "--pass" if i + 1 < args.len() => {
+                creds.pass = Some(args[i + 1].clone());
+                i += 1;
+            }
+            other => eprintln!("ftp.qrfs: argumento ignorado: {}", other),
+        }
+        i += 1;
+    }
+
+    if creds.user.is_some() != creds.pass.is_some() {
+        return Err(QrfsError::Other("--user y --pass van juntos".into()));
+    }
+
+    let sb = QrStorageManager::probe_superblock(qrfolder.as_str())?.ok_or_else(|| {
+        QrfsError::NotFormatted(format!("{} (ejecute mkfs primero)", qrfolder))
+    })?;
+    let storage = QrStorageManager::new(qrfolder, sb.block_size as usize, sb.total_blocks);
+    let api = Arc::new(QrfsApi::open(Arc::new(storage))?);
+    let creds = Arc::new(creds);
+
+    let listener = TcpListener::bind((bind.as_str(), port))?;
+    println!("ftp.qrfs: sirviendo '{}' por ftp en {}:{}", qrfolder, bind, port);
+    if creds.user.is_none() {
+        println!("  - acceso anonimo (use --user/--pass para pedir clave)");
+    }
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("ftp.qrfs: conexion fallida: {}", e);
+                continue;
+            }
+        };
+        let api = api.clone();
+        let creds = creds.clone();
+        thread::spawn(move || {
+            let peer = stream
+                .peer_addr()
+                .map(|a| a.to_string())
+                .unwrap_or_default();
+            println!("ftp.qrfs: cliente conectado {}", peer);
+            if let Err(e) = FtpSession::new(api, creds).serve(stream) {
+                println!("ftp.qrfs: cliente {} desconectado: {}", peer, e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+// estado de una conexion de control
+struct FtpSession {
+    api: Arc<FtpApi>,
+    creds: Arc<Credentials>,
+    user: Option<String>,
+    logged_in: bool,
+    passive: Option<TcpListener>,
+    rename_from: Option<String>,
+    restart_at: u64,
+}
+
+impl FtpSession {
+    fn new(api: Arc<FtpApi>, creds: Arc<Credentials>) -> Self {
+        Self {
+            api,
+            creds,
+            user: None,
+            logged_in: false,
+            passive: None,
+            rename_from: None,
+            restart_at: 0,
+        }
+    }
+
+    fn serve(mut self, stream: TcpStream) -> io::Result<()> {
+        let local_ip = stream.local_addr()?.ip();
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut ctrl = stream;
+
+        reply(&mut ctrl, 220, "qrfs ftp listo")?;
+
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            let (cmd, arg) = match line.split_once(' ') {
+                Some((c, a)) => (c.to_ascii_uppercase(), a.to_string()),
+                None => (line.to_ascii_uppercase(), String::new()),
+            };
+
+            if cmd == "QUIT" {
+                return reply(&mut ctrl, 221, "adios");
+            }
+            self.command(&mut ctrl, local_ip, &cmd, &arg)?;
+        }
+    }
+
+    fn command(&mut self, ctrl: &mut TcpStream, local_ip: IpAddr, cmd: &str, arg: &str) -> io::Result<()> {
+        // antes de entrar solo se aceptan estos comandos
+        let public = matches!(cmd, "USER" | "PASS" | "SYST" | "FEAT" | "NOOP" | "OPTS");
+        if !self.logged_in && !public {
+            return reply(ctrl, 530, "inicie sesion con USER y PASS");
+        }
+
+        match cmd {
+            "USER" => {
+                self.user = Some(arg.to_string());
+                self.logged_in = false;
+                if self.creds.user.is_none() {
+                    self.logged_in = true;
+                    reply(ctrl, 230, "acceso anonimo concedido")
+                } else {
+                    reply(ctrl, 331, "se necesita clave")
+                }
+            }
+            "PASS" => {
+                if self.creds.user.is_none() {
+                    self.logged_in = true;
+                    return reply(ctrl, 230, "acceso anonimo concedido");
+                }
+                if self.user == self.creds.user && Some(arg) == self.creds.pass.as_deref() {
+                    self.logged_in = true;
+                    reply(ctrl, 230, "sesion iniciada")
+                } else {
+                    reply(ctrl, 530, "usuario o clave incorrectos")
+                }
+            }
+            "SYST" => reply(ctrl, 215, "UNIX Type: L8"),
+            "FEAT" => {
+                ctrl.write_all(b"211-extensiones:\r\n SIZE\r\n MDTM\r\n EPSV\r\n PASV\r\n REST STREAM\r\n UTF8\r\n211 fin\r\n")
+            }
+            "OPTS" => reply(ctrl, 200, "ok"),
+            "NOOP" => reply(ctrl, 200, "ok"),
+            "TYPE" | "MODE" | "STRU" => reply(ctrl, 200, "ok (todo se transfiere en binario)"),
+            "PWD" | "XPWD" => reply(ctrl, 257, "\"/\" es el directorio actual"),
+            "CWD" | "XCWD" => {
+                if arg.trim_matches('/').is_empty() || arg == "." || arg == ".." {
+                    reply(ctrl, 250, "directorio actual: /")
+                } else {
+                    reply(ctrl, 550, "qrfs solo tiene directorio raiz")
+                }
+            }
+            "CDUP" => reply(ctrl, 250, "directorio actual: /"),
+            "MKD" | "XMKD" | "RMD" | "XRMD" => reply(ctrl, 550, "qrfs solo tiene directorio raiz"),
+            "PASV" => {
+                let listener = TcpListener::bind((local_ip, 0))?;
+                let port = listener.local_addr()?.port();
+                self.passive = Some(listener);
+                let octets = match local_ip {
+                    IpAddr::V4(v4) => v4.octets(),
+                    IpAddr::V6(_) => return reply(ctrl, 522, "use EPSV con ipv6"),
+                };
+                reply(
+                    ctrl,
+                    227,
+                    &format!(
+                        "modo pasivo ({},{},{},{},{},{})",
+                        octets[0],
+                        octets[1],
+                        octets[2],
+                        octets[3],
+                        port >> 8,
+                        port & 0xff
+                    ),
+                )
+            }
+            "EPSV" => {
+                let listener = TcpListener::bind((local_ip, 0))?;
+                let port = listener.local_addr()?.port();
+                self.passive = Some(listener);
+                reply(ctrl, 229, &format!("modo pasivo extendido (|||{}|)", port))
+            }
+            "PORT" | "EPRT" => reply(ctrl, 502, "solo se soporta modo pasivo"),
+            "LIST" | "NLST" => {
+                let files = match self.api.list("/") {
+                    Ok(f) => f,
+                    Err(e) => return reply_error(ctrl, &e),
+                };
+                let mut listing = String::new();
+                for file in &files {
+                    if cmd == "LIST" {
+                        listing.push_str(&list_line(file));
+                    } else {
+                        listing.push_str(&file.name);
+                    }
+                    listing.push_str("\r\n");
+                }
+                self.send_data(ctrl, listing.as_bytes())
+            }
+            "SIZE" => match self.api.stat(&ftp_path(arg)) {
+                Ok(info) if !info.is_dir() => reply(ctrl, 213, &info.size.to_string()),
+                Ok(_) => reply(ctrl, 550, "no es un archivo"),
+                Err(e) => reply_error(ctrl, &e),
+            },
+            "MDTM" => match self.api.stat(&ftp_path(arg)) {
+                Ok(info) => {
+                    let (y, mo, d, h, mi, s) = civil_time(info.modified_at);
+                    reply(ctrl, 213, &format!("{:04}{:02}{:02}{:02}{:02}{:02}", y, mo, d, h, mi, s))
+                }
+                Err(e) => reply_error(ctrl, &e),
+            },
+            "REST" => match arg.parse::<u64>() {
+                Ok(offset) => {
+                    self.restart_at = offset;
+                    reply(ctrl, 350, &format!("continuando desde {}", offset))
+                }
+                Err(_) => reply(ctrl, 501, "offset invalido"),
+            },
+            "RETR" => {
+                let offset = std::mem::take(&mut self.restart_at);
+                match self.api.read_at(&ftp_path(arg), offset, u64::MAX) {
+                    Ok(data) => self.send_data(ctrl, &data),
+                    Err(e) => reply_error(ctrl, &e),
+                }
+            }
+            "STOR" | "APPE" => {
+                let path = ftp_path(arg);
+                let offset = std::mem::take(&mut self.restart_at);
+                let data = match self.recv_data(ctrl)? {
+                    Some(d) => d,
+                    None => return Ok(()),
+                };
+
+                let result = if cmd == "STOR" && offset == 0 {
+                    self.api.write(&path, &data).map(|_| ())
+                } else {
+                    // APPE o STOR despues de REST: escribir sin borrar lo anterior
+                    let start = match self.api.stat(&path) {
+                        Ok(info) if cmd == "APPE" => info.size,
+                        Ok(_) => offset,
+                        Err(_) => match self.api.create(&path, 0o644) {
+                            Ok(_) => offset,
+                            Err(e) => return reply_error(ctrl, &e),
+                        },
+                    };
+                    self.api
+                        .write_at(&path, start, &data)
+                        .and_then(|_| self.api.flush())
+                };
+
+                match result {
+                    Ok(()) => reply(ctrl, 226, &format!("{} bytes guardados en qrfs", data.len())),
+                    Err(e) => reply_error(ctrl, &e),
+                }
+            }
+            "DELE" => match self.api.remove(&ftp_path(arg)) {
+                Ok(()) => reply(ctrl, 250, "archivo borrado"),
+                Err(e) => reply_error(ctrl, &e),
+            },
+            "RNFR" => match self.api.stat(&ftp_path(arg)) {
+                Ok(_) => {
+                    self.rename_from = Some(ftp_path(arg));
+                    reply(ctrl, 350, "listo para RNTO")
+                }
+                Err(e) => reply_error(ctrl, &e),
+            },
+            "RNTO" => {
+                let from = match self.rename_from.take() {
+                    Some(f) => f,
+                    None => return reply(ctrl, 503, "use RNFR primero"),
+                };
+                match self.api.rename(&from, &ftp_path(arg)) {
+                    Ok(()) => reply(ctrl, 250, "renombrado"),
+                    Err(e) => reply_error(ctrl, &e),
+                }
+            }
+            _ => reply(ctrl, 502, "comando no soportado"),
+        }
+    }
+
+    // acepta la conexion de datos pasiva abierta con PASV/EPSV
+    fn accept_data(&mut self, ctrl: &mut TcpStream) -> io::Result<Option<TcpStream>> {
+        let listener = match self.passive.take() {
+            Some(l) => l,
+            None => {
+                reply(ctrl, 425, "use PASV o EPSV primero")?;
+                return Ok(None);
+            }
+        };
+
+        // accept con limite de tiempo para no colgar la sesion
+        listener.set_nonblocking(true)?;
+        let deadline = std::time::Instant::now() + DATA_TIMEOUT;
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(false)?;
+                    return Ok(Some(stream));
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    if std::time::Instant::now() > deadline {
+                        reply(ctrl, 425, "el cliente no abrio la conexion de datos")?;
+                        return Ok(None);
+                    }
+                    thread::sleep(Duration::from_millis(20));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn send_data(&mut self, ctrl: &mut TcpStream, data: &[u8]) -> io::Result<()> {
+        reply(ctrl, 150, "abriendo conexion de datos")?;
+        let Some(mut stream) = self.accept_data(ctrl)? else {
+            return Ok(());
+        };
+        stream.write_all(data)?;
+        drop(stream);
+        reply(ctrl, 226, "transferencia completa")
+    }
+
+    fn recv_data(&mut self, ctrl: &mut TcpStream) -> io::Result<Option<Vec<u8>>> {
+        reply(ctrl, 150, "abriendo conexion de datos")?;
+        let Some(mut stream) = self.accept_data(ctrl)? else {
+            return Ok(None);
+        };
+        let mut data = Vec::new();
+        stream.read_to_end(&mut data)?;
+        Ok(Some(data))
+    }
+}
+
+fn reply(ctrl: &mut TcpStream, code: u16, msg: &str) -> io::Result<()> {
+    ctrl.write_all(format!("{} {}\r\n", code, msg).as_bytes())
+}
+
+fn reply_error(ctrl: &mut TcpStream, e: &QrfsError) -> io::Result<()> {
+    let code = match e {
+        QrfsError::NoSpace(_) => 552,
+        QrfsError::NotFound(_) | QrfsError::AlreadyExists(_) => 550,
+        _ => 451,
+    };
+    reply(ctrl, code, &e.to_string())
+}
+
+// los clientes mandan "archivo", "/archivo" o "./archivo"
+fn ftp_path(arg: &str) -> String {
+    format!("/{}", arg.trim_start_matches("./").trim_start_matches('/'))
+}
+
+// una linea estilo "ls -l", la que entienden todos los clientes
+fn list_line(info: &FileInfo) -> String {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let (_, mo, d, h, mi, _) = civil_time(info.modified_at);
+    let perms = if info.is_dir() { "drwxr-xr-x" } else { "-rw-r--r--" };
+    format!(
+        "{} 1 qrfs qrfs {:>10} {} {:>2} {:02}:{:02} {}",
+        perms,
+        info.size,
+        MONTHS[(mo - 1) as usize],
+        d,
+        h,
+        mi,
+        info.name
+    )
+}
+
+// segundos unix -> (anio, mes, dia, hora, minuto, segundo) en utc
+fn civil_time(secs: u64) -> (i64, u32, u32, u32, u32, u32) {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // algoritmo de howard hinnant (days_from_civil inverso)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (
+        year,
+        month,
+        day,
+        (rem / 3600) as u32,
+        ((rem % 3600) / 60) as u32,
+        (rem % 60) as u32,
+    )
+}
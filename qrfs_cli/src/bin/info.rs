@@ -0,0 +1,94 @@
+// info - resumen de un disco qrfs sin montarlo
+// info.qrfs <qr_folder|disco.img> [--fragmentation] [--config qrfs.toml]
+// con --fragmentation muestra como esta repartido el espacio libre y que
+// archivos estan partidos (para saber si vale la pena desfragmentar)
+
+use std::env;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+
+use qrfs_core::api::{FragmentationReport, QrfsApi};
+use qrfs_core::codec::{codec_name, DEFAULT_CODEC};
+use qrfs_core::config::{config_arg, DiskProfile, QrfsConfig};
+use qrfs_core::errors::QrfsError;
+use qrfs_core::storage::{BlockStorage, ContainerStorage};
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("info.qrfs: error: {e}");
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), QrfsError> {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 2 {
+        eprintln!("Uso: info.qrfs <qr_folder|disco.img> [--fragmentation] [--config qrfs.toml]");
+        return Ok(());
+    }
+
+    let disk = &args[1];
+    let config = QrfsConfig::for_disk(disk, config_arg(&args))?;
+    let fragmentation = args[2..].iter().any(|a| a == "--fragmentation");
+
+    if Path::new(disk).is_file() {
+        show(ContainerStorage::open_disk(disk)?, fragmentation)
+    } else {
+        show(config.qr_storage(disk), fragmentation)
+    }
+}
+
+fn show<B: BlockStorage + 'static>(storage: B, fragmentation: bool) -> Result<(), QrfsError> {
+    let api = QrfsApi::open(Arc::new(storage))?;
+    let usage = api.usage();
+
+    if let Some(label) = api.label() {
+        println!("Rótulo:         {} (se ve como \"{}\")", label.label, label.display_name());
+        println!("UUID:           {}", label.uuid_string());
+    }
+    if let Some(profile) = DiskProfile::from_id(api.superblock().profile) {
+        println!("Plantilla:      {}", profile.name());
+    }
+    if api.superblock().codec != DEFAULT_CODEC {
+        println!("Codec:          {}", codec_name(api.superblock().codec));
+    }
+    if api.superblock().deterministic {
+        println!("Determinista:   hora fija en los inodos (SOURCE_DATE_EPOCH), uuid de QRFS_SEED");
+    }
+    println!("Bloques:        {} de {} bytes", usage.total_blocks, usage.block_size);
+    println!("Bloques libres: {} de {} de datos", usage.free_blocks, usage.data_blocks);
+    println!("Inodos libres:  {} de {}", usage.free_inodes, usage.total_inodes);
+    if usage.pinned_blocks > 0 {
+        println!(
+            "Zona fija:      {} libres de {}",
+            usage.free_pinned_blocks, usage.pinned_blocks
+        );
+    }
+
+    if fragmentation {
+        print_fragmentation(&api.fragmentation()?);
+    }
+    Ok(())
+}
+
+fn print_fragmentation(report: &FragmentationReport) {
+    println!();
+    println!(
+        "Espacio libre:  {} bloques en {} tramos (el mayor: {} bloques)",
+        report.free_blocks, report.free_extents, report.largest_free_extent
+    );
+    for &(size, count) in &report.free_extent_histogram {
+        println!("  hasta {:>6} bloques: {}", size, count);
+    }
+
+    println!();
+    println!("{:>6} {:>7} {:>6}  archivo", "indice", "bloques", "tramos");
+    for file in &report.files {
+        println!(
+            "{:>6.2} {:>7} {:>6}  {}",
+            file.score, file.blocks, file.extents, file.name
+        );
+    }
+}
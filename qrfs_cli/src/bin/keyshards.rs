@@ -0,0 +1,98 @@
+// keyshards - reparte la clave maestra de un disco cifrado en tarjetas qr
+// keyshards.qrfs <qr_folder|disco.img> --split K/N --out <carpeta> [--config qrfs.toml]
+// escribe N imagenes (fragmento_X_de_N.png) de las que hacen falta K para
+// recuperar el disco sin la frase: mount.qrfs --key-shards o fsck.qrfs
+// --key-shards (que pone una frase nueva). van impresas y guardadas aparte de
+// los qr de datos y de las otras tarjetas
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::config::{config_arg, QrfsConfig};
+use qrfs_core::errors::QrfsError;
+use qrfs_core::keyring::read_passphrase;
+use qrfs_core::shamir::shard_image;
+use qrfs_core::storage::{BlockStorage, ContainerStorage};
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("keyshards.qrfs: error: {e}");
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), QrfsError> {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 2 {
+        eprintln!("Uso: keyshards.qrfs <qr_folder|disco.img> --split K/N --out <carpeta> [--config qrfs.toml]");
+        eprintln!("     con K de las N tarjetas se recupera el disco (mount.qrfs/fsck.qrfs --key-shards)");
+        return Ok(());
+    }
+
+    let disk = &args[1];
+    let config = QrfsConfig::for_disk(disk, config_arg(&args))?;
+    let mut split = None;
+    let mut out = None;
+    let mut i = 2;
+    while i < args.len() {
+        if args[i] == "--split" && i + 1 < args.len() {
+            split = Some(parse_split(&args[i + 1])?);
+            i += 1;
+        } else if args[i] == "--out" && i + 1 < args.len() {
+            out = Some(args[i + 1].clone());
+            i += 1;
+        }
+        i += 1;
+    }
+    let (threshold, count) =
+        split.ok_or_else(|| QrfsError::Other("falta --split K/N (ej. --split 3/5)".into()))?;
+    let out = out.ok_or_else(|| QrfsError::Other("falta --out <carpeta>".into()))?;
+    // las tarjetas no pueden quedar mezcladas con los qr del disco
+    if fs::canonicalize(&out).ok() == fs::canonicalize(disk).ok() {
+        return Err(QrfsError::Other("--out tiene que ser una carpeta aparte del disco".into()));
+    }
+
+    if Path::new(disk).is_file() {
+        write_shards(ContainerStorage::open_disk(disk)?, threshold, count, &out)
+    } else {
+        write_shards(config.qr_storage(disk), threshold, count, &out)
+    }
+}
+
+// "3/5" -> (3, 5)
+fn parse_split(text: &str) -> Result<(u8, u8), QrfsError> {
+    let invalid = || QrfsError::Other(format!("reparto invalido '{}' (se espera K/N, ej. 3/5)", text));
+    let (k, n) = text.split_once('/').ok_or_else(invalid)?;
+    Ok((k.parse().map_err(|_| invalid())?, n.parse().map_err(|_| invalid())?))
+}
+
+fn write_shards<B: BlockStorage + 'static>(
+    storage: B,
+    threshold: u8,
+    count: u8,
+    out: &str,
+) -> Result<(), QrfsError> {
+    let api = QrfsApi::open(Arc::new(storage))?;
+    let passphrase = read_passphrase("keyshards.qrfs: frase del disco: ")?;
+    let shards = api.key_shards(&passphrase, threshold, count)?;
+
+    fs::create_dir_all(out)?;
+    for shard in &shards {
+        let path = Path::new(out).join(format!("fragmento_{}_de_{}.png", shard.x, count));
+        shard_image(shard)?
+            .save(&path)
+            .map_err(|e| QrfsError::Other(format!("no se pudo guardar '{}': {}", path.display(), e)))?;
+        println!("keyshards.qrfs: {}", path.display());
+    }
+    println!(
+        "keyshards.qrfs: {} tarjetas, hacen falta {} para recuperar la clave",
+        count, threshold
+    );
+    println!("keyshards.qrfs: despues de un rekey estas tarjetas dejan de servir");
+    Ok(())
+}
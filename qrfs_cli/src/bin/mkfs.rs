@@ -1,10 +1,21 @@
 use std::env;
+use std::io::{self, BufRead};
 use std::process;
+use std::sync::Arc;
 
-use qrfs_core::disk::Superblock;
+use qrfs_core::api::QrfsApi;
+use qrfs_core::codec::{codec_name, parse_codec, DEFAULT_CODEC};
+use qrfs_core::config::{
+    config_arg, parse_ec_level, DiskNeeds, DiskProfile, QrfsConfig, Redundancy, CONFIG_FILE,
+};
+use qrfs_core::keyring::read_passphrase;
+use qrfs_core::disk::{check_label, Superblock};
 use qrfs_core::errors::QrfsError;
-use qrfs_core::fs_format::{create_empty_bitmap, create_inode_table, serialize_superblock};
-use qrfs_core::storage::{BlockStorage, QrStorageManager};
+use qrfs_core::estimate::{parse_size, QRS_PER_PAGE};
+use qrfs_core::fs_format::format_storage;
+use qrfs_core::mirror::MirrorStorage;
+use qrfs_core::storage::{BlockStorage, ContainerStorage, QrStorageManager};
+use qrfs_core::volume::{default_paths, split_volumes, VolumeStorage};
 
 fn main() {
     if let Err(e) = run() {
@@ -16,89 +27,415 @@ fn main() {
 fn run() -> Result<(), QrfsError> {
     let args: Vec<String> = env::args().collect();
 
-    // sintaxis: mkfs.qrfs <qrfolder> [--blocks n]
+    // sintaxis: mkfs.qrfs <qrfolder> [--interactive] [--profile archive|transfer|tiny] [--blocks n] [--block-size n] [--inodes n] [--qr-size px] [--ec-level L|M|Q|H] [--container] [--shard n] [--cas] [--pack-tails] [--pinned n] [--mirror espejo] [--volumes n] [--label rotulo] [--carrier portadas/] [--encrypt] [--deterministic] [--codec nombre] [--config f]
     if args.len() < 2 {
-        eprintln!("Uso: mkfs.qrfs <qrfolder/> [--blocks N]");
+        eprintln!("Uso: mkfs.qrfs <qrfolder/|disco.img> [--interactive] [--profile archive|transfer|tiny] [--blocks N] [--block-size N] [--inodes N] [--qr-size PX] [--ec-level L|M|Q|H] [--container] [--shard N] [--cas] [--pack-tails] [--pinned N] [--mirror <espejo>] [--volumes N] [--label <rotulo>] [--carrier portadas/] [--encrypt] [--deterministic] [--codec qr-v1|qr-v2] [--config qrfs.toml]");
         return Ok(());
     }
 
     let qr_folder = &args[1];
-    let mut total_blocks = 400; // valor por defecto seguro
+    // perfil: --config o el qrfs.toml de la carpeta, despues la plantilla de
+    // --profile (o lo que sale de --interactive) y al final las banderas
+    // sueltas, que pisan a las dos
+    let mut config = QrfsConfig::for_disk(qr_folder, config_arg(&args))?;
+    let mut profile = match args.iter().position(|a| a == "--profile") {
+        Some(i) => {
+            let name = args.get(i + 1).ok_or_else(|| QrfsError::Other("--profile espera un nombre".into()))?;
+            Some(DiskProfile::parse(name)?)
+        }
+        None => None,
+    };
+    let mut needs = None;
+    if args.iter().any(|a| a == "--interactive") {
+        let answers = wizard()?;
+        profile = Some(config.plan_for(&answers));
+        needs = Some(answers);
+    } else if let Some(profile) = profile {
+        config.apply_profile(profile);
+    }
+    let mut total_blocks = config.total_blocks;
+    let mut container = config.container; // un solo archivo con los bloques crudos, sin qr
+    let mut shard_size = config.shard_size; // pngs por subcarpeta (0 = todos juntos)
+    let mut content_addressed = config.content_addressed; // bloques de datos nombrados por digest
+    let mut tail_packing = config.pack_tails; // colas de archivos chicos en bloques compartidos
+    let mut pinned_blocks = config.pinned_blocks; // zona fija al principio del area de datos
+    let mut carrier: Option<String> = None; // carpeta de portadas (modo portador, feature stego)
+    let mut mirror: Option<String> = None; // segundo disco con los mismos bloques (ver mirror.rs)
+    let mut encrypt = false; // clave maestra con frase: cada archivo cifrado con su clave
+    let mut deterministic = false; // mismos qr en cada corrida (ver Superblock::deterministic)
+    let mut codec = DEFAULT_CODEC; // como se escribe cada bloque en su imagen (ver codec.rs)
+    let mut volumes = 1; // carpetas en las que se reparte el disco (ver volume.rs)
+    let mut label: Option<String> = None; // nombre que muestran los administradores de archivos
 
     // parseo manual de argumentos opcionales
     let mut i = 2;
     while i < args.len() {
-        match args[i].as_str() {
-            "--blocks" => {
-                if i + 1 < args.len() {
-                    if let Ok(n) = args[i + 1].parse::<u32>() {
-                        total_blocks = n;
-                    }
-                    i += 1;
-                }
+        if args[i] == "--blocks" && i + 1 < args.len() {
+            if let Ok(n) = args[i + 1].parse::<u32>() {
+                total_blocks = n;
+            }
+            i += 1;
+        } else if args[i] == "--block-size" && i + 1 < args.len() {
+            if let Ok(n) = args[i + 1].parse::<usize>() {
+                config.block_size = n;
+            }
+            i += 1;
+        } else if args[i] == "--inodes" && i + 1 < args.len() {
+            if let Ok(n) = args[i + 1].parse::<u32>() {
+                config.inode_count = n;
+            }
+            i += 1;
+        } else if args[i] == "--qr-size" && i + 1 < args.len() {
+            if let Ok(n) = args[i + 1].parse::<u32>() {
+                config.qr_size = n;
             }
-            _ => {}
+            i += 1;
+        } else if args[i] == "--ec-level" && i + 1 < args.len() {
+            config.qr_ec_level = parse_ec_level(&args[i + 1]).map_err(QrfsError::Other)?;
+            i += 1;
+        } else if args[i] == "--shard" && i + 1 < args.len() {
+            if let Ok(n) = args[i + 1].parse::<u32>() {
+                shard_size = n;
+            }
+            i += 1;
+        } else if args[i] == "--container" {
+            container = true;
+        } else if args[i] == "--cas" {
+            content_addressed = true;
+        } else if args[i] == "--pack-tails" {
+            tail_packing = true;
+        } else if args[i] == "--pinned" && i + 1 < args.len() {
+            if let Ok(n) = args[i + 1].parse::<u32>() {
+                pinned_blocks = n;
+            }
+            i += 1;
+        } else if args[i] == "--encrypt" {
+            encrypt = true;
+        } else if args[i] == "--deterministic" {
+            deterministic = true;
+        } else if args[i] == "--codec" && i + 1 < args.len() {
+            codec = parse_codec(&args[i + 1])?;
+            i += 1;
+        } else if args[i] == "--carrier" && i + 1 < args.len() {
+            carrier = Some(args[i + 1].clone());
+            i += 1;
+        } else if args[i] == "--label" && i + 1 < args.len() {
+            label = Some(args[i + 1].clone());
+            i += 1;
+        } else if args[i] == "--volumes" && i + 1 < args.len() {
+            if let Ok(n) = args[i + 1].parse::<u32>() {
+                volumes = n;
+            }
+            i += 1;
+        } else if args[i] == "--mirror" && i + 1 < args.len() {
+            mirror = Some(args[i + 1].clone());
+            i += 1;
         }
         i += 1;
     }
 
-    let inode_count = 64; // cantidad fija de archivos soportados
+    let inode_count = config.inode_count; // inodos de la tabla fija
+    if config.qr_size < 21 {
+        return Err(QrfsError::Other(format!("--qr-size {} es menor que un qr (21 px)", config.qr_size)));
+    }
+    // el tamaño de bloque de las banderas se revisa igual que el del perfil
+    config.container = container;
+    config.total_blocks = total_blocks;
+    config.validate()?;
 
     // crear e inicializar superblock
-    let superblock = Superblock::new(total_blocks, inode_count);
+    let mut superblock = Superblock::with_block_size(total_blocks, inode_count, config.block_size as u32);
+    // en un contenedor no hay pngs que repartir; las imagenes portadoras no se
+    // reparten ni se nombran por contenido
+    if !container && carrier.is_none() {
+        superblock.shard_size = shard_size;
+        superblock.content_addressed = content_addressed;
+    }
+    superblock.tail_packing = tail_packing;
+    superblock.pinned_blocks = pinned_blocks;
+    superblock.profile = profile.map_or(0, DiskProfile::id);
+    // las claves de los archivos cifrados se sortean siempre
+    if deterministic && encrypt {
+        return Err(QrfsError::Unimplemented("--deterministic no se combina con --encrypt".into()));
+    }
+    superblock.deterministic = deterministic;
+    // un contenedor guarda los bytes crudos y las portadas tienen su forma
+    if codec != DEFAULT_CODEC && (container || carrier.is_some()) {
+        return Err(QrfsError::Unimplemented("--codec no se combina con --container ni --carrier".into()));
+    }
+    superblock.codec = codec;
     if !superblock.is_valid() {
         return Err(QrfsError::Other("Error interno creando superblock".into()));
     }
+    let data_blocks = superblock.total_blocks.saturating_sub(superblock.data_block_start);
+    if pinned_blocks > data_blocks {
+        return Err(QrfsError::Other(format!(
+            "la zona fija ({} bloques) no entra en los {} bloques de datos",
+            pinned_blocks, data_blocks
+        )));
+    }
+    // cada volumen es una carpeta de qr con un rango fijo de ids: nada de
+    // contenedores, portadas, espejos ni bloques nombrados por digest
+    if volumes > 1 {
+        if container || carrier.is_some() || mirror.is_some() || superblock.content_addressed {
+            return Err(QrfsError::Unimplemented(
+                "--volumes no se combina con --container, --carrier, --mirror ni --cas".into(),
+            ));
+        }
+        split_volumes(&mut superblock, volumes)?;
+    }
+
+    if let Some(label) = &label {
+        check_label(label)?;
+    }
+
+    // la frase se pide antes de escribir nada
+    let passphrase = if encrypt {
+        Some(read_passphrase("mkfs.qrfs: frase del disco: ")?)
+    } else {
+        None
+    };
+    let finish = Finish {
+        passphrase: passphrase.as_deref(),
+        label: label.as_deref(),
+    };
 
     println!("mkfs.qrfs: Creando sistema de archivos en '{}'...", qr_folder);
+    if let Some(profile) = profile {
+        println!("  - Plantilla: {}", profile.name());
+    }
     println!("  - Bloques Totales: {}", total_blocks);
     println!("  - Inodos Máximos:  {}", inode_count);
+    if superblock.shard_size > 0 {
+        println!("  - Subcarpetas de {} bloques", superblock.shard_size);
+    }
+    if superblock.content_addressed {
+        println!("  - Bloques de datos direccionados por contenido");
+    }
+    if superblock.tail_packing {
+        println!("  - Colas de archivos chicos empaquetadas en bloques compartidos");
+    }
+    if superblock.pinned_blocks > 0 {
+        println!(
+            "  - Zona fija: bloques {} a {} (raiz, tabla de inodos y archivos fijos)",
+            superblock.data_block_start,
+            superblock.data_block_start + superblock.pinned_blocks - 1
+        );
+    }
+    if encrypt {
+        println!("  - Archivos cifrados, cada uno con su clave");
+    }
+    if codec != DEFAULT_CODEC {
+        println!("  - Codec: {}", codec_name(codec));
+    }
+    if deterministic {
+        println!("  - Determinista: hora fija en los inodos, uuid e ids de la semilla");
+    }
+    if let Some(label) = &label {
+        println!("  - Rótulo: {}", label);
+    }
+    let volume_paths = default_paths(qr_folder, superblock.volumes().len());
+    if superblock.volume_set.is_some() {
+        println!("  - Repartido en {} volumenes:", superblock.volumes().len());
+        for (n, (volume, path)) in superblock.volumes().iter().zip(&volume_paths).enumerate() {
+            println!(
+                "      {}: '{}' (bloques {} a {})",
+                n + 1,
+                path.display(),
+                volume.first,
+                volume.first + volume.count - 1
+            );
+        }
+    }
+    if let Some(path) = &mirror {
+        if carrier.is_some() {
+            return Err(QrfsError::Unimplemented("--mirror no sirve en modo portador".into()));
+        }
+        println!("  - Espejo en '{}'", path);
+    }
+    if let Some(needs) = needs {
+        if needs.printed {
+            println!(
+                "  - Hojas para imprimir: {} ({} qr por hoja)",
+                superblock.total_blocks.div_ceil(QRS_PER_PAGE),
+                QRS_PER_PAGE
+            );
+        } else {
+            println!("  - Qr para pasar por pantalla: {}", superblock.total_blocks);
+        }
+        if ask("¿Formatear? [s/N]")? != "s" {
+            println!("mkfs.qrfs: cancelado, no se toco '{}'", qr_folder);
+            return Ok(());
+        }
+    }
 
     let block_size = superblock.block_size as usize;
-    let storage = QrStorageManager::new(qr_folder, block_size, superblock.total_blocks);
-
-    // inicializar disco fisico (imagenes vacias)
-    storage.init_empty_blocks()?;
-
-    // escribir superblock (bloque 0 - la "firma" del inicio)
-    let sb_bytes = serialize_superblock(&superblock)?;
-    let mut sb_block = vec![0u8; block_size];
-    sb_block[..sb_bytes.len()].copy_from_slice(&sb_bytes);
-    storage.write_block(0, &sb_block)?;
-
-    // escribir bitmap
-    let mut bitmap = create_empty_bitmap(superblock.total_blocks);
-    // marcar bloques reservados como usados
-    for blk in 0..superblock.data_block_start {
-        let byte = (blk / 8) as usize;
-        let bit = (blk % 8) as u8;
-        if byte < bitmap.len() { bitmap[byte] |= 1 << bit; }
-    }
-    
-    let mut offset = 0;
-    for i in 0..superblock.free_map_blocks {
-        let mut blk_buf = vec![0u8; block_size];
-        let end = usize::min(offset + block_size, bitmap.len());
-        let len = end - offset;
-        if len > 0 { blk_buf[..len].copy_from_slice(&bitmap[offset..end]); }
-        storage.write_block(superblock.free_map_start + i, &blk_buf)?;
-        offset += block_size;
-    }
-
-    // escribir tabla de inodos
-    let inode_table_raw = create_inode_table(superblock.inode_count)?;
-    let mut offset = 0;
-    for i in 0..superblock.inode_table_blocks {
-        let mut blk_buf = vec![0u8; block_size];
-        if offset < inode_table_raw.len() {
-            let end = usize::min(offset + block_size, inode_table_raw.len());
-            let len = end - offset;
-            if len > 0 { blk_buf[..len].copy_from_slice(&inode_table_raw[offset..end]); }
+    if let Some(covers) = &carrier {
+        println!("  - Bloques escondidos en las portadas de '{}'", covers);
+        format_carrier(qr_folder, covers, &superblock, &finish)?;
+    } else if superblock.volume_set.is_some() {
+        let storage = VolumeStorage::create(&superblock, &volume_paths, |s| {
+            s.with_qr_options(config.qr_size, config.qr_ec_level)
+        })?;
+        // cada bloque en cero se borra de la carpeta de su volumen
+        let empty = vec![0u8; block_size];
+        for id in 0..superblock.total_blocks {
+            storage.write_block(id, &empty)?;
+        }
+        format_disk(storage, &superblock, &finish)?;
+    } else if container {
+        // el archivo se crea lleno de ceros, solo falta la metadata
+        let storage = ContainerStorage::create(qr_folder, block_size, superblock.total_blocks)?;
+        match &mirror {
+            Some(path) => {
+                let copy = ContainerStorage::create(path, block_size, superblock.total_blocks)?;
+                format_disk(MirrorStorage::new(storage, copy), &superblock, &finish)?;
+            }
+            None => format_disk(storage, &superblock, &finish)?,
+        }
+    } else {
+        let storage = QrStorageManager::new(qr_folder, block_size, superblock.total_blocks)
+            .with_shard_size(superblock.shard_size)
+            .with_codec(superblock.codec)
+            .with_qr_options(config.qr_size, config.qr_ec_level);
+
+        // inicializar disco fisico (imagenes vacias)
+        storage.init_empty_blocks()?;
+
+        match &mirror {
+            Some(path) => {
+                let copy = QrStorageManager::new(path, block_size, superblock.total_blocks)
+                    .with_shard_size(superblock.shard_size)
+                    .with_codec(superblock.codec)
+                    .with_qr_options(config.qr_size, config.qr_ec_level);
+                copy.init_empty_blocks()?;
+                format_disk(MirrorStorage::new(storage, copy), &superblock, &finish)?;
+            }
+            None => format_disk(storage, &superblock, &finish)?,
         }
-        storage.write_block(superblock.inode_table_start + i, &blk_buf)?;
-        offset += block_size;
+    }
+
+    if !container && carrier.is_none() {
+        keep_qr_options(qr_folder, &config, profile)?;
     }
 
     println!("mkfs.qrfs: ¡Éxito! Sistema de archivos creado.");
     Ok(())
-}
\ No newline at end of file
+}
+
+// mkfs --interactive: pregunta por stderr y lee de la entrada estandar,
+// repitiendo la pregunta hasta que la respuesta sirva
+fn wizard() -> Result<DiskNeeds, QrfsError> {
+    let data_bytes = loop {
+        match parse_size(&ask("¿Cuantos datos vas a guardar? (ej. 500, 20K, 1M)")?) {
+            Some(n) => break n,
+            None => eprintln!("mkfs.qrfs: un numero de bytes, con K o M si hace falta"),
+        }
+    };
+    let printed = loop {
+        match ask("¿Los qr se imprimen o se pasan de pantalla a camara? [imprimir/pantalla]")?.as_str() {
+            "i" | "imprimir" => break true,
+            "p" | "pantalla" => break false,
+            _ => eprintln!("mkfs.qrfs: imprimir o pantalla"),
+        }
+    };
+    let redundancy = loop {
+        match ask("¿Cuanta redundancia? [baja/media/alta]")?.as_str() {
+            "b" | "baja" => break Redundancy::Low,
+            "m" | "media" => break Redundancy::Normal,
+            "a" | "alta" => break Redundancy::High,
+            _ => eprintln!("mkfs.qrfs: baja, media o alta"),
+        }
+    };
+    Ok(DiskNeeds { data_bytes, printed, redundancy })
+}
+
+fn ask(question: &str) -> Result<String, QrfsError> {
+    eprint!("{} ", question);
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        return Err(QrfsError::Other("se termino la entrada antes de contestar".into()));
+    }
+    Ok(line.trim().to_lowercase())
+}
+
+// los qr que escriba mount despues salen con el qrfs.toml de la carpeta: si
+// no hay uno y la plantilla o las banderas cambiaron tamaño o correccion, se
+// deja uno con esa seccion [qr]
+fn keep_qr_options(qr_folder: &str, config: &QrfsConfig, profile: Option<DiskProfile>) -> Result<(), QrfsError> {
+    let path = std::path::Path::new(qr_folder).join(CONFIG_FILE);
+    let later = QrfsConfig::for_disk(qr_folder, None)?;
+    if path.exists() || (later.qr_size, later.qr_ec_level) == (config.qr_size, config.qr_ec_level) {
+        return Ok(());
+    }
+    let header = match profile {
+        Some(profile) => format!("# escrito por mkfs.qrfs --profile {}\n", profile.name()),
+        None => "# escrito por mkfs.qrfs\n".to_string(),
+    };
+    std::fs::write(&path, header + &config.qr_section())
+        .map_err(|e| QrfsError::Other(format!("no se pudo escribir '{}': {}", path.display(), e)))?;
+    println!("  - Opciones de los qr en '{}'", path.display());
+    Ok(())
+}
+
+// lo que se agrega despues de formatear, con el filesystem ya abierto
+struct Finish<'a> {
+    passphrase: Option<&'a str>,
+    label: Option<&'a str>,
+}
+
+// superblock, bitmap y tabla de inodos, y despues la clave y el rotulo
+fn format_disk<B: BlockStorage + 'static>(
+    storage: B,
+    superblock: &Superblock,
+    finish: &Finish,
+) -> Result<(), QrfsError> {
+    format_storage(&storage, superblock)?;
+    finish_disk(storage, finish)
+}
+
+// guarda la clave maestra envuelta con la frase en el superblock recien
+// escrito y rotula el disco
+fn finish_disk<B: BlockStorage + 'static>(storage: B, finish: &Finish) -> Result<(), QrfsError> {
+    if finish.passphrase.is_none() && finish.label.is_none() {
+        return Ok(());
+    }
+    let api = QrfsApi::open(Arc::new(storage))?;
+    if let Some(passphrase) = finish.passphrase {
+        api.enable_encryption(passphrase)?;
+    }
+    if let Some(label) = finish.label {
+        let label = api.set_label(label)?;
+        println!("  - UUID: {}", label.uuid_string());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "stego")]
+fn format_carrier(
+    qr_folder: &str,
+    covers: &str,
+    superblock: &Superblock,
+    finish: &Finish,
+) -> Result<(), QrfsError> {
+    let storage = qrfs_core::stego::CarrierStorage::create(
+        qr_folder,
+        covers,
+        superblock.block_size as usize,
+        superblock.total_blocks,
+    )?;
+    format_storage(&storage, superblock)?;
+    finish_disk(storage, finish)
+}
+
+#[cfg(not(feature = "stego"))]
+fn format_carrier(
+    _qr_folder: &str,
+    _covers: &str,
+    _superblock: &Superblock,
+    _finish: &Finish,
+) -> Result<(), QrfsError> {
+    Err(QrfsError::Unimplemented(
+        "--carrier necesita compilar con --features stego".into(),
+    ))
+}
@@ -1,10 +1,17 @@
 use std::env;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process;
 use std::sync::Arc;
+use qrfs_core::audit::AuditLog;
+use qrfs_core::config::{config_arg, QrfsConfig};
 use qrfs_core::errors::QrfsError;
 use qrfs_core::fs::QrfsFilesystem;
-use qrfs_core::storage::QrStorageManager;
+use qrfs_core::http::{is_remote, HttpStorage};
+use qrfs_core::keyring::PassphraseSource;
+use qrfs_core::mirror::MirrorStorage;
+use qrfs_core::shamir::{key_shards_arg, read_shards};
+use qrfs_core::storage::{BlockStorage, ContainerStorage, DiskLock, CARRIER_FILE};
+use qrfs_core::volume::{probe_volumes, volume_dirs_arg, volume_paths, VolumeStorage};
 
 fn main() {
     if let Err(e) = run() {
@@ -16,31 +23,262 @@ fn main() {
 fn run() -> Result<(), QrfsError> {
     let args: Vec<String> = env::args().collect();
     
-    // sintaxis: mount.qrfs <qrfolder> <mountpoint>
-    if args.len() != 3 {
-        eprintln!("Uso: mount.qrfs <qrfolder/> <mountpoint/>");
+    // sintaxis: mount.qrfs <qrfolder> <mountpoint> [--no-writeback] [--sealed] [--degraded] [--force-shared] [--key-file f] [--keyring] [--key-shards img...] [--audit-log dir] [--mirror espejo] [--volume-dirs carpeta...] [--config f]
+    if args.len() < 3 {
+        eprintln!("Uso: mount.qrfs <qrfolder/|disco.img|http://host/archivo/> <mountpoint/> [--no-writeback] [--sealed] [--degraded] [--force-shared] [--key-file <archivo>] [--keyring] [--key-shards <img>...] [--audit-log <dir>] [--mirror <espejo>] [--volume-dirs <carpeta>...] [--config qrfs.toml]");
+        eprintln!("     un disco cifrado pide la frase (o la toma de --key-file, QRFS_PASSPHRASE o el llavero con --keyring)");
+        eprintln!("     sin la frase, --key-shards abre el disco con las tarjetas de keyshards.qrfs");
+        eprintln!("     un disco repartido en volumenes busca disco.vol2, disco.vol3... o las carpetas de --volume-dirs");
+        eprintln!("     un disco publicado por http (carpeta de qr terminada en / o un .img) se monta en solo lectura");
         return Ok(());
     }
 
     let qrfolder = &args[1];
     let mountpoint = &args[2];
+    let config = QrfsConfig::for_disk(qrfolder, config_arg(&args))?;
+
+    // el montaje es el unico que escribe el disco, la cache del kernel es segura
+    let mut writeback = config.writeback;
+    // archivo sellado: solo lectura y cada bloque verificado contra lo medido al montar
+    let mut sealed = false;
+    // disco a medio escanear: lo que falta da EIO y se lista en /.qrfs_missing
+    let mut degraded = false;
+    // otro proceso (el servidor) escribe el mismo disco: se revalida antes de cada cambio
+    let mut shared = false;
+    // carpeta del registro de auditoria (fuera del disco qr)
+    let mut audit_dir: Option<String> = None;
+    // segundo disco con los mismos bloques: lo ilegible se repara desde ahi
+    let mut mirror: Option<String> = None;
+    let mut i = 3;
+    while i < args.len() {
+        if args[i] == "--no-writeback" {
+            writeback = false;
+        } else if args[i] == "--sealed" {
+            sealed = true;
+        } else if args[i] == "--degraded" {
+            degraded = true;
+        } else if args[i] == "--force-shared" {
+            shared = true;
+        } else if args[i] == "--audit-log" && i + 1 < args.len() {
+            audit_dir = Some(args[i + 1].clone());
+            i += 1;
+        } else if args[i] == "--mirror" && i + 1 < args.len() {
+            mirror = Some(args[i + 1].clone());
+            i += 1;
+        }
+        i += 1;
+    }
+    // con el disco compartido la cache del kernel puede quedar vieja
+    if shared {
+        writeback = false;
+    }
+    let remote = is_remote(qrfolder);
+    let options = MountFlags {
+        read_only: remote,
+        writeback,
+        shared,
+        sealed,
+        degraded,
+        disk: qrfolder.clone(),
+        passphrase: PassphraseSource::from_args(&args),
+        key_shards: key_shards_arg(&args),
+        audit_dir,
+        dirty_blocks: config.dirty_blocks,
+    };
+
+    #[cfg(target_os = "macos")]
+    check_macfuse()?;
 
     println!("mount.qrfs: Montando '{}' en '{}'...", qrfolder, mountpoint);
 
-    // configuracion estandar (debe coincidir con mkfs)
-    let block_size = 128; 
-    let total_blocks = 400; 
+    // disco publicado en la web: se baja de a bloques y nunca se escribe, no
+    // hay nada que tomar
+    if remote {
+        if mirror.is_some() {
+            return Err(QrfsError::Unimplemented("--mirror no sirve con un disco por http".into()));
+        }
+        return mount_storage(HttpStorage::open_remote(qrfolder)?, mountpoint, &options);
+    }
 
-    // inicializar almacenamiento
-    let storage = QrStorageManager::new(qrfolder, block_size, total_blocks);
-    
+    // nadie mas escribe el disco mientras este montado (salvo --force-shared
+    // en los dos lados); se suelta al terminar el proceso
+    let _lock = DiskLock::acquire(qrfolder, "mount.qrfs", shared)?;
+
+    // inicializar almacenamiento: carpeta de qr o contenedor en un archivo
+    // (la geometria sale del superblock; el perfil solo aporta los qr nuevos)
+    if Path::new(qrfolder).join(CARRIER_FILE).exists() {
+        if mirror.is_some() {
+            return Err(QrfsError::Unimplemented("--mirror no sirve en modo portador".into()));
+        }
+        return mount_carrier(qrfolder, mountpoint, &options);
+    }
+    match (Path::new(qrfolder).is_file(), &mirror) {
+        (true, None) => mount_storage(ContainerStorage::open_disk(qrfolder)?, mountpoint, &options),
+        (false, None) => mount_folder(qrfolder, &args, &config, mountpoint, &options),
+        (true, Some(path)) => {
+            let storage = ContainerStorage::open_disk(qrfolder)?;
+            mount_mirrored(storage, path, &config, mountpoint, &options)
+        }
+        (false, Some(path)) => {
+            if probe_volumes(qrfolder)?.is_some() {
+                return Err(QrfsError::Unimplemented("--mirror no sirve con un disco repartido en volumenes".into()));
+            }
+            mount_mirrored(config.qr_storage(qrfolder), path, &config, mountpoint, &options)
+        }
+    }
+}
+
+// carpeta de qr; un disco repartido en volumenes se arma con todas sus
+// carpetas, que tienen que ser las del juego y estar en orden
+fn mount_folder(
+    qrfolder: &str,
+    args: &[String],
+    config: &QrfsConfig,
+    mountpoint: &str,
+    options: &MountFlags,
+) -> Result<(), QrfsError> {
+    let Some(superblock) = probe_volumes(qrfolder)? else {
+        return mount_storage(config.qr_storage(qrfolder), mountpoint, options);
+    };
+    let paths = volume_paths(qrfolder, &superblock, volume_dirs_arg(args));
+    let storage = VolumeStorage::open(&superblock, &paths, |s| config.configure(s))?;
+    // los demas volumenes tambien quedan tomados
+    let _locks = paths[1..]
+        .iter()
+        .map(|path| DiskLock::acquire(path, "mount.qrfs", options.shared))
+        .collect::<Result<Vec<_>, _>>()?;
+    for (n, path) in paths.iter().enumerate() {
+        println!("mount.qrfs: Volumen {} de {} en '{}'.", n + 1, paths.len(), path.display());
+    }
+    mount_storage(storage, mountpoint, options)
+}
+
+// disco espejado: las lecturas que fallan en el principal salen del espejo y
+// se reescriben (ver mirror.rs); el espejo tambien queda tomado
+fn mount_mirrored<B: BlockStorage + 'static>(
+    storage: B,
+    mirror: &str,
+    config: &QrfsConfig,
+    mountpoint: &str,
+    options: &MountFlags,
+) -> Result<(), QrfsError> {
+    let _lock = DiskLock::acquire(mirror, "mount.qrfs", options.shared)?;
+    println!("mount.qrfs: Espejo en '{}'.", mirror);
+    if Path::new(mirror).is_file() {
+        let copy = ContainerStorage::open_disk(mirror)?;
+        mount_storage(MirrorStorage::new(storage, copy), mountpoint, options)
+    } else {
+        mount_storage(MirrorStorage::new(storage, config.qr_storage(mirror)), mountpoint, options)
+    }
+}
+
+// en macos fuse no viene con el sistema: sin macfuse el montaje falla con un
+// error poco claro, mejor avisar antes
+#[cfg(target_os = "macos")]
+fn check_macfuse() -> Result<(), QrfsError> {
+    if Path::new("/Library/Filesystems/macfuse.fs").exists() {
+        Ok(())
+    } else {
+        Err(QrfsError::Other(
+            "no se encontro macFUSE (/Library/Filesystems/macfuse.fs); instalelo para poder montar"
+                .into(),
+        ))
+    }
+}
+
+// carpeta en modo portador: bloques escondidos en imagenes de portada
+#[cfg(feature = "stego")]
+fn mount_carrier(qrfolder: &str, mountpoint: &str, options: &MountFlags) -> Result<(), QrfsError> {
+    let storage = qrfs_core::stego::CarrierStorage::open(qrfolder)?;
+    mount_storage(storage, mountpoint, options)
+}
+
+#[cfg(not(feature = "stego"))]
+fn mount_carrier(_qrfolder: &str, _mountpoint: &str, _options: &MountFlags) -> Result<(), QrfsError> {
+    Err(QrfsError::Unimplemented(
+        "el disco esta en modo portador; compile con --features stego para montarlo".into(),
+    ))
+}
+
+// opciones de la linea de comandos que afectan al filesystem
+struct MountFlags {
+    // el backend no escribe (http)
+    read_only: bool,
+    writeback: bool,
+    shared: bool,
+    sealed: bool,
+    degraded: bool,
+    // ruta del disco, con la que se busca la frase en el llavero
+    disk: String,
+    // de donde sale la frase si el disco esta cifrado
+    passphrase: PassphraseSource,
+    // tarjetas de la clave maestra (en vez de la frase)
+    key_shards: Vec<PathBuf>,
+    audit_dir: Option<String>,
+    dirty_blocks: usize,
+}
+
+fn mount_storage<B: BlockStorage + 'static>(
+    storage: B,
+    mountpoint: &str,
+    options: &MountFlags,
+) -> Result<(), QrfsError> {
     // inicializar Filesystem (esto lee la firma en el Bloque 0)
-    let fs = QrfsFilesystem::new(Arc::new(storage))?;
+    // si la verificacion rapida encuentra problemas se monta en solo lectura
+    let mut fs = QrfsFilesystem::new(Arc::new(storage))?
+        .with_writeback_cache(options.writeback)
+        .with_max_dirty_blocks(options.dirty_blocks)
+        .with_shared_disk(options.shared)
+        .with_quick_check();
+    if options.read_only {
+        fs = fs.with_read_only(true);
+    }
+    if options.sealed {
+        fs = fs.with_sealed();
+    }
+    if options.degraded {
+        fs = fs.with_degraded();
+    }
+    // disco cifrado: sin la clave maestra los archivos cifrados no se leen
+    if fs.is_encrypted() && !options.key_shards.is_empty() {
+        fs.unlock_with_shards(&read_shards(&options.key_shards)?)?;
+        println!("mount.qrfs: Clave maestra rearmada con {} tarjetas.", options.key_shards.len());
+    } else if fs.is_encrypted() {
+        options
+            .passphrase
+            .unlock(&options.disk, "mount.qrfs: frase del disco: ", |p| fs.unlock(p))?;
+    }
+    if let Some(dir) = &options.audit_dir {
+        let log = AuditLog::open(dir)?;
+        println!("mount.qrfs: Auditoria en '{}'.", log.path().display());
+        fs = fs.with_audit_log(log);
+    }
 
-    println!("mount.qrfs: Sistema listo. Presione Ctrl+C para desmontar.");
+    // borrar los inodos sin nombre y devolver al bitmap los bloques que
+    // quedaron asignados sin dueño
+    if !fs.is_read_only() {
+        let inodes = fs.reclaim_orphan_inodes()?;
+        if !inodes.is_empty() {
+            println!("mount.qrfs: {} inodos huerfanos liberados.", inodes.len());
+        }
+        let orphans = fs.reclaim_orphan_blocks()?;
+        if !orphans.is_empty() {
+            println!("mount.qrfs: {} bloques huerfanos liberados.", orphans.len());
+        }
+    }
+
+    if options.sealed {
+        println!("mount.qrfs: Archivo SELLADO (estado en /.qrfs_status). Presione Ctrl+C para desmontar.");
+    } else if options.degraded {
+        println!("mount.qrfs: Montaje DEGRADADO en solo lectura (faltantes en /.qrfs_missing). Presione Ctrl+C para desmontar.");
+    } else if fs.is_read_only() {
+        println!("mount.qrfs: Sistema listo en SOLO LECTURA. Presione Ctrl+C para desmontar.");
+    } else {
+        println!("mount.qrfs: Sistema listo. Presione Ctrl+C para desmontar.");
+    }
     
     // montar (bloquea la terminal)
     fs.mount(Path::new(mountpoint))?;
 
     Ok(())
-}
\ No newline at end of file
+}
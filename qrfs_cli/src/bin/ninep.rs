@@ -0,0 +1,565 @@
+// ninep - expone un disco qrfs por 9P2000.L sobre tcp
+// sirve para montar donde no hay fuse (vms con virtio-9p/tcp, wsl):
+//   mount -t 9p -o trans=tcp,port=5640,version=9p2000.L <ip> /mnt
+// es un adaptador delgado sobre QrfsApi
+
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::sync::Arc;
+use std::thread;
+
+use qrfs_core::api::{FileInfo, QrfsApi};
+use qrfs_core::errors::QrfsError;
+use qrfs_core::storage::QrStorageManager;
+
+// puerto estandar de 9p
+const DEFAULT_PORT: u16 = 564;
+
+// tamaño maximo de mensaje que negociamos
+const MAX_MSIZE: u32 = 64 * 1024;
+// y el minimo que se acepta en TVERSION: con menos no entra ni un Rread
+const MIN_MSIZE: u32 = 4096;
+
+// cabecera: size[4] type[1] tag[2]
+const HEADER_LEN: usize = 7;
+// Rread y Rreaddir: cabecera mas count[4]
+const IO_HEADER_LEN: u32 = HEADER_LEN as u32 + 4;
+
+// tipos de mensaje 9P2000.L (cada R es T + 1)
+const RLERROR: u8 = 7;
+const TSTATFS: u8 = 8;
+const TLOPEN: u8 = 12;
+const TLCREATE: u8 = 14;
+const TRENAME: u8 = 20;
+const TGETATTR: u8 = 24;
+const TSETATTR: u8 = 26;
+const TXATTRWALK: u8 = 30;
+const TREADDIR: u8 = 40;
+const TFSYNC: u8 = 50;
+const TMKDIR: u8 = 72;
+const TRENAMEAT: u8 = 74;
+const TUNLINKAT: u8 = 76;
+const TVERSION: u8 = 100;
+const TAUTH: u8 = 102;
+const TATTACH: u8 = 104;
+const TFLUSH: u8 = 108;
+const TWALK: u8 = 110;
+const TREAD: u8 = 116;
+const TWRITE: u8 = 118;
+const TCLUNK: u8 = 120;
+const TREMOVE: u8 = 122;
+
+// bits de qid.type y de setattr
+const QTDIR: u8 = 0x80;
+const QTFILE: u8 = 0x00;
+const SETATTR_SIZE: u32 = 0x8;
+const GETATTR_BASIC: u64 = 0x7ff;
+
+// magic de statfs para v9fs
+const V9FS_MAGIC: u32 = 0x0102_1997;
+
+type NineApi = QrfsApi<QrStorageManager>;
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("ninep.qrfs: error: {e}");
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), QrfsError> {
+    let args: Vec<String> = env::args().collect();
+
+    // sintaxis: ninep <qrfolder> [--port n] [--bind addr]
+    if args.len() < 2 {
+        eprintln!("Uso: ninep.qrfs <qrfolder/> [--port N] [--bind ADDR]");
+        return Ok(());
+    }
+
+    let qrfolder = &args[1];
+    let mut port = DEFAULT_PORT;
+    // sin autenticacion: solo esta maquina salvo que se pida otra cosa
+    let mut bind = "127.0.0.1".to_string();
+
+    let mut i = 2;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--port" if i + 1 < args.len() => {
+                port = args[i + 1]
+                    .parse()
+                    .map_err(|_| QrfsError::Other(format!("puerto invalido: {}", args[i + 1])))?;
+                i += 1;
+            }
+            "--bind" if i + 1 < args.len() => {
+                bind = args[i + 1].clone();
+                i += 1;
+            }
+            other => eprintln!("ninep.qrfs: argumento ignorado: {}", other),
+        }
+        i += 1;
+    }
+
+    let sb = QrStorageManager::probe_superblock(qrfolder.as_str())?.ok_or_else(|| {
+        QrfsError::NotFormatted(format!("{} (ejecute mkfs primero)", qrfolder))
+    })?;
+    let storage = QrStorageManager::new(qrfolder, sb.block_size as usize, sb.total_blocks);
+    let api = Arc::new(QrfsApi::open(Arc::new(storage))?);
+
+    let listener = TcpListener::bind((bind.as_str(), port))?;
+    println!("ninep.qrfs: sirviendo '{}' por 9P2000.L en {}:{}", qrfolder, bind, port);
+    println!(
+        "  - linux: mount -t 9p -o trans=tcp,port={},version=9p2000.L <ip> /mnt",
+        port
+    );
+
+    // un hilo por conexion; QrfsApi serializa el acceso al disco
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("ninep.qrfs: conexion fallida: {}", e);
+                continue;
+            }
+        };
+        let api = api.clone();
+        thread::spawn(move || {
+            let peer = stream
+                .peer_addr()
+                .map(|a| a.to_string())
+                .unwrap_or_default();
+            println!("ninep.qrfs: cliente conectado {}", peer);
+            if let Err(e) = Session::new(api).serve(stream) {
+                println!("ninep.qrfs: cliente {} desconectado: {}", peer, e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+// estado de una conexion: fids abiertos -> path en qrfs ("/", "/nombre",
+// "/dir/nombre")
+struct Session {
+    api: Arc<NineApi>,
+    fids: HashMap<u32, String>,
+    msize: u32,
+}
+
+// error de protocolo: se responde con Rlerror(errno)
+struct Errno(i32);
+
+impl From<QrfsError> for Errno {
+    fn from(e: QrfsError) -> Self {
+        Errno(e.errno())
+    }
+}
+
+impl Session {
+    fn new(api: Arc<NineApi>) -> Self {
+        Self {
+            api,
+            fids: HashMap::new(),
+            msize: MAX_MSIZE,
+        }
+    }
+
+    fn serve(mut self, mut stream: TcpStream) -> io::Result<()> {
+        loop {
+            let mut size_buf = [0u8; 4];
+            if let Err(e) = stream.read_exact(&mut size_buf) {
+                // cierre normal del cliente
+                if e.kind() == io::ErrorKind::UnexpectedEof {
+                    return Ok(());
+                }
+                return Err(e);
+            }
+            let size = u32::from_le_bytes(size_buf) as usize;
+            if !(HEADER_LEN..=self.msize as usize).contains(&size) {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "mensaje 9p invalido"));
+            }
+
+            let mut msg = vec![0u8; size - 4];
+            stream.read_exact(&mut msg)?;
+
+            let kind = msg[0];
+            let tag = u16::from_le_bytes([msg[1], msg[2]]);
+            let mut body = Decoder::new(&msg[3..]);
+
+            let (rkind, payload) = match self.handle(kind, &mut body) {
+                Ok(out) => (kind + 1, out.buf),
+                Err(Errno(code)) => {
+                    let mut out = Encoder::new();
+                    out.u32(code as u32);
+                    (RLERROR, out.buf)
+                }
+            };
+
+            let mut reply = Vec::with_capacity(HEADER_LEN + payload.len());
+            reply.extend_from_slice(&((HEADER_LEN + payload.len()) as u32).to_le_bytes());
+            reply.push(rkind);
+            reply.extend_from_slice(&tag.to_le_bytes());
+            reply.extend_from_slice(&payload);
+            stream.write_all(&reply)?;
+        }
+    }
+
+    fn handle(&mut self, kind: u8, d: &mut Decoder) -> Result<Encoder, Errno> {
+        let mut out = Encoder::new();
+        match kind {
+            TVERSION => {
+                let msize = d.u32()?;
+                let version = d.string()?;
+                if msize < MIN_MSIZE {
+                    return Err(Errno(libc::EINVAL));
+                }
+                self.msize = msize.min(MAX_MSIZE);
+                // una nueva version reinicia la sesion
+                self.fids.clear();
+                out.u32(self.msize);
+                out.string(if version == "9P2000.L" { "9P2000.L" } else { "unknown" });
+            }
+            TAUTH => return Err(Errno(libc::EOPNOTSUPP)),
+            TATTACH => {
+                let fid = d.u32()?;
+                let info = self.api.stat("/")?;
+                self.fids.insert(fid, "/".to_string());
+                out.qid(&info);
+            }
+            TFLUSH => {
+                // las peticiones se atienden en orden, no hay nada pendiente
+            }
+            TWALK => {
+                let fid = d.u32()?;
+                let newfid = d.u32()?;
+                let nwname = d.u16()?;
+                let mut path = self.fid(fid)?.clone();
+                let mut qids = Vec::new();
+
+                for i in 0..nwname {
+                    let name = d.string()?;
+                    let next = match name.as_str() {
+                        ".." => parent(&path),
+                        "." => path.clone(),
+                        _ => child(&path, &name)?,
+                    };
+                    match self.api.stat(&next) {
+                        Ok(info) => {
+                            qids.push(info);
+                            path = next;
+                        }
+                        Err(e) => {
+                            if i == 0 {
+                                return Err(e.into());
+                            }
+                            break;
+                        }
+                    }
+                }
+
+                // el newfid solo existe si se recorrio todo el camino
+                if qids.len() == nwname as usize {
+                    self.fids.insert(newfid, path);
+                }
+                out.u16(qids.len() as u16);
+                for info in &qids {
+                    out.qid(info);
+                }
+            }
+            TGETATTR => {
+                let fid = d.u32()?;
+                let info = self.api.stat(self.fid(fid)?)?;
+                out.u64(GETATTR_BASIC);
+                out.qid(&info);
+                out.u32(unix_mode(&info));
+                out.u32(0); // uid
+                out.u32(0); // gid
+                out.u64(if info.is_dir() { 2 } else { 1 });
+                out.u64(0); // rdev
+                out.u64(info.size);
+                out.u64(self.api.usage().block_size as u64);
+                out.u64(info.size.div_ceil(512));
+                for secs in [info.modified_at, info.modified_at, info.modified_at, info.created_at] {
+                    out.u64(secs);
+                    out.u64(0);
+                }
+                out.u64(0); // gen
+                out.u64(0); // data_version
+            }
+            TSETATTR => {
+                let fid = d.u32()?;
+                let valid = d.u32()?;
+                let _mode = d.u32()?;
+                let _uid = d.u32()?;
+                let _gid = d.u32()?;
+                let size = d.u64()?;
+                // los timestamps y permisos se ignoran, como en fuse
+                if valid & SETATTR_SIZE != 0 {
+                    let path = self.fid(fid)?.clone();
+                    self.api.truncate(&path, size)?;
+                }
+            }
+            TSTATFS => {
+                let usage = self.api.usage();
+                out.u32(V9FS_MAGIC);
+                out.u32(usage.block_size);
+                out.u64(usage.data_blocks);
+                out.u64(usage.free_blocks);
+                out.u64(usage.free_blocks);
+                out.u64(usage.total_inodes);
+                out.u64(usage.free_inodes);
+                out.u64(0); // fsid
+                out.u32(255);
+            }
+            TLOPEN => {
+                let fid = d.u32()?;
+                let flags = d.u32()?;
+                let path = self.fid(fid)?.clone();
+                if flags & libc::O_TRUNC as u32 != 0 && path != "/" {
+                    self.api.truncate(&path, 0)?;
+                }
+                let info = self.api.stat(&path)?;
+                out.qid(&info);
+                out.u32(0); // iounit: el cliente usa msize
+            }
+            TLCREATE => {
+                let fid = d.u32()?;
+                let name = d.string()?;
+                let _flags = d.u32()?;
+                let mode = d.u32()?;
+                let path = child(self.fid(fid)?, &name)?;
+                let info = self.api.create(&path, (mode & 0o7777) as u16)?;
+                // el fid del directorio pasa a ser el archivo nuevo
+                self.fids.insert(fid, path);
+                out.qid(&info);
+                out.u32(0);
+            }
+            TMKDIR => {
+                let dfid = d.u32()?;
+                let name = d.string()?;
+                let mode = d.u32()?;
+                let _gid = d.u32()?;
+                let path = child(self.fid(dfid)?, &name)?;
+                let info = self.api.mkdir(&path, (mode & 0o7777) as u16)?;
+                out.qid(&info);
+            }
+            TXATTRWALK => return Err(Errno(libc::EOPNOTSUPP)),
+            TREAD => {
+                let fid = d.u32()?;
+                let offset = d.u64()?;
+                let count = d.u32()?.min(self.msize - IO_HEADER_LEN);
+                let data = self.api.read_at(self.fid(fid)?, offset, count as u64)?;
+                out.u32(data.len() as u32);
+                out.bytes(&data);
+            }
+            TWRITE => {
+                let fid = d.u32()?;
+                let offset = d.u64()?;
+                let count = d.u32()? as usize;
+                let data = d.take(count)?;
+                let path = self.fid(fid)?.clone();
+                let written = self.api.write_at(&path, offset, data)?;
+                out.u32(written as u32);
+            }
+            TREADDIR => {
+                let fid = d.u32()?;
+                let offset = d.u64()?;
+                let count = d.u32()?.min(self.msize - IO_HEADER_LEN) as usize;
+                let path = self.fid(fid)?.clone();
+
+                let mut entries = vec![
+                    (".".to_string(), self.api.stat(&path)?),
+                    ("..".to_string(), self.api.stat(&parent(&path))?),
+                ];
+                for file in self.api.list(&path)? {
+                    entries.push((file.name.clone(), file));
+                }
+
+                // el offset de cada entrada es la posicion de la siguiente
+                let mut dirents = Encoder::new();
+                for (idx, (name, info)) in entries.iter().enumerate().skip(offset as usize) {
+                    let mut entry = Encoder::new();
+                    entry.qid(info);
+                    entry.u64(idx as u64 + 1);
+                    entry.u8(if info.is_dir() { libc::DT_DIR } else { libc::DT_REG });
+                    entry.string(name);
+                    if dirents.buf.len() + entry.buf.len() > count {
+                        break;
+                    }
+                    dirents.bytes(&entry.buf);
+                }
+                out.u32(dirents.buf.len() as u32);
+                out.bytes(&dirents.buf);
+            }
+            TRENAME => {
+                let fid = d.u32()?;
+                let dfid = d.u32()?;
+                let name = d.string()?;
+                let from = self.fid(fid)?.clone();
+                let to = child(self.fid(dfid)?, &name)?;
+                self.rename(&from, &to)?;
+            }
+            TRENAMEAT => {
+                let olddirfid = d.u32()?;
+                let oldname = d.string()?;
+                let newdirfid = d.u32()?;
+                let newname = d.string()?;
+                let from = child(self.fid(olddirfid)?, &oldname)?;
+                let to = child(self.fid(newdirfid)?, &newname)?;
+                self.rename(&from, &to)?;
+            }
+            TUNLINKAT => {
+                let dirfid = d.u32()?;
+                let name = d.string()?;
+                let path = child(self.fid(dirfid)?, &name)?;
+                self.api.remove(&path)?;
+            }
+            TREMOVE => {
+                let fid = d.u32()?;
+                // el fid se libera aunque falle el borrado
+                let path = self.fids.remove(&fid).ok_or(Errno(libc::EBADF))?;
+                if path == "/" {
+                    return Err(Errno(libc::EPERM));
+                }
+                self.api.remove(&path)?;
+            }
+            TFSYNC => {
+                self.api.flush()?;
+            }
+            TCLUNK => {
+                let fid = d.u32()?;
+                self.fids.remove(&fid).ok_or(Errno(libc::EBADF))?;
+                // como close(): lo escrito con TWRITE pasa a qr
+                self.api.flush()?;
+            }
+            _ => return Err(Errno(libc::EOPNOTSUPP)),
+        }
+        Ok(out)
+    }
+
+    fn fid(&self, fid: u32) -> Result<&String, Errno> {
+        self.fids.get(&fid).ok_or(Errno(libc::EBADF))
+    }
+
+    // renombra y actualiza los fids que apuntaban al nombre viejo o a algo
+    // adentro de el
+    fn rename(&mut self, from: &str, to: &str) -> Result<(), Errno> {
+        self.api.rename(from, to)?;
+        for path in self.fids.values_mut() {
+            if path == from {
+                *path = to.to_string();
+            } else if let Some(rest) = path.strip_prefix(from).filter(|rest| rest.starts_with('/')) {
+                *path = format!("{}{}", to, rest);
+            }
+        }
+        Ok(())
+    }
+}
+
+// ruta de un nombre dentro de un directorio; el nombre es un solo componente
+fn child(dir: &str, name: &str) -> Result<String, Errno> {
+    if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+        return Err(Errno(libc::EINVAL));
+    }
+    Ok(format!("{}/{}", dir.trim_end_matches('/'), name))
+}
+
+// directorio que contiene la ruta (la raiz es su propio padre)
+fn parent(path: &str) -> String {
+    match path.trim_end_matches('/').rsplit_once('/') {
+        Some((dir, _)) if !dir.is_empty() => dir.to_string(),
+        _ => "/".to_string(),
+    }
+}
+
+fn unix_mode(info: &FileInfo) -> u32 {
+    if info.is_dir() {
+        libc::S_IFDIR | 0o755
+    } else {
+        libc::S_IFREG | (info.mode as u32 & 0o7777)
+    }
+}
+
+// lectura de campos little-endian del cuerpo de un mensaje
+struct Decoder<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Errno> {
+        if self.pos + n > self.buf.len() {
+            return Err(Errno(libc::EPROTO));
+        }
+        let slice = &self.buf[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(slice)
+    }
+
+    fn u16(&mut self) -> Result<u16, Errno> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, Errno> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, Errno> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, Errno> {
+        let len = self.u16()? as usize;
+        let raw = self.take(len)?;
+        String::from_utf8(raw.to_vec()).map_err(|_| Errno(libc::EINVAL))
+    }
+}
+
+// escritura de campos de respuesta
+struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    fn new() -> Self {
+        Self { buf: Vec::new() }
+    }
+
+    fn u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+
+    fn u16(&mut self, v: u16) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn bytes(&mut self, v: &[u8]) {
+        self.buf.extend_from_slice(v);
+    }
+
+    fn string(&mut self, s: &str) {
+        self.u16(s.len() as u16);
+        self.bytes(s.as_bytes());
+    }
+
+    // qid: type[1] version[4] path[8]; el path es el id de inodo
+    fn qid(&mut self, info: &FileInfo) {
+        self.u8(if info.is_dir() { QTDIR } else { QTFILE });
+        self.u32(info.modified_at as u32);
+        self.u64(info.inode_id as u64);
+    }
+}
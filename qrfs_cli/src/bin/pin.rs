@@ -0,0 +1,79 @@
+// pin - fija archivos al principio de un disco qrfs
+// pin.qrfs <qr_folder|disco.img> <ruta>... [--unpin] [--config qrfs.toml]
+// los bloques de un archivo fijo van a la zona fija (mkfs.qrfs --pinned N),
+// junto a la raiz y la tabla de inodos: son los primeros qr para imprimir y
+// escanear, y evacuate.qrfs no los saca de ahi. con --unpin se sueltan (los
+// bloques quedan donde estan hasta que se reescriban)
+use std::env;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::config::{config_arg, QrfsConfig};
+use qrfs_core::errors::QrfsError;
+use qrfs_core::storage::{BlockStorage, ContainerStorage};
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("pin.qrfs: error: {e}");
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), QrfsError> {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 3 {
+        eprintln!("Uso: pin.qrfs <qr_folder|disco.img> <ruta>... [--unpin] [--config qrfs.toml]");
+        return Ok(());
+    }
+
+    let disk = &args[1];
+    let config = QrfsConfig::for_disk(disk, config_arg(&args))?;
+    let mut paths: Vec<&str> = Vec::new();
+    let mut pinned = true;
+    let mut i = 2;
+    while i < args.len() {
+        if args[i] == "--unpin" {
+            pinned = false;
+        } else if args[i] == "--config" {
+            i += 1;
+        } else {
+            paths.push(&args[i]);
+        }
+        i += 1;
+    }
+    if paths.is_empty() {
+        return Err(QrfsError::Other("falta el archivo a fijar".into()));
+    }
+
+    if Path::new(disk).is_file() {
+        pin(ContainerStorage::open_disk(disk)?, &paths, pinned)
+    } else {
+        pin(config.qr_storage(disk), &paths, pinned)
+    }
+}
+
+fn pin<B: BlockStorage + 'static>(storage: B, paths: &[&str], pinned: bool) -> Result<(), QrfsError> {
+    let api = QrfsApi::open(Arc::new(storage))?;
+    for path in paths {
+        let (moved, left) = api.set_pinned(path, pinned)?;
+        if !pinned {
+            println!("pin.qrfs: {} suelto", path);
+        } else if left > 0 {
+            println!(
+                "pin.qrfs: {} fijo, {} bloques movidos y {} afuera (zona fija llena)",
+                path, moved, left
+            );
+        } else {
+            println!("pin.qrfs: {} fijo, {} bloques movidos", path, moved);
+        }
+    }
+    let usage = api.usage();
+    println!(
+        "pin.qrfs: zona fija con {} libres de {}",
+        usage.free_pinned_blocks, usage.pinned_blocks
+    );
+    Ok(())
+}
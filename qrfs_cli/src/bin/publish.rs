@@ -0,0 +1,265 @@
+// publish - exporta un disco qrfs como sitio estatico de solo lectura
+// publish.qrfs <qr_folder> <salida> [--config f]
+// arma index.html con el listado, una pagina por archivo (vista previa,
+// descarga y sus qr en orden, mas un zip con esos qr) y bloques.html con
+// todas las imagenes del disco. todo con rutas relativas: se sube tal cual
+// a cualquier hosting y desde el navegador se puede volver a escanear
+
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+
+use qrfs_core::api::{QrfsApi, TreeEntry};
+use qrfs_core::config::{config_arg, QrfsConfig};
+use qrfs_core::errors::QrfsError;
+use qrfs_core::storage::QrStorageManager;
+use qrfs_core::zip::ZipWriter;
+use qrfs_core::BlockId;
+
+// texto mas largo que esto se corta en la vista previa (se descarga entero)
+const PREVIEW_MAX_BYTES: usize = 64 * 1024;
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+table{border-collapse:collapse}td,th{padding:.3em .8em;border-bottom:1px solid #ddd;text-align:left}\
+pre{background:#f4f4f4;padding:1em;overflow:auto}\
+.qr{display:inline-block;margin:.5em;text-align:center;font-size:.8em}.qr img{width:220px;display:block}";
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("publish.qrfs: error: {e}");
+        process::exit(1);
+    }
+}
+
+fn print_usage() {
+    eprintln!("Uso: publish.qrfs <qr_folder> <salida> [--config f]");
+    eprintln!("  genera index.html, una pagina por archivo y bloques.html en <salida>");
+}
+
+fn run() -> Result<(), QrfsError> {
+    let args: Vec<String> = env::args().collect();
+    let positional: Vec<&String> = args[1..]
+        .iter()
+        .enumerate()
+        .filter(|(i, a)| !a.starts_with("--") && args[*i] != "--config")
+        .map(|(_, a)| a)
+        .collect();
+    if positional.len() < 2 {
+        print_usage();
+        return Ok(());
+    }
+    let qrfolder = positional[0].as_str();
+    let output = Path::new(positional[1].as_str());
+
+    let config = QrfsConfig::for_disk(qrfolder, config_arg(&args))?;
+    let storage = Arc::new(config.qr_storage(qrfolder));
+    let api = QrfsApi::open(storage.clone())?;
+    let superblock = api.superblock();
+
+    fs::create_dir_all(output.join("archivos"))?;
+    fs::create_dir_all(output.join("bloques"))?;
+
+    // todos los qr que hay en la carpeta, mas los de archivos que nunca se
+    // dibujaron (export_block los genera)
+    let entries: Vec<TreeEntry> = api.walk()?.into_iter().filter(|e| !e.info.is_dir()).collect();
+    let mut blocks: BTreeSet<BlockId> =
+        (0..superblock.total_blocks).filter(|&id| storage.block_path(id).exists()).collect();
+    blocks.extend(entries.iter().flat_map(|e| e.blocks.iter().copied()));
+    for &id in &blocks {
+        storage.export_block(id, &output.join(block_href(id)))?;
+    }
+
+    for entry in &entries {
+        publish_file(&api, output, entry)?;
+    }
+
+    let title = match api.label() {
+        Some(label) => format!("QRFS: {}", label.label),
+        None => format!("QRFS: {}", qrfolder),
+    };
+    fs::write(output.join("index.html"), index_page(&title, &entries, blocks.len()))?;
+    fs::write(output.join("bloques.html"), blocks_page(&title, &blocks))?;
+
+    println!(
+        "publish.qrfs: {} archivos y {} qr en '{}' (abrir index.html)",
+        entries.len(),
+        blocks.len(),
+        output.display()
+    );
+    Ok(())
+}
+
+// archivos/<inodo>.html, y en archivos/<inodo>/ el archivo y el zip de sus qr
+fn publish_file(
+    api: &QrfsApi<QrStorageManager>,
+    output: &Path,
+    entry: &TreeEntry,
+) -> Result<(), QrfsError> {
+    let info = &entry.info;
+    let dir = output.join("archivos").join(info.inode_id.to_string());
+    fs::create_dir_all(&dir)?;
+
+    // un cifrado se publica solo como qr: sin su clave no hay nada que mostrar
+    let data = if info.encrypted { None } else { api.read_inode(info.inode_id).ok() };
+    if let Some(data) = &data {
+        fs::write(dir.join(&info.name), data)?;
+    }
+
+    let mut zip = ZipWriter::create(&dir.join("qr.zip"))?;
+    for (idx, &id) in entry.blocks.iter().enumerate() {
+        let png = fs::read(output.join(block_href(id)))?;
+        zip.add(&format!("block_{:04}.png", idx), &png)?;
+    }
+    let readme = format!(
+        "archivo: {}\ntamaño: {} bytes\nqr: {} (escanear en orden, block_0000 primero)\n",
+        entry.path,
+        info.size,
+        entry.blocks.len()
+    );
+    zip.add("index.txt", readme.as_bytes())?;
+    zip.finish()?;
+
+    let href = format!("{}/{}", info.inode_id, url_escape(&info.name));
+    let mut body = format!(
+        "<p><a href=\"../index.html\">&larr; todos los archivos</a></p>\n<h1>{}</h1>\n<p>{} bytes, {} qr",
+        html_escape(&entry.path),
+        info.size,
+        entry.blocks.len()
+    );
+    if data.is_some() {
+        body.push_str(&format!(" &middot; <a href=\"{}\" download>descargar</a>", href));
+    }
+    body.push_str(&format!(
+        " &middot; <a href=\"{}/qr.zip\">qr en un zip</a></p>\n",
+        info.inode_id
+    ));
+
+    body.push_str(&match &data {
+        None if info.encrypted => {
+            "<p>archivo cifrado: se recupera de sus qr con qr_extract --decrypt y su clave</p>\n".to_string()
+        }
+        None => "<p>no se pudo leer el archivo; sus qr estan abajo</p>\n".to_string(),
+        Some(data) => preview(&info.name, &href, data),
+    });
+
+    body.push_str("<h2>qr, en orden de lectura</h2>\n");
+    for (idx, &id) in entry.blocks.iter().enumerate() {
+        body.push_str(&format!(
+            "<div class=\"qr\"><img src=\"../{}\" alt=\"bloque {}\">{} &middot; bloque {}</div>\n",
+            block_href(id),
+            id,
+            idx + 1,
+            id
+        ));
+    }
+    fs::write(
+        output.join("archivos").join(format!("{}.html", info.inode_id)),
+        page(&entry.path, &body),
+    )?;
+    Ok(())
+}
+
+fn preview(name: &str, href: &str, data: &[u8]) -> String {
+    let ext = name.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()).unwrap_or_default();
+    if matches!(ext.as_str(), "png" | "jpg" | "jpeg" | "gif" | "webp" | "svg" | "bmp") {
+        return format!("<p><img src=\"{}\" alt=\"{}\" style=\"max-width:100%\"></p>\n", href, html_escape(name));
+    }
+    let shown = &data[..data.len().min(PREVIEW_MAX_BYTES)];
+    match std::str::from_utf8(shown) {
+        // el corte puede caer en medio de un caracter
+        Ok(text) => text_preview(text, shown.len() < data.len()),
+        Err(e) if e.error_len().is_none() && shown.len() < data.len() => {
+            text_preview(std::str::from_utf8(&shown[..e.valid_up_to()]).unwrap(), true)
+        }
+        Err(_) => "<p>archivo binario, sin vista previa</p>\n".to_string(),
+    }
+}
+
+fn text_preview(text: &str, cut: bool) -> String {
+    let mut html = format!("<pre>{}</pre>\n", html_escape(text));
+    if cut {
+        html.push_str("<p>(vista previa cortada, descargar para verlo entero)</p>\n");
+    }
+    html
+}
+
+fn index_page(title: &str, entries: &[TreeEntry], qr_count: usize) -> String {
+    let mut body = format!(
+        "<h1>{}</h1>\n<p>{} archivos &middot; <a href=\"bloques.html\">los {} qr del disco</a></p>\n",
+        html_escape(title),
+        entries.len(),
+        qr_count
+    );
+    body.push_str("<table>\n<tr><th>archivo</th><th>tamaño</th><th>qr</th><th></th></tr>\n");
+    for entry in entries {
+        let id = entry.info.inode_id;
+        body.push_str(&format!(
+            "<tr><td><a href=\"archivos/{id}.html\">{}</a>{}</td><td>{}</td><td>{}</td><td><a href=\"archivos/{id}/qr.zip\">zip</a></td></tr>\n",
+            html_escape(&entry.path),
+            if entry.info.encrypted { " (cifrado)" } else { "" },
+            entry.info.size,
+            entry.blocks.len(),
+        ));
+    }
+    body.push_str("</table>\n");
+    page(title, &body)
+}
+
+fn blocks_page(title: &str, blocks: &BTreeSet<BlockId>) -> String {
+    let mut body = format!(
+        "<p><a href=\"index.html\">&larr; todos los archivos</a></p>\n<h1>{}: qr del disco</h1>\n\
+         <p>escanear todos (en cualquier orden) reconstruye el disco entero</p>\n",
+        html_escape(title)
+    );
+    for &id in blocks {
+        body.push_str(&format!(
+            "<div class=\"qr\"><img src=\"{}\" alt=\"bloque {}\">bloque {}</div>\n",
+            block_href(id),
+            id,
+            id
+        ));
+    }
+    page(title, &body)
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"es\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        html_escape(title),
+        STYLE,
+        body
+    )
+}
+
+// relativa a la raiz del sitio
+fn block_href(id: BlockId) -> String {
+    format!("bloques/{:06}.png", id)
+}
+
+fn html_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// un nombre de archivo como parte de una url relativa
+fn url_escape(name: &str) -> String {
+    name.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
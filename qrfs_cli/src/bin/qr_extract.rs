@@ -1,4 +1,8 @@
 // qr_extract - extrae los bloques qr de un archivo qrfs a una carpeta
+// con --zip empaqueta los qr de cada archivo en su propio zip (mas un
+// index.json), para mandar o guardar "solo los qr de este documento"
+// un archivo cifrado se comparte con sus qr y su clave (--file-key); quien
+// los recibe lo recupera con --decrypt, sin el disco ni la frase
 
 use std::env;
 use std::fs;
@@ -6,9 +10,18 @@ use std::path::Path;
 use std::process;
 use std::sync::Arc;
 
+use serde::Serialize;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::config::{config_arg, QrfsConfig};
+use qrfs_core::crypt::FileKey;
 use qrfs_core::errors::QrfsError;
+use qrfs_core::fs_format::{read_inode_table, read_superblock};
+use qrfs_core::keyring::read_passphrase;
+use qrfs_core::sha256::sha256_hex;
 use qrfs_core::storage::{BlockStorage, QrStorageManager};
-use qrfs_core::Superblock;
+use qrfs_core::zip::ZipWriter;
+use qrfs_core::{decode_qr_bytes, Superblock};
 
 fn main() {
     if let Err(e) = run() {
@@ -20,6 +33,25 @@ fn main() {
 fn run() -> Result<(), QrfsError> {
     let args: Vec<String> = env::args().collect();
 
+    // qr_extract <qrfolder> --zip <output_dir>: todos los archivos, un zip cada uno
+    if args.len() >= 4 && args[2] == "--zip" {
+        let config = QrfsConfig::for_disk(&args[1], config_arg(&args))?;
+        let use_cache = config.decode_cache && !args.iter().any(|a| a == "--no-cache");
+        let svg = args.iter().any(|a| a == "--svg");
+        return export_zips(&args[1], &args[3], &config, use_cache, svg);
+    }
+
+    // qr_extract --decrypt <clave> <carpeta_qr> --out <archivo>: no necesita el disco
+    if args.len() >= 6 && args[1] == "--decrypt" && args[4] == "--out" {
+        return decrypt_shared(&args[2], &args[3], &args[5]);
+    }
+
+    // qr_extract <qrfolder> --file-key <id_inodo>: clave para compartir un archivo cifrado
+    if args.len() >= 4 && args[2] == "--file-key" {
+        let config = QrfsConfig::for_disk(&args[1], config_arg(&args))?;
+        return print_file_key(&args[1], &args[3], &config);
+    }
+
     // parsear argumentos: qr_extract <qrfolder> <archivo_nombre> --out <output_dir>
     if args.len() < 5 {
         print_usage();
@@ -28,14 +60,21 @@ fn run() -> Result<(), QrfsError> {
 
     let qrfolder = &args[1];
     let file_identifier = &args[2]; // puede ser id de inodo o nombre
+    let config = QrfsConfig::for_disk(qrfolder, config_arg(&args))?;
     
-    // buscar --out
+    // buscar --out, --no-cache y --svg
     let mut output_dir: Option<String> = None;
+    let mut use_cache = config.decode_cache;
+    let mut svg = false;
     let mut i = 3;
     while i < args.len() {
         if args[i] == "--out" && i + 1 < args.len() {
             output_dir = Some(args[i + 1].clone());
-            break;
+            i += 1;
+        } else if args[i] == "--no-cache" {
+            use_cache = false;
+        } else if args[i] == "--svg" {
+            svg = true;
         }
         i += 1;
     }
@@ -52,14 +91,10 @@ fn run() -> Result<(), QrfsError> {
     println!("qrfs qr: extrayendo bloques de '{}' a '{}'", file_identifier, output_dir);
 
     // cargar filesystem
-    let block_size = 128;
-    let total_blocks = 400;
-    let storage = Arc::new(QrStorageManager::new(qrfolder, block_size, total_blocks));
+    let storage = Arc::new(config.qr_storage(qrfolder).with_decode_cache(use_cache));
 
     // leer superblock
-    let sb_data = storage.read_block(0)?;
-    let superblock: Superblock = bincode::deserialize(&sb_data)
-        .map_err(|e| QrfsError::Other(format!("error leyendo superblock: {}", e)))?;
+    let superblock = read_superblock(storage.as_ref())?;
 
     if !superblock.is_valid() {
         return Err(QrfsError::Other("filesystem no valido".into()));
@@ -83,6 +118,11 @@ fn run() -> Result<(), QrfsError> {
             let kind = match inode.kind {
                 qrfs_core::InodeKind::File => "archivo",
                 qrfs_core::InodeKind::Directory => "directorio",
+                qrfs_core::InodeKind::Symlink => "enlace",
+                qrfs_core::InodeKind::Fifo => "fifo",
+                qrfs_core::InodeKind::Socket => "socket",
+                qrfs_core::InodeKind::CharDevice => "dispositivo de caracteres",
+                qrfs_core::InodeKind::BlockDevice => "dispositivo de bloques",
             };
             println!("  inodo {}: {} ({} bloques, {} bytes)", 
                      inode.id, kind, inode.blocks.len(), inode.size);
@@ -122,15 +162,6 @@ fn run() -> Result<(), QrfsError> {
     let mut error_count = 0;
 
     for (idx, &block_id) in target_inode.blocks.iter().enumerate() {
-        // obtener path del qr original
-        let source_path = storage.block_path(block_id);
-        
-        if !source_path.exists() {
-            println!("qrfs qr: error: bloque {} (id {}) no existe en disco", idx, block_id);
-            error_count += 1;
-            continue;
-        }
-        
         // leer tamaño del bloque para estadisticas
         match storage.read_block(block_id) {
             Ok(data) => {
@@ -141,11 +172,17 @@ fn run() -> Result<(), QrfsError> {
             }
         }
         
-        // copiar el qr directamente con nombre correlativo
-        let output_filename = format!("block_{:04}.png", idx);
+        // copiar el qr con nombre correlativo (los bloques en cero no tienen png, se generan)
+        // en svg se vuelve a dibujar el mismo qr con los parametros del disco
+        let output_filename = format!("block_{:04}.{}", idx, if svg { "svg" } else { "png" });
         let output_path = Path::new(&output_dir).join(output_filename);
-        
-        match fs::copy(&source_path, &output_path) {
+
+        let exported = if svg {
+            storage.export_block_svg(block_id, &output_path)
+        } else {
+            storage.export_block(block_id, &output_path)
+        };
+        match exported {
             Ok(_) => {
                 extracted_count += 1;
             }
@@ -182,7 +219,10 @@ fn run() -> Result<(), QrfsError> {
 
 fn print_usage() {
     eprintln!("uso:");
-    eprintln!("  qr_extract <qrfolder> <id_inodo> --out <directorio_salida>");
+    eprintln!("  qr_extract <qrfolder> <id_inodo> --out <directorio_salida> [--no-cache] [--svg] [--config qrfs.toml]");
+    eprintln!("  qr_extract <qrfolder> --zip <directorio_salida> [--no-cache] [--svg] [--config qrfs.toml]");
+    eprintln!("  qr_extract <qrfolder> --file-key <id_inodo> [--config qrfs.toml]");
+    eprintln!("  qr_extract --decrypt <clave> <carpeta_qr> --out <archivo>");
     eprintln!();
     eprintln!("ejemplo:");
     eprintln!("  qr_extract disco_qr 2 --out ./qr_extraidos/");
@@ -190,6 +230,11 @@ fn print_usage() {
     eprintln!("notas:");
     eprintln!("  - usa 'list' como id_inodo para ver todos los archivos disponibles");
     eprintln!("  - el inodo 0 es el directorio root");
+    eprintln!("  - --no-cache decodifica todos los qr aunque esten en la cache");
+    eprintln!("  - --svg genera los qr en svg en vez de copiar los png");
+    eprintln!("  - --zip deja un <archivo>.zip por archivo y un index.json (sirve de manifiesto para verify)");
+    eprintln!("  - --file-key pide la frase del disco (o QRFS_PASSPHRASE) y muestra la clave del archivo");
+    eprintln!("  - --decrypt lee los block_NNNN.png de la carpeta en orden y descifra con esa clave");
     eprintln!("  - los archivos regulares empiezan desde el inodo 1 o 2");
 }
 
@@ -199,24 +244,174 @@ fn load_all_inodes(
     sb: &Superblock,
 ) -> Result<Vec<qrfs_core::Inode>, QrfsError> {
     let mut inodes = Vec::new();
-    let mut inode_buffer = Vec::new();
 
-    // leer bloques de la tabla de inodos
-    for i in 0..sb.inode_table_blocks {
-        let data = storage.read_block(sb.inode_table_start + i)?;
-        inode_buffer.extend_from_slice(&data);
+    // deserializar inodos slot por slot
+    for slot in read_inode_table(storage.as_ref(), sb) {
+        match slot {
+            // solo inodos validos (mode != 0)
+            Ok(inode) if inode.mode != 0 => inodes.push(inode),
+            Ok(_) => {}
+            Err(e) => eprintln!("qrfs qr: advertencia: {}", e),
+        }
     }
 
-    // deserializar inodos
-    let mut cursor = std::io::Cursor::new(inode_buffer);
-    for _ in 0..sb.inode_count {
-        if let Ok(inode) = bincode::deserialize_from::<_, qrfs_core::Inode>(&mut cursor) {
-            // solo inodos validos (mode != 0)
-            if inode.mode != 0 {
-                inodes.push(inode);
+    Ok(inodes)
+}
+
+// clave de un archivo cifrado, para mandarla junto con sus qr
+fn print_file_key(qrfolder: &str, inode: &str, config: &QrfsConfig) -> Result<(), QrfsError> {
+    let id: u32 = inode
+        .parse()
+        .map_err(|_| QrfsError::Other(format!("id de inodo invalido: {}", inode)))?;
+    let api = QrfsApi::open(Arc::new(config.qr_storage(qrfolder)))?;
+    api.unlock(&read_passphrase("qrfs qr: frase del disco: ")?)?;
+    let name = api
+        .list("/")?
+        .into_iter()
+        .find(|info| info.inode_id == id)
+        .map(|info| info.name)
+        .ok_or_else(|| QrfsError::NotFound(format!("inodo {}", id)))?;
+    println!("{}", api.file_key(&format!("/{}", name))?.to_text());
+    Ok(())
+}
+
+// recupera un archivo compartido: sus qr (block_NNNN.png, en orden) y su clave
+fn decrypt_shared(key: &str, qr_dir: &str, output: &str) -> Result<(), QrfsError> {
+    let key = FileKey::parse(key)?;
+    let mut paths: Vec<_> = fs::read_dir(qr_dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            name.starts_with("block_") && name.ends_with(".png")
+        })
+        .collect();
+    paths.sort();
+
+    let mut blocks = Vec::new();
+    for path in &paths {
+        let block = decode_qr_bytes(&fs::read(path)?)?;
+        if !block.checksum_ok {
+            return Err(QrfsError::Other(format!(
+                "{}: el crc del qr no coincide",
+                path.display()
+            )));
+        }
+        blocks.push(block.data);
+    }
+    let data = key.decrypt_blocks(&blocks)?;
+    if (data.len() as u64) < key.size {
+        return Err(QrfsError::Other(format!(
+            "faltan qr: {} bytes de {}",
+            data.len(),
+            key.size
+        )));
+    }
+    fs::write(output, &data)?;
+    println!("qrfs qr: {} qr descifrados, {} bytes en '{}'", blocks.len(), data.len(), output);
+    Ok(())
+}
+
+// una entrada de index.json; name, size y sha256 son los campos que lee
+// verify.qrfs --manifest (los archivos cifrados van sin sha256: sin la frase
+// no se pueden leer)
+#[derive(Serialize)]
+struct ZipIndexEntry {
+    name: String,
+    inode_id: u32,
+    size: u64,
+    blocks: Vec<u32>,
+    zip: String,
+    sha256: Option<String>,
+    encrypted: bool,
+}
+
+#[derive(Serialize)]
+struct ZipIndex {
+    disk: String,
+    files: Vec<ZipIndexEntry>,
+}
+
+fn export_zips(
+    qrfolder: &str,
+    output_dir: &str,
+    config: &QrfsConfig,
+    use_cache: bool,
+    svg: bool,
+) -> Result<(), QrfsError> {
+    let storage = Arc::new(config.qr_storage(qrfolder).with_decode_cache(use_cache));
+    let superblock = read_superblock(storage.as_ref())?;
+    if !superblock.is_valid() {
+        return Err(QrfsError::Other("filesystem no valido".into()));
+    }
+    let inodes = load_all_inodes(&storage, &superblock)?;
+    let api = QrfsApi::open(storage.clone())?;
+
+    fs::create_dir_all(output_dir)
+        .map_err(|e| QrfsError::Other(format!("error creando directorio: {}", e)))?;
+    // cada qr se exporta aca y se copia al zip
+    let scratch = Path::new(output_dir).join(format!(".qr_zip_{}", process::id()));
+    let ext = if svg { "svg" } else { "png" };
+
+    let mut files = Vec::new();
+    for info in api.list("/")? {
+        if info.is_dir() {
+            continue;
+        }
+        let blocks = inodes
+            .iter()
+            .find(|inode| inode.id == info.inode_id)
+            .map(|inode| inode.blocks.clone())
+            .unwrap_or_default();
+        let zip_name = format!("{}.zip", info.name);
+        println!("qrfs qr: {} ({} qr) -> {}", info.name, blocks.len(), zip_name);
+
+        let mut zip = ZipWriter::create(&Path::new(output_dir).join(&zip_name))?;
+        for (idx, &block_id) in blocks.iter().enumerate() {
+            if svg {
+                storage.export_block_svg(block_id, &scratch)?;
+            } else {
+                storage.export_block(block_id, &scratch)?;
             }
+            zip.add(&format!("block_{:04}.{}", idx, ext), &fs::read(&scratch)?)?;
+        }
+        let mut readme = format!(
+            "archivo: {}\ntamaño: {} bytes\nqr: {} (escanear en orden, block_0000 primero)\nbloques del disco: {:?}\n",
+            info.name,
+            info.size,
+            blocks.len(),
+            blocks
+        );
+        if info.encrypted {
+            readme.push_str("cifrado: descifrar con qr_extract --decrypt <clave del archivo>\n");
         }
+        zip.add("index.txt", readme.as_bytes())?;
+        zip.finish()?;
+
+        let sha256 = if info.encrypted {
+            None
+        } else {
+            Some(sha256_hex(&api.read(&format!("/{}", info.name))?))
+        };
+        files.push(ZipIndexEntry {
+            sha256,
+            encrypted: info.encrypted,
+            name: info.name,
+            inode_id: info.inode_id,
+            size: info.size,
+            blocks,
+            zip: zip_name,
+        });
     }
+    let _ = fs::remove_file(&scratch);
 
-    Ok(inodes)
-}
\ No newline at end of file
+    let index = ZipIndex {
+        disk: qrfolder.to_string(),
+        files,
+    };
+    let json = serde_json::to_string_pretty(&index)
+        .map_err(|e| QrfsError::Other(format!("error serializando el indice: {}", e)))?;
+    fs::write(Path::new(output_dir).join("index.json"), json)?;
+
+    println!("qrfs qr: {} zips e index.json en '{}'", index.files.len(), output_dir);
+    Ok(())
+}
@@ -0,0 +1,97 @@
+// reencode - vuelve a generar los qr de los bloques de uno o mas archivos
+// con la configuracion actual (despues de cambiar parametros o reparar imagenes)
+// sin montar:  reencode.qrfs <qr_folder|disco.img> <archivo|id_inodo>...
+// montado:     reencode.qrfs --mounted <ruta>... (usa el ioctl del montaje)
+
+use std::env;
+#[cfg(unix)]
+use std::fs::File;
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::errors::QrfsError;
+#[cfg(unix)]
+use qrfs_core::fs::QRFS_IOC_REENCODE;
+use qrfs_core::storage::{BlockStorage, ContainerStorage, QrStorageManager};
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("reencode.qrfs: error: {e}");
+        process::exit(1);
+    }
+}
+
+fn print_usage() {
+    eprintln!("Uso: reencode.qrfs <qr_folder|disco.img> <archivo|id_inodo>...");
+    eprintln!("     reencode.qrfs --mounted <ruta_en_el_montaje>...");
+}
+
+fn run() -> Result<(), QrfsError> {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 3 {
+        print_usage();
+        return Ok(());
+    }
+
+    if args[1] == "--mounted" {
+        for path in &args[2..] {
+            let count = reencode_mounted(path)?;
+            println!("reencode.qrfs: '{}': {} bloques regenerados", path, count);
+        }
+        return Ok(());
+    }
+
+    let disk = &args[1];
+    if Path::new(disk).is_file() {
+        reencode_offline(ContainerStorage::open_disk(disk)?, &args[2..])
+    } else {
+        let sb = QrStorageManager::probe_superblock(disk.as_str())?.ok_or_else(|| {
+            QrfsError::Other(format!("'{}' no tiene superblock, corra mkfs primero", disk))
+        })?;
+        let storage = QrStorageManager::new(disk, sb.block_size as usize, sb.total_blocks);
+        reencode_offline(storage, &args[2..])
+    }
+}
+
+fn reencode_offline<B: BlockStorage + 'static>(storage: B, targets: &[String]) -> Result<(), QrfsError> {
+    let api = QrfsApi::open(Arc::new(storage))?;
+    for target in targets {
+        // igual que qr_extract: un numero es un id de inodo, sino un nombre
+        let count = match target.parse::<u32>() {
+            Ok(id) => api.reencode_inode(id)?,
+            Err(_) => api.reencode(&format!("/{}", target.trim_start_matches('/')))?,
+        };
+        println!("reencode.qrfs: '{}': {} bloques regenerados", target, count);
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn reencode_mounted(path: &str) -> Result<u32, QrfsError> {
+    let file = File::open(path)?;
+    let mut count: u32 = 0;
+    let ret = unsafe {
+        libc::ioctl(
+            file.as_raw_fd(),
+            QRFS_IOC_REENCODE as _,
+            &mut count as *mut u32,
+        )
+    };
+    if ret < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(count)
+}
+
+// el ioctl solo existe con el backend fuse
+#[cfg(not(unix))]
+fn reencode_mounted(_path: &str) -> Result<u32, QrfsError> {
+    Err(QrfsError::Unimplemented(
+        "--mounted necesita un montaje fuse; use el modo sin montar".into(),
+    ))
+}
@@ -0,0 +1,74 @@
+// rekey - rota las claves de un disco cifrado: maestra nueva con la frase
+// nueva y cada archivo cifrado pasado a una clave propia nueva
+// rekey.qrfs <qr_folder|disco.img> [--passphrase-only] [--config qrfs.toml]
+// los archivos se recifran de a uno a bloques libres: si se corta, volver a
+// correrlo (con las mismas frases) sigue con los que faltan.
+// con --passphrase-only solo cambia la frase, sin recifrar nada
+
+use std::env;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::config::{config_arg, QrfsConfig};
+use qrfs_core::keyring::{read_passphrase, read_passphrase_from, NEW_PASSPHRASE_ENV};
+use qrfs_core::errors::QrfsError;
+use qrfs_core::storage::{BlockStorage, ContainerStorage};
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("rekey.qrfs: error: {e}");
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), QrfsError> {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 2 {
+        eprintln!("Uso: rekey.qrfs <qr_folder|disco.img> [--passphrase-only] [--config qrfs.toml]");
+        eprintln!("     las frases salen de QRFS_PASSPHRASE y QRFS_NEW_PASSPHRASE o se piden");
+        return Ok(());
+    }
+
+    let disk = &args[1];
+    let config = QrfsConfig::for_disk(disk, config_arg(&args))?;
+    let passphrase_only = args[2..].iter().any(|a| a == "--passphrase-only");
+
+    if Path::new(disk).is_file() {
+        rekey(ContainerStorage::open_disk(disk)?, passphrase_only)
+    } else {
+        rekey(config.qr_storage(disk), passphrase_only)
+    }
+}
+
+fn rekey<B: BlockStorage + 'static>(storage: B, passphrase_only: bool) -> Result<(), QrfsError> {
+    let api = QrfsApi::open(Arc::new(storage))?;
+    let old = read_passphrase("rekey.qrfs: frase actual: ")?;
+    let new = read_passphrase_from(NEW_PASSPHRASE_ENV, "rekey.qrfs: frase nueva: ")?;
+
+    if passphrase_only {
+        api.change_passphrase(&old, &new)?;
+        println!("rekey.qrfs: frase cambiada (las claves de los archivos siguen iguales)");
+        return Ok(());
+    }
+
+    let pending = api.begin_rekey(&old, &new)?;
+    println!("rekey.qrfs: {} archivos por recifrar", pending.len());
+    let mut blocks = 0;
+    for (done, &id) in pending.iter().enumerate() {
+        let copied = api.rekey_inode(id)?;
+        blocks += copied;
+        println!(
+            "rekey.qrfs: [{}/{}] inodo {}: {} bloques",
+            done + 1,
+            pending.len(),
+            id,
+            copied
+        );
+    }
+    api.finish_rekey()?;
+    println!("rekey.qrfs: listo, {} bloques recifrados con la clave nueva", blocks);
+    Ok(())
+}
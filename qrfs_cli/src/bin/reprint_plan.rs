@@ -0,0 +1,162 @@
+// reprint_plan - que qr volver a imprimir despues de editar el disco
+// reprint_plan.qrfs <qr_folder> [--manifest <impresion.json>] [--pdf salida.pdf] [--cols N] [--rows N] [--record] [--config qrfs.toml]
+// compara el crc de cada bloque con lo que hay en papel y lista los que
+// cambiaron (nunca impreso: todos). el papel sale del registro de
+// impresiones de la carpeta, o de un manifiesto suelto con --manifest. --pdf
+// arma las hojas solo con esos qr, a tamaño de imprimir, y --record anota
+// esta impresion en el registro (y en el manifiesto, si se dio)
+use std::env;
+use std::path::Path;
+use std::process;
+
+use image::imageops::{self, FilterType};
+use image::{Rgb, RgbImage};
+
+use qrfs_core::config::{config_arg, QrfsConfig};
+use qrfs_core::errors::QrfsError;
+use qrfs_core::reprint::{PrintLog, PrintManifest, PrintParams, ReprintReason, PRINTS_FILE};
+use qrfs_core::sheet::{draw_text, write_pdf};
+use qrfs_core::storage::QrStorageManager;
+use qrfs_core::BlockId;
+
+// celda de cada qr en la hoja, en pixeles
+const QR_SIZE: u32 = 360;
+const PAD: u32 = 16;
+const LABEL_H: u32 = 24;
+const MARGIN: u32 = 30;
+const TITLE_H: u32 = 40;
+
+const WHITE: Rgb<u8> = Rgb([255, 255, 255]);
+const BLACK: Rgb<u8> = Rgb([0, 0, 0]);
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("reprint_plan.qrfs: error: {e}");
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), QrfsError> {
+    let args: Vec<String> = env::args().collect();
+    let value = |flag: &str| args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1));
+
+    let Some(disk) = args.get(1) else {
+        eprintln!("Uso: reprint_plan.qrfs <qr_folder> [--manifest <impresion.json>] [--pdf salida.pdf] [--cols N] [--rows N] [--record] [--config qrfs.toml]");
+        return Ok(());
+    };
+    let manifest_path = value("--manifest");
+    let number = |flag: &str, default: u32| -> Result<u32, QrfsError> {
+        match value(flag) {
+            None => Ok(default),
+            Some(n) => n
+                .parse::<u32>()
+                .map(|n| n.max(1))
+                .map_err(|_| QrfsError::Other(format!("{} espera un numero", flag))),
+        }
+    };
+    let cols = number("--cols", 2)?;
+    let rows = number("--rows", 3)?;
+
+    let config = QrfsConfig::for_disk(disk, config_arg(&args))?;
+    if QrStorageManager::probe_superblock(disk.as_str())?.is_none() {
+        return Err(QrfsError::Other(format!("'{}' no es una carpeta de qr con superblock", disk)));
+    }
+    let storage = config.qr_storage(disk);
+
+    let mut log = PrintLog::load(disk)?;
+    let (current, unreadable) = PrintManifest::capture(&storage);
+    println!("reprint_plan.qrfs: {}", log.status(&current));
+    let printed = match manifest_path {
+        Some(path) if Path::new(path).exists() => PrintManifest::load(path)?,
+        Some(path) => {
+            println!("reprint_plan.qrfs: sin impresion anterior en '{}', va todo", path);
+            PrintManifest::default()
+        }
+        None => log.paper(),
+    };
+    let plan = printed.plan(&current);
+
+    for (id, reason) in &plan.reprint {
+        println!("reimprimir #{:06} ({})", id, reason.describe());
+    }
+    for id in &plan.obsolete {
+        println!("sobra      #{:06} (ya no tiene qr, la hoja se puede tirar)", id);
+    }
+    for id in &unreadable {
+        eprintln!("reprint_plan.qrfs: warning: el qr #{:06} no se puede leer, no entra en el plan", id);
+    }
+    println!(
+        "reprint_plan.qrfs: {} de {} qr para reimprimir, {} sobran",
+        plan.reprint.len(),
+        current.blocks.len(),
+        plan.obsolete.len()
+    );
+
+    if let Some(pdf) = value("--pdf") {
+        let per_page = (cols * rows) as usize;
+        let chunks: Vec<&[(BlockId, ReprintReason)]> = plan.reprint.chunks(per_page).collect();
+        let pages: Vec<RgbImage> = chunks
+            .iter()
+            .enumerate()
+            .map(|(n, chunk)| {
+                let title = format!("qrfs {} - reimpresion {}/{}", disk, n + 1, chunks.len());
+                render_page(&storage, chunk, cols, rows, &title)
+            })
+            .collect();
+        if pages.is_empty() {
+            println!("reprint_plan.qrfs: nada para imprimir, no se escribe '{}'", pdf);
+        } else {
+            write_pdf(pdf, &pages)?;
+            println!("reprint_plan.qrfs: '{}' ({} paginas)", pdf, pages.len());
+        }
+    }
+
+    if args.iter().any(|a| a == "--record") {
+        let params = PrintParams {
+            qr_size: storage.qr_params().size,
+            ec_level: format!("{:?}", storage.qr_params().ec_level),
+            cols,
+            rows,
+        };
+        let blocks = plan.reprint.iter().map(|&(id, _)| (id, current.blocks[&id])).collect();
+        let number = log.record(blocks, params).number;
+        log.save(disk)?;
+        println!(
+            "reprint_plan.qrfs: impresion #{} anotada en {}/{}",
+            number, disk, PRINTS_FILE
+        );
+        if let Some(path) = manifest_path {
+            current.save(path)?;
+            println!("reprint_plan.qrfs: impresion registrada en '{}'", path);
+        }
+    }
+    Ok(())
+}
+
+// una pagina con los qr a tamaño completo y el id debajo, para recortar
+fn render_page(
+    storage: &QrStorageManager,
+    blocks: &[(BlockId, ReprintReason)],
+    cols: u32,
+    rows: u32,
+    title: &str,
+) -> RgbImage {
+    let cell_w = QR_SIZE + 2 * PAD;
+    let cell_h = QR_SIZE + LABEL_H + 2 * PAD;
+    let width = 2 * MARGIN + cols * cell_w;
+    let height = 2 * MARGIN + TITLE_H + rows * cell_h;
+    let mut page = RgbImage::from_pixel(width, height, WHITE);
+    draw_text(&mut page, MARGIN, MARGIN, title, 2, BLACK);
+
+    for (n, (id, _)) in blocks.iter().enumerate() {
+        let x = MARGIN + (n as u32 % cols) * cell_w + PAD;
+        let y = MARGIN + TITLE_H + (n as u32 / cols) * cell_h + PAD;
+        if let Ok(img) = image::open(storage.block_path(*id)) {
+            // nearest para que los modulos del qr queden nitidos
+            let qr = imageops::resize(&img.to_rgb8(), QR_SIZE, QR_SIZE, FilterType::Nearest);
+            imageops::overlay(&mut page, &qr, x as i64, y as i64);
+        }
+        draw_text(&mut page, x, y + QR_SIZE + 4, &format!("#{:06}", id), 2, BLACK);
+    }
+    page
+}
@@ -1,11 +1,39 @@
 use actix_cors::Cors;
-use actix_web::{get, post, web, App, HttpResponse, HttpServer, Responder};
-use qrfs_core::storage::{BlockStorage, QrStorageManager};
+use actix_files::Files;
+use actix_web::error::{InternalError, JsonPayloadError};
+use actix_web::http::StatusCode;
+use actix_web::{get, post, web, App, HttpRequest, HttpResponse, HttpServer, Responder};
+use futures_util::StreamExt;
+use qrfs_core::blockrpc::{self, BlockAck, BlockRange};
+use qrfs_core::config::{config_arg, QrfsConfig};
+use qrfs_core::crypt::check_passphrase;
+use qrfs_core::disk::{Superblock, VersionAccess, DEFAULT_INODE_COUNT};
+use qrfs_core::errors::QrfsError;
+use qrfs_core::events::{EventBus, FsEvent};
+use qrfs_core::fs::{MountSession, QrfsFilesystem};
+use qrfs_core::fs_format::read_superblock;
+use qrfs_core::fsck::{self, FsckOptions};
+use qrfs_core::health::{HealthMonitor, HealthReport};
+use qrfs_core::keyring::PassphraseSource;
+use qrfs_core::missing::scan_next;
+use qrfs_core::sha256::sha256_hex;
+use qrfs_core::qr::{BlockStatus, DecodedBlock, EnvelopeVersion};
+use qrfs_core::snapshot::{SnapshotBlocks, SnapshotStorage};
+use qrfs_core::storage::{BlockStorage, DiskLock, InMemoryBlockStorage, QrStorageManager, ScanDecision};
+use qrfs_core::sync;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::env;
+use std::net::IpAddr;
+use std::path::{Component, Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use base64::{engine::general_purpose, Engine as _}; 
-use serde_json;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// tamaño maximo de cualquier cuerpo json aceptado por el servidor
+const MAX_JSON_BODY: usize = 64 * 1024;
+
+// bloques que se sugiere escanear despues en cada respuesta
+const SCAN_HINTS: usize = 16;
 
 // estructura para recibir datos
 #[derive(Deserialize)]
@@ -21,386 +49,603 @@ struct ResponseMsg {
     message: String,
 }
 
-struct AppState {
-    storage: Arc<Mutex<QrStorageManager>>,
+// disco (carpeta de qrs) administrado por el servidor
+struct ManagedDisk {
+    path: String,
+    // con candados por bloque adentro: los handlers lo usan a la vez
+    storage: Arc<QrStorageManager>,
+    // ReadOnly si el superblock es de una version menor mas nueva: se sirve
+    // pero no se le escriben bloques
+    access: VersionAccess,
+    ingest: Mutex<HashMap<String, IngestSession>>,
+    // frase de un disco cifrado, para montarlo (se pide al arrancar o llega
+    // con el primer POST /mount)
+    passphrase: Mutex<Option<String>>,
+    // candado del disco mientras este registrado (el montaje propio no lo repite)
+    _lock: DiskLock,
 }
 
-#[get("/")]
-async fn index() -> impl Responder {
-    let html = r#"
-<!DOCTYPE html>
-<html>
-<head>
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>lector qrfs - modo manual</title>
-    <script src="https://unpkg.com/html5-qrcode" type="text/javascript"></script>
-    <style>
-        body { font-family: sans-serif; padding: 20px; text-align: center; background: #f0f2f5; }
-        #reader { width: 100%; max-width: 500px; margin: 0 auto; border: 2px solid #333; }
-        .input-group { margin: 20px 0; padding: 10px; background: white; border-radius: 8px; }
-        input[type="number"] { padding: 10px; font-size: 1.5rem; width: 80px; text-align: center; }
-        textarea { 
-            width: 90%; 
-            min-height: 150px; 
-            padding: 10px; 
-            font-family: monospace; 
-            font-size: 0.9rem;
-            border: 2px solid #ddd;
-            border-radius: 5px;
-            margin: 10px 0;
-        }
-        button {
-            background: #4CAF50;
-            color: white;
-            padding: 12px 24px;
-            border: none;
-            border-radius: 5px;
-            font-size: 1rem;
-            cursor: pointer;
-            margin: 5px;
-        }
-        button:hover { background: #45a049; }
-        button.secondary { background: #2196F3; }
-        button.secondary:hover { background: #0b7dda; }
-        .status { margin-top: 20px; padding: 10px; border-radius: 5px; font-weight: bold; }
-        .success { background-color: #d4edda; color: #155724; }
-        .error { background-color: #f8d7da; color: #721c24; }
-        .mode-selector {
-            background: white;
-            padding: 15px;
-            border-radius: 8px;
-            margin-bottom: 20px;
-        }
-        .hidden { display: none; }
-        .preview-box {
-            background: white;
-            border: 2px solid #4CAF50;
-            border-radius: 8px;
-            padding: 15px;
-            margin: 20px auto;
-            max-width: 600px;
-            text-align: left;
-        }
-        .preview-box h3 {
-            margin-top: 0;
-            color: #4CAF50;
-        }
-        .preview-content {
-            background: #f5f5f5;
-            padding: 10px;
-            border-radius: 5px;
-            font-family: monospace;
-            word-wrap: break-word;
-            max-height: 200px;
-            overflow-y: auto;
-        }
-        .metadata {
-            background: #e3f2fd;
-            padding: 8px;
-            border-radius: 5px;
-            margin: 10px 0;
-            font-size: 0.9rem;
-        }
-    </style>
-</head>
-<body>
-    <h2>lector qrfs</h2>
-    
-    <div class="mode-selector">
-        <button onclick="showMode('camera')" class="secondary">usar camara</button>
-        <button onclick="showMode('manual')">modo manual (pegar texto)</button>
-    </div>
-
-    <div class="input-group">
-        <label>bloque id a guardar:</label><br>
-        <input type="number" id="blockId" value="0">
-    </div>
-
-    <div id="cameraMode">
-        <div id="reader"></div>
-    </div>
-
-    <div id="manualMode" class="hidden">
-        <div class="input-group">
-            <h3>modo manual</h3>
-            <p>escanea el qr con tu celular y pega el contenido aqui:</p>
-            <textarea id="qrContent" placeholder='pega aqui el contenido del qr, ejemplo:
-{"block_id":0,"data":"SGVsbG8gV29ybGQ="}
-
-o directamente el base64:
-SGVsbG8gV29ybGQ='></textarea>
-            <br>
-            <button onclick="uploadManual()">enviar bloque</button>
-        </div>
-    </div>
-
-    <div id="result" class="status">esperando escaneo...</div>
-
-    <div id="previewBox" class="preview-box hidden">
-        <h3>contenido decodificado del bloque <span id="previewBlockId"></span></h3>
-        <div class="metadata">
-            <strong>tamaño:</strong> <span id="previewSize"></span> bytes<br>
-            <strong>tipo:</strong> <span id="previewType"></span>
-        </div>
-        <div class="preview-content" id="previewContent"></div>
-    </div>
-
-    <script>
-        let html5QrcodeScanner = null;
-        let currentMode = 'camera';
-
-        function showMode(mode) {
-            currentMode = mode;
-            if (mode === 'camera') {
-                document.getElementById('cameraMode').classList.remove('hidden');
-                document.getElementById('manualMode').classList.add('hidden');
-                if (!html5QrcodeScanner) {
-                    initCamera();
+// sesion de ingesta para qrs sin metadata (base64 directo)
+// el cliente declara el bloque inicial; cada qr trae su posicion en la sesion
+// (seq) o, sin ella, el servidor asigna ids en orden de llegada
+#[derive(Serialize, Deserialize, Clone)]
+struct IngestSession {
+    name: String,
+    start_block: u32,
+    next_block: u32,
+    // bloque guardado -> sha256 del payload, para reconocer un re-escaneo
+    #[serde(default)]
+    blocks: BTreeMap<u32, String>,
+    // ids reservados mientras se escribe el png, con su sha256
+    #[serde(skip)]
+    pending: HashMap<u32, String>,
+}
+
+// que hacer con un qr sin metadata
+enum IngestSlot {
+    // id reservado: escribir el bloque y despues commit (o release si falla)
+    Reserved(u32),
+    // esa posicion ya esta guardada con el mismo contenido
+    Duplicate(u32),
+    Conflict(u32, String),
+}
+
+impl IngestSession {
+    fn new(name: &str, start_block: u32) -> Self {
+        IngestSession {
+            name: name.to_string(),
+            start_block,
+            next_block: start_block,
+            blocks: BTreeMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    fn taken(&self, block_id: u32) -> bool {
+        self.blocks.contains_key(&block_id) || self.pending.contains_key(&block_id)
+    }
+
+    fn reserve(&mut self, seq: Option<u32>, hash: &str) -> IngestSlot {
+        let block_id = match seq {
+            Some(seq) => {
+                let block_id = self.start_block.saturating_add(seq);
+                if self.pending.contains_key(&block_id) {
+                    return IngestSlot::Conflict(
+                        block_id,
+                        format!("el bloque {} se esta guardando, reintenta", block_id),
+                    );
                 }
-            } else {
-                document.getElementById('cameraMode').classList.add('hidden');
-                document.getElementById('manualMode').classList.remove('hidden');
-                if (html5QrcodeScanner) {
-                    html5QrcodeScanner.clear();
+                match self.blocks.get(&block_id) {
+                    Some(stored) if stored == hash => return IngestSlot::Duplicate(block_id),
+                    Some(_) => {
+                        return IngestSlot::Conflict(
+                            block_id,
+                            format!("la posicion {} (bloque {}) ya tiene otro contenido", seq, block_id),
+                        )
+                    }
+                    None => block_id,
+                }
+            }
+            None => {
+                // sin posicion no se distingue un re-escaneo de otro bloque con
+                // el mismo contenido: se avisa en vez de adivinar
+                let same = self.blocks.iter().chain(self.pending.iter()).find(|(_, stored)| *stored == hash);
+                if let Some((&block_id, _)) = same {
+                    return IngestSlot::Conflict(
+                        block_id,
+                        format!(
+                            "mismo contenido que el bloque {}: si es otro bloque mandalo con seq",
+                            block_id
+                        ),
+                    );
+                }
+                let mut block_id = self.next_block;
+                while self.taken(block_id) {
+                    block_id += 1;
                 }
+                block_id
             }
+        };
+        self.pending.insert(block_id, hash.to_string());
+        self.next_block = self.next_block.max(block_id + 1);
+        IngestSlot::Reserved(block_id)
+    }
+
+    fn commit(&mut self, block_id: u32) {
+        if let Some(hash) = self.pending.remove(&block_id) {
+            self.blocks.insert(block_id, hash);
         }
+    }
 
-        function initCamera() {
-            html5QrcodeScanner = new Html5QrcodeScanner(
-                "reader", 
-                { fps: 10, qrbox: {width: 250, height: 250} },
-                false
-            );
-            html5QrcodeScanner.render(onScanSuccess);
+    // la escritura fallo: el id vuelve a quedar libre
+    fn release(&mut self, block_id: u32) {
+        self.pending.remove(&block_id);
+        if self.next_block == block_id + 1 {
+            self.next_block = block_id;
         }
+    }
+}
 
-        function decodeAndPreview(content, blockId) {
-            try {
-                const parsed = JSON.parse(content);
-                if (parsed.data) {
-                    const decoded = atob(parsed.data);
-                    showPreview(blockId, decoded, 'datos del filesystem');
-                    return;
-                }
-            } catch (e) {
-                try {
-                    const decoded = atob(content);
-                    showPreview(blockId, decoded, 'base64 directo');
-                    return;
-                } catch (e2) {
-                    showPreview(blockId, content, 'texto plano');
-                }
-            }
+// las sesiones se guardan junto a los qrs para poder retomarlas tras reiniciar
+fn ingest_file(disk_path: &str) -> std::path::PathBuf {
+    std::path::Path::new(disk_path).join(".qrfs_ingest.json")
+}
+
+fn load_ingest_sessions(disk_path: &str) -> HashMap<String, IngestSession> {
+    std::fs::read_to_string(ingest_file(disk_path))
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn save_ingest_sessions(disk_path: &str, sessions: &HashMap<String, IngestSession>) {
+    let result = serde_json::to_string_pretty(sessions)
+        .map_err(std::io::Error::other)
+        .and_then(|text| std::fs::write(ingest_file(disk_path), text));
+    if let Err(e) = result {
+        eprintln!(">> advertencia: no se pudieron guardar las sesiones de ingesta: {}", e);
+    }
+}
+
+struct AppState {
+    disks: Mutex<Vec<ManagedDisk>>,
+    // indice del disco que reciben los uploads
+    active: Mutex<usize>,
+    // perfil del servidor: geometria para discos nuevos sin superblock,
+    // parametros de los qr y del montaje
+    config: QrfsConfig,
+    // resultado del ultimo POST /fsck
+    last_fsck: Mutex<Option<FsckReport>>,
+    mount: Mutex<Option<ActiveMount>>,
+    limiter: RateLimiter,
+    // carpeta con las paginas html (None = usar las embebidas)
+    static_dir: Option<PathBuf>,
+    // POST /disks solo registra carpetas de aca adentro
+    data_dir: PathBuf,
+    // y POST /mount solo monta aca adentro
+    mount_root: PathBuf,
+    // --force-shared: un mount.qrfs aparte puede escribir los mismos discos
+    force_shared: bool,
+    // avisos de cambios al montaje activo (ver events.rs); el servidor
+    // publica con events_origin cada bloque que llega
+    events: Arc<EventBus>,
+    events_origin: u64,
+}
+
+// limite de uploads por cliente con ventana fija de un segundo
+// max_per_second = 0 desactiva el limite
+struct RateLimiter {
+    max_per_second: u32,
+    clients: Mutex<HashMap<IpAddr, (Instant, u32)>>,
+}
+
+impl RateLimiter {
+    fn new(max_per_second: u32) -> Self {
+        Self {
+            max_per_second,
+            clients: Mutex::new(HashMap::new()),
         }
+    }
 
-        function showPreview(blockId, content, type) {
-            document.getElementById('previewBlockId').textContent = blockId;
-            document.getElementById('previewSize').textContent = content.length;
-            
-            let blockType = 'datos desconocidos';
-            if (blockId == 0) {
-                blockType = 'superblock (metadata del fs)';
-            } else if (blockId >= 1 && blockId < 2) {
-                blockType = 'bitmap (mapa de bloques libres)';
-            } else if (blockId >= 2 && blockId < 10) {
-                blockType = 'tabla de inodos';
-            } else {
-                blockType = 'datos de archivo';
+    fn allow(&self, ip: IpAddr) -> bool {
+        if self.max_per_second == 0 {
+            return true;
+        }
+
+        let mut clients = self.clients.lock().unwrap();
+        let now = Instant::now();
+        // se tiran las ventanas vencidas: sin esto el mapa crece con cada ip
+        // que paso alguna vez (queda chico, solo las del ultimo segundo)
+        clients.retain(|_, window| now.duration_since(window.0) < Duration::from_secs(1));
+        let window = clients.entry(ip).or_insert((now, 0));
+        window.1 += 1;
+        window.1 <= self.max_per_second
+    }
+}
+
+// largo maximo del texto de un qr para un bloque: base64 del payload mas el sobre json
+fn max_content_len(block_size: usize) -> usize {
+    block_size.div_ceil(3) * 4 + 128
+}
+
+// validaciones comunes a los endpoints de upload, devuelve el codigo http a responder si falla
+fn check_upload(req: &HttpRequest, state: &AppState, content: &str) -> Result<(), (StatusCode, String)> {
+    check_writable(state)?;
+    if let Some(addr) = req.peer_addr() {
+        if !state.limiter.allow(addr.ip()) {
+            return Err((
+                StatusCode::TOO_MANY_REQUESTS,
+                "demasiados uploads, espera un momento".to_string(),
+            ));
+        }
+    }
+
+    let block_size = state.active_storage().block_size();
+    if content.len() > max_content_len(block_size) {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!(
+                "qr demasiado grande para bloques de {} bytes ({} caracteres)",
+                block_size,
+                content.len()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+// un disco de una version mas nueva se sirve en solo lectura
+fn check_writable(state: &AppState) -> Result<(), (StatusCode, String)> {
+    let disks = state.disks.lock().unwrap();
+    let disk = &disks[*state.active.lock().unwrap()];
+    if disk.access == VersionAccess::ReadOnly {
+        return Err((
+            StatusCode::FORBIDDEN,
+            format!("{}: disco de una version mas nueva, en solo lectura", disk.path),
+        ));
+    }
+    Ok(())
+}
+
+// valida el payload ya decodificado contra la geometria del disco
+fn check_payload(storage: &QrStorageManager, block_id: u32, bytes: &[u8]) -> Result<(), (StatusCode, String)> {
+    if block_id >= storage.total_blocks() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("bloque {} fuera de rango 0..{}", block_id, storage.total_blocks()),
+        ));
+    }
+    if bytes.len() > storage.block_size() {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!(
+                "payload de {} bytes excede el tamaño de bloque ({})",
+                bytes.len(),
+                storage.block_size()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+// montaje fuse lanzado desde el servidor
+struct ActiveMount {
+    disk: String,
+    mountpoint: String,
+    session: MountSession,
+}
+
+impl AppState {
+    fn active_storage(&self) -> Arc<QrStorageManager> {
+        let disks = self.disks.lock().unwrap();
+        let active = *self.active.lock().unwrap();
+        disks[active].storage.clone()
+    }
+
+    // un bloque nuevo en el disco activo: el montaje se fija si cambio la metadata
+    fn block_arrived(&self, block_id: u32) {
+        self.events.publish(self.events_origin, FsEvent::BlockStored(block_id));
+    }
+}
+
+// paginas embebidas en el binario, se usan si no hay carpeta static
+const EMBEDDED_PAGES: &[(&str, &str)] = &[
+    ("index.html", include_str!("../../static/index.html")),
+    ("scanner.html", include_str!("../../static/scanner.html")),
+    ("dashboard.html", include_str!("../../static/dashboard.html")),
+];
+
+// carpeta static del codigo fuente, sirve para editar la ui sin recompilar
+const DEFAULT_STATIC_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/static");
+
+// reemplaza cada {{nombre}} del template por su valor
+fn render_template(template: &str, vars: &[(&str, String)]) -> String {
+    let mut html = template.to_string();
+    for (name, value) in vars {
+        html = html.replace(&format!("{{{{{}}}}}", name), value);
+    }
+    html
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// variables disponibles en los templates: disco activo y su layout
+fn page_vars(state: &AppState) -> Vec<(&'static str, String)> {
+    let disk = {
+        let disks = state.disks.lock().unwrap();
+        disks[*state.active.lock().unwrap()].path.clone()
+    };
+    let total_blocks = state.active_storage().total_blocks();
+
+    // sin superblock todavia se asume el layout que generaria mkfs
+    let sb = match QrStorageManager::probe_superblock(&disk) {
+        Ok(Some(sb)) => sb,
+        _ => Superblock::new(total_blocks, DEFAULT_INODE_COUNT),
+    };
+
+    vec![
+        ("disk", escape_html(&disk)),
+        ("total_blocks", sb.total_blocks.to_string()),
+        ("free_map_start", sb.free_map_start.to_string()),
+        ("inode_table_start", sb.inode_table_start.to_string()),
+        ("data_block_start", sb.data_block_start.to_string()),
+    ]
+}
+
+// sirve una pagina desde la carpeta static, o la version embebida si no esta
+fn render_page(state: &AppState, name: &str) -> HttpResponse {
+    let from_dir = state
+        .static_dir
+        .as_ref()
+        .and_then(|dir| std::fs::read_to_string(dir.join(name)).ok());
+
+    let template = match from_dir {
+        Some(text) => text,
+        None => match EMBEDDED_PAGES.iter().find(|(page, _)| *page == name) {
+            Some((_, text)) => text.to_string(),
+            None => return HttpResponse::NotFound().finish(),
+        },
+    };
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(render_template(&template, &page_vars(state)))
+}
+
+// abre una carpeta de qrs tomando la geometria del superblock si ya existe
+// si no hay bloque 0 se usa la geometria pedida
+fn open_disk(
+    path: &str,
+    block_size: usize,
+    total_blocks: u32,
+    config: &QrfsConfig,
+    shared: bool,
+) -> Result<ManagedDisk, QrfsError> {
+    std::fs::create_dir_all(path)?;
+    let lock = DiskLock::acquire(path, "server.qrfs", shared)?;
+
+    let (block_size, total_blocks, access) = match QrStorageManager::probe_superblock(path)? {
+        Some(sb) => {
+            let access = sb.version_access()?;
+            if access == VersionAccess::ReadOnly {
+                println!(
+                    "   {}: disco de la version {}, mas nueva que este servidor: se monta en solo lectura",
+                    path,
+                    sb.version_string()
+                );
             }
-            
-            document.getElementById('previewType').textContent = blockType;
-            
-            let preview = '';
-            
-            const nullCount = (content.match(/\0/g) || []).length;
-            const printableCount = content.replace(/[^\x20-\x7E]/g, '').length;
-            
-            if (nullCount > content.length * 0.8) {
-                preview = '[bloque de metadata del filesystem]\n\n';
-                preview += 'bytes totales: ' + content.length + '\n';
-                preview += 'bytes nulos: ' + nullCount + '\n';
-                preview += 'bytes con datos: ' + (content.length - nullCount);
-            } else if (printableCount > content.length * 0.3) {
-                const printable = content.replace(/[^\x20-\x7E\n\r\t]/g, '.');
-                preview = printable.substring(0, 500);
-                if (content.length > 500) {
-                    preview += '\n\n... (truncado, total: ' + content.length + ' bytes)';
-                }
-            } else {
-                preview = '[contenido binario]\n\n';
-                const bytes = [];
-                for (let i = 0; i < Math.min(64, content.length); i++) {
-                    const byte = content.charCodeAt(i).toString(16).padStart(2, '0');
-                    bytes.push(byte);
-                    if ((i + 1) % 16 === 0) bytes.push('\n');
-                }
-                preview += bytes.join(' ');
-                if (content.length > 64) {
-                    preview += '\n\n... (mostrando primeros 64 de ' + content.length + ' bytes)';
-                }
+            if sb.block_size as usize != block_size || sb.total_blocks != total_blocks {
+                println!(
+                    "   {}: usando geometria del superblock ({} bloques de {} bytes)",
+                    path, sb.total_blocks, sb.block_size
+                );
             }
-            
-            preview = preview.replace(/&/g, '&amp;')
-                        .replace(/</g, '&lt;')
-                        .replace(/>/g, '&gt;');
-            
-            document.getElementById('previewContent').innerHTML = preview;
-            document.getElementById('previewBox').classList.remove('hidden');
-        }
-        
-        function onScanSuccess(decodedText, decodedResult) {
-            html5QrcodeScanner.clear();
-
-            let blockId = document.getElementById('blockId').value;
-            let resultDiv = document.getElementById('result');
-            
-            resultDiv.innerText = "procesando bloque " + blockId + "...";
-            resultDiv.className = "status";
-
-            let cleanText = decodedText.trim();
-
-            decodeAndPreview(cleanText, blockId);
-
-            fetch('/upload', {
-                method: 'POST',
-                headers: { 'Content-Type': 'application/json' },
-                body: JSON.stringify({ 
-                    block_id: parseInt(blockId), 
-                    content: cleanText 
-                })
-            })
-            .then(response => response.json())
-            .then(data => {
-                if (data.status === "ok") {
-                    resultDiv.innerText = "ok - " + data.message;
-                    resultDiv.className = "status success";
-                    
-                    document.getElementById('blockId').value = parseInt(blockId) + 1;
-                    
-                    setTimeout(() => {
-                        html5QrcodeScanner.render(onScanSuccess);
-                    }, 1500);
-                } else {
-                    resultDiv.innerText = "error: " + data.message;
-                    resultDiv.className = "status error";
-                    setTimeout(() => {
-                        html5QrcodeScanner.render(onScanSuccess);
-                    }, 3000);
-                }
-            })
-            .catch(err => {
-                resultDiv.innerText = "error de red: " + err;
-                resultDiv.className = "status error";
-            });
+            (sb.block_size as usize, sb.total_blocks, access)
         }
+        None => (block_size, total_blocks, VersionAccess::ReadWrite),
+    };
+
+    Ok(ManagedDisk {
+        path: path.to_string(),
+        storage: Arc::new(
+            QrStorageManager::new(path, block_size, total_blocks)
+                .with_qr_options(config.qr_size, config.qr_ec_level),
+        ),
+        access,
+        ingest: Mutex::new(load_ingest_sessions(path)),
+        passphrase: Mutex::new(None),
+        _lock: lock,
+    })
+}
+
+#[derive(Serialize)]
+struct DiskInfo {
+    path: String,
+    block_size: usize,
+    total_blocks: u32,
+    // true si el bloque 0 ya tiene un superblock valido
+    formatted: bool,
+    active: bool,
+}
 
-        async function uploadManual() {
-            let blockId = document.getElementById('blockId').value;
-            let content = document.getElementById('qrContent').value.trim();
-            let resultDiv = document.getElementById('result');
+#[get("/disks")]
+async fn list_disks(state: web::Data<AppState>) -> impl Responder {
+    let disks = state.disks.lock().unwrap();
+    let active = *state.active.lock().unwrap();
 
-            if (!content) {
-                resultDiv.innerText = "debes pegar el contenido del qr";
-                resultDiv.className = "status error";
-                return;
+    let info: Vec<DiskInfo> = disks
+        .iter()
+        .enumerate()
+        .map(|(i, disk)| {
+            let storage = &disk.storage;
+            DiskInfo {
+                path: disk.path.clone(),
+                block_size: storage.block_size(),
+                total_blocks: storage.total_blocks(),
+                formatted: matches!(QrStorageManager::probe_superblock(&disk.path), Ok(Some(_))),
+                active: i == active,
             }
+        })
+        .collect();
+
+    HttpResponse::Ok().json(info)
+}
+
+// una ruta que llega por la red tiene que quedar adentro de root: relativa,
+// sin .. y sin un enlace que salga de ahi
+fn confined(root: &Path, requested: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(requested);
+    if requested.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(format!("'{}' tiene que ser una ruta relativa adentro de {}", requested, root.display()));
+    }
+    let path = root.join(relative);
+    if let (Ok(real), Ok(real_root)) = (path.canonicalize(), root.canonicalize()) {
+        if !real.starts_with(&real_root) {
+            return Err(format!("'{}' sale de {}", requested, root.display()));
+        }
+    }
+    Ok(path)
+}
 
-            resultDiv.innerText = "enviando bloque " + blockId + "...";
-            resultDiv.className = "status";
+#[derive(Deserialize)]
+struct CreateDiskData {
+    path: String,
+    block_size: Option<usize>,
+    total_blocks: Option<u32>,
+}
+
+// registra (y crea si hace falta) otra carpeta de qrs adentro de data_dir
+#[post("/disks")]
+async fn create_disk(data: web::Json<CreateDiskData>, state: web::Data<AppState>) -> impl Responder {
+    let path = match confined(&state.data_dir, &data.path) {
+        Ok(path) => path.to_string_lossy().into_owned(),
+        Err(message) => {
+            return HttpResponse::BadRequest().json(ResponseMsg {
+                status: "error".to_string(),
+                message,
+            })
+        }
+    };
 
-            decodeAndPreview(content, blockId);
+    // la misma geometria que acepta qrfs.toml
+    let mut geometry = state.config.clone();
+    geometry.block_size = data.block_size.unwrap_or(state.config.block_size);
+    geometry.total_blocks = data.total_blocks.unwrap_or(state.config.total_blocks);
+    geometry.container = false;
+    if let Err(e) = geometry.validate() {
+        return HttpResponse::BadRequest().json(ResponseMsg {
+            status: "error".to_string(),
+            message: e.to_string(),
+        });
+    }
 
-            try {
-                const response = await fetch('/upload', {
-                    method: 'POST',
-                    headers: { 'Content-Type': 'application/json' },
-                    body: JSON.stringify({ 
-                        block_id: parseInt(blockId), 
-                        content: content 
-                    })
-                });
+    let mut disks = state.disks.lock().unwrap();
+    if disks.iter().any(|d| d.path == path) {
+        return HttpResponse::Conflict().json(ResponseMsg {
+            status: "error".to_string(),
+            message: format!("el disco {} ya esta registrado", path),
+        });
+    }
 
-                const data = await response.json();
-
-                if (data.status === "ok") {
-                    resultDiv.innerText = "ok - " + data.message;
-                    resultDiv.className = "status success";
-                    
-                    document.getElementById('blockId').value = parseInt(blockId) + 1;
-                    document.getElementById('qrContent').value = '';
-                    document.getElementById('qrContent').focus();
-                } else {
-                    resultDiv.innerText = "error: " + data.message;
-                    resultDiv.className = "status error";
-                }
-            } catch (err) {
-                resultDiv.innerText = "error de red: " + err;
-                resultDiv.className = "status error";
-            }
+    match open_disk(&path, geometry.block_size, geometry.total_blocks, &state.config, state.force_shared) {
+        Ok(disk) => {
+            println!(">> disco registrado: {}", disk.path);
+            disks.push(disk);
+            HttpResponse::Ok().json(ResponseMsg {
+                status: "ok".to_string(),
+                message: format!("disco {} registrado", path),
+            })
+        }
+        Err(e) => HttpResponse::InternalServerError().json(ResponseMsg {
+            status: "error".to_string(),
+            message: format!("no se pudo abrir {}: {}", path, e),
+        }),
+    }
+}
+
+#[derive(Deserialize)]
+struct SelectDiskData {
+    path: String,
+}
+
+// cambia el disco que reciben los uploads
+#[post("/disks/select")]
+async fn select_disk(data: web::Json<SelectDiskData>, state: web::Data<AppState>) -> impl Responder {
+    let disks = state.disks.lock().unwrap();
+
+    match disks.iter().position(|d| d.path == data.path) {
+        Some(idx) => {
+            *state.active.lock().unwrap() = idx;
+            println!(">> disco activo: {}", data.path);
+            HttpResponse::Ok().json(ResponseMsg {
+                status: "ok".to_string(),
+                message: format!("disco activo: {}", data.path),
+            })
         }
+        None => HttpResponse::NotFound().json(ResponseMsg {
+            status: "error".to_string(),
+            message: format!("disco {} no registrado", data.path),
+        }),
+    }
+}
 
-        showMode('manual');
-    </script>
-</body>
-</html>
-    "#;
-    HttpResponse::Ok().content_type("text/html").body(html)
+#[get("/")]
+async fn index(state: web::Data<AppState>) -> impl Responder {
+    render_page(&state, "index.html")
 }
 
 #[post("/upload")]
-async fn upload_block(data: web::Json<ScanData>, state: web::Data<AppState>) -> impl Responder {
+async fn upload_block(
+    req: HttpRequest,
+    data: web::Json<ScanData>,
+    state: web::Data<AppState>,
+) -> impl Responder {
     println!(">> recibido bloque id: {}", data.block_id);
     println!(">> longitud datos: {} caracteres", data.content.len());
 
-    let bytes = if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&data.content) {
-        if let Some(data_str) = parsed.get("data").and_then(|v| v.as_str()) {
-            println!("   formato: json con metadata");
-            match general_purpose::STANDARD.decode(data_str) {
-                Ok(b) => b,
-                Err(_) => {
-                    match general_purpose::URL_SAFE_NO_PAD.decode(data_str) {
-                        Ok(b) => b,
-                        Err(e) => {
-                            eprintln!("   error base64: {}", e);
-                            return HttpResponse::Ok().json(ResponseMsg {
-                                status: "error".to_string(),
-                                message: format!("qr corrupto o ilegible: {}", e)
-                            });
-                        }
-                    }
-                }
-            }
-        } else {
-            eprintln!("   error: json sin campo 'data'");
-            return HttpResponse::Ok().json(ResponseMsg {
+    if let Err((code, message)) = check_upload(&req, &state, &data.content) {
+        eprintln!("   rechazado: {}", message);
+        return HttpResponse::build(code).json(ResponseMsg {
+            status: "error".to_string(),
+            message,
+        });
+    }
+
+    let bytes = match DecodedBlock::from_text(&data.content) {
+        // un qr con metadata tiene que ser del bloque que se escaneo
+        Ok(block) if !block.is_block(data.block_id) => {
+            let message = format!(
+                "el qr es del bloque {}, no del {}",
+                block.block_id.unwrap_or(0),
+                data.block_id
+            );
+            eprintln!("   rechazado: {}", message);
+            return HttpResponse::BadRequest().json(ResponseMsg {
                 status: "error".to_string(),
-                message: "json invalido: falta campo 'data'".to_string()
+                message,
             });
         }
-    } else {
-        println!("   formato: base64 directo");
-        match general_purpose::STANDARD.decode(&data.content) {
-            Ok(b) => b,
-            Err(_) => {
-                match general_purpose::URL_SAFE_NO_PAD.decode(&data.content) {
-                    Ok(b) => b,
-                    Err(e) => {
-                        eprintln!("   error base64: {}", e);
-                        return HttpResponse::Ok().json(ResponseMsg {
-                            status: "error".to_string(),
-                            message: format!("qr corrupto o ilegible: {}", e)
-                        });
-                    }
-                }
+        Ok(block) if block.status() == BlockStatus::ChecksumMismatch => {
+            eprintln!("   rechazado: checksum no coincide");
+            return HttpResponse::BadRequest().json(ResponseMsg {
+                status: "error".to_string(),
+                message: format!("checksum invalido en bloque {}, vuelve a escanear", data.block_id),
+            });
+        }
+        Ok(block) => {
+            match block.envelope {
+                EnvelopeVersion::Raw => println!("   formato: base64 directo"),
+                _ => println!("   formato: json con metadata"),
             }
+            block.data
+        }
+        Err(e) => {
+            eprintln!("   {}", e);
+            return HttpResponse::BadRequest().json(ResponseMsg {
+                status: "error".to_string(),
+                message: format!("qr corrupto o ilegible: {}", e)
+            });
         }
     };
 
-    let storage = state.storage.lock().unwrap();
+    let storage = state.active_storage();
+
+    if let Err((code, message)) = check_payload(&storage, data.block_id, &bytes) {
+        eprintln!("   rechazado: {}", message);
+        return HttpResponse::build(code).json(ResponseMsg {
+            status: "error".to_string(),
+            message,
+        });
+    }
     
     match storage.write_block(data.block_id, &bytes) {
         Ok(_) => {
+            state.block_arrived(data.block_id);
             println!(">> bloque {} guardado correctamente.\n", data.block_id);
             HttpResponse::Ok().json(ResponseMsg {
                 status: "ok".to_string(),
@@ -409,7 +654,7 @@ async fn upload_block(data: web::Json<ScanData>, state: web::Data<AppState>) ->
         },
         Err(e) => {
             eprintln!(">> error escribiendo archivo: {}\n", e);
-            HttpResponse::Ok().json(ResponseMsg {
+            HttpResponse::InternalServerError().json(ResponseMsg {
                 status: "error".to_string(),
                 message: format!("fallo de escritura: {}", e)
             })
@@ -418,237 +663,89 @@ async fn upload_block(data: web::Json<ScanData>, state: web::Data<AppState>) ->
 }
 
 #[get("/scanner")]
-async fn scanner_page() -> impl Responder {
-    let html = r#"
-<!DOCTYPE html>
-<html>
-<head>
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <meta charset="UTF-8">
-    <title>escaner qrfs - modo masivo</title>
-    <script src="https://unpkg.com/html5-qrcode" type="text/javascript"></script>
-    <style>
-        * { margin: 0; padding: 0; box-sizing: border-box; }
-        body { 
-            font-family: system-ui, sans-serif; 
-            background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
-            min-height: 100vh;
-            padding: 20px;
-            color: white;
-        }
-        .container {
-            max-width: 600px;
-            margin: 0 auto;
-        }
-        h1 {
-            text-align: center;
-            margin-bottom: 20px;
-            font-size: 1.8rem;
-            text-shadow: 2px 2px 4px rgba(0,0,0,0.3);
-        }
-        .stats {
-            background: rgba(255,255,255,0.2);
-            backdrop-filter: blur(10px);
-            border-radius: 15px;
-            padding: 20px;
-            margin-bottom: 20px;
-        }
-        .stat-row {
-            display: flex;
-            justify-content: space-between;
-            margin: 10px 0;
-            font-size: 1.1rem;
-        }
-        .stat-value {
-            font-weight: bold;
-            color: #ffd700;
-        }
-        #reader {
-            border-radius: 15px;
-            overflow: hidden;
-            box-shadow: 0 10px 30px rgba(0,0,0,0.3);
-            background: white;
-        }
-        .log {
-            background: rgba(0,0,0,0.4);
-            border-radius: 10px;
-            padding: 15px;
-            margin-top: 20px;
-            max-height: 200px;
-            overflow-y: auto;
-            font-family: monospace;
-            font-size: 0.9rem;
-        }
-        .log-entry {
-            margin: 5px 0;
-            padding: 5px;
-            border-left: 3px solid #4ade80;
-            padding-left: 10px;
-        }
-        .log-entry.error {
-            border-left-color: #ef4444;
-        }
-        .controls {
-            text-align: center;
-            margin-top: 20px;
-        }
-        button {
-            background: #4ade80;
-            color: black;
-            border: none;
-            padding: 15px 30px;
-            border-radius: 10px;
-            font-size: 1.1rem;
-            font-weight: bold;
-            cursor: pointer;
-            box-shadow: 0 4px 15px rgba(74, 222, 128, 0.4);
-            transition: all 0.3s;
-        }
-        button:hover {
-            transform: translateY(-2px);
-            box-shadow: 0 6px 20px rgba(74, 222, 128, 0.6);
-        }
-        button:disabled {
-            background: #6b7280;
-            cursor: not-allowed;
-            box-shadow: none;
-        }
-    </style>
-</head>
-<body>
-    <div class="container">
-        <h1>escaner qrfs - modo masivo</h1>
-        
-        <div class="stats">
-            <div class="stat-row">
-                <span>bloques escaneados:</span>
-                <span class="stat-value" id="scannedCount">0</span>
-            </div>
-            <div class="stat-row">
-                <span>errores:</span>
-                <span class="stat-value" id="errorCount">0</span>
-            </div>
-            <div class="stat-row">
-                <span>ultimo bloque:</span>
-                <span class="stat-value" id="lastBlock">-</span>
-            </div>
-        </div>
-
-        <div id="reader"></div>
-        
-        <div class="controls">
-            <button id="toggleBtn" onclick="toggleScanning()">pausar</button>
-        </div>
-
-        <div class="log" id="log"></div>
-    </div>
-
-    <script>
-        if (navigator.mediaDevices && navigator.mediaDevices.getUserMedia) {
-            console.log('camara disponible');
-        } else {
-            alert('tu navegador no soporta acceso a camara o necesitas https');
-        }
-
-        let scannedCount = 0;
-        let errorCount = 0;
-        let isScanning = true;
-        let scannedBlocks = new Set();
-        let html5QrcodeScanner;
-
-        function addLog(message, isError = false) {
-            const log = document.getElementById('log');
-            const entry = document.createElement('div');
-            entry.className = 'log-entry' + (isError ? ' error' : '');
-            const timestamp = new Date().toLocaleTimeString();
-            entry.textContent = `[${timestamp}] ${message}`;
-            log.insertBefore(entry, log.firstChild);
-            
-            if (log.children.length > 20) {
-                log.removeChild(log.lastChild);
-            }
-        }
+async fn scanner_page(state: web::Data<AppState>) -> impl Responder {
+    render_page(&state, "scanner.html")
+}
 
-        function updateStats(blockId = null) {
-            document.getElementById('scannedCount').textContent = scannedCount;
-            document.getElementById('errorCount').textContent = errorCount;
-            if (blockId !== null) {
-                document.getElementById('lastBlock').textContent = blockId;
-            }
-        }
+#[get("/dashboard")]
+async fn dashboard_page(state: web::Data<AppState>) -> impl Responder {
+    render_page(&state, "dashboard.html")
+}
 
-        function toggleScanning() {
-            const btn = document.getElementById('toggleBtn');
-            isScanning = !isScanning;
-            
-            if (isScanning) {
-                btn.textContent = 'pausar';
-                html5QrcodeScanner.resume();
-                addLog('escaner reanudado');
-            } else {
-                btn.textContent = 'reanudar';
-                html5QrcodeScanner.pause();
-                addLog('escaner pausado');
-            }
-        }
+#[derive(Serialize)]
+struct UiPage {
+    path: &'static str,
+    title: &'static str,
+}
 
-        async function onScanSuccess(decodedText) {
-            if (!isScanning) return;
+#[derive(Serialize)]
+struct UiEndpoint {
+    method: &'static str,
+    path: &'static str,
+    description: &'static str,
+}
 
-            try {
-                const response = await fetch('/upload_auto', {
-                    method: 'POST',
-                    headers: { 'Content-Type': 'application/json' },
-                    body: JSON.stringify({ content: decodedText })
-                });
+#[derive(Serialize)]
+struct UiManifest {
+    pages: Vec<UiPage>,
+    endpoints: Vec<UiEndpoint>,
+}
 
-                const data = await response.json();
-                
-                if (data.status === "ok") {
-                    const blockId = data.block_id;
-                    
-                    if (!scannedBlocks.has(blockId)) {
-                        scannedBlocks.add(blockId);
-                        scannedCount++;
-                        addLog(`bloque ${blockId} guardado correctamente`);
-                        updateStats(blockId);
-                    } else {
-                        addLog(`bloque ${blockId} ya escaneado (omitido)`, false);
-                    }
-                } else {
-                    errorCount++;
-                    addLog(`error: ${data.message}`, true);
-                    updateStats();
-                }
-            } catch (err) {
-                errorCount++;
-                addLog(`error de red: ${err.message}`, true);
-                updateStats();
-            }
-        }
+// paginas y endpoints que muestra el panel, agregar aqui los endpoints nuevos
+const UI_PAGES: &[(&str, &str)] = &[
+    ("/", "lector manual"),
+    ("/scanner", "escaner masivo"),
+    ("/dashboard", "panel"),
+];
 
-        html5QrcodeScanner = new Html5QrcodeScanner(
-            "reader",
-            { 
-                fps: 10,
-                qrbox: { width: 250, height: 250 },
-                aspectRatio: 1.0
-            },
-            false
-        );
+const UI_ENDPOINTS: &[(&str, &str, &str)] = &[
+    ("POST", "/upload", "guardar un bloque con id explicito"),
+    ("POST", "/upload_auto", "guardar un qr detectando su id"),
+    ("GET", "/disks", "listar discos administrados"),
+    ("POST", "/disks", "registrar una carpeta de qrs"),
+    ("POST", "/disks/select", "elegir el disco activo"),
+    ("POST", "/ingest/start", "iniciar o retomar una sesion de ingesta"),
+    ("GET", "/ingest/{session}", "estado de una sesion de ingesta y que escanear despues"),
+    ("GET", "/scan/next", "bloques que faltan del disco activo, los mas importantes primero"),
+    ("GET", "/health", "qr ilegibles, checksums y reparaciones de cada disco"),
+    ("GET", "/sync/manifest", "bloques del disco activo con su generacion"),
+    ("GET", "/sync/block/{id}", "un bloque del disco activo para otro servidor"),
+    ("POST", "/sync/pull", "traer de otro servidor los bloques que faltan o son mas nuevos"),
+    ("POST", blockrpc::UPLOAD_PATH, "subir bloques en protobuf desde una app (ver proto/blocks.proto)"),
+    ("POST", blockrpc::DOWNLOAD_PATH, "bajar en protobuf los bloques escaneados de un rango"),
+    ("POST", "/fsck", "verificar el disco activo"),
+    ("GET", "/fsck/last", "ultimo reporte de fsck"),
+    ("POST", "/mount", "montar el disco activo"),
+    ("POST", "/unmount", "desmontar"),
+    ("GET", "/api/ui", "este listado"),
+];
 
-        html5QrcodeScanner.render(onScanSuccess);
-        addLog('escaner iniciado - apunta a los codigos qr');
-    </script>
-</body>
-</html>
-    "#;
-    HttpResponse::Ok().content_type("text/html").body(html)
+#[get("/api/ui")]
+async fn ui_manifest() -> impl Responder {
+    HttpResponse::Ok().json(UiManifest {
+        pages: UI_PAGES
+            .iter()
+            .map(|&(path, title)| UiPage { path, title })
+            .collect(),
+        endpoints: UI_ENDPOINTS
+            .iter()
+            .map(|&(method, path, description)| UiEndpoint {
+                method,
+                path,
+                description,
+            })
+            .collect(),
+    })
 }
 
 #[derive(Deserialize)]
 struct AutoScanData {
     content: String,
+    // sesion de ingesta, necesaria para qrs sin metadata
+    session: Option<String>,
+    // posicion del qr en la sesion (0 = start_block); sin ella el id sale del
+    // orden de llegada
+    seq: Option<u32>,
 }
 
 #[derive(Serialize)]
@@ -656,140 +753,1061 @@ struct AutoScanResponse {
     status: String,
     message: String,
     block_id: u32,
+    // que se hizo con el qr: "saved", "duplicate", "stale" o "rejected"
+    decision: String,
 }
 
 #[post("/upload_auto")]
-async fn upload_auto(data: web::Json<AutoScanData>, state: web::Data<AppState>) -> impl Responder {
+async fn upload_auto(
+    req: HttpRequest,
+    data: web::Json<AutoScanData>,
+    state: web::Data<AppState>,
+) -> impl Responder {
     println!(">> recibido qr para analisis automatico");
+
+    if let Err((code, message)) = check_upload(&req, &state, &data.content) {
+        return HttpResponse::build(code).json(AutoScanResponse {
+            status: "error".to_string(),
+            message,
+            block_id: 0,
+            decision: "rejected".to_string(),
+        });
+    }
+    
+    let decoded = match DecodedBlock::from_text(&data.content) {
+        Ok(block) => block,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(AutoScanResponse {
+                status: "error".to_string(),
+                message: format!("qr corrupto: {}", e),
+                block_id: 0,
+                decision: "rejected".to_string(),
+            });
+        }
+    };
+
+    if let Some(block_id) = decoded.block_id {
+        if !decoded.checksum_ok {
+            eprintln!(">> bloque {}: checksum no coincide, se descarta", block_id);
+            return HttpResponse::BadRequest().json(AutoScanResponse {
+                status: "error".to_string(),
+                message: format!("checksum invalido en bloque {}, vuelve a escanear", block_id),
+                block_id,
+                decision: "rejected".to_string(),
+            });
+        }
+
+        let storage = state.active_storage();
+
+        if let Err((code, message)) = check_payload(&storage, block_id, &decoded.data) {
+            return HttpResponse::build(code).json(AutoScanResponse {
+                status: "error".to_string(),
+                message,
+                block_id,
+                decision: "rejected".to_string(),
+            });
+        }
+
+        return match storage.store_scanned(block_id, &decoded) {
+            Ok(ScanDecision::Duplicate) => {
+                println!(">> bloque {} duplicado, se omite", block_id);
+                HttpResponse::Ok().json(AutoScanResponse {
+                    status: "ok".to_string(),
+                    message: format!("ya tenemos el bloque {}", block_id),
+                    block_id,
+                    decision: "duplicate".to_string(),
+                })
+            }
+            Ok(ScanDecision::Stale { scanned, stored }) => {
+                println!(
+                    ">> advertencia: bloque {} obsoleto (gen {} < gen {} guardada), se omite",
+                    block_id, scanned, stored
+                );
+                HttpResponse::Conflict().json(AutoScanResponse {
+                    status: "warning".to_string(),
+                    message: format!(
+                        "bloque {} obsoleto: gen {} es anterior a la guardada ({})",
+                        block_id, scanned, stored
+                    ),
+                    block_id,
+                    decision: "stale".to_string(),
+                })
+            }
+            Ok(ScanDecision::Conflict(gen)) => {
+                println!(
+                    ">> advertencia: bloque {} con gen {} igual a la guardada pero otro contenido, se omite",
+                    block_id, gen
+                );
+                HttpResponse::Conflict().json(AutoScanResponse {
+                    status: "warning".to_string(),
+                    message: format!(
+                        "bloque {}: la gen {} ya esta guardada con otro contenido (hojas de copias distintas?)",
+                        block_id, gen
+                    ),
+                    block_id,
+                    decision: "conflict".to_string(),
+                })
+            }
+            Ok(ScanDecision::Saved(gen)) => {
+                state.block_arrived(block_id);
+                println!(">> bloque {} guardado correctamente (gen {})", block_id, gen);
+                HttpResponse::Ok().json(AutoScanResponse {
+                    status: "ok".to_string(),
+                    message: format!("bloque {} guardado", block_id),
+                    block_id,
+                    decision: "saved".to_string(),
+                })
+            }
+            Err(e) => {
+                HttpResponse::InternalServerError().json(AutoScanResponse {
+                    status: "error".to_string(),
+                    message: format!("error escribiendo: {}", e),
+                    block_id: 0,
+                    decision: "rejected".to_string(),
+                })
+            }
+        };
+    }
     
-    if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&data.content) {
-        if let (Some(block_id), Some(data_str)) = (
-            parsed.get("block_id").and_then(|v| v.as_u64()),
-            parsed.get("data").and_then(|v| v.as_str())
-        ) {
-            let bytes = match general_purpose::STANDARD.decode(data_str) {
-                Ok(b) => b,
-                Err(e) => {
-                    return HttpResponse::Ok().json(AutoScanResponse {
-                        status: "error".to_string(),
-                        message: format!("error decodificando base64: {}", e),
-                        block_id: 0,
-                    });
+    let bytes = decoded.data;
+
+    // sin metadata el id sale de la sesion de ingesta declarada por el cliente
+    let session_name = match &data.session {
+        Some(name) => name.clone(),
+        None => {
+            return HttpResponse::BadRequest().json(AutoScanResponse {
+                status: "error".to_string(),
+                message: "qr sin metadata: inicia una sesion con POST /ingest/start".to_string(),
+                block_id: 0,
+                decision: "rejected".to_string(),
+            });
+        }
+    };
+
+    let hash = sha256_hex(&bytes);
+    // el id se reserva con los candados tomados y el png se escribe sin ellos
+    let (storage, disk_path, slot) = {
+        let disks = state.disks.lock().unwrap();
+        let disk = &disks[*state.active.lock().unwrap()];
+        let mut sessions = disk.ingest.lock().unwrap();
+
+        let session = match sessions.get_mut(&session_name) {
+            Some(session) => session,
+            None => {
+                return HttpResponse::NotFound().json(AutoScanResponse {
+                    status: "error".to_string(),
+                    message: format!("sesion de ingesta '{}' no existe", session_name),
+                    block_id: 0,
+                    decision: "rejected".to_string(),
+                });
+            }
+        };
+
+        if bytes.len() > disk.storage.block_size() {
+            return HttpResponse::PayloadTooLarge().json(AutoScanResponse {
+                status: "error".to_string(),
+                message: format!("payload de {} bytes excede el tamaño de bloque", bytes.len()),
+                block_id: 0,
+                decision: "rejected".to_string(),
+            });
+        }
+
+        let slot = session.reserve(data.seq, &hash);
+        if let IngestSlot::Reserved(block_id) = slot {
+            if block_id >= disk.storage.total_blocks() {
+                session.release(block_id);
+                return HttpResponse::InsufficientStorage().json(AutoScanResponse {
+                    status: "error".to_string(),
+                    message: "no hay bloques disponibles".to_string(),
+                    block_id: 0,
+                    decision: "rejected".to_string(),
+                });
+            }
+        }
+        (disk.storage.clone(), disk.path.clone(), slot)
+    };
+
+    let block_id = match slot {
+        IngestSlot::Reserved(block_id) => block_id,
+        IngestSlot::Duplicate(block_id) => {
+            println!(">> bloque {} re-escaneado en sesion '{}', se omite", block_id, session_name);
+            return HttpResponse::Ok().json(AutoScanResponse {
+                status: "ok".to_string(),
+                message: format!("ya tenemos el bloque {}", block_id),
+                block_id,
+                decision: "duplicate".to_string(),
+            });
+        }
+        IngestSlot::Conflict(block_id, message) => {
+            println!(">> advertencia: sesion '{}': {}", session_name, message);
+            return HttpResponse::Conflict().json(AutoScanResponse {
+                status: "warning".to_string(),
+                message,
+                block_id,
+                decision: "conflict".to_string(),
+            });
+        }
+    };
+
+    let written = match web::block(move || storage.write_block(block_id, &bytes)).await {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+
+    // el id entra a la sesion solo si el bloque quedo escrito
+    {
+        let disks = state.disks.lock().unwrap();
+        if let Some(disk) = disks.iter().find(|disk| disk.path == disk_path) {
+            let mut sessions = disk.ingest.lock().unwrap();
+            if let Some(session) = sessions.get_mut(&session_name) {
+                match written {
+                    Ok(_) => session.commit(block_id),
+                    Err(_) => session.release(block_id),
                 }
+            }
+            save_ingest_sessions(&disk.path, &sessions);
+        }
+    }
+
+    match written {
+        Ok(_) => {
+            state.block_arrived(block_id);
+            println!(">> bloque {} guardado (sesion '{}')", block_id, session_name);
+            HttpResponse::Ok().json(AutoScanResponse {
+                status: "ok".to_string(),
+                message: format!("bloque {} guardado", block_id),
+                block_id,
+                decision: "saved".to_string(),
+            })
+        }
+        Err(e) => HttpResponse::InternalServerError().json(AutoScanResponse {
+            status: "error".to_string(),
+            message: format!("error escribiendo: {}", e),
+            block_id: 0,
+            decision: "rejected".to_string(),
+        }),
+    }
+}
+
+#[derive(Deserialize)]
+struct IngestStartData {
+    session: String,
+    start_block: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct IngestStatus {
+    status: String,
+    message: String,
+    session: String,
+    next_block: u32,
+    assigned: usize,
+    // que escanear despues para que lo escaneado sirva cuanto antes
+    scan_next: Vec<ScanHint>,
+}
+
+#[derive(Serialize)]
+struct ScanHint {
+    block: u32,
+    role: &'static str,
+}
+
+// los bloques que faltan del disco, de la metadata a los datos (ver
+// missing::scan_order): con el superblock, el bitmap y la tabla de inodos un
+// escaneo a medias ya se monta degradado
+fn scan_hints(storage: &QrStorageManager) -> Vec<ScanHint> {
+    scan_next(storage, SCAN_HINTS)
+        .into_iter()
+        .map(|(block, role)| ScanHint {
+            block,
+            role: role.name(),
+        })
+        .collect()
+}
+
+// crea una sesion de ingesta, o la retoma si ya existe con ese nombre
+#[post("/ingest/start")]
+async fn ingest_start(data: web::Json<IngestStartData>, state: web::Data<AppState>) -> impl Responder {
+    let disks = state.disks.lock().unwrap();
+    let disk = &disks[*state.active.lock().unwrap()];
+    let mut sessions = disk.ingest.lock().unwrap();
+
+    let resumed = sessions.contains_key(&data.session);
+    let session = sessions
+        .entry(data.session.clone())
+        .or_insert_with(|| IngestSession::new(&data.session, data.start_block.unwrap_or(0)));
+
+    let message = if resumed {
+        format!("sesion '{}' retomada en bloque {}", session.name, session.next_block)
+    } else {
+        format!("sesion '{}' iniciada en bloque {}", session.name, session.start_block)
+    };
+    println!(">> {}", message);
+
+    let reply = IngestStatus {
+        status: "ok".to_string(),
+        message,
+        session: session.name.clone(),
+        next_block: session.next_block,
+        assigned: session.blocks.len(),
+        scan_next: scan_hints(&disk.storage),
+    };
+    save_ingest_sessions(&disk.path, &sessions);
+    HttpResponse::Ok().json(reply)
+}
+
+#[get("/ingest/{session}")]
+async fn ingest_status(path: web::Path<String>, state: web::Data<AppState>) -> impl Responder {
+    let disks = state.disks.lock().unwrap();
+    let disk = &disks[*state.active.lock().unwrap()];
+    let sessions = disk.ingest.lock().unwrap();
+
+    match sessions.get(path.as_str()) {
+        Some(session) => HttpResponse::Ok().json(IngestStatus {
+            status: "ok".to_string(),
+            message: format!("sesion '{}'", session.name),
+            session: session.name.clone(),
+            next_block: session.next_block,
+            assigned: session.blocks.len(),
+            scan_next: scan_hints(&disk.storage),
+        }),
+        None => HttpResponse::NotFound().json(ResponseMsg {
+            status: "error".to_string(),
+            message: format!("sesion de ingesta '{}' no existe", path.as_str()),
+        }),
+    }
+}
+
+#[derive(Serialize)]
+struct DiskHealth {
+    path: String,
+    active: bool,
+    health: HealthReport,
+}
+
+#[derive(Serialize)]
+struct HealthStatus {
+    // "ok" si ningun disco tiene bloques con fallas sin reparar
+    status: &'static str,
+    disks: Vec<DiskHealth>,
+}
+
+// fallas de lectura de cada disco desde que arranco el servidor (las de los
+// uploads, fsck y el montaje comparten el storage)
+#[get("/health")]
+async fn health_status(state: web::Data<AppState>) -> impl Responder {
+    let disks = state.disks.lock().unwrap();
+    let active = *state.active.lock().unwrap();
+    let disks: Vec<DiskHealth> = disks
+        .iter()
+        .enumerate()
+        .map(|(i, disk)| DiskHealth {
+            path: disk.path.clone(),
+            active: i == active,
+            health: disk.storage.health().map(|h| h.report()).unwrap_or_else(|| HealthMonitor::new().report()),
+        })
+        .collect();
+    let status = if disks.iter().all(|d| d.health.status != "con fallas") {
+        "ok"
+    } else {
+        "con fallas"
+    };
+    HttpResponse::Ok().json(HealthStatus { status, disks })
+}
+
+// sync entre servidores con copias del mismo disco (ver sync.rs)
+#[get("/sync/manifest")]
+async fn sync_manifest(state: web::Data<AppState>) -> impl Responder {
+    let storage = state.active_storage();
+    match web::block(move || sync::manifest(&storage)).await {
+        Ok(manifest) => HttpResponse::Ok().json(manifest),
+        Err(e) => HttpResponse::InternalServerError().json(ResponseMsg {
+            status: "error".to_string(),
+            message: e.to_string(),
+        }),
+    }
+}
+
+#[get("/sync/block/{id}")]
+async fn sync_block(path: web::Path<u32>, state: web::Data<AppState>) -> impl Responder {
+    let id = path.into_inner();
+    let storage = state.active_storage();
+    if id >= storage.total_blocks() {
+        return HttpResponse::BadRequest().json(ResponseMsg {
+            status: "error".to_string(),
+            message: format!("bloque {} fuera de rango", id),
+        });
+    }
+    match sync::export_block(&storage, id) {
+        Ok(Some(block)) => HttpResponse::Ok().json(block),
+        Ok(None) => HttpResponse::NotFound().json(ResponseMsg {
+            status: "error".to_string(),
+            message: format!("el bloque {} no esta escaneado", id),
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(ResponseMsg {
+            status: "error".to_string(),
+            message: e.to_string(),
+        }),
+    }
+}
+
+#[derive(Deserialize)]
+struct SyncPullData {
+    // http://host:puerto del otro servidor
+    peer: String,
+}
+
+#[derive(Serialize)]
+struct SyncPullResponse {
+    status: String,
+    disk: String,
+    pulled: Vec<u32>,
+    failed: Vec<(u32, String)>,
+}
+
+// el pedido trae Authorization: Bearer <token> del qrfs.toml; sin token
+// configurado el endpoint queda apagado
+fn authorized(req: &HttpRequest, state: &AppState) -> bool {
+    let Some(token) = state.config.server_token.as_deref() else {
+        return false;
+    };
+    let sent = req
+        .headers()
+        .get(actix_web::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or("");
+    // sin cortar en el primer byte distinto
+    sent.len() == token.len() && sent.bytes().zip(token.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+// pull solo desde los peers del qrfs.toml: si no, el servidor le pide a
+// cualquier direccion de la red lo que diga el cliente
+#[post("/sync/pull")]
+async fn sync_pull(req: HttpRequest, data: web::Json<SyncPullData>, state: web::Data<AppState>) -> impl Responder {
+    if !authorized(&req, &state) {
+        return HttpResponse::Unauthorized().json(ResponseMsg {
+            status: "error".to_string(),
+            message: "falta el token de [server] (Authorization: Bearer ...)".to_string(),
+        });
+    }
+    if let Err((code, message)) = check_writable(&state) {
+        return HttpResponse::build(code).json(ResponseMsg {
+            status: "error".to_string(),
+            message,
+        });
+    }
+    let peer = data.into_inner().peer;
+    let known = |p: &String| p.trim_end_matches('/') == peer.trim_end_matches('/');
+    if !state.config.server_peers.iter().any(known) {
+        return HttpResponse::Forbidden().json(ResponseMsg {
+            status: "error".to_string(),
+            message: format!("{} no esta en [server] peers", peer),
+        });
+    }
+
+    let path = {
+        let disks = state.disks.lock().unwrap();
+        disks[*state.active.lock().unwrap()].path.clone()
+    };
+    let storage = state.active_storage();
+
+    println!(">> sync de {} desde {}", path, peer);
+    let result = web::block(move || sync::pull(&storage, &peer)).await;
+    match result {
+        Ok(Ok(result)) => {
+            for &block_id in &result.pulled {
+                state.block_arrived(block_id);
+            }
+            println!(
+                ">> sync terminado: {} bloques traidos, {} con error",
+                result.pulled.len(),
+                result.failed.len()
+            );
+            HttpResponse::Ok().json(SyncPullResponse {
+                status: if result.failed.is_empty() { "ok" } else { "parcial" }.to_string(),
+                disk: path,
+                pulled: result.pulled,
+                failed: result.failed,
+            })
+        }
+        Ok(Err(e)) => HttpResponse::BadGateway().json(ResponseMsg {
+            status: "error".to_string(),
+            message: format!("sync fallo: {}", e),
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(ResponseMsg {
+            status: "error".to_string(),
+            message: format!("sync fallo: {}", e),
+        }),
+    }
+}
+
+// servicio de bloques para apps nativas (ver blockrpc.rs): los mensajes se
+// procesan a medida que llegan, sin esperar el cuerpo entero. cada bloque
+// cuenta para el limite de uploads, como un POST /upload
+#[post("/qrfs.blocks.BlockService/Upload")]
+async fn rpc_upload(req: HttpRequest, mut payload: web::Payload, state: web::Data<AppState>) -> HttpResponse {
+    if let Err((code, message)) = check_writable(&state) {
+        return HttpResponse::build(code).body(message);
+    }
+    let client = req.peer_addr().map(|addr| addr.ip());
+    let storage = state.active_storage();
+    let max = blockrpc::max_block_message(storage.block_size());
+
+    let mut pending = Vec::new();
+    let mut acks = Vec::new();
+    let (mut stored, mut rejected) = (0, 0);
+    while let Some(chunk) = payload.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+        };
+        pending.extend_from_slice(&chunk);
+        let frames = match blockrpc::take_frames(&mut pending, max) {
+            Ok(frames) => frames,
+            Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+        };
+        for frame in frames {
+            let block = match blockrpc::Block::decode(&frame) {
+                Ok(block) => block,
+                Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
             };
-            
-            let storage = state.storage.lock().unwrap();
-            
-            match storage.write_block(block_id as u32, &bytes) {
-                Ok(_) => {
-                    println!(">> bloque {} guardado correctamente", block_id);
-                    return HttpResponse::Ok().json(AutoScanResponse {
-                        status: "ok".to_string(),
-                        message: format!("bloque {} guardado", block_id),
-                        block_id: block_id as u32,
-                    });
-                },
-                Err(e) => {
-                    return HttpResponse::Ok().json(AutoScanResponse {
-                        status: "error".to_string(),
-                        message: format!("error escribiendo: {}", e),
-                        block_id: 0,
-                    });
+            let ack = if client.is_some_and(|ip| !state.limiter.allow(ip)) {
+                BlockAck {
+                    id: block.id,
+                    ok: false,
+                    error: "demasiados uploads, espera un momento".to_string(),
+                }
+            } else {
+                // escribir el png (o el contenedor) no va en el executor
+                let storage = storage.clone();
+                match web::block(move || blockrpc::store_block(&storage, &block)).await {
+                    Ok(ack) => ack,
+                    Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
                 }
+            };
+            if ack.ok {
+                stored += 1;
+                state.block_arrived(ack.id);
+            } else {
+                rejected += 1;
+                eprintln!("   rpc: bloque {} rechazado: {}", ack.id, ack.error);
             }
+            blockrpc::frame(&ack.encode(), &mut acks);
         }
     }
-    
-    let bytes = match general_purpose::STANDARD.decode(&data.content) {
-        Ok(b) => b,
-        Err(_) => {
-            match general_purpose::URL_SAFE_NO_PAD.decode(&data.content) {
-                Ok(b) => b,
-                Err(e) => {
-                    return HttpResponse::Ok().json(AutoScanResponse {
-                        status: "error".to_string(),
-                        message: format!("qr corrupto: {}", e),
-                        block_id: 0,
-                    });
-                }
+    if !pending.is_empty() {
+        return HttpResponse::BadRequest().body("mensaje cortado al final del cuerpo");
+    }
+    println!(">> rpc: {} bloques guardados, {} rechazados", stored, rejected);
+    HttpResponse::Ok().content_type(blockrpc::CONTENT_TYPE).body(acks)
+}
+
+#[post("/qrfs.blocks.BlockService/Download")]
+async fn rpc_download(body: web::Bytes, state: web::Data<AppState>) -> HttpResponse {
+    let mut body = body.to_vec();
+    let range = match blockrpc::take_frames(&mut body, 64) {
+        Ok(frames) if frames.len() == 1 && body.is_empty() => BlockRange::decode(&frames[0]),
+        Ok(_) => Err(QrfsError::Other("se espera un solo BlockRange".into())),
+        Err(e) => Err(e),
+    };
+    let range = match range {
+        Ok(range) => range,
+        Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+    };
+    let storage = state.active_storage();
+    match web::block(move || blockrpc::read_range(&storage, range)).await {
+        Ok(Ok(blocks)) => {
+            let mut out = Vec::new();
+            for block in blocks {
+                blockrpc::frame(&block.encode(), &mut out);
+            }
+            HttpResponse::Ok().content_type(blockrpc::CONTENT_TYPE).body(out)
+        }
+        Ok(Err(e)) => HttpResponse::BadRequest().body(e.to_string()),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[derive(Serialize)]
+struct ScanNext {
+    status: String,
+    disk: String,
+    scan_next: Vec<ScanHint>,
+}
+
+// que escanear despues en el disco activo (uploads con id, sin sesion)
+#[get("/scan/next")]
+async fn scan_next_blocks(state: web::Data<AppState>) -> impl Responder {
+    let disks = state.disks.lock().unwrap();
+    let disk = &disks[*state.active.lock().unwrap()];
+    HttpResponse::Ok().json(ScanNext {
+        status: "ok".to_string(),
+        disk: disk.path.clone(),
+        scan_next: scan_hints(&disk.storage),
+    })
+}
+
+#[derive(Serialize, Clone)]
+struct FsckReport {
+    disk: String,
+    checked_at: u64,
+    // clean y checks (ver qrfs_core::fsck)
+    #[serde(flatten)]
+    report: fsck::FsckReport,
+    // bloques en uso sin png (metadata o datos todavia no escaneados, o datos en cero)
+    missing_blocks: Vec<u32>,
+    // bloques con png que no se pudo decodificar
+    unreadable_blocks: Vec<u32>,
+}
+
+// decodifica todos los qr y corre los pasos de fsck sobre lo que se pudo
+// leer (lo que falta cuenta como ceros). a diferencia de fsck.qrfs no se
+// detiene en el primer error
+fn run_fsck(path: &str, storage: &QrStorageManager) -> FsckReport {
+    let mut missing_blocks = Vec::new();
+    let mut unreadable_blocks = Vec::new();
+    let mut qr_errors = Vec::new();
+    let data_start = read_superblock(storage).map_or(u32::MAX, |sb| sb.data_block_start);
+    let copy = InMemoryBlockStorage::new(storage.total_blocks(), storage.block_size());
+    for id in 0..storage.total_blocks() {
+        // sin png = bloque en cero; en la zona de metadata eso significa que falta
+        if !storage.block_path(id).exists() {
+            if id < data_start {
+                missing_blocks.push(id);
+            }
+            continue;
+        }
+        match storage.read_block(id).and_then(|data| copy.write_block(id, &data)) {
+            Ok(()) => {}
+            Err(e) => {
+                unreadable_blocks.push(id);
+                qr_errors.push(format!("bloque {}: {}", id, e));
             }
         }
+    }
+    if !missing_blocks.is_empty() {
+        qr_errors.push(format!("{} bloques de metadata sin escanear", missing_blocks.len()));
+    }
+
+    let mut report = fsck::check(&copy, &FsckOptions::default());
+    report.add("qr", qr_errors);
+
+    // bloques de datos en uso sin png: se leen como ceros, puede que falte escanearlos
+    for &blk in &report.blocks_in_use {
+        if blk < storage.total_blocks() && !storage.block_path(blk).exists() && !missing_blocks.contains(&blk) {
+            missing_blocks.push(blk);
+        }
+    }
+
+    FsckReport {
+        disk: path.to_string(),
+        checked_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+        report,
+        missing_blocks,
+        unreadable_blocks,
+    }
+}
+
+// verifica el disco activo (puede tardar: decodifica todos los qr)
+#[post("/fsck")]
+async fn fsck_disk(state: web::Data<AppState>) -> impl Responder {
+    let path = {
+        let disks = state.disks.lock().unwrap();
+        disks[*state.active.lock().unwrap()].path.clone()
     };
-    
-    let storage = state.storage.lock().unwrap();
-    
-    for block_id in 0..storage.total_blocks() {
-        let path_str = std::env::args().nth(1).unwrap_or_else(|| ".".to_string());
-        let path = format!("{}/{:06}.png", path_str, block_id);
-        
-        if !std::path::Path::new(&path).exists() {
-            match storage.write_block(block_id, &bytes) {
-                Ok(_) => {
-                    println!(">> bloque {} guardado automaticamente", block_id);
-                    return HttpResponse::Ok().json(AutoScanResponse {
-                        status: "ok".to_string(),
-                        message: format!("bloque {} guardado", block_id),
-                        block_id,
-                    });
-                },
-                Err(e) => {
-                    return HttpResponse::Ok().json(AutoScanResponse {
-                        status: "error".to_string(),
-                        message: format!("error escribiendo: {}", e),
-                        block_id: 0,
-                    });
-                }
+    let storage = state.active_storage();
+
+    println!(">> fsck de {} iniciado", path);
+    let result = web::block(move || {
+        run_fsck(&path, &storage)
+    })
+    .await;
+
+    match result {
+        Ok(report) => {
+            println!(
+                ">> fsck terminado: {}",
+                if report.report.clean { "limpio" } else { "con errores" }
+            );
+            *state.last_fsck.lock().unwrap() = Some(report.clone());
+            HttpResponse::Ok().json(report)
+        }
+        Err(e) => HttpResponse::InternalServerError().json(ResponseMsg {
+            status: "error".to_string(),
+            message: format!("fsck fallo: {}", e),
+        }),
+    }
+}
+
+#[get("/fsck/last")]
+async fn fsck_last(state: web::Data<AppState>) -> impl Responder {
+    match state.last_fsck.lock().unwrap().as_ref() {
+        Some(report) => HttpResponse::Ok().json(report),
+        None => HttpResponse::NotFound().json(ResponseMsg {
+            status: "error".to_string(),
+            message: "todavia no se ha ejecutado fsck".to_string(),
+        }),
+    }
+}
+
+#[derive(Deserialize)]
+struct MountData {
+    mountpoint: String,
+    // vista fija de solo lectura: los bloques que sigan llegando no la cambian
+    #[serde(default)]
+    snapshot: bool,
+    // frase de un disco cifrado si el servidor no la tiene
+    #[serde(default)]
+    passphrase: Option<String>,
+}
+
+// abre la clave maestra del disco activo si esta cifrado, con la frase del
+// pedido o la que ya tenia el servidor; la que funciona queda guardada para
+// los proximos montajes
+fn unlock_active<B: BlockStorage + 'static>(
+    state: &AppState,
+    fs: QrfsFilesystem<B>,
+    requested: Option<&str>,
+) -> Result<QrfsFilesystem<B>, QrfsError> {
+    if !fs.is_encrypted() {
+        return Ok(fs);
+    }
+    let disks = state.disks.lock().unwrap();
+    let mut known = disks[*state.active.lock().unwrap()].passphrase.lock().unwrap();
+    let passphrase = match requested.or(known.as_deref()) {
+        Some(passphrase) => passphrase.to_string(),
+        None => return Err(QrfsError::NotPermitted("disco cifrado: falta la frase".into())),
+    };
+    let fs = fs.with_passphrase(&passphrase)?;
+    *known = Some(passphrase);
+    Ok(fs)
+}
+
+// monta el disco activo en segundo plano. el punto de montaje va adentro de
+// mount_root, y abrir el disco, liberar huerfanos y montar corre afuera del
+// executor (web::block) sin tener tomado el candado del montaje
+#[post("/mount")]
+async fn mount_disk(data: web::Json<MountData>, state: web::Data<AppState>) -> impl Responder {
+    if let Some(active) = state.mount.lock().unwrap().as_ref() {
+        return HttpResponse::Conflict().json(ResponseMsg {
+            status: "error".to_string(),
+            message: format!("{} ya esta montado en {}", active.disk, active.mountpoint),
+        });
+    }
+
+    let mountpoint = match confined(&state.mount_root, &data.mountpoint) {
+        Ok(path) => path,
+        Err(message) => {
+            return HttpResponse::BadRequest().json(ResponseMsg {
+                status: "error".to_string(),
+                message,
+            })
+        }
+    };
+    let disk = {
+        let disks = state.disks.lock().unwrap();
+        disks[*state.active.lock().unwrap()].path.clone()
+    };
+    let data = data.into_inner();
+
+    let result = {
+        let state = state.clone();
+        let mountpoint = mountpoint.clone();
+        web::block(move || {
+            if data.snapshot {
+                mount_snapshot(&state, &mountpoint, data.passphrase.as_deref())
+                    .map(|session| (session, " (instantanea, solo lectura)"))
+            } else {
+                mount_live(&state, &mountpoint, data.passphrase.as_deref())
+                    .map(|(session, read_only)| (session, if read_only { " (solo lectura, corra fsck)" } else { "" }))
+            }
+        })
+        .await
+        .unwrap_or_else(|e| Err(QrfsError::Other(e.to_string())))
+    };
+    let mountpoint = mountpoint.to_string_lossy().into_owned();
+
+    match result {
+        Ok((session, suffix)) => {
+            let mut mount = state.mount.lock().unwrap();
+            // otro POST /mount gano mientras este montaba: se desmonta el propio
+            if let Some(active) = mount.as_ref() {
+                return HttpResponse::Conflict().json(ResponseMsg {
+                    status: "error".to_string(),
+                    message: format!("{} ya esta montado en {}", active.disk, active.mountpoint),
+                });
             }
+            println!(">> {} montado en {}{}", disk, mountpoint, suffix);
+            let message = format!("{} montado en {}{}", disk, mountpoint, suffix);
+            *mount = Some(ActiveMount {
+                disk,
+                mountpoint,
+                session,
+            });
+            HttpResponse::Ok().json(ResponseMsg {
+                status: "ok".to_string(),
+                message,
+            })
+        }
+        Err(e) => {
+            eprintln!(">> error montando {}: {}", disk, e);
+            HttpResponse::InternalServerError().json(ResponseMsg {
+                status: "error".to_string(),
+                message: format!("no se pudo montar: {}", e),
+            })
         }
     }
-    
-    HttpResponse::Ok().json(AutoScanResponse {
+}
+
+// el disco activo tal cual; si la verificacion rapida falla se monta en
+// solo lectura
+fn mount_live(
+    state: &AppState,
+    mountpoint: &Path,
+    passphrase: Option<&str>,
+) -> Result<(MountSession, bool), QrfsError> {
+    std::fs::create_dir_all(mountpoint)?;
+    // sin writeback cache: el escaner puede escribir bloques con el disco montado
+    let fs = unlock_active(state, QrfsFilesystem::new(state.active_storage())?, passphrase)?;
+    let mut fs = fs
+        .with_max_dirty_blocks(state.config.dirty_blocks)
+        .with_shared_disk(state.force_shared)
+        .with_events(state.events.clone())
+        .with_quick_check();
+    let read_only = fs.is_read_only();
+    if !read_only {
+        let inodes = fs.reclaim_orphan_inodes()?;
+        if !inodes.is_empty() {
+            println!(">> {} inodos huerfanos liberados", inodes.len());
+        }
+        let orphans = fs.reclaim_orphan_blocks()?;
+        if !orphans.is_empty() {
+            println!(">> {} bloques huerfanos liberados", orphans.len());
+        }
+    }
+    fs.spawn_mount(mountpoint).map(|session| (session, read_only))
+}
+
+// instantanea del disco activo: se puede revisar un escaneo a medias mientras
+// el escaner sigue subiendo bloques
+fn mount_snapshot(state: &AppState, mountpoint: &Path, passphrase: Option<&str>) -> Result<MountSession, QrfsError> {
+    let storage = state.active_storage();
+    let blocks = SnapshotBlocks::new();
+    storage.register_snapshot(&blocks);
+
+    std::fs::create_dir_all(mountpoint)?;
+    let fs = QrfsFilesystem::new(Arc::new(SnapshotStorage::new(storage, blocks)))?;
+    unlock_active(state, fs, passphrase)?.with_snapshot()?.spawn_mount(mountpoint)
+}
+
+#[post("/unmount")]
+async fn unmount_disk(state: web::Data<AppState>) -> impl Responder {
+    match state.mount.lock().unwrap().take() {
+        Some(active) => {
+            // soltar la sesion desmonta el sistema de archivos
+            drop(active.session);
+            println!(">> {} desmontado de {}", active.disk, active.mountpoint);
+            HttpResponse::Ok().json(ResponseMsg {
+                status: "ok".to_string(),
+                message: format!("{} desmontado", active.mountpoint),
+            })
+        }
+        None => HttpResponse::Conflict().json(ResponseMsg {
+            status: "error".to_string(),
+            message: "no hay ningun disco montado".to_string(),
+        }),
+    }
+}
+
+// responde los errores de parseo json con el mismo formato que el resto de la api
+fn json_error(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let code = match &err {
+        JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+            StatusCode::PAYLOAD_TOO_LARGE
+        }
+        _ => StatusCode::BAD_REQUEST,
+    };
+    let response = HttpResponse::build(code).json(ResponseMsg {
         status: "error".to_string(),
-        message: "no hay bloques disponibles".to_string(),
-        block_id: 0,
-    })
+        message: format!("peticion invalida: {}", err),
+    });
+    InternalError::from_response(err, response).into()
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("uso: cargo run --bin server <carpeta_disco_qr>");
+    if args.len() < 2 {
+        eprintln!(
+            "uso: cargo run --bin server <carpeta_disco_qr> [--block-size N] [--blocks N] [--rate-limit N] [--static-dir DIR] [--bind ADDR] [--data-dir DIR] [--mount-root DIR] [--force-shared] [--key-file F] [--keyring] [--config qrfs.toml]"
+        );
         std::process::exit(1);
     }
 
     let qr_folder = &args[1];
-    
-    std::fs::create_dir_all(qr_folder)?;
+    // perfil del disco (--config o qrfs.toml de la carpeta); las banderas lo pisan
+    let mut config = QrfsConfig::for_disk(qr_folder, config_arg(&args))
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+    let mut static_dir = PathBuf::from(DEFAULT_STATIC_DIR);
+    let mut force_shared = false;
+
+    // geometria para discos nuevos; si la carpeta ya tiene superblock se usa la de el
+    let mut i = 2;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--block-size" if i + 1 < args.len() => {
+                if let Ok(n) = args[i + 1].parse::<usize>() {
+                    config.block_size = n;
+                }
+                i += 1;
+            }
+            "--blocks" if i + 1 < args.len() => {
+                if let Ok(n) = args[i + 1].parse::<u32>() {
+                    config.total_blocks = n;
+                }
+                i += 1;
+            }
+            "--static-dir" if i + 1 < args.len() => {
+                static_dir = PathBuf::from(&args[i + 1]);
+                i += 1;
+            }
+            "--bind" if i + 1 < args.len() => {
+                config.server_bind = args[i + 1].clone();
+                i += 1;
+            }
+            "--mount-root" if i + 1 < args.len() => {
+                config.server_mount_root = Some(args[i + 1].clone());
+                i += 1;
+            }
+            "--data-dir" if i + 1 < args.len() => {
+                config.server_data_dir = Some(args[i + 1].clone());
+                i += 1;
+            }
+            "--rate-limit" if i + 1 < args.len() => {
+                if let Ok(n) = args[i + 1].parse::<u32>() {
+                    config.rate_limit = n;
+                }
+                i += 1;
+            }
+            "--force-shared" => {
+                force_shared = true;
+            }
+            // ya leidos arriba
+            "--config" | "--key-file" if i + 1 < args.len() => {
+                i += 1;
+            }
+            "--keyring" => {}
+            other => {
+                eprintln!("server: argumento desconocido '{}'", other);
+            }
+        }
+        i += 1;
+    }
+
+    config.validate().map_err(|e| std::io::Error::other(e.to_string()))?;
+    // sin data_dir los discos nuevos van al lado del primero
+    let data_dir = match &config.server_data_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(qr_folder).parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
+    let data_dir = if data_dir.as_os_str().is_empty() { PathBuf::from(".") } else { data_dir };
+    let mount_root = config.server_mount_root.as_ref().map_or_else(|| data_dir.clone(), PathBuf::from);
 
-    let block_size = 128;
-    let total_blocks = 400;
+    let static_dir = if static_dir.is_dir() {
+        Some(static_dir)
+    } else {
+        println!("server: sin carpeta static, usando paginas embebidas");
+        None
+    };
 
-    let storage = QrStorageManager::new(qr_folder, block_size, total_blocks);
+    let disk = open_disk(qr_folder, config.block_size, config.total_blocks, &config, force_shared)
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+    // disco cifrado: la frase se pide (o se lee de --key-file / el llavero)
+    // una sola vez al arrancar, antes de aceptar conexiones
+    if let Some(sb) = QrStorageManager::probe_superblock(qr_folder)
+        .map_err(|e| std::io::Error::other(e.to_string()))?
+        .filter(|sb| !sb.key_slot.is_empty())
+    {
+        let passphrase = PassphraseSource::from_args(&args)
+            .unlock(qr_folder, "server: frase del disco: ", |p| check_passphrase(&sb.key_slot, p))
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        *disk.passphrase.lock().unwrap() = Some(passphrase);
+    }
+    let bind = (config.server_bind.clone(), config.server_port);
+    let events = EventBus::new();
     let app_state = web::Data::new(AppState {
-        storage: Arc::new(Mutex::new(storage)),
+        disks: Mutex::new(vec![disk]),
+        active: Mutex::new(0),
+        last_fsck: Mutex::new(None),
+        mount: Mutex::new(None),
+        limiter: RateLimiter::new(config.rate_limit),
+        static_dir: static_dir.clone(),
+        data_dir: data_dir.clone(),
+        mount_root,
+        force_shared,
+        events_origin: events.join(),
+        events,
+        config,
     });
 
     println!("=============================================");
     println!("servidor lector qrfs activo");
     println!("carpeta destino: {}", qr_folder);
+    println!("discos nuevos en: {}", data_dir.display());
+    println!("escuchando en:    {}:{} (--bind 0.0.0.0 para el celular)", bind.0, bind.1);
     println!("=============================================");
     println!();
     println!("modos disponibles:");
-    println!("  - modo manual:    http://IP:8080/");
-    println!("  - modo escaneo:   http://IP:8080/scanner");
+    println!("  - modo manual:    http://IP:{}/", bind.1);
+    println!("  - modo escaneo:   http://IP:{}/scanner", bind.1);
+    println!("  - panel:          http://IP:{}/dashboard", bind.1);
+    println!("  - discos:         GET/POST http://IP:{}/disks", bind.1);
+    println!("  - verificacion:   POST http://IP:{}/fsck", bind.1);
+    println!("  - montaje:        POST http://IP:{}/mount, /unmount", bind.1);
+    println!("  - disco cifrado:  POST http://IP:{}/mount {{\"passphrase\": \"...\"}}", bind.1);
+    println!("  - instantanea:    POST http://IP:{}/mount {{\"snapshot\": true}} (solo lectura)", bind.1);
+    println!("  - ingesta:        POST http://IP:{}/ingest/start (qrs sin metadata)", bind.1);
     println!();
 
     HttpServer::new(move || {
         App::new()
             .wrap(Cors::permissive())
             .app_data(app_state.clone())
+            .app_data(web::JsonConfig::default().limit(MAX_JSON_BODY).error_handler(json_error))
             .service(index)
             .service(scanner_page)
             .service(upload_block)
             .service(upload_auto)
+            .service(list_disks)
+            .service(create_disk)
+            .service(select_disk)
+            .service(fsck_disk)
+            .service(fsck_last)
+            .service(mount_disk)
+            .service(unmount_disk)
+            .service(ingest_start)
+            .service(ingest_status)
+            .service(scan_next_blocks)
+            .service(health_status)
+            .service(sync_manifest)
+            .service(sync_block)
+            .service(sync_pull)
+            .service(rpc_upload)
+            .service(rpc_download)
+            .service(dashboard_page)
+            .service(ui_manifest)
+            .configure(|cfg| {
+                // archivos extra (css, js, imagenes) junto a las paginas
+                if let Some(dir) = &static_dir {
+                    cfg.service(Files::new("/static", dir));
+                }
+            })
     })
-    .bind(("0.0.0.0", 8080))?
+    .bind(bind)?
     .run()
     .await
-}
\ No newline at end of file
+}
@@ -0,0 +1,132 @@
+// verify - compara un disco qrfs contra un manifiesto exportado
+// verify.qrfs <qr_folder|disco.img> --manifest disco.img.json [--config qrfs.toml]
+// el manifiesto es el json que deja disk_image to: por cada archivo se
+// comparan largo y sha-256. es el ultimo paso despues de reingestar un
+// archivo impreso; sale con codigo 1 si algun archivo no coincide
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::config::{config_arg, QrfsConfig};
+use qrfs_core::errors::QrfsError;
+use qrfs_core::sha256::{to_hex, Sha256};
+use qrfs_core::storage::{BlockStorage, ContainerStorage};
+
+// se lee de a pedazos: el hash no necesita el archivo entero en memoria
+const CHUNK: u64 = 1 << 20;
+
+#[derive(Deserialize)]
+struct Manifest {
+    files: Vec<ManifestFile>,
+}
+
+#[derive(Deserialize)]
+struct ManifestFile {
+    name: String,
+    size: u64,
+    // los manifiestos viejos no lo tienen: solo se compara el largo
+    #[serde(default)]
+    sha256: Option<String>,
+}
+
+fn main() {
+    match run() {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("verify.qrfs: error: {e}");
+            process::exit(1);
+        }
+    }
+}
+
+fn run() -> Result<bool, QrfsError> {
+    let args: Vec<String> = env::args().collect();
+
+    let manifest_path = args
+        .iter()
+        .position(|a| a == "--manifest")
+        .and_then(|i| args.get(i + 1));
+    let (Some(disk), Some(manifest_path)) = (args.get(1), manifest_path) else {
+        eprintln!("Uso: verify.qrfs <qr_folder|disco.img> --manifest <manifiesto.json> [--config qrfs.toml]");
+        return Ok(true);
+    };
+
+    let manifest: Manifest = serde_json::from_str(&fs::read_to_string(manifest_path)?)
+        .map_err(|e| QrfsError::Other(format!("manifiesto invalido '{}': {}", manifest_path, e)))?;
+
+    let config = QrfsConfig::for_disk(disk, config_arg(&args))?;
+    if Path::new(disk).is_file() {
+        verify(ContainerStorage::open_disk(disk)?, &manifest)
+    } else {
+        verify(config.qr_storage(disk), &manifest)
+    }
+}
+
+fn verify<B: BlockStorage + 'static>(storage: B, manifest: &Manifest) -> Result<bool, QrfsError> {
+    let api = QrfsApi::open(Arc::new(storage))?;
+
+    let mut failed = 0;
+    for file in &manifest.files {
+        match check_file(&api, file) {
+            Ok(None) => println!("OK     {} ({} bytes)", file.name, file.size),
+            Ok(Some(problem)) => {
+                failed += 1;
+                println!("FALLA  {}: {}", file.name, problem);
+            }
+            Err(e) => {
+                failed += 1;
+                println!("FALLA  {}: no se pudo leer: {}", file.name, e);
+            }
+        }
+    }
+
+    println!("--------------------------------------------------");
+    println!(
+        "verify.qrfs: {} de {} archivos coinciden con el manifiesto.",
+        manifest.files.len() - failed,
+        manifest.files.len()
+    );
+    Ok(failed == 0)
+}
+
+// None si coincide, si no la descripcion de la diferencia
+fn check_file<B: BlockStorage + 'static>(
+    api: &QrfsApi<B>,
+    file: &ManifestFile,
+) -> Result<Option<String>, QrfsError> {
+    let path = if file.name.starts_with('/') {
+        file.name.clone()
+    } else {
+        format!("/{}", file.name)
+    };
+    let size = api.stat(&path)?.size;
+    if size != file.size {
+        return Ok(Some(format!("{} bytes, el manifiesto dice {}", size, file.size)));
+    }
+    let Some(expected) = &file.sha256 else {
+        return Ok(None);
+    };
+
+    let mut hasher = Sha256::new();
+    let mut offset = 0;
+    while offset < size {
+        let chunk = api.read_at(&path, offset, CHUNK)?;
+        if chunk.is_empty() {
+            break;
+        }
+        offset += chunk.len() as u64;
+        hasher.update(&chunk);
+    }
+    let actual = to_hex(&hasher.finish());
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Ok(Some(format!("sha-256 {}, el manifiesto dice {}", actual, expected)));
+    }
+    Ok(None)
+}
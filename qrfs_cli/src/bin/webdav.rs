@@ -0,0 +1,383 @@
+// webdav - expone un disco qrfs como unidad de red (windows, macos, davfs2)
+// no necesita fuse: todas las operaciones pasan por QrfsApi
+
+use actix_web::http::header::{self, HttpDate};
+use actix_web::http::StatusCode;
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use qrfs_core::api::{FileInfo, QrfsApi};
+use qrfs_core::errors::QrfsError;
+use qrfs_core::storage::QrStorageManager;
+use std::env;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+// puerto por defecto (el lector qr ya usa el 8080)
+const DEFAULT_PORT: u16 = 8081;
+
+type DavApi = QrfsApi<QrStorageManager>;
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    if let Err(e) = run().await {
+        eprintln!("webdav.qrfs: error: {e}");
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+async fn run() -> Result<(), QrfsError> {
+    let args: Vec<String> = env::args().collect();
+
+    // sintaxis: webdav <qrfolder> [--port n] [--bind addr]
+    if args.len() < 2 {
+        eprintln!("Uso: webdav.qrfs <qrfolder/> [--port N] [--bind ADDR]");
+        return Ok(());
+    }
+
+    let qrfolder = &args[1];
+    let mut port = DEFAULT_PORT;
+    let mut bind = "0.0.0.0".to_string();
+
+    let mut i = 2;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--port" if i + 1 < args.len() => {
+                port = args[i + 1]
+                    .parse()
+                    .map_err(|_| QrfsError::Other(format!("puerto invalido: {}", args[i + 1])))?;
+                i += 1;
+            }
+            "--bind" if i + 1 < args.len() => {
+                bind = args[i + 1].clone();
+                i += 1;
+            }
+            other => eprintln!("webdav.qrfs: argumento ignorado: {}", other),
+        }
+        i += 1;
+    }
+
+    // la geometria sale del superblock; sin superblock no hay disco que servir
+    let sb = QrStorageManager::probe_superblock(qrfolder.as_str())?.ok_or_else(|| {
+        QrfsError::NotFormatted(format!("{} (ejecute mkfs primero)", qrfolder))
+    })?;
+    let storage = QrStorageManager::new(qrfolder, sb.block_size as usize, sb.total_blocks);
+    let api = web::Data::new(QrfsApi::open(Arc::new(storage))?);
+
+    // un PUT nunca puede superar la capacidad del disco
+    let max_body = sb.total_blocks as usize * sb.block_size as usize;
+
+    println!("webdav.qrfs: sirviendo '{}' en http://{}:{}/", qrfolder, bind, port);
+    println!("  - windows: net use Q: http://<ip>:{}/", port);
+    println!("  - macos:   finder > ir > conectarse al servidor");
+
+    HttpServer::new(move || {
+        App::new()
+            .app_data(api.clone())
+            .app_data(web::PayloadConfig::new(max_body))
+            .default_service(web::to(dispatch))
+    })
+    .bind((bind.as_str(), port))?
+    .run()
+    .await?;
+
+    Ok(())
+}
+
+// webdav usa metodos propios (PROPFIND, MOVE...), asi que se despacha a mano
+async fn dispatch(req: HttpRequest, body: web::Bytes, api: web::Data<DavApi>) -> HttpResponse {
+    let path = percent_decode(req.path());
+
+    let result = match req.method().as_str() {
+        "OPTIONS" => Ok(options()),
+        "PROPFIND" => propfind(&req, &api, &path),
+        "PROPPATCH" => proppatch(&path),
+        // en HEAD actix descarta el cuerpo pero conserva content-length
+        "GET" | "HEAD" => get(&api, &path),
+        "PUT" => put(&api, &path, &body),
+        "DELETE" => delete(&api, &path),
+        "MOVE" => move_or_copy(&req, &api, &path, true),
+        "COPY" => move_or_copy(&req, &api, &path, false),
+        "MKCOL" => Ok(text(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "qrfs solo tiene directorio raiz",
+        )),
+        "LOCK" => Ok(lock(&path)),
+        "UNLOCK" => Ok(HttpResponse::NoContent().finish()),
+        _ => Ok(text(StatusCode::METHOD_NOT_ALLOWED, "metodo no soportado")),
+    };
+
+    result.unwrap_or_else(error_response)
+}
+
+fn options() -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(("DAV", "1, 2"))
+        .insert_header(("MS-Author-Via", "DAV"))
+        .insert_header((
+            header::ALLOW,
+            "OPTIONS, PROPFIND, PROPPATCH, GET, HEAD, PUT, DELETE, MOVE, COPY, LOCK, UNLOCK",
+        ))
+        .finish()
+}
+
+fn propfind(req: &HttpRequest, api: &DavApi, path: &str) -> Result<HttpResponse, QrfsError> {
+    let depth = req
+        .headers()
+        .get("Depth")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("1");
+
+    let info = api.stat(path)?;
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n");
+
+    if info.is_dir() {
+        xml.push_str(&prop_response("/", &info));
+        // "infinity" se trata igual que 1: no hay subdirectorios
+        if depth != "0" {
+            for file in api.list("/")? {
+                xml.push_str(&prop_response(&format!("/{}", file.name), &file));
+            }
+        }
+    } else {
+        xml.push_str(&prop_response(path, &info));
+    }
+
+    xml.push_str("</D:multistatus>\n");
+    Ok(HttpResponse::build(StatusCode::MULTI_STATUS)
+        .content_type("application/xml; charset=utf-8")
+        .body(xml))
+}
+
+// un <D:response> con las propiedades que piden los clientes comunes
+fn prop_response(href: &str, info: &FileInfo) -> String {
+    let modified = HttpDate::from(UNIX_EPOCH + Duration::from_secs(info.modified_at));
+    let (resource_type, extra) = if info.is_dir() {
+        ("<D:collection/>".to_string(), String::new())
+    } else {
+        (
+            String::new(),
+            format!(
+                "<D:getcontentlength>{}</D:getcontentlength><D:getcontenttype>application/octet-stream</D:getcontenttype>",
+                info.size
+            ),
+        )
+    };
+
+    format!(
+        "<D:response><D:href>{}</D:href><D:propstat><D:prop>\
+         <D:displayname>{}</D:displayname>\
+         <D:resourcetype>{}</D:resourcetype>{}\
+         <D:getlastmodified>{}</D:getlastmodified>\
+         <D:getetag>\"{}-{}-{}\"</D:getetag>\
+         </D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n",
+        escape_xml(&percent_encode(href)),
+        escape_xml(&info.name),
+        resource_type,
+        extra,
+        modified,
+        info.inode_id,
+        info.size,
+        info.modified_at
+    )
+}
+
+// windows intenta fijar fechas con PROPPATCH; se acepta sin guardar nada
+fn proppatch(path: &str) -> Result<HttpResponse, QrfsError> {
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\
+         <D:response><D:href>{}</D:href><D:propstat><D:prop/>\
+         <D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response></D:multistatus>\n",
+        escape_xml(&percent_encode(path))
+    );
+    Ok(HttpResponse::build(StatusCode::MULTI_STATUS)
+        .content_type("application/xml; charset=utf-8")
+        .body(xml))
+}
+
+fn get(api: &DavApi, path: &str) -> Result<HttpResponse, QrfsError> {
+    let info = api.stat(path)?;
+    if info.is_dir() {
+        // listado simple para quien abra la url en un navegador
+        let mut html = String::from("<html><body><h1>qrfs</h1><ul>");
+        for file in api.list("/")? {
+            html.push_str(&format!(
+                "<li><a href=\"/{}\">{}</a> ({} bytes)</li>",
+                percent_encode(&file.name),
+                escape_xml(&file.name),
+                file.size
+            ));
+        }
+        html.push_str("</ul></body></html>");
+        return Ok(HttpResponse::Ok().content_type("text/html; charset=utf-8").body(html));
+    }
+
+    let modified = HttpDate::from(UNIX_EPOCH + Duration::from_secs(info.modified_at));
+    let mut builder = HttpResponse::Ok();
+    builder
+        .content_type("application/octet-stream")
+        .insert_header((header::LAST_MODIFIED, modified));
+
+    Ok(builder.body(api.read(path)?))
+}
+
+fn put(api: &DavApi, path: &str, body: &[u8]) -> Result<HttpResponse, QrfsError> {
+    if is_root(path) {
+        return Ok(text(StatusCode::METHOD_NOT_ALLOWED, "no se puede escribir en /"));
+    }
+
+    let existed = api.stat(path).is_ok();
+    api.write(path, body)?;
+
+    Ok(if existed {
+        HttpResponse::NoContent().finish()
+    } else {
+        HttpResponse::Created().finish()
+    })
+}
+
+fn delete(api: &DavApi, path: &str) -> Result<HttpResponse, QrfsError> {
+    if is_root(path) {
+        return Ok(text(StatusCode::FORBIDDEN, "no se puede borrar /"));
+    }
+    api.remove(path)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+fn move_or_copy(
+    req: &HttpRequest,
+    api: &DavApi,
+    path: &str,
+    is_move: bool,
+) -> Result<HttpResponse, QrfsError> {
+    let destination = match destination_path(req) {
+        Some(d) => d,
+        None => return Ok(text(StatusCode::BAD_REQUEST, "falta header Destination")),
+    };
+    if is_root(path) || is_root(&destination) {
+        return Ok(text(StatusCode::FORBIDDEN, "no se puede mover /"));
+    }
+
+    // Overwrite: F prohibe reemplazar el destino
+    let overwrite = req
+        .headers()
+        .get("Overwrite")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| !v.eq_ignore_ascii_case("F"))
+        .unwrap_or(true);
+
+    api.stat(path)?;
+    let existed = api.stat(&destination).is_ok();
+    if existed && !overwrite {
+        return Ok(text(StatusCode::PRECONDITION_FAILED, "el destino ya existe"));
+    }
+
+    if is_move {
+        api.rename(path, &destination)?;
+    } else {
+        let data = api.read(path)?;
+        api.write(&destination, &data)?;
+    }
+
+    Ok(if existed {
+        HttpResponse::NoContent().finish()
+    } else {
+        HttpResponse::Created().finish()
+    })
+}
+
+// windows y macos se niegan a escribir sin LOCK; se entrega un token falso
+fn lock(path: &str) -> HttpResponse {
+    let token = format!(
+        "opaquelocktoken:qrfs-{}",
+        std::time::SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+    );
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:prop xmlns:D=\"DAV:\"><D:lockdiscovery><D:activelock>\
+         <D:locktype><D:write/></D:locktype><D:lockscope><D:exclusive/></D:lockscope>\
+         <D:depth>0</D:depth><D:timeout>Second-3600</D:timeout>\
+         <D:locktoken><D:href>{}</D:href></D:locktoken>\
+         <D:lockroot><D:href>{}</D:href></D:lockroot>\
+         </D:activelock></D:lockdiscovery></D:prop>\n",
+        token,
+        escape_xml(&percent_encode(path))
+    );
+    HttpResponse::Ok()
+        .insert_header(("Lock-Token", format!("<{}>", token)))
+        .content_type("application/xml; charset=utf-8")
+        .body(xml)
+}
+
+// Destination viene como url absoluta: se queda solo el path
+fn destination_path(req: &HttpRequest) -> Option<String> {
+    let raw = req.headers().get("Destination")?.to_str().ok()?;
+    let path = match raw.find("://") {
+        Some(idx) => {
+            let rest = &raw[idx + 3..];
+            rest.find('/').map(|p| &rest[p..]).unwrap_or("/")
+        }
+        None => raw,
+    };
+    Some(percent_decode(path))
+}
+
+fn is_root(path: &str) -> bool {
+    path.trim_matches('/').is_empty()
+}
+
+fn error_response(e: QrfsError) -> HttpResponse {
+    let status = match e {
+        QrfsError::NotFound(_) => StatusCode::NOT_FOUND,
+        QrfsError::AlreadyExists(_) => StatusCode::CONFLICT,
+        QrfsError::NoSpace(_) => StatusCode::INSUFFICIENT_STORAGE,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    text(status, &e.to_string())
+}
+
+fn text(status: StatusCode, msg: &str) -> HttpResponse {
+    HttpResponse::build(status)
+        .content_type("text/plain; charset=utf-8")
+        .body(msg.to_string())
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(b) = u8::from_str_radix(hex, 16) {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn percent_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,12 @@
+// lo que comparten las pruebas de integracion
+
+use std::fs;
+use std::path::PathBuf;
+
+// carpeta vacia en el temporal del sistema, una por prueba y proceso
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("qrfs_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
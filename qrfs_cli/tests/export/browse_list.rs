@@ -0,0 +1,62 @@
+use std::fs;
+use std::process::Command;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::storage::QrStorageManager;
+
+use crate::common::temp_dir;
+
+#[test]
+fn list_mode_shows_files_and_flags_misplaced_qr() {
+    let dir = temp_dir("browse");
+    let qrfolder = dir.join("qr");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&qrfolder)
+        .args(["--blocks", "120"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+
+    let block = {
+        let storage = Arc::new(QrStorageManager::new(&qrfolder, BLOCK_SIZE, 120));
+        let api = QrfsApi::open(storage).unwrap();
+        api.write("/nota.txt", b"hola").unwrap();
+        api.write("/datos.bin", &[7u8; 300]).unwrap();
+        drop(api);
+        let out = Command::new(env!("CARGO_BIN_EXE_browse"))
+            .arg(&qrfolder)
+            .arg("--list")
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        let text = String::from_utf8_lossy(&out.stdout).to_string();
+        assert!(text.contains("nota.txt"), "{}", text);
+        assert!(!text.contains("MAL"), "{}", text);
+
+        // primer bloque de datos.bin, sacado del mapa que imprime browse
+        let line = text
+            .lines()
+            .skip_while(|l| !l.starts_with("datos.bin"))
+            .nth(1)
+            .unwrap()
+            .to_string();
+        let first = line.split_whitespace().nth(1).unwrap();
+        first.split(':').next().unwrap().parse::<u32>().unwrap()
+    };
+
+    // pisar ese qr con el de otro bloque: decodifica, pero no es el suyo
+    fs::copy(qrfolder.join("000000.png"), qrfolder.join(format!("{:06}.png", block))).unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_browse"))
+        .arg(&qrfolder)
+        .arg("--list")
+        .output()
+        .unwrap();
+    let text = String::from_utf8_lossy(&out.stdout);
+    assert!(text.contains(&format!("{}:MAL", block)), "{}", text);
+    assert!(text.contains("1 ilegibles"), "{}", text);
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,78 @@
+use std::fs;
+use std::process::Command;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::bundle::open_bundle;
+use qrfs_core::decode_cache::DECODE_CACHE_FILE;
+use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::storage::{ContainerStorage, QrStorageManager};
+
+use crate::common::temp_dir;
+
+#[test]
+fn a_damaged_bundle_unpacks_with_parity_into_a_folder_and_an_image() {
+    let dir = temp_dir("bundle");
+    let original = dir.join("original");
+    let bundle = dir.join("disco.qrb");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&original)
+        .args(["--blocks", "128"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+
+    let data: Vec<u8> = (0..1500u32).map(|i| (i * 13 % 251) as u8).collect();
+    {
+        let api = QrfsApi::open(Arc::new(QrStorageManager::new(&original, BLOCK_SIZE, 128))).unwrap();
+        api.write("/datos.bin", &data).unwrap();
+        api.write("/nota.txt", b"en un solo archivo").unwrap();
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_bundle"))
+        .arg("pack")
+        .arg(&original)
+        .arg(&bundle)
+        .args(["--parity", "4"])
+        .output()
+        .expect("no se pudo ejecutar bundle");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    // un byte dañado en el ultimo bloque de datos: la paridad lo arregla
+    let mut bytes = fs::read(&bundle).unwrap();
+    let manifest = open_bundle(&bytes).unwrap().manifest;
+    let last = bytes.len() - manifest.parity_crcs.len() * manifest.block_size - 1;
+    bytes[last] ^= 0x5a;
+    fs::write(&bundle, &bytes).unwrap();
+
+    let folder = dir.join("copia");
+    let output = Command::new(env!("CARGO_BIN_EXE_bundle"))
+        .arg("unpack")
+        .arg(&bundle)
+        .arg(&folder)
+        .output()
+        .expect("no se pudo ejecutar bundle");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("rearmados con la paridad"));
+    {
+        // se lee de los qr redibujados, sin la cache de decodificacion
+        let _ = fs::remove_file(folder.join(DECODE_CACHE_FILE));
+        let api = QrfsApi::open(Arc::new(QrStorageManager::new(&folder, BLOCK_SIZE, 128))).unwrap();
+        assert_eq!(api.read("/datos.bin").unwrap(), data);
+        assert_eq!(api.read("/nota.txt").unwrap(), b"en un solo archivo");
+    }
+
+    let image = dir.join("copia.img");
+    let status = Command::new(env!("CARGO_BIN_EXE_bundle"))
+        .arg("unpack")
+        .arg(&bundle)
+        .arg(&image)
+        .status()
+        .expect("no se pudo ejecutar bundle");
+    assert!(status.success());
+    let api = QrfsApi::open(Arc::new(ContainerStorage::open(&image, BLOCK_SIZE).unwrap())).unwrap();
+    assert_eq!(api.read("/datos.bin").unwrap(), data);
+
+    let _ = fs::remove_dir_all(&dir);
+}
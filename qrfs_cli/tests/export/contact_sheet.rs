@@ -0,0 +1,61 @@
+use std::fs;
+use std::process::Command;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::fs_format::read_inode_table;
+use qrfs_core::storage::QrStorageManager;
+
+use crate::common::temp_dir;
+
+#[test]
+fn sheet_counts_missing_blocks_and_writes_png_and_pdf() {
+    let dir = temp_dir("sheet");
+    let qrfolder = dir.join("qr");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&qrfolder)
+        .args(["--blocks", "120"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+
+    let last_block = {
+        let storage = Arc::new(QrStorageManager::new(&qrfolder, BLOCK_SIZE, 120));
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        api.write("/datos.bin", &[3u8; 300]).unwrap();
+        let id = api.stat("/datos.bin").unwrap().inode_id;
+        let inode = read_inode_table(storage.as_ref(), &api.superblock())
+            .filter_map(|slot| slot.ok())
+            .find(|inode| inode.id == id)
+            .unwrap();
+        *inode.blocks.last().unwrap()
+    };
+    fs::remove_file(qrfolder.join(format!("{:06}.png", last_block))).unwrap();
+
+    let png = dir.join("hoja.png");
+    let out = Command::new(env!("CARGO_BIN_EXE_contact_sheet"))
+        .arg(&qrfolder)
+        .arg(&png)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let text = String::from_utf8_lossy(&out.stdout);
+    assert!(text.contains("1 faltan, 0 corruptos"), "{}", text);
+    assert!(fs::read(&png).unwrap().starts_with(b"\x89PNG"));
+
+    let pdf = dir.join("hoja.pdf");
+    let out = Command::new(env!("CARGO_BIN_EXE_contact_sheet"))
+        .arg(&qrfolder)
+        .arg(&pdf)
+        .args(["--rows", "2"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let bytes = fs::read(&pdf).unwrap();
+    assert!(bytes.starts_with(b"%PDF-1.4"));
+    assert!(bytes.ends_with(b"%%EOF\n"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
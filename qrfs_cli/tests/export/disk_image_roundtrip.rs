@@ -0,0 +1,74 @@
+use std::fs;
+use std::process::Command;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::storage::{ContainerStorage, QrStorageManager};
+
+use crate::common::temp_dir;
+
+#[test]
+fn image_roundtrip_keeps_files_and_lists_their_blocks() {
+    let dir = temp_dir("image");
+    let container = dir.join("origen.img");
+    let image = dir.join("volcado.img");
+    let qrfolder = dir.join("qr");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&container)
+        .args(["--blocks", "200", "--container"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+
+    let data: Vec<u8> = (0..600u32).map(|i| (i % 251) as u8).collect();
+    {
+        let storage = ContainerStorage::open(&container, BLOCK_SIZE).unwrap();
+        let api = QrfsApi::open(Arc::new(storage)).unwrap();
+        api.write("/datos.bin", &data).unwrap();
+    }
+
+    let status = Command::new(env!("CARGO_BIN_EXE_disk_image"))
+        .arg("to")
+        .arg(&container)
+        .arg(&image)
+        .status()
+        .expect("no se pudo ejecutar disk_image");
+    assert!(status.success());
+
+    // con la lista de bloques del json el archivo se arma sin qrfs
+    let manifest: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("volcado.img.json")).unwrap()).unwrap();
+    let file = &manifest["files"][0];
+    assert_eq!(file["name"], "datos.bin");
+    let raw = fs::read(&image).unwrap();
+    let mut rebuilt = Vec::new();
+    for block in file["blocks"].as_array().unwrap() {
+        let start = block.as_u64().unwrap() as usize * BLOCK_SIZE;
+        rebuilt.extend_from_slice(&raw[start..start + BLOCK_SIZE]);
+    }
+    rebuilt.truncate(file["size"].as_u64().unwrap() as usize);
+    assert_eq!(rebuilt, data);
+
+    // el orden de escaneo empieza por el superblock y termina en los datos
+    let order = manifest["scan_order"].as_array().unwrap();
+    assert_eq!(order[0]["block"], 0);
+    assert_eq!(order[0]["role"], "superblock");
+    assert_eq!(order.last().unwrap()["role"], "datos");
+    assert_eq!(order.last().unwrap()["block"], *file["blocks"].as_array().unwrap().last().unwrap());
+
+    let status = Command::new(env!("CARGO_BIN_EXE_disk_image"))
+        .arg("from")
+        .arg(&image)
+        .arg(&qrfolder)
+        .status()
+        .expect("no se pudo ejecutar disk_image");
+    assert!(status.success());
+
+    let storage = QrStorageManager::new(&qrfolder, BLOCK_SIZE, 200);
+    let api = QrfsApi::open(Arc::new(storage)).unwrap();
+    assert_eq!(api.read("/datos.bin").unwrap(), data);
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,12 @@
+// lo que sale del disco: imagenes, paquetes, zip, sitio estatico, hojas y manifiestos
+
+#[path = "../common/mod.rs"]
+mod common;
+
+mod browse_list;
+mod bundle_roundtrip;
+mod contact_sheet;
+mod disk_image_roundtrip;
+mod qr_zip_export;
+mod static_site;
+mod verify_manifest;
@@ -0,0 +1,81 @@
+use std::fs;
+use std::process::Command;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::storage::QrStorageManager;
+
+use crate::common::temp_dir;
+
+// nombres y contenidos de las entradas, recorriendo los encabezados locales
+fn zip_entries(zip: &[u8]) -> Vec<(String, Vec<u8>)> {
+    let u16_at = |pos: usize| u16::from_le_bytes([zip[pos], zip[pos + 1]]) as usize;
+    let u32_at = |pos: usize| u32::from_le_bytes(zip[pos..pos + 4].try_into().unwrap()) as usize;
+    let mut entries = Vec::new();
+    let mut pos = 0;
+    while u32_at(pos) == 0x04034b50 {
+        let len = u32_at(pos + 18);
+        let name_len = u16_at(pos + 26);
+        let start = pos + 30 + name_len + u16_at(pos + 28);
+        let name = String::from_utf8(zip[pos + 30..pos + 30 + name_len].to_vec()).unwrap();
+        entries.push((name, zip[start..start + len].to_vec()));
+        pos = start + len;
+    }
+    // despues de las entradas viene el directorio central
+    assert_eq!(u32_at(pos), 0x02014b50);
+    entries
+}
+
+#[test]
+fn zip_export_packs_each_file_with_its_qrs() {
+    let dir = temp_dir("zip");
+    let qrfolder = dir.join("qr");
+    let out = dir.join("zips");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&qrfolder)
+        .args(["--blocks", "200"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+
+    let data: Vec<u8> = (0..700u32).map(|i| (i % 251) as u8).collect();
+    {
+        let api = QrfsApi::open(Arc::new(QrStorageManager::new(&qrfolder, BLOCK_SIZE, 200))).unwrap();
+        api.write("/informe.bin", &data).unwrap();
+        api.write("/nota.txt", b"hola").unwrap();
+    }
+
+    let status = Command::new(env!("CARGO_BIN_EXE_qr_extract"))
+        .arg(&qrfolder)
+        .arg("--zip")
+        .arg(&out)
+        .status()
+        .expect("no se pudo ejecutar qr_extract");
+    assert!(status.success());
+
+    let entries = zip_entries(&fs::read(out.join("informe.bin.zip")).unwrap());
+    let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+    let blocks = data.len().div_ceil(BLOCK_SIZE);
+    assert_eq!(names.len(), blocks + 1);
+    assert_eq!(names[0], "block_0000.png");
+    assert_eq!(names[blocks], "index.txt");
+    assert!(entries[0].1.starts_with(b"\x89PNG"));
+    assert_eq!(zip_entries(&fs::read(out.join("nota.txt.zip")).unwrap()).len(), 2);
+
+    // el indice tambien sirve de manifiesto para verify
+    let index: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(out.join("index.json")).unwrap()).unwrap();
+    assert_eq!(index["files"].as_array().unwrap().len(), 2);
+    assert_eq!(index["files"][0]["zip"], "informe.bin.zip");
+    let output = Command::new(env!("CARGO_BIN_EXE_verify"))
+        .arg(&qrfolder)
+        .arg("--manifest")
+        .arg(out.join("index.json"))
+        .output()
+        .expect("no se pudo ejecutar verify");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+
+    let _ = fs::remove_dir_all(&dir);
+}
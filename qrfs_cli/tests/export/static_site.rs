@@ -0,0 +1,70 @@
+use std::fs;
+use std::process::Command;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::storage::QrStorageManager;
+
+use crate::common::temp_dir;
+
+#[test]
+fn publish_writes_a_browsable_site_with_every_qr() {
+    let dir = temp_dir("publish");
+    let qrfolder = dir.join("qr");
+    let site = dir.join("sitio");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&qrfolder)
+        .args(["--blocks", "200"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+
+    let data: Vec<u8> = (0..700u32).map(|i| (i % 251) as u8).collect();
+    let (nota, informe) = {
+        let api = QrfsApi::open(Arc::new(QrStorageManager::new(&qrfolder, BLOCK_SIZE, 200))).unwrap();
+        let nota = api.write("/a<b>.txt", b"hola <script>").unwrap();
+        let informe = api.write("/informe.bin", &data).unwrap();
+        (nota.inode_id, informe.inode_id)
+    };
+
+    let output = Command::new(env!("CARGO_BIN_EXE_publish"))
+        .arg(&qrfolder)
+        .arg(&site)
+        .output()
+        .expect("no se pudo ejecutar publish");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let index = fs::read_to_string(site.join("index.html")).unwrap();
+    assert!(index.contains("/a&lt;b&gt;.txt"), "{}", index);
+    assert!(index.contains(&format!("archivos/{}.html", informe)));
+
+    // el texto se ve escapado y el archivo se descarga tal cual
+    let page = fs::read_to_string(site.join(format!("archivos/{}.html", nota))).unwrap();
+    assert!(page.contains("<pre>hola &lt;script&gt;</pre>"), "{}", page);
+    assert!(page.contains(&format!("href=\"{}/a%3Cb%3E.txt\"", nota)));
+    assert_eq!(fs::read(site.join(format!("archivos/{}/a<b>.txt", nota))).unwrap(), b"hola <script>");
+
+    // cada qr del archivo esta en la pagina, en bloques/ y en su zip
+    let page = fs::read_to_string(site.join(format!("archivos/{}.html", informe))).unwrap();
+    assert!(page.contains("archivo binario"));
+    let images: Vec<&str> = page.split("<img src=\"../").skip(1).map(|s| s.split('"').next().unwrap()).collect();
+    assert_eq!(images.len(), data.len().div_ceil(BLOCK_SIZE));
+    for image in &images {
+        assert!(fs::read(site.join(image)).unwrap().starts_with(b"\x89PNG"));
+    }
+    let zip = fs::read(site.join(format!("archivos/{}/qr.zip", informe))).unwrap();
+    assert!(zip.starts_with(b"PK\x03\x04"));
+
+    // bloques.html tiene todos los qr de la carpeta, metadata incluida
+    let blocks = fs::read_to_string(site.join("bloques.html")).unwrap();
+    assert!(blocks.contains("bloques/000000.png"));
+    let on_disk = fs::read_dir(&qrfolder)
+        .unwrap()
+        .filter(|e| e.as_ref().unwrap().path().extension().is_some_and(|ext| ext == "png"))
+        .count();
+    assert_eq!(fs::read_dir(site.join("bloques")).unwrap().count(), on_disk);
+
+    let _ = fs::remove_dir_all(&dir);
+}
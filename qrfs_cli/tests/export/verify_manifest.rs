@@ -0,0 +1,65 @@
+use std::fs;
+use std::process::Command;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::storage::ContainerStorage;
+
+use crate::common::temp_dir;
+
+#[test]
+fn verify_passes_on_a_copy_and_flags_changed_files() {
+    let dir = temp_dir("verify");
+    let container = dir.join("origen.img");
+    let image = dir.join("volcado.img");
+    let manifest = dir.join("volcado.img.json");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&container)
+        .args(["--blocks", "200", "--container"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+
+    let data: Vec<u8> = (0..900u32).map(|i| (i % 253) as u8).collect();
+    {
+        let api = QrfsApi::open(Arc::new(ContainerStorage::open(&container, BLOCK_SIZE).unwrap())).unwrap();
+        api.write("/datos.bin", &data).unwrap();
+        api.write("/nota.txt", b"hola").unwrap();
+    }
+
+    let status = Command::new(env!("CARGO_BIN_EXE_disk_image"))
+        .arg("to")
+        .arg(&container)
+        .arg(&image)
+        .status()
+        .expect("no se pudo ejecutar disk_image");
+    assert!(status.success());
+
+    let verify = || {
+        Command::new(env!("CARGO_BIN_EXE_verify"))
+            .arg(&container)
+            .arg("--manifest")
+            .arg(&manifest)
+            .output()
+            .expect("no se pudo ejecutar verify")
+    };
+    let output = verify();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("2 de 2 archivos coinciden"), "{}", stdout);
+
+    // mismo largo, otro contenido: solo el hash lo detecta
+    {
+        let api = QrfsApi::open(Arc::new(ContainerStorage::open(&container, BLOCK_SIZE).unwrap())).unwrap();
+        api.write("/nota.txt", b"HOLA").unwrap();
+    }
+    let output = verify();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!output.status.success());
+    assert!(stdout.contains("OK     datos.bin"), "{}", stdout);
+    assert!(stdout.contains("FALLA  nota.txt: sha-256"), "{}", stdout);
+
+    let _ = fs::remove_dir_all(&dir);
+}
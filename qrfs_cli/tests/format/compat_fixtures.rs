@@ -0,0 +1,14 @@
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn every_reference_disk_still_reads() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("../fixtures/compat");
+    let output = Command::new(env!("CARGO_BIN_EXE_compat_check"))
+        .arg(&fixtures)
+        .output()
+        .expect("no se pudo ejecutar compat_check");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(!stdout.contains("FALLA"), "{}", stdout);
+}
@@ -0,0 +1,62 @@
+use std::fs;
+use std::process::Command;
+
+use qrfs_core::storage::QrStorageManager;
+
+use crate::common::temp_dir;
+
+#[test]
+fn mkfs_and_fsck_follow_the_profile_in_the_disk_folder() {
+    let dir = temp_dir("config");
+    let qrfolder = dir.join("qr");
+    fs::create_dir_all(&qrfolder).unwrap();
+    fs::write(
+        qrfolder.join("qrfs.toml"),
+        "[disk]\ntotal_blocks = 90\ninodes = 16\n\n[qr]\nsize = 160\nec_level = \"L\"\n",
+    )
+    .unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&qrfolder)
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+
+    let sb = QrStorageManager::probe_superblock(&qrfolder).unwrap().unwrap();
+    assert_eq!(sb.total_blocks, 90);
+    assert_eq!(sb.inode_count, 16);
+    let png = image::open(qrfolder.join("000000.png")).unwrap();
+    assert!(png.width() <= 160, "{}", png.width());
+
+    // las banderas ganan sobre el perfil
+    let other = dir.join("qr2");
+    let profile = dir.join("perfil.toml");
+    fs::write(&profile, "[disk]\ntotal_blocks = 90\n").unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&other)
+        .args(["--config", profile.to_str().unwrap(), "--blocks", "110"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+    let sb = QrStorageManager::probe_superblock(&other).unwrap().unwrap();
+    assert_eq!(sb.total_blocks, 110);
+
+    let out = Command::new(env!("CARGO_BIN_EXE_fsck"))
+        .arg(&qrfolder)
+        .output()
+        .expect("no se pudo ejecutar fsck");
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    // un perfil con errores se rechaza antes de tocar nada
+    fs::write(&profile, "[disk]\ntotal_bloks = 90\n").unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(dir.join("qr3"))
+        .args(["--config", profile.to_str().unwrap()])
+        .output()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("total_bloks"));
+    assert!(!dir.join("qr3").exists());
+
+    let _ = fs::remove_dir_all(&dir);
+}
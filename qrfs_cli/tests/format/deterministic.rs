@@ -0,0 +1,62 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::storage::QrStorageManager;
+
+use crate::common::temp_dir;
+
+// nombre -> bytes de cada png del disco
+fn images(dir: &Path) -> BTreeMap<String, Vec<u8>> {
+    fs::read_dir(dir)
+        .unwrap()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "png"))
+        .map(|path| (path.file_name().unwrap().to_string_lossy().into_owned(), fs::read(&path).unwrap()))
+        .collect()
+}
+
+fn build(disk: &Path) -> BTreeMap<String, Vec<u8>> {
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(disk)
+        .args(["--blocks", "96", "--inodes", "16", "--pack-tails", "--label", "Archivo", "--deterministic"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+
+    let api = QrfsApi::open(Arc::new(QrStorageManager::new(disk, BLOCK_SIZE, 96))).unwrap();
+    for (n, name) in ["c.txt", "a.txt", "b.txt"].iter().enumerate() {
+        api.write(&format!("/{}", name), &vec![b'a' + n as u8; 40 + 90 * n]).unwrap();
+    }
+    api.remove("/a.txt").unwrap();
+    api.flush().unwrap();
+    images(disk)
+}
+
+#[test]
+fn two_deterministic_runs_give_the_same_qr_images() {
+    let dir = temp_dir("deterministic_dos");
+    let first = build(&dir.join("uno"));
+    let second = build(&dir.join("dos"));
+
+    assert!(!first.is_empty());
+    assert_eq!(first.keys().collect::<Vec<_>>(), second.keys().collect::<Vec<_>>());
+    for (name, bytes) in &first {
+        assert!(&second[name] == bytes, "{} cambio entre corridas", name);
+    }
+
+    let rejected = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(dir.join("cifrado"))
+        .args(["--deterministic", "--encrypt"])
+        .env("QRFS_PASSPHRASE", "frase")
+        .output()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(!rejected.status.success());
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,8 @@
+// formato del disco: perfiles de qrfs.toml, discos de referencia y discos deterministas
+
+#[path = "../common/mod.rs"]
+mod common;
+
+mod compat_fixtures;
+mod config_profile;
+mod deterministic;
@@ -0,0 +1,53 @@
+#![cfg(feature = "audio")]
+
+use std::fs;
+use std::process::Command;
+
+use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::fs_format::read_superblock;
+use qrfs_core::storage::{BlockStorage, QrStorageManager};
+
+use crate::common::temp_dir;
+
+#[test]
+fn superblock_and_bitmap_cross_over_as_sound() {
+    let dir = temp_dir("audio");
+    let original = dir.join("original");
+    let received = dir.join("recibido");
+    let wav = dir.join("metadata.wav");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&original)
+        .args(["--blocks", "64"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+
+    let status = Command::new(env!("CARGO_BIN_EXE_audio"))
+        .arg("export")
+        .arg(&original)
+        .arg(&wav)
+        .status()
+        .expect("no se pudo ejecutar audio");
+    assert!(status.success());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_audio"))
+        .arg("import")
+        .arg(&received)
+        .arg(&wav)
+        .output()
+        .expect("no se pudo ejecutar audio");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let source = QrStorageManager::new(&original, BLOCK_SIZE, 64);
+    let copy = QrStorageManager::new(&received, BLOCK_SIZE, 64);
+    let sb = read_superblock(&copy).unwrap();
+    assert!(sb.is_valid());
+    for block in (0..sb.region_blocks()).chain(sb.free_map_start..sb.free_map_start + sb.free_map_blocks) {
+        assert_eq!(copy.read_block(block).unwrap(), source.read_block(block).unwrap(), "bloque {}", block);
+    }
+    // solo la metadata: los inodos siguen por qr
+    assert!(!copy.has_block(sb.inode_table_start));
+
+    let _ = fs::remove_dir_all(&dir);
+}
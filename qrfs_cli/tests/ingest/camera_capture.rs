@@ -0,0 +1,119 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::storage::QrStorageManager;
+
+use crate::common::temp_dir;
+
+// las imagenes del disco (con shards o sin) como si fueran cuadros de camara
+fn copy_frames(dir: &Path, frames: &Path, count: &mut usize) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            copy_frames(&path, frames, count);
+        } else if path.extension().is_some_and(|ext| ext == "png") {
+            *count += 1;
+            fs::copy(&path, frames.join(format!("{:06}.png", count))).unwrap();
+        }
+    }
+}
+
+#[test]
+fn frames_from_a_folder_rebuild_the_disk() {
+    let dir = temp_dir("capture_cuadros");
+    let original = dir.join("original");
+    let frames = dir.join("cuadros");
+    let scanned = dir.join("escaneado");
+    fs::create_dir_all(&frames).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&original)
+        .args(["--blocks", "64"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+    let data: Vec<u8> = (0..500u32).map(|i| (i % 253) as u8).collect();
+    QrfsApi::open(Arc::new(QrStorageManager::new(&original, BLOCK_SIZE, 64)))
+        .unwrap()
+        .write("/camara.bin", &data)
+        .unwrap();
+
+    let mut count = 0;
+    copy_frames(&original, &frames, &mut count);
+    // la misma hoja vista dos veces no cuenta doble
+    fs::copy(frames.join("000001.png"), frames.join("999999.png")).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_capture"))
+        .arg(&scanned)
+        .arg("--frames")
+        .arg(&frames)
+        .arg("--once")
+        .output()
+        .expect("no se pudo ejecutar capture");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains(&format!("{} bloques guardados", count)), "{}", stdout);
+
+    let api = QrfsApi::open(Arc::new(QrStorageManager::new(&scanned, BLOCK_SIZE, 64))).unwrap();
+    assert_eq!(api.read("/camara.bin").unwrap(), data);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[cfg(unix)]
+#[test]
+fn a_sequence_played_on_screen_is_captured_until_the_disk_is_complete() {
+    let dir = temp_dir("capture_pantalla");
+    let original = dir.join("original");
+    let frames = dir.join("cuadros");
+    let scanned = dir.join("escaneado");
+    fs::create_dir_all(&frames).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&original)
+        .args(["--blocks", "64"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+    let data = b"de una pantalla a la otra".repeat(20);
+    QrfsApi::open(Arc::new(QrStorageManager::new(&original, BLOCK_SIZE, 64)))
+        .unwrap()
+        .write("/pantalla.txt", &data)
+        .unwrap();
+    let mut count = 0;
+    copy_frames(&original, &frames, &mut count);
+
+    // la "pantalla" pasa las hojas en orden y vuelve a empezar
+    let script = dir.join("pantalla.sh");
+    fs::write(
+        &script,
+        format!(
+            "n=$(cat {dir}/n 2>/dev/null || echo 0)\necho $((n + 1)) > {dir}/n\ncat {frames}/$(ls {frames} | sed -n \"$((n % {count} + 1))p\")\n",
+            dir = dir.display(),
+            frames = frames.display(),
+            count = count
+        ),
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_capture"))
+        .arg(&scanned)
+        .arg("--screen")
+        .arg("--screen-cmd")
+        .arg(format!("sh {}", script.display()))
+        .args(["--idle", "60"])
+        .output()
+        .expect("no se pudo ejecutar capture");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("disco completo"), "{}", stdout);
+
+    let api = QrfsApi::open(Arc::new(QrStorageManager::new(&scanned, BLOCK_SIZE, 64))).unwrap();
+    assert_eq!(api.read("/pantalla.txt").unwrap(), data);
+
+    let _ = fs::remove_dir_all(&dir);
+}
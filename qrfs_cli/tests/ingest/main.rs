@@ -0,0 +1,8 @@
+// lo que entra al disco: camara, pantalla, audio y el plan de reimpresion
+
+#[path = "../common/mod.rs"]
+mod common;
+
+mod audio_channel;
+mod camera_capture;
+mod reprint_plan;
@@ -0,0 +1,102 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::storage::QrStorageManager;
+
+use crate::common::temp_dir;
+
+fn plan(disk: &Path, manifest: &Path, extra: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_reprint_plan"))
+        .arg(disk)
+        .arg("--manifest")
+        .arg(manifest)
+        .args(extra)
+        .output()
+        .expect("no se pudo ejecutar reprint_plan");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn after_an_edit_only_the_touched_qrs_are_reprinted() {
+    let dir = temp_dir("reprint_manifiesto");
+    let disk = dir.join("disco");
+    let manifest = dir.join("impresion.json");
+    let pdf = dir.join("reimprimir.pdf");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&disk)
+        .args(["--blocks", "64"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+    let api = QrfsApi::open(Arc::new(QrStorageManager::new(&disk, BLOCK_SIZE, 64))).unwrap();
+    api.write("/quieto.txt", &[b'q'; 400]).unwrap();
+    api.write("/editado.txt", b"primera version").unwrap();
+
+    // primera impresion: todo
+    let first = plan(&disk, &manifest, &["--record"]);
+    let printed = first.lines().filter(|l| l.starts_with("reimprimir")).count();
+    assert!(first.contains(&format!("{0} de {0} qr para reimprimir", printed)), "{}", first);
+    assert!(manifest.exists());
+    assert!(plan(&disk, &manifest, &[]).contains(&format!("0 de {}", printed)));
+
+    api.write("/editado.txt", b"segunda version").unwrap();
+    let second = plan(&disk, &manifest, &["--pdf", pdf.to_str().unwrap()]);
+    let reprint = second.lines().filter(|l| l.starts_with("reimprimir")).count();
+    // el dato del archivo y la metadata (la tabla de inodos sale entera: cada
+    // bloque lleva la generacion de la tabla), no los datos del otro archivo
+    assert!(reprint > 0 && reprint < printed, "{}", second);
+    assert!(second.contains(&format!("{} de {} qr para reimprimir", reprint, printed)), "{}", second);
+    assert!(fs::read(&pdf).unwrap().starts_with(b"%PDF"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn print_runs_stay_with_the_disk_and_fsck_reports_them() {
+    let dir = temp_dir("reprint_registro");
+    let disk = dir.join("disco");
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&disk)
+        .args(["--blocks", "64"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+    let fsck = || {
+        let output = Command::new(env!("CARGO_BIN_EXE_fsck"))
+            .arg(&disk)
+            .output()
+            .expect("no se pudo ejecutar fsck");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    let record = |expected: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_reprint_plan"))
+            .arg(&disk)
+            .arg("--record")
+            .output()
+            .expect("no se pudo ejecutar reprint_plan");
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        assert!(stdout.contains(expected), "{}", stdout);
+        stdout
+    };
+    record("nunca se imprimio");
+    assert!(fsck().contains("papel: corresponde al papel de la impresion #1"));
+
+    let api = QrfsApi::open(Arc::new(QrStorageManager::new(&disk, BLOCK_SIZE, 64))).unwrap();
+    api.write("/nuevo.txt", b"despues de imprimir").unwrap();
+    let report = fsck();
+    assert!(report.contains("warning:") && report.contains("desde la impresion #1"), "{}", report);
+
+    let second = record("desde la impresion #1");
+    assert!(second.contains("impresion #2 anotada"), "{}", second);
+    assert!(fsck().contains("corresponde al papel de la impresion #2"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,73 @@
+use std::fs;
+use std::process::Command;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::keyring::PASSPHRASE_ENV;
+use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::storage::QrStorageManager;
+
+use crate::common::temp_dir;
+
+#[test]
+fn encrypted_file_is_shared_with_its_qrs_and_key() {
+    let dir = temp_dir("crypt");
+    let qrfolder = dir.join("qr");
+    let qrs = dir.join("qrs");
+    let restored = dir.join("restaurado.bin");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&qrfolder)
+        .args(["--blocks", "200", "--encrypt"])
+        .env(PASSPHRASE_ENV, "frase de prueba")
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+
+    let data: Vec<u8> = (0..500u32).map(|i| (i * 7 % 256) as u8).collect();
+    let id = {
+        let api = QrfsApi::open(Arc::new(QrStorageManager::new(&qrfolder, BLOCK_SIZE, 200))).unwrap();
+        assert!(api.write("/secreto.bin", &data).is_err());
+        api.unlock("frase de prueba").unwrap();
+        let info = api.write("/secreto.bin", &data).unwrap();
+        assert!(info.encrypted);
+        info.inode_id
+    };
+
+    let output = Command::new(env!("CARGO_BIN_EXE_qr_extract"))
+        .arg(&qrfolder)
+        .args(["--file-key", &id.to_string()])
+        .env(PASSPHRASE_ENV, "frase de prueba")
+        .output()
+        .expect("no se pudo ejecutar qr_extract");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let key = stdout
+        .lines()
+        .find(|line| line.starts_with("qrfs-key:"))
+        .expect("qr_extract no mostro la clave")
+        .to_string();
+    assert!(key.starts_with("qrfs-key:500:"));
+
+    let status = Command::new(env!("CARGO_BIN_EXE_qr_extract"))
+        .arg(&qrfolder)
+        .arg(id.to_string())
+        .arg("--out")
+        .arg(&qrs)
+        .status()
+        .expect("no se pudo ejecutar qr_extract");
+    assert!(status.success());
+
+    // quien recibe los qr y la clave no necesita el disco ni la frase
+    let status = Command::new(env!("CARGO_BIN_EXE_qr_extract"))
+        .args(["--decrypt", &key])
+        .arg(&qrs)
+        .arg("--out")
+        .arg(&restored)
+        .status()
+        .expect("no se pudo ejecutar qr_extract");
+    assert!(status.success());
+    assert_eq!(fs::read(&restored).unwrap(), data);
+
+    let _ = fs::remove_dir_all(&dir);
+}
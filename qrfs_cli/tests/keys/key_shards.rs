@@ -0,0 +1,66 @@
+use std::fs;
+use std::process::Command;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::keyring::{NEW_PASSPHRASE_ENV, PASSPHRASE_ENV};
+use qrfs_core::storage::QrStorageManager;
+
+use crate::common::temp_dir;
+
+#[test]
+fn forgotten_passphrase_is_replaced_with_the_key_cards() {
+    let dir = temp_dir("shards");
+    let qrfolder = dir.join("qr");
+    let cards = dir.join("tarjetas");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&qrfolder)
+        .args(["--blocks", "200", "--encrypt"])
+        .env(PASSPHRASE_ENV, "frase olvidada")
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+    {
+        let api = QrfsApi::open(Arc::new(QrStorageManager::new(&qrfolder, BLOCK_SIZE, 200))).unwrap();
+        api.unlock("frase olvidada").unwrap();
+        api.write("/nota.txt", b"guardado con la frase vieja").unwrap();
+    }
+
+    let status = Command::new(env!("CARGO_BIN_EXE_keyshards"))
+        .arg(&qrfolder)
+        .args(["--split", "2/3", "--out"])
+        .arg(&cards)
+        .env(PASSPHRASE_ENV, "frase olvidada")
+        .status()
+        .expect("no se pudo ejecutar keyshards");
+    assert!(status.success());
+
+    // con una sola tarjeta no alcanza
+    let status = Command::new(env!("CARGO_BIN_EXE_fsck"))
+        .arg(&qrfolder)
+        .arg("--key-shards")
+        .arg(cards.join("fragmento_2_de_3.png"))
+        .env(NEW_PASSPHRASE_ENV, "frase nueva")
+        .status()
+        .expect("no se pudo ejecutar fsck");
+    assert!(!status.success());
+
+    let status = Command::new(env!("CARGO_BIN_EXE_fsck"))
+        .arg(&qrfolder)
+        .arg("--key-shards")
+        .arg(cards.join("fragmento_3_de_3.png"))
+        .arg(cards.join("fragmento_1_de_3.png"))
+        .env(NEW_PASSPHRASE_ENV, "frase nueva")
+        .status()
+        .expect("no se pudo ejecutar fsck");
+    assert!(status.success());
+
+    let api = QrfsApi::open(Arc::new(QrStorageManager::new(&qrfolder, BLOCK_SIZE, 200))).unwrap();
+    assert!(api.unlock("frase olvidada").is_err());
+    api.unlock("frase nueva").unwrap();
+    assert_eq!(api.read("/nota.txt").unwrap(), b"guardado con la frase vieja");
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,7 @@
+// cifrado: archivos compartidos con su clave y la maestra en fragmentos
+
+#[path = "../common/mod.rs"]
+mod common;
+
+mod encrypted_share;
+mod key_shards;
@@ -0,0 +1,58 @@
+use std::fs;
+use std::process::Command;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::storage::ContainerStorage;
+
+use crate::common::temp_dir;
+
+#[test]
+fn evacuated_block_moves_the_file_data_elsewhere() {
+    let dir = temp_dir("evacuate");
+    let container = dir.join("disco.img");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&container)
+        .args(["--blocks", "200", "--container"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+
+    let open = || QrfsApi::open(Arc::new(ContainerStorage::open(&container, BLOCK_SIZE).unwrap())).unwrap();
+    let data: Vec<u8> = (0..300u32).map(|i| (i % 251) as u8).collect();
+    let walk_blocks = |api: &QrfsApi<ContainerStorage>| {
+        api.walk().unwrap().into_iter().find(|e| e.path == "/datos.bin").unwrap().blocks
+    };
+    let blocks = {
+        let api = open();
+        api.write("/datos.bin", &data).unwrap();
+        walk_blocks(&api)
+    };
+
+    let output = Command::new(env!("CARGO_BIN_EXE_evacuate"))
+        .arg(&container)
+        .arg(blocks[1].to_string())
+        .args(["--to", "180"])
+        .output()
+        .expect("no se pudo ejecutar evacuate");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains(&format!("bloque {} -> 180", blocks[1])), "{}", stdout);
+
+    let api = open();
+    assert_eq!(walk_blocks(&api), [blocks[0], 180, blocks[2]]);
+    assert_eq!(api.read("/datos.bin").unwrap(), data);
+    drop(api);
+
+    // un bloque libre no tiene nada que evacuar
+    let status = Command::new(env!("CARGO_BIN_EXE_evacuate"))
+        .arg(&container)
+        .arg(blocks[1].to_string())
+        .status()
+        .expect("no se pudo ejecutar evacuate");
+    assert!(!status.success());
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,108 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::disk::{DirectoryEntry, InodeKind, BLOCK_SIZE};
+use qrfs_core::fs_format::{encode_directory, read_directory, read_inode_table, read_superblock};
+use qrfs_core::storage::{BlockStorage, ContainerStorage};
+
+use crate::common::temp_dir;
+
+// reescribe la raiz con las entradas que devuelva edit
+fn edit_root(container: &Path, edit: impl FnOnce(&mut Vec<DirectoryEntry>)) {
+    let storage = ContainerStorage::open(container, BLOCK_SIZE).unwrap();
+    let sb = read_superblock(&storage).unwrap();
+    let root = read_inode_table(&storage, &sb).next().unwrap().unwrap();
+    let (mut entries, generation) = read_directory(&storage, &sb, &root).unwrap();
+    edit(&mut entries);
+    let (data, _) = encode_directory(root.id, generation + 1, &entries, BLOCK_SIZE, sb.dir_format).unwrap();
+    for (chunk, &block) in data.chunks(BLOCK_SIZE).zip(&root.blocks) {
+        storage.write_block(block, chunk).unwrap();
+    }
+}
+
+fn mkfs(dir: &Path) -> PathBuf {
+    let container = dir.join("arbol.img");
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&container)
+        .args(["--blocks", "200", "--container"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+    container
+}
+
+fn open(container: &Path) -> QrfsApi<ContainerStorage> {
+    QrfsApi::open(Arc::new(ContainerStorage::open(container, BLOCK_SIZE).unwrap())).unwrap()
+}
+
+fn fsck(container: &Path, extra: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_fsck"))
+        .arg(container)
+        .args(extra)
+        .output()
+        .expect("no se pudo ejecutar fsck");
+    let text = String::from_utf8_lossy(&output.stdout).to_string() + &String::from_utf8_lossy(&output.stderr);
+    (output.status.success(), text)
+}
+
+#[test]
+fn fsck_repairs_dot_entries_and_reports_loops() {
+    let dir = temp_dir("fsck_tree_test_dots");
+    let container = mkfs(&dir);
+    open(&container).write("/nota.txt", b"hola").unwrap();
+    let (ok, out) = fsck(&container, &[]);
+    assert!(ok, "{}", out);
+
+    // '.' de la raiz apuntando a otro inodo
+    edit_root(&container, |entries| {
+        entries.iter_mut().find(|e| e.name == ".").unwrap().inode_id = 7;
+    });
+    let (ok, out) = fsck(&container, &[]);
+    assert!(!ok && out.contains("--repair"), "{}", out);
+    let (ok, out) = fsck(&container, &["--repair"]);
+    assert!(ok && out.contains("REPARADO"), "{}", out);
+    let (ok, out) = fsck(&container, &[]);
+    assert!(ok, "{}", out);
+
+    // la raiz metida dentro de si misma: no hay '..' que lo arregle
+    edit_root(&container, |entries| {
+        entries.push(DirectoryEntry { name: "bucle".into(), inode_id: 0, kind: InodeKind::Directory });
+    });
+    let (ok, out) = fsck(&container, &["--repair"]);
+    assert!(!ok && out.contains("ciclo"), "{}", out);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn fsck_keeps_the_newest_of_two_entries_with_the_same_name() {
+    let dir = temp_dir("fsck_tree_test_dups");
+    let container = mkfs(&dir);
+    let api = open(&container);
+    let old = api.write("/a.txt", b"version vieja").unwrap().inode_id;
+    let new = api.write("/b.txt", b"version nueva").unwrap().inode_id;
+    drop(api);
+
+    // como quedaria una mezcla de dos escaneos: dos 'a.txt'
+    edit_root(&container, |entries| {
+        entries.iter_mut().find(|e| e.name == "b.txt").unwrap().name = "a.txt".into();
+    });
+    let (ok, out) = fsck(&container, &[]);
+    assert!(!ok && out.contains("nombres repetidos"), "{}", out);
+    let (ok, out) = fsck(&container, &["--repair"]);
+    assert!(ok && out.contains(&format!("'a.txt' (inodo {}) -> 'a.txt.dup{}'", old, old)), "{}", out);
+
+    // el mas nuevo se queda con el nombre y el otro no se pierde
+    let api = open(&container);
+    assert_eq!(api.read("/a.txt").unwrap(), b"version nueva");
+    assert_eq!(api.read(&format!("/a.txt.dup{}", old)).unwrap(), b"version vieja");
+    assert_eq!(api.stat("/a.txt").unwrap().inode_id, new);
+    drop(api);
+    let (ok, out) = fsck(&container, &[]);
+    assert!(ok, "{}", out);
+
+    let _ = fs::remove_dir_all(&dir);
+}
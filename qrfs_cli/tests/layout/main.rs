@@ -0,0 +1,11 @@
+// ubicacion de los bloques: zona fija, colas, evacuacion, volumenes, espejos y fsck
+
+#[path = "../common/mod.rs"]
+mod common;
+
+mod evacuate_block;
+mod fsck_tree;
+mod mirror_repair;
+mod multi_volume;
+mod pinned_blocks;
+mod tail_packing;
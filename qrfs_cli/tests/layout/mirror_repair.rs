@@ -0,0 +1,59 @@
+use std::fs;
+use std::process::Command;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::mirror::MirrorStorage;
+use qrfs_core::storage::{BlockStorage, QrStorageManager};
+
+use crate::common::temp_dir;
+
+#[test]
+fn damaged_qr_is_rebuilt_from_the_mirror_on_read() {
+    let dir = temp_dir("mirror");
+    let disk = dir.join("disco");
+    let copy = dir.join("espejo");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&disk)
+        .args(["--blocks", "64", "--mirror"])
+        .arg(&copy)
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+    // el espejo nace con el mismo superblock
+    assert!(copy.join("000000.png").exists());
+
+    let open = || {
+        let primary = QrStorageManager::new(&disk, BLOCK_SIZE, 64);
+        let mirror = QrStorageManager::new(&copy, BLOCK_SIZE, 64);
+        QrfsApi::open(Arc::new(MirrorStorage::new(primary, mirror))).unwrap()
+    };
+    let data: Vec<u8> = (0..300u32).map(|i| (i % 251) as u8).collect();
+    let blocks = {
+        let api = open();
+        api.write("/datos.bin", &data).unwrap();
+        api.walk().unwrap().into_iter().find(|e| e.path == "/datos.bin").unwrap().blocks
+    };
+
+    // un qr que ya no se decodifica y otro que se perdio
+    let damaged = disk.join(format!("{:06}.png", blocks[0]));
+    fs::write(&damaged, b"no es un png").unwrap();
+    fs::remove_file(disk.join(format!("{:06}.png", blocks[2]))).unwrap();
+
+    let api = open();
+    assert_eq!(api.read("/datos.bin").unwrap(), data);
+    // la falla y las dos reparaciones quedan en el informe de salud
+    let health = api.health();
+    assert_eq!(health.status, "reparado");
+    assert_eq!((health.decode_failures, health.repairs), (1, 2));
+    drop(api);
+
+    // las imagenes quedaron reescritas en el disco principal
+    let primary = QrStorageManager::new(&disk, BLOCK_SIZE, 64);
+    assert_eq!(primary.read_verified(blocks[0]).unwrap(), data[..BLOCK_SIZE]);
+    assert!(primary.has_block(blocks[2]));
+
+    let _ = fs::remove_dir_all(&dir);
+}
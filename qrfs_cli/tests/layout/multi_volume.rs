@@ -0,0 +1,66 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::volume::{default_paths, probe_volumes, VolumeStorage};
+
+use crate::common::temp_dir;
+
+fn fsck(disk: &Path, extra: &[&Path]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_fsck"));
+    command.arg(disk);
+    if !extra.is_empty() {
+        command.arg("--volume-dirs").args(extra);
+    }
+    command.output().expect("no se pudo ejecutar fsck")
+}
+
+fn pngs(dir: &Path) -> usize {
+    fs::read_dir(dir)
+        .unwrap()
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "png"))
+        .count()
+}
+
+#[test]
+fn a_disk_spans_three_folders_and_fsck_checks_the_set() {
+    let dir = temp_dir("volumes_tres");
+    let disk = dir.join("disco");
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&disk)
+        .args(["--blocks", "64", "--volumes", "3"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+
+    let disk_str = disk.to_str().unwrap();
+    let paths = default_paths(disk_str, 3);
+    let superblock = probe_volumes(disk_str).unwrap().expect("disco sin volumenes");
+    assert_eq!(superblock.volumes().len(), 3);
+    let storage = VolumeStorage::open(&superblock, &paths, |s| s).unwrap();
+    let api = QrfsApi::open(Arc::new(storage)).unwrap();
+    let data: Vec<u8> = (0..1200).map(|i| (i % 251) as u8).collect();
+    api.write("/grande.bin", &data).unwrap();
+    assert_eq!(api.read("/grande.bin").unwrap(), data);
+    // los datos pasan a los otros volumenes
+    assert!(pngs(&paths[1]) > 0 && pngs(&paths[2]) > 0);
+
+    let output = fsck(&disk, &[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("OK (3 volúmenes)"), "{}", stdout);
+
+    // las carpetas movidas se dan a mano, y en orden
+    let moved = dir.join("tercero");
+    fs::rename(&paths[2], &moved).unwrap();
+    assert!(!fsck(&disk, &[]).status.success());
+    assert!(fsck(&disk, &[&paths[1], &moved]).status.success());
+    let swapped = fsck(&disk, &[&moved, &paths[1]]);
+    assert!(!swapped.status.success());
+    assert!(String::from_utf8_lossy(&swapped.stderr).contains("es el volumen 3 de 3"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
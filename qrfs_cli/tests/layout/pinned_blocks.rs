@@ -0,0 +1,67 @@
+use std::fs;
+use std::process::Command;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::storage::ContainerStorage;
+
+use crate::common::temp_dir;
+
+#[test]
+fn pinned_file_moves_to_the_start_and_evacuate_keeps_it_there() {
+    let dir = temp_dir("pin");
+    let container = dir.join("disco.img");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&container)
+        .args(["--blocks", "200", "--container", "--pinned", "10"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+
+    let open = || QrfsApi::open(Arc::new(ContainerStorage::open(&container, BLOCK_SIZE).unwrap())).unwrap();
+    let data: Vec<u8> = (0..300u32).map(|i| (i % 251) as u8).collect();
+    let walk_blocks = |api: &QrfsApi<ContainerStorage>| {
+        api.walk().unwrap().into_iter().find(|e| e.path == "/indice.txt").unwrap().blocks
+    };
+    let before = {
+        let api = open();
+        api.write("/indice.txt", &data).unwrap();
+        walk_blocks(&api)
+    };
+
+    let output = Command::new(env!("CARGO_BIN_EXE_pin"))
+        .arg(&container)
+        .arg("/indice.txt")
+        .output()
+        .expect("no se pudo ejecutar pin");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("/indice.txt fijo, 3 bloques movidos"), "{}", stdout);
+
+    // los bloques quedan antes de los que tenia y el contenido es el mismo
+    let api = open();
+    let after = walk_blocks(&api);
+    assert!(after.iter().all(|b| b < &before[0]), "{:?} {:?}", after, before);
+    assert_eq!(api.read("/indice.txt").unwrap(), data);
+    drop(api);
+
+    // evacuate no lo saca de la zona fija
+    let status = Command::new(env!("CARGO_BIN_EXE_evacuate"))
+        .arg(&container)
+        .arg(after[0].to_string())
+        .args(["--to", "190"])
+        .status()
+        .expect("no se pudo ejecutar evacuate");
+    assert!(!status.success());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_info"))
+        .arg(&container)
+        .output()
+        .expect("no se pudo ejecutar info");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Zona fija:"), "{}", stdout);
+
+    let _ = fs::remove_dir_all(&dir);
+}
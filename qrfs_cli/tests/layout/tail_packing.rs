@@ -0,0 +1,60 @@
+use std::fs;
+use std::process::Command;
+use std::sync::Arc;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::storage::ContainerStorage;
+
+use crate::common::temp_dir;
+
+#[test]
+fn small_files_end_up_sharing_blocks_and_fsck_checks_them() {
+    let dir = temp_dir("tails");
+    let container = dir.join("docs.img");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&container)
+        .args(["--blocks", "200", "--container", "--pack-tails"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+
+    let open = || QrfsApi::open(Arc::new(ContainerStorage::open(&container, BLOCK_SIZE).unwrap())).unwrap();
+    let free = {
+        let api = open();
+        for i in 0..6 {
+            api.write(&format!("/nota{}.txt", i), format!("nota numero {}", i).as_bytes())
+                .unwrap();
+        }
+        api.usage().free_blocks
+    };
+
+    // al soltar la api las seis colas quedan en un solo bloque
+    let api = open();
+    assert_eq!(api.usage().free_blocks, free + 5);
+    assert_eq!(api.read("/nota4.txt").unwrap(), b"nota numero 4");
+    drop(api);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fsck"))
+        .arg(&container)
+        .output()
+        .expect("no se pudo ejecutar fsck");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("6 colas en 1 bloques compartidos"), "{}", stdout);
+
+    // du cuenta el bloque compartido una vez y muestra lo guardado de cada nota
+    let output = Command::new(env!("CARGO_BIN_EXE_du"))
+        .arg(&container)
+        .output()
+        .expect("no se pudo ejecutar du");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    let nota = stdout.lines().find(|l| l.ends_with("/nota0.txt")).unwrap();
+    let columns: Vec<&str> = nota.split_whitespace().collect();
+    assert_eq!(columns[..4], ["13", "1", "128", "21"], "{}", stdout);
+    assert!(stdout.contains("Guardado:"), "{}", stdout);
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -1,21 +1,20 @@
+mod common;
+
 use std::fs;
-use std::path::PathBuf;
-use std::process::Command;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
 
+use qrfs_core::api::QrfsApi;
 use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::qr::decode_qr_text;
+use qrfs_core::storage::{BlockStorage, ContainerStorage, QrStorageManager};
 
-fn temp_dir() -> PathBuf {
-    let base = std::env::temp_dir();
-    let unique = format!("qrfs_mkfs_test_{}", std::process::id());
-    let dir = base.join(unique);
-    let _ = fs::remove_dir_all(&dir);
-    fs::create_dir_all(&dir).unwrap();
-    dir
-}
+use common::temp_dir;
 
 #[test]
 fn mkfs_creates_block_zero_with_expected_size() {
-    let dir = temp_dir();
+    let dir = temp_dir("mkfs_test_bloque0");
     let qrfolder = dir.join("fs");
 
     let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
@@ -25,9 +24,217 @@ fn mkfs_creates_block_zero_with_expected_size() {
 
     assert!(status.success());
 
-    let block0 = qrfolder.join("000000.blk");
+    let block0 = qrfolder.join("000000.png");
     assert!(block0.exists());
 
-    let data = fs::read(block0).unwrap();
+    let storage = QrStorageManager::new(&qrfolder, BLOCK_SIZE, 400);
+    let data = storage.read_block(0).unwrap();
     assert_eq!(data.len(), BLOCK_SIZE);
 }
+
+#[test]
+fn the_label_shows_in_info_and_fsck() {
+    let dir = temp_dir("mkfs_test_rotulo");
+    let qrfolder = dir.join("fs");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&qrfolder)
+        .args(["--blocks", "64", "--label", "Impuestos2024"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+
+    let run = |bin: &str| {
+        let output = Command::new(bin).arg(&qrfolder).output().expect("no se pudo ejecutar");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    let info = run(env!("CARGO_BIN_EXE_info"));
+    assert!(info.contains("\"QRFS: Impuestos2024\""), "{}", info);
+    assert!(info.contains("UUID:"), "{}", info);
+    let fsck = run(env!("CARGO_BIN_EXE_fsck"));
+    assert!(fsck.contains("rótulo 'Impuestos2024'"), "{}", fsck);
+    assert!(fsck.contains("LIMPIO"), "{}", fsck);
+
+    let rejected = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(dir.join("otro"))
+        .args(["--label", "a/b"])
+        .output()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(!rejected.status.success());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn the_codec_is_kept_in_the_superblock_and_used_for_every_block() {
+    let dir = temp_dir("mkfs_test_codec");
+    let qrfolder = dir.join("fs");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&qrfolder)
+        .args(["--blocks", "48", "--inodes", "16", "--codec", "qr-v2"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+
+    let info = Command::new(env!("CARGO_BIN_EXE_info")).arg(&qrfolder).output().expect("no se pudo ejecutar info");
+    let info = String::from_utf8_lossy(&info.stdout);
+    assert!(info.contains("Codec:          qr-v2"), "{}", info);
+
+    let storage = QrStorageManager::new(&qrfolder, BLOCK_SIZE, 48);
+    let text = decode_qr_text(&image::open(storage.block_path(0)).unwrap()).unwrap();
+    assert!(text.starts_with("QRFS2:"), "{}", text);
+    let api = QrfsApi::open(Arc::new(storage)).unwrap();
+    api.write("/a.txt", b"binario").unwrap();
+    assert_eq!(api.read("/a.txt").unwrap(), b"binario");
+
+    let rejected = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(dir.join("otro"))
+        .args(["--codec", "datamatrix"])
+        .output()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(!rejected.status.success());
+    assert!(String::from_utf8_lossy(&rejected.stderr).contains("qr-v1, qr-v2"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn a_profile_picks_the_qr_options_and_flags_override_it() {
+    let dir = temp_dir("mkfs_test_plantilla");
+    let qrfolder = dir.join("fs");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&qrfolder)
+        .args(["--profile", "tiny", "--blocks", "80", "--ec-level", "Q"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+
+    // la plantilla pone 64 bloques y correccion L; las banderas ganan
+    let config = fs::read_to_string(qrfolder.join("qrfs.toml")).unwrap();
+    assert!(config.contains("--profile tiny") && config.contains("ec_level = \"Q\""), "{}", config);
+    let output = Command::new(env!("CARGO_BIN_EXE_info"))
+        .arg(&qrfolder)
+        .output()
+        .expect("no se pudo ejecutar info");
+    let info = String::from_utf8_lossy(&output.stdout);
+    assert!(info.contains("Plantilla:      tiny"), "{}", info);
+    assert!(info.contains("Bloques:        80"), "{}", info);
+    assert!(info.contains("de 16"), "{}", info);
+
+    let rejected = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(dir.join("otro"))
+        .args(["--profile", "enorme"])
+        .output()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(!rejected.status.success());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn the_interactive_wizard_sizes_the_disk_and_waits_for_a_yes() {
+    let dir = temp_dir("mkfs_test_asistente");
+    let qrfolder = dir.join("fs");
+    let wizard = |answers: &str| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+            .arg(&qrfolder)
+            .arg("--interactive")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("no se pudo ejecutar mkfs");
+        child.stdin.take().unwrap().write_all(answers.as_bytes()).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    // una respuesta que no sirve se vuelve a preguntar; sin "s" no se formatea
+    let cancelled = wizard("mucho\n10K\nimprimir\nalta\nn\n");
+    assert!(cancelled.contains("Hojas para imprimir"), "{}", cancelled);
+    assert!(cancelled.contains("cancelado"), "{}", cancelled);
+    assert!(!qrfolder.exists());
+
+    let created = wizard("10K\npantalla\nbaja\ns\n");
+    assert!(created.contains("Qr para pasar por pantalla"), "{}", created);
+    let output = Command::new(env!("CARGO_BIN_EXE_info"))
+        .arg(&qrfolder)
+        .output()
+        .expect("no se pudo ejecutar info");
+    let info = String::from_utf8_lossy(&output.stdout);
+    assert!(info.contains("Plantilla:      transfer"), "{}", info);
+    assert!(fs::read_to_string(qrfolder.join("qrfs.toml")).unwrap().contains("ec_level = \"L\""));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn the_estimate_sizes_a_disk_that_holds_the_data() {
+    let dir = temp_dir("mkfs_test_estimacion");
+    let qrfolder = dir.join("fs");
+    let output = Command::new(env!("CARGO_BIN_EXE_estimate"))
+        .args(["--size", "6K", "--ec", "Q"])
+        .output()
+        .expect("no se pudo ejecutar estimate");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let report = String::from_utf8_lossy(&output.stdout);
+    assert!(report.contains("Hojas impresas:"), "{}", report);
+
+    // la ultima linea es el mkfs que corresponde
+    let suggested = report.lines().last().unwrap();
+    let flags: Vec<&str> = suggested.split_whitespace().skip(2).collect();
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&qrfolder)
+        .args(&flags)
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+    let total: u32 = flags[1].parse().unwrap();
+    let api = QrfsApi::open(Arc::new(QrStorageManager::new(&qrfolder, BLOCK_SIZE, total))).unwrap();
+    api.write("/datos.bin", &[7u8; 6 * 1024]).unwrap();
+
+    // otro tamaño de bloque va en la linea de mkfs; uno que no entra en un qr no
+    let bigger = Command::new(env!("CARGO_BIN_EXE_estimate"))
+        .args(["--size", "1M", "--block-size", "512"])
+        .output()
+        .expect("no se pudo ejecutar estimate");
+    assert!(bigger.status.success());
+    assert!(String::from_utf8_lossy(&bigger.stdout).contains("--block-size 512"));
+    let rejected = Command::new(env!("CARGO_BIN_EXE_estimate"))
+        .args(["--size", "1M", "--block-size", "4096", "--ec", "H"])
+        .output()
+        .expect("no se pudo ejecutar estimate");
+    assert!(!rejected.status.success());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn a_container_keeps_the_block_size_it_was_formatted_with() {
+    let dir = temp_dir("mkfs_test_bloque_grande");
+    let container = dir.join("disco.img");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&container)
+        .args(["--blocks", "64", "--container", "--block-size", "1024"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+
+    // sin decirle el tamaño: lo saca del superblock
+    let data: Vec<u8> = (0..3000u32).map(|i| (i % 251) as u8).collect();
+    let storage = ContainerStorage::open_disk(&container).unwrap();
+    assert_eq!(storage.block_size(), 1024);
+    let api = QrfsApi::open(Arc::new(storage)).unwrap();
+    api.write("/datos.bin", &data).unwrap();
+    assert_eq!(api.read("/datos.bin").unwrap(), data);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_info")).arg(&container).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let _ = fs::remove_dir_all(&dir);
+}
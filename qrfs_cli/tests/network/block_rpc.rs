@@ -0,0 +1,113 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Arc;
+use std::thread;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::blockrpc::{self, Block, BlockClient, BlockRange};
+use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::storage::QrStorageManager;
+
+use crate::common::temp_dir;
+
+// las dos rutas del servicio de server.rs sobre una carpeta
+fn serve_blocks(disk: PathBuf) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let storage = QrStorageManager::new(&disk, BLOCK_SIZE, 64);
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let path = line.split_whitespace().nth(1).unwrap_or("/").to_string();
+            let mut len = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                // los nombres de cabecera no distinguen mayusculas
+                if let Some(value) = header.to_ascii_lowercase().strip_prefix("content-length: ") {
+                    len = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; len];
+            reader.read_exact(&mut body).unwrap();
+
+            let frames = blockrpc::take_frames(&mut body, usize::MAX).unwrap();
+            let mut out = Vec::new();
+            if path == blockrpc::UPLOAD_PATH {
+                for frame in frames {
+                    let ack = blockrpc::store_block(&storage, &Block::decode(&frame).unwrap());
+                    blockrpc::frame(&ack.encode(), &mut out);
+                }
+            } else {
+                let range = BlockRange::decode(&frames[0]).unwrap();
+                for block in blockrpc::read_range(&storage, range).unwrap() {
+                    blockrpc::frame(&block.encode(), &mut out);
+                }
+            }
+            let mut response = format!("HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: {}\r\n\r\n", out.len()).into_bytes();
+            response.extend(out);
+            let _ = stream.write_all(&response);
+        }
+    });
+    port
+}
+
+#[test]
+fn scanned_disk_goes_up_and_comes_back_down_through_the_block_service() {
+    let dir = temp_dir("rpc");
+    let phone = dir.join("celular");
+    let server = dir.join("servidor");
+    let copy = dir.join("copia");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&phone)
+        .args(["--blocks", "64"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+    let data: Vec<u8> = (0..300u32).map(|i| (i % 251) as u8).collect();
+    QrfsApi::open(Arc::new(QrStorageManager::new(&phone, BLOCK_SIZE, 64)))
+        .unwrap()
+        .write("/escaneado.bin", &data)
+        .unwrap();
+
+    let url = format!("http://127.0.0.1:{}", serve_blocks(server.clone()));
+    let output = Command::new(env!("CARGO_BIN_EXE_blocks"))
+        .arg("push")
+        .arg(&phone)
+        .arg(&url)
+        .output()
+        .expect("no se pudo ejecutar blocks");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("0 rechazados"), "{}", stdout);
+
+    // fuera de rango: el ack lo dice y el resto del pedido sigue
+    let acks = BlockClient::new(&url)
+        .upload(&[Block { id: 99, gen: 0, data: vec![1] }])
+        .unwrap();
+    assert!(!acks[0].ok && acks[0].error.contains("fuera de rango"), "{:?}", acks);
+
+    let status = Command::new(env!("CARGO_BIN_EXE_blocks"))
+        .arg("pull")
+        .arg(&url)
+        .arg(&copy)
+        .args(["--count", "64"])
+        .status()
+        .expect("no se pudo ejecutar blocks");
+    assert!(status.success());
+
+    let api = QrfsApi::open(Arc::new(QrStorageManager::new(&copy, BLOCK_SIZE, 64))).unwrap();
+    assert_eq!(api.read("/escaneado.bin").unwrap(), data);
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,102 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::thread;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::storage::QrStorageManager;
+use qrfs_core::sync;
+
+use crate::common::temp_dir;
+
+// las dos rutas de sync de server.rs sobre una carpeta
+fn serve_sync(disk: PathBuf) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let storage = QrStorageManager::new(&disk, BLOCK_SIZE, 64);
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let path = line.split_whitespace().nth(1).unwrap_or("/").to_string();
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+            }
+            let body = if path == "/sync/manifest" {
+                Some(serde_json::to_vec(&sync::manifest(&storage)).unwrap())
+            } else {
+                let id: u32 = path.trim_start_matches("/sync/block/").parse().unwrap();
+                sync::export_block(&storage, id)
+                    .unwrap()
+                    .map(|block| serde_json::to_vec(&block).unwrap())
+            };
+            let response = match body {
+                None => b"HTTP/1.1 404 Not Found\r\nConnection: close\r\nContent-Length: 0\r\n\r\n".to_vec(),
+                Some(body) => {
+                    let mut response = format!("HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: {}\r\n\r\n", body.len()).into_bytes();
+                    response.extend(body);
+                    response
+                }
+            };
+            let _ = stream.write_all(&response);
+        }
+    });
+    port
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    fs::create_dir_all(to).unwrap();
+    for entry in fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        fs::copy(entry.path(), to.join(entry.file_name())).unwrap();
+    }
+}
+
+#[test]
+fn server_pulls_the_blocks_its_peer_ingested() {
+    let dir = temp_dir("sync");
+    let ours = dir.join("nuestro");
+    let theirs = dir.join("del_otro");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+        .arg(&theirs)
+        .args(["--blocks", "64"])
+        .status()
+        .expect("no se pudo ejecutar mkfs");
+    assert!(status.success());
+    // las dos copias arrancan iguales; despues solo el otro recibe el archivo
+    copy_dir(&theirs, &ours);
+    let data: Vec<u8> = (0..300u32).map(|i| (i % 251) as u8).collect();
+    QrfsApi::open(Arc::new(QrStorageManager::new(&theirs, BLOCK_SIZE, 64)))
+        .unwrap()
+        .write("/lote2.bin", &data)
+        .unwrap();
+
+    let port = serve_sync(theirs.clone());
+    let storage = QrStorageManager::new(&ours, BLOCK_SIZE, 64);
+    let result = sync::pull(&storage, &format!("http://127.0.0.1:{}/", port)).unwrap();
+    assert!(result.failed.is_empty(), "{:?}", result.failed);
+    // los datos, el inodo, el bitmap y el directorio
+    assert!(result.pulled.len() >= 4, "{:?}", result.pulled);
+
+    let api = QrfsApi::open(Arc::new(storage)).unwrap();
+    assert_eq!(api.read("/lote2.bin").unwrap(), data);
+    drop(api);
+
+    // ya iguales: un segundo pull no trae nada
+    let storage = QrStorageManager::new(&ours, BLOCK_SIZE, 64);
+    let again = sync::pull(&storage, &format!("http://127.0.0.1:{}", port)).unwrap();
+    assert!(again.pulled.is_empty(), "{:?}", again.pulled);
+
+    let _ = fs::remove_dir_all(&dir);
+}
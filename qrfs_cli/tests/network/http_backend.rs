@@ -0,0 +1,98 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::thread;
+
+use qrfs_core::api::QrfsApi;
+use qrfs_core::disk::BLOCK_SIZE;
+use qrfs_core::http::HttpStorage;
+use qrfs_core::storage::{ContainerStorage, QrStorageManager};
+
+use crate::common::temp_dir;
+
+// hosting estatico minimo: archivos de root, con Range y 404
+fn serve(root: PathBuf) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let path = line.split_whitespace().nth(1).unwrap_or("/").to_string();
+            let mut range = None;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                // los nombres de cabecera no distinguen mayusculas
+                if let Some(value) = header.to_ascii_lowercase().strip_prefix("range: bytes=") {
+                    let (a, b) = value.trim().split_once('-').unwrap();
+                    range = Some((a.parse::<usize>().unwrap(), b.parse::<usize>().unwrap() + 1));
+                }
+            }
+            let response = match fs::read(Path::new(&root).join(path.trim_start_matches('/'))) {
+                Err(_) => b"HTTP/1.1 404 Not Found\r\nConnection: close\r\nContent-Length: 0\r\n\r\n".to_vec(),
+                Ok(body) => {
+                    let (status, body) = match range {
+                        Some((a, b)) => ("206 Partial Content", body[a..b.min(body.len())].to_vec()),
+                        None => ("200 OK", body),
+                    };
+                    let mut response = format!("HTTP/1.1 {}\r\nConnection: close\r\nContent-Length: {}\r\n\r\n", status, body.len()).into_bytes();
+                    response.extend(body);
+                    response
+                }
+            };
+            let _ = stream.write_all(&response);
+        }
+    });
+    port
+}
+
+#[test]
+fn published_qr_folder_and_container_are_read_over_http() {
+    let dir = temp_dir("http");
+    let folder = dir.join("archivo");
+    let container = dir.join("disco.img");
+    let data: Vec<u8> = (0..300u32).map(|i| (i % 251) as u8).collect();
+
+    for (disk, extra) in [(&folder, None), (&container, Some("--container"))] {
+        let status = Command::new(env!("CARGO_BIN_EXE_mkfs"))
+            .arg(disk)
+            .args(["--blocks", "64"])
+            .args(extra)
+            .status()
+            .expect("no se pudo ejecutar mkfs");
+        assert!(status.success());
+    }
+    QrfsApi::open(Arc::new(QrStorageManager::new(&folder, BLOCK_SIZE, 64)))
+        .unwrap()
+        .write("/leeme.txt", &data)
+        .unwrap();
+    QrfsApi::open(Arc::new(ContainerStorage::open(&container, BLOCK_SIZE).unwrap()))
+        .unwrap()
+        .write("/leeme.txt", &data)
+        .unwrap();
+
+    let port = serve(dir.clone());
+    for url in [
+        format!("http://127.0.0.1:{}/archivo/", port),
+        format!("http://127.0.0.1:{}/disco.img", port),
+    ] {
+        let api = QrfsApi::open(Arc::new(HttpStorage::open_remote(&url).unwrap())).unwrap();
+        assert_eq!(api.read("/leeme.txt").unwrap(), data, "{}", url);
+        // lo publicado no se puede cambiar
+        assert!(api.write("/nuevo.txt", b"x").is_err(), "{}", url);
+    }
+
+    let missing = format!("http://127.0.0.1:{}/no_existe/", port);
+    assert!(HttpStorage::open(&missing).is_err());
+
+    let _ = fs::remove_dir_all(&dir);
+}
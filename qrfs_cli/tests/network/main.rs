@@ -0,0 +1,8 @@
+// servicios por la red: blockrpc, sync entre servidores y discos publicados por http
+
+#[path = "../common/mod.rs"]
+mod common;
+
+mod block_rpc;
+mod block_sync;
+mod http_backend;
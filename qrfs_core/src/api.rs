@@ -0,0 +1,1782 @@
+// api de archivos de alto nivel sobre QrfsFilesystem
+// la usan los frontends que no pasan por fuse (webdav, 9p, ftp...)
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::crypt::{FileKey, PBKDF2_ITERATIONS};
+use crate::disk::{BlockId, DiskLabel, Inode, InodeKind, Superblock};
+use crate::errors::QrfsError;
+use crate::events::{FsEvent, STORE_MAX_BYTES};
+use crate::fs::QrfsFilesystem;
+use crate::health::HealthReport;
+use crate::shamir::KeyShard;
+use crate::storage::BlockStorage;
+
+// metadatos de un archivo tal como los ven los frontends
+#[derive(Debug, Clone)]
+pub struct FileInfo {
+    pub name: String,
+    pub inode_id: u32,
+    pub kind: InodeKind,
+    pub size: u64,
+    pub mode: u16,
+    pub created_at: u64,
+    pub modified_at: u64,
+    // INODE_FLAG_* (inmutable, solo agregar, cola compartida)
+    pub flags: u32,
+    // tiene su propia clave (ver crypt.rs)
+    pub encrypted: bool,
+}
+
+impl FileInfo {
+    fn from_inode(name: &str, inode: &Inode) -> Self {
+        Self {
+            name: name.to_string(),
+            inode_id: inode.id,
+            kind: inode.kind.clone(),
+            size: inode.size,
+            mode: inode.mode,
+            created_at: inode.created_at,
+            modified_at: inode.modified_at,
+            flags: inode.flags,
+            encrypted: !inode.key.is_empty(),
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        matches!(self.kind, InodeKind::Directory)
+    }
+}
+
+// un archivo o directorio del arbol con los bloques que ocupa (para du)
+#[derive(Debug, Clone)]
+pub struct TreeEntry {
+    // ruta completa desde la raiz ("/", "/docs", "/docs/a.txt")
+    pub path: String,
+    pub info: FileInfo,
+    pub blocks: Vec<BlockId>,
+    // bytes que ocupa de verdad: una cola empaquetada cuenta solo su pedazo
+    // del bloque compartido
+    pub stored: u64,
+}
+
+// ocupacion del disco (para statfs de los frontends)
+#[derive(Debug, Clone, Copy)]
+pub struct FsUsage {
+    pub block_size: u32,
+    pub total_blocks: u64,
+    // bloques del area de datos; free_blocks siempre cuenta solo estos
+    pub data_blocks: u64,
+    pub free_blocks: u64,
+    pub total_inodes: u64,
+    pub free_inodes: u64,
+    // zona fija al principio del area de datos (0 si el disco no tiene)
+    pub pinned_blocks: u64,
+    pub free_pinned_blocks: u64,
+}
+
+// como esta repartido el espacio libre y cuanto estan partidos los archivos
+#[derive(Debug, Clone)]
+pub struct FragmentationReport {
+    pub free_blocks: u64,
+    pub free_extents: u64,
+    pub largest_free_extent: u64,
+    // corridas libres por tamaño: (hasta cuantos bloques, cuantas corridas),
+    // en potencias de dos
+    pub free_extent_histogram: Vec<(u64, u64)>,
+    // ordenados del mas partido al menos partido
+    pub files: Vec<FileFragmentation>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileFragmentation {
+    pub name: String,
+    pub inode_id: u32,
+    pub blocks: u64,
+    // tramos de bloques contiguos
+    pub extents: u64,
+    // 0.0 = contiguo, 1.0 = ningun bloque sigue al anterior
+    pub score: f64,
+}
+
+impl FileFragmentation {
+    fn new(name: &str, inode: &Inode) -> Self {
+        let extents = block_extents(&inode.blocks);
+        let blocks = inode.blocks.len() as u64;
+        let score = if blocks > 1 {
+            (extents - 1) as f64 / (blocks - 1) as f64
+        } else {
+            0.0
+        };
+        Self {
+            name: name.to_string(),
+            inode_id: inode.id,
+            blocks,
+            extents,
+            score,
+        }
+    }
+}
+
+// envoltorio seguro entre hilos: todas las operaciones toman el mutex
+pub struct QrfsApi<B: BlockStorage + 'static> {
+    fs: Mutex<QrfsFilesystem<B>>,
+}
+
+impl<B: BlockStorage + 'static> QrfsApi<B> {
+    // carga el filesystem desde el almacenamiento (debe estar formateado)
+    pub fn open(storage: Arc<B>) -> Result<Self, QrfsError> {
+        Ok(Self::new(QrfsFilesystem::new(storage)?))
+    }
+
+    pub fn new(fs: QrfsFilesystem<B>) -> Self {
+        Self { fs: Mutex::new(fs) }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, QrfsFilesystem<B>> {
+        self.fs.lock().unwrap()
+    }
+
+    // para las lecturas por nombre: antes se mira si otro proceso cambio el disco
+    fn lock_fresh(&self) -> MutexGuard<'_, QrfsFilesystem<B>> {
+        let mut fs = self.lock();
+        fs.recheck_metadata();
+        fs
+    }
+
+    pub fn superblock(&self) -> Superblock {
+        self.lock().superblock().clone()
+    }
+
+    // rotulo y uuid del disco (None si nunca se rotulo)
+    pub fn label(&self) -> Option<DiskLabel> {
+        self.lock().label()
+    }
+
+    // cambia el rotulo; el uuid queda el mismo
+    pub fn set_label(&self, label: &str) -> Result<DiskLabel, QrfsError> {
+        self.lock().set_label(label)
+    }
+
+    pub fn usage(&self) -> FsUsage {
+        let fs = self.lock();
+        let sb = fs.superblock();
+        let (free_blocks, free_inodes) = fs.free_counts();
+        let pinned = fs.pinned_area();
+        FsUsage {
+            block_size: sb.block_size,
+            total_blocks: sb.total_blocks as u64,
+            data_blocks: fs.data_block_count(),
+            free_blocks,
+            total_inodes: sb.inode_count as u64,
+            free_inodes,
+            pinned_blocks: pinned.len() as u64,
+            free_pinned_blocks: pinned.filter(|&blk| !fs.is_block_used(blk)).count() as u64,
+        }
+    }
+
+    pub fn fragmentation(&self) -> Result<FragmentationReport, QrfsError> {
+        let mut fs = self.lock();
+        let runs = fs.free_runs();
+
+        let mut histogram: Vec<(u64, u64)> = Vec::new();
+        for &run in &runs {
+            let bucket = run.next_power_of_two();
+            match histogram.iter_mut().find(|(size, _)| *size == bucket) {
+                Some((_, count)) => *count += 1,
+                None => histogram.push((bucket, 1)),
+            }
+        }
+        histogram.sort();
+
+        let mut files: Vec<FileFragmentation> = fs
+            .root_entries()?
+            .into_iter()
+            .filter_map(|(name, id)| fs.inode(id).map(|inode| FileFragmentation::new(&name, inode)))
+            .collect();
+        files.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+
+        Ok(FragmentationReport {
+            free_blocks: runs.iter().sum(),
+            free_extents: runs.len() as u64,
+            largest_free_extent: runs.iter().copied().max().unwrap_or(0),
+            free_extent_histogram: histogram,
+            files,
+        })
+    }
+
+    // recorre todo el arbol (incluidos los directorios creados por fuse)
+    pub fn walk(&self) -> Result<Vec<TreeEntry>, QrfsError> {
+        let mut fs = self.lock_fresh();
+        let paths = fs.walk_tree()?;
+        Ok(paths
+            .into_iter()
+            .filter_map(|(path, id)| {
+                let inode = fs.inode(id)?;
+                let name = path.rsplit('/').next().unwrap_or("").to_string();
+                Some(TreeEntry {
+                    info: FileInfo::from_inode(&name, inode),
+                    blocks: inode.blocks.clone(),
+                    stored: fs.stored_bytes(inode),
+                    path,
+                })
+            })
+            .collect())
+    }
+
+    // lista un directorio ("/" o "/docs"), ordenado por nombre
+    pub fn list(&self, path: &str) -> Result<Vec<FileInfo>, QrfsError> {
+        let mut fs = self.lock_fresh();
+        let dir = resolve(&mut fs, path)?;
+        if !fs.inode(dir).is_some_and(|inode| inode.kind == InodeKind::Directory) {
+            return Err(QrfsError::NotADirectory(path.to_string()));
+        }
+        let mut files: Vec<FileInfo> = fs
+            .entries_in(dir)?
+            .into_iter()
+            .filter_map(|(name, id)| fs.inode(id).map(|inode| FileInfo::from_inode(&name, inode)))
+            .collect();
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(files)
+    }
+
+    // metadatos de "/", "/archivo" o "/docs/archivo"
+    pub fn stat(&self, path: &str) -> Result<FileInfo, QrfsError> {
+        let mut fs = self.lock_fresh();
+        let id = resolve(&mut fs, path)?;
+        let name = components(path)?.last().copied().unwrap_or("");
+        fs.inode(id)
+            .map(|inode| FileInfo::from_inode(name, inode))
+            .ok_or_else(|| QrfsError::NotFound(path.to_string()))
+    }
+
+    // lee el archivo completo
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, QrfsError> {
+        self.read_at(path, 0, u64::MAX)
+    }
+
+    pub fn read_at(&self, path: &str, offset: u64, size: u64) -> Result<Vec<u8>, QrfsError> {
+        let mut fs = self.lock_fresh();
+        let id = file_id(&mut fs, path)?;
+        let inode = fs.inode(id).ok_or_else(|| QrfsError::NotFound(path.to_string()))?;
+        let size = size.min(inode.size.saturating_sub(offset));
+        fs.read_data(id, offset, size)
+    }
+
+    // lee un archivo por su inodo: sirve para los de subdirectorios (walk)
+    pub fn read_inode(&self, id: u32) -> Result<Vec<u8>, QrfsError> {
+        let fs = self.lock_fresh();
+        let inode = fs.inode(id).ok_or_else(|| QrfsError::NotFound(format!("inodo {}", id)))?;
+        let size = inode.size;
+        fs.read_data(id, 0, size)
+    }
+
+    // crea o reemplaza el contenido completo de un archivo
+    pub fn write(&self, path: &str, data: &[u8]) -> Result<FileInfo, QrfsError> {
+        {
+            let mut fs = self.lock();
+            let (dir, name) = parent_and_name(&mut fs, path)?;
+            let id = match fs.lookup_in(dir, name)? {
+                Some(id) => id,
+                None => fs.create_in(dir, name, InodeKind::File, 0o644)?.id,
+            };
+            fs.truncate(id, 0)?;
+            fs.write_all_data(id, 0, data)?;
+            // archivo completo: se guarda ya, como al cerrarlo
+            fs.flush_data()?;
+            // un montaje en el mismo bus lo deja en la cache de paginas
+            if data.len() <= STORE_MAX_BYTES {
+                fs.publish(FsEvent::Stored { inode: id, offset: 0, data: data.into() });
+            }
+        }
+        self.stat(path)
+    }
+
+    // escribe en una posicion sin tocar el resto del archivo
+    pub fn write_at(&self, path: &str, offset: u64, data: &[u8]) -> Result<usize, QrfsError> {
+        let mut fs = self.lock();
+        let id = file_id(&mut fs, path)?;
+        fs.write_all_data(id, offset, data)
+    }
+
+    // regenera los qr de "/" o de "/archivo"; devuelve los bloques regenerados
+    pub fn reencode(&self, path: &str) -> Result<usize, QrfsError> {
+        let mut fs = self.lock();
+        let id = resolve(&mut fs, path)?;
+        fs.reencode_inode(id)
+    }
+
+    pub fn reencode_inode(&self, id: u32) -> Result<usize, QrfsError> {
+        self.lock().reencode_inode(id)
+    }
+
+    // mueve el bloque logico index del inodo al bloque fisico libre to
+    pub fn relocate_block(&self, id: u32, index: usize, to: BlockId) -> Result<(), QrfsError> {
+        self.lock().relocate_block(id, index, to)
+    }
+
+    // saca el contenido del bloque fisico from (a to o al primer libre) y
+    // devuelve el bloque nuevo
+    pub fn evacuate_block(&self, from: BlockId, to: Option<BlockId>) -> Result<BlockId, QrfsError> {
+        self.lock().evacuate_block(from, to)
+    }
+
+    // fallas de lectura por bloque (qr ilegibles, checksums, reparaciones)
+    // desde que se abrio; un storage sin decodificacion solo ve las del sello
+    pub fn health(&self) -> HealthReport {
+        self.lock().health_report()
+    }
+
+    // pasa a qr los bloques de datos que write_at dejo en memoria
+    pub fn flush(&self) -> Result<(), QrfsError> {
+        self.lock().flush_data()
+    }
+
+    // crea un archivo vacio, falla si ya existe
+    pub fn create(&self, path: &str, mode: u16) -> Result<FileInfo, QrfsError> {
+        let mut fs = self.lock();
+        let (dir, name) = parent_and_name(&mut fs, path)?;
+        let inode = fs.create_in(dir, name, InodeKind::File, mode)?;
+        Ok(FileInfo::from_inode(name, &inode))
+    }
+
+    // crea un directorio vacio, falla si ya existe
+    pub fn mkdir(&self, path: &str, mode: u16) -> Result<FileInfo, QrfsError> {
+        let mut fs = self.lock();
+        let (dir, name) = parent_and_name(&mut fs, path)?;
+        let inode = fs.create_in(dir, name, InodeKind::Directory, mode)?;
+        Ok(FileInfo::from_inode(name, &inode))
+    }
+
+    // borra un archivo o un directorio vacio
+    pub fn remove(&self, path: &str) -> Result<(), QrfsError> {
+        let mut fs = self.lock();
+        let (dir, name) = parent_and_name(&mut fs, path)?;
+        fs.remove_in(dir, name)
+    }
+
+    // renombra o mueve de directorio; si el destino existe se reemplaza
+    // (todo en una transaccion)
+    pub fn rename(&self, from: &str, to: &str) -> Result<(), QrfsError> {
+        let mut fs = self.lock();
+        let (from_dir, from_name) = parent_and_name(&mut fs, from)?;
+        let (to_dir, to_name) = parent_and_name(&mut fs, to)?;
+        fs.move_entry(from_dir, from_name, to_dir, to_name)
+    }
+
+    pub fn truncate(&self, path: &str, size: u64) -> Result<(), QrfsError> {
+        let mut fs = self.lock();
+        let id = file_id(&mut fs, path)?;
+        fs.truncate(id, size)
+    }
+
+    // cambia los flags de un archivo (INODE_FLAG_IMMUTABLE, INODE_FLAG_APPEND)
+    pub fn set_flags(&self, path: &str, flags: u32) -> Result<(), QrfsError> {
+        let mut fs = self.lock();
+        let id = file_id(&mut fs, path)?;
+        fs.set_inode_flags(id, flags)
+    }
+
+    // fija un archivo al principio del disco (sus qr quedan juntos con la
+    // metadata, primeros para imprimir y escanear) o lo suelta; devuelve
+    // (bloques movidos, bloques que no entraron en la zona fija)
+    pub fn set_pinned(&self, path: &str, pinned: bool) -> Result<(usize, usize), QrfsError> {
+        let mut fs = self.lock();
+        let id = file_id(&mut fs, path)?;
+        fs.set_pinned(id, pinned)
+    }
+
+    // junta las colas de los archivos chicos en bloques compartidos (ver
+    // tail.rs); devuelve cuantos bloques se liberaron. con el empaquetado
+    // prendido tambien se hace solo al soltar la api o desmontar
+    pub fn pack_tails(&self) -> Result<usize, QrfsError> {
+        self.lock().pack_tails()
+    }
+
+    // crea la clave maestra del disco; los archivos que se creen despues
+    // quedan cifrados, cada uno con su clave
+    pub fn enable_encryption(&self, passphrase: &str) -> Result<(), QrfsError> {
+        self.lock().enable_encryption(passphrase, PBKDF2_ITERATIONS)
+    }
+
+    // abre la clave maestra para leer y crear archivos cifrados
+    pub fn unlock(&self, passphrase: &str) -> Result<(), QrfsError> {
+        self.lock().unlock(passphrase)
+    }
+
+    // cambia la frase sin tocar las claves (no recifra nada)
+    pub fn change_passphrase(&self, old_passphrase: &str, new_passphrase: &str) -> Result<(), QrfsError> {
+        self.lock()
+            .change_passphrase(old_passphrase, new_passphrase, PBKDF2_ITERATIONS)
+    }
+
+    // empieza (o retoma) la rotacion de claves; devuelve los inodos que
+    // faltan recifrar, para pasarlos de a uno a rekey_inode
+    pub fn begin_rekey(&self, old_passphrase: &str, new_passphrase: &str) -> Result<Vec<u32>, QrfsError> {
+        let mut fs = self.lock();
+        fs.begin_rekey(old_passphrase, new_passphrase, PBKDF2_ITERATIONS)?;
+        Ok(fs.rekey_pending())
+    }
+
+    pub fn rekey_inode(&self, id: u32) -> Result<usize, QrfsError> {
+        self.lock().rekey_inode(id)
+    }
+
+    pub fn finish_rekey(&self) -> Result<(), QrfsError> {
+        self.lock().finish_rekey()
+    }
+
+    // fragmentos de la maestra: con threshold de los count se recupera el
+    // disco sin la frase
+    pub fn key_shards(&self, passphrase: &str, threshold: u8, count: u8) -> Result<Vec<KeyShard>, QrfsError> {
+        self.lock().master_key_shards(passphrase, threshold, count)
+    }
+
+    pub fn unlock_with_shards(&self, shards: &[KeyShard]) -> Result<(), QrfsError> {
+        self.lock().unlock_with_shards(shards)
+    }
+
+    // pone una frase nueva a partir de los fragmentos (frase olvidada)
+    pub fn recover_passphrase(&self, shards: &[KeyShard], new_passphrase: &str) -> Result<(), QrfsError> {
+        self.lock()
+            .recover_passphrase(shards, new_passphrase, PBKDF2_ITERATIONS)
+    }
+
+    // clave de un archivo cifrado: con ella y sus qr se lee sin el disco
+    pub fn file_key(&self, path: &str) -> Result<FileKey, QrfsError> {
+        let mut fs = self.lock_fresh();
+        let id = file_id(&mut fs, path)?;
+        fs.shared_file_key(id)
+    }
+}
+
+// cantidad de tramos contiguos en una lista de bloques
+fn block_extents(blocks: &[BlockId]) -> u64 {
+    if blocks.is_empty() {
+        return 0;
+    }
+    1 + blocks.windows(2).filter(|pair| pair[1] != pair[0] + 1).count() as u64
+}
+
+// "/" -> [], "/docs/a.txt" -> ["docs", "a.txt"]
+fn components(path: &str) -> Result<Vec<&str>, QrfsError> {
+    let parts: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+    if parts.iter().any(|&part| part == "." || part == "..") {
+        return Err(QrfsError::NotFound(format!("{} (sin . ni .. en la ruta)", path)));
+    }
+    Ok(parts)
+}
+
+// inodo de la ruta, bajando directorio por directorio desde la raiz
+fn resolve<B: BlockStorage + 'static>(fs: &mut QrfsFilesystem<B>, path: &str) -> Result<u32, QrfsError> {
+    let mut id = fs.superblock().root_inode;
+    for name in components(path)? {
+        id = fs
+            .lookup_in(id, name)?
+            .ok_or_else(|| QrfsError::NotFound(path.to_string()))?;
+    }
+    Ok(id)
+}
+
+// directorio que contiene la ruta y el ultimo nombre
+fn parent_and_name<'a, B: BlockStorage + 'static>(
+    fs: &mut QrfsFilesystem<B>,
+    path: &'a str,
+) -> Result<(u32, &'a str), QrfsError> {
+    let mut parts = components(path)?;
+    let name = parts
+        .pop()
+        .ok_or_else(|| QrfsError::Other("se esperaba un archivo, no /".into()))?;
+    let mut dir = fs.superblock().root_inode;
+    for part in parts {
+        dir = fs
+            .lookup_in(dir, part)?
+            .ok_or_else(|| QrfsError::NotFound(path.to_string()))?;
+    }
+    Ok((dir, name))
+}
+
+fn file_id<B: BlockStorage + 'static>(
+    fs: &mut QrfsFilesystem<B>,
+    path: &str,
+) -> Result<u32, QrfsError> {
+    let (dir, name) = parent_and_name(fs, path)?;
+    fs.lookup_in(dir, name)?
+        .ok_or_else(|| QrfsError::NotFound(path.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypt::BLOCK_OVERHEAD;
+    use crate::disk::{BLOCK_SIZE, INODE_FLAG_APPEND, INODE_FLAG_IMMUTABLE, INODE_FLAG_PINNED, INODE_FLAG_TAIL};
+    use crate::fs::{XATTR_RATIO, XATTR_STORED};
+    use crate::fs_format::{format_storage, read_directory, read_inode_table, read_superblock};
+    use crate::tail;
+    use crate::storage::InMemoryBlockStorage;
+
+    fn new_api() -> QrfsApi<InMemoryBlockStorage> {
+        let storage = InMemoryBlockStorage::new(200, BLOCK_SIZE);
+        format_storage(&storage, &Superblock::new(200, 64)).unwrap();
+        QrfsApi::open(Arc::new(storage)).unwrap()
+    }
+
+    #[test]
+    fn write_read_and_list_roundtrip() {
+        let api = new_api();
+        let data: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+
+        let info = api.write("/datos.bin", &data).unwrap();
+        assert_eq!(info.size, data.len() as u64);
+        assert_eq!(api.read("/datos.bin").unwrap(), data);
+        assert_eq!(api.read_at("/datos.bin", 126, 4).unwrap(), &data[126..130]);
+
+        let names: Vec<String> = api.list("/").unwrap().into_iter().map(|f| f.name).collect();
+        assert_eq!(names, vec!["datos.bin".to_string()]);
+
+        // reemplazar con algo mas corto no deja restos
+        api.write("/datos.bin", b"hola").unwrap();
+        assert_eq!(api.read("/datos.bin").unwrap(), b"hola");
+    }
+
+    #[test]
+    fn subdirectories_by_path_keep_the_tree_consistent() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        format_storage(&*storage, &Superblock::new(200, 64)).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+
+        api.mkdir("/docs", 0o755).unwrap();
+        api.mkdir("/docs/viejos", 0o755).unwrap();
+        api.write("/docs/a.txt", b"hola").unwrap();
+        assert!(api.stat("/docs").unwrap().is_dir());
+        assert_eq!(api.read("/docs/a.txt").unwrap(), b"hola");
+        let names: Vec<String> = api.list("/docs").unwrap().into_iter().map(|f| f.name).collect();
+        assert_eq!(names, ["a.txt", "viejos"]);
+        assert_eq!(api.list("/docs/a.txt").unwrap_err().errno(), libc::ENOTDIR);
+        assert_eq!(api.stat("/docs/a.txt/x").unwrap_err().errno(), libc::ENOTDIR);
+
+        // mover entre directorios reescribe el '..' del que se mueve
+        api.rename("/docs/a.txt", "/docs/viejos/a.txt").unwrap();
+        api.rename("/docs/viejos", "/viejos").unwrap();
+        assert_eq!(api.read("/viejos/a.txt").unwrap(), b"hola");
+        api.mkdir("/viejos/sub", 0o755).unwrap();
+        assert!(api.rename("/viejos", "/viejos/sub/v").is_err());
+        api.remove("/viejos/sub").unwrap();
+        assert_eq!(api.remove("/viejos").unwrap_err().errno(), libc::ENOTEMPTY);
+        api.flush().unwrap();
+        let report = crate::fsck::check(&*storage, &crate::fsck::FsckOptions::default());
+        assert!(report.clean, "{:?}", report.first_problem());
+
+        // y sigue igual al volver a abrir
+        drop(api);
+        let api = QrfsApi::open(storage).unwrap();
+        assert_eq!(api.read("/viejos/a.txt").unwrap(), b"hola");
+        api.remove("/viejos/a.txt").unwrap();
+        api.remove("/viejos").unwrap();
+        assert!(api.list("/docs").unwrap().is_empty());
+    }
+
+    #[test]
+    fn data_path_uses_the_block_size_of_the_superblock() {
+        let storage = InMemoryBlockStorage::new(100, 512);
+        format_storage(&storage, &Superblock::with_block_size(100, 64, 512)).unwrap();
+        let api = QrfsApi::open(Arc::new(storage)).unwrap();
+        let data: Vec<u8> = (0..1500u32).map(|i| (i % 251) as u8).collect();
+        // la entrada del directorio tambien ocupa un bloque
+        api.create("/a", 0o644).unwrap();
+        let free = api.usage().free_blocks;
+
+        api.write("/a", &data).unwrap();
+        assert_eq!(api.usage().free_blocks, free - 3);
+        assert_eq!(api.read_at("/a", 510, 4).unwrap(), &data[510..514]);
+
+        // escritura parcial en el medio de un bloque y agrandando el archivo
+        api.write_at("/a", 1400, &[9u8; 200]).unwrap();
+        api.flush().unwrap();
+        assert_eq!(api.usage().free_blocks, free - 4);
+        assert_eq!(api.read_at("/a", 1398, 4).unwrap(), [data[1398], data[1399], 9, 9]);
+
+        api.truncate("/a", 600).unwrap();
+        api.truncate("/a", 1100).unwrap();
+        assert_eq!(api.usage().free_blocks, free - 2);
+        let read = api.read("/a").unwrap();
+        assert_eq!(&read[..600], &data[..600]);
+        assert!(read[600..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn usage_counts_only_data_blocks() {
+        let api = new_api();
+        let sb = api.superblock();
+        let usage = api.usage();
+
+        assert_eq!(usage.data_blocks, (sb.total_blocks - sb.data_block_start) as u64);
+        assert_eq!(usage.free_blocks, usage.data_blocks);
+
+        api.write("/a", &[1u8; 3 * BLOCK_SIZE]).unwrap();
+        // tres bloques de datos y uno para el directorio raiz
+        assert_eq!(api.usage().free_blocks, usage.data_blocks - 4);
+    }
+
+    #[test]
+    fn rename_remove_and_errors() {
+        let api = new_api();
+        api.write("/a.txt", b"uno").unwrap();
+        api.write("/b.txt", b"dos").unwrap();
+
+        assert!(matches!(api.create("/a.txt", 0o644), Err(QrfsError::AlreadyExists(_))));
+
+        api.rename("/a.txt", "/b.txt").unwrap();
+        assert_eq!(api.read("/b.txt").unwrap(), b"uno");
+        assert!(matches!(api.stat("/a.txt"), Err(QrfsError::NotFound(_))));
+
+        let before = api.usage();
+        api.remove("/b.txt").unwrap();
+        let after = api.usage();
+        assert_eq!(after.free_inodes, before.free_inodes + 1);
+        assert!(after.free_blocks > before.free_blocks);
+        assert!(api.list("/").unwrap().is_empty());
+        assert!(matches!(api.stat("/sub/x"), Err(QrfsError::NotFound(_))));
+    }
+
+    #[test]
+    fn truncate_frees_blocks_and_zeroes_tail() {
+        let api = new_api();
+        api.write("/t", &[7u8; 300]).unwrap();
+
+        api.truncate("/t", 10).unwrap();
+        api.truncate("/t", 20).unwrap();
+
+        let data = api.read("/t").unwrap();
+        assert_eq!(&data[..10], &[7u8; 10]);
+        assert_eq!(&data[10..], &[0u8; 10]);
+    }
+
+    #[test]
+    fn write_at_fills_holes_and_rolls_back_on_enospc() {
+        let api = new_api();
+        api.create("/hueco", 0o644).unwrap();
+        api.write_at("/hueco", 300, b"fin").unwrap();
+
+        let data = api.read("/hueco").unwrap();
+        assert_eq!(data.len(), 303);
+        assert!(data[..300].iter().all(|&b| b == 0));
+        assert_eq!(&data[300..], b"fin");
+
+        // pedir mas bloques de los que hay no debe dejar bloques perdidos
+        let before = api.usage();
+        let huge = vec![1u8; (before.free_blocks as usize + 4) * BLOCK_SIZE];
+        assert!(matches!(api.write_at("/hueco", 0, &huge), Err(QrfsError::NoSpace(_))));
+        assert_eq!(api.usage().free_blocks, before.free_blocks);
+    }
+
+    #[test]
+    fn quick_check_falls_back_to_read_only() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        let sb = Superblock::new(200, 64);
+        format_storage(storage.as_ref(), &sb).unwrap();
+
+        let fs = QrfsFilesystem::new(storage.clone()).unwrap();
+        assert!(fs.quick_check().is_empty());
+        let api = QrfsApi::new(fs.with_quick_check());
+        api.write("/a.txt", b"hola").unwrap();
+        drop(api);
+
+        // bitmap borrado: los bloques reservados y el del archivo quedan "libres"
+        storage.write_block(sb.free_map_start, &[0u8; BLOCK_SIZE]).unwrap();
+
+        let fs = QrfsFilesystem::new(storage).unwrap().with_quick_check();
+        assert!(fs.is_read_only());
+        let api = QrfsApi::new(fs);
+        assert_eq!(api.read("/a.txt").unwrap(), b"hola");
+        assert!(matches!(api.write("/a.txt", b"x"), Err(QrfsError::ReadOnly(_))));
+        assert!(matches!(api.remove("/a.txt"), Err(QrfsError::ReadOnly(_))));
+    }
+
+    #[test]
+    fn a_newer_minor_version_mounts_read_only() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        let mut sb = Superblock::new(200, 64);
+        sb.minor_version = crate::disk::QRFS_MINOR_VERSION + 1;
+        format_storage(storage.as_ref(), &sb).unwrap();
+
+        // pedir escritura no alcanza para pisar campos que no se conocen
+        let fs = QrfsFilesystem::new(storage.clone()).unwrap().with_read_only(false);
+        assert!(fs.is_read_only());
+        let api = QrfsApi::new(fs);
+        assert!(api.list("/").unwrap().is_empty());
+        assert!(matches!(api.write("/a.txt", b"x"), Err(QrfsError::ReadOnly(_))));
+
+        // otra mayor que no se declara compatible no se monta
+        sb.version = crate::disk::QRFS_VERSION + 1;
+        sb.min_compat_version = sb.version as u8;
+        format_storage(storage.as_ref(), &sb).unwrap();
+        assert!(QrfsFilesystem::new(storage).is_err());
+    }
+
+    #[test]
+    fn orphan_blocks_are_returned_to_the_bitmap() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        let sb = Superblock::new(200, 64);
+        format_storage(storage.as_ref(), &sb).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        api.write("/a.txt", &[1u8; 300]).unwrap();
+        let before = api.usage().free_blocks;
+        drop(api);
+
+        // marcar como usado un bloque que ningun inodo tiene
+        let mut bitmap = storage.read_block(sb.free_map_start).unwrap();
+        bitmap[199 / 8] |= 1 << (199 % 8);
+        storage.write_block(sb.free_map_start, &bitmap).unwrap();
+
+        let mut fs = QrfsFilesystem::new(storage).unwrap();
+        assert!(fs.quick_check().is_empty());
+        assert_eq!(fs.reclaim_orphan_blocks().unwrap(), vec![199]);
+        assert!(fs.reclaim_orphan_blocks().unwrap().is_empty());
+
+        let api = QrfsApi::new(fs);
+        assert_eq!(api.usage().free_blocks, before);
+        assert_eq!(api.read("/a.txt").unwrap(), vec![1u8; 300]);
+    }
+
+    #[test]
+    fn failed_create_rolls_back_metadata() {
+        let api = new_api();
+        // dejar el disco lleno (un bloque queda para el directorio)
+        let free = api.usage().free_blocks as usize;
+        api.write("/lleno", &vec![1u8; (free - 1) * BLOCK_SIZE]).unwrap();
+        assert_eq!(api.usage().free_blocks, 0);
+
+        // crear archivos hasta que el directorio necesite otro bloque
+        let mut failed = None;
+        for i in 0..32 {
+            let name = format!("/archivo_{:02}", i);
+            let before = api.usage();
+            match api.create(&name, 0o644) {
+                Ok(_) => continue,
+                Err(e) => {
+                    assert!(matches!(e, QrfsError::NoSpace(_)));
+                    assert!(matches!(api.stat(&name), Err(QrfsError::NotFound(_))));
+                    assert_eq!(api.usage().free_inodes, before.free_inodes);
+                    failed = Some(name);
+                    break;
+                }
+            }
+        }
+        let name = failed.expect("el directorio nunca se quedo sin espacio");
+
+        // al liberar espacio la misma operacion funciona
+        api.remove("/lleno").unwrap();
+        api.create(&name, 0o644).unwrap();
+        assert!(api.list("/").unwrap().iter().any(|f| format!("/{}", f.name) == name));
+    }
+
+    #[test]
+    fn removing_entries_shrinks_the_directory() {
+        let api = new_api();
+        let root = api.superblock().root_inode;
+        api.create("/queda", 0o644).unwrap();
+        let usage = api.usage();
+        let blocks = api.lock().inode(root).unwrap().blocks.clone();
+        assert_eq!(blocks.len(), 1);
+
+        for i in 0..30 {
+            api.create(&format!("/archivo_con_nombre_largo_{:02}", i), 0o644).unwrap();
+        }
+        assert!(api.lock().inode(root).unwrap().blocks.len() > 3);
+        for i in 0..30 {
+            api.remove(&format!("/archivo_con_nombre_largo_{:02}", i)).unwrap();
+        }
+
+        // quedan los bloques del principio y el resto vuelve al bitmap
+        assert_eq!(api.lock().inode(root).unwrap().blocks, blocks);
+        assert_eq!(api.usage().free_blocks, usage.free_blocks);
+        let names: Vec<String> = api.list("/").unwrap().into_iter().map(|f| f.name).collect();
+        assert_eq!(names, vec!["queda"]);
+    }
+
+    #[test]
+    fn inode_table_grows_past_mkfs_size() {
+        let storage = Arc::new(InMemoryBlockStorage::new(2000, BLOCK_SIZE));
+        let sb = Superblock::new(2000, 64);
+        format_storage(storage.as_ref(), &sb).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+
+        // mas archivos que slots tenia mkfs, y uno con mas bloques que la tabla fija
+        for i in 0..80 {
+            api.write(&format!("/f{:02}", i), format!("archivo {}", i).as_bytes())
+                .unwrap();
+        }
+        let big: Vec<u8> = (0..1200 * BLOCK_SIZE).map(|i| (i % 253) as u8).collect();
+        api.write("/grande", &big).unwrap();
+        assert!(api.superblock().inode_count > 64);
+        assert_ne!(api.superblock().inode_ext_head, 0);
+        drop(api);
+
+        let mut fs = QrfsFilesystem::new(storage).unwrap();
+        assert!(fs.quick_check().is_empty(), "{:?}", fs.quick_check());
+        assert!(fs.reclaim_orphan_blocks().unwrap().is_empty());
+
+        let api = QrfsApi::new(fs);
+        assert_eq!(api.list("/").unwrap().len(), 81);
+        assert_eq!(api.read("/f79").unwrap(), b"archivo 79");
+        assert_eq!(api.read("/grande").unwrap(), big);
+    }
+
+    #[test]
+    fn directories_load_on_first_use() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        let sb = Superblock::new(200, 64);
+        format_storage(storage.as_ref(), &sb).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        api.write("/a.txt", b"hola").unwrap();
+        let dir_block = api.lock().inode(sb.root_inode).unwrap().blocks[0];
+        drop(api);
+
+        // con el directorio roto el montaje igual abre (no lo lee) ...
+        let good = storage.read_block(dir_block).unwrap();
+        storage.write_block(dir_block, &[0xffu8; BLOCK_SIZE]).unwrap();
+        let fs = QrfsFilesystem::new(storage.clone()).unwrap();
+        assert!(fs.quick_check().iter().any(|p| p.contains("directorio raiz")));
+
+        // ... y el error aparece recien al usarlo, sin confundirlo con un directorio vacio
+        let api = QrfsApi::new(fs);
+        assert!(matches!(api.list("/"), Err(QrfsError::Other(_))));
+        drop(api);
+
+        storage.write_block(dir_block, &good).unwrap();
+        let api = QrfsApi::open(storage).unwrap();
+        api.create("/b.txt", 0o644).unwrap();
+        let names: Vec<String> = api.list("/").unwrap().into_iter().map(|f| f.name).collect();
+        assert_eq!(names, vec!["a.txt", "b.txt"]);
+    }
+
+    #[test]
+    fn small_writes_stay_buffered_until_flush() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        let sb = Superblock::new(200, 64);
+        format_storage(storage.as_ref(), &sb).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        api.create("/log", 0o644).unwrap();
+
+        for i in 0..20u64 {
+            api.write_at("/log", i * 4, b"abcd").unwrap();
+        }
+        let id = api.stat("/log").unwrap().inode_id;
+        let block = api.lock().inode(id).unwrap().blocks[0];
+        assert_eq!(api.read("/log").unwrap(), b"abcd".repeat(20));
+        // el bloque todavia no se codifico
+        assert!(storage.read_block(block).unwrap().iter().all(|&b| b == 0));
+
+        api.flush().unwrap();
+        assert_eq!(&storage.read_block(block).unwrap()[..80], &b"abcd".repeat(20)[..]);
+    }
+
+    #[test]
+    fn appends_persist_the_bitmap_only_on_flush() {
+        use std::sync::atomic::Ordering;
+
+        let inner = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        let sb = Superblock::new(200, 64);
+        format_storage(inner.as_ref(), &sb).unwrap();
+        let api = QrfsApi::open(inner.clone()).unwrap();
+        api.create("/log", 0o644).unwrap();
+        drop(api);
+
+        let counting = Arc::new(CrashingStorage {
+            inner: inner.clone(),
+            writes_left: usize::MAX.into(),
+            broken_from: u32::MAX,
+        });
+        let api = QrfsApi::open(counting.clone()).unwrap();
+        // cada escritura reserva un bloque nuevo
+        let chunk = vec![7u8; BLOCK_SIZE];
+        for i in 0..10u64 {
+            api.write_at("/log", i * BLOCK_SIZE as u64, &chunk).unwrap();
+        }
+        assert_eq!(counting.writes_left.load(Ordering::SeqCst), usize::MAX);
+
+        // el flush guarda los 10 bloques, el bitmap una vez, la tabla y el superblock
+        api.flush().unwrap();
+        let writes = usize::MAX - counting.writes_left.load(Ordering::SeqCst);
+        let metadata = (sb.free_map_blocks + sb.inode_table_blocks + sb.region_blocks()) as usize;
+        assert_eq!(writes, 10 + metadata);
+        drop(api);
+
+        let api = QrfsApi::open(inner).unwrap();
+        assert_eq!(api.read("/log").unwrap(), chunk.repeat(10));
+        assert!(api.lock().quick_check().is_empty());
+    }
+
+    #[test]
+    fn fallocate_reserves_all_blocks_or_none() {
+        let api = new_api();
+        api.write("/a", b"hola").unwrap();
+        let id = api.stat("/a").unwrap().inode_id;
+        let free = api.usage().free_blocks;
+
+        // uno mas de los que hay libres: no se reserva ninguno
+        let too_many = (free + 2) * BLOCK_SIZE as u64;
+        let err = api.lock().allocate_range(id, 0, too_many, false).unwrap_err();
+        assert!(matches!(err, QrfsError::NoSpace(_)));
+        assert_eq!(api.usage().free_blocks, free);
+        assert_eq!(api.stat("/a").unwrap().size, 4);
+
+        // con keep_size los bloques quedan reservados pero el tamaño no cambia
+        api.lock().allocate_range(id, 0, 3 * BLOCK_SIZE as u64, true).unwrap();
+        assert_eq!(api.usage().free_blocks, free - 2);
+        assert_eq!(api.stat("/a").unwrap().size, 4);
+
+        api.lock().allocate_range(id, 0, 3 * BLOCK_SIZE as u64, false).unwrap();
+        assert_eq!(api.usage().free_blocks, free - 2);
+        let data = api.read("/a").unwrap();
+        assert_eq!(data.len(), 3 * BLOCK_SIZE);
+        assert_eq!(&data[..4], b"hola");
+        assert!(data[4..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn fragmentation_reports_free_runs_and_split_files() {
+        let api = new_api();
+        let block = vec![1u8; BLOCK_SIZE];
+        api.write("/a", &block).unwrap();
+        api.write("/b", &block).unwrap();
+        api.write("/c", &block).unwrap();
+        // a crece despues de b y c: queda en dos tramos
+        api.write_at("/a", BLOCK_SIZE as u64, &block).unwrap();
+        api.remove("/b").unwrap();
+
+        let report = api.fragmentation().unwrap();
+        assert_eq!(report.free_blocks, api.usage().free_blocks);
+        // el hueco de b y el resto del disco
+        assert_eq!(report.free_extents, 2);
+        assert_eq!(report.largest_free_extent, report.free_blocks - 1);
+        assert_eq!(report.free_extent_histogram[0], (1, 1));
+
+        assert_eq!(report.files[0].name, "a");
+        assert_eq!((report.files[0].blocks, report.files[0].extents), (2, 2));
+        assert_eq!(report.files[0].score, 1.0);
+        assert_eq!(report.files[1].name, "c");
+        assert_eq!(report.files[1].score, 0.0);
+    }
+
+    #[test]
+    fn sealed_mount_rejects_writes_and_changed_blocks() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        format_storage(storage.as_ref(), &Superblock::new(200, 64)).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        api.write("/a.txt", b"hola").unwrap();
+        api.write("/b.txt", b"chau").unwrap();
+        let id = api.stat("/b.txt").unwrap().inode_id;
+        let block = api.lock().inode(id).unwrap().blocks[0];
+        drop(api);
+
+        let api = QrfsApi::new(QrfsFilesystem::new(storage.clone()).unwrap().with_sealed());
+        assert!(matches!(api.write("/c.txt", b"x"), Err(QrfsError::ReadOnly(_))));
+
+        // un bloque cambiado despues de medir no se entrega
+        storage.write_block(block, b"otra cosa").unwrap();
+        assert_eq!(api.read("/a.txt").unwrap(), b"hola");
+        assert!(api.read("/b.txt").is_err());
+        let status = api.lock().seal_status().unwrap();
+        assert!(status.contains("fallas: 1"), "{}", status);
+    }
+
+    #[test]
+    fn degraded_mount_reports_blocks_still_to_scan() {
+        let dir = std::env::temp_dir().join(format!("qrfs_degraded_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let storage = Arc::new(crate::storage::QrStorageManager::new(&dir, 128, 64));
+        format_storage(storage.as_ref(), &Superblock::with_block_size(64, 16, 128)).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        let data: Vec<u8> = (0..300u32).map(|i| (i % 200 + 1) as u8).collect();
+        api.write("/escaneo.bin", &data).unwrap();
+        api.flush().unwrap();
+        let id = api.stat("/escaneo.bin").unwrap().inode_id;
+        let missing = api.lock().inode(id).unwrap().blocks[1];
+        drop(api);
+
+        // el qr del segundo bloque todavia no se escaneo
+        let png = storage.block_path(missing);
+        let saved = std::fs::read(&png).unwrap();
+        std::fs::remove_file(&png).unwrap();
+
+        let api = QrfsApi::new(QrfsFilesystem::new(storage.clone()).unwrap().with_degraded());
+        assert!(api.read("/escaneo.bin").is_err());
+        assert_eq!(api.read_at("/escaneo.bin", 0, 128).unwrap(), &data[..128]);
+        assert_eq!(api.read_at("/escaneo.bin", 256, 44).unwrap(), &data[256..]);
+        let report = api.lock().missing_status().unwrap();
+        assert!(report.contains("escaneo.bin (inodo"), "{}", report);
+        assert!(report.contains("faltan 1 de 3 bloques"), "{}", report);
+        assert!(report.contains("bytes: 128..256"), "{}", report);
+        assert!(matches!(api.write("/otro.txt", b"x"), Err(QrfsError::ReadOnly(_))));
+
+        // llega el qr: se lee sin volver a montar
+        std::fs::write(&png, saved).unwrap();
+        assert_eq!(api.read("/escaneo.bin").unwrap(), data);
+        assert!(api.lock().missing_status().unwrap().starts_with("bloques sin escanear: 0"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn immutable_and_append_only_flags_are_enforced() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        format_storage(storage.as_ref(), &Superblock::new(200, 64)).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        api.write("/fijo.txt", b"hola").unwrap();
+        api.write("/log.txt", b"uno\n").unwrap();
+        api.write("/otro.txt", b"x").unwrap();
+
+        api.set_flags("/fijo.txt", INODE_FLAG_IMMUTABLE).unwrap();
+        let denied = |r: Result<(), QrfsError>| matches!(r, Err(QrfsError::NotPermitted(_)));
+        assert!(denied(api.write_at("/fijo.txt", 0, b"X").map(|_| ())));
+        assert!(denied(api.truncate("/fijo.txt", 0)));
+        assert!(denied(api.remove("/fijo.txt")));
+        assert!(denied(api.rename("/fijo.txt", "/otro.txt")));
+        assert!(denied(api.rename("/otro.txt", "/fijo.txt")));
+        assert_eq!(api.read("/otro.txt").unwrap(), b"x");
+
+        api.set_flags("/log.txt", INODE_FLAG_APPEND).unwrap();
+        assert!(denied(api.write_at("/log.txt", 0, b"X").map(|_| ())));
+        api.write_at("/log.txt", 4, b"dos\n").unwrap();
+        // reescribir lo que ya estaba (como hace el writeback) no cambia nada
+        api.write_at("/log.txt", 0, b"uno\ndos\ntres\n").unwrap();
+        assert!(denied(api.write_at("/log.txt", 0, b"UNO\n").map(|_| ())));
+        assert!(denied(api.truncate("/log.txt", 0)));
+
+        // los flags sobreviven a volver a abrir, y sacarlos libera el archivo
+        drop(api);
+        let api = QrfsApi::open(storage).unwrap();
+        assert_eq!(api.stat("/fijo.txt").unwrap().flags, INODE_FLAG_IMMUTABLE);
+        assert_eq!(api.read("/log.txt").unwrap(), b"uno\ndos\ntres\n");
+        api.set_flags("/fijo.txt", 0).unwrap();
+        api.remove("/fijo.txt").unwrap();
+    }
+
+    #[test]
+    fn old_inode_format_still_mounts_without_flags() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        let mut sb = Superblock::new(200, 64);
+        sb.inode_format = 0;
+        format_storage(storage.as_ref(), &sb).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        api.write("/a.txt", b"hola").unwrap();
+        assert!(matches!(
+            api.set_flags("/a.txt", INODE_FLAG_IMMUTABLE),
+            Err(QrfsError::Unimplemented(_))
+        ));
+        drop(api);
+
+        let api = QrfsApi::open(storage).unwrap();
+        assert_eq!(api.read("/a.txt").unwrap(), b"hola");
+        assert_eq!(api.superblock().inode_format, 0);
+    }
+
+    #[test]
+    fn special_files_keep_their_kind_and_device_number() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        format_storage(storage.as_ref(), &Superblock::new(200, 64)).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        {
+            let mut fs = api.lock();
+            fs.create_node("fifo", InodeKind::Fifo, 0o644, 0).unwrap();
+            fs.create_node("tty", InodeKind::CharDevice, 0o620, 0x0401).unwrap();
+            fs.create_symlink("enlace", "datos/archivo.txt").unwrap();
+        }
+        api.flush().unwrap();
+        drop(api);
+
+        let api = QrfsApi::open(storage).unwrap();
+        assert_eq!(api.stat("/fifo").unwrap().kind, InodeKind::Fifo);
+        let tty = api.stat("/tty").unwrap();
+        assert_eq!(tty.kind, InodeKind::CharDevice);
+        assert_eq!(api.lock().inode(tty.inode_id).unwrap().rdev, 0x0401);
+        let link = api.stat("/enlace").unwrap();
+        assert_eq!(link.kind, InodeKind::Symlink);
+        assert_eq!(api.lock().read_link(link.inode_id).unwrap(), b"datos/archivo.txt");
+        assert!(api.lock().read_link(tty.inode_id).is_err());
+    }
+
+    #[test]
+    fn directory_entries_keep_the_kind_through_renames() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        format_storage(storage.as_ref(), &Superblock::new(200, 64)).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        api.lock().create_symlink("enlace", "destino").unwrap();
+        api.lock().create_node("fifo", InodeKind::Fifo, 0o644, 0).unwrap();
+        api.rename("/enlace", "/otro").unwrap();
+        // el destino reemplazado deja su entrada con el tipo del que llega
+        api.rename("/otro", "/fifo").unwrap();
+        drop(api);
+
+        let sb = read_superblock(storage.as_ref()).unwrap();
+        let root = read_inode_table(storage.as_ref(), &sb)
+            .find_map(|slot| slot.ok().filter(|inode| inode.id == sb.root_inode))
+            .unwrap();
+        let (entries, _) = read_directory(storage.as_ref(), &sb, &root).unwrap();
+        let kinds: Vec<(String, InodeKind)> = entries
+            .into_iter()
+            .filter(|entry| !entry.name.starts_with('.'))
+            .map(|entry| (entry.name, entry.kind))
+            .collect();
+        assert_eq!(kinds, vec![("fifo".to_string(), InodeKind::Symlink)]);
+    }
+
+    #[test]
+    fn second_mount_notices_commits_from_the_first() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        format_storage(storage.as_ref(), &Superblock::new(200, 64)).unwrap();
+        let writer = QrfsApi::open(storage.clone()).unwrap();
+        let reader = QrfsApi::new(
+            QrfsFilesystem::new(storage.clone())
+                .unwrap()
+                .with_metadata_recheck(std::time::Duration::ZERO),
+        );
+        assert!(reader.list("/").unwrap().is_empty());
+
+        writer.write("/nuevo.txt", b"hola").unwrap();
+        writer.flush().unwrap();
+        assert!(read_superblock(storage.as_ref()).unwrap().meta_generation > 0);
+        let names: Vec<String> = reader.list("/").unwrap().into_iter().map(|f| f.name).collect();
+        assert_eq!(names, vec!["nuevo.txt".to_string()]);
+        assert_eq!(reader.read("/nuevo.txt").unwrap(), b"hola");
+
+        writer.remove("/nuevo.txt").unwrap();
+        assert!(reader.stat("/nuevo.txt").is_err());
+    }
+
+    #[test]
+    fn shared_writers_revalidate_before_each_change() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        format_storage(storage.as_ref(), &Superblock::new(200, 64)).unwrap();
+        let shared = || QrfsApi::new(QrfsFilesystem::new(storage.clone()).unwrap().with_shared_disk(true));
+        let a = shared();
+        let b = shared();
+
+        // cada uno escribe sobre lo que dejo el otro, nada se pisa
+        a.write("/desde_a.txt", b"aaa").unwrap();
+        b.write("/desde_b.txt", b"bbb").unwrap();
+        a.write("/otra_de_a.txt", b"a2").unwrap();
+        drop((a, b));
+
+        let api = QrfsApi::open(storage).unwrap();
+        let mut names: Vec<String> = api.list("/").unwrap().into_iter().map(|f| f.name).collect();
+        names.sort();
+        assert_eq!(names, vec!["desde_a.txt", "desde_b.txt", "otra_de_a.txt"]);
+        assert_eq!(api.read("/desde_a.txt").unwrap(), b"aaa");
+        assert_eq!(api.read("/desde_b.txt").unwrap(), b"bbb");
+        assert_eq!(api.read("/otra_de_a.txt").unwrap(), b"a2");
+    }
+
+    #[test]
+    fn old_inode_format_refuses_device_nodes() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        let mut sb = Superblock::new(200, 64);
+        sb.inode_format = 2;
+        format_storage(storage.as_ref(), &sb).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        assert!(matches!(
+            api.lock().create_node("sda", InodeKind::BlockDevice, 0o660, 0x0800),
+            Err(QrfsError::Unimplemented(_))
+        ));
+        // sin rdev que guardar, una fifo entra en la tabla vieja
+        api.lock().create_node("fifo", InodeKind::Fifo, 0o644, 0).unwrap();
+        drop(api);
+
+        let api = QrfsApi::open(storage).unwrap();
+        assert_eq!(api.stat("/fifo").unwrap().kind, InodeKind::Fifo);
+        assert!(api.stat("/sda").is_err());
+    }
+
+    #[test]
+    fn encrypted_files_open_with_the_passphrase_or_their_own_key() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        format_storage(storage.as_ref(), &Superblock::new(200, 64)).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        api.write("/claro.txt", b"a la vista").unwrap();
+        api.lock().enable_encryption("frase", 10).unwrap();
+
+        let content: Vec<u8> = (0..300u32).map(|i| (i % 251) as u8).collect();
+        api.write("/secreto", &content).unwrap();
+        api.write_at("/secreto", 120, b"medio").unwrap();
+        api.truncate("/secreto", 290).unwrap();
+        api.flush().unwrap();
+        let mut expected = content[..290].to_vec();
+        expected[120..125].copy_from_slice(b"medio");
+        assert_eq!(api.read("/secreto").unwrap(), expected);
+        assert!(api.stat("/secreto").unwrap().encrypted);
+        assert!(!api.stat("/claro.txt").unwrap().encrypted);
+
+        // en el disco solo queda el texto cifrado
+        let id = api.stat("/secreto").unwrap().inode_id;
+        let blocks = api.lock().inode(id).unwrap().blocks.clone();
+        let raw: Vec<Vec<u8>> = blocks.iter().map(|&b| storage.read_block(b).unwrap()).collect();
+        assert_ne!(&raw[0][..], &expected[..BLOCK_SIZE]);
+
+        // los qr y la clave del archivo alcanzan para leerlo
+        let key = api.file_key("/secreto").unwrap();
+        assert_eq!(key.decrypt_blocks(&raw).unwrap(), expected);
+        assert!(api.file_key("/claro.txt").is_err());
+        drop(api);
+
+        let api = QrfsApi::open(storage).unwrap();
+        assert_eq!(api.read("/claro.txt").unwrap(), b"a la vista");
+        assert!(matches!(api.read("/secreto"), Err(QrfsError::NotPermitted(_))));
+        assert!(api.unlock("otra").is_err());
+        api.unlock("frase").unwrap();
+        assert_eq!(api.read("/secreto").unwrap(), expected);
+    }
+
+    #[test]
+    fn a_partial_write_over_a_block_that_does_not_open_fails() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        format_storage(storage.as_ref(), &Superblock::new(200, 64)).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        api.lock().enable_encryption("frase", 10).unwrap();
+        api.write("/secreto", &[7u8; 300]).unwrap();
+        api.flush().unwrap();
+        let id = api.stat("/secreto").unwrap().inode_id;
+        let block = api.lock().inode(id).unwrap().blocks[0];
+        drop(api);
+
+        let mut raw = storage.read_block(block).unwrap();
+        raw[30] ^= 1;
+        storage.write_block(block, &raw).unwrap();
+
+        // reescribirlo con ceros alrededor de lo nuevo perderia el resto
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        api.unlock("frase").unwrap();
+        assert!(matches!(api.write_at("/secreto", 10, b"x"), Err(QrfsError::Corrupt(_))));
+        assert_eq!(storage.read_block(block).unwrap(), raw);
+    }
+
+    #[test]
+    fn rekey_moves_every_file_to_new_keys_and_resumes_after_a_cut() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        format_storage(storage.as_ref(), &Superblock::new(200, 64)).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        api.lock().enable_encryption("vieja", 10).unwrap();
+        let a: Vec<u8> = (0..400u32).map(|i| (i % 13) as u8).collect();
+        api.write("/a", &a).unwrap();
+        api.write("/b", b"segundo archivo").unwrap();
+        let key_a = api.file_key("/a").unwrap();
+        let free = api.usage().free_blocks;
+
+        api.lock().begin_rekey("vieja", "nueva", 10).unwrap();
+        let pending = api.lock().rekey_pending();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(api.rekey_inode(pending[0]).unwrap(), a.len().div_ceil(BLOCK_SIZE - BLOCK_OVERHEAD));
+        assert!(api.finish_rekey().is_err());
+        // corte: el disco queda con las dos ranuras y un archivo rotado
+        drop(api);
+
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        api.lock().begin_rekey("vieja", "nueva", 10).unwrap();
+        assert_eq!(api.lock().rekey_pending(), vec![pending[1]]);
+        api.rekey_inode(pending[1]).unwrap();
+        api.finish_rekey().unwrap();
+        assert_eq!(api.usage().free_blocks, free);
+        assert_ne!(api.file_key("/a").unwrap(), key_a);
+        drop(api);
+
+        let api = QrfsApi::open(storage).unwrap();
+        assert!(api.unlock("vieja").is_err());
+        api.unlock("nueva").unwrap();
+        assert_eq!(api.read("/a").unwrap(), a);
+        assert_eq!(api.read("/b").unwrap(), b"segundo archivo");
+
+        api.lock().change_passphrase("nueva", "otra", 10).unwrap();
+        assert_eq!(api.read("/a").unwrap(), a);
+        assert!(api.unlock("nueva").is_err());
+    }
+
+    #[test]
+    fn walk_lists_every_entry_with_its_blocks() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        format_storage(storage.as_ref(), &Superblock::new(200, 64)).unwrap();
+        let api = QrfsApi::open(storage).unwrap();
+        api.write("/b.bin", &[7u8; 300]).unwrap();
+        api.write("/a.txt", b"hola").unwrap();
+
+        let entries = api.walk().unwrap();
+        let paths: Vec<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["/", "/a.txt", "/b.bin"]);
+        assert!(entries[0].info.is_dir());
+        assert_eq!(entries[1].blocks.len(), 1);
+        assert_eq!(entries[2].blocks.len(), 300usize.div_ceil(BLOCK_SIZE));
+        assert_eq!(entries[2].info.size, 300);
+        assert_eq!(entries[2].stored, 3 * BLOCK_SIZE as u64);
+    }
+
+    #[test]
+    fn key_shards_open_the_disk_and_reset_a_forgotten_passphrase() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        format_storage(storage.as_ref(), &Superblock::new(200, 64)).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        api.lock().enable_encryption("olvidada", 10).unwrap();
+        api.write("/a", b"contenido cifrado").unwrap();
+        assert!(api.key_shards("otra", 2, 3).is_err());
+        let shards = api.key_shards("olvidada", 2, 3).unwrap();
+        drop(api);
+
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        assert!(api.unlock_with_shards(&shards[..1]).is_err());
+        api.unlock_with_shards(&[shards[2].clone(), shards[0].clone()]).unwrap();
+        assert_eq!(api.read("/a").unwrap(), b"contenido cifrado");
+        api.lock().recover_passphrase(&shards[1..], "nueva", 10).unwrap();
+        drop(api);
+
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        assert!(api.unlock("olvidada").is_err());
+        api.unlock("nueva").unwrap();
+        assert_eq!(api.read("/a").unwrap(), b"contenido cifrado");
+
+        // despues de un rekey los fragmentos viejos no abren nada
+        api.lock().begin_rekey("nueva", "rotada", 10).unwrap();
+        let pending = api.lock().rekey_pending();
+        for id in pending {
+            api.rekey_inode(id).unwrap();
+        }
+        api.finish_rekey().unwrap();
+        drop(api);
+        let api = QrfsApi::open(storage).unwrap();
+        assert!(matches!(api.unlock_with_shards(&shards), Err(QrfsError::NotPermitted(_))));
+    }
+
+    #[test]
+    fn a_relabel_keeps_the_uuid_and_the_label_fits_beside_a_rekey() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        format_storage(storage.as_ref(), &Superblock::new(200, 64)).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        assert!(api.label().is_none());
+        assert!(api.set_label("con,coma").is_err());
+        let first = api.set_label("Impuestos2023").unwrap();
+        let old_block = api.superblock().label_block;
+        let free = api.usage().free_blocks;
+
+        let second = api.set_label("Impuestos2024").unwrap();
+        assert_eq!(second.uuid, first.uuid);
+        assert_ne!(api.superblock().label_block, old_block);
+        // el bloque viejo se libera
+        assert_eq!(api.usage().free_blocks, free);
+        assert_eq!(second.display_name(), "QRFS: Impuestos2024");
+
+        // las dos ranuras de un rekey entran con el disco rotulado
+        api.lock().enable_encryption("vieja", 10).unwrap();
+        api.lock().begin_rekey("vieja", "nueva", 10).unwrap();
+        drop(api);
+        let api = QrfsApi::open(storage).unwrap();
+        assert_eq!(api.label().unwrap().label, "Impuestos2024");
+        assert!(api.lock().quick_check().is_empty());
+    }
+
+    #[test]
+    fn small_file_tails_share_a_block_until_they_change() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        let mut sb = Superblock::new(200, 64);
+        sb.tail_packing = true;
+        format_storage(storage.as_ref(), &sb).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        let b: Vec<u8> = (0..130u32).map(|i| i as u8).collect();
+        api.write("/a", &[1; 20]).unwrap();
+        api.write("/b", &b).unwrap();
+        api.write("/c", &[3; 40]).unwrap();
+        // una cola de 100 bytes no entra con otra
+        api.write("/d", &[4; 100]).unwrap();
+
+        let free = api.usage().free_blocks;
+        assert_eq!(api.pack_tails().unwrap(), 2);
+        assert_eq!(api.usage().free_blocks, free + 2);
+        assert_eq!(api.pack_tails().unwrap(), 0);
+        drop(api);
+
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        assert_eq!(api.read("/a").unwrap(), [1; 20]);
+        assert_eq!(api.read("/b").unwrap(), b);
+        assert_eq!(api.read_at("/b", 125, 10).unwrap(), &b[125..]);
+        assert_eq!(api.read("/d").unwrap(), [4; 100]);
+        assert_ne!(api.stat("/a").unwrap().flags & INODE_FLAG_TAIL, 0);
+        // el tamaño sigue siendo el logico; lo guardado es solo la cola
+        let entries = api.walk().unwrap();
+        let a = entries.iter().find(|e| e.path == "/a").unwrap();
+        assert_eq!((a.info.size, a.stored), (20, 20 + tail::ENTRY_LEN as u64));
+        let id = a.info.inode_id;
+        assert_eq!(api.lock().stored_xattr(id, XATTR_STORED).unwrap(), "28");
+        assert_eq!(api.lock().stored_xattr(id, XATTR_RATIO).unwrap(), "4.57");
+        assert_eq!(api.stat("/d").unwrap().flags & INODE_FLAG_TAIL, 0);
+        assert!(api.lock().quick_check().is_empty());
+
+        // escribir, truncar o borrar saca la cola del bloque compartido
+        api.write_at("/b", 130, b"mas").unwrap();
+        api.truncate("/c", 50).unwrap();
+        api.set_flags("/a", INODE_FLAG_IMMUTABLE).unwrap();
+        assert_eq!(api.stat("/a").unwrap().flags, INODE_FLAG_IMMUTABLE | INODE_FLAG_TAIL);
+        assert_eq!(&api.read("/b").unwrap()[128..], &[128, 129, b'm', b'a', b's']);
+        let mut c = vec![3; 40];
+        c.resize(50, 0);
+        assert_eq!(api.read("/c").unwrap(), c);
+        assert_eq!(api.read("/a").unwrap(), [1; 20]);
+        api.set_flags("/a", 0).unwrap();
+        api.remove("/a").unwrap();
+        assert!(api.lock().reclaim_orphan_blocks().unwrap().is_empty());
+
+        // al soltar la api se vuelven a empaquetar
+        drop(api);
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        assert_ne!(api.stat("/c").unwrap().flags & INODE_FLAG_TAIL, 0);
+        assert_eq!(api.read("/c").unwrap(), c);
+        assert_eq!(api.read("/b").unwrap().len(), 133);
+
+        // sin el flag en el superblock no se empaqueta
+        assert!(new_api().pack_tails().is_err());
+    }
+
+    #[test]
+    fn relocated_blocks_keep_the_data_and_free_the_old_one() {
+        let api = new_api();
+        let data: Vec<u8> = (0..300u32).map(|i| (i % 251) as u8).collect();
+        api.write("/a.bin", &data).unwrap();
+        let id = api.stat("/a.bin").unwrap().inode_id;
+        let blocks = api.lock().inode(id).unwrap().blocks.clone();
+        let free = api.usage().free_blocks;
+
+        api.relocate_block(id, 1, 150).unwrap();
+        assert_eq!(api.lock().inode(id).unwrap().blocks, [blocks[0], 150, blocks[2]]);
+        assert_eq!(api.read("/a.bin").unwrap(), data);
+        assert_eq!(api.usage().free_blocks, free);
+        assert!(api.lock().quick_check().is_empty());
+
+        // ocupado, metadata, fuera del disco o sin dueño
+        assert!(api.relocate_block(id, 0, blocks[2]).is_err());
+        assert!(api.relocate_block(id, 0, 0).is_err());
+        assert!(api.relocate_block(id, 0, 500).is_err());
+        assert!(api.relocate_block(id, 3, 151).is_err());
+        assert!(matches!(api.evacuate_block(0, None), Err(QrfsError::NotPermitted(_))));
+        assert!(matches!(api.evacuate_block(blocks[1], None), Err(QrfsError::NotFound(_))));
+
+        // al primer libre: el que acaba de quedar
+        assert_eq!(api.evacuate_block(150, None).unwrap(), blocks[1]);
+        assert_eq!(api.read("/a.bin").unwrap(), data);
+
+        // una cola empaquetada se mueve con las que comparten su bloque
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        let mut sb = Superblock::new(200, 64);
+        sb.tail_packing = true;
+        format_storage(storage.as_ref(), &sb).unwrap();
+        let api = QrfsApi::open(storage).unwrap();
+        api.write("/x", b"cola x").unwrap();
+        api.write("/y", b"cola y").unwrap();
+        api.pack_tails().unwrap();
+        let x = api.stat("/x").unwrap().inode_id;
+        let shared = api.lock().inode(x).unwrap().blocks[0];
+        let moved = api.evacuate_block(shared, Some(190)).unwrap();
+        assert_eq!(moved, 190);
+        assert_eq!(api.read("/x").unwrap(), b"cola x");
+        assert_eq!(api.read("/y").unwrap(), b"cola y");
+        assert!(api.lock().quick_check().is_empty());
+    }
+
+    #[test]
+    fn pinned_files_live_in_the_pinned_area_and_stay_there() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        let mut sb = Superblock::new(200, 64);
+        sb.pinned_blocks = 8;
+        format_storage(storage.as_ref(), &sb).unwrap();
+        let area = sb.data_block_start..sb.data_block_start + 8;
+        let api = QrfsApi::open(storage.clone()).unwrap();
+
+        // los datos comunes no caen en la zona; la raiz si
+        let data: Vec<u8> = (0..300u32).map(|i| (i % 251) as u8).collect();
+        api.write("/hot.bin", &data).unwrap();
+        api.write("/cold.bin", &data).unwrap();
+        let hot = api.stat("/hot.bin").unwrap().inode_id;
+        let blocks = |id: u32| api.lock().inode(id).unwrap().blocks.clone();
+        assert!(blocks(hot).iter().all(|b| !area.contains(b)));
+        assert!(blocks(sb.root_inode).iter().all(|b| area.contains(b)));
+
+        assert_eq!(api.set_pinned("/hot.bin", true).unwrap(), (3, 0));
+        assert!(blocks(hot).iter().all(|b| area.contains(b)));
+        assert_eq!(api.read("/hot.bin").unwrap(), data);
+        assert!(api.lock().quick_check().is_empty());
+
+        // no sale de la zona: ni relocate ni evacuate, y lo que crece va ahi
+        let outside = sb.data_block_start + 100;
+        assert!(matches!(api.relocate_block(hot, 0, outside), Err(QrfsError::NotPermitted(_))));
+        let first = blocks(hot)[0];
+        assert!(area.contains(&api.evacuate_block(first, None).unwrap()));
+        api.write_at("/hot.bin", 300, &[7; 100]).unwrap();
+        api.flush().unwrap();
+        assert!(blocks(hot).iter().all(|b| area.contains(b)));
+
+        // chattr no lo pisa y queda guardado en el disco
+        api.set_flags("/hot.bin", INODE_FLAG_APPEND).unwrap();
+        drop(api);
+        let api = QrfsApi::open(storage).unwrap();
+        assert_eq!(api.stat("/hot.bin").unwrap().flags, INODE_FLAG_APPEND | INODE_FLAG_PINNED);
+        let usage = api.usage();
+        assert_eq!(usage.pinned_blocks, 8);
+        assert!(usage.free_pinned_blocks < 8);
+
+        api.set_flags("/hot.bin", 0).unwrap();
+        assert_eq!(api.set_pinned("/hot.bin", false).unwrap(), (0, 0));
+        assert_eq!(api.stat("/hot.bin").unwrap().flags, 0);
+        api.relocate_block(hot, 0, outside).unwrap();
+
+        // un disco sin zona fija no fija nada
+        let api = new_api();
+        api.write("/x", b"x").unwrap();
+        assert!(matches!(api.set_pinned("/x", true), Err(QrfsError::Unimplemented(_))));
+    }
+
+    #[test]
+    fn relocate_cut_at_any_write_keeps_the_file() {
+        use std::sync::atomic::Ordering;
+
+        let data: Vec<u8> = (0..300u32).map(|i| (i % 251) as u8).collect();
+        let before = || {
+            let inner = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+            format_storage(inner.as_ref(), &Superblock::new(200, 16)).unwrap();
+            QrfsApi::open(inner.clone()).unwrap().write("/a.bin", &data).unwrap();
+            inner
+        };
+        let relocate = |inner: &Arc<InMemoryBlockStorage>, budget: usize| {
+            let crashing = Arc::new(CrashingStorage {
+                inner: inner.clone(),
+                writes_left: budget.into(),
+                broken_from: u32::MAX,
+            });
+            let api = QrfsApi::open(crashing.clone()).unwrap();
+            let id = api.stat("/a.bin").unwrap().inode_id;
+            let moved = api.relocate_block(id, 1, 150).is_ok();
+            (moved, budget - crashing.writes_left.load(Ordering::SeqCst))
+        };
+
+        let (moved, writes) = relocate(&before(), usize::MAX);
+        assert!(moved);
+        for budget in 0..writes {
+            let inner = before();
+            relocate(&inner, budget);
+            let mut fs = match QrfsFilesystem::new(inner) {
+                Ok(fs) => fs,
+                Err(QrfsError::Corrupt(_)) => continue,
+                Err(e) => panic!("corte {}: {}", budget, e),
+            };
+            assert!(fs.quick_check().is_empty(), "corte {}: {:?}", budget, fs.quick_check());
+            fs.reclaim_orphan_blocks().unwrap();
+            assert_eq!(QrfsApi::new(fs).read("/a.bin").unwrap(), data, "corte {}", budget);
+        }
+    }
+
+    #[test]
+    fn corrupt_inode_table_refuses_to_mount() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        let sb = Superblock::new(200, 64);
+        format_storage(storage.as_ref(), &sb).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        api.write("/a.txt", b"hola").unwrap();
+        drop(api);
+
+        let mut block = storage.read_block(sb.inode_table_start).unwrap();
+        block[40] ^= 0x80;
+        storage.write_block(sb.inode_table_start, &block).unwrap();
+        assert!(matches!(
+            QrfsFilesystem::new(storage).err(),
+            Some(QrfsError::Corrupt(_))
+        ));
+    }
+
+    // almacenamiento que deja de escribir despues de cierta cantidad de bloques
+    // (un corte de luz a mitad de un commit) y que no puede escribir los
+    // bloques desde broken_from en adelante (un sector roto)
+    struct CrashingStorage {
+        inner: Arc<InMemoryBlockStorage>,
+        writes_left: std::sync::atomic::AtomicUsize,
+        broken_from: u32,
+    }
+
+    impl BlockStorage for CrashingStorage {
+        fn block_size(&self) -> usize {
+            self.inner.block_size()
+        }
+        fn total_blocks(&self) -> u32 {
+            self.inner.total_blocks()
+        }
+        fn read_block(&self, id: u32) -> Result<Vec<u8>, QrfsError> {
+            self.inner.read_block(id)
+        }
+        fn write_block(&self, id: u32, data: &[u8]) -> Result<(), QrfsError> {
+            use std::sync::atomic::Ordering;
+            if id >= self.broken_from {
+                return Err(QrfsError::Other(format!("bloque {} roto", id)));
+            }
+            self.writes_left
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .map_err(|_| QrfsError::Other("corte".into()))?;
+            self.inner.write_block(id, data)
+        }
+    }
+
+    #[test]
+    fn create_cut_at_any_write_leaves_no_half_file() {
+        use std::sync::atomic::Ordering;
+
+        let before_create = || {
+            let inner = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+            format_storage(inner.as_ref(), &Superblock::new(200, 16)).unwrap();
+            let api = QrfsApi::open(inner.clone()).unwrap();
+            api.write("/a.txt", b"hola").unwrap();
+            let usage = api.usage();
+            (inner, usage)
+        };
+        let create = |inner: &Arc<InMemoryBlockStorage>, budget: usize| {
+            let crashing = Arc::new(CrashingStorage {
+                inner: inner.clone(),
+                writes_left: budget.into(),
+                broken_from: u32::MAX,
+            });
+            let created = QrfsApi::open(crashing.clone()).unwrap().create("/b.txt", 0o644).is_ok();
+            (created, budget - crashing.writes_left.load(Ordering::SeqCst))
+        };
+
+        let (inner, _) = before_create();
+        let (created, writes) = create(&inner, usize::MAX);
+        assert!(created);
+
+        let (mut torn, mut orphaned, mut complete) = (0, 0, 0);
+        for budget in 0..writes {
+            let (inner, before) = before_create();
+            let (created, _) = create(&inner, budget);
+            assert!(!created);
+
+            // un corte dentro de la tabla de inodos se detecta y no se monta
+            let mut fs = match QrfsFilesystem::new(inner) {
+                Ok(fs) => fs,
+                Err(QrfsError::Corrupt(_)) => {
+                    torn += 1;
+                    continue;
+                }
+                Err(e) => panic!("corte {}: {}", budget, e),
+            };
+            assert!(fs.quick_check().is_empty(), "corte {}: {:?}", budget, fs.quick_check());
+            orphaned += fs.reclaim_orphan_inodes().unwrap().len();
+
+            // nunca queda un archivo a medias: o esta entero (el directorio llego
+            // y solo falto la tabla final) o no esta y su inodo se recupera
+            let api = QrfsApi::new(fs);
+            let names: Vec<String> = api.list("/").unwrap().into_iter().map(|f| f.name).collect();
+            if names.len() == 2 {
+                assert_eq!(names, vec!["a.txt", "b.txt"], "corte {}", budget);
+                assert_eq!(api.read("/b.txt").unwrap(), b"", "corte {}", budget);
+                complete += 1;
+            } else {
+                assert_eq!(names, vec!["a.txt"], "corte {}", budget);
+                assert_eq!(api.usage().free_inodes, before.free_inodes, "corte {}", budget);
+            }
+            assert_eq!(api.read("/a.txt").unwrap(), b"hola");
+        }
+        assert!(torn > 0 && orphaned > 0 && complete > 0, "{} {} {}", torn, orphaned, complete);
+    }
+
+    #[test]
+    fn failed_directory_growth_rolls_back_disk_state() {
+        let inner = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        let sb = Superblock::new(200, 64);
+        format_storage(inner.as_ref(), &sb).unwrap();
+        // el primer bloque del directorio se puede escribir, el que sigue no
+        let storage = Arc::new(CrashingStorage {
+            inner: inner.clone(),
+            writes_left: usize::MAX.into(),
+            broken_from: sb.data_block_start + 1,
+        });
+        let api = QrfsApi::open(storage).unwrap();
+
+        let mut created = Vec::new();
+        for i in 0..32 {
+            let name = format!("archivo_{:02}", i);
+            let bitmap = inner.read_block(sb.free_map_start).unwrap();
+            let usage = api.usage();
+            if api.create(&format!("/{}", name), 0o644).is_ok() {
+                created.push(name);
+                continue;
+            }
+
+            // ni el bitmap ni la memoria se quedan con el bloque que no se pudo escribir
+            assert_eq!(inner.read_block(sb.free_map_start).unwrap(), bitmap);
+            assert_eq!(api.usage().free_blocks, usage.free_blocks);
+            assert_eq!(api.usage().free_inodes, usage.free_inodes);
+            break;
+        }
+        assert!(!created.is_empty() && created.len() < 32);
+        drop(api);
+
+        let mut fs = QrfsFilesystem::new(inner).unwrap();
+        assert!(fs.quick_check().is_empty(), "{:?}", fs.quick_check());
+        assert!(fs.reclaim_orphan_inodes().unwrap().is_empty());
+        assert!(fs.reclaim_orphan_blocks().unwrap().is_empty());
+        let names: Vec<String> = QrfsApi::new(fs).list("/").unwrap().into_iter().map(|f| f.name).collect();
+        assert_eq!(names, created);
+    }
+
+    #[test]
+    fn rename_over_existing_is_atomic_across_cuts() {
+        let before_rename = || {
+            let inner = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+            format_storage(inner.as_ref(), &Superblock::new(200, 16)).unwrap();
+            let api = QrfsApi::open(inner.clone()).unwrap();
+            api.write("/a.txt", b"uno").unwrap();
+            api.write("/b.txt", b"dos").unwrap();
+            inner
+        };
+        let rename = |inner: &Arc<InMemoryBlockStorage>, budget: usize| {
+            use std::sync::atomic::Ordering;
+            let crashing = Arc::new(CrashingStorage {
+                inner: inner.clone(),
+                writes_left: budget.into(),
+                broken_from: u32::MAX,
+            });
+            let renamed = QrfsApi::open(crashing.clone()).unwrap().rename("/a.txt", "/b.txt").is_ok();
+            (renamed, budget - crashing.writes_left.load(Ordering::SeqCst))
+        };
+
+        let inner = before_rename();
+        let (renamed, writes) = rename(&inner, usize::MAX);
+        assert!(renamed);
+        let api = QrfsApi::open(inner).unwrap();
+        assert_eq!(api.read("/b.txt").unwrap(), b"uno");
+        assert_eq!(api.list("/").unwrap().len(), 1);
+
+        for budget in 0..writes {
+            let inner = before_rename();
+            // un corte en el ultimo bitmap solo deja bloques huerfanos
+            rename(&inner, budget);
+
+            let mut fs = match QrfsFilesystem::new(inner) {
+                Ok(fs) => fs,
+                Err(QrfsError::Corrupt(_)) => continue,
+                Err(e) => panic!("corte {}: {}", budget, e),
+            };
+            assert!(fs.quick_check().is_empty(), "corte {}: {:?}", budget, fs.quick_check());
+            fs.reclaim_orphan_inodes().unwrap();
+            fs.reclaim_orphan_blocks().unwrap();
+
+            // b.txt existe siempre: con el contenido viejo o con el de a.txt
+            let api = QrfsApi::new(fs);
+            let names: Vec<String> = api.list("/").unwrap().into_iter().map(|f| f.name).collect();
+            match api.read("/b.txt").unwrap().as_slice() {
+                b"dos" => {
+                    assert_eq!(names, vec!["a.txt", "b.txt"], "corte {}", budget);
+                    assert_eq!(api.read("/a.txt").unwrap(), b"uno");
+                }
+                b"uno" => assert_eq!(names, vec!["b.txt"], "corte {}", budget),
+                other => panic!("corte {}: {:?}", budget, other),
+            }
+            assert_eq!(
+                api.usage().free_inodes,
+                api.superblock().inode_count as u64 - 1 - names.len() as u64,
+                "corte {}",
+                budget
+            );
+        }
+    }
+}
@@ -0,0 +1,272 @@
+// canal de audio (feature "audio"): los bloques chicos y criticos (superblock,
+// bitmap) como sonido, para cuando no hay camara. es otra simbologia para el
+// mismo texto que viaja en un qr (codec.rs): el texto va modulado en fsk tipo
+// bell 202 (1200 baudios, marca 1200 hz, espacio 2200 hz), un byte por trama
+// uart (bit de arranque, 8 bits del menos significativo al mas, bit de
+// parada). un .wav puede llevar varios bloques seguidos; se reproduce con
+// cualquier parlante (aplay, afplay) y se graba con cualquier microfono
+//
+// cada bloque: MAGIC, largo (u16 le), texto, crc del texto (u32 le), con un
+// tono de marca antes y despues para que el receptor se acomode
+
+use std::fs;
+use std::path::Path;
+
+use crate::errors::QrfsError;
+use crate::qr::block_checksum;
+
+pub const SAMPLE_RATE: u32 = 48_000;
+pub const BAUD: u32 = 1200;
+const MARK_HZ: f32 = 1200.0;
+const SPACE_HZ: f32 = 2200.0;
+const AMPLITUDE: f32 = 12_000.0;
+// tono de marca antes de cada bloque, en bits
+const PREAMBLE_BITS: usize = 240;
+const MAGIC: &[u8; 4] = b"QRFA";
+
+// modula los textos uno tras otro; muestras pcm de 16 bits a SAMPLE_RATE
+pub fn encode_texts(texts: &[String]) -> Result<Vec<i16>, QrfsError> {
+    let mut bits: Vec<bool> = Vec::new();
+    for text in texts {
+        let len = u16::try_from(text.len())
+            .map_err(|_| QrfsError::NoSpace(format!("texto de {} bytes, el audio lleva hasta 65535", text.len())))?;
+        let mut frame = MAGIC.to_vec();
+        frame.extend_from_slice(&len.to_le_bytes());
+        frame.extend_from_slice(text.as_bytes());
+        frame.extend_from_slice(&block_checksum(text.as_bytes()).to_le_bytes());
+
+        bits.extend(std::iter::repeat_n(true, PREAMBLE_BITS));
+        for byte in frame {
+            bits.push(false);
+            bits.extend((0..8).map(|i| byte >> i & 1 == 1));
+            bits.push(true);
+        }
+    }
+    bits.extend(std::iter::repeat_n(true, PREAMBLE_BITS));
+
+    // fase continua entre bits: sin saltos no hay chasquidos
+    let per_bit = (SAMPLE_RATE / BAUD) as usize;
+    let mut phase = 0f32;
+    let mut samples = Vec::with_capacity(bits.len() * per_bit);
+    for bit in bits {
+        let step = std::f32::consts::TAU * if bit { MARK_HZ } else { SPACE_HZ } / SAMPLE_RATE as f32;
+        for _ in 0..per_bit {
+            samples.push((phase.sin() * AMPLITUDE) as i16);
+            phase = (phase + step) % std::f32::consts::TAU;
+        }
+    }
+    Ok(samples)
+}
+
+// los textos de todos los bloques que se entienden en la grabacion; un bloque
+// con el crc mal se descarta
+pub fn decode_texts(samples: &[i16], sample_rate: u32) -> Vec<String> {
+    let per_bit = (sample_rate as f32 / BAUD as f32).round() as usize;
+    if per_bit < 4 || samples.len() < per_bit {
+        return Vec::new();
+    }
+    let soft = soft_bits(samples, sample_rate, per_bit);
+    let bytes = uart_bytes(&soft, per_bit);
+
+    let mut texts = Vec::new();
+    let mut i = 0;
+    while i + MAGIC.len() + 2 <= bytes.len() {
+        if &bytes[i..i + MAGIC.len()] != MAGIC {
+            i += 1;
+            continue;
+        }
+        let len = u16::from_le_bytes([bytes[i + 4], bytes[i + 5]]) as usize;
+        let start = i + MAGIC.len() + 2;
+        let Some(frame) = bytes.get(start..start + len + 4) else {
+            break;
+        };
+        let (text, crc) = frame.split_at(len);
+        if crc == block_checksum(text).to_le_bytes() {
+            if let Ok(text) = String::from_utf8(text.to_vec()) {
+                texts.push(text);
+                i = start + len + 4;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    texts
+}
+
+// para cada posicion, energia de marca menos energia de espacio en la ventana
+// de un bit que empieza ahi (dft deslizante de las dos frecuencias)
+fn soft_bits(samples: &[i16], sample_rate: u32, window: usize) -> Vec<f32> {
+    // sumas acumuladas en f64: en f32 la resta de dos sumas grandes se come
+    // la ventana
+    let tone = |hz: f64| -> Vec<f64> {
+        let w = std::f64::consts::TAU * hz / sample_rate as f64;
+        let mut sums = vec![(0f64, 0f64); samples.len() + 1];
+        for (n, &sample) in samples.iter().enumerate() {
+            let (re, im) = sums[n];
+            let (sin, cos) = (w * n as f64).sin_cos();
+            let x = sample as f64;
+            sums[n + 1] = (re + x * cos, im - x * sin);
+        }
+        (0..=samples.len() - window)
+            .map(|n| {
+                let re = sums[n + window].0 - sums[n].0;
+                let im = sums[n + window].1 - sums[n].1;
+                re * re + im * im
+            })
+            .collect()
+    };
+    let mark = tone(MARK_HZ as f64);
+    let space = tone(SPACE_HZ as f64);
+    mark.iter().zip(&space).map(|(m, s)| (m - s) as f32).collect()
+}
+
+// bytes uart: se espera el flanco del bit de arranque y se lee cada bit con la
+// ventana entera dentro de el
+fn uart_bytes(soft: &[f32], per_bit: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut i = 0;
+    while i < soft.len() {
+        if soft[i] >= 0.0 {
+            i += 1;
+            continue;
+        }
+        // la ventana cruza el cero a mitad de camino del flanco
+        let start = i + per_bit / 2;
+        let bit = |k: usize| soft.get(start + k * per_bit).map(|&v| v > 0.0);
+        let (Some(false), Some(true)) = (bit(0), bit(9)) else {
+            i += per_bit / 2;
+            continue;
+        };
+        let mut byte = 0u8;
+        for k in 0..8 {
+            if bit(1 + k) == Some(true) {
+                byte |= 1 << k;
+            }
+        }
+        bytes.push(byte);
+        i = start + 9 * per_bit;
+    }
+    bytes
+}
+
+// wav pcm de 16 bits, mono
+pub fn write_wav(path: impl AsRef<Path>, samples: &[i16]) -> Result<(), QrfsError> {
+    let data_len = (samples.len() * 2) as u32;
+    let mut out = Vec::with_capacity(44 + data_len as usize);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    out.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        out.extend_from_slice(&sample.to_le_bytes());
+    }
+    fs::write(path, out)?;
+    Ok(())
+}
+
+// lee un wav pcm de 16 bits (una grabacion en estereo se mezcla a mono);
+// devuelve las muestras y la frecuencia de muestreo
+pub fn read_wav(path: impl AsRef<Path>) -> Result<(Vec<i16>, u32), QrfsError> {
+    let path = path.as_ref();
+    let raw = fs::read(path)?;
+    let invalid = |why: &str| QrfsError::Other(format!("{}: {}", path.display(), why));
+    if raw.len() < 12 || &raw[0..4] != b"RIFF" || &raw[8..12] != b"WAVE" {
+        return Err(invalid("no es un wav"));
+    }
+
+    let mut format: Option<(u16, u16, u32, u16)> = None;
+    let mut pos = 12;
+    while pos + 8 <= raw.len() {
+        let id = &raw[pos..pos + 4];
+        let len = u32::from_le_bytes(raw[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let body = &raw[pos + 8..(pos + 8 + len).min(raw.len())];
+        match id {
+            b"fmt " if body.len() >= 16 => {
+                format = Some((
+                    u16::from_le_bytes([body[0], body[1]]),
+                    u16::from_le_bytes([body[2], body[3]]),
+                    u32::from_le_bytes(body[4..8].try_into().unwrap()),
+                    u16::from_le_bytes([body[14], body[15]]),
+                ));
+            }
+            b"data" => {
+                let (kind, channels, rate, bits) = format.ok_or_else(|| invalid("data antes de fmt"))?;
+                // 0xfffe: extensible, usado por algunos grabadores para pcm comun
+                if !(kind == 1 || kind == 0xfffe) || bits != 16 || channels == 0 {
+                    return Err(invalid("solo pcm de 16 bits"));
+                }
+                let samples = body
+                    .chunks_exact(2 * channels as usize)
+                    .map(|frame| {
+                        let sum: i32 = frame
+                            .chunks_exact(2)
+                            .map(|s| i16::from_le_bytes([s[0], s[1]]) as i32)
+                            .sum();
+                        (sum / channels as i32) as i16
+                    })
+                    .collect();
+                return Ok((samples, rate));
+            }
+            _ => {}
+        }
+        // los chunks de largo impar llevan un byte de relleno
+        pos += 8 + len + (len & 1);
+    }
+    Err(invalid("sin datos de audio"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{BinaryCodec, BlockCodec};
+    use crate::qr::DecodedBlock;
+
+    #[test]
+    fn blocks_survive_the_trip_through_a_wav() {
+        let codec = BinaryCodec;
+        let texts = vec![codec.encode(0, 3, &[0x51; 128]), codec.encode(1, 1, &[0xff, 0x0f, 0])];
+        let dir = std::env::temp_dir().join(format!("qrfs_audio_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let wav = dir.join("metadata.wav");
+        write_wav(&wav, &encode_texts(&texts).unwrap()).unwrap();
+
+        let (samples, rate) = read_wav(&wav).unwrap();
+        assert_eq!(rate, SAMPLE_RATE);
+        let decoded = decode_texts(&samples, rate);
+        assert_eq!(decoded, texts);
+        let block = DecodedBlock::from_text(&decoded[1]).unwrap();
+        assert_eq!((block.block_id, block.generation, block.data), (Some(1), 1, vec![0xff, 0x0f, 0]));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn noisy_and_quiet_recordings_still_decode() {
+        let texts = vec!["QRFS2:AAAA".to_string()];
+        // mas bajo, con ruido y silencio antes, como un microfono
+        let mut seed = 7u32;
+        let mut recording: Vec<i16> = vec![0; 5000];
+        recording.extend(encode_texts(&texts).unwrap().iter().map(|&s| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (s / 4).saturating_add((seed >> 16) as i16 % 1500)
+        }));
+        assert_eq!(decode_texts(&recording, SAMPLE_RATE), texts);
+
+        // un 1 del texto pasado a 0 (el tono del bit de arranque): el crc lo
+        // descarta
+        let mut broken = encode_texts(&texts).unwrap();
+        let per_bit = (SAMPLE_RATE / BAUD) as usize;
+        let start_bit = (PREAMBLE_BITS + 10 * 8) * per_bit;
+        let flip = start_bit + 3 * per_bit;
+        broken.copy_within(start_bit..start_bit + per_bit, flip);
+        assert!(decode_texts(&broken, SAMPLE_RATE).is_empty());
+    }
+}
@@ -0,0 +1,197 @@
+// registro de auditoria de cambios al filesystem
+// una linea json por operacion (create, write, truncate, unlink, rename) en un
+// archivo que solo crece, fuera de los qr. cada entrada lleva el hash de la
+// anterior, asi borrar o editar una linea rompe la cadena y verify lo detecta
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+
+use crate::errors::QrfsError;
+
+// nombre del archivo dentro de la carpeta de auditoria
+pub const AUDIT_FILE: &str = "qrfs_audit.log";
+
+// hash "anterior" de la primera entrada
+const GENESIS: &str = "0000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub time: u64,
+    pub uid: u32,
+    pub op: String,
+    pub inode: u32,
+    pub name: String,
+    // datos extra de la operacion (rango escrito, nombre nuevo...)
+    #[serde(default)]
+    pub detail: String,
+    pub prev: String,
+    #[serde(default)]
+    pub hash: String,
+}
+
+impl AuditEntry {
+    // hash de la entrada sin su propio campo hash
+    fn compute_hash(&self) -> String {
+        let mut unhashed = self.clone();
+        unhashed.hash = String::new();
+        // serializar un struct sin mapas no puede fallar
+        let json = serde_json::to_string(&unhashed).unwrap();
+        Sha1::digest(json.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+pub struct AuditLog {
+    path: PathBuf,
+    file: File,
+    next_seq: u64,
+    last_hash: String,
+}
+
+impl AuditLog {
+    // abre (o crea) el registro de la carpeta y sigue la cadena donde quedo
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, QrfsError> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let path = dir.join(AUDIT_FILE);
+
+        let (next_seq, last_hash) = match read_entries(&path) {
+            Ok(entries) => match entries.last() {
+                Some(last) => (last.seq + 1, last.hash.clone()),
+                None => (0, GENESIS.to_string()),
+            },
+            Err(QrfsError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                (0, GENESIS.to_string())
+            }
+            Err(e) => return Err(e),
+        };
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            file,
+            next_seq,
+            last_hash,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // agrega una entrada al final y la baja a disco antes de volver
+    pub fn append(
+        &mut self,
+        uid: u32,
+        op: &str,
+        inode: u32,
+        name: &str,
+        detail: &str,
+    ) -> Result<(), QrfsError> {
+        let mut entry = AuditEntry {
+            seq: self.next_seq,
+            time: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            uid,
+            op: op.to_string(),
+            inode,
+            name: name.to_string(),
+            detail: detail.to_string(),
+            prev: self.last_hash.clone(),
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+
+        let mut line = serde_json::to_string(&entry).unwrap();
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        self.file.sync_data()?;
+
+        self.next_seq += 1;
+        self.last_hash = entry.hash;
+        Ok(())
+    }
+}
+
+// todas las entradas del archivo, en orden
+pub fn read_entries(path: &Path) -> Result<Vec<AuditEntry>, QrfsError> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+    for (n, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(&line)
+            .map_err(|e| QrfsError::Other(format!("linea {} ilegible: {}", n + 1, e)))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+// recorre la cadena de hashes; devuelve cuantas entradas hay si esta sana
+pub fn verify_chain(path: &Path) -> Result<usize, QrfsError> {
+    let entries = read_entries(path)?;
+    let mut prev = GENESIS.to_string();
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.seq != i as u64 {
+            return Err(QrfsError::Other(format!(
+                "entrada {}: numero de secuencia {} (falta o sobra una entrada)",
+                i, entry.seq
+            )));
+        }
+        if entry.prev != prev {
+            return Err(QrfsError::Other(format!(
+                "entrada {}: no sigue a la anterior",
+                i
+            )));
+        }
+        if entry.hash != entry.compute_hash() {
+            return Err(QrfsError::Other(format!("entrada {}: hash invalido (fue editada)", i)));
+        }
+        prev = entry.hash.clone();
+    }
+    Ok(entries.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chain_survives_reopen_and_detects_edits() {
+        let dir = std::env::temp_dir().join(format!("qrfs_audit_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        {
+            let mut log = AuditLog::open(&dir).unwrap();
+            log.append(1000, "create", 1, "a.txt", "").unwrap();
+            log.append(1000, "write", 1, "a.txt", "0+4").unwrap();
+        }
+        let mut log = AuditLog::open(&dir).unwrap();
+        log.append(0, "unlink", 1, "a.txt", "").unwrap();
+        let path = log.path().to_path_buf();
+        assert_eq!(verify_chain(&path).unwrap(), 3);
+
+        // cambiar el uid de una entrada rompe su hash
+        let text = fs::read_to_string(&path).unwrap();
+        fs::write(&path, text.replacen("\"uid\":1000", "\"uid\":0", 1)).unwrap();
+        assert!(verify_chain(&path).is_err());
+
+        // borrar una linea rompe la secuencia
+        let lines: Vec<&str> = text.lines().collect();
+        fs::write(&path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        assert!(verify_chain(&path).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
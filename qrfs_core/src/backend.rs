@@ -0,0 +1,210 @@
+// capa de montaje
+// QrfsFilesystem no sabe como se monta: cada plataforma tiene un backend que
+// traduce las llamadas del sistema operativo a las operaciones del filesystem.
+// en linux/macos es fuse (el impl Filesystem de fs.rs); en windows todavia no
+// hay. mkfs, fsck y extract no pasan por aca y funcionan en cualquier lado
+
+use std::path::Path;
+
+use crate::errors::QrfsError;
+#[cfg(unix)]
+use crate::events::{FsEvent, Notice};
+use crate::fs::QrfsFilesystem;
+use crate::storage::BlockStorage;
+
+pub trait MountBackend {
+    // montaje en segundo plano; al soltarlo se desmonta
+    type Session;
+
+    fn name(&self) -> &'static str;
+
+    // monta y bloquea hasta que se desmonte
+    fn mount<B: BlockStorage + 'static>(
+        &self,
+        fs: QrfsFilesystem<B>,
+        mountpoint: &Path,
+    ) -> Result<(), QrfsError>;
+
+    // monta en un hilo aparte y devuelve enseguida
+    fn spawn_mount<B: BlockStorage + 'static>(
+        &self,
+        fs: QrfsFilesystem<B>,
+        mountpoint: &Path,
+    ) -> Result<Self::Session, QrfsError>;
+}
+
+#[cfg(unix)]
+pub struct FuseBackend;
+
+#[cfg(unix)]
+impl FuseBackend {
+    fn options<B: BlockStorage + 'static>(fs: &QrfsFilesystem<B>) -> Vec<fuser::MountOption> {
+        use fuser::MountOption;
+        // un disco rotulado se ve como "QRFS: rotulo" en /proc/mounts y en
+        // los administradores de archivos (el tipo queda fuse.qrfs)
+        let label = fs.label();
+        let fsname = label.as_ref().map_or_else(|| "qrfs".to_string(), |label| label.display_name());
+        #[allow(unused_mut)]
+        let mut options = vec![
+            if fs.is_read_only() { MountOption::RO } else { MountOption::RW },
+            MountOption::FSName(fsname),
+            MountOption::Subtype("qrfs".to_string()),
+        ];
+        // macfuse: nombre del volumen en finder, y que finder no llene el
+        // disco de archivos ._* y xattrs de apple (cada uno serian varios qr)
+        #[cfg(target_os = "macos")]
+        options.extend([
+            MountOption::CUSTOM(format!(
+                "volname={}",
+                label.as_ref().map_or("qrfs", |label| label.label.as_str())
+            )),
+            MountOption::CUSTOM("noappledouble".to_string()),
+            MountOption::CUSTOM("noapplexattr".to_string()),
+        ]);
+        options
+    }
+
+    // lo necesario para pasar al kernel los avisos del bus del filesystem
+    // (None si no tiene): el bus, el id con el que publica y su raiz
+    fn event_source<B: BlockStorage + 'static>(fs: &QrfsFilesystem<B>) -> Option<EventSource> {
+        fs.events().map(|link| EventSource {
+            notices: link.bus.subscribe(),
+            origin: link.origin,
+            root_inode: fs.superblock().root_inode,
+        })
+    }
+}
+
+#[cfg(unix)]
+struct EventSource {
+    notices: std::sync::mpsc::Receiver<Notice>,
+    origin: u64,
+    root_inode: u32,
+}
+
+// pasa al kernel, desde un hilo aparte, lo que cambian los demas en el bus
+// (ver events.rs): entradas e inodos se sacan de su cache y el contenido que
+// viene en el aviso va directo a la cache de paginas. lo que hizo el mismo
+// montaje el kernel ya lo sabe. termina cuando el kernel deja de aceptar
+// avisos (se desmonto)
+#[cfg(unix)]
+fn forward_events(source: EventSource, notifier: fuser::Notifier) {
+    let EventSource { notices, origin, root_inode } = source;
+    let ino = move |id: u32| if id == root_inode { 1 } else { id as u64 };
+    std::thread::spawn(move || {
+        for notice in notices {
+            let own = notice.origin == origin;
+            let result = match notice.event {
+                FsEvent::Entry { .. } | FsEvent::Inode(_) | FsEvent::Stored { .. } if own => Ok(()),
+                FsEvent::Entry { parent, name } => notifier.inval_entry(ino(parent), std::ffi::OsStr::new(&name)),
+                FsEvent::Inode(id) => notifier.inval_inode(ino(id), 0, 0),
+                // el contenido ya esta; solo el tamaño y las fechas se piden de nuevo
+                FsEvent::Stored { inode, offset, data } => notifier
+                    .store(ino(inode), offset, &data)
+                    .and_then(|_| notifier.inval_inode(ino(inode), -1, 0)),
+                FsEvent::Reloaded { inodes } => {
+                    inodes.into_iter().try_for_each(|id| notifier.inval_inode(ino(id), 0, 0))
+                }
+                // que el kernel vuelva a preguntar por la raiz: ahi el
+                // montaje se fija si cambio la metadata
+                FsEvent::BlockStored(_) => notifier.inval_inode(1, -1, 0),
+            };
+            if let Err(e) = result {
+                if !matches!(e.raw_os_error(), Some(libc::ENODEV | libc::ENOTCONN | libc::EBADF)) {
+                    eprintln!("qrfs: warning: el kernel no acepto un aviso de cambios, no se mandan mas: {e}");
+                }
+                return;
+            }
+        }
+    });
+}
+
+#[cfg(unix)]
+impl MountBackend for FuseBackend {
+    type Session = fuser::BackgroundSession;
+
+    fn name(&self) -> &'static str {
+        "fuse"
+    }
+
+    fn mount<B: BlockStorage + 'static>(
+        &self,
+        fs: QrfsFilesystem<B>,
+        mountpoint: &Path,
+    ) -> Result<(), QrfsError> {
+        let options = Self::options(&fs);
+        let events = Self::event_source(&fs);
+        let mut session = fuser::Session::new(fs, mountpoint, &options)
+            .map_err(|e| QrfsError::Other(format!("fuse error: {}", e)))?;
+        if let Some(events) = events {
+            forward_events(events, session.notifier());
+        }
+        session.run().map_err(|e| QrfsError::Other(format!("fuse error: {}", e)))
+    }
+
+    fn spawn_mount<B: BlockStorage + 'static>(
+        &self,
+        fs: QrfsFilesystem<B>,
+        mountpoint: &Path,
+    ) -> Result<Self::Session, QrfsError> {
+        let options = Self::options(&fs);
+        let events = Self::event_source(&fs);
+        let session = fuser::spawn_mount2(fs, mountpoint, &options)
+            .map_err(|e| QrfsError::Other(format!("fuse error: {}", e)))?;
+        if let Some(events) = events {
+            forward_events(events, session.notifier());
+        }
+        Ok(session)
+    }
+}
+
+// fuera de unix no hay backend de montaje: un adaptador de winfsp necesita el
+// sdk de winfsp instalado para compilar y no hay como probarlo. montar falla
+// con un error claro; en windows el disco se usa con webdav.qrfs (que windows
+// monta como unidad de red), extract o la api. qrfs_core compila igual: lo de
+// unix (fuse, pwrite, termios) esta detras de cfg(unix)
+#[cfg(not(unix))]
+pub struct NoMountBackend;
+
+#[cfg(not(unix))]
+pub struct NoMountSession;
+
+#[cfg(not(unix))]
+impl MountBackend for NoMountBackend {
+    type Session = NoMountSession;
+
+    fn name(&self) -> &'static str {
+        "ninguno"
+    }
+
+    fn mount<B: BlockStorage + 'static>(
+        &self,
+        _fs: QrfsFilesystem<B>,
+        _mountpoint: &Path,
+    ) -> Result<(), QrfsError> {
+        Err(QrfsError::Unimplemented(
+            "montar no esta soportado en esta plataforma; use webdav.qrfs, extract o la api".into(),
+        ))
+    }
+
+    fn spawn_mount<B: BlockStorage + 'static>(
+        &self,
+        fs: QrfsFilesystem<B>,
+        mountpoint: &Path,
+    ) -> Result<Self::Session, QrfsError> {
+        self.mount(fs, mountpoint).map(|_| NoMountSession)
+    }
+}
+
+// backend que usan mount.qrfs y el servidor en esta plataforma
+#[cfg(unix)]
+pub type PlatformBackend = FuseBackend;
+#[cfg(not(unix))]
+pub type PlatformBackend = NoMountBackend;
+
+#[cfg(unix)]
+pub const PLATFORM_BACKEND: PlatformBackend = FuseBackend;
+#[cfg(not(unix))]
+pub const PLATFORM_BACKEND: PlatformBackend = NoMountBackend;
+
+pub type MountSession = <PlatformBackend as MountBackend>::Session;
@@ -0,0 +1,289 @@
+// un disco entero en un solo archivo .qrb, para mandarlo o subirlo a un
+// hosting sin empaquetar la carpeta de qr. guarda los datos de cada bloque
+// (no las imagenes: unbundle las vuelve a dibujar), el manifiesto con los
+// archivos y, si se pide, un bloque de paridad xor por cada grupo de n
+// bloques: con eso se recupera un bloque dañado por grupo
+//
+//   "QRFSQRB1" | largo del manifiesto (u32) | crc del manifiesto (u32)
+//   | manifiesto json | datos de cada bloque, en el orden del manifiesto
+//   | paridad de cada grupo
+//
+// los bloques en cero no van: el disco nuevo nace en cero
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::api::QrfsApi;
+use crate::disk::BlockId;
+use crate::errors::QrfsError;
+use crate::fs_format::read_superblock;
+use crate::sha256::sha256_hex;
+use crate::storage::{BlockStorage, InMemoryBlockStorage};
+
+pub const BUNDLE_MAGIC: &[u8; 8] = b"QRFSQRB1";
+const HEADER_LEN: usize = 16;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub block_size: usize,
+    pub total_blocks: u32,
+    pub blocks: Vec<BundleBlock>,
+    // bloques por grupo de paridad; 0 sin paridad
+    pub parity_group: usize,
+    pub parity_crcs: Vec<u32>,
+    // mismo formato que el json de disk_image: sirve para verify --manifest
+    pub files: Vec<BundleFile>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct BundleBlock {
+    pub id: BlockId,
+    pub crc: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleFile {
+    pub name: String,
+    pub inode_id: u32,
+    pub size: u64,
+    pub blocks: Vec<BlockId>,
+    // sin clave no se puede leer un cifrado
+    pub sha256: Option<String>,
+}
+
+pub struct Bundle {
+    pub manifest: BundleManifest,
+    pub blocks: Vec<(BlockId, Vec<u8>)>,
+    // los que estaban dañados y se rearmaron con la paridad
+    pub repaired: Vec<BlockId>,
+}
+
+// arma el .qrb de un disco; parity_group 0 para no agregar paridad
+pub fn create_bundle<B: BlockStorage + 'static>(storage: Arc<B>, parity_group: usize) -> Result<Vec<u8>, QrfsError> {
+    let sb = read_superblock(storage.as_ref())?;
+    if !sb.is_valid() {
+        return Err(QrfsError::NotFormatted("firma invalida en bloque 0".into()));
+    }
+    if sb.content_addressed || sb.volume_set.is_some() {
+        return Err(QrfsError::Unimplemented(
+            "los discos cas o repartidos en volumenes no se empaquetan".into(),
+        ));
+    }
+    let block_size = storage.block_size();
+
+    let mut blocks = Vec::new();
+    for id in 0..sb.total_blocks {
+        let data = storage.read_block(id)?;
+        if data.iter().any(|&b| b != 0) {
+            blocks.push((id, data));
+        }
+    }
+
+    let api = QrfsApi::open(storage)?;
+    let tree = api.walk()?;
+    let mut files = Vec::new();
+    for info in api.list("/")? {
+        if info.is_dir() {
+            continue;
+        }
+        let sha256 = if info.encrypted {
+            None
+        } else {
+            Some(sha256_hex(&api.read_inode(info.inode_id)?))
+        };
+        let blocks = tree
+            .iter()
+            .find(|entry| entry.info.inode_id == info.inode_id)
+            .map(|entry| entry.blocks.clone())
+            .unwrap_or_default();
+        files.push(BundleFile {
+            name: info.name,
+            inode_id: info.inode_id,
+            size: info.size,
+            blocks,
+            sha256,
+        });
+    }
+
+    let parity = if parity_group == 0 {
+        Vec::new()
+    } else {
+        blocks.chunks(parity_group).map(|group| xor_all(group.iter().map(|(_, d)| d.as_slice()), block_size)).collect()
+    };
+    let manifest = BundleManifest {
+        block_size,
+        total_blocks: sb.total_blocks,
+        blocks: blocks.iter().map(|(id, data)| BundleBlock { id: *id, crc: crc32fast::hash(data) }).collect(),
+        parity_group,
+        parity_crcs: parity.iter().map(|p: &Vec<u8>| crc32fast::hash(p)).collect(),
+        files,
+    };
+    let json = serde_json::to_vec(&manifest)
+        .map_err(|e| QrfsError::Other(format!("error serializando el manifiesto: {}", e)))?;
+
+    let mut out = Vec::with_capacity(HEADER_LEN + json.len() + (blocks.len() + parity.len()) * block_size);
+    out.extend_from_slice(BUNDLE_MAGIC);
+    out.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    out.extend_from_slice(&crc32fast::hash(&json).to_le_bytes());
+    out.extend_from_slice(&json);
+    for (_, data) in &blocks {
+        out.extend_from_slice(data);
+    }
+    for data in &parity {
+        out.extend_from_slice(data);
+    }
+    Ok(out)
+}
+
+// lee un .qrb y arregla con la paridad lo que se pueda; falla si queda
+// algun bloque dañado
+pub fn open_bundle(bytes: &[u8]) -> Result<Bundle, QrfsError> {
+    if bytes.len() < HEADER_LEN || &bytes[..8] != BUNDLE_MAGIC {
+        return Err(QrfsError::Other("no es un bundle qrfs (.qrb)".into()));
+    }
+    let json_len = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+    let json_crc = u32::from_le_bytes(bytes[12..16].try_into().unwrap());
+    let json = bytes
+        .get(HEADER_LEN..HEADER_LEN + json_len)
+        .filter(|json| crc32fast::hash(json) == json_crc)
+        .ok_or_else(|| QrfsError::Other("el manifiesto del bundle esta dañado".into()))?;
+    let manifest: BundleManifest = serde_json::from_slice(json)
+        .map_err(|e| QrfsError::Other(format!("manifiesto invalido: {}", e)))?;
+
+    let block_size = manifest.block_size;
+    let data_start = HEADER_LEN + json_len;
+    let expected = data_start + (manifest.blocks.len() + manifest.parity_crcs.len()) * block_size;
+    if bytes.len() < expected {
+        return Err(QrfsError::Other(format!(
+            "bundle cortado: tiene {} bytes de {}",
+            bytes.len(),
+            expected
+        )));
+    }
+    let slot = |i: usize| &bytes[data_start + i * block_size..data_start + (i + 1) * block_size];
+
+    let mut blocks: Vec<(BlockId, Vec<u8>)> =
+        manifest.blocks.iter().enumerate().map(|(i, block)| (block.id, slot(i).to_vec())).collect();
+    let bad: Vec<usize> = (0..blocks.len())
+        .filter(|&i| crc32fast::hash(&blocks[i].1) != manifest.blocks[i].crc)
+        .collect();
+
+    let mut repaired = Vec::new();
+    let mut lost = Vec::new();
+    for &i in &bad {
+        let group = i.checked_div(manifest.parity_group);
+        let fixed = group.and_then(|group| {
+            let members = group * manifest.parity_group..((group + 1) * manifest.parity_group).min(blocks.len());
+            // la paridad arregla uno solo por grupo, y tiene que estar sana
+            if bad.iter().filter(|b| members.contains(b)).count() != 1 {
+                return None;
+            }
+            let parity = slot(manifest.blocks.len() + group);
+            if crc32fast::hash(parity) != manifest.parity_crcs[group] {
+                return None;
+            }
+            let others = members.filter(|&m| m != i).map(|m| blocks[m].1.as_slice());
+            let data = xor_all(others.chain(std::iter::once(parity)), block_size);
+            Some(data).filter(|data| crc32fast::hash(data) == manifest.blocks[i].crc)
+        });
+        match fixed {
+            Some(data) => {
+                repaired.push(blocks[i].0);
+                blocks[i].1 = data;
+            }
+            None => lost.push(blocks[i].0),
+        }
+    }
+    if !lost.is_empty() {
+        return Err(QrfsError::Other(format!(
+            "bloques dañados que la paridad no alcanza a rearmar: {:?}",
+            lost
+        )));
+    }
+    Ok(Bundle { manifest, blocks, repaired })
+}
+
+impl Bundle {
+    // el disco en memoria, para leer el superblock o abrirlo con la api
+    pub fn to_storage(&self) -> Result<InMemoryBlockStorage, QrfsError> {
+        let storage = InMemoryBlockStorage::new(self.manifest.total_blocks, self.manifest.block_size);
+        self.restore(&storage)?;
+        Ok(storage)
+    }
+
+    // escribe cada bloque en un disco vacio de la misma geometria
+    pub fn restore<S: BlockStorage + ?Sized>(&self, storage: &S) -> Result<(), QrfsError> {
+        for (id, data) in &self.blocks {
+            storage.write_block(*id, data)?;
+        }
+        Ok(())
+    }
+}
+
+fn xor_all<'a>(blocks: impl Iterator<Item = &'a [u8]>, block_size: usize) -> Vec<u8> {
+    let mut out = vec![0u8; block_size];
+    for data in blocks {
+        for (o, b) in out.iter_mut().zip(data) {
+            *o ^= b;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::{Superblock, BLOCK_SIZE};
+    use crate::fs_format::format_storage;
+
+    fn disk() -> Arc<InMemoryBlockStorage> {
+        let storage = Arc::new(InMemoryBlockStorage::new(64, BLOCK_SIZE));
+        format_storage(storage.as_ref(), &Superblock::new(64, 16)).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        api.write("/a.bin", &(0..900u32).map(|i| (i % 253) as u8).collect::<Vec<_>>()).unwrap();
+        api.write("/b.txt", b"hola").unwrap();
+        api.flush().unwrap();
+        storage
+    }
+
+    #[test]
+    fn a_bundle_reopens_as_the_same_disk() {
+        let bytes = create_bundle(disk(), 0).unwrap();
+        let bundle = open_bundle(&bytes).unwrap();
+        assert!(bundle.repaired.is_empty());
+        assert_eq!(bundle.manifest.files.len(), 2);
+
+        let api = QrfsApi::open(Arc::new(bundle.to_storage().unwrap())).unwrap();
+        assert_eq!(api.read("/b.txt").unwrap(), b"hola");
+        let sha = sha256_hex(&api.read("/a.bin").unwrap());
+        assert_eq!(bundle.manifest.files[0].sha256.as_deref(), Some(sha.as_str()));
+
+        // sin paridad un bloque dañado no tiene arreglo
+        let mut broken = bytes.clone();
+        let last = broken.len() - 1;
+        broken[last] ^= 0xff;
+        assert!(open_bundle(&broken).is_err());
+        assert!(open_bundle(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn parity_rebuilds_one_damaged_block_per_group() {
+        let bytes = create_bundle(disk(), 4).unwrap();
+        let manifest = open_bundle(&bytes).unwrap().manifest;
+        let data_start = bytes.len() - (manifest.blocks.len() + manifest.parity_crcs.len()) * manifest.block_size;
+
+        // uno en el grupo 0 y otro en el grupo 1: los dos se arreglan
+        let mut damaged = bytes.clone();
+        damaged[data_start + 3] ^= 0x55;
+        damaged[data_start + 5 * manifest.block_size] ^= 0x55;
+        let bundle = open_bundle(&damaged).unwrap();
+        assert_eq!(bundle.repaired, vec![manifest.blocks[0].id, manifest.blocks[5].id]);
+        let api = QrfsApi::open(Arc::new(bundle.to_storage().unwrap())).unwrap();
+        assert_eq!(api.read("/b.txt").unwrap(), b"hola");
+
+        // dos en el mismo grupo ya no
+        damaged[data_start + manifest.block_size] ^= 0x55;
+        assert!(open_bundle(&damaged).is_err());
+    }
+}
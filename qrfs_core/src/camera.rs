@@ -0,0 +1,320 @@
+// ingesta desde una camara de la compu, sin servidor ni celular
+// capture.qrfs pide cuadros a una fuente, busca todos los qr de cada uno y
+// guarda los bloques en la carpeta del disco con las mismas reglas que el
+// servidor (QrStorageManager::store_scanned). fuentes:
+//   - una webcam por v4l2 (linux), en yuyv, gris o mjpeg; el acceso al
+//     dispositivo esta en capture.rs, aca solo el trait
+//   - una carpeta donde otro programa va dejando cuadros (png, jpeg...), por
+//     ejemplo ffmpeg -f avfoundation -i 0 -r 2 cuadros/%06d.png en macos,
+//     donde no hay v4l2
+//   - una zona de la pantalla donde se esta pasando una secuencia de qr (otra
+//     compu mostrando las hojas una tras otra), con la herramienta de captura
+//     del sistema: grim en wayland, import (imagemagick) en x11, screencapture
+//     en macos
+// solo los qr con metadata: sin el id no se sabe de que bloque son
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use image::DynamicImage;
+
+use crate::disk::BlockId;
+use crate::errors::QrfsError;
+use crate::qr::{decode_all_qr_text, DecodedBlock};
+use crate::storage::{BlockStorage, QrStorageManager, ScanDecision};
+
+pub trait FrameSource {
+    // el proximo cuadro; None si la fuente se termino
+    fn next_frame(&mut self) -> Result<Option<DynamicImage>, QrfsError>;
+}
+
+// lo que salio de un qr del cuadro
+#[derive(Debug)]
+pub struct ScannedQr {
+    // None si el qr no trae metadata
+    pub block: Option<BlockId>,
+    pub result: Result<ScanDecision, QrfsError>,
+}
+
+// busca los qr del cuadro y guarda los bloques
+pub fn ingest_frame(storage: &QrStorageManager, frame: &DynamicImage) -> Vec<ScannedQr> {
+    decode_all_qr_text(frame)
+        .iter()
+        .map(|text| ingest_text(storage, text))
+        .collect()
+}
+
+// guarda el bloque de un texto leido (de un qr o de otra simbologia, ver
+// audio.rs)
+pub fn ingest_text(storage: &QrStorageManager, text: &str) -> ScannedQr {
+    let decoded = match DecodedBlock::from_text(text) {
+        Ok(decoded) => decoded,
+        Err(e) => return ScannedQr { block: None, result: Err(e) },
+    };
+    let Some(block) = decoded.block_id else {
+        return ScannedQr {
+            block: None,
+            result: Err(QrfsError::Other("qr sin metadata, no se sabe de que bloque es".into())),
+        };
+    };
+    let result = if !decoded.checksum_ok {
+        Err(QrfsError::Other(format!("checksum invalido en el bloque {}", block)))
+    } else if block >= storage.total_blocks() || decoded.data.len() > storage.block_size() {
+        Err(QrfsError::Other(format!("el bloque {} no es de este disco", block)))
+    } else {
+        storage.store_scanned(block, &decoded)
+    };
+    ScannedQr {
+        block: Some(block),
+        result,
+    }
+}
+
+// cuadros que otro programa deja en una carpeta, en orden de nombre. con
+// follow espera los que van llegando; sin follow termina al agotarlos
+pub struct FolderFrames {
+    dir: PathBuf,
+    seen: HashSet<PathBuf>,
+    // los que no abrieron (ya avisados); se reintentan en la proxima vuelta
+    unreadable: HashSet<PathBuf>,
+    follow: bool,
+}
+
+impl FolderFrames {
+    pub fn new(dir: impl Into<PathBuf>, follow: bool) -> Self {
+        Self {
+            dir: dir.into(),
+            seen: HashSet::new(),
+            unreadable: HashSet::new(),
+            follow,
+        }
+    }
+}
+
+impl FrameSource for FolderFrames {
+    fn next_frame(&mut self) -> Result<Option<DynamicImage>, QrfsError> {
+        loop {
+            let mut pending: Vec<PathBuf> = fs::read_dir(&self.dir)?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.is_file() && !self.seen.contains(path))
+                .filter(|path| image::ImageFormat::from_path(path).is_ok())
+                .collect();
+            pending.sort();
+            for path in pending {
+                // un cuadro a medio escribir no abre: queda sin ver y se
+                // vuelve a probar cuando el otro programa lo termine
+                match image::open(&path) {
+                    Ok(img) => {
+                        self.unreadable.remove(&path);
+                        self.seen.insert(path);
+                        return Ok(Some(img));
+                    }
+                    Err(e) => {
+                        if self.unreadable.insert(path.clone()) {
+                            eprintln!("qrfs: warning: cuadro {}: {}", path.display(), e);
+                        }
+                    }
+                }
+            }
+            if !self.follow {
+                return Ok(None);
+            }
+            thread::sleep(Duration::from_millis(200));
+        }
+    }
+}
+
+// zona de la pantalla en formato de x11: ANCHOxALTO+X+Y
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    pub fn parse(text: &str) -> Result<Self, QrfsError> {
+        let invalid = || QrfsError::Other(format!("zona invalida '{}' (ANCHOxALTO+X+Y)", text));
+        let (size, offset) = text.split_once('+').ok_or_else(invalid)?;
+        let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+        let (x, y) = offset.split_once('+').ok_or_else(invalid)?;
+        let number = |n: &str| n.parse::<u32>().map_err(|_| invalid());
+        let region = Region {
+            x: number(x)?,
+            y: number(y)?,
+            width: number(width)?,
+            height: number(height)?,
+        };
+        if region.width == 0 || region.height == 0 {
+            return Err(invalid());
+        }
+        Ok(region)
+    }
+}
+
+// entre captura y captura; la secuencia cambia de hoja mas lento que esto
+const SCREEN_INTERVAL: Duration = Duration::from_millis(100);
+
+// capturas de pantalla: un programa que escribe un png por stdout. nunca se
+// termina, capture.qrfs corta cuando el disco esta completo
+pub struct ScreenFrames {
+    command: Vec<String>,
+}
+
+impl ScreenFrames {
+    // la herramienta del sistema, toda la pantalla o solo la zona
+    pub fn new(region: Option<Region>) -> Result<Self, QrfsError> {
+        let mut command: Vec<String> = if cfg!(target_os = "macos") {
+            vec!["screencapture".into(), "-x".into(), "-t".into(), "png".into()]
+        } else if cfg!(unix) && std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            vec!["grim".into()]
+        } else if cfg!(unix) {
+            vec!["import".into(), "-silent".into(), "-window".into(), "root".into()]
+        } else {
+            return Err(QrfsError::Unimplemented(
+                "captura de pantalla sin herramienta en este sistema; use --screen-cmd".into(),
+            ));
+        };
+        match (command[0].as_str(), region) {
+            ("screencapture", Some(r)) => {
+                command.extend(["-R".into(), format!("{},{},{},{}", r.x, r.y, r.width, r.height)])
+            }
+            ("grim", Some(r)) => command.extend(["-g".into(), format!("{},{} {}x{}", r.x, r.y, r.width, r.height)]),
+            ("import", Some(r)) => command.extend(["-crop".into(), format!("{}x{}+{}+{}", r.width, r.height, r.x, r.y)]),
+            _ => {}
+        }
+        command.push(
+            match command[0].as_str() {
+                "screencapture" => "/dev/stdout",
+                "grim" => "-",
+                _ => "png:-",
+            }
+            .into(),
+        );
+        Ok(Self { command })
+    }
+
+    // otra herramienta (argumentos separados por espacios)
+    pub fn with_command(command: &str) -> Result<Self, QrfsError> {
+        let command: Vec<String> = command.split_whitespace().map(String::from).collect();
+        if command.is_empty() {
+            return Err(QrfsError::Other("comando de captura vacio".into()));
+        }
+        Ok(Self { command })
+    }
+}
+
+impl FrameSource for ScreenFrames {
+    fn next_frame(&mut self) -> Result<Option<DynamicImage>, QrfsError> {
+        thread::sleep(SCREEN_INTERVAL);
+        let output = Command::new(&self.command[0])
+            .args(&self.command[1..])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|e| QrfsError::Other(format!("no se pudo ejecutar {}: {}", self.command[0], e)))?;
+        if !output.status.success() {
+            return Err(QrfsError::Other(format!("{} respondio {}", self.command[0], output.status)));
+        }
+        image::load_from_memory(&output.stdout)
+            .map(Some)
+            .map_err(|e| QrfsError::Other(format!("captura de {} ilegible: {}", self.command[0], e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::BLOCK_SIZE;
+    use crate::qr::{encode_block, QrParams};
+
+    #[test]
+    fn every_qr_in_a_frame_is_stored_once() {
+        let dir = std::env::temp_dir().join(format!("qrfs_camera_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let frames = dir.join("cuadros");
+        fs::create_dir_all(&frames).unwrap();
+        let storage = QrStorageManager::new(dir.join("disco"), BLOCK_SIZE, 16);
+
+        // dos qr lado a lado en el mismo cuadro
+        let params = QrParams::default();
+        let a = encode_block(3, 2, &[7; 40], params).unwrap();
+        let b = encode_block(5, 1, &[9; 40], params).unwrap();
+        let mut frame = image::GrayImage::from_pixel(a.width() + b.width() + 40, a.height().max(b.height()) + 20, image::Luma([255]));
+        image::imageops::overlay(&mut frame, &a, 10, 10);
+        image::imageops::overlay(&mut frame, &b, a.width() as i64 + 30, 10);
+        frame.save(frames.join("000001.png")).unwrap();
+        frame.save(frames.join("000002.png")).unwrap();
+
+        let mut source = FolderFrames::new(&frames, false);
+        let first = ingest_frame(&storage, &source.next_frame().unwrap().unwrap());
+        let mut saved: Vec<_> = first.iter().map(|q| (q.block, q.result.as_ref().ok().copied())).collect();
+        saved.sort_by_key(|(block, _)| *block);
+        assert_eq!(
+            saved,
+            [(Some(3), Some(ScanDecision::Saved(2))), (Some(5), Some(ScanDecision::Saved(1)))]
+        );
+        assert_eq!(&storage.read_block(3).unwrap()[..40], &[7; 40]);
+
+        // el mismo cuadro otra vez: nada nuevo
+        let second = ingest_frame(&storage, &source.next_frame().unwrap().unwrap());
+        assert!(second.iter().all(|q| matches!(q.result, Ok(ScanDecision::Duplicate))));
+        assert!(source.next_frame().unwrap().is_none());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_half_written_frame_is_read_once_it_is_complete() {
+        let dir = std::env::temp_dir().join(format!("qrfs_camera_partial_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let frame = encode_block(1, 1, &[5; 20], QrParams::default()).unwrap();
+        frame.save(dir.join("000001.png")).unwrap();
+        let full = fs::read(dir.join("000001.png")).unwrap();
+        fs::write(dir.join("000001.png"), &full[..full.len() / 2]).unwrap();
+
+        let mut source = FolderFrames::new(&dir, false);
+        assert!(source.next_frame().unwrap().is_none());
+        // el otro programa termina de escribirlo
+        fs::write(dir.join("000001.png"), &full).unwrap();
+        assert!(source.next_frame().unwrap().is_some());
+        assert!(source.next_frame().unwrap().is_none());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn region_uses_x11_geometry() {
+        assert_eq!(
+            Region::parse("640x480+10+20").unwrap(),
+            Region { x: 10, y: 20, width: 640, height: 480 }
+        );
+        assert!(Region::parse("640x480").is_err());
+        assert!(Region::parse("0x480+0+0").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn screen_frames_read_the_png_the_tool_prints() {
+        let dir = std::env::temp_dir().join(format!("qrfs_screen_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let shot = dir.join("pantalla.png");
+        encode_block(2, 1, &[4; 30], QrParams::default()).unwrap().save(&shot).unwrap();
+
+        let mut source = ScreenFrames::with_command(&format!("cat {}", shot.display())).unwrap();
+        let storage = QrStorageManager::new(dir.join("disco"), BLOCK_SIZE, 16);
+        let scanned = ingest_frame(&storage, &source.next_frame().unwrap().unwrap());
+        assert!(matches!(scanned[..], [ScannedQr { block: Some(2), result: Ok(ScanDecision::Saved(1)) }]));
+
+        let mut broken = ScreenFrames::with_command("false").unwrap();
+        assert!(broken.next_frame().is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,98 @@
+// modo archivo direccionado por contenido
+// los bloques de datos se guardan como cas/<sha1>.png: dos bloques iguales
+// comparten imagen, y cada imagen se verifica con su propio nombre. si solo
+// sobreviven algunos qr, el indice dice que bloques se pueden recuperar.
+// la metadata (superblock, bitmap, inodos) sigue con ids fijos
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sha1::{Digest, Sha1};
+
+use crate::disk::BlockId;
+use crate::errors::QrfsError;
+
+// indice bloque -> digest, dentro de la carpeta del disco
+pub const CAS_INDEX_FILE: &str = ".qrfs_cas_index.json";
+// subcarpeta con las imagenes nombradas por digest
+pub const CAS_DIR: &str = "cas";
+
+// digest (sha1 en hex) del contenido de un bloque
+pub fn block_digest(data: &[u8]) -> String {
+    Sha1::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub struct CasIndex {
+    root_dir: PathBuf,
+    // bloques desde aca se direccionan por contenido
+    data_start: BlockId,
+    digests: HashMap<BlockId, String>,
+}
+
+impl CasIndex {
+    // carga el indice de la carpeta; si no existe empieza vacio
+    pub fn load(root_dir: &Path, data_start: BlockId) -> Result<Self, QrfsError> {
+        let path = root_dir.join(CAS_INDEX_FILE);
+        let digests = match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|e| QrfsError::Other(format!("indice cas ilegible: {}", e)))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            root_dir: root_dir.to_path_buf(),
+            data_start,
+            digests,
+        })
+    }
+
+    pub fn covers(&self, id: BlockId) -> bool {
+        id >= self.data_start
+    }
+
+    pub fn digest(&self, id: BlockId) -> Option<&str> {
+        self.digests.get(&id).map(|d| d.as_str())
+    }
+
+    pub fn image_path(&self, digest: &str) -> PathBuf {
+        self.root_dir.join(CAS_DIR).join(format!("{}.png", digest))
+    }
+
+    // asigna (o borra con None) el digest de un bloque y guarda el indice.
+    // devuelve el digest anterior si ningun otro bloque lo usa (su imagen sobra)
+    pub fn set(&mut self, id: BlockId, digest: Option<String>) -> Result<Option<String>, QrfsError> {
+        let old = match digest {
+            Some(d) => self.digests.insert(id, d),
+            None => self.digests.remove(&id),
+        };
+        self.save()?;
+        Ok(old.filter(|old| !self.digests.values().any(|d| d == old)))
+    }
+
+    // bloques del indice cuya imagen no esta en la carpeta
+    pub fn missing(&self) -> Vec<BlockId> {
+        let mut ids: Vec<BlockId> = self
+            .digests
+            .iter()
+            .filter(|(_, d)| !self.image_path(d).exists())
+            .map(|(&id, _)| id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    // escribe a un temporal y renombra para no dejar el indice a medias
+    fn save(&self) -> Result<(), QrfsError> {
+        let text = serde_json::to_string(&self.digests)
+            .map_err(|e| QrfsError::Other(format!("error serializando indice cas: {}", e)))?;
+        let path = self.root_dir.join(CAS_INDEX_FILE);
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, text)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+}
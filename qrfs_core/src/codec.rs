@@ -0,0 +1,321 @@
+// formatos del texto que viaja dentro de cada qr
+// v1: sobre json {"block_id","gen","crc","len","data"} (lo que escribe el storage)
+// v2: binario compacto en base64 con prefijo: id, generacion y crc en
+//     little endian seguidos del payload, sin nombres de campos
+// los dos se leen con DecodedBlock::from_text
+//
+// cada codec tiene un id de un byte que el superblock guarda (Superblock::codec):
+// el disco dice como se escribieron sus bloques. los de este crate son
+// qr-v1 (0, el de siempre) y qr-v2 (1); otro crate agrega los suyos (una
+// datamatrix, un archivo plano) con register_codec desde FIRST_EXTERNAL_CODEC
+
+use std::sync::{Arc, RwLock};
+
+use base64::{engine::general_purpose, Engine as _};
+use image::{DynamicImage, GrayImage};
+
+use crate::errors::QrfsError;
+use crate::qr::{block_checksum, decode_qr_text, encode_text, BlockEnvelope, DecodedBlock, EnvelopeVersion, QrParams};
+
+// prefijo del formato binario (no puede ser json ni base64 valido por el ':')
+pub const BINARY_PREFIX: &str = "QRFS2:";
+// id (4) + generacion (8) + crc (4)
+const BINARY_HEADER: usize = 16;
+
+// el que usa un superblock sin el campo (discos de antes del registro)
+pub const DEFAULT_CODEC: u8 = 0;
+// los ids de abajo quedan para este crate
+pub const FIRST_EXTERNAL_CODEC: u8 = 64;
+
+pub trait BlockCodec: Send + Sync {
+    // como se elige en mkfs --codec y como lo muestra info
+    fn name(&self) -> &'static str;
+    // la version de sobre que mas se le parece (la que reporta DecodedBlock)
+    fn version(&self) -> EnvelopeVersion;
+    fn encode(&self, id: u32, gen: u64, data: &[u8]) -> String;
+    fn decode(&self, text: &str) -> Result<DecodedBlock, QrfsError>;
+
+    // la simbologia: como se dibuja el texto y como se lo saca de la imagen.
+    // por defecto un qr
+    fn draw(&self, text: &str, params: QrParams) -> Result<GrayImage, QrfsError> {
+        encode_text(text, params)
+    }
+
+    fn scan(&self, img: &DynamicImage) -> Result<String, QrfsError> {
+        decode_qr_text(img)
+    }
+}
+
+// los que agregan otros crates; los de este se resuelven en codec_by_id
+static REGISTRY: RwLock<Vec<(u8, Arc<dyn BlockCodec>)>> = RwLock::new(Vec::new());
+
+// deja disponible un codec para los discos que lo nombren. hay que hacerlo
+// antes de abrir el disco; el id no se puede repetir ni cambiar despues (esta
+// escrito en los superblocks)
+pub fn register_codec(id: u8, codec: Arc<dyn BlockCodec>) -> Result<(), QrfsError> {
+    if id < FIRST_EXTERNAL_CODEC {
+        return Err(QrfsError::Other(format!(
+            "los codecs de otros crates usan ids desde {} (se pidio {})",
+            FIRST_EXTERNAL_CODEC, id
+        )));
+    }
+    let mut registry = REGISTRY.write().unwrap();
+    let clash = codecs_in(&registry)
+        .into_iter()
+        .find(|(other, existing)| *other == id || existing.name() == codec.name());
+    if let Some((other, existing)) = clash {
+        return Err(QrfsError::AlreadyExists(format!(
+            "el codec '{}' ya esta registrado con el id {}",
+            existing.name(),
+            other
+        )));
+    }
+    registry.push((id, codec));
+    Ok(())
+}
+
+pub fn codec_by_id(id: u8) -> Option<Arc<dyn BlockCodec>> {
+    codecs().into_iter().find(|(other, _)| *other == id).map(|(_, codec)| codec)
+}
+
+pub fn codec_by_name(name: &str) -> Option<(u8, Arc<dyn BlockCodec>)> {
+    codecs().into_iter().find(|(_, codec)| codec.name() == name)
+}
+
+// los de este crate primero, despues los registrados en orden
+pub fn codecs() -> Vec<(u8, Arc<dyn BlockCodec>)> {
+    codecs_in(&REGISTRY.read().unwrap())
+}
+
+fn codecs_in(registered: &[(u8, Arc<dyn BlockCodec>)]) -> Vec<(u8, Arc<dyn BlockCodec>)> {
+    let mut all: Vec<(u8, Arc<dyn BlockCodec>)> =
+        vec![(DEFAULT_CODEC, Arc::new(JsonCodec)), (1, Arc::new(BinaryCodec))];
+    all.extend(registered.iter().cloned());
+    all
+}
+
+// id del codec con ese nombre (mkfs --codec)
+pub fn parse_codec(name: &str) -> Result<u8, QrfsError> {
+    codec_by_name(name).map(|(id, _)| id).ok_or_else(|| {
+        let known: Vec<&str> = codecs().iter().map(|(_, codec)| codec.name()).collect();
+        QrfsError::Other(format!("codec desconocido '{}' (hay {})", name, known.join(", ")))
+    })
+}
+
+// nombre para mostrar, aunque el codec no este registrado en este programa
+pub fn codec_name(id: u8) -> String {
+    codec_by_id(id).map_or_else(|| format!("desconocido ({})", id), |codec| codec.name().to_string())
+}
+
+pub struct JsonCodec;
+
+impl BlockCodec for JsonCodec {
+    fn name(&self) -> &'static str {
+        "qr-v1"
+    }
+
+    fn version(&self) -> EnvelopeVersion {
+        EnvelopeVersion::JsonChecked
+    }
+
+    fn encode(&self, id: u32, gen: u64, data: &[u8]) -> String {
+        BlockEnvelope::new(id, gen, data).to_json()
+    }
+
+    fn decode(&self, text: &str) -> Result<DecodedBlock, QrfsError> {
+        let block = DecodedBlock::from_text(text)?;
+        if block.envelope == EnvelopeVersion::Binary {
+            return Err(QrfsError::Other("el qr no es un sobre json".into()));
+        }
+        Ok(block)
+    }
+}
+
+pub struct BinaryCodec;
+
+impl BlockCodec for BinaryCodec {
+    fn name(&self) -> &'static str {
+        "qr-v2"
+    }
+
+    fn version(&self) -> EnvelopeVersion {
+        EnvelopeVersion::Binary
+    }
+
+    fn encode(&self, id: u32, gen: u64, data: &[u8]) -> String {
+        let mut raw = Vec::with_capacity(BINARY_HEADER + data.len());
+        raw.extend_from_slice(&id.to_le_bytes());
+        raw.extend_from_slice(&gen.to_le_bytes());
+        raw.extend_from_slice(&block_checksum(data).to_le_bytes());
+        raw.extend_from_slice(data);
+        format!("{}{}", BINARY_PREFIX, general_purpose::STANDARD.encode(raw))
+    }
+
+    fn decode(&self, text: &str) -> Result<DecodedBlock, QrfsError> {
+        let body = text
+            .strip_prefix(BINARY_PREFIX)
+            .ok_or_else(|| QrfsError::Other("el qr no es un bloque binario".into()))?;
+        let raw = general_purpose::STANDARD
+            .decode(body)
+            .map_err(|e| QrfsError::Other(format!("error decodificando base64: {}", e)))?;
+        if raw.len() < BINARY_HEADER {
+            return Err(QrfsError::Other(format!(
+                "bloque binario de {} bytes, el encabezado ocupa {}",
+                raw.len(),
+                BINARY_HEADER
+            )));
+        }
+
+        let data = raw[BINARY_HEADER..].to_vec();
+        let crc = u32::from_le_bytes(raw[12..16].try_into().unwrap());
+        Ok(DecodedBlock {
+            block_id: Some(u32::from_le_bytes(raw[0..4].try_into().unwrap())),
+            generation: u64::from_le_bytes(raw[4..12].try_into().unwrap()),
+            checksum_ok: crc == block_checksum(&data),
+            // el largo es el del base64: no hay otro que comparar
+            declared_len: Some(data.len()),
+            envelope: EnvelopeVersion::Binary,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::BLOCK_SIZE;
+    use crate::qr::{decode_qr_image, encode_text, BlockStatus, QrParams};
+    use image::DynamicImage;
+    use proptest::prelude::*;
+    use qrcode::EcLevel;
+
+    fn codecs() -> [&'static dyn BlockCodec; 2] {
+        [&JsonCodec, &BinaryCodec]
+    }
+
+    proptest! {
+        // solo el texto: barato, se prueban bloques mas grandes que un qr
+        #[test]
+        fn codecs_roundtrip_any_payload(
+            id in any::<u32>(),
+            gen in any::<u64>(),
+            data in proptest::collection::vec(any::<u8>(), 0..4096),
+        ) {
+            for codec in codecs() {
+                let block = codec.decode(&codec.encode(id, gen, &data)).unwrap();
+                prop_assert_eq!(block.block_id, Some(id));
+                prop_assert_eq!(block.generation, gen);
+                prop_assert_eq!(&block.data, &data);
+                prop_assert_eq!(block.envelope, codec.version());
+                prop_assert_eq!(block.status(), BlockStatus::Verified);
+                prop_assert!(block.length_matches());
+            }
+        }
+    }
+
+    proptest! {
+        // texto + qr + imagen: cada caso dibuja y decodifica dos qr
+        #![proptest_config(ProptestConfig::with_cases(24))]
+        #[test]
+        fn codecs_roundtrip_through_qr_images(
+            id in any::<u32>(),
+            gen in any::<u64>(),
+            data in proptest::collection::vec(any::<u8>(), 0..=BLOCK_SIZE),
+            ec_level in prop_oneof![Just(EcLevel::L), Just(EcLevel::M)],
+        ) {
+            // tamaño pensado para el bloque mas grande: los modulos no bajan de 2px
+            let params = QrParams { size: 400, ec_level };
+            for codec in codecs() {
+                let image = encode_text(&codec.encode(id, gen, &data), params).unwrap();
+                let block = decode_qr_image(&DynamicImage::ImageLuma8(image)).unwrap();
+                prop_assert_eq!(block.block_id, Some(id));
+                prop_assert_eq!(block.generation, gen);
+                prop_assert_eq!(&block.data, &data);
+                prop_assert!(block.checksum_ok);
+            }
+        }
+    }
+
+    // un codec de afuera: el bloque en hex, dibujado en qr como los demas
+    struct HexCodec;
+
+    impl BlockCodec for HexCodec {
+        fn name(&self) -> &'static str {
+            "hex-prueba"
+        }
+
+        fn version(&self) -> EnvelopeVersion {
+            EnvelopeVersion::Raw
+        }
+
+        fn encode(&self, id: u32, gen: u64, data: &[u8]) -> String {
+            let hex: String = data.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("HEX:{}:{}:{}", id, gen, hex)
+        }
+
+        fn decode(&self, text: &str) -> Result<DecodedBlock, QrfsError> {
+            let fields: Vec<&str> = text.split(':').collect();
+            let ["HEX", id, gen, hex] = fields[..] else {
+                return Err(QrfsError::Other("no es hex".into()));
+            };
+            let data = (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+                .collect::<Result<Vec<u8>, _>>()
+                .map_err(|e| QrfsError::Other(e.to_string()))?;
+            Ok(DecodedBlock {
+                block_id: id.parse().ok(),
+                generation: gen.parse().unwrap_or(0),
+                declared_len: None,
+                envelope: EnvelopeVersion::Raw,
+                checksum_ok: true,
+                data,
+            })
+        }
+    }
+
+    #[test]
+    fn a_registered_codec_is_named_in_the_superblock_and_read_back() {
+        use crate::api::QrfsApi;
+        use crate::disk::Superblock;
+        use crate::fs_format::format_storage;
+        use crate::qr::decode_qr_text;
+        use crate::storage::QrStorageManager;
+        use std::sync::Arc;
+
+        assert!(register_codec(3, Arc::new(HexCodec)).is_err());
+        register_codec(200, Arc::new(HexCodec)).unwrap();
+        assert!(register_codec(201, Arc::new(HexCodec)).is_err());
+        assert_eq!(parse_codec("hex-prueba").unwrap(), 200);
+        assert_eq!(parse_codec("qr-v2").unwrap(), 1);
+        assert!(parse_codec("datamatrix").is_err());
+
+        let dir = std::env::temp_dir().join(format!("qrfs_codec_registry_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut superblock = Superblock::new(48, 16);
+        superblock.codec = 200;
+        let storage = QrStorageManager::new(&dir, BLOCK_SIZE, 48).with_codec(200);
+        format_storage(&storage, &superblock).unwrap();
+        let api = QrfsApi::open(Arc::new(storage)).unwrap();
+        api.write("/a.txt", b"en hex").unwrap();
+        api.flush().unwrap();
+        drop(api);
+
+        // el disco dice su codec: se abre sin decirle nada
+        let storage = QrStorageManager::new(&dir, BLOCK_SIZE, 48).with_decode_cache(false);
+        assert_eq!(storage.codec_id(), 200);
+        let text = decode_qr_text(&image::open(storage.block_path(0)).unwrap()).unwrap();
+        assert!(text.starts_with("HEX:0:"), "{}", text);
+        let api = QrfsApi::open(Arc::new(storage)).unwrap();
+        assert_eq!(api.read("/a.txt").unwrap(), b"en hex");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn binary_codec_rejects_short_and_foreign_text() {
+        assert!(BinaryCodec.decode("QRFS2:AAAA").is_err());
+        assert!(BinaryCodec.decode(&JsonCodec.encode(1, 1, b"x")).is_err());
+        assert!(JsonCodec.decode(&BinaryCodec.encode(1, 1, b"x")).is_err());
+    }
+}
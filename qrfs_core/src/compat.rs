@@ -0,0 +1,218 @@
+// discos de referencia: uno chico por cada version de formato (sobre del qr,
+// inodos y directorios) guardado en fixtures/compat. compat_check.qrfs los
+// abre con el codigo de hoy y compara lo que lee con lo que se grabo, asi un
+// cambio que deje sin leer el papel viejo salta antes de salir.
+//
+// los discos se arman una sola vez (compat_check.qrfs --write) y despues no
+// se tocan: un formato nuevo es una entrada nueva en FIXTURES
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use base64::{engine::general_purpose, Engine as _};
+
+use crate::api::QrfsApi;
+use crate::codec::{BinaryCodec, BlockCodec};
+use crate::decode_cache::DECODE_CACHE_FILE;
+use crate::disk::{Superblock, BLOCK_SIZE};
+use crate::errors::QrfsError;
+use crate::fs_format::format_storage;
+use crate::qr::{decode_qr_text, encode_text, BlockEnvelope, DecodedBlock, EnvelopeVersion};
+use crate::storage::QrStorageManager;
+
+// relativo a la raiz del repositorio
+pub const FIXTURES_DIR: &str = "fixtures/compat";
+pub const FIXTURE_BLOCKS: u32 = 48;
+const FIXTURE_INODES: u32 = 16;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Fixture {
+    pub name: &'static str,
+    pub envelope: EnvelopeVersion,
+    pub inode_format: u32,
+    pub dir_format: u32,
+}
+
+pub const FIXTURES: &[Fixture] = &[
+    Fixture { name: "v0-base64", envelope: EnvelopeVersion::Raw, inode_format: 0, dir_format: 0 },
+    Fixture { name: "v1-json", envelope: EnvelopeVersion::Json, inode_format: 1, dir_format: 0 },
+    Fixture { name: "v2-json-crc", envelope: EnvelopeVersion::JsonChecked, inode_format: 2, dir_format: 1 },
+    Fixture { name: "v3-binary", envelope: EnvelopeVersion::Binary, inode_format: 3, dir_format: 1 },
+    Fixture { name: "v4-json-crc", envelope: EnvelopeVersion::JsonChecked, inode_format: 4, dir_format: 1 },
+];
+
+impl Fixture {
+    pub fn describe(&self) -> String {
+        format!(
+            "sobre {:?}, inodos v{}, directorios v{}",
+            self.envelope, self.inode_format, self.dir_format
+        )
+    }
+}
+
+// lo que tiene cada disco de referencia: un archivo de un bloque, uno de
+// varios y uno vacio
+pub fn fixture_files() -> Vec<(&'static str, Vec<u8>)> {
+    vec![
+        ("hola.txt", b"hola desde el papel\n".to_vec()),
+        ("tabla.bin", (0..300u32).map(|i| (i * 7 % 251) as u8).collect()),
+        ("vacio.txt", Vec::new()),
+    ]
+}
+
+// el texto del qr de un bloque tal como lo escribia esa version
+pub fn envelope_text(envelope: EnvelopeVersion, id: u32, gen: u64, data: &[u8]) -> String {
+    match envelope {
+        EnvelopeVersion::Raw => general_purpose::STANDARD.encode(data),
+        EnvelopeVersion::Json => serde_json::json!({
+            "block_id": id,
+            "data": general_purpose::STANDARD.encode(data),
+        })
+        .to_string(),
+        EnvelopeVersion::JsonChecked => BlockEnvelope::new(id, gen, data).to_json(),
+        EnvelopeVersion::Binary => BinaryCodec.encode(id, gen, data),
+    }
+}
+
+// arma un disco de referencia en dir (que no tiene que existir): formato
+// viejo en el superblock, los archivos con el codigo de hoy y despues cada
+// qr redibujado con el sobre de esa version
+pub fn build_fixture(dir: &Path, fixture: &Fixture) -> Result<(), QrfsError> {
+    let storage = Arc::new(QrStorageManager::new(dir, BLOCK_SIZE, FIXTURE_BLOCKS));
+    let mut superblock = Superblock::new(FIXTURE_BLOCKS, FIXTURE_INODES);
+    superblock.inode_format = fixture.inode_format;
+    superblock.dir_format = fixture.dir_format;
+    superblock.deterministic = true;
+    storage.init_empty_blocks()?;
+    format_storage(storage.as_ref(), &superblock)?;
+
+    let api = QrfsApi::open(storage.clone())?;
+    for (name, data) in fixture_files() {
+        api.write(&format!("/{}", name), &data)?;
+    }
+    api.flush()?;
+    drop(api);
+
+    for id in 0..FIXTURE_BLOCKS {
+        let Some(data) = storage.read_payload(id)? else {
+            continue;
+        };
+        let gen = storage.generation(id).unwrap_or(0);
+        let text = envelope_text(fixture.envelope, id, gen, &data);
+        encode_text(&text, storage.qr_params())?
+            .save(storage.block_path(id))
+            .map_err(|e| QrfsError::Other(format!("error guardando imagen: {}", e)))?;
+    }
+    // la cache de decodificacion se guarda al soltar el storage; en el disco
+    // de referencia solo van los qr
+    drop(storage);
+    let _ = fs::remove_file(dir.join(DECODE_CACHE_FILE));
+    Ok(())
+}
+
+// lo que no coincide entre el disco de referencia y lo que se espera; vacio
+// si se lee entero. se trabaja sobre una copia: abrir el disco puede
+// escribir y los de referencia no se tocan
+pub fn check_fixture(dir: &Path, fixture: &Fixture) -> Result<Vec<String>, QrfsError> {
+    if !dir.is_dir() {
+        return Ok(vec![format!("falta el disco de referencia '{}'", dir.display())]);
+    }
+    let copy = scratch_copy(dir, fixture.name)?;
+    let problems = check_copy(&copy, fixture);
+    let _ = fs::remove_dir_all(&copy);
+    problems
+}
+
+fn check_copy(dir: &Path, fixture: &Fixture) -> Result<Vec<String>, QrfsError> {
+    let mut problems = Vec::new();
+    let Some(superblock) = QrStorageManager::probe_superblock(dir)? else {
+        return Ok(vec!["no se lee el superblock".to_string()]);
+    };
+    if (superblock.inode_format, superblock.dir_format) != (fixture.inode_format, fixture.dir_format) {
+        problems.push(format!(
+            "el superblock dice inodos v{} y directorios v{}",
+            superblock.inode_format, superblock.dir_format
+        ));
+    }
+
+    let storage = QrStorageManager::new(dir, BLOCK_SIZE, superblock.total_blocks);
+    for id in 0..superblock.total_blocks {
+        let path = storage.block_path(id);
+        if !path.exists() {
+            continue;
+        }
+        let decoded = image::open(&path)
+            .map_err(|e| QrfsError::Other(e.to_string()))
+            .and_then(|img| decode_qr_text(&img))
+            .and_then(|text| DecodedBlock::from_text(&text));
+        match decoded {
+            Ok(block) if block.envelope != fixture.envelope => {
+                problems.push(format!("el bloque {} se lee como sobre {:?}", id, block.envelope))
+            }
+            Ok(block) if !block.checksum_ok || !block.is_block(id) => {
+                problems.push(format!("el bloque {} no pasa el crc o dice ser de otro bloque", id))
+            }
+            Ok(_) => {}
+            Err(e) => problems.push(format!("el bloque {} no se decodifica: {}", id, e)),
+        }
+    }
+
+    let api = match QrfsApi::open(Arc::new(storage)) {
+        Ok(api) => api,
+        Err(e) => {
+            problems.push(format!("no se monta: {}", e));
+            return Ok(problems);
+        }
+    };
+    let expected = fixture_files();
+    let mut listed: Vec<String> = api.list("/")?.into_iter().map(|info| info.name).collect();
+    listed.sort();
+    let names: Vec<&str> = expected.iter().map(|(name, _)| *name).collect();
+    if listed != names {
+        problems.push(format!("la raiz tiene {:?} y se esperaba {:?}", listed, names));
+    }
+    for (name, data) in expected {
+        match api.read(&format!("/{}", name)) {
+            Ok(read) if read == data => {}
+            Ok(read) => problems.push(format!("{} tiene {} bytes distintos de los grabados", name, read.len())),
+            Err(e) => problems.push(format!("{} no se lee: {}", name, e)),
+        }
+    }
+    Ok(problems)
+}
+
+fn scratch_copy(dir: &Path, name: &str) -> Result<PathBuf, QrfsError> {
+    let copy = std::env::temp_dir().join(format!("qrfs_compat_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&copy);
+    fs::create_dir_all(&copy)?;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        // solo los qr: que se decodifiquen de verdad
+        if path.extension().is_some_and(|ext| ext == "png") {
+            fs::copy(&path, copy.join(path.file_name().unwrap()))?;
+        }
+    }
+    Ok(copy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_fixture_reads_back_and_a_changed_envelope_is_reported() {
+        let dir = std::env::temp_dir().join(format!("qrfs_compat_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let fixture = FIXTURES[0];
+        build_fixture(&dir, &fixture).unwrap();
+        assert_eq!(check_fixture(&dir, &fixture).unwrap(), Vec::<String>::new());
+
+        // el mismo disco no pasa por uno con otro sobre
+        let other = Fixture { envelope: EnvelopeVersion::Binary, ..fixture };
+        let problems = check_fixture(&dir, &other).unwrap();
+        assert!(problems.iter().any(|p| p.contains("sobre Raw")), "{:?}", problems);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,576 @@
+// perfil del filesystem (qrfs.toml)
+// archivo opcional en la carpeta del disco (o pasado con --config) con la
+// geometria, el backend, los parametros de los qr, los caches y el servidor.
+// mkfs, mount, fsck, extract y el servidor lo leen igual; las banderas de la
+// linea de comandos ganan sobre el archivo, y el superblock de un disco ya
+// formateado gana sobre los dos (la geometria no se puede cambiar despues).
+//
+// lo lee el crate toml; una seccion o clave desconocida es un error (un typo
+// no tiene que pasar callado)
+//
+//   [disk]
+//   block_size = 128
+//   total_blocks = 400
+//   inodes = 64
+//   backend = "qr"          # o "container"
+//   shard = 0
+//   cas = false
+//   pack_tails = false      # colas de archivos chicos en bloques compartidos
+//   pinned = 0              # bloques de la zona fija al principio del disco
+//
+//   [qr]
+//   size = 200              # lado maximo de cada png en pixeles
+//   ec_level = "M"          # L, M, Q o H
+//
+//   [cache]
+//   decode = true
+//   dirty_blocks = 256
+//
+//   [mount]
+//   writeback = true
+//
+//   [retry]                 # errores pasajeros del backend (EINTR, red)
+//   attempts = 1            # intentos en total, 1 = sin reintentos
+//   backoff_ms = 50         # espera antes del primer reintento, se duplica
+//   timeout_ms = 0          # por operacion, 0 = sin limite (no en carpetas qr)
+//
+//   [server]
+//   bind = "127.0.0.1"      # "0.0.0.0" para escanear desde el celular
+//   port = 8080
+//   rate_limit = 20
+//   data_dir = "discos"     # POST /disks solo abre carpetas de aca adentro
+//   mount_root = "montajes" # y POST /mount solo monta aca adentro
+//   token = "..."           # Authorization: Bearer para POST /sync/pull
+//   peers = ["http://10.0.0.2:8080"]  # los unicos servidores de los que se hace pull
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use qrcode::EcLevel;
+use serde::Deserialize;
+
+use crate::disk::{BLOCK_SIZE, DEFAULT_INODE_COUNT, DEFAULT_TOTAL_BLOCKS};
+use crate::errors::QrfsError;
+use crate::estimate::{block_qr_modules, estimate};
+use crate::retry::RetryPolicy;
+use crate::storage::QrStorageManager;
+
+pub const CONFIG_FILE: &str = "qrfs.toml";
+
+// el bloque mas chico con el que se puede formatear
+pub const MIN_BLOCK_SIZE: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QrfsConfig {
+    pub block_size: usize,
+    pub total_blocks: u32,
+    pub inode_count: u32,
+    // guardar los bloques crudos en un archivo en vez de pngs
+    pub container: bool,
+    pub shard_size: u32,
+    pub content_addressed: bool,
+    // colas de los archivos chicos en bloques compartidos (ver tail.rs)
+    pub pack_tails: bool,
+    // bloques al principio del area de datos para la raiz, la tabla de
+    // inodos y los archivos fijos (0 = sin zona fija)
+    pub pinned_blocks: u32,
+
+    pub qr_size: u32,
+    pub qr_ec_level: EcLevel,
+
+    pub decode_cache: bool,
+    pub dirty_blocks: usize,
+
+    pub writeback: bool,
+
+    pub retry: RetryPolicy,
+
+    pub server_bind: String,
+    pub server_port: u16,
+    pub rate_limit: u32,
+    // carpeta de la que cuelgan los discos que se registran por la red
+    // (None = la carpeta que contiene al disco del arranque)
+    pub server_data_dir: Option<String>,
+    // carpeta de los puntos de montaje de POST /mount (None = data_dir)
+    pub server_mount_root: Option<String>,
+    // sin token no hay POST /sync/pull; con token solo hacia peers
+    pub server_token: Option<String>,
+    pub server_peers: Vec<String>,
+}
+
+impl Default for QrfsConfig {
+    fn default() -> Self {
+        Self {
+            block_size: BLOCK_SIZE,
+            total_blocks: DEFAULT_TOTAL_BLOCKS,
+            inode_count: DEFAULT_INODE_COUNT,
+            container: false,
+            shard_size: 0,
+            content_addressed: false,
+            pack_tails: false,
+            pinned_blocks: 0,
+            qr_size: 200,
+            qr_ec_level: EcLevel::M,
+            decode_cache: true,
+            dirty_blocks: 256,
+            writeback: true,
+            retry: RetryPolicy::once(),
+            // solo esta maquina; la red hay que pedirla
+            server_bind: "127.0.0.1".to_string(),
+            server_port: 8080,
+            rate_limit: 20,
+            server_data_dir: None,
+            server_mount_root: None,
+            server_token: None,
+            server_peers: Vec::new(),
+        }
+    }
+}
+
+// plantillas de mkfs (--profile): combinaciones pensadas para un uso. se
+// aplican sobre el qrfs.toml y las banderas de mkfs las pisan; el superblock
+// guarda cual se uso
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiskProfile {
+    // papel que se guarda años: la correccion de errores mas alta, qr grandes,
+    // la metadata junta al principio y colas empaquetadas (menos hojas)
+    Archive,
+    // pasar el disco por pantalla o camara: qr medianos que se escanean rapido
+    Transfer,
+    // unos pocos archivos chicos en un punado de hojas
+    Tiny,
+}
+
+impl DiskProfile {
+    pub const ALL: [DiskProfile; 3] = [DiskProfile::Archive, DiskProfile::Transfer, DiskProfile::Tiny];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DiskProfile::Archive => "archive",
+            DiskProfile::Transfer => "transfer",
+            DiskProfile::Tiny => "tiny",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, QrfsError> {
+        Self::ALL
+            .into_iter()
+            .find(|profile| profile.name() == name)
+            .ok_or_else(|| QrfsError::Other(format!("perfil '{}' desconocido (archive, transfer o tiny)", name)))
+    }
+
+    // lo que se guarda en Superblock::profile
+    pub fn id(self) -> u8 {
+        match self {
+            DiskProfile::Archive => 1,
+            DiskProfile::Transfer => 2,
+            DiskProfile::Tiny => 3,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|profile| profile.id() == id)
+    }
+}
+
+// cuanta correccion de errores pide el que arma el disco (mkfs --interactive)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Redundancy {
+    Low,
+    Normal,
+    High,
+}
+
+// lo que pregunta mkfs --interactive
+#[derive(Debug, Clone, Copy)]
+pub struct DiskNeeds {
+    pub data_bytes: u64,
+    // en papel o de pantalla a camara
+    pub printed: bool,
+    pub redundancy: Redundancy,
+}
+
+// el archivo tal cual viene; lo que falta queda en el valor por defecto
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    disk: DiskSection,
+    qr: QrSection,
+    cache: CacheSection,
+    mount: MountSection,
+    retry: RetrySection,
+    server: ServerSection,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct DiskSection {
+    block_size: Option<usize>,
+    total_blocks: Option<u32>,
+    inodes: Option<u32>,
+    backend: Option<Backend>,
+    shard: Option<u32>,
+    cas: Option<bool>,
+    pack_tails: Option<bool>,
+    pinned: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Backend {
+    Qr,
+    Container,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct QrSection {
+    size: Option<u32>,
+    ec_level: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct CacheSection {
+    decode: Option<bool>,
+    dirty_blocks: Option<usize>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct MountSection {
+    writeback: Option<bool>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct RetrySection {
+    attempts: Option<u32>,
+    backoff_ms: Option<u64>,
+    timeout_ms: Option<u64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ServerSection {
+    bind: Option<String>,
+    port: Option<u16>,
+    rate_limit: Option<u32>,
+    data_dir: Option<String>,
+    mount_root: Option<String>,
+    token: Option<String>,
+    peers: Option<Vec<String>>,
+}
+
+impl QrfsConfig {
+    pub fn parse(text: &str) -> Result<Self, QrfsError> {
+        let file: ConfigFile = toml::from_str(text).map_err(|e| {
+            // el mensaje de toml trae el pedazo de archivo; alcanza con la linea
+            let line = e.span().map_or(1, |span| text[..span.start].matches('\n').count() + 1);
+            QrfsError::Other(format!("{} linea {}: {}", CONFIG_FILE, line, e.message()))
+        })?;
+
+        let mut config = Self::default();
+        let disk = file.disk;
+        config.block_size = disk.block_size.unwrap_or(config.block_size);
+        config.total_blocks = disk.total_blocks.unwrap_or(config.total_blocks);
+        config.inode_count = disk.inodes.unwrap_or(config.inode_count);
+        if let Some(backend) = disk.backend {
+            config.container = matches!(backend, Backend::Container);
+        }
+        config.shard_size = disk.shard.unwrap_or(config.shard_size);
+        config.content_addressed = disk.cas.unwrap_or(config.content_addressed);
+        config.pack_tails = disk.pack_tails.unwrap_or(config.pack_tails);
+        config.pinned_blocks = disk.pinned.unwrap_or(config.pinned_blocks);
+
+        config.qr_size = file.qr.size.unwrap_or(config.qr_size);
+        if let Some(name) = file.qr.ec_level {
+            config.qr_ec_level =
+                parse_ec_level(&name).map_err(|e| QrfsError::Other(format!("{}: {}", CONFIG_FILE, e)))?;
+        }
+
+        config.decode_cache = file.cache.decode.unwrap_or(config.decode_cache);
+        config.dirty_blocks = file.cache.dirty_blocks.unwrap_or(config.dirty_blocks);
+        config.writeback = file.mount.writeback.unwrap_or(config.writeback);
+
+        config.retry.attempts = file.retry.attempts.unwrap_or(config.retry.attempts);
+        if let Some(ms) = file.retry.backoff_ms {
+            config.retry.backoff = Duration::from_millis(ms);
+        }
+        if let Some(ms) = file.retry.timeout_ms {
+            config.retry.timeout = (ms > 0).then(|| Duration::from_millis(ms));
+        }
+
+        config.server_bind = file.server.bind.unwrap_or(config.server_bind);
+        config.server_port = file.server.port.unwrap_or(config.server_port);
+        config.rate_limit = file.server.rate_limit.unwrap_or(config.rate_limit);
+        config.server_data_dir = file.server.data_dir.or(config.server_data_dir);
+        config.server_mount_root = file.server.mount_root.or(config.server_mount_root);
+        config.server_token = file.server.token.or(config.server_token);
+        config.server_peers = file.server.peers.unwrap_or(config.server_peers);
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    // valores que el resto del codigo no soporta. mkfs lo vuelve a llamar
+    // despues de aplicar sus banderas
+    pub fn validate(&self) -> Result<(), QrfsError> {
+        // el tamaño de bloque queda en el superblock y la capa de archivos lo
+        // toma de ahi; tiene que dejar lugar a los encabezados de la tabla de
+        // inodos y los directorios, y en la carpeta qr un bloque lleno tiene
+        // que entrar en un solo qr (un contenedor no tiene ese limite)
+        if self.block_size < MIN_BLOCK_SIZE {
+            return Err(QrfsError::Other(format!(
+                "{}: block_size {} es muy chico (minimo {})",
+                CONFIG_FILE, self.block_size, MIN_BLOCK_SIZE
+            )));
+        }
+        if !self.container && block_qr_modules(self.total_blocks, self.block_size, self.qr_ec_level) == 0 {
+            return Err(QrfsError::Other(format!(
+                "{}: un bloque de {} bytes no entra en un qr con correccion {:?}",
+                CONFIG_FILE, self.block_size, self.qr_ec_level
+            )));
+        }
+        if self.retry.attempts == 0 {
+            return Err(QrfsError::Other(format!(
+                "{}: attempts tiene que ser al menos 1",
+                CONFIG_FILE
+            )));
+        }
+        if self.server_token.as_deref() == Some("") {
+            return Err(QrfsError::Other(format!("{}: token no puede estar vacio", CONFIG_FILE)));
+        }
+        if self.qr_size < 21 {
+            return Err(QrfsError::Other(format!(
+                "{}: size {} es muy chico para un qr",
+                CONFIG_FILE, self.qr_size
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, QrfsError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| QrfsError::Other(format!("no se pudo leer '{}': {}", path.display(), e)))?;
+        Self::parse(&text)
+    }
+
+    // el --config explicito, o el qrfs.toml de la carpeta del disco, o los
+    // valores por defecto. un contenedor no tiene carpeta propia: solo --config
+    pub fn for_disk(disk: impl AsRef<Path>, explicit: Option<&str>) -> Result<Self, QrfsError> {
+        if let Some(path) = explicit {
+            return Self::load(path);
+        }
+        let candidate = disk.as_ref().join(CONFIG_FILE);
+        if disk.as_ref().is_dir() && candidate.is_file() {
+            return Self::load(candidate);
+        }
+        Ok(Self::default())
+    }
+
+    // pisa lo que la plantilla elige; el resto (backend, caches, servidor)
+    // queda como estaba
+    pub fn apply_profile(&mut self, profile: DiskProfile) {
+        match profile {
+            DiskProfile::Archive => {
+                self.qr_ec_level = EcLevel::H;
+                self.qr_size = 300;
+                self.inode_count = DEFAULT_INODE_COUNT;
+                self.pack_tails = true;
+                self.pinned_blocks = 32;
+            }
+            DiskProfile::Transfer => {
+                self.qr_ec_level = EcLevel::M;
+                self.qr_size = 200;
+                self.inode_count = 32;
+                self.pack_tails = false;
+                self.pinned_blocks = 0;
+            }
+            DiskProfile::Tiny => {
+                self.total_blocks = 64;
+                self.qr_ec_level = EcLevel::L;
+                self.qr_size = 120;
+                self.inode_count = 16;
+                self.pack_tails = true;
+                self.pinned_blocks = 0;
+            }
+        }
+    }
+
+    // arma el disco para lo que se quiere guardar: la plantilla de papel o de
+    // pantalla, y encima bloques, inodos (ver estimate.rs) y correccion. al
+    // papel le toca un nivel mas de correccion que a la pantalla (se mancha,
+    // se dobla)
+    pub fn plan_for(&mut self, needs: &DiskNeeds) -> DiskProfile {
+        let profile = if needs.printed { DiskProfile::Archive } else { DiskProfile::Transfer };
+        self.apply_profile(profile);
+        self.qr_ec_level = match (needs.printed, needs.redundancy) {
+            (false, Redundancy::Low) => EcLevel::L,
+            (false, Redundancy::Normal) | (true, Redundancy::Low) => EcLevel::M,
+            (false, Redundancy::High) | (true, Redundancy::Normal) => EcLevel::Q,
+            (true, Redundancy::High) => EcLevel::H,
+        };
+
+        let sized = estimate(needs.data_bytes, self.block_size, self.qr_ec_level, self.pinned_blocks, 1);
+        self.inode_count = sized.inode_count;
+        self.total_blocks = sized.total_blocks;
+        profile
+    }
+
+    // la seccion [qr] en toml: mkfs la deja en la carpeta del disco para que
+    // los qr que se escriban despues salgan igual que los de la plantilla
+    pub fn qr_section(&self) -> String {
+        format!("[qr]\nsize = {}\nec_level = \"{:?}\"\n", self.qr_size, self.qr_ec_level)
+    }
+
+    // almacenamiento qr con los parametros del perfil; si la carpeta ya tiene
+    // superblock la geometria sale de ahi
+    pub fn qr_storage(&self, root_dir: &str) -> QrStorageManager {
+        let (block_size, total_blocks) = match QrStorageManager::probe_superblock(root_dir) {
+            Ok(Some(sb)) => (sb.block_size as usize, sb.total_blocks),
+            _ => (self.block_size, self.total_blocks),
+        };
+        self.configure(QrStorageManager::new(root_dir, block_size, total_blocks))
+    }
+
+    // las opciones del perfil sobre una carpeta ya abierta (los volumenes de
+    // un disco repartido, ver volume.rs)
+    pub fn configure(&self, storage: QrStorageManager) -> QrStorageManager {
+        storage
+            .with_qr_options(self.qr_size, self.qr_ec_level)
+            .with_decode_cache(self.decode_cache)
+            .with_retry(self.retry)
+    }
+}
+
+// busca --config <archivo> entre los argumentos
+pub fn config_arg(args: &[String]) -> Option<&str> {
+    args.iter()
+        .position(|a| a == "--config")
+        .and_then(|i| args.get(i + 1))
+        .map(|s| s.as_str())
+}
+
+// nivel de correccion de errores de los qr ([qr] ec_level, mkfs --ec-level)
+pub fn parse_ec_level(name: &str) -> Result<EcLevel, String> {
+    match name.to_uppercase().as_str() {
+        "L" => Ok(EcLevel::L),
+        "M" => Ok(EcLevel::M),
+        "Q" => Ok(EcLevel::Q),
+        "H" => Ok(EcLevel::H),
+        other => Err(format!("ec_level '{}' desconocido (L, M, Q o H)", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::Superblock;
+
+    #[test]
+    fn the_plan_fits_the_data_beside_the_metadata() {
+        let mut config = QrfsConfig::default();
+        let needs = DiskNeeds { data_bytes: 20_000, printed: true, redundancy: Redundancy::High };
+        assert_eq!(config.plan_for(&needs), DiskProfile::Archive);
+        assert_eq!(config.qr_ec_level, EcLevel::H);
+        let superblock = Superblock::new(config.total_blocks, config.inode_count);
+        let data_blocks = config.total_blocks - superblock.data_block_start - config.pinned_blocks;
+        assert!(data_blocks as u64 * BLOCK_SIZE as u64 >= 20_000);
+
+        let screen = DiskNeeds { printed: false, redundancy: Redundancy::Low, ..needs };
+        assert_eq!(config.plan_for(&screen), DiskProfile::Transfer);
+        assert_eq!((config.qr_ec_level, config.pinned_blocks), (EcLevel::L, 0));
+    }
+
+    #[test]
+    fn a_profile_survives_the_round_trip_through_the_superblock_id_and_toml() {
+        for profile in DiskProfile::ALL {
+            assert_eq!(DiskProfile::parse(profile.name()).unwrap(), profile);
+            assert_eq!(DiskProfile::from_id(profile.id()), Some(profile));
+        }
+        assert_eq!(DiskProfile::from_id(0), None);
+        assert!(DiskProfile::parse("enorme").is_err());
+
+        let mut config = QrfsConfig::default();
+        config.apply_profile(DiskProfile::Archive);
+        assert_eq!(config.qr_ec_level, EcLevel::H);
+        let written = QrfsConfig::parse(&config.qr_section()).unwrap();
+        assert_eq!((written.qr_size, written.qr_ec_level), (300, EcLevel::H));
+    }
+
+    #[test]
+    fn parses_every_section_and_keeps_defaults_for_the_rest() {
+        let config = QrfsConfig::parse(
+            r#"
+            # perfil de prueba
+            [disk]
+            total_blocks = 1_000
+            backend = "container"   # sin pngs
+            cas = true
+            pack_tails = true
+            pinned = 16
+
+            [qr]
+            ec_level = "h"
+
+            [retry]
+            attempts = 3
+            timeout_ms = 2000
+
+            [server]
+            port = 9090
+            bind = "0.0.0.0"
+            data_dir = "/srv/qrfs"
+            peers = ["http://10.0.0.2:8080/"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.total_blocks, 1000);
+        assert!(config.container);
+        assert!(config.content_addressed);
+        assert!(config.pack_tails);
+        assert_eq!(config.pinned_blocks, 16);
+        assert_eq!(config.qr_ec_level, EcLevel::H);
+        assert_eq!(config.server_port, 9090);
+        assert_eq!(config.server_bind, "0.0.0.0");
+        assert_eq!(config.server_data_dir.as_deref(), Some("/srv/qrfs"));
+        assert_eq!(config.server_peers, ["http://10.0.0.2:8080/"]);
+        assert_eq!(config.server_token, None);
+        assert_eq!(config.block_size, BLOCK_SIZE);
+        assert_eq!(config.qr_size, 200);
+        assert!(config.writeback);
+        assert_eq!(config.retry.attempts, 3);
+        assert_eq!(config.retry.backoff, Duration::from_millis(50));
+        assert_eq!(config.retry.timeout, Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn rejects_typos_bad_values_and_unsupported_geometry() {
+        let bad = [
+            "[disk]\ntotal_bloks = 10",
+            "[disco]",
+            "total_blocks = 10",
+            "[disk]\ncas = 1",
+            "[disk]\nbackend = \"cinta\"",
+            "[disk]\nblock_size = 16",
+            "[disk]\nblock_size = 4096",
+            "[server]\nport = 70000",
+            "[retry]\nattempts = 0",
+        ];
+        for text in bad {
+            assert!(QrfsConfig::parse(text).is_err(), "{}", text);
+        }
+
+        // el tamaño de bloque lo elige el perfil; grande solo si entra en un qr
+        assert_eq!(QrfsConfig::parse("[disk]\nblock_size = 256").unwrap().block_size, 256);
+        let container = QrfsConfig::parse("[disk]\nblock_size = 4096\nbackend = \"container\"").unwrap();
+        assert_eq!(container.block_size, 4096);
+
+        let err = QrfsConfig::parse("[disk]\n\ntotal_bloks = 10").unwrap_err();
+        assert!(err.to_string().contains("linea 3"), "{}", err);
+    }
+}
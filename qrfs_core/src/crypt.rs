@@ -0,0 +1,326 @@
+// cifrado por archivo: cada archivo regular nuevo recibe su propia clave (32
+// bytes al azar) que va en el inodo envuelta con la clave maestra, y la
+// maestra va en el superblock envuelta con una clave derivada de la frase
+// (pbkdf2). para compartir un archivo alcanza con sus qr y su clave (FileKey),
+// sin la frase ni el resto del disco
+//
+// todo va con aead de rustcrypto y un nonce al azar en cada escritura: los
+// bloques de datos con xchacha20-poly1305 y las claves envueltas con
+// chacha20-poly1305 (nonce de 12 bytes, asi las dos ranuras de la maestra
+// entran en los bytes reservados del superblock; una maestra envuelve una
+// clave por archivo, lejos de donde un nonce de 96 bits al azar se repite).
+// el bloque guarda nonce + texto cifrado + etiqueta, asi que un archivo
+// cifrado lleva BLOCK_OVERHEAD bytes menos de datos por bloque. la posicion
+// del bloque en el archivo va autenticada: mover bloques (desfragmentar,
+// deduplicar) no obliga a recifrar, pero cambiar uno de lugar dentro del
+// archivo no abre. se cifran todos los bloques, tambien los que son ceros
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce, XChaCha20Poly1305, XNonce};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::errors::QrfsError;
+
+pub const KEY_LEN: usize = 32;
+// nonce + clave cifrada + etiqueta
+pub const WRAPPED_KEY_LEN: usize = WRAP_NONCE_LEN + KEY_LEN + TAG_LEN;
+// iteraciones de pbkdf2 para las frases nuevas
+pub const PBKDF2_ITERATIONS: u32 = 100_000;
+// prefijo del texto con la clave de un archivo compartido
+pub const FILE_KEY_PREFIX: &str = "qrfs-key:";
+// lo que el sellado le agrega a cada bloque cifrado
+pub const BLOCK_OVERHEAD: usize = NONCE_LEN + TAG_LEN;
+
+const NONCE_LEN: usize = 24;
+const WRAP_NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const SALT_LEN: usize = 16;
+// iteraciones (u32) + sal + maestra envuelta
+pub const KEY_SLOT_LEN: usize = 4 + SALT_LEN + WRAPPED_KEY_LEN;
+
+pub type Key = [u8; KEY_LEN];
+
+// hmac-sha256 de la concatenacion de parts
+pub fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("hmac acepta claves de cualquier largo");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+// pbkdf2-hmac-sha256 (rfc 8018) con una clave de salida de 32 bytes
+pub fn pbkdf2_sha256(passphrase: &[u8], salt: &[u8], iterations: u32) -> Key {
+    pbkdf2::pbkdf2_hmac_array::<Sha256, KEY_LEN>(passphrase, salt, iterations)
+}
+
+// bytes de datos de un bloque de un archivo (cifrado o en claro)
+pub fn payload_len(block_size: usize, encrypted: bool) -> usize {
+    if encrypted {
+        block_size.saturating_sub(BLOCK_OVERHEAD)
+    } else {
+        block_size
+    }
+}
+
+pub(crate) fn random_bytes(buf: &mut [u8]) -> Result<(), QrfsError> {
+    getrandom::fill(buf)
+        .map_err(|e| QrfsError::Other(format!("no se pudieron generar bytes al azar: {}", e)))
+}
+
+// semilla de los discos deterministas (mkfs --deterministic); sin ella se usa
+// la vacia
+pub const SEED_ENV: &str = "QRFS_SEED";
+
+// lo que en un disco normal se sortea: en uno determinista sale de la semilla
+// y de para que se usa, asi dos corridas con la misma semilla coinciden
+pub(crate) fn disk_bytes(deterministic: bool, purpose: &str, buf: &mut [u8]) -> Result<(), QrfsError> {
+    if !deterministic {
+        return random_bytes(buf);
+    }
+    let seed = std::env::var(SEED_ENV).unwrap_or_default();
+    for (counter, chunk) in buf.chunks_mut(32).enumerate() {
+        let digest = hmac_sha256(seed.as_bytes(), &[purpose.as_bytes(), &(counter as u32).to_le_bytes()]);
+        chunk.copy_from_slice(&digest[..chunk.len()]);
+    }
+    Ok(())
+}
+
+pub fn random_key() -> Result<Key, QrfsError> {
+    let mut key = [0u8; KEY_LEN];
+    random_bytes(&mut key)?;
+    Ok(key)
+}
+
+// nonce al azar + texto cifrado con etiqueta; aad liga el bloque a su
+// posicion en el archivo
+fn seal_block(key: &Key, aad: &[u8], plain: &[u8]) -> Result<Vec<u8>, QrfsError> {
+    let mut nonce = [0u8; NONCE_LEN];
+    random_bytes(&mut nonce)?;
+    let sealed = XChaCha20Poly1305::new(key.into())
+        .encrypt(XNonce::from_slice(&nonce), Payload { msg: plain, aad })
+        .map_err(|_| QrfsError::Other("no se pudo cifrar el bloque".into()))?;
+    Ok([&nonce[..], &sealed].concat())
+}
+
+// la etiqueta se compara en tiempo constante dentro de poly1305
+fn open_block(key: &Key, aad: &[u8], sealed: &[u8]) -> Option<Vec<u8>> {
+    if sealed.len() < NONCE_LEN + TAG_LEN {
+        return None;
+    }
+    let (nonce, rest) = sealed.split_at(NONCE_LEN);
+    XChaCha20Poly1305::new(key.into())
+        .decrypt(XNonce::from_slice(nonce), Payload { msg: rest, aad })
+        .ok()
+}
+
+// envuelve una clave con otra; una clave equivocada o bytes cambiados no abren
+pub fn wrap_key(kek: &Key, key: &Key) -> Result<Vec<u8>, QrfsError> {
+    let mut nonce = [0u8; WRAP_NONCE_LEN];
+    random_bytes(&mut nonce)?;
+    let sealed = ChaCha20Poly1305::new(kek.into())
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: key, aad: b"qrfs-envolver" })
+        .map_err(|_| QrfsError::Other("no se pudo envolver la clave".into()))?;
+    Ok([&nonce[..], &sealed].concat())
+}
+
+pub fn unwrap_key(kek: &Key, wrapped: &[u8]) -> Result<Key, QrfsError> {
+    if wrapped.len() != WRAPPED_KEY_LEN {
+        return Err(QrfsError::Corrupt(format!(
+            "clave envuelta de {} bytes (se esperaban {})",
+            wrapped.len(),
+            WRAPPED_KEY_LEN
+        )));
+    }
+    let (nonce, sealed) = wrapped.split_at(WRAP_NONCE_LEN);
+    // la etiqueta se compara en tiempo constante dentro de poly1305
+    ChaCha20Poly1305::new(kek.into())
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: sealed, aad: b"qrfs-envolver" })
+        .ok()
+        .and_then(|key| key.try_into().ok())
+        .ok_or_else(|| QrfsError::NotPermitted("la clave no corresponde".into()))
+}
+
+// lo que va en Superblock::key_slot: iteraciones, sal y la maestra envuelta
+// con la clave que sale de la frase. durante una rotacion (rekey) hay dos
+// ranuras seguidas: la maestra vieja y la nueva
+pub fn seal_master(master: &Key, passphrase: &str, iterations: u32) -> Result<Vec<u8>, QrfsError> {
+    let mut salt = [0u8; SALT_LEN];
+    random_bytes(&mut salt)?;
+    let kek = pbkdf2_sha256(passphrase.as_bytes(), &salt, iterations);
+
+    let mut slot = Vec::with_capacity(KEY_SLOT_LEN);
+    slot.extend_from_slice(&iterations.to_le_bytes());
+    slot.extend_from_slice(&salt);
+    slot.extend(wrap_key(&kek, master)?);
+    Ok(slot)
+}
+
+// ranuras de Superblock::key_slot (una, o dos a mitad de un rekey)
+pub fn key_slots(data: &[u8]) -> Result<Vec<&[u8]>, QrfsError> {
+    if data.is_empty() || !data.len().is_multiple_of(KEY_SLOT_LEN) || data.len() > 2 * KEY_SLOT_LEN {
+        return Err(QrfsError::Corrupt(format!(
+            "ranura de clave de {} bytes (se esperaban {} o {})",
+            data.len(),
+            KEY_SLOT_LEN,
+            2 * KEY_SLOT_LEN
+        )));
+    }
+    Ok(data.chunks(KEY_SLOT_LEN).collect())
+}
+
+// la frase abre alguna de las ranuras (sin abrir el filesystem)
+pub fn check_passphrase(key_slot: &[u8], passphrase: &str) -> Result<(), QrfsError> {
+    let slots = key_slots(key_slot)?;
+    if slots.iter().any(|slot| open_master(slot, passphrase).is_ok()) {
+        Ok(())
+    } else {
+        Err(QrfsError::NotPermitted("frase incorrecta".into()))
+    }
+}
+
+pub fn open_master(slot: &[u8], passphrase: &str) -> Result<Key, QrfsError> {
+    if slot.len() != KEY_SLOT_LEN {
+        return Err(QrfsError::Corrupt(format!(
+            "ranura de clave de {} bytes (se esperaban {})",
+            slot.len(),
+            KEY_SLOT_LEN
+        )));
+    }
+    let iterations = u32::from_le_bytes(slot[..4].try_into().unwrap());
+    let kek = pbkdf2_sha256(passphrase.as_bytes(), &slot[4..4 + SALT_LEN], iterations);
+    unwrap_key(&kek, &slot[4 + SALT_LEN..])
+        .map_err(|_| QrfsError::NotPermitted("frase incorrecta".into()))
+}
+
+fn block_aad(index: u64) -> [u8; 19] {
+    let mut aad = [0u8; 19];
+    aad[..11].copy_from_slice(b"qrfs-bloque");
+    aad[11..].copy_from_slice(&index.to_le_bytes());
+    aad
+}
+
+// cifra el bloque que ocupa la posicion index del archivo: plain mide
+// payload_len(block_size, true) y el resultado block_size
+pub fn encrypt_block(key: &Key, index: u64, plain: &[u8]) -> Result<Vec<u8>, QrfsError> {
+    seal_block(key, &block_aad(index), plain)
+}
+
+pub fn decrypt_block(key: &Key, index: u64, sealed: &[u8]) -> Result<Vec<u8>, QrfsError> {
+    open_block(key, &block_aad(index), sealed).ok_or_else(|| {
+        QrfsError::Corrupt(format!(
+            "el bloque {} del archivo no abre: cambiado, fuera de lugar o de otra clave",
+            index
+        ))
+    })
+}
+
+// clave de un archivo para compartirlo: con esto y sus qr en orden se
+// recupera el contenido. en texto: qrfs-key:<tamaño>:<clave en hex>
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileKey {
+    pub key: Key,
+    pub size: u64,
+}
+
+impl FileKey {
+    pub fn to_text(&self) -> String {
+        let hex: String = self.key.iter().map(|b| format!("{:02x}", b)).collect();
+        format!("{}{}:{}", FILE_KEY_PREFIX, self.size, hex)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, QrfsError> {
+        let invalid = || QrfsError::Other(format!("clave de archivo invalida: '{}'", text.trim()));
+        let body = text.trim().strip_prefix(FILE_KEY_PREFIX).ok_or_else(invalid)?;
+        let (size, hex) = body.split_once(':').ok_or_else(invalid)?;
+        let size = size.parse().map_err(|_| invalid())?;
+        if hex.len() != KEY_LEN * 2 || !hex.is_ascii() {
+            return Err(invalid());
+        }
+        let mut key = [0u8; KEY_LEN];
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+        }
+        Ok(Self { key, size })
+    }
+
+    // contenido a partir de los bloques del archivo en orden (los que se
+    // leyeron de sus qr)
+    pub fn decrypt_blocks(&self, blocks: &[Vec<u8>]) -> Result<Vec<u8>, QrfsError> {
+        let mut out = Vec::new();
+        for (index, block) in blocks.iter().enumerate() {
+            out.extend(decrypt_block(&self.key, index as u64, block)?);
+        }
+        out.truncate(self.size as usize);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn primitives_match_the_reference_vectors() {
+        // rfc 4231, caso 1
+        assert_eq!(
+            hex(&hmac_sha256(&[0x0b; 20], &[b"Hi", b" There"])),
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"
+        );
+        // rfc 7914, 11 (los primeros 32 bytes) y uno con mas de una iteracion
+        assert_eq!(
+            hex(&pbkdf2_sha256(b"passwd", b"salt", 1)),
+            "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc"
+        );
+        assert_eq!(
+            hex(&pbkdf2_sha256(b"password", b"salt", 2)),
+            "ae4d0c95af6b46d32d0adff928f06dd02a303f8ef3c251dfd6e2d85a95474c43"
+        );
+    }
+
+    #[test]
+    fn blocks_are_sealed_per_write_and_only_open_in_place() {
+        let key = random_key().unwrap();
+        let plain: Vec<u8> = (0..88u32).map(|i| (i * 3) as u8).collect();
+
+        let first = encrypt_block(&key, 0, &plain).unwrap();
+        assert_eq!(first.len(), plain.len() + BLOCK_OVERHEAD);
+        assert_eq!(decrypt_block(&key, 0, &first).unwrap(), plain);
+        // reescribir lo mismo no deja el mismo bloque
+        assert_ne!(encrypt_block(&key, 0, &plain).unwrap(), first);
+        // en otra posicion, con otra clave o con un byte cambiado no abre
+        assert!(decrypt_block(&key, 1, &first).is_err());
+        assert!(decrypt_block(&random_key().unwrap(), 0, &first).is_err());
+        let mut tampered = first.clone();
+        tampered[40] ^= 1;
+        assert!(decrypt_block(&key, 0, &tampered).is_err());
+
+        // un bloque en ceros tambien se cifra
+        let zeros = encrypt_block(&key, 5, &[0u8; 88]).unwrap();
+        assert!(zeros.iter().any(|&b| b != 0));
+        assert_eq!(decrypt_block(&key, 5, &zeros).unwrap(), [0u8; 88]);
+    }
+
+    #[test]
+    fn wrapped_keys_only_open_with_the_right_secret() {
+        let master = random_key().unwrap();
+        let file = random_key().unwrap();
+        let wrapped = wrap_key(&master, &file).unwrap();
+        assert_eq!(wrapped.len(), WRAPPED_KEY_LEN);
+        assert_eq!(unwrap_key(&master, &wrapped).unwrap(), file);
+        assert!(unwrap_key(&random_key().unwrap(), &wrapped).is_err());
+
+        let slot = seal_master(&master, "frase", 10).unwrap();
+        assert_eq!(open_master(&slot, "frase").unwrap(), master);
+        assert!(matches!(open_master(&slot, "otra"), Err(QrfsError::NotPermitted(_))));
+
+        let shared = FileKey { key: file, size: 300 };
+        assert_eq!(FileKey::parse(&shared.to_text()).unwrap(), shared);
+        assert!(FileKey::parse("qrfs-key:300:abcd").is_err());
+    }
+}
@@ -0,0 +1,183 @@
+// cache persistente de qr decodificados
+// decodificar un png con rqrr es lo mas caro de leer un bloque; si la imagen no
+// cambio (mismo mtime, tamaño y crc de los bytes) se reutiliza el texto ya
+// decodificado, incluso entre procesos distintos (mount, fsck, qr_extract, server).
+// el crc cubre los pngs reescritos dentro de la resolucion del mtime con el
+// mismo tamaño, y leer el archivo cuesta mucho menos que decodificarlo
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::QrfsError;
+
+// archivo dentro de la carpeta del disco (como .qrfs_ingest.json)
+pub const DECODE_CACHE_FILE: &str = ".qrfs_decode_cache.json";
+
+// cada cuantas entradas nuevas se guarda el archivo (ademas de al cerrar)
+const FLUSH_EVERY: usize = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct CacheEntry {
+    mtime_ns: u64,
+    size: u64,
+    // las caches de antes no lo tienen: esas entradas no coinciden y se rehacen
+    #[serde(default)]
+    crc: u32,
+    text: String,
+}
+
+pub struct DecodeCache {
+    path: PathBuf,
+    // clave: nombre del png (sin carpeta, el disco se puede mover)
+    entries: Mutex<HashMap<String, CacheEntry>>,
+    pending: Mutex<usize>,
+}
+
+impl DecodeCache {
+    // carga la cache de la carpeta del disco; si no existe o esta rota empieza vacia
+    pub fn load(root_dir: &Path) -> Self {
+        let path = root_dir.join(DECODE_CACHE_FILE);
+        let entries = fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+
+        Self {
+            path,
+            entries: Mutex::new(entries),
+            pending: Mutex::new(0),
+        }
+    }
+
+    // texto decodificado de png si la imagen no cambio desde que se guardo
+    pub fn get(&self, png: &Path) -> Option<String> {
+        let stamp = file_stamp(png)?;
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(&key(png)?)
+            .filter(|e| (e.mtime_ns, e.size, e.crc) == stamp)
+            .map(|e| e.text.clone())
+    }
+
+    pub fn insert(&self, png: &Path, text: &str) {
+        let (Some((mtime_ns, size, crc)), Some(key)) = (file_stamp(png), key(png)) else {
+            return;
+        };
+        let entry = CacheEntry {
+            mtime_ns,
+            size,
+            crc,
+            text: text.to_string(),
+        };
+        if self.entries.lock().unwrap().insert(key, entry.clone()) == Some(entry) {
+            return;
+        }
+        self.mark_dirty();
+    }
+
+    pub fn remove(&self, png: &Path) {
+        let Some(key) = key(png) else { return };
+        if self.entries.lock().unwrap().remove(&key).is_some() {
+            self.mark_dirty();
+        }
+    }
+
+    fn mark_dirty(&self) {
+        let mut pending = self.pending.lock().unwrap();
+        *pending += 1;
+        if *pending >= FLUSH_EVERY {
+            *pending = 0;
+            drop(pending);
+            if let Err(e) = self.write_file() {
+                eprintln!("qrfs: warning: no se pudo guardar la cache de qr: {e}");
+            }
+        }
+    }
+
+    // guarda la cache si hay cambios pendientes
+    pub fn flush(&self) -> Result<(), QrfsError> {
+        let mut pending = self.pending.lock().unwrap();
+        if *pending == 0 {
+            return Ok(());
+        }
+        *pending = 0;
+        drop(pending);
+        self.write_file()
+    }
+
+    // escribe a un temporal y renombra para no dejar un json a medias
+    fn write_file(&self) -> Result<(), QrfsError> {
+        let text = {
+            let entries = self.entries.lock().unwrap();
+            serde_json::to_string(&*entries)
+                .map_err(|e| QrfsError::Other(format!("error serializando cache: {}", e)))?
+        };
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, text)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+impl Drop for DecodeCache {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            eprintln!("qrfs: warning: no se pudo guardar la cache de qr: {e}");
+        }
+    }
+}
+
+fn key(png: &Path) -> Option<String> {
+    png.file_name().map(|n| n.to_string_lossy().into_owned())
+}
+
+fn file_stamp(png: &Path) -> Option<(u64, u64, u32)> {
+    let meta = fs::metadata(png).ok()?;
+    let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    let crc = crc32fast::hash(&fs::read(png).ok()?);
+    Some((mtime.as_nanos() as u64, meta.len(), crc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_survive_reload_and_expire_when_file_changes() {
+        let dir = std::env::temp_dir().join(format!("qrfs_decode_cache_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let png = dir.join("000001.png");
+        fs::write(&png, b"imagen").unwrap();
+
+        {
+            let cache = DecodeCache::load(&dir);
+            assert!(cache.get(&png).is_none());
+            cache.insert(&png, "texto");
+            assert_eq!(cache.get(&png).as_deref(), Some("texto"));
+        }
+
+        // otro proceso (nueva instancia) ve la entrada guardada al cerrar
+        let cache = DecodeCache::load(&dir);
+        assert_eq!(cache.get(&png).as_deref(), Some("texto"));
+
+        // si la imagen cambia de tamaño la entrada deja de valer
+        fs::write(&png, b"imagen distinta").unwrap();
+        assert!(cache.get(&png).is_none());
+
+        // y tambien si cambia el contenido con el mismo tamaño y mtime
+        // (reescrita dentro de la resolucion del reloj del filesystem)
+        cache.insert(&png, "texto");
+        let mtime = fs::metadata(&png).unwrap().modified().unwrap();
+        fs::write(&png, b"imagen DISTINTA").unwrap();
+        fs::File::options().write(true).open(&png).unwrap().set_modified(mtime).unwrap();
+        assert!(cache.get(&png).is_none());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
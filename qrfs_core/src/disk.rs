@@ -1,27 +1,57 @@
 use serde::{Deserialize, Serialize};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::errors::QrfsError;
+
 // identificador de bloque
 pub type BlockId = u32;
 
 // tamaño fijo del bloque logico
 pub const BLOCK_SIZE: usize = 128;
 
+// cantidad de bloques por defecto de un disco nuevo
+pub const DEFAULT_TOTAL_BLOCKS: u32 = 400;
+
+// cantidad fija de inodos que crea mkfs
+pub const DEFAULT_INODE_COUNT: u32 = 64;
+
+// hora fija de los discos deterministas (la convencion de builds reproducibles)
+pub const SOURCE_DATE_EPOCH_ENV: &str = "SOURCE_DATE_EPOCH";
+
 // numero magico qrfs
 pub const QRFS_MAGIC: u32 = 0x5152_4653;
 
-// version del formato qrfs
+// version del formato qrfs (la mayor: un lector de otra mayor no entiende
+// el disco, salvo que el disco diga lo contrario con min_compat_version)
 pub const QRFS_VERSION: u32 = 1;
 
+// version menor: campos agregados que un lector de la misma mayor puede
+// ignorar al leer, pero que pisaria al escribir. los discos de antes tienen 0
+pub const QRFS_MINOR_VERSION: u8 = 1;
+
 // tipos de inodo
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// los nuevos van siempre al final: bincode guarda el indice de la variante
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum InodeKind {
     File,
     Directory,
+    // el destino se guarda como contenido del archivo
+    Symlink,
+    Fifo,
+    Socket,
+    // los dispositivos usan rdev
+    CharDevice,
+    BlockDevice,
+}
+
+impl InodeKind {
+    pub fn is_device(&self) -> bool {
+        matches!(self, InodeKind::CharDevice | InodeKind::BlockDevice)
+    }
 }
 
 // estructura del inodo
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Inode {
     pub id: u32,
     pub kind: InodeKind,
@@ -37,15 +67,175 @@ pub struct Inode {
     // timestamps unix
     pub created_at: u64,
     pub modified_at: u64,
+
+    // flags estilo chattr (INODE_FLAG_*); solo en tablas con formato >= 1
+    pub flags: u32,
+
+    // numero de dispositivo (mayor y menor como los arma makedev) de los
+    // CharDevice y BlockDevice; solo en tablas con formato >= 3
+    pub rdev: u32,
+
+    // clave del archivo envuelta con la clave maestra (ver crypt.rs); vacia
+    // en los archivos sin cifrar. solo en tablas con formato >= 4
+    pub key: Vec<u8>,
+}
+
+// el archivo no se puede escribir, truncar, borrar ni renombrar
+pub const INODE_FLAG_IMMUTABLE: u32 = 1;
+// solo se puede escribir al final; tampoco se borra ni se renombra
+pub const INODE_FLAG_APPEND: u32 = 2;
+// el ultimo bloque es compartido con las colas de otros archivos (ver
+// tail.rs); no es un flag de chattr, lo maneja el filesystem
+pub const INODE_FLAG_TAIL: u32 = 4;
+// los bloques del inodo van a la zona fija del principio del disco y no se
+// reubican (ver Superblock::pinned_blocks)
+pub const INODE_FLAG_PINNED: u32 = 8;
+
+// formato de la tabla de inodos: 0 = original (sin flags), 1 = con flags,
+// 2 = con flags y un InodeBlockHeader en cada bloque de la tabla, 3 = ademas rdev,
+// 4 = ademas la clave envuelta de cada archivo cifrado
+// los discos viejos siguen en su formato y se leen y escriben en ese formato
+pub const INODE_FORMAT: u32 = 4;
+
+// inodo tal como lo guarda una tabla con formato 0
+#[derive(Serialize, Deserialize)]
+pub(crate) struct InodeV0 {
+    id: u32,
+    kind: InodeKind,
+    size: u64,
+    blocks: Vec<BlockId>,
+    mode: u16,
+    created_at: u64,
+    modified_at: u64,
+}
+
+impl From<InodeV0> for Inode {
+    fn from(old: InodeV0) -> Self {
+        Self {
+            id: old.id,
+            kind: old.kind,
+            size: old.size,
+            blocks: old.blocks,
+            mode: old.mode,
+            created_at: old.created_at,
+            modified_at: old.modified_at,
+            flags: 0,
+            rdev: 0,
+            key: Vec::new(),
+        }
+    }
+}
+
+// inodo tal como lo guardan las tablas con formato 1 y 2
+#[derive(Serialize, Deserialize)]
+pub(crate) struct InodeV1 {
+    id: u32,
+    kind: InodeKind,
+    size: u64,
+    blocks: Vec<BlockId>,
+    mode: u16,
+    created_at: u64,
+    modified_at: u64,
+    flags: u32,
+}
+
+impl From<InodeV1> for Inode {
+    fn from(old: InodeV1) -> Self {
+        Self {
+            id: old.id,
+            kind: old.kind,
+            size: old.size,
+            blocks: old.blocks,
+            mode: old.mode,
+            created_at: old.created_at,
+            modified_at: old.modified_at,
+            flags: old.flags,
+            rdev: 0,
+            key: Vec::new(),
+        }
+    }
+}
+
+// inodo tal como lo guardan las tablas con formato 3
+#[derive(Serialize, Deserialize)]
+pub(crate) struct InodeV3 {
+    id: u32,
+    kind: InodeKind,
+    size: u64,
+    blocks: Vec<BlockId>,
+    mode: u16,
+    created_at: u64,
+    modified_at: u64,
+    flags: u32,
+    rdev: u32,
+}
+
+impl From<InodeV3> for Inode {
+    fn from(old: InodeV3) -> Self {
+        Self {
+            id: old.id,
+            kind: old.kind,
+            size: old.size,
+            blocks: old.blocks,
+            mode: old.mode,
+            created_at: old.created_at,
+            modified_at: old.modified_at,
+            flags: old.flags,
+            rdev: old.rdev,
+            key: Vec::new(),
+        }
+    }
 }
 
 impl Inode {
+    // bytes del inodo en el formato de tabla indicado (los flags se pierden en
+    // 0, rdev antes del 3 y la clave antes del 4)
+    pub fn encode(&self, format: u32) -> Result<Vec<u8>, bincode::Error> {
+        match format {
+            0 => bincode::serialize(&InodeV0 {
+                id: self.id,
+                kind: self.kind.clone(),
+                size: self.size,
+                blocks: self.blocks.clone(),
+                mode: self.mode,
+                created_at: self.created_at,
+                modified_at: self.modified_at,
+            }),
+            1 | 2 => bincode::serialize(&InodeV1 {
+                id: self.id,
+                kind: self.kind.clone(),
+                size: self.size,
+                blocks: self.blocks.clone(),
+                mode: self.mode,
+                created_at: self.created_at,
+                modified_at: self.modified_at,
+                flags: self.flags,
+            }),
+            3 => bincode::serialize(&InodeV3 {
+                id: self.id,
+                kind: self.kind.clone(),
+                size: self.size,
+                blocks: self.blocks.clone(),
+                mode: self.mode,
+                created_at: self.created_at,
+                modified_at: self.modified_at,
+                flags: self.flags,
+                rdev: self.rdev,
+            }),
+            _ => bincode::serialize(self),
+        }
+    }
+
     pub fn new(id: u32, kind: InodeKind) -> Self {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
+        Self::new_at(id, kind, now)
+    }
 
+    // con la hora del disco (Superblock::timestamp)
+    pub fn new_at(id: u32, kind: InodeKind, now: u64) -> Self {
         Self {
             id,
             kind,
@@ -54,6 +244,9 @@ impl Inode {
             mode: 0o755,
             created_at: now,
             modified_at: now,
+            flags: 0,
+            rdev: 0,
+            key: Vec::new(),
         }
     }
 }
@@ -66,6 +259,33 @@ pub struct DirectoryEntry {
     pub kind: InodeKind,
 }
 
+// cada bloque de un directorio (formato >= 1) empieza con este encabezado y
+// sigue con su parte de las entradas serializadas. todos los bloques del
+// directorio llevan el mismo encabezado: un bloque viejo que no se reescribio
+// queda con otra generacion y uno de otro directorio con otro inodo
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DirBlockHeader {
+    pub magic: u32,
+    pub inode_id: u32,
+    // sube en cada escritura del directorio
+    pub generation: u32,
+    pub entry_count: u32,
+    // bytes de las entradas serializadas. el directorio se lee con este valor y
+    // no con el size del inodo, que se guarda despues (la tabla puede quedar atras)
+    pub size: u32,
+    // crc32 de las entradas serializadas (sin los encabezados)
+    pub crc: u32,
+}
+
+// "QDIR"
+pub const DIR_MAGIC: u32 = 0x5144_4952;
+
+// bytes del encabezado al principio de cada bloque de directorio
+pub const DIR_HEADER: usize = 24;
+
+// formato de los bloques de directorio: 0 = bincode plano, 1 = con encabezado
+pub const DIR_FORMAT: u32 = 1;
+
 // superblock qrfs
 // bloque 0 contiene esta estructura serializada con bincode
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -90,15 +310,200 @@ pub struct Superblock {
 
     // inicio de los bloques de datos
     pub data_block_start: BlockId,
+
+    // primer bloque de la extension de la tabla de inodos (0 = sin extension)
+    // va al final: en discos viejos el relleno con ceros del bloque 0 lo lee como 0
+    pub inode_ext_head: BlockId,
+
+    // bloques por subcarpeta de pngs (0 = todos en la carpeta del disco)
+    // el bloque 0 queda siempre afuera para poder leer este valor
+    pub shard_size: u32,
+
+    // bloques de datos guardados por digest (ver cas.rs)
+    pub content_addressed: bool,
+
+    // formato de la tabla de inodos (0 en discos anteriores a los flags)
+    pub inode_format: u32,
+
+    // formato de los bloques de directorio (0 en discos anteriores al encabezado)
+    pub dir_format: u32,
+
+    // sube con cada commit de metadata: un montaje que ve otro valor en el
+    // disco sabe que alguien mas escribio y vuelve a leer la metadata
+    pub meta_generation: u64,
+
+    // clave maestra envuelta con la frase (ver crypt::seal_master); vacia si
+    // el disco no tiene archivos cifrados
+    pub key_slot: Vec<u8>,
+
+    // las colas de los archivos chicos se empaquetan en bloques compartidos
+    // (ver tail.rs)
+    pub tail_packing: bool,
+
+    // bloques al principio del area de datos reservados para lo que conviene
+    // tener junto al imprimir y escanear primero: la extension de la tabla de
+    // inodos, la raiz y los inodos con INODE_FLAG_PINNED. los datos comunes
+    // solo caen ahi con el resto del disco lleno (0 = sin zona fija)
+    pub pinned_blocks: u32,
+
+    // disco repartido en varias carpetas (ver volume.rs); None (un byte, el
+    // superblock de un rekey ya casi llena la zona reservada) si todo esta en
+    // la carpeta del disco
+    pub volume_set: Option<VolumeSet>,
+
+    // bloque con el rotulo y el uuid del disco (DiskLabel), como la extension
+    // de la tabla de inodos: en la zona del superblock no entran junto a las
+    // dos ranuras de un rekey. 0 = sin rotulo
+    pub label_block: BlockId,
+
+    // plantilla con la que se creo el disco (config::DiskProfile::id; 0 = ninguna)
+    pub profile: u8,
+
+    // disco reproducible (mkfs --deterministic): hora fija en los inodos y
+    // uuid e ids sacados de QRFS_SEED, para que dos corridas iguales den los
+    // mismos qr
+    pub deterministic: bool,
+
+    // con que codec se escriben los bloques (codec::codec_by_id; 0 = qr-v1,
+    // el de los discos de antes)
+    pub codec: u8,
+
+    // version menor del formato con que se escribio (QRFS_MINOR_VERSION).
+    // los dos de un byte: con un rekey la zona reservada queda casi llena
+    pub minor_version: u8,
+
+    // la mayor mas vieja que todavia puede leer el disco (en solo lectura);
+    // 0 en los discos de antes, que solo se leen con su propia mayor
+    pub min_compat_version: u8,
+}
+
+// que se puede hacer con un disco segun su version (ver Superblock::version_access)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionAccess {
+    ReadWrite,
+    // mas nuevo que este lector pero compatible: se lee sin tocarlo
+    ReadOnly,
 }
 
+// rotulo del disco: lo que muestran los administradores de archivos al
+// montarlo ("QRFS: Impuestos2024") y un uuid fijo desde que se rotulo
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskLabel {
+    pub magic: u32,
+    pub uuid: [u8; 16],
+    pub label: String,
+}
+
+// "QLBL"
+pub const LABEL_BLOCK_MAGIC: u32 = 0x514C_424C;
+
+// bytes del rotulo; sin comas ni barras (va en las opciones de montaje)
+pub const MAX_LABEL_LEN: usize = 32;
+
+impl DiskLabel {
+    // uuid en la forma de siempre, 8-4-4-4-12
+    pub fn uuid_string(&self) -> String {
+        let hex: String = self.uuid.iter().map(|b| format!("{:02x}", b)).collect();
+        format!(
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+
+    // nombre para mostrar en el escritorio
+    pub fn display_name(&self) -> String {
+        format!("QRFS: {}", self.label)
+    }
+
+    // contenido de .xdg-volume-info, de donde gio toma el nombre del volumen
+    pub fn volume_info(&self) -> String {
+        format!("[Volume Info]\nName={}\n", self.display_name())
+    }
+}
+
+// archivo de .qrfs_meta con el rotulo y el uuid
+pub const LABEL_FILE: &str = "label";
+// archivo virtual de la raiz que leen gio y los administradores de archivos
+pub const VOLUME_INFO_FILE: &str = ".xdg-volume-info";
+
+// un rotulo se puede usar tal cual en fsname y volname
+pub fn check_label(label: &str) -> Result<(), QrfsError> {
+    if label.is_empty() || label.len() > MAX_LABEL_LEN {
+        return Err(QrfsError::Other(format!(
+            "el rotulo tiene que tener de 1 a {} bytes",
+            MAX_LABEL_LEN
+        )));
+    }
+    if let Some(c) = label.chars().find(|&c| c == ',' || c == '/' || c == '\\' || c.is_control()) {
+        return Err(QrfsError::Other(format!("el rotulo no puede tener {:?}", c)));
+    }
+    Ok(())
+}
+
+// numero del juego, igual en la marca de cada carpeta, y el rango de bloques
+// de cada volumen en orden
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VolumeSet {
+    pub id: u64,
+    pub volumes: Vec<VolumeRecord>,
+}
+
+// un volumen del disco: los bloques first..first + count, en su propia carpeta
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VolumeRecord {
+    pub first: BlockId,
+    pub count: u32,
+}
+
+// tope de volumenes: los registros tienen que entrar en la zona del superblock
+pub const MAX_VOLUMES: usize = 8;
+
+// bytes que mkfs reserva para el superblock al principio del disco: los campos
+// nuevos entran sin mover el bitmap (hoy ocupa menos de un bloque)
+pub const SUPERBLOCK_RESERVED_BYTES: u32 = 256;
+// tope para los lectores: un superblock que diga ocupar mas es basura
+pub const SUPERBLOCK_MAX_BLOCKS: u32 = 8;
+
+// cada bloque de la extension de la tabla de inodos empieza con el id del
+// siguiente (u32, 0 = ultimo) y el resto sigue la tabla donde quedo
+pub const INODE_EXT_HEADER: usize = 4;
+
+// encabezado de cada bloque de la tabla de inodos (formato >= 2), despues del
+// puntero al siguiente en los bloques de la extension. la tabla se reescribe
+// entera en cada commit con una generacion nueva: bloques con generaciones
+// distintas son una escritura cortada, un crc que no coincide es basura
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InodeBlockHeader {
+    pub magic: u32,
+    pub generation: u32,
+    // crc32 del resto del bloque (todo menos este encabezado)
+    pub crc: u32,
+}
+
+// "QINO"
+pub const INODE_BLOCK_MAGIC: u32 = 0x5149_4E4F;
+
+pub const INODE_BLOCK_HEADER: usize = 12;
+
+// inodos que se agregan cada vez que la tabla se llena
+pub const INODE_GROW_STEP: u32 = 32;
+
 impl Superblock {
     pub fn new(total_blocks: u32, inode_count: u32) -> Self {
-        // bloque 0 siempre es superblock
-        let block_size = BLOCK_SIZE as u32;
-        let free_map_start = 1;
+        Self::with_block_size(total_blocks, inode_count, BLOCK_SIZE as u32)
+    }
 
-        let free_map_blocks = 1;
+    // el resto del codigo toma el tamaño de bloque de aca, no de BLOCK_SIZE
+    pub fn with_block_size(total_blocks: u32, inode_count: u32, block_size: u32) -> Self {
+        // el superblock ocupa los bloques reservados desde el 0, el bitmap sigue
+        let free_map_start = SUPERBLOCK_RESERVED_BYTES.div_ceil(block_size).max(1);
+
+        // un bit por bloque; con bloques de 128 bytes uno alcanza hasta 1024 bloques
+        let free_map_blocks = total_blocks.div_ceil(8).div_ceil(block_size).max(1);
 
         let inode_table_start = free_map_start + free_map_blocks;
 
@@ -107,7 +512,8 @@ impl Superblock {
         let total_inode_bytes = inode_count * bytes_per_inode;
 
         // division techo (ceiling division) para asegurar que quepan
-        let inode_table_blocks = (total_inode_bytes + block_size - 1) / block_size;
+        // (cada bloque pierde los bytes del encabezado)
+        let inode_table_blocks = total_inode_bytes.div_ceil(block_size - INODE_BLOCK_HEADER as u32);
 
         let data_block_start = inode_table_start + inode_table_blocks;
 
@@ -123,11 +529,88 @@ impl Superblock {
             inode_table_blocks,
             root_inode: 0,
             data_block_start,
+            inode_ext_head: 0,
+            shard_size: 0,
+            content_addressed: false,
+            inode_format: INODE_FORMAT,
+            dir_format: DIR_FORMAT,
+            meta_generation: 0,
+            key_slot: Vec::new(),
+            tail_packing: false,
+            pinned_blocks: 0,
+            volume_set: None,
+            label_block: 0,
+            profile: 0,
+            deterministic: false,
+            codec: 0,
+            minor_version: QRFS_MINOR_VERSION,
+            min_compat_version: QRFS_VERSION as u8,
         }
     }
 
+    // firma qrfs y una version que este lector puede al menos leer
     pub fn is_valid(&self) -> bool {
-        self.magic == QRFS_MAGIC && self.version == QRFS_VERSION
+        self.magic == QRFS_MAGIC && self.version_access().is_ok()
+    }
+
+    // la misma politica para mount, fsck y el servidor: una menor mas nueva
+    // se monta en solo lectura, otra mayor se rechaza salvo que el disco
+    // declare que esta mayor todavia lo puede leer
+    pub fn version_access(&self) -> Result<VersionAccess, QrfsError> {
+        let min_compat = match self.min_compat_version {
+            0 => self.version,
+            v => v as u32,
+        };
+        if self.version == QRFS_VERSION {
+            if self.minor_version > QRFS_MINOR_VERSION {
+                return Ok(VersionAccess::ReadOnly);
+            }
+            return Ok(VersionAccess::ReadWrite);
+        }
+        if self.version > QRFS_VERSION && min_compat <= QRFS_VERSION {
+            return Ok(VersionAccess::ReadOnly);
+        }
+        Err(QrfsError::Unimplemented(format!(
+            "disco con formato version {} (este qrfs es la {}.{} y no lo puede leer)",
+            self.version_string(),
+            QRFS_VERSION,
+            QRFS_MINOR_VERSION
+        )))
+    }
+
+    // "mayor.menor", para los mensajes
+    pub fn version_string(&self) -> String {
+        format!("{}.{}", self.version, self.minor_version)
+    }
+
+    // bloques que ocupa el superblock: todos los anteriores al bitmap
+    // (free_map_start esta en los primeros bytes del bloque 0; 1 en discos viejos)
+    pub fn region_blocks(&self) -> u32 {
+        self.free_map_start.max(1)
+    }
+
+    // hora para los inodos: la del reloj, o la fija de un disco determinista
+    // (SOURCE_DATE_EPOCH, o 0 si no esta)
+    pub fn timestamp(&self) -> u64 {
+        if self.deterministic {
+            return std::env::var(SOURCE_DATE_EPOCH_ENV)
+                .ok()
+                .and_then(|secs| secs.trim().parse().ok())
+                .unwrap_or(0);
+        }
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    // rangos de los volumenes; vacio si el disco es de una sola carpeta
+    pub fn volumes(&self) -> &[VolumeRecord] {
+        self.volume_set.as_ref().map_or(&[], |set| &set.volumes)
+    }
+
+    // volumen (indice desde 0) que guarda el bloque; None sin volumenes
+    pub fn volume_of(&self, id: BlockId) -> Option<usize> {
+        self.volumes()
+            .iter()
+            .position(|v| id >= v.first && id - v.first < v.count)
     }
 }
 
@@ -144,7 +627,8 @@ mod tests {
         assert!(sb.is_valid());
 
         assert_eq!(sb.block_size as usize, BLOCK_SIZE);
-        assert_eq!(sb.free_map_start, 1);
+        assert_eq!(sb.free_map_start, 2);
+        assert_eq!(sb.region_blocks(), 2);
         assert_eq!(sb.free_map_blocks, 1);
 
         assert_eq!(sb.inode_table_start, sb.free_map_start + sb.free_map_blocks);
@@ -154,6 +638,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn newer_minor_versions_read_only_and_other_majors_only_if_declared() {
+        let mut sb = Superblock::new(128, 64);
+        assert_eq!(sb.version_access().unwrap(), VersionAccess::ReadWrite);
+
+        // un disco de antes de estos campos
+        sb.minor_version = 0;
+        sb.min_compat_version = 0;
+        assert_eq!(sb.version_access().unwrap(), VersionAccess::ReadWrite);
+
+        sb.minor_version = QRFS_MINOR_VERSION + 1;
+        assert_eq!(sb.version_access().unwrap(), VersionAccess::ReadOnly);
+        assert!(sb.is_valid());
+
+        sb.version = QRFS_VERSION + 1;
+        assert!(sb.version_access().is_err());
+        assert!(!sb.is_valid());
+        sb.min_compat_version = QRFS_VERSION as u8;
+        assert_eq!(sb.version_access().unwrap(), VersionAccess::ReadOnly);
+
+        sb.version = 0;
+        assert!(sb.version_access().is_err());
+    }
+
+    #[test]
+    fn bitmap_grows_with_the_disk() {
+        assert_eq!(Superblock::new(1024, 64).free_map_blocks, 1);
+        assert_eq!(Superblock::new(1025, 64).free_map_blocks, 2);
+
+        let sb = Superblock::new(4000, 64);
+        assert!(sb.free_map_blocks as usize * BLOCK_SIZE * 8 >= 4000);
+        assert_eq!(sb.inode_table_start, sb.free_map_start + sb.free_map_blocks);
+    }
+
     #[test]
     fn inode_new_has_default_values() {
         let inode = Inode::new(10, InodeKind::File);
@@ -15,6 +15,47 @@ pub enum QrfsError {
     #[error("unimplemented feature: {0}")]
     Unimplemented(String),
 
+    #[error("not found: {0}")]
+    NotFound(String),
+
+    #[error("already exists: {0}")]
+    AlreadyExists(String),
+
+    #[error("no space left: {0}")]
+    NoSpace(String),
+
+    #[error("read-only filesystem: {0}")]
+    ReadOnly(String),
+
+    #[error("operation not permitted: {0}")]
+    NotPermitted(String),
+
+    #[error("not a directory: {0}")]
+    NotADirectory(String),
+
+    #[error("directory not empty: {0}")]
+    NotEmpty(String),
+
+    #[error("corrupt metadata: {0}")]
+    Corrupt(String),
+
     #[error("other error: {0}")]
     Other(String),
 }
+
+impl QrfsError {
+    // codigo errno equivalente, para responder a fuse y a los servidores de archivos
+    pub fn errno(&self) -> i32 {
+        match self {
+            QrfsError::NotFound(_) => libc::ENOENT,
+            QrfsError::AlreadyExists(_) => libc::EEXIST,
+            QrfsError::NoSpace(_) => libc::ENOSPC,
+            QrfsError::ReadOnly(_) => libc::EROFS,
+            QrfsError::NotPermitted(_) => libc::EPERM,
+            QrfsError::NotADirectory(_) => libc::ENOTDIR,
+            QrfsError::NotEmpty(_) => libc::ENOTEMPTY,
+            QrfsError::Unimplemented(_) => libc::ENOSYS,
+            _ => libc::EIO,
+        }
+    }
+}
@@ -0,0 +1,98 @@
+// cuanto disco hace falta para un conjunto de datos, antes de correr mkfs
+// (estimate.qrfs, mkfs --interactive)
+
+use qrcode::{EcLevel, QrCode};
+
+use crate::disk::Superblock;
+use crate::qr::BlockEnvelope;
+
+// qr por hoja impresa: la hoja de reprint_plan.qrfs (2 columnas x 3 filas)
+pub const QRS_PER_PAGE: u32 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Estimate {
+    pub data_blocks: u32,
+    pub inode_count: u32,
+    // datos mas metadata y zona fija: lo que va en mkfs --blocks
+    pub total_blocks: u32,
+    // una imagen por bloque y por copia (un disco lleno no tiene bloques en cero)
+    pub qr_images: u32,
+    pub pages: u32,
+    // modulos por lado del qr de un bloque lleno con esa correccion
+    pub qr_modules: u32,
+}
+
+// un 25% de margen para directorios y colas, un inodo cada 8 bloques de
+// datos. copies es 2 con --mirror
+pub fn estimate(data_bytes: u64, block_size: usize, ec_level: EcLevel, pinned_blocks: u32, copies: u32) -> Estimate {
+    let data_blocks = ((data_bytes + data_bytes / 4).div_ceil(block_size as u64) as u32).max(16);
+    let inode_count = (data_blocks / 8).clamp(16, 1024);
+    // la metadata crece con el disco (el bitmap): se ajusta hasta que entra
+    let mut total_blocks = data_blocks;
+    loop {
+        let needed = Superblock::with_block_size(total_blocks, inode_count, block_size as u32).data_block_start
+            + pinned_blocks
+            + data_blocks;
+        if needed <= total_blocks {
+            break;
+        }
+        total_blocks = needed;
+    }
+    let qr_images = total_blocks * copies.max(1);
+    Estimate {
+        data_blocks,
+        inode_count,
+        total_blocks,
+        qr_images,
+        pages: qr_images.div_ceil(QRS_PER_PAGE),
+        qr_modules: block_qr_modules(total_blocks, block_size, ec_level),
+    }
+}
+
+// el sobre mas largo que puede tener el disco: ultimo id, generacion alta y
+// el bloque entero. 0 si no entra en un qr (no pasa con bloques de 128)
+pub fn block_qr_modules(total_blocks: u32, block_size: usize, ec_level: EcLevel) -> u32 {
+    let envelope = BlockEnvelope::new(total_blocks.saturating_sub(1), 1_000_000, &vec![0xff; block_size]);
+    QrCode::with_error_correction_level(envelope.to_json(), ec_level).map_or(0, |qr| qr.width() as u32)
+}
+
+// "500", "20K", "25MB", "1g" (potencias de 1024)
+pub fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim().to_uppercase();
+    let text = text.strip_suffix('B').unwrap_or(&text);
+    let (digits, unit) = match text.chars().last()? {
+        'K' => (&text[..text.len() - 1], 1024),
+        'M' => (&text[..text.len() - 1], 1024 * 1024),
+        'G' => (&text[..text.len() - 1], 1024 * 1024 * 1024),
+        _ => (text, 1),
+    };
+    digits.trim().parse::<u64>().ok().filter(|&n| n > 0).and_then(|n| n.checked_mul(unit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::BLOCK_SIZE;
+
+    #[test]
+    fn the_estimate_fits_the_data_and_more_correction_means_bigger_qrs() {
+        assert_eq!(parse_size("25MB"), Some(25 * 1024 * 1024));
+        assert_eq!(parse_size("20k"), Some(20 * 1024));
+        assert_eq!(parse_size("500"), Some(500));
+        assert_eq!(parse_size("mucho"), None);
+
+        let low = estimate(20_000, BLOCK_SIZE, EcLevel::L, 0, 1);
+        let superblock = Superblock::new(low.total_blocks, low.inode_count);
+        assert!((low.total_blocks - superblock.data_block_start) as usize * BLOCK_SIZE >= 20_000);
+        assert_eq!(low.pages, low.total_blocks.div_ceil(QRS_PER_PAGE));
+
+        let high = estimate(20_000, BLOCK_SIZE, EcLevel::H, 0, 2);
+        assert_eq!(high.qr_images, 2 * high.total_blocks);
+        assert!(high.qr_modules > low.qr_modules && low.qr_modules > 0);
+
+        // bloques mas grandes: menos bloques y qr mas densos; uno enorme no entra
+        let big = estimate(20_000, 512, EcLevel::L, 0, 1);
+        assert!(big.data_blocks < low.data_blocks && big.qr_modules > low.qr_modules);
+        assert_eq!(block_qr_modules(64, 4096, EcLevel::L), 0);
+    }
+}
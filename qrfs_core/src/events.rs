@@ -0,0 +1,144 @@
+// avisos de cambios entre los que usan el mismo disco en un proceso (el
+// montaje, la api, el servidor). cada QrfsFilesystem con with_events publica
+// lo que cambia en cada commit y se entera de lo que cambian los otros: un
+// montaje recarga la metadata y le pide al kernel que olvide lo que tenia en
+// cache (ver backend.rs). quien solo quiera mirar (la ui, un sync) se suscribe
+//
+//   let events = EventBus::new();
+//   let api = QrfsApi::new(QrfsFilesystem::new(storage.clone())?.with_events(events.clone()));
+//   let session = QrfsFilesystem::new(storage)?.with_events(events.clone()).spawn_mount(punto)?;
+//   for notice in events.subscribe() { ... }
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+use crate::disk::BlockId;
+
+// contenido mas grande que esto no se copia en el aviso: el kernel lo vuelve
+// a pedir
+pub const STORE_MAX_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FsEvent {
+    // una entrada de parent aparecio, se fue o apunta a otro inodo
+    Entry { parent: u32, name: String },
+    // cambiaron los atributos o los datos de un inodo (o se borro)
+    Inode(u32),
+    // contenido nuevo y ya guardado de un inodo, a partir de offset (api.write)
+    Stored { inode: u32, offset: u64, data: Arc<[u8]> },
+    // quien publica recargo la metadata por cambios de otro: estos inodos
+    // cambiaron desde su ultima lectura
+    Reloaded { inodes: Vec<u32> },
+    // llego un bloque por afuera del filesystem (escaner, sync): la metadata
+    // puede haber cambiado sin que nadie la haya publicado
+    BlockStored(BlockId),
+}
+
+#[derive(Debug, Clone)]
+pub struct Notice {
+    // quien lo publico (EventBus::join); el que lo mande ve sus propios avisos
+    pub origin: u64,
+    pub event: FsEvent,
+}
+
+#[derive(Default)]
+pub struct EventBus {
+    next_origin: AtomicU64,
+    subscribers: Mutex<Vec<Sender<Notice>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self { next_origin: AtomicU64::new(1), ..Self::default() })
+    }
+
+    // un id nuevo para publicar (uno por filesystem o herramienta)
+    pub fn join(&self) -> u64 {
+        self.next_origin.fetch_add(1, Ordering::Relaxed)
+    }
+
+    // todo lo que se publique desde ahora; al soltar el receptor se deja de mandar
+    pub fn subscribe(&self) -> Receiver<Notice> {
+        let (tx, rx) = channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    pub fn publish(&self, origin: u64, event: FsEvent) {
+        let notice = Notice { origin, event };
+        self.subscribers.lock().unwrap().retain(|tx| tx.send(notice.clone()).is_ok());
+    }
+}
+
+// lo que guarda un filesystem conectado al bus: con que id publica y lo
+// que publicaron los demas desde la ultima vez que miro
+pub(crate) struct EventLink {
+    pub(crate) bus: Arc<EventBus>,
+    pub(crate) origin: u64,
+    inbox: Receiver<Notice>,
+}
+
+impl EventLink {
+    pub(crate) fn new(bus: Arc<EventBus>) -> Self {
+        Self { origin: bus.join(), inbox: bus.subscribe(), bus }
+    }
+
+    pub(crate) fn publish(&self, event: FsEvent) {
+        self.bus.publish(self.origin, event);
+    }
+
+    // true si otro publico algo desde la ultima llamada; vacia la bandeja
+    pub(crate) fn others_changed(&self) -> bool {
+        self.inbox.try_iter().filter(|notice| notice.origin != self.origin).count() > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::QrfsApi;
+    use crate::disk::{Superblock, BLOCK_SIZE};
+    use crate::fs::QrfsFilesystem;
+    use crate::fs_format::format_storage;
+    use crate::storage::InMemoryBlockStorage;
+    use std::time::Duration;
+
+    #[test]
+    fn a_write_through_the_api_reaches_another_filesystem_on_the_bus() {
+        let storage = Arc::new(InMemoryBlockStorage::new(64, BLOCK_SIZE));
+        format_storage(storage.as_ref(), &Superblock::new(64, 16)).unwrap();
+        let events = EventBus::new();
+        let watcher = events.subscribe();
+
+        let writer = QrfsApi::new(QrfsFilesystem::new(storage.clone()).unwrap().with_events(events.clone()));
+        // sin avisos no volveria a mirar el disco en una hora
+        let mounted = QrfsApi::new(
+            QrfsFilesystem::new(storage.clone())
+                .unwrap()
+                .with_metadata_recheck(Duration::from_secs(3600))
+                .with_events(events.clone()),
+        );
+        assert!(mounted.stat("/nuevo.txt").is_err());
+
+        let info = writer.write("/nuevo.txt", b"desde la api").unwrap();
+        assert_eq!(mounted.read("/nuevo.txt").unwrap(), b"desde la api");
+
+        let seen: Vec<FsEvent> = watcher.try_iter().map(|notice| notice.event).collect();
+        let root = writer.superblock().root_inode;
+        assert!(seen.contains(&FsEvent::Entry { parent: root, name: "nuevo.txt".into() }), "{:?}", seen);
+        assert!(seen.contains(&FsEvent::Inode(info.inode_id)), "{:?}", seen);
+        assert!(seen.iter().any(|event| matches!(
+            event,
+            FsEvent::Stored { inode, offset: 0, data } if *inode == info.inode_id && &data[..] == b"desde la api"
+        )));
+        // el otro recargo y dijo que inodos encontro cambiados
+        assert!(seen.iter().any(|event| matches!(
+            event,
+            FsEvent::Reloaded { inodes } if inodes.contains(&info.inode_id)
+        )));
+
+        writer.remove("/nuevo.txt").unwrap();
+        assert!(mounted.stat("/nuevo.txt").is_err());
+    }
+}
@@ -1,331 +1,3087 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+#[cfg(unix)]
 use std::ffi::OsStr;
+#[cfg(unix)]
 use std::io::Write;
 use std::path::Path;
 use std::sync::Arc;
-use std::time::{Duration, UNIX_EPOCH};
+use std::time::{Duration, Instant};
+#[cfg(unix)]
+use std::time::UNIX_EPOCH;
+use crate::audit::AuditLog;
+use crate::backend::MountBackend;
+use crate::crypt::{self, FileKey, Key};
 use crate::disk::DirectoryEntry;
-use crate::disk::{Inode, InodeKind, BLOCK_SIZE};
+use crate::events::{EventBus, EventLink, FsEvent};
+use crate::disk::{
+    check_label, BlockId, DiskLabel, Inode, InodeKind, LABEL_BLOCK_MAGIC, INODE_FLAG_APPEND, INODE_FLAG_IMMUTABLE, INODE_FLAG_PINNED, INODE_FLAG_TAIL, INODE_GROW_STEP, QRFS_MAGIC, VersionAccess,
+};
+use crate::fs_format::{
+    decode_directory, encode_directory, encode_inode_table, encode_label, encode_superblock,
+    inode_ext_chain, inode_table_layout, read_label, read_superblock, InodeTableReader,
+};
+use crate::health::{HealthEvent, HealthMonitor, HealthReport};
+use crate::missing::{report_text, MissingFile, MISSING_FILE};
+use crate::seal::SealIndex;
+use crate::shamir::{self, KeyShard};
+use crate::tail;
+use crate::transaction::{DirEntries, MetadataTransaction};
 use crate::storage::BlockStorage;
 use crate::Superblock;
 
-use fuser::{
-    FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyDirectory, ReplyEntry, Request,
-};
+#[cfg(unix)]
+use fuser::{FileAttr, FileType, Filesystem, ReplyAttr, ReplyDirectory, ReplyEntry, Request};
+#[cfg(unix)]
 use libc::ENOENT;
-
+#[cfg(unix)]
+use crate::disk::{LABEL_FILE, VOLUME_INFO_FILE};
+#[cfg(unix)]
+use crate::health::{HEALTH_FILE, META_DIR};
+#[cfg(unix)]
+use crate::seal::STATUS_FILE;
+
+#[cfg(unix)]
 const TTL: Duration = Duration::from_secs(1);
 
+// tamaños pedidos al kernel en init (el kernel puede bajarlos)
+#[cfg(unix)]
+const MAX_WRITE: u32 = 128 * 1024;
+#[cfg(unix)]
+const MAX_READAHEAD: u32 = 128 * 1024;
+
+// bloques de datos que se juntan en memoria antes de pasarlos a qr
+const DEFAULT_MAX_DIRTY_BLOCKS: usize = 256;
+
+// cada cuanto se mira el contador de commits del superblock en disco
+const DEFAULT_METADATA_RECHECK: Duration = Duration::from_secs(1);
+
+// flags que maneja el filesystem y no chattr
+const INTERNAL_FLAGS: u32 = INODE_FLAG_TAIL | INODE_FLAG_PINNED;
+
+// ioctl para regenerar los qr de un archivo: _IOR('Q', 1, u32)
+// responde con la cantidad de bloques regenerados. en los bsd el bit de
+// "sale dato" es otro, asi que el numero cambia
+#[cfg(not(target_os = "macos"))]
+pub const QRFS_IOC_REENCODE: u32 = 0x8004_5101;
+#[cfg(target_os = "macos")]
+pub const QRFS_IOC_REENCODE: u32 = 0x4004_5101;
+
+// ioctl con lo que ocupa un archivo: _IOR('Q', 2, [u64; 2])
+// responde con los bytes asignados (bloques logicos) y los guardados
+#[cfg(not(target_os = "macos"))]
+pub const QRFS_IOC_STORED: u32 = 0x8010_5102;
+#[cfg(target_os = "macos")]
+pub const QRFS_IOC_STORED: u32 = 0x4010_5102;
+
+// atributos extendidos de solo lectura con lo que ocupa un archivo
+pub const XATTR_STORED: &str = "user.qrfs.stored";
+pub const XATTR_RATIO: &str = "user.qrfs.ratio";
+
+// getxattr de un atributo que no existe
+#[cfg(target_os = "linux")]
+const NO_XATTR: i32 = libc::ENODATA;
+#[cfg(all(unix, not(target_os = "linux")))]
+const NO_XATTR: i32 = libc::ENOATTR;
+
+// flags de chflags que macos manda en setattr y lee de stat (UF_* los cambia
+// el dueño, SF_* solo root); fuse en linux no los usa
+#[cfg(unix)]
+const UF_IMMUTABLE: u32 = 0x0000_0002;
+#[cfg(unix)]
+const UF_APPEND: u32 = 0x0000_0004;
+#[cfg(unix)]
+const SF_IMMUTABLE: u32 = 0x0002_0000;
+#[cfg(unix)]
+const SF_APPEND: u32 = 0x0004_0000;
+
+// ioctls de chattr/lsattr (las variantes de 32 y 64 bits), solo linux
+#[cfg(target_os = "linux")]
+mod attr_ioctl {
+    pub const FS_IOC_GETFLAGS: [u32; 2] = [0x8008_6601, 0x8004_6601];
+    pub const FS_IOC_SETFLAGS: [u32; 2] = [0x4008_6602, 0x4004_6602];
+    pub const FS_IMMUTABLE_FL: u32 = 0x10;
+    pub const FS_APPEND_FL: u32 = 0x20;
+    pub const FS_IOC_FSGETXATTR: u32 = 0x801c_581f;
+    pub const FS_IOC_FSSETXATTR: u32 = 0x401c_5820;
+    pub const FS_XFLAG_IMMUTABLE: u32 = 0x08;
+    pub const FS_XFLAG_APPEND: u32 = 0x10;
+    pub const FSXATTR_SIZE: usize = 28;
+}
+#[cfg(target_os = "linux")]
+use attr_ioctl::*;
+
+// inodo fuse del archivo de estado del modo sellado (ningun inodo real llega)
+#[cfg(unix)]
+const STATUS_INO: u64 = u32::MAX as u64;
+// y el del informe de bloques faltantes del montaje degradado
+#[cfg(unix)]
+const MISSING_INO: u64 = u32::MAX as u64 - 1;
+// la carpeta virtual .qrfs_meta y su informe de salud
+#[cfg(unix)]
+const META_INO: u64 = u32::MAX as u64 - 2;
+#[cfg(unix)]
+const HEALTH_INO: u64 = u32::MAX as u64 - 3;
+// el rotulo en .qrfs_meta y el .xdg-volume-info de la raiz
+#[cfg(unix)]
+const LABEL_INO: u64 = u32::MAX as u64 - 4;
+#[cfg(unix)]
+const VOLUME_INFO_INO: u64 = u32::MAX as u64 - 5;
+
+// sesion de un montaje en segundo plano, al soltarla se desmonta
+pub use crate::backend::MountSession;
+
 // implementacion de qrfs que implementa fuser::filesystem
 pub struct QrfsFilesystem<B: BlockStorage + 'static> {
     storage: Arc<B>,
     superblock: Superblock,
     inodes: HashMap<u32, Inode>,
+    // bloques de datos que extienden la tabla de inodos, en orden de la cadena
+    inode_ext_blocks: Vec<BlockId>,
+    // generacion de la ultima escritura de la tabla de inodos (formato >= 2)
+    inode_table_generation: u32,
     bitmap: Vec<u8>,
-    dir_cache: HashMap<String, u32>,
+    // directorios ya leidos: inodo del directorio -> nombre -> (inodo, tipo)
+    // se llenan la primera vez que se usan (lookup, readdir, create...)
+    dir_cache: HashMap<u32, DirEntries>,
+    // generacion con la que se leyo o escribio por ultima vez cada directorio
+    dir_generations: HashMap<u32, u32>,
+    // el '..' que tenia cada directorio al leerlo (ver parent_of)
+    dir_parents: HashMap<u32, u32>,
+    // bloques de datos escritos que todavia no se codificaron a qr; se pasan
+    // al storage en flush/fsync/release o cuando hay demasiados
+    dirty_blocks: HashMap<BlockId, Vec<u8>>,
+    max_dirty_blocks: usize,
+    // inodos cambiados por escrituras cuyo bitmap y tabla todavia no se
+    // guardaron; van al disco despues de sus bloques, en flush_data
+    pending_inodes: HashSet<u32>,
+    writeback_cache: bool,
+    read_only: bool,
+    // modo sellado: checksums medidos al montar que toda lectura debe respetar
+    seal: Option<SealIndex>,
+    // montaje degradado: los bloques de datos sin qr son EIO (ver missing.rs)
+    degraded: bool,
+    // registro de auditoria opcional y uid de quien pide la operacion en curso
+    audit: Option<AuditLog>,
+    caller_uid: u32,
+    // problemas encontrados al cargar (slots de inodos, directorio raiz)
+    load_problems: Vec<String>,
+    // ultima vez que se comparo meta_generation con el disco (ver refresh_metadata)
+    last_recheck: Instant,
+    recheck_interval: Duration,
+    shared_disk: bool,
+    // clave maestra abierta con la frase: sin ella los archivos cifrados no
+    // se leen ni se escriben, y los nuevos se crean sin cifrar
+    master_key: Option<Key>,
+    // maestra nueva de un rekey a medias (segunda ranura del superblock)
+    next_key: Option<Key>,
+    // fallas de lectura por bloque: el del storage si tiene (ver health.rs)
+    health: Arc<HealthMonitor>,
+    // avisos de cambios con los demas que usan el disco (ver events.rs)
+    events: Option<EventLink>,
 }
 
 impl<B: BlockStorage + 'static> QrfsFilesystem<B> {
     pub fn new(storage: Arc<B>) -> Result<Self, crate::errors::QrfsError> {
         // leer superblock
-        let sb_data = storage.read_block(0)?;
-        let superblock: Superblock = bincode::deserialize(&sb_data)
-            .map_err(|_| crate::errors::QrfsError::Other("bloque 0 ilegible".into()))?;
+        let superblock = read_superblock(storage.as_ref())?;
 
-        if !superblock.is_valid() {
+        if superblock.magic != QRFS_MAGIC {
             return Err(crate::errors::QrfsError::Other("firma invalida".into()));
         }
+        // una version menor mas nueva se lee pero no se escribe
+        let version_read_only = superblock.version_access()? == VersionAccess::ReadOnly;
+        if version_read_only {
+            eprintln!(
+                "qrfs: warning: el disco es de la version {}, mas nueva que esta; se monta en SOLO LECTURA",
+                superblock.version_string()
+            );
+        }
 
-        // cargar bitmap
-        let mut bitmap = Vec::new();
-        for i in 0..superblock.free_map_blocks {
-            let data = storage.read_block(superblock.free_map_start + i)?;
-            bitmap.extend_from_slice(&data);
+        // cargar bitmap (un solo buffer, los backends que pueden leen sin copias extra)
+        let block_size = storage.block_size();
+        let mut bitmap = vec![0u8; superblock.free_map_blocks as usize * block_size];
+        for (i, chunk) in bitmap.chunks_mut(block_size).enumerate() {
+            storage.read_block_into(superblock.free_map_start + i as u32, chunk)?;
         }
-        let total_bytes = (superblock.total_blocks as usize + 7) / 8;
+        let total_bytes = (superblock.total_blocks as usize).div_ceil(8);
         if bitmap.len() > total_bytes {
             bitmap.truncate(total_bytes);
         }
 
-        // cargar inodos
-        let mut inodes = HashMap::new();
-        let mut inode_buffer = Vec::new();
+        // cargar inodos (tabla fija de mkfs + extension encadenada)
+        let mut inodes = HashMap::new();
+        let mut load_problems = Vec::new();
+        let inode_ext_blocks = match inode_ext_chain(storage.as_ref(), &superblock) {
+            Ok(chain) => chain,
+            Err(e) => {
+                eprintln!("qrfs: warning: {e}");
+                load_problems.push(e.to_string());
+                Vec::new()
+            }
+        };
+        let mut table = InodeTableReader::new(&superblock, block_size, &inode_ext_blocks, |id, buf| {
+            storage.read_block_into(id, buf)
+        });
+        for slot in table.by_ref() {
+            match slot {
+                Ok(inode) => {
+                    if inode.id == 0 || inode.mode != 0 {
+                        inodes.insert(inode.id, inode);
+                    }
+                }
+                // una tabla con encabezados rotos no se monta ni en solo lectura:
+                // los inodos que faltarian no se distinguen de los libres
+                Err(e) if matches!(e.error, crate::errors::QrfsError::Corrupt(_)) => {
+                    return Err(crate::errors::QrfsError::Corrupt(e.to_string()));
+                }
+                Err(e) => {
+                    eprintln!("qrfs: warning: {e}");
+                    load_problems.push(e.to_string());
+                }
+            }
+        }
+
+        let inode_table_generation = table.generation().unwrap_or(0);
+        let health = storage.health().unwrap_or_default();
+
+        // los directorios no se leen aca: se cargan recien cuando se usan
+        Ok(Self {
+            storage,
+            superblock,
+            inodes,
+            inode_ext_blocks,
+            inode_table_generation,
+            bitmap,
+            dir_cache: HashMap::new(),
+            dir_generations: HashMap::new(),
+            dir_parents: HashMap::new(),
+            dirty_blocks: HashMap::new(),
+            max_dirty_blocks: DEFAULT_MAX_DIRTY_BLOCKS,
+            pending_inodes: HashSet::new(),
+            writeback_cache: false,
+            read_only: version_read_only,
+            seal: None,
+            degraded: false,
+            audit: None,
+            caller_uid: current_uid(),
+            load_problems,
+            last_recheck: Instant::now(),
+            recheck_interval: DEFAULT_METADATA_RECHECK,
+            shared_disk: false,
+            master_key: None,
+            next_key: None,
+            health,
+            events: None,
+        })
+    }
+
+    // cada cuanto se buscan cambios hechos por otros en el disco (ZERO = siempre)
+    pub fn with_metadata_recheck(mut self, interval: Duration) -> Self {
+        self.recheck_interval = interval;
+        self
+    }
+
+    // si otro montaje o herramienta (servidor, import) hizo commits desde la
+    // ultima lectura, vuelve a cargar superblock, bitmap e inodos y descarta
+    // los directorios en cache. con escrituras pendientes no se recarga: se
+    // perderian, y las de este montaje pisan a las otras al guardarse.
+    // no se llama dentro de una transaccion, solo al empezar una operacion
+    // un aviso de otro en el bus hace mirar ya, sin esperar el intervalo
+    pub(crate) fn refresh_metadata(&mut self) -> Result<bool, crate::errors::QrfsError> {
+        let announced = self.events.as_ref().is_some_and(EventLink::others_changed);
+        if self.seal.is_some() || (!announced && self.last_recheck.elapsed() < self.recheck_interval) {
+            return Ok(false);
+        }
+        self.reload_if_changed()
+    }
+
+    fn reload_if_changed(&mut self) -> Result<bool, crate::errors::QrfsError> {
+        self.last_recheck = Instant::now();
+        let on_disk = read_superblock(self.storage.as_ref())?;
+        if on_disk.meta_generation == self.superblock.meta_generation {
+            return Ok(false);
+        }
+        if !self.pending_inodes.is_empty() || !self.dirty_blocks.is_empty() {
+            eprintln!("qrfs: warning: el disco cambio afuera de este montaje con escrituras pendientes");
+            return Ok(false);
+        }
+
+        // fresh no tiene nada pendiente: al soltarlo no escribe nada
+        let mut fresh = Self::new(self.storage.clone())?;
+        if let Some(events) = &self.events {
+            let mut inodes: Vec<u32> = self
+                .inodes
+                .keys()
+                .chain(fresh.inodes.keys())
+                .filter(|id| self.inodes.get(id) != fresh.inodes.get(id))
+                .copied()
+                .collect();
+            inodes.sort();
+            inodes.dedup();
+            events.publish(FsEvent::Reloaded { inodes });
+        }
+        std::mem::swap(&mut self.superblock, &mut fresh.superblock);
+        std::mem::swap(&mut self.inodes, &mut fresh.inodes);
+        std::mem::swap(&mut self.inode_ext_blocks, &mut fresh.inode_ext_blocks);
+        std::mem::swap(&mut self.bitmap, &mut fresh.bitmap);
+        std::mem::swap(&mut self.load_problems, &mut fresh.load_problems);
+        self.inode_table_generation = fresh.inode_table_generation;
+        // otro lo pudo haber pasado a una version mas nueva
+        self.read_only |= fresh.read_only;
+        self.dir_cache.clear();
+        self.dir_generations.clear();
+        self.dir_parents.clear();
+        println!(
+            "debug: metadata recargada (cambios de otro proceso, generacion {})",
+            self.superblock.meta_generation
+        );
+        Ok(true)
+    }
+
+    // disco compartido: antes de cada cambio de metadata se guarda lo
+    // pendiente y se vuelve a leer lo que hayan escrito los demas, asi la
+    // transaccion parte del estado actual del disco
+    fn revalidate_shared(&mut self) -> Result<(), crate::errors::QrfsError> {
+        self.flush_data()?;
+        self.reload_if_changed()?;
+        Ok(())
+    }
+
+    // refresh_metadata para el comienzo de lookup/readdir: si el disco no se
+    // puede leer ahora, se sigue con lo que hay en memoria
+    pub(crate) fn recheck_metadata(&mut self) {
+        if let Err(e) = self.refresh_metadata() {
+            eprintln!("qrfs: warning: no se pudo revisar el superblock: {e}");
+        }
+    }
+
+    // publica en bus lo que cambie este filesystem y recarga la metadata
+    // cuando otro avisa que cambio algo
+    pub fn with_events(mut self, bus: Arc<EventBus>) -> Self {
+        self.events = Some(EventLink::new(bus));
+        self
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn events(&self) -> Option<&EventLink> {
+        self.events.as_ref()
+    }
+
+    pub(crate) fn publish(&self, event: FsEvent) {
+        if let Some(events) = &self.events {
+            events.publish(event);
+        }
+    }
+
+    // getattr y open: si otro avisa en el bus que cambio algo se recarga ya,
+    // sin esperar al proximo lookup. sin avisos no se toca el disco
+    #[cfg(unix)]
+    fn catch_up(&mut self) {
+        if self.seal.is_none() && self.events.as_ref().is_some_and(EventLink::others_changed) {
+            if let Err(e) = self.reload_if_changed() {
+                eprintln!("qrfs: warning: no se pudo revisar el superblock: {e}");
+            }
+        }
+    }
+
+    // cache de escritura del kernel: junta escrituras chicas en paginas.
+    // solo es seguro si nadie mas modifica el disco mientras esta montado
+    pub fn with_writeback_cache(mut self, enabled: bool) -> Self {
+        self.writeback_cache = enabled;
+        self
+    }
+
+    // otro proceso escribe el mismo disco (--force-shared): cada transaccion
+    // revalida contra el superblock antes de empezar y ninguna se difiere
+    pub fn with_shared_disk(mut self, shared: bool) -> Self {
+        self.shared_disk = shared;
+        self
+    }
+
+    // cuantos bloques sucios se aguantan antes de codificarlos (0 = ninguno)
+    pub fn with_max_dirty_blocks(mut self, max: usize) -> Self {
+        self.max_dirty_blocks = max;
+        self
+    }
+
+    // monta en solo lectura: el kernel rechaza las escrituras y la api tambien
+    // (un disco de una version mas nueva queda en solo lectura igual)
+    pub fn with_read_only(mut self, enabled: bool) -> Self {
+        self.read_only = enabled || matches!(self.superblock.version_access(), Ok(VersionAccess::ReadOnly));
+        self
+    }
+
+    // archivo sellado: mide todos los bloques en uso y pasa a solo lectura;
+    // desde aca cada lectura se compara con lo medido
+    pub fn with_sealed(mut self) -> Self {
+        let mut blocks: Vec<BlockId> = self
+            .inodes
+            .values()
+            .flat_map(|inode| inode.blocks.iter().copied())
+            .collect();
+        blocks.sort();
+        blocks.dedup();
+
+        let seal = SealIndex::measure(self.storage.as_ref(), &blocks);
+        println!("qrfs: sellado: {} bloques medidos", blocks.len());
+        self.seal = Some(seal);
+        self.read_only = true;
+        self
+    }
+
+    // el disco tiene clave maestra: hace falta la frase para leer lo cifrado
+    pub fn is_encrypted(&self) -> bool {
+        !self.superblock.key_slot.is_empty()
+    }
+
+    // abre la clave maestra del disco con la frase: desde aca se leen los
+    // archivos cifrados y cada archivo nuevo recibe su propia clave
+    pub fn with_passphrase(mut self, passphrase: &str) -> Result<Self, crate::errors::QrfsError> {
+        self.unlock(passphrase)?;
+        Ok(self)
+    }
+
+    pub fn unlock(&mut self, passphrase: &str) -> Result<(), crate::errors::QrfsError> {
+        let slots = self.key_slots()?;
+        // a mitad de un rekey la frase puede abrir la maestra vieja, la nueva
+        // o las dos (si no cambio)
+        let master = crypt::open_master(&slots[0], passphrase);
+        self.next_key = slots.get(1).and_then(|slot| crypt::open_master(slot, passphrase).ok());
+        match master {
+            Ok(key) => self.master_key = Some(key),
+            Err(e) if self.next_key.is_none() => return Err(e),
+            Err(_) => {}
+        }
+        Ok(())
+    }
+
+    fn key_slots(&self) -> Result<Vec<Vec<u8>>, crate::errors::QrfsError> {
+        if self.superblock.key_slot.is_empty() {
+            return Err(crate::errors::QrfsError::NotFound(
+                "el disco no tiene clave maestra (ver mkfs.qrfs --encrypt)".into(),
+            ));
+        }
+        Ok(crypt::key_slots(&self.superblock.key_slot)?
+            .into_iter()
+            .map(|slot| slot.to_vec())
+            .collect())
+    }
+
+    fn set_key_slot(&mut self, slot: Vec<u8>) -> Result<(), crate::errors::QrfsError> {
+        self.transaction(|fs, txn| {
+            txn.touch_superblock(&fs.superblock, &fs.inode_ext_blocks);
+            fs.superblock.key_slot = slot;
+            Ok(())
+        })
+    }
+
+    // cambia solo la frase: la maestra (y con ella todos los archivos) sigue igual
+    pub(crate) fn change_passphrase(
+        &mut self,
+        old_passphrase: &str,
+        new_passphrase: &str,
+        iterations: u32,
+    ) -> Result<(), crate::errors::QrfsError> {
+        self.check_writable()?;
+        let slots = self.key_slots()?;
+        if slots.len() > 1 {
+            return Err(crate::errors::QrfsError::Other(
+                "hay un rekey a medias; termine el rekey primero".into(),
+            ));
+        }
+        let master = crypt::open_master(&slots[0], old_passphrase)?;
+        self.set_key_slot(crypt::seal_master(&master, new_passphrase, iterations)?)?;
+        self.master_key = Some(master);
+        Ok(())
+    }
+
+    // rotacion de claves (rekey): la maestra nueva va en una segunda ranura
+    // del superblock hasta que todos los archivos cifrados pasan a ella. si ya
+    // habia un rekey a medias se retoma (la frase nueva abre la segunda ranura)
+    pub(crate) fn begin_rekey(
+        &mut self,
+        old_passphrase: &str,
+        new_passphrase: &str,
+        iterations: u32,
+    ) -> Result<(), crate::errors::QrfsError> {
+        self.check_writable()?;
+        let slots = self.key_slots()?;
+        let master = crypt::open_master(&slots[0], old_passphrase)?;
+        let next = match slots.get(1) {
+            Some(slot) => crypt::open_master(slot, new_passphrase)?,
+            None => {
+                let next = crypt::random_key()?;
+                let mut data = slots[0].clone();
+                data.extend(crypt::seal_master(&next, new_passphrase, iterations)?);
+                self.set_key_slot(data)?;
+                next
+            }
+        };
+        self.master_key = Some(master);
+        self.next_key = Some(next);
+        Ok(())
+    }
+
+    // archivos cifrados que todavia usan la maestra vieja
+    pub(crate) fn rekey_pending(&self) -> Vec<u32> {
+        let Some(next) = &self.next_key else {
+            return Vec::new();
+        };
+        let mut ids: Vec<u32> = self
+            .inodes
+            .values()
+            .filter(|inode| !inode.key.is_empty() && crypt::unwrap_key(next, &inode.key).is_err())
+            .map(|inode| inode.id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    // pasa un archivo a una clave nueva envuelta con la maestra nueva: los
+    // bloques se copian recifrados de a uno a bloques libres y el inodo cambia
+    // de bloques y de clave en la misma transaccion, asi un corte deja el
+    // archivo entero con la clave vieja. devuelve los bloques copiados
+    pub(crate) fn rekey_inode(&mut self, id: u32) -> Result<usize, crate::errors::QrfsError> {
+        self.check_writable()?;
+        let next = self
+            .next_key
+            .ok_or_else(|| crate::errors::QrfsError::Other("no hay un rekey en curso".into()))?;
+        if !self.rekey_pending().contains(&id) {
+            return Ok(0);
+        }
+        // lo que esta en memoria primero tiene que llegar a su qr
+        self.flush_data()?;
+
+        let old_key = self.file_key(id)?.ok_or_else(|| {
+            crate::errors::QrfsError::Other(format!("el inodo {} no esta cifrado", id))
+        })?;
+        let new_key = crypt::random_key()?;
+        let wrapped = crypt::wrap_key(&next, &new_key)?;
+        let old_blocks = self.inodes[&id].blocks.clone();
+
+        self.transaction(|fs, txn| {
+            let new_blocks = fs.txn_reserve_blocks_for(txn, id, old_blocks.len()).ok_or_else(|| {
+                crate::errors::QrfsError::NoSpace(format!(
+                    "el inodo {} necesita {} bloques libres para recifrarse",
+                    id,
+                    old_blocks.len()
+                ))
+            })?;
+            for (index, (&from, &to)) in old_blocks.iter().zip(&new_blocks).enumerate() {
+                let plain = crypt::decrypt_block(&old_key, index as u64, &fs.read_data_block(from)?)?;
+                fs.storage.write_block(to, &crypt::encrypt_block(&new_key, index as u64, &plain)?)?;
+            }
+            if let Some(inode) = fs.txn_inode_mut(txn, id) {
+                inode.blocks = new_blocks;
+                inode.key = wrapped;
+            }
+            txn.freed.extend(old_blocks.iter().copied());
+            Ok(old_blocks.len())
+        })
+    }
+
+    // con todos los archivos rotados la maestra nueva queda como unica
+    pub(crate) fn finish_rekey(&mut self) -> Result<(), crate::errors::QrfsError> {
+        let pending = self.rekey_pending().len();
+        if pending > 0 {
+            return Err(crate::errors::QrfsError::Other(format!(
+                "faltan {} archivos por recifrar",
+                pending
+            )));
+        }
+        let slots = self.key_slots()?;
+        let next = self
+            .next_key
+            .ok_or_else(|| crate::errors::QrfsError::Other("no hay un rekey en curso".into()))?;
+        self.set_key_slot(slots[1].clone())?;
+        self.master_key = Some(next);
+        self.next_key = None;
+        Ok(())
+    }
+
+    // reparte la maestra en fragmentos para imprimir aparte de los datos (ver
+    // shamir.rs). a mitad de un rekey no: la maestra esta por cambiar
+    pub(crate) fn master_key_shards(
+        &self,
+        passphrase: &str,
+        threshold: u8,
+        count: u8,
+    ) -> Result<Vec<KeyShard>, crate::errors::QrfsError> {
+        let slots = self.key_slots()?;
+        if slots.len() > 1 {
+            return Err(crate::errors::QrfsError::Other(
+                "hay un rekey a medias; termine el rekey primero".into(),
+            ));
+        }
+        shamir::split(&crypt::open_master(&slots[0], passphrase)?, threshold, count)
+    }
+
+    // la maestra rearmada tiene que abrir las claves de los archivos cifrados
+    // (los fragmentos de antes de un rekey ya no sirven)
+    fn recovered_master(&self, shards: &[KeyShard]) -> Result<Key, crate::errors::QrfsError> {
+        self.key_slots()?;
+        let master = shamir::combine(shards)?;
+        let mut wrapped = self.inodes.values().filter(|inode| !inode.key.is_empty()).peekable();
+        if wrapped.peek().is_some() && !wrapped.any(|inode| crypt::unwrap_key(&master, &inode.key).is_ok()) {
+            return Err(crate::errors::QrfsError::NotPermitted(
+                "los fragmentos no son de la clave actual de este disco".into(),
+            ));
+        }
+        Ok(master)
+    }
+
+    // abre el disco con los fragmentos en vez de la frase
+    pub fn unlock_with_shards(&mut self, shards: &[KeyShard]) -> Result<(), crate::errors::QrfsError> {
+        self.master_key = Some(self.recovered_master(shards)?);
+        self.next_key = None;
+        Ok(())
+    }
+
+    // frase olvidada: la maestra rearmada se vuelve a sellar con una frase nueva
+    pub(crate) fn recover_passphrase(
+        &mut self,
+        shards: &[KeyShard],
+        new_passphrase: &str,
+        iterations: u32,
+    ) -> Result<(), crate::errors::QrfsError> {
+        self.check_writable()?;
+        if self.key_slots()?.len() > 1 {
+            return Err(crate::errors::QrfsError::Other(
+                "hay un rekey a medias; termine el rekey con la frase primero".into(),
+            ));
+        }
+        let master = self.recovered_master(shards)?;
+        self.set_key_slot(crypt::seal_master(&master, new_passphrase, iterations)?)?;
+        self.master_key = Some(master);
+        Ok(())
+    }
+
+    // rotulo del disco; uno ilegible se avisa y cuenta como sin rotulo
+    pub fn label(&self) -> Option<DiskLabel> {
+        read_label(self.storage.as_ref(), &self.superblock).unwrap_or_else(|e| {
+            eprintln!("qrfs: warning: {e}");
+            None
+        })
+    }
+
+    // rotula el disco. el rotulo va a un bloque nuevo de la zona fija y el
+    // viejo se libera en el mismo commit (un corte deja el anterior); el uuid
+    // se sortea la primera vez (de la semilla en un disco determinista) y
+    // despues se conserva
+    pub(crate) fn set_label(&mut self, label: &str) -> Result<DiskLabel, crate::errors::QrfsError> {
+        check_label(label)?;
+        let uuid = match self.label() {
+            Some(old) => old.uuid,
+            None => {
+                // uuid version 4
+                let mut uuid = [0u8; 16];
+                crypt::disk_bytes(self.superblock.deterministic, "uuid", &mut uuid)?;
+                uuid[6] = (uuid[6] & 0x0f) | 0x40;
+                uuid[8] = (uuid[8] & 0x3f) | 0x80;
+                uuid
+            }
+        };
+        let disk_label = DiskLabel {
+            magic: LABEL_BLOCK_MAGIC,
+            uuid,
+            label: label.to_string(),
+        };
+        let block = encode_label(&disk_label, self.superblock.block_size as usize)?;
+        self.transaction(|fs, txn| {
+            let new = fs
+                .txn_reserve_blocks_in(txn, 1, true)
+                .and_then(|blocks| blocks.first().copied())
+                .ok_or_else(|| crate::errors::QrfsError::NoSpace("sin bloques para el rotulo".into()))?;
+            fs.storage.write_block(new, &block)?;
+            txn.touch_superblock(&fs.superblock, &fs.inode_ext_blocks);
+            if fs.superblock.label_block != 0 {
+                txn.freed.push(fs.superblock.label_block);
+            }
+            fs.superblock.label_block = new;
+            Ok(())
+        })?;
+        Ok(disk_label)
+    }
+
+    // crea la clave maestra y la guarda en el superblock envuelta con la frase
+    pub(crate) fn enable_encryption(
+        &mut self,
+        passphrase: &str,
+        iterations: u32,
+    ) -> Result<(), crate::errors::QrfsError> {
+        self.check_writable()?;
+        if self.superblock.inode_format < 4 {
+            return Err(crate::errors::QrfsError::Unimplemented(format!(
+                "la tabla de inodos de este disco no guarda claves (formato {})",
+                self.superblock.inode_format
+            )));
+        }
+        if !self.superblock.key_slot.is_empty() {
+            return Err(crate::errors::QrfsError::AlreadyExists(
+                "el disco ya tiene clave maestra".into(),
+            ));
+        }
+        let master = crypt::random_key()?;
+        self.set_key_slot(crypt::seal_master(&master, passphrase, iterations)?)?;
+        self.master_key = Some(master);
+        Ok(())
+    }
+
+    // clave de datos de un inodo (None si no esta cifrado)
+    fn file_key(&self, id: u32) -> Result<Option<Key>, crate::errors::QrfsError> {
+        let wrapped = match self.inodes.get(&id) {
+            Some(inode) if !inode.key.is_empty() => &inode.key,
+            _ => return Ok(None),
+        };
+        if self.master_key.is_none() && self.next_key.is_none() {
+            return Err(crate::errors::QrfsError::NotPermitted(format!(
+                "el inodo {} esta cifrado; falta la frase del disco",
+                id
+            )));
+        }
+        // a mitad de un rekey cada archivo esta con una maestra o la otra
+        self.master_key
+            .iter()
+            .chain(&self.next_key)
+            .find_map(|master| crypt::unwrap_key(master, wrapped).ok())
+            .map(Some)
+            .ok_or_else(|| {
+                crate::errors::QrfsError::NotPermitted(format!(
+                    "la clave del inodo {} no abre con esta frase (rekey a medias?)",
+                    id
+                ))
+            })
+    }
+
+    // clave para compartir un archivo cifrado junto con sus qr
+    pub(crate) fn shared_file_key(&self, id: u32) -> Result<FileKey, crate::errors::QrfsError> {
+        let size = self
+            .inodes
+            .get(&id)
+            .map(|inode| inode.size)
+            .ok_or_else(|| crate::errors::QrfsError::NotFound(format!("inodo {}", id)))?;
+        match self.file_key(id)? {
+            Some(key) => Ok(FileKey { key, size }),
+            None => Err(crate::errors::QrfsError::Other(format!(
+                "el inodo {} no esta cifrado",
+                id
+            ))),
+        }
+    }
+
+    // disco a medio escanear: solo lectura, lo que falta da EIO y se lista en
+    // /.qrfs_missing. la metadata se sigue recargando a medida que llega
+    pub fn with_degraded(mut self) -> Self {
+        self.degraded = true;
+        self.read_only = true;
+        self
+    }
+
+    // archivos con bloques de datos que el storage no tiene, por inodo
+    pub fn missing_files(&mut self) -> Vec<MissingFile> {
+        // los nombres salen de los directorios; uno que no se puede leer deja
+        // sus archivos sin nombre en el informe
+        let mut ids: Vec<u32> = self.inodes.keys().copied().collect();
+        ids.sort();
+        for &id in &ids {
+            if self.inodes[&id].kind == InodeKind::Directory {
+                let _ = self.dir_entries(id);
+            }
+        }
+
+        let mut files = Vec::new();
+        for id in ids {
+            let inode = &self.inodes[&id];
+            let blocks: Vec<(u32, BlockId)> = inode
+                .blocks
+                .iter()
+                .enumerate()
+                .filter(|&(_, &blk)| !self.storage.has_block(blk))
+                .map(|(index, &blk)| (index as u32, blk))
+                .collect();
+            if !blocks.is_empty() {
+                files.push(MissingFile {
+                    inode: id,
+                    name: self.cached_name(id),
+                    size: inode.size,
+                    total_blocks: inode.blocks.len(),
+                    blocks,
+                });
+            }
+        }
+        files
+    }
+
+    // texto del informe de faltantes, None si no es un montaje degradado
+    pub fn missing_status(&mut self) -> Option<String> {
+        if !self.degraded {
+            return None;
+        }
+        let files = self.missing_files();
+        Some(report_text(&files, self.superblock.block_size as u64))
+    }
+
+    // instantanea (storage de snapshot.rs): solo lectura y toda la metadata en
+    // memoria desde ya, los directorios incluidos. no se recarga: el disco de
+    // abajo sigue cambiando pero la instantanea no
+    pub fn with_snapshot(mut self) -> Result<Self, crate::errors::QrfsError> {
+        let mut dirs: Vec<u32> = self
+            .inodes
+            .values()
+            .filter(|inode| inode.kind == InodeKind::Directory)
+            .map(|inode| inode.id)
+            .collect();
+        dirs.sort();
+        for id in dirs {
+            self.dir_entries(id)?;
+        }
+        self.recheck_interval = Duration::MAX;
+        self.read_only = true;
+        Ok(self)
+    }
+
+    // anota cada create/write/truncate/unlink/rename en el registro
+    pub fn with_audit_log(mut self, log: AuditLog) -> Self {
+        self.audit = Some(log);
+        self
+    }
+
+    // un fallo del registro no deshace la operacion (ya esta en disco)
+    fn audit(&mut self, op: &str, inode: u32, name: &str, detail: &str) {
+        let uid = self.caller_uid;
+        if let Some(log) = self.audit.as_mut() {
+            if let Err(e) = log.append(uid, op, inode, name, detail) {
+                eprintln!("qrfs: warning: no se pudo anotar '{op}' en la auditoria: {e}");
+            }
+        }
+    }
+
+    // nombre de un inodo en los directorios cargados (para la auditoria)
+    fn cached_name(&self, id: u32) -> String {
+        self.dir_cache
+            .values()
+            .flat_map(|entries| entries.iter())
+            .find(|(_, &(entry_id, _))| entry_id == id)
+            .map(|(name, _)| name.clone())
+            .unwrap_or_default()
+    }
+
+    // texto del archivo de estado, None si no esta sellado
+    pub fn seal_status(&self) -> Option<String> {
+        self.seal.as_ref().map(|seal| seal.status_text())
+    }
+
+    // fallas de decodificacion, checksums y reparaciones vistas desde que se
+    // abrio el disco
+    pub fn health_report(&self) -> HealthReport {
+        self.health.report()
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    // corre quick_check y, si encuentra problemas, pasa a solo lectura avisando
+    pub fn with_quick_check(self) -> Self {
+        let problems = self.quick_check();
+        if problems.is_empty() {
+            return self;
+        }
+
+        eprintln!("qrfs: warning: el disco tiene {} problemas:", problems.len());
+        for problem in problems.iter().take(10) {
+            eprintln!("qrfs: warning:   - {}", problem);
+        }
+        if problems.len() > 10 {
+            eprintln!("qrfs: warning:   ... y {} mas", problems.len() - 10);
+        }
+        eprintln!("qrfs: warning: se monta en SOLO LECTURA, corra fsck.qrfs para revisarlo");
+        self.with_read_only(true)
+    }
+
+    // verificacion rapida en memoria (sin leer bloques de datos):
+    // superblock vs almacenamiento, bitmap vs inodos y el directorio raiz
+    pub fn quick_check(&self) -> Vec<String> {
+        let mut problems = self.load_problems.clone();
+        let sb = &self.superblock;
+
+        // los mismos pasos que fsck (ver fsck.rs), con lo que ya esta cargado
+        problems.extend(crate::fsck::check_layout(sb, self.storage.block_size(), self.storage.total_blocks()));
+        let (links, _) = crate::fsck::check_links(sb, &self.bitmap, self.inodes.values(), &self.inode_ext_blocks);
+        problems.extend(links);
+
+        // directorio raiz
+        match self.inodes.get(&sb.root_inode) {
+            Some(root) if matches!(root.kind, InodeKind::Directory) => {}
+            Some(_) => problems.push("el inodo raiz no es un directorio".to_string()),
+            None => problems.push("falta el inodo raiz".to_string()),
+        }
+        // la raiz se lee aca mismo (sin pasar por la cache) solo para verificarla
+        match self.load_directory(sb.root_inode) {
+            Ok((entries, _)) => {
+                for entry in entries {
+                    if entry.name != "." && entry.name != ".." && !self.inodes.contains_key(&entry.inode_id) {
+                        problems.push(format!("'{}' apunta al inodo libre {}", entry.name, entry.inode_id));
+                    }
+                }
+            }
+            Err(e) => problems.push(format!("directorio raiz ilegible: {}", e)),
+        }
+
+        problems
+    }
+
+    // libera los bloques de datos marcados en el bitmap que ningun inodo usa
+    // (quedan si un commit se corta antes de liberar o de versiones viejas)
+    // conviene correrlo despues de quick_check, sobre un disco sano
+    pub fn reclaim_orphan_blocks(&mut self) -> Result<Vec<u32>, crate::errors::QrfsError> {
+        self.check_writable()?;
+
+        let owned: std::collections::HashSet<u32> = self
+            .inodes
+            .values()
+            .flat_map(|inode| inode.blocks.iter().copied())
+            .chain(self.inode_ext_blocks.iter().copied())
+            .chain(Some(self.superblock.label_block).filter(|&blk| blk != 0))
+            .collect();
+
+        let orphans: Vec<u32> = (self.superblock.data_block_start..self.superblock.total_blocks)
+            .filter(|blk| self.is_block_used(*blk) && !owned.contains(blk))
+            .collect();
+        if orphans.is_empty() {
+            return Ok(orphans);
+        }
+
+        self.transaction(|_, txn| {
+            txn.freed.extend_from_slice(&orphans);
+            Ok(())
+        })?;
+        Ok(orphans)
+    }
+
+    // libera los inodos que ningun directorio nombra (quedan si un create se
+    // corta despues de escribir la tabla de inodos y antes del directorio)
+    // como reclaim_orphan_blocks, conviene correrlo despues de quick_check
+    pub fn reclaim_orphan_inodes(&mut self) -> Result<Vec<u32>, crate::errors::QrfsError> {
+        self.check_writable()?;
+
+        let mut dirs: Vec<u32> = self
+            .inodes
+            .values()
+            .filter(|inode| matches!(inode.kind, InodeKind::Directory))
+            .map(|inode| inode.id)
+            .collect();
+        dirs.sort();
+        let mut named = std::collections::HashSet::from([self.superblock.root_inode]);
+        for dir_id in dirs {
+            named.extend(self.dir_entries(dir_id)?.values().map(|&(id, _)| id));
+        }
+
+        let mut orphans: Vec<u32> = self
+            .inodes
+            .keys()
+            .filter(|id| !named.contains(id))
+            .copied()
+            .collect();
+        if orphans.is_empty() {
+            return Ok(orphans);
+        }
+        orphans.sort();
+
+        self.transaction(|fs, txn| {
+            for &id in &orphans {
+                fs.free_inode(txn, id)?;
+            }
+            Ok(())
+        })?;
+        Ok(orphans)
+    }
+
+    pub(crate) fn is_block_used(&self, blk: u32) -> bool {
+        let byte = (blk / 8) as usize;
+        byte < self.bitmap.len() && (self.bitmap[byte] & (1 << (blk % 8))) != 0
+    }
+
+    // EPERM si los flags del inodo no dejan modificarlo. a un archivo
+    // append-only solo se le puede escribir (write = offset y datos) al final;
+    // con writeback el kernel reescribe la pagina entera desde antes del final,
+    // eso se acepta mientras los bytes que ya estaban no cambien
+    pub(crate) fn check_flags(
+        &self,
+        id: u32,
+        write: Option<(u64, &[u8])>,
+    ) -> Result<(), crate::errors::QrfsError> {
+        let Some(inode) = self.inodes.get(&id) else {
+            return Ok(());
+        };
+        if inode.flags & INODE_FLAG_IMMUTABLE != 0 {
+            return Err(crate::errors::QrfsError::NotPermitted(format!("inodo {} inmutable", id)));
+        }
+        if inode.flags & INODE_FLAG_APPEND != 0 {
+            let appends = match write {
+                Some((offset, data)) if offset <= inode.size => {
+                    let overlap = (inode.size - offset).min(data.len() as u64);
+                    overlap == 0 || self.read_data(id, offset, overlap)? == data[..overlap as usize]
+                }
+                _ => false,
+            };
+            if !appends {
+                return Err(crate::errors::QrfsError::NotPermitted(format!(
+                    "inodo {} solo admite escribir al final",
+                    id
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn inode_flags(&self, id: u32) -> Option<u32> {
+        self.inodes.get(&id).map(|inode| inode.flags)
+    }
+
+    // cambia los flags chattr de un inodo (INODE_FLAG_*); los internos (cola
+    // compartida, zona fija) no son de chattr y se conservan
+    pub(crate) fn set_inode_flags(&mut self, id: u32, flags: u32) -> Result<(), crate::errors::QrfsError> {
+        self.check_writable()?;
+        if self.superblock.inode_format == 0 {
+            return Err(crate::errors::QrfsError::Unimplemented(
+                "la tabla de inodos de este disco no guarda flags (formato 0)".into(),
+            ));
+        }
+        if !self.inodes.contains_key(&id) {
+            return Err(crate::errors::QrfsError::NotFound(format!("inodo {}", id)));
+        }
+        self.transaction(|fs, txn| {
+            if let Some(inode) = fs.txn_inode_mut(txn, id) {
+                inode.flags = (flags & !INTERNAL_FLAGS) | (inode.flags & INTERNAL_FLAGS);
+            }
+            Ok(())
+        })
+    }
+
+    // fecha de creacion (macos la cambia con setattr, p.ej. SetFile -d)
+    #[cfg(unix)]
+    pub(crate) fn set_created_at(&mut self, id: u32, secs: u64) -> Result<(), crate::errors::QrfsError> {
+        self.check_writable()?;
+        self.check_flags(id, None)?;
+        self.transaction(|fs, txn| {
+            if let Some(inode) = fs.txn_inode_mut(txn, id) {
+                inode.created_at = secs;
+            }
+            Ok(())
+        })
+    }
+
+    fn check_writable(&self) -> Result<(), crate::errors::QrfsError> {
+        if self.read_only {
+            return Err(crate::errors::QrfsError::ReadOnly("montado en solo lectura".into()));
+        }
+        Ok(())
+    }
+
+    // monta con el backend de la plataforma (ver backend.rs)
+    pub fn mount(self, mountpoint: &Path) -> Result<(), crate::errors::QrfsError> {
+        crate::backend::PLATFORM_BACKEND.mount(self, mountpoint)
+    }
+
+    // monta en un hilo aparte y devuelve enseguida
+    // el disco se desmonta al soltar (drop) la sesion devuelta
+    pub fn spawn_mount(
+        self,
+        mountpoint: &Path,
+    ) -> Result<MountSession, crate::errors::QrfsError> {
+        crate::backend::PLATFORM_BACKEND.spawn_mount(self, mountpoint)
+    }
+
+    // lee los bloques de datos de un inodo (directorio) y devuelve la lista de
+    // archivos y la generacion con la que se escribio
+    fn load_directory(
+        &self,
+        inode_id: u32,
+    ) -> Result<(Vec<DirectoryEntry>, u32), crate::errors::QrfsError> {
+        let inode = match self.inodes.get(&inode_id) {
+            Some(i) => i,
+            None => return Ok((Vec::new(), 0)),
+        };
+
+        let mut raw_data = Vec::new();
+        for &block_id in &inode.blocks {
+            let block = self.storage.read_block(block_id)?;
+            if let Some(seal) = &self.seal {
+                seal.verify(block_id, &block)?;
+            }
+            raw_data.extend_from_slice(&block);
+        }
+
+        if raw_data.is_empty() {
+            return Ok((Vec::new(), 0));
+        }
+
+        decode_directory(
+            inode,
+            &raw_data,
+            self.superblock.block_size as usize,
+            self.superblock.dir_format,
+        )
+    }
+
+    // entradas de un directorio (sin "." ni ".."), leyendolo la primera vez
+    fn dir_entries(
+        &mut self,
+        dir_id: u32,
+    ) -> Result<&DirEntries, crate::errors::QrfsError> {
+        if !self.dir_cache.contains_key(&dir_id) {
+            match self.inodes.get(&dir_id) {
+                Some(inode) if matches!(inode.kind, InodeKind::Directory) => {}
+                Some(_) => {
+                    return Err(crate::errors::QrfsError::NotADirectory(format!(
+                        "inodo {} no es un directorio",
+                        dir_id
+                    )))
+                }
+                None => {
+                    return Err(crate::errors::QrfsError::NotFound(format!("inodo {}", dir_id)))
+                }
+            }
+
+            let (entries, generation) = self.load_directory(dir_id)?;
+            if let Some(parent) = entries.iter().find(|entry| entry.name == "..") {
+                self.dir_parents.insert(dir_id, parent.inode_id);
+            }
+            let entries: DirEntries = entries
+                .into_iter()
+                .filter(|entry| entry.name != "." && entry.name != "..")
+                .map(|entry| (entry.name, (entry.inode_id, entry.kind)))
+                .collect();
+            println!(
+                "debug: directorio {} cargado. {} archivos encontrados.",
+                dir_id,
+                entries.len()
+            );
+            self.dir_cache.insert(dir_id, entries);
+            self.dir_generations.insert(dir_id, generation);
+        }
+        Ok(&self.dir_cache[&dir_id])
+    }
+
+    // inodo del nombre dentro de un directorio
+    fn dir_lookup(&mut self, dir_id: u32, name: &str) -> Result<Option<u32>, crate::errors::QrfsError> {
+        Ok(self.dir_entries(dir_id)?.get(name).map(|&(id, _)| id))
+    }
+
+    // serializa las entradas en cache de un directorio; si hacen falta bloques
+    // nuevos se reservan dentro de la transaccion y los que sobran se anotan
+    // para liberar (todavia no se escribe nada)
+    fn prepare_directory(
+        &mut self,
+        txn: &mut MetadataTransaction,
+        dir_id: u32,
+    ) -> Result<(Vec<u32>, Vec<u8>), crate::errors::QrfsError> {
+        let (data, total_size) = self.encode_cached_directory(dir_id)?;
+
+        let mut current_blocks = match self.inodes.get(&dir_id) {
+            Some(inode) => inode.blocks.clone(),
+            None => return Err(crate::errors::QrfsError::NotFound(format!("inodo {}", dir_id))),
+        };
+
+        // los bloques que faltan se reservan todos juntos o ninguno
+        let block_size = self.superblock.block_size as usize;
+        let needed_blocks = data.len().div_ceil(block_size);
+        if current_blocks.len() < needed_blocks {
+            let missing = needed_blocks - current_blocks.len();
+            let new_blocks = self.txn_reserve_blocks_for(txn, dir_id, missing).ok_or_else(|| {
+                crate::errors::QrfsError::NoSpace(format!(
+                    "disco lleno guardando directorio ({} bloques mas)",
+                    missing
+                ))
+            })?;
+            current_blocks.extend(new_blocks);
+        }
+        // los que sobran se liberan al final del commit, con el directorio ya
+        // escrito en los que quedan
+        if current_blocks.len() > needed_blocks {
+            txn.freed.extend(current_blocks.drain(needed_blocks..));
+        }
+
+        let now = self.superblock.timestamp();
+        if let Some(dir_inode) = self.txn_inode_mut(txn, dir_id) {
+            dir_inode.blocks = current_blocks.clone();
+            dir_inode.size = total_size;
+            dir_inode.modified_at = now;
+        }
+
+        Ok((current_blocks, data))
+    }
+
+    // serializa las entradas en cache de un directorio con una generacion nueva
+    // y devuelve los bytes para sus bloques y el size del inodo
+    fn encode_cached_directory(
+        &mut self,
+        dir_id: u32,
+    ) -> Result<(Vec<u8>, u64), crate::errors::QrfsError> {
+        let parent_id = self.parent_of(dir_id);
+
+        let mut entries = Vec::new();
+
+        entries.push(DirectoryEntry {
+            name: ".".to_string(),
+            inode_id: dir_id,
+            kind: InodeKind::Directory,
+        });
+        entries.push(DirectoryEntry {
+            name: "..".to_string(),
+            inode_id: parent_id,
+            kind: InodeKind::Directory,
+        });
+
+        // en orden de nombre: el mismo directorio da siempre los mismos bytes
+        let mut cached: Vec<_> = self.dir_cache.get(&dir_id).cloned().unwrap_or_default().into_iter().collect();
+        cached.sort_by(|a, b| a.0.cmp(&b.0));
+        for (name, (id, kind)) in cached {
+            entries.push(DirectoryEntry {
+                name,
+                inode_id: id,
+                kind,
+            });
+        }
+
+        // una generacion salteada por un rollback no molesta: solo se compara
+        // entre los bloques de una misma escritura
+        let generation = self.dir_generations.get(&dir_id).map_or(1, |g| g.wrapping_add(1));
+        self.dir_generations.insert(dir_id, generation);
+        encode_directory(
+            dir_id,
+            generation,
+            &entries,
+            self.superblock.block_size as usize,
+            self.superblock.dir_format,
+        )
+    }
+
+    // directorio que contiene a dir_id: el que lo nombra en la cache (despues
+    // de un mkdir o un rename), si no el '..' que tenia en disco, si no la raiz
+    fn parent_of(&self, dir_id: u32) -> u32 {
+        let root = self.superblock.root_inode;
+        if dir_id == root {
+            return root;
+        }
+        self.dir_cache
+            .iter()
+            .find(|(_, entries)| entries.values().any(|&(id, _)| id == dir_id))
+            .map(|(&parent, _)| parent)
+            .or_else(|| self.dir_parents.get(&dir_id).copied())
+            .unwrap_or(root)
+    }
+
+    // escribe los bloques de un directorio preparados por prepare_directory
+    fn write_directory(
+        &self,
+        blocks: &[u32],
+        data: &[u8],
+    ) -> Result<(), crate::errors::QrfsError> {
+        let block_size = self.superblock.block_size as usize;
+
+        let mut offset = 0;
+        for &block_id in blocks {
+            let mut chunk = vec![0u8; block_size];
+
+            if offset < data.len() {
+                let end = std::cmp::min(offset + block_size, data.len());
+                let slice = &data[offset..end];
+                chunk[..slice.len()].copy_from_slice(slice);
+                offset += slice.len();
+            }
+
+            self.storage.write_block(block_id, &chunk)?;
+        }
+
+        Ok(())
+    }
+
+    // corre una operacion de metadata como transaccion: si f o el commit fallan
+    // se restaura el estado en memoria, asi no quedan bloques ni inodos a medias
+    fn transaction<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self, &mut MetadataTransaction) -> Result<T, crate::errors::QrfsError>,
+    ) -> Result<T, crate::errors::QrfsError> {
+        self.check_writable()?;
+        // la tabla que escribe el commit ya incluye lo de las escrituras
+        // diferidas: sus bloques y su bitmap tienen que llegar antes
+        if self.shared_disk {
+            self.revalidate_shared()?;
+        } else if !self.pending_inodes.is_empty() {
+            self.flush_data()?;
+        }
+
+        let mut txn = MetadataTransaction::new();
+        let result = f(self, &mut txn).and_then(|value| self.commit(&mut txn).map(|_| value));
+        if result.is_err() {
+            self.rollback(txn);
+        }
+        result
+    }
+
+    // como transaction, pero si f solo cambio bloques y tamaños de inodos que
+    // ya existian no se escribe nada: bitmap y tabla quedan pendientes hasta
+    // flush_data (fsync, release, demasiados bloques sucios)
+    fn deferred_transaction<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self, &mut MetadataTransaction) -> Result<T, crate::errors::QrfsError>,
+    ) -> Result<T, crate::errors::QrfsError> {
+        if self.shared_disk {
+            return self.transaction(f);
+        }
+        self.check_writable()?;
+
+        let mut txn = MetadataTransaction::new();
+        let value = match f(self, &mut txn) {
+            Ok(value) => value,
+            Err(e) => {
+                self.rollback(txn);
+                return Err(e);
+            }
+        };
+        let deferrable = txn.dirs_before.is_empty()
+            && txn.superblock_before.is_none()
+            && txn.freed.is_empty()
+            && txn.inodes_before.values().all(|before| before.is_some());
+        if deferrable {
+            self.pending_inodes.extend(txn.inodes_before.keys().copied());
+            return Ok(value);
+        }
+        match self.commit(&mut txn) {
+            Ok(()) => Ok(value),
+            Err(e) => {
+                self.rollback(txn);
+                Err(e)
+            }
+        }
+    }
+
+    // escribe la transaccion en orden fijo:
+    //   1. bitmap con los bloques reservados (los liberados siguen ocupados)
+    //   2. si hay inodos nuevos o directorios que crecen: tabla de inodos con
+    //      los inodos nuevos y los bloques agregados a los directorios, el
+    //      resto como estaba (y superblock si la tabla crecio)
+    //   3. bloques de los directorios modificados
+    //   4. tabla de inodos final (con su extension)
+    //   5. superblock con el contador de commits subido (otro montaje o
+    //      herramienta sobre el mismo disco ve que su cache quedo vieja) y la
+    //      tabla crecida si no se escribio en el paso 2
+    //   6. bitmap con los bloques liberados
+    // un inodo se escribe antes que la entrada que lo nombra y se borra despues
+    // de sacarla, y un directorio lleva su propio largo, asi un corte entre
+    // bloques deja a lo sumo bloques o inodos huerfanos, que se recuperan al
+    // montar, y nunca una entrada que apunte a un inodo libre. un corte dentro
+    // de la tabla se detecta por los encabezados de sus bloques.
+    // entre un paso y el siguiente va una barrera (BlockStorage::barrier), y
+    // otra antes del paso 1 para los bloques de datos ya mandados: un backend
+    // que reordena escrituras nunca deja el superblock mas nuevo que lo que
+    // apunta. cuando haya journal, se escribe antes del paso 1
+    fn commit(&mut self, txn: &mut MetadataTransaction) -> Result<(), crate::errors::QrfsError> {
+        let mut dir_ids: Vec<u32> = txn.dirs_before.keys().copied().collect();
+        dir_ids.sort();
+        let mut dirs = Vec::new();
+        for dir_id in dir_ids {
+            // un directorio borrado en la misma transaccion no se guarda
+            if self.inodes.contains_key(&dir_id) {
+                dirs.push(self.prepare_directory(txn, dir_id)?);
+            }
+        }
+
+        let early_table = self.early_inode_table(txn)?;
+        let table = if !txn.inodes_before.is_empty() || txn.superblock_before.is_some() {
+            Some(self.serialize_inode_table()?)
+        } else {
+            None
+        };
+
+        // si la tabla no entra, se reservan bloques de extension antes del bitmap
+        let needed = early_table.iter().chain(&table).map(|data| data.len()).max().unwrap_or(0);
+        if needed > self.inode_table_capacity() {
+            self.grow_inode_table(txn, needed)?;
+        }
+
+        self.storage.barrier()?;
+        if txn.allocated {
+            self.save_bitmap()?;
+            txn.bitmap_written = true;
+            self.storage.barrier()?;
+        }
+        let mut superblock_pending = txn.superblock_before.is_some();
+        txn.metadata_written = early_table.is_some() || !dirs.is_empty() || table.is_some();
+        if let Some(data) = &early_table {
+            self.write_inode_table(data)?;
+            if superblock_pending {
+                self.save_superblock()?;
+                superblock_pending = false;
+            }
+            self.storage.barrier()?;
+        }
+        if !dirs.is_empty() {
+            for (blocks, data) in &dirs {
+                self.write_directory(blocks, data)?;
+            }
+            self.storage.barrier()?;
+        }
+        if let Some(data) = &table {
+            self.write_inode_table(data)?;
+            self.storage.barrier()?;
+        }
+        if superblock_pending || txn.bitmap_written || txn.metadata_written {
+            self.superblock.meta_generation = self.superblock.meta_generation.wrapping_add(1);
+            self.save_superblock()?;
+            // los liberados no se reusan hasta que el superblock nuevo este
+            self.storage.barrier()?;
+        }
+
+        // desde aca los cambios ya estan en disco: no hay vuelta atras
+        if !txn.freed.is_empty() {
+            let freed = std::mem::take(&mut txn.freed);
+            for &block_id in &freed {
+                self.dirty_blocks.remove(&block_id);
+                self.free_block(block_id);
+            }
+            match self.save_bitmap() {
+                Ok(()) => {
+                    for block_id in freed {
+                        self.storage.block_freed(block_id);
+                    }
+                }
+                Err(e) => println!("error guardando bitmap (los bloques liberados quedan huerfanos): {}", e),
+            }
+        }
+
+        self.publish_commit(txn);
+        Ok(())
+    }
+
+    // avisa en el bus que entradas e inodos cambio la transaccion ya escrita
+    fn publish_commit(&self, txn: &MetadataTransaction) {
+        let Some(events) = &self.events else {
+            return;
+        };
+        let mut dir_ids: Vec<u32> = txn.dirs_before.keys().copied().collect();
+        dir_ids.sort();
+        for dir_id in dir_ids {
+            let after = self.dir_cache.get(&dir_id);
+            // sin la version de antes no se sabe que nombres cambiaron
+            let Some(before) = &txn.dirs_before[&dir_id] else {
+                events.publish(FsEvent::Inode(dir_id));
+                continue;
+            };
+            let mut names: Vec<&String> = before.keys().chain(after.into_iter().flat_map(|a| a.keys())).collect();
+            names.sort();
+            names.dedup();
+            for name in names {
+                let target = |entries: Option<&DirEntries>| entries.and_then(|e| e.get(name)).map(|entry| entry.0);
+                if target(Some(before)) != target(after) {
+                    events.publish(FsEvent::Entry { parent: dir_id, name: name.clone() });
+                }
+            }
+        }
+        let mut inode_ids: Vec<u32> = txn.inodes_before.keys().copied().collect();
+        inode_ids.sort();
+        for id in inode_ids {
+            events.publish(FsEvent::Inode(id));
+        }
+    }
+
+    // tabla de inodos que se escribe antes que los directorios (ver commit):
+    // estado previo a la transaccion mas los inodos nuevos y los bloques que
+    // se agregaron a cada directorio. None si no hace falta
+    fn early_inode_table(
+        &mut self,
+        txn: &MetadataTransaction,
+    ) -> Result<Option<Vec<u8>>, crate::errors::QrfsError> {
+        let mut early = HashMap::new();
+        let mut needed = false;
+        for (&id, before) in &txn.inodes_before {
+            let inode = match (before, self.inodes.get(&id)) {
+                (None, Some(now)) => {
+                    needed = true;
+                    Some(now.clone())
+                }
+                (Some(before), Some(now))
+                    if matches!(now.kind, InodeKind::Directory) && before.blocks.len() < now.blocks.len() =>
+                {
+                    needed = true;
+                    Some(Inode { blocks: now.blocks.clone(), ..before.clone() })
+                }
+                (before, _) => before.clone(),
+            };
+            early.insert(id, inode);
+        }
+        if !needed {
+            return Ok(None);
+        }
+
+        // se arma la tabla con esos inodos y se vuelve al estado actual
+        let mut current = HashMap::new();
+        for (id, inode) in early {
+            let previous = match inode {
+                Some(inode) => self.inodes.insert(id, inode),
+                None => self.inodes.remove(&id),
+            };
+            current.insert(id, previous);
+        }
+        let data = self.serialize_inode_table();
+        for (id, inode) in current {
+            match inode {
+                Some(inode) => self.inodes.insert(id, inode),
+                None => self.inodes.remove(&id),
+            };
+        }
+        data.map(Some)
+    }
+
+    // vuelve la metadata en memoria al estado previo a la transaccion
+    fn rollback(&mut self, txn: MetadataTransaction) {
+        let mut dir_ids: Vec<u32> = txn.dirs_before.keys().copied().collect();
+        dir_ids.sort();
+        let superblock_changed = txn.superblock_before.is_some();
+
+        for (id, before) in txn.inodes_before {
+            match before {
+                Some(inode) => {
+                    self.inodes.insert(id, inode);
+                }
+                None => {
+                    self.inodes.remove(&id);
+                }
+            }
+        }
+        for (dir_id, before) in txn.dirs_before {
+            match before {
+                Some(entries) => {
+                    self.dir_cache.insert(dir_id, entries);
+                }
+                None => {
+                    self.dir_cache.remove(&dir_id);
+                }
+            }
+        }
+        if let Some((superblock, ext_blocks)) = txn.superblock_before {
+            self.superblock = superblock;
+            self.inode_ext_blocks = ext_blocks;
+        }
+
+        // si el commit ya escribio directorios o la tabla, volver a escribirlos
+        // como estaban, en el orden de un borrado: directorios, tabla y superblock.
+        // recien despues se liberan en el bitmap los bloques reservados
+        if txn.metadata_written {
+            if let Err(e) = self.rewrite_metadata(&dir_ids, superblock_changed) {
+                println!("error restaurando metadata (fsck puede encontrar huerfanos): {}", e);
+            }
+        }
+        if let Some(bitmap) = txn.bitmap_before {
+            self.bitmap = bitmap;
+            // si el bitmap con las reservas ya llego al disco, volver a escribirlo
+            if txn.bitmap_written {
+                if let Err(e) = self.save_bitmap() {
+                    println!("error restaurando bitmap (quedan bloques huerfanos): {}", e);
+                }
+            }
+        }
+
+        // lo escrito en bloques que volvieron a quedar libres no se guarda
+        let bitmap = &self.bitmap;
+        self.dirty_blocks
+            .retain(|&blk, _| bitmap[blk as usize / 8] & (1 << (blk % 8)) != 0);
+    }
+
+    // escribe los directorios y la tabla de inodos tal como estan en memoria
+    // (los directorios entran en sus bloques: es el estado previo a un commit)
+    fn rewrite_metadata(
+        &mut self,
+        dir_ids: &[u32],
+        superblock_changed: bool,
+    ) -> Result<(), crate::errors::QrfsError> {
+        for &dir_id in dir_ids {
+            let Some(blocks) = self.inodes.get(&dir_id).map(|inode| inode.blocks.clone()) else {
+                continue;
+            };
+            if !self.dir_cache.contains_key(&dir_id) {
+                continue;
+            }
+            let (data, _) = self.encode_cached_directory(dir_id)?;
+            self.write_directory(&blocks, &data)?;
+        }
+        let table = self.serialize_inode_table()?;
+        self.write_inode_table(&table)?;
+        if superblock_changed {
+            self.save_superblock()?;
+        }
+        Ok(())
+    }
+
+    // operaciones sobre la metadata en memoria, anotadas en la transaccion
+
+    // reserva count bloques o ninguno (sin tocar el bitmap si no alcanzan)
+    fn txn_reserve_blocks(&mut self, txn: &mut MetadataTransaction, count: usize) -> Option<Vec<u32>> {
+        self.txn_reserve_blocks_in(txn, count, false)
+    }
+
+    // igual, pero para los bloques del inodo id: si es fijo van a la zona fija
+    fn txn_reserve_blocks_for(
+        &mut self,
+        txn: &mut MetadataTransaction,
+        id: u32,
+        count: usize,
+    ) -> Option<Vec<u32>> {
+        let pinned = self.is_pinned(id);
+        self.txn_reserve_blocks_in(txn, count, pinned)
+    }
+
+    fn txn_reserve_blocks_in(
+        &mut self,
+        txn: &mut MetadataTransaction,
+        count: usize,
+        pinned: bool,
+    ) -> Option<Vec<u32>> {
+        if count == 0 {
+            return Some(Vec::new());
+        }
+        txn.touch_bitmap(&self.bitmap);
+        let blocks = self.reserve_blocks_in(count, pinned)?;
+        txn.allocated = true;
+        Some(blocks)
+    }
+
+    // reserva un bloque de datos en particular; false si esta ocupado o fuera
+    // del area de datos
+    fn txn_reserve_block_at(&mut self, txn: &mut MetadataTransaction, block_id: BlockId) -> bool {
+        if block_id < self.superblock.data_block_start
+            || block_id >= self.superblock.total_blocks
+            || self.is_block_used(block_id)
+        {
+            return false;
+        }
+        txn.touch_bitmap(&self.bitmap);
+        self.bitmap[(block_id / 8) as usize] |= 1 << (block_id % 8);
+        txn.allocated = true;
+        true
+    }
+
+    fn txn_inode_mut(&mut self, txn: &mut MetadataTransaction, id: u32) -> Option<&mut Inode> {
+        txn.touch_inode(id, self.inodes.get(&id));
+        self.inodes.get_mut(&id)
+    }
+
+    fn txn_insert_inode(&mut self, txn: &mut MetadataTransaction, inode: Inode) {
+        txn.touch_inode(inode.id, self.inodes.get(&inode.id));
+        self.inodes.insert(inode.id, inode);
+    }
+
+    fn txn_remove_inode(&mut self, txn: &mut MetadataTransaction, id: u32) -> Option<Inode> {
+        txn.touch_inode(id, self.inodes.get(&id));
+        self.inodes.remove(&id)
+    }
+
+    fn txn_dir_insert(
+        &mut self,
+        txn: &mut MetadataTransaction,
+        dir_id: u32,
+        name: &str,
+        id: u32,
+        kind: InodeKind,
+    ) -> Result<(), crate::errors::QrfsError> {
+        self.dir_entries(dir_id)?;
+        txn.touch_dir(dir_id, self.dir_cache.get(&dir_id));
+        if let Some(entries) = self.dir_cache.get_mut(&dir_id) {
+            entries.insert(name.to_string(), (id, kind));
+        }
+        Ok(())
+    }
+
+    fn txn_dir_remove(
+        &mut self,
+        txn: &mut MetadataTransaction,
+        dir_id: u32,
+        name: &str,
+    ) -> Result<Option<(u32, InodeKind)>, crate::errors::QrfsError> {
+        self.dir_entries(dir_id)?;
+        txn.touch_dir(dir_id, self.dir_cache.get(&dir_id));
+        Ok(self
+            .dir_cache
+            .get_mut(&dir_id)
+            .and_then(|entries| entries.remove(name)))
+    }
+
+    // serializa todos los slots de la tabla (los libres con mode 0)
+    fn serialize_inode_table(&self) -> Result<Vec<u8>, crate::errors::QrfsError> {
+        let mut serialized_data = Vec::new();
+
+        let now = self.superblock.timestamp();
+        for id in 0..self.superblock.inode_count {
+            let inode_to_write = if let Some(inode) = self.inodes.get(&id) {
+                inode.clone()
+            } else {
+                let mut empty = Inode::new_at(id, InodeKind::File, now);
+                empty.mode = 0;
+                empty
+            };
+
+            let bytes = inode_to_write
+                .encode(self.superblock.inode_format)
+                .map_err(|_| crate::errors::QrfsError::Other("error serializando inodo".into()))?;
+            serialized_data.extend_from_slice(&bytes);
+        }
+
+        Ok(serialized_data)
+    }
+
+    // bytes de tabla que entran en los bloques fijos mas la extension
+    fn inode_table_capacity(&self) -> usize {
+        let block_size = self.superblock.block_size as usize;
+        inode_table_layout(&self.superblock, &self.inode_ext_blocks)
+            .iter()
+            .map(|&(_, start)| block_size - start)
+            .sum()
+    }
+
+    // agrega bloques de datos a la extension hasta que entren `needed` bytes
+    fn grow_inode_table(
+        &mut self,
+        txn: &mut MetadataTransaction,
+        needed: usize,
+    ) -> Result<(), crate::errors::QrfsError> {
+        txn.touch_superblock(&self.superblock, &self.inode_ext_blocks);
+        let capacity = self.inode_table_capacity();
+        if capacity < needed {
+            // lo que entra en un bloque de extension (despues de sus encabezados)
+            let block_size = self.superblock.block_size as usize;
+            let per_block = inode_table_layout(&self.superblock, &[0])
+                .last()
+                .map(|&(_, start)| block_size - start)
+                .unwrap_or(block_size);
+            let missing = (needed - capacity).div_ceil(per_block);
+            // la extension de la tabla va siempre a la zona fija
+            let blocks = self.txn_reserve_blocks_in(txn, missing, true).ok_or_else(|| {
+                crate::errors::QrfsError::NoSpace(format!(
+                    "sin bloques para agrandar la tabla de inodos ({} bytes)",
+                    needed
+                ))
+            })?;
+            self.inode_ext_blocks.extend(blocks);
+        }
+        self.superblock.inode_ext_head = self.inode_ext_blocks.first().copied().unwrap_or(0);
+        Ok(())
+    }
+
+    // escribe la tabla serializada en sus bloques con una generacion nueva;
+    // cada bloque de la extension lleva adelante el id del siguiente
+    fn write_inode_table(&mut self, serialized_data: &[u8]) -> Result<(), crate::errors::QrfsError> {
+        let block_size = self.superblock.block_size as usize;
+
+        // las listas de bloques son de largo variable: si no entra, cortar la tabla
+        // la dejaria ilegible, mejor fallar y que la transaccion se deshaga
+        let capacity = self.inode_table_capacity();
+        if serialized_data.len() > capacity {
+            return Err(crate::errors::QrfsError::NoSpace(format!(
+                "la tabla de inodos necesita {} bytes y tiene {}",
+                serialized_data.len(),
+                capacity
+            )));
+        }
+
+        let generation = self.inode_table_generation.wrapping_add(1);
+        let blocks = encode_inode_table(
+            &self.superblock,
+            &self.inode_ext_blocks,
+            block_size,
+            serialized_data,
+            generation,
+        )?;
+        self.inode_table_generation = generation;
+        for (block_id, block) in blocks {
+            self.storage.write_block(block_id, &block)?;
+        }
+
+        Ok(())
+    }
+
+    // guarda el superblock (cambia cuando crece la tabla de inodos)
+    fn save_superblock(&self) -> Result<(), crate::errors::QrfsError> {
+        for (id, block) in encode_superblock(&self.superblock)?.iter().enumerate() {
+            self.storage.write_block(id as BlockId, block)?;
+        }
+        Ok(())
+    }
+
+    // encuentra un id de inodo libre
+    fn find_free_inode_id(&self) -> Option<u32> {
+        (2..self.superblock.inode_count).find(|i| !self.inodes.contains_key(i))
+    }
+
+    // id libre, agrandando la tabla si estan todos ocupados
+    fn txn_allocate_inode_id(&mut self, txn: &mut MetadataTransaction) -> Option<u32> {
+        if let Some(id) = self.find_free_inode_id() {
+            return Some(id);
+        }
+
+        let grown = self.superblock.inode_count.checked_add(INODE_GROW_STEP)?;
+        txn.touch_superblock(&self.superblock, &self.inode_ext_blocks);
+        self.superblock.inode_count = grown;
+        self.find_free_inode_id()
+    }
+
+    // guarda el bitmap al disco
+    fn save_bitmap(&self) -> Result<(), crate::errors::QrfsError> {
+        let block_size = self.superblock.block_size as usize;
+        let start_block = self.superblock.free_map_start;
+        let num_blocks = self.superblock.free_map_blocks;
+
+        let mut offset = 0;
+        for i in 0..num_blocks {
+            let block_id = start_block + i;
+            let mut chunk = vec![0u8; block_size];
+
+            if offset < self.bitmap.len() {
+                let end = std::cmp::min(offset + block_size, self.bitmap.len());
+                let slice = &self.bitmap[offset..end];
+                chunk[..slice.len()].copy_from_slice(slice);
+                offset += slice.len();
+            }
+
+            self.storage.write_block(block_id, &chunk)?;
+        }
+
+        Ok(())
+    }
+
+    // busca count bits libres en el bitmap y los marca como usados, todos o
+    // ninguno: si no alcanzan el bitmap queda como estaba (solo memoria).
+    // solo se miran bloques de datos: superblock, bitmap y tabla de inodos
+    // estan marcados como usados desde mkfs (y quick_check lo verifica).
+    // lo fijo busca primero en la zona fija y despues en el resto; los datos
+    // comunes al reves, asi la zona solo se llena de datos con el disco lleno
+    fn reserve_blocks_in(&mut self, count: usize, pinned: bool) -> Option<Vec<BlockId>> {
+        let area = self.pinned_area();
+        let rest = area.end..self.superblock.total_blocks;
+        let order = if pinned {
+            area.chain(rest)
+        } else {
+            rest.chain(area)
+        };
+        let blocks: Vec<BlockId> = order
+            .filter(|&blk| !self.is_block_used(blk))
+            .take(count)
+            .collect();
+        if blocks.len() < count {
+            return None;
+        }
+
+        for &block_id in &blocks {
+            let byte_idx = (block_id as usize) / 8;
+            self.bitmap[byte_idx] |= 1 << (block_id % 8);
+        }
+        Some(blocks)
+    }
+
+    // la zona fija: los primeros pinned_blocks bloques del area de datos
+    // (vacia si el disco no tiene)
+    pub(crate) fn pinned_area(&self) -> std::ops::Range<BlockId> {
+        let start = self.superblock.data_block_start;
+        let end = start
+            .saturating_add(self.superblock.pinned_blocks)
+            .min(self.superblock.total_blocks);
+        start..end
+    }
+
+    // la raiz va siempre a la zona fija; los demas si tienen INODE_FLAG_PINNED
+    pub(crate) fn is_pinned(&self, id: u32) -> bool {
+        id == self.superblock.root_inode
+            || self
+                .inodes
+                .get(&id)
+                .is_some_and(|inode| inode.flags & INODE_FLAG_PINNED != 0)
+    }
+
+    // libera un bloque en el bitmap (solo memoria)
+    fn free_block(&mut self, block_id: u32) {
+        let byte_idx = (block_id as usize) / 8;
+        let bit_idx = (block_id as usize) % 8;
+
+        if byte_idx < self.bitmap.len() {
+            self.bitmap[byte_idx] &= !(1 << bit_idx);
+        }
+    }
+
+    // operaciones de archivos usadas por fuse y por QrfsApi
+
+    // bloques de datos (sin la metadata reservada) que tiene el disco
+    pub(crate) fn data_block_count(&self) -> u64 {
+        self.superblock
+            .total_blocks
+            .saturating_sub(self.superblock.data_block_start) as u64
+    }
+
+    // bloques de datos e inodos libres segun el bitmap y la tabla en memoria
+    pub(crate) fn free_counts(&self) -> (u64, u64) {
+        let free_blocks = (self.superblock.data_block_start..self.superblock.total_blocks)
+            .filter(|&blk| !self.is_block_used(blk))
+            .count() as u64;
+
+        let total_inodes = self.superblock.inode_count as u64;
+        let free_inodes = total_inodes - self.inodes.len() as u64;
+
+        (free_blocks, free_inodes)
+    }
+
+    // largo de cada corrida de bloques de datos libres, en orden de disco
+    pub(crate) fn free_runs(&self) -> Vec<u64> {
+        let mut runs = Vec::new();
+        let mut current = 0u64;
+        for blk in self.superblock.data_block_start..self.superblock.total_blocks {
+            if self.is_block_used(blk) {
+                if current > 0 {
+                    runs.push(current);
+                }
+                current = 0;
+            } else {
+                current += 1;
+            }
+        }
+        if current > 0 {
+            runs.push(current);
+        }
+        runs
+    }
+
+    pub(crate) fn superblock(&self) -> &Superblock {
+        &self.superblock
+    }
+
+    pub(crate) fn inode(&self, id: u32) -> Option<&Inode> {
+        self.inodes.get(&id)
+    }
+
+    // entradas del directorio raiz (sin "." ni "..")
+    pub(crate) fn root_entries(&mut self) -> Result<Vec<(String, u32)>, crate::errors::QrfsError> {
+        self.entries_in(self.superblock.root_inode)
+    }
+
+    // entradas de cualquier directorio (la api con rutas)
+    pub(crate) fn entries_in(&mut self, dir_id: u32) -> Result<Vec<(String, u32)>, crate::errors::QrfsError> {
+        Ok(self
+            .dir_entries(dir_id)?
+            .iter()
+            .map(|(name, &(id, _))| (name.clone(), id))
+            .collect())
+    }
+
+    // inodo del nombre dentro de un directorio
+    pub(crate) fn lookup_in(&mut self, dir_id: u32, name: &str) -> Result<Option<u32>, crate::errors::QrfsError> {
+        self.dir_lookup(dir_id, name)
+    }
+
+    // todo el arbol desde la raiz: (ruta, inodo), cada directorio antes que
+    // lo que tiene adentro y los nombres ordenados
+    pub(crate) fn walk_tree(&mut self) -> Result<Vec<(String, u32)>, crate::errors::QrfsError> {
+        let root = self.superblock.root_inode;
+        let mut out = vec![("/".to_string(), root)];
+        let mut visited = HashSet::from([root]);
+        self.walk_dir(root, "", &mut visited, &mut out)?;
+        Ok(out)
+    }
+
+    fn walk_dir(
+        &mut self,
+        dir_id: u32,
+        prefix: &str,
+        visited: &mut HashSet<u32>,
+        out: &mut Vec<(String, u32)>,
+    ) -> Result<(), crate::errors::QrfsError> {
+        let mut entries: Vec<(String, u32, InodeKind)> = self
+            .dir_entries(dir_id)?
+            .iter()
+            .map(|(name, (id, kind))| (name.clone(), *id, kind.clone()))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        for (name, id, kind) in entries {
+            let path = format!("{}/{}", prefix, name);
+            out.push((path.clone(), id));
+            // un directorio enlazado dos veces (disco roto) no se recorre de nuevo
+            if matches!(kind, InodeKind::Directory) && visited.insert(id) {
+                self.walk_dir(id, &path, visited, out)?;
+            }
+        }
+        Ok(())
+    }
+
+    // lee hasta size bytes desde offset, corta en el final del archivo
+    pub(crate) fn read_data(
+        &self,
+        id: u32,
+        offset: u64,
+        size: u64,
+    ) -> Result<Vec<u8>, crate::errors::QrfsError> {
+        let inode = self
+            .inodes
+            .get(&id)
+            .ok_or_else(|| crate::errors::QrfsError::NotFound(format!("inodo {}", id)))?;
+        let block_size = self.file_block_len(inode) as u64;
+
+        if offset >= inode.size {
+            return Ok(Vec::new());
+        }
+        let key = self.file_key(id)?;
+
+        let mut data_buffer = Vec::new();
+        let mut current_offset = offset;
+        let end_offset = std::cmp::min(inode.size, offset + size);
+
+        while current_offset < end_offset {
+            let logical_block_idx = current_offset / block_size;
+            let offset_in_block = (current_offset % block_size) as usize;
+
+            let remaining_in_file = end_offset - current_offset;
+            let remaining_in_block = block_size - (offset_in_block as u64);
+            let len_to_read = std::cmp::min(remaining_in_file, remaining_in_block) as usize;
+
+            if (logical_block_idx as usize) < inode.blocks.len() {
+                let phys_id = inode.blocks[logical_block_idx as usize];
+                let block_data = if Self::is_packed_tail(inode, logical_block_idx) {
+                    let mut data = tail::tail_of(&self.read_data_block(phys_id)?, id)?.to_vec();
+                    data.resize(block_size as usize, 0);
+                    data
+                } else {
+                    self.read_file_block(key.as_ref(), logical_block_idx, phys_id)?
+                };
+
+                if block_data.len() >= offset_in_block + len_to_read {
+                    data_buffer.extend_from_slice(
+                        &block_data[offset_in_block..offset_in_block + len_to_read],
+                    );
+                } else {
+                    data_buffer.extend(vec![0u8; len_to_read]);
+                }
+            } else {
+                data_buffer.extend(vec![0u8; len_to_read]);
+            }
+
+            current_offset += len_to_read as u64;
+        }
+
+        Ok(data_buffer)
+    }
+
+    // bloque de datos: el buffer en memoria si tiene cambios sin guardar
+    fn read_data_block(&self, block_id: BlockId) -> Result<Vec<u8>, crate::errors::QrfsError> {
+        if let Some(data) = self.dirty_blocks.get(&block_id) {
+            return Ok(data.clone());
+        }
+        if self.degraded && !self.storage.has_block(block_id) {
+            return Err(crate::errors::QrfsError::Other(format!(
+                "bloque {} sin escanear (ver {})",
+                block_id, MISSING_FILE
+            )));
+        }
+        let data = self.storage.read_block(block_id)?;
+        if let Some(seal) = &self.seal {
+            seal.verify(block_id, &data).inspect_err(|e| {
+                self.health.record(block_id, HealthEvent::ChecksumMismatch, &e.to_string());
+            })?;
+        }
+        Ok(data)
+    }
+
+    // bytes del archivo en cada bloque: uno cifrado lleva ademas su nonce y
+    // su etiqueta
+    fn file_block_len(&self, inode: &Inode) -> usize {
+        crypt::payload_len(self.superblock.block_size as usize, !inode.key.is_empty())
+    }
+
+    // bloque de un archivo en claro: se descifra con la clave del archivo y
+    // su posicion en el
+    fn read_file_block(
+        &self,
+        key: Option<&Key>,
+        index: u64,
+        block_id: BlockId,
+    ) -> Result<Vec<u8>, crate::errors::QrfsError> {
+        let data = self.read_data_block(block_id)?;
+        match key {
+            Some(key) => crypt::decrypt_block(key, index, &data),
+            None => Ok(data),
+        }
+    }
+
+    fn write_file_block(
+        &mut self,
+        key: Option<&Key>,
+        index: u64,
+        block_id: BlockId,
+        data: Vec<u8>,
+    ) -> Result<(), crate::errors::QrfsError> {
+        let data = match key {
+            Some(key) => crypt::encrypt_block(key, index, &data)?,
+            None => data,
+        };
+        self.write_data_block(block_id, data);
+        Ok(())
+    }
+
+    // flags de stat para macos a partir de los del inodo
+    #[cfg(unix)]
+    fn bsd_flags(inode: &Inode) -> u32 {
+        let mut flags = 0;
+        if inode.flags & INODE_FLAG_IMMUTABLE != 0 {
+            flags |= UF_IMMUTABLE;
+        }
+        if inode.flags & INODE_FLAG_APPEND != 0 {
+            flags |= UF_APPEND;
+        }
+        flags
+    }
+
+    // archivos virtuales de la raiz: estado del sellado, informe de faltantes,
+    // el .xdg-volume-info de un disco rotulado y la carpeta .qrfs_meta (con el
+    // informe de salud y el rotulo adentro)
+    #[cfg(unix)]
+    fn virtual_ino(&self, name: &str) -> Option<u64> {
+        match name {
+            STATUS_FILE if self.seal.is_some() => Some(STATUS_INO),
+            MISSING_FILE if self.degraded => Some(MISSING_INO),
+            VOLUME_INFO_FILE if self.superblock.label_block != 0 => Some(VOLUME_INFO_INO),
+            META_DIR => Some(META_INO),
+            _ => None,
+        }
+    }
+
+    #[cfg(unix)]
+    fn is_virtual_file(ino: u64) -> bool {
+        matches!(ino, STATUS_INO | MISSING_INO | HEALTH_INO | LABEL_INO | VOLUME_INFO_INO)
+    }
+
+    #[cfg(unix)]
+    fn virtual_text(&mut self, ino: u64) -> Option<String> {
+        match ino {
+            STATUS_INO => self.seal_status(),
+            MISSING_INO => self.missing_status(),
+            HEALTH_INO => Some(self.health_report().text()),
+            LABEL_INO => self.label().map(|label| format!("{}\n{}\n", label.label, label.uuid_string())),
+            VOLUME_INFO_INO => self.label().map(|label| label.volume_info()),
+            _ => None,
+        }
+    }
+
+    #[cfg(unix)]
+    fn virtual_attr(&mut self, ino: u64) -> Option<FileAttr> {
+        let (kind, size, perm, nlink) = if ino == META_INO {
+            (FileType::Directory, 0, 0o555, 2)
+        } else {
+            (FileType::RegularFile, self.virtual_text(ino)?.len() as u64, 0o444, 1)
+        };
+        Some(FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: UNIX_EPOCH,
+            mtime: UNIX_EPOCH,
+            ctime: UNIX_EPOCH,
+            crtime: UNIX_EPOCH,
+            kind,
+            perm,
+            nlink,
+            uid: 1000,
+            gid: 1000,
+            rdev: 0,
+            flags: 0,
+            blksize: 512,
+        })
+    }
+
+    // escribe en el buffer; muchas escrituras chicas al mismo bloque terminan
+    // en una sola codificacion a qr
+    fn write_data_block(&mut self, block_id: BlockId, data: Vec<u8>) {
+        self.dirty_blocks.insert(block_id, data);
+    }
+
+    // pasa al storage los bloques de datos pendientes, en orden de bloque, y
+    // despues el bitmap y la tabla de las escrituras diferidas. termina con
+    // una barrera: al volver (fsync) todo esta en el backend.
+    // si uno falla, ese y los que siguen quedan en memoria para reintentar
+    pub fn flush_data(&mut self) -> Result<(), crate::errors::QrfsError> {
+        let mut ids: Vec<BlockId> = self.dirty_blocks.keys().copied().collect();
+        ids.sort();
+        for block_id in ids {
+            self.storage.write_block(block_id, &self.dirty_blocks[&block_id])?;
+            self.dirty_blocks.remove(&block_id);
+        }
+
+        if self.pending_inodes.is_empty() {
+            return self.storage.barrier();
+        }
+        let pending: Vec<u32> = self.pending_inodes.drain().collect();
+        let result = self.transaction(|fs, txn| {
+            // el bitmap en memoria ya tiene las reservas de esas escrituras
+            txn.touch_bitmap(&fs.bitmap);
+            txn.allocated = true;
+            for &id in &pending {
+                fs.txn_inode_mut(txn, id);
+            }
+            Ok(())
+        });
+        if result.is_err() {
+            self.pending_inodes.extend(pending);
+        }
+        result?;
+        self.storage.barrier()
+    }
+
+    // vuelve a generar los qr de los bloques de un inodo con la configuracion
+    // actual (generacion y checksum nuevos); devuelve cuantos bloques paso
+    pub(crate) fn reencode_inode(&mut self, id: u32) -> Result<usize, crate::errors::QrfsError> {
+        self.check_writable()?;
+        // lo que esta en memoria primero tiene que llegar a su qr
+        self.flush_data()?;
+
+        let blocks = self
+            .inodes
+            .get(&id)
+            .map(|inode| inode.blocks.clone())
+            .ok_or_else(|| crate::errors::QrfsError::NotFound(format!("inodo {}", id)))?;
+        for &block_id in &blocks {
+            self.storage.reencode_block(block_id)?;
+        }
+        Ok(blocks.len())
+    }
+
+    // mueve el bloque logico index de un inodo al bloque fisico to, que tiene
+    // que estar libre (para desfragmentar, achicar el disco o esquivar un
+    // bloque danado). la copia va directo al qr antes de la transaccion que
+    // cambia el inodo y libera el viejo: un corte deja el archivo entero en
+    // uno u otro. una cola empaquetada se mueve con todas las que comparten
+    // su bloque
+    pub(crate) fn relocate_block(
+        &mut self,
+        id: u32,
+        index: usize,
+        to: BlockId,
+    ) -> Result<(), crate::errors::QrfsError> {
+        self.check_writable()?;
+        // lo que esta en memoria primero tiene que llegar a su qr
+        self.flush_data()?;
+
+        let inode = self
+            .inodes
+            .get(&id)
+            .ok_or_else(|| crate::errors::QrfsError::NotFound(format!("inodo {}", id)))?;
+        let from = *inode.blocks.get(index).ok_or_else(|| {
+            crate::errors::QrfsError::NotFound(format!("el inodo {} no tiene bloque {}", id, index))
+        })?;
+        let owners: Vec<(u32, usize)> = if Self::is_packed_tail(inode, index as u64) {
+            self.inodes
+                .values()
+                .filter(|other| other.flags & INODE_FLAG_TAIL != 0 && other.blocks.last() == Some(&from))
+                .map(|other| (other.id, other.blocks.len() - 1))
+                .collect()
+        } else {
+            vec![(id, index)]
+        };
+        // lo fijo no sale de la zona fija (ni la desfragmentacion lo saca)
+        if self.is_pinned(id) && !self.pinned_area().contains(&to) {
+            return Err(crate::errors::QrfsError::NotPermitted(format!(
+                "el bloque {} es de un archivo fijo: solo se mueve dentro de la zona fija",
+                from
+            )));
+        }
+
+        self.transaction(|fs, txn| {
+            if !fs.txn_reserve_block_at(txn, to) {
+                return Err(crate::errors::QrfsError::NoSpace(format!(
+                    "el bloque {} no esta libre o no es del area de datos",
+                    to
+                )));
+            }
+            let data = fs.read_data_block(from)?;
+            fs.storage.write_block(to, &data)?;
+            for &(owner, index) in &owners {
+                if let Some(inode) = fs.txn_inode_mut(txn, owner) {
+                    inode.blocks[index] = to;
+                }
+            }
+            txn.freed.push(from);
+            Ok(())
+        })
+    }
+
+    // saca del bloque fisico from lo que tenga (a to o al primer bloque
+    // libre); devuelve el bloque nuevo
+    pub(crate) fn evacuate_block(
+        &mut self,
+        from: BlockId,
+        to: Option<BlockId>,
+    ) -> Result<BlockId, crate::errors::QrfsError> {
+        let mut ids: Vec<&u32> = self.inodes.keys().collect();
+        ids.sort();
+        let owner = ids.into_iter().find_map(|&id| {
+            let index = self.inodes[&id].blocks.iter().position(|&b| b == from)?;
+            Some((id, index))
+        });
+        let Some((id, index)) = owner else {
+            if from < self.superblock.data_block_start
+                || self.inode_ext_blocks.contains(&from)
+                || from == self.superblock.label_block
+            {
+                return Err(crate::errors::QrfsError::NotPermitted(format!(
+                    "el bloque {} es de la metadata",
+                    from
+                )));
+            }
+            return Err(crate::errors::QrfsError::NotFound(format!(
+                "ningun archivo usa el bloque {}",
+                from
+            )));
+        };
+        let to = match to {
+            Some(to) => to,
+            None => {
+                // un bloque fijo busca lugar solo en la zona fija
+                let candidates = if self.is_pinned(id) {
+                    self.pinned_area()
+                } else {
+                    self.superblock.data_block_start..self.superblock.total_blocks
+                };
+                candidates
+                    .into_iter()
+                    .find(|&blk| !self.is_block_used(blk))
+                    .ok_or_else(|| crate::errors::QrfsError::NoSpace("sin bloques libres".into()))?
+            }
+        };
+        self.relocate_block(id, index, to)?;
+        Ok(to)
+    }
+
+    // marca o desmarca un inodo como fijo. al marcarlo sus bloques se mudan a
+    // la zona fija mientras haya lugar (una cola compartida se separa antes);
+    // devuelve (bloques movidos, bloques que quedaron afuera por falta de
+    // lugar). al desmarcarlo los bloques quedan donde estan
+    pub(crate) fn set_pinned(&mut self, id: u32, pinned: bool) -> Result<(usize, usize), crate::errors::QrfsError> {
+        self.check_writable()?;
+        if self.superblock.pinned_blocks == 0 {
+            return Err(crate::errors::QrfsError::Unimplemented(
+                "este disco no tiene zona fija (mkfs.qrfs --pinned N)".into(),
+            ));
+        }
+        if self.superblock.inode_format == 0 {
+            return Err(crate::errors::QrfsError::Unimplemented(
+                "la tabla de inodos de este disco no guarda flags (formato 0)".into(),
+            ));
+        }
+        if !self.inodes.contains_key(&id) {
+            return Err(crate::errors::QrfsError::NotFound(format!("inodo {}", id)));
+        }
+        if id == self.superblock.root_inode && !pinned {
+            return Err(crate::errors::QrfsError::NotPermitted(
+                "la raiz siempre va en la zona fija".into(),
+            ));
+        }
+        self.flush_data()?;
+        self.transaction(|fs, txn| {
+            if let Some(inode) = fs.txn_inode_mut(txn, id) {
+                if pinned {
+                    inode.flags |= INODE_FLAG_PINNED;
+                } else {
+                    inode.flags &= !INODE_FLAG_PINNED;
+                }
+            }
+            if pinned {
+                fs.unpack_tail(txn, id)?;
+            }
+            Ok(())
+        })?;
+        if !pinned {
+            return Ok((0, 0));
+        }
+
+        let area = self.pinned_area();
+        let outside: Vec<usize> = self.inodes[&id]
+            .blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, blk)| !area.contains(blk))
+            .map(|(index, _)| index)
+            .collect();
+        let mut moved = 0;
+        for &index in &outside {
+            let Some(to) = area.clone().find(|&blk| !self.is_block_used(blk)) else {
+                break;
+            };
+            self.relocate_block(id, index, to)?;
+            moved += 1;
+        }
+        Ok((moved, outside.len() - moved))
+    }
+
+    // escribe un rango completo: reserva todos los bloques de una vez y
+    // escribe cada bloque una sola vez. bitmap e inodos se guardan con los
+    // bloques, en flush_data
+    pub(crate) fn write_all_data(
+        &mut self,
+        id: u32,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<usize, crate::errors::QrfsError> {
+        self.check_writable()?;
+        self.check_flags(id, Some((offset, data)))?;
+        if data.is_empty() {
+            return Ok(0);
+        }
+        let written = self.deferred_transaction(|fs, txn| fs.write_range(txn, id, offset, data))?;
+        if self.audit.is_some() {
+            let name = self.cached_name(id);
+            self.audit("write", id, &name, &format!("{}+{}", offset, written));
+        }
+        if self.dirty_blocks.len() > self.max_dirty_blocks {
+            self.flush_data()?;
+        }
+        Ok(written)
+    }
+
+    fn write_range(
+        &mut self,
+        txn: &mut MetadataTransaction,
+        id: u32,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<usize, crate::errors::QrfsError> {
+        self.unpack_tail(txn, id)?;
+        let (block_size, mut blocks) = match self.inodes.get(&id) {
+            Some(inode) => (self.file_block_len(inode), inode.blocks.clone()),
+            None => return Err(crate::errors::QrfsError::NotFound(format!("inodo {}", id))),
+        };
+        let first = offset / block_size as u64;
+        let last = (offset + data.len() as u64 - 1) / block_size as u64;
+        let old_len = blocks.len() as u64;
+        let key = self.file_key(id)?;
+
+        // los bloques que faltan se reservan todos juntos o ninguno
+        let missing = (last + 1).saturating_sub(old_len) as usize;
+        match self.txn_reserve_blocks_for(txn, id, missing) {
+            Some(new_blocks) => blocks.extend(new_blocks),
+            None => return Err(crate::errors::QrfsError::NoSpace("sin bloques libres".into())),
+        }
+
+        // bloques nuevos que quedan antes del rango (huecos): se llenan con ceros
+        for logical in old_len..first {
+            self.write_file_block(key.as_ref(), logical, blocks[logical as usize], vec![0u8; block_size])?;
+        }
+
+        let mut done = 0usize;
+        for logical in first..=last {
+            let phys_id = blocks[logical as usize];
+            let start = if logical == first { (offset % block_size as u64) as usize } else { 0 };
+            let len = std::cmp::min(block_size - start, data.len() - done);
+
+            // solo los bloques parciales ya existentes necesitan leerse
+            let mut block_data = if len == block_size || logical >= old_len {
+                vec![0u8; block_size]
+            } else {
+                // si no se lee, reescribirlo perderia lo que no cubre el rango
+                self.read_file_block(key.as_ref(), logical, phys_id)?
+            };
 
-        for i in 0..superblock.inode_table_blocks {
-            let data = storage.read_block(superblock.inode_table_start + i)?;
-            inode_buffer.extend_from_slice(&data);
+            block_data[start..start + len].copy_from_slice(&data[done..done + len]);
+            self.write_file_block(key.as_ref(), logical, phys_id, block_data)?;
+            done += len;
         }
 
-        let mut cursor = std::io::Cursor::new(inode_buffer);
-        for _ in 0..superblock.inode_count {
-            if let Ok(inode) = bincode::deserialize_from::<_, Inode>(&mut cursor) {
-                if inode.id == 0 || inode.mode != 0 {
-                    inodes.insert(inode.id, inode);
-                }
+        let now = self.superblock.timestamp();
+        if let Some(inode) = self.txn_inode_mut(txn, id) {
+            inode.blocks = blocks;
+            let new_end = offset + done as u64;
+            if new_end > inode.size {
+                inode.size = new_end;
             }
+            inode.modified_at = now;
         }
 
-        let mut fs = Self {
-            storage,
-            superblock,
-            inodes,
-            bitmap,
-            dir_cache: HashMap::new(),
-        };
+        Ok(done)
+    }
 
-        // intentar cargar el directorio raiz del disco
-        let root_id = fs.superblock.root_inode;
-        println!("debug: cargando directorio raiz (inodo {})...", root_id);
+    // reserva los bloques de [offset, offset + length) sin escribir datos
+    // (fallocate): todos o ninguno, en ceros. con keep_size el tamaño no cambia
+    // por ahora solo la expone fuse
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub(crate) fn allocate_range(
+        &mut self,
+        id: u32,
+        offset: u64,
+        length: u64,
+        keep_size: bool,
+    ) -> Result<(), crate::errors::QrfsError> {
+        self.check_writable()?;
+        let size = match self.inodes.get(&id) {
+            Some(inode) => inode.size,
+            None => return Err(crate::errors::QrfsError::NotFound(format!("inodo {}", id))),
+        };
+        // reservar no cambia lo escrito: en solo-agregar se permite
+        self.check_flags(id, Some((size, &[])))?;
+        if length == 0 {
+            return Ok(());
+        }
+        let end = offset.saturating_add(length);
+        let key = self.file_key(id)?;
+
+        self.deferred_transaction(|fs, txn| {
+            fs.unpack_tail(txn, id)?;
+            let block_size = fs.file_block_len(&fs.inodes[&id]);
+            let mut blocks = fs.inodes[&id].blocks.clone();
+            let missing = end.div_ceil(block_size as u64).saturating_sub(blocks.len() as u64) as usize;
+            let new_blocks = fs.txn_reserve_blocks_for(txn, id, missing).ok_or_else(|| {
+                crate::errors::QrfsError::NoSpace(format!("faltan {} bloques libres", missing))
+            })?;
+            // un bloque recien reservado puede tener datos viejos en su qr
+            for (i, &block_id) in new_blocks.iter().enumerate() {
+                let index = (blocks.len() + i) as u64;
+                fs.write_file_block(key.as_ref(), index, block_id, vec![0u8; block_size])?;
+            }
+            blocks.extend(new_blocks);
 
-        match fs.load_directory(root_id) {
-            Ok(entries) => {
-                for entry in entries {
-                    if entry.name != "." && entry.name != ".." {
-                        fs.dir_cache.insert(entry.name, entry.inode_id);
-                    }
+            if let Some(inode) = fs.txn_inode_mut(txn, id) {
+                inode.blocks = blocks;
+                if !keep_size && end > inode.size {
+                    inode.size = end;
                 }
-                println!(
-                    "debug: directorio cargado. {} archivos encontrados.",
-                    fs.dir_cache.len()
-                );
-            }
-            Err(e) => {
-                println!(
-                    "debug: no se pudo cargar directorio (normal si es disco nuevo): {}",
-                    e
-                );
             }
+            Ok(())
+        })?;
+        if self.audit.is_some() {
+            let name = self.cached_name(id);
+            self.audit("fallocate", id, &name, &format!("{}+{}", offset, length));
+        }
+        if self.dirty_blocks.len() > self.max_dirty_blocks {
+            self.flush_data()?;
         }
+        Ok(())
+    }
 
-        Ok(fs)
+    // bytes que el archivo ocupa de verdad en el disco: sus bloques propios y,
+    // con la cola empaquetada, solo su pedazo del bloque compartido y su
+    // entrada del mapa. el tamaño (getattr) sigue siendo el logico; si algun
+    // dia hay bloques comprimidos, su largo guardado se cuenta aca
+    pub(crate) fn stored_bytes(&self, inode: &Inode) -> u64 {
+        let block_size = self.superblock.block_size as u64;
+        let own = inode.blocks.len() as u64 * block_size;
+        if inode.flags & INODE_FLAG_TAIL == 0 || inode.blocks.is_empty() {
+            return own;
+        }
+        let tail = inode.size - (inode.blocks.len() as u64 - 1) * block_size;
+        own - block_size + tail + tail::ENTRY_LEN as u64
     }
 
-    pub fn mount(self, mountpoint: &Path) -> Result<(), crate::errors::QrfsError> {
-        let options = vec![
-            MountOption::RW,
-            MountOption::FSName("qrfs".to_string()),
-        ];
+    // valor de XATTR_STORED o XATTR_RATIO (asignado / guardado: mas de 1 es
+    // lo que se ahorra)
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub(crate) fn stored_xattr(&self, id: u32, name: &str) -> Option<String> {
+        let inode = self.inodes.get(&id)?;
+        let stored = self.stored_bytes(inode);
+        let allocated = inode.blocks.len() as u64 * self.superblock.block_size as u64;
+        match name {
+            XATTR_STORED => Some(stored.to_string()),
+            XATTR_RATIO if stored == 0 => Some("1.00".to_string()),
+            XATTR_RATIO => Some(format!("{:.2}", allocated as f64 / stored as f64)),
+            _ => None,
+        }
+    }
 
-        fuser::mount2(self, mountpoint, &options)
-            .map_err(|e| crate::errors::QrfsError::Other(format!("fuse error: {}", e)))?;
-        Ok(())
+    // el bloque logico index del inodo es su cola en un bloque compartido
+    fn is_packed_tail(inode: &Inode, index: u64) -> bool {
+        inode.flags & INODE_FLAG_TAIL != 0 && index + 1 == inode.blocks.len() as u64
     }
 
-    // lee los bloques de datos de un inodo (directorio) y devuelve la lista de archivos
-    fn load_directory(
-        &self,
-        inode_id: u32,
-    ) -> Result<Vec<DirectoryEntry>, crate::errors::QrfsError> {
-        let inode = match self.inodes.get(&inode_id) {
-            Some(i) => i,
-            None => return Ok(Vec::new()),
+    // antes de cambiar un archivo con la cola compartida se la copia a un
+    // bloque propio. la copia va directo al qr: la misma transaccion puede
+    // liberar el bloque compartido
+    fn unpack_tail(
+        &mut self,
+        txn: &mut MetadataTransaction,
+        id: u32,
+    ) -> Result<(), crate::errors::QrfsError> {
+        let Some(inode) = self.inodes.get(&id) else {
+            return Ok(());
         };
-
-        let mut raw_data = Vec::new();
-        for &block_id in &inode.blocks {
-            let block = self.storage.read_block(block_id)?;
-            raw_data.extend_from_slice(&block);
+        let Some(&shared) = inode.blocks.last() else {
+            return Ok(());
+        };
+        if inode.flags & INODE_FLAG_TAIL == 0 {
+            return Ok(());
         }
+        let mut data = tail::tail_of(&self.read_data_block(shared)?, id)?.to_vec();
+        data.resize(self.superblock.block_size as usize, 0);
+
+        let own = self
+            .txn_reserve_blocks_for(txn, id, 1)
+            .ok_or_else(|| crate::errors::QrfsError::NoSpace("sin bloques libres".into()))?[0];
+        self.storage.write_block(own, &data)?;
+        if let Some(inode) = self.txn_inode_mut(txn, id) {
+            if let Some(last) = inode.blocks.last_mut() {
+                *last = own;
+            }
+            inode.flags &= !INODE_FLAG_TAIL;
+        }
+        self.release_shared_tail(txn, shared);
+        Ok(())
+    }
 
-        if inode.size == 0 || raw_data.is_empty() {
-            return Ok(Vec::new());
+    // el bloque compartido se libera con la ultima cola que lo usa
+    fn release_shared_tail(&mut self, txn: &mut MetadataTransaction, shared: BlockId) {
+        let used = self
+            .inodes
+            .values()
+            .any(|inode| inode.flags & INODE_FLAG_TAIL != 0 && inode.blocks.last() == Some(&shared));
+        if !used {
+            txn.freed.push(shared);
         }
+    }
 
-        let valid_data = &raw_data[..inode.size as usize];
+    // junta las colas de los archivos chicos en bloques compartidos: cada
+    // bloque nuevo se escribe entero antes de la transaccion que mueve los
+    // inodos y libera sus bloques propios. quedan afuera los cifrados (la
+    // cola va cifrada con la clave del archivo) y los que tienen bloques
+    // reservados de mas. devuelve cuantos bloques se liberaron
+    pub(crate) fn pack_tails(&mut self) -> Result<usize, crate::errors::QrfsError> {
+        self.check_writable()?;
+        if !self.superblock.tail_packing || self.superblock.inode_format == 0 {
+            return Err(crate::errors::QrfsError::Unimplemented(
+                "este disco no empaqueta colas (mkfs.qrfs --pack-tails)".into(),
+            ));
+        }
+        let block_size = self.superblock.block_size as usize;
+        if block_size > u16::MAX as usize || self.degraded || self.shared_disk {
+            return Ok(0);
+        }
+        self.flush_data()?;
+
+        let mut candidates: Vec<(u32, BlockId, u64)> = self
+            .inodes
+            .values()
+            .filter(|inode| {
+                inode.kind == InodeKind::File
+                    && inode.key.is_empty()
+                    && inode.flags & (INODE_FLAG_TAIL | INODE_FLAG_PINNED) == 0
+                    && inode.size > 0
+                    && inode.blocks.len() as u64 == inode.size.div_ceil(block_size as u64)
+            })
+            .filter_map(|inode| {
+                let len = inode.size - (inode.blocks.len() as u64 - 1) * block_size as u64;
+                (len as usize <= tail::max_tail(block_size))
+                    .then(|| (inode.id, *inode.blocks.last().unwrap(), len))
+            })
+            .collect();
+        candidates.sort();
+        let mut tails = Vec::new();
+        for (id, block_id, len) in candidates {
+            let mut data = self.read_data_block(block_id)?;
+            data.truncate(len as usize);
+            tails.push((id, data));
+        }
+        let bins = tail::pack(block_size, tails);
+        if bins.is_empty() {
+            return Ok(0);
+        }
 
-        let entries: Vec<DirectoryEntry> = bincode::deserialize(valid_data).map_err(|_| {
-            crate::errors::QrfsError::Other("error deserializando directorio".into())
-        })?;
+        self.transaction(|fs, txn| {
+            let shared = fs.txn_reserve_blocks(txn, bins.len()).ok_or_else(|| {
+                crate::errors::QrfsError::NoSpace("sin bloques libres para empaquetar colas".into())
+            })?;
+            let mut freed = 0;
+            for (bin, &block_id) in bins.iter().zip(&shared) {
+                fs.storage.write_block(block_id, &tail::encode(block_size, bin))?;
+                for (id, _) in bin {
+                    if let Some(inode) = fs.txn_inode_mut(txn, *id) {
+                        if let Some(last) = inode.blocks.last_mut() {
+                            txn.freed.push(*last);
+                            *last = block_id;
+                        }
+                        inode.flags |= INODE_FLAG_TAIL;
+                    }
+                }
+                freed += bin.len() - 1;
+            }
+            Ok(freed)
+        })
+    }
 
-        Ok(entries)
+    // al desmontar (o soltar la api) en un disco con empaquetado de colas
+    fn pack_tails_on_close(&mut self) {
+        if !self.superblock.tail_packing || self.read_only {
+            return;
+        }
+        if let Err(e) = self.pack_tails() {
+            eprintln!("qrfs: error empaquetando colas: {e}");
+        }
     }
 
-    // guarda la lista actual de archivos (dir_cache) en los bloques del inodo raiz
-    fn save_root_directory(&mut self) -> Result<(), crate::errors::QrfsError> {
-        let root_id = self.superblock.root_inode;
+    // lectura de exactamente un bloque alineado: devuelve el bloque tal cual,
+    // sin pasar por el buffer intermedio de read_data
+    #[cfg(all(unix, feature = "fuse-lowlevel"))]
+    fn whole_block(&self, id: u32, offset: u64, size: u64) -> Option<Vec<u8>> {
+        let inode = self.inodes.get(&id)?;
+        let block_size = self.file_block_len(inode) as u64;
 
-        let mut entries = Vec::new();
+        if !offset.is_multiple_of(block_size) || size != block_size || offset + block_size > inode.size {
+            return None;
+        }
 
-        entries.push(DirectoryEntry {
-            name: ".".to_string(),
-            inode_id: root_id,
-            kind: InodeKind::Directory,
-        });
-        entries.push(DirectoryEntry {
-            name: "..".to_string(),
-            inode_id: root_id,
-            kind: InodeKind::Directory,
-        });
+        let index = offset / block_size;
+        let phys_id = *inode.blocks.get(index as usize)?;
+        let key = self.file_key(id).ok()?;
+        self.read_file_block(key.as_ref(), index, phys_id)
+            .ok()
+            .filter(|data| data.len() as u64 == block_size)
+    }
 
-        for (name, &id) in &self.dir_cache {
-            let kind = if let Some(inode) = self.inodes.get(&id) {
-                inode.kind.clone()
-            } else {
-                InodeKind::File
-            };
+    // crea un archivo vacio en el directorio raiz (fuse; la api va por create_in)
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub(crate) fn create_file(
+        &mut self,
+        name: &str,
+        mode: u16,
+    ) -> Result<Inode, crate::errors::QrfsError> {
+        self.create_node(name, InodeKind::File, mode, 0)
+    }
 
-            entries.push(DirectoryEntry {
-                name: name.clone(),
-                inode_id: id,
-                kind,
-            });
+    // crea un archivo vacio o un directorio vacio dentro de dir_id
+    pub(crate) fn create_in(
+        &mut self,
+        dir_id: u32,
+        name: &str,
+        kind: InodeKind,
+        mode: u16,
+    ) -> Result<Inode, crate::errors::QrfsError> {
+        if !matches!(kind, InodeKind::File | InodeKind::Directory) {
+            return Err(crate::errors::QrfsError::Unimplemented(format!("{:?} fuera de la raiz", kind)));
         }
+        self.create_entry(dir_id, name, kind, mode, 0, &[])
+    }
 
-        let data = bincode::serialize(&entries)?;
-        let total_size = data.len() as u64;
-
-        let mut current_blocks = self.inodes.get(&root_id).unwrap().blocks.clone();
+    // crea un archivo especial (fifo, socket, dispositivo) o regular en el
+    // directorio raiz (mknod)
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub(crate) fn create_node(
+        &mut self,
+        name: &str,
+        kind: InodeKind,
+        mode: u16,
+        rdev: u32,
+    ) -> Result<Inode, crate::errors::QrfsError> {
+        if kind.is_device() && self.superblock.inode_format < 3 {
+            return Err(crate::errors::QrfsError::Unimplemented(format!(
+                "la tabla de inodos de este disco no guarda dispositivos (formato {})",
+                self.superblock.inode_format
+            )));
+        }
+        self.create_entry(self.superblock.root_inode, name, kind, mode, rdev, &[])
+    }
 
-        let block_size = self.superblock.block_size as usize;
-        let needed_blocks = (data.len() + block_size - 1) / block_size;
+    // enlace simbolico: el destino queda como contenido del inodo
+    // por ahora solo la expone fuse
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub(crate) fn create_symlink(
+        &mut self,
+        name: &str,
+        target: &str,
+    ) -> Result<Inode, crate::errors::QrfsError> {
+        self.create_entry(self.superblock.root_inode, name, InodeKind::Symlink, 0o777, 0, target.as_bytes())
+    }
 
-        while current_blocks.len() < needed_blocks {
-            if let Some(phys_id) = self.allocate_block() {
-                current_blocks.push(phys_id);
-            } else {
-                return Err(crate::errors::QrfsError::Other(
-                    "disco lleno guardando directorio".into(),
-                ));
-            }
+    fn create_entry(
+        &mut self,
+        dir_id: u32,
+        name: &str,
+        kind: InodeKind,
+        mode: u16,
+        rdev: u32,
+        data: &[u8],
+    ) -> Result<Inode, crate::errors::QrfsError> {
+        self.check_writable()?;
+        if self.dir_lookup(dir_id, name)?.is_some() {
+            return Err(crate::errors::QrfsError::AlreadyExists(name.to_string()));
         }
 
-        let mut offset = 0;
-        for (_i, &block_id) in current_blocks.iter().enumerate() {
-            let mut chunk = vec![0u8; block_size];
-
-            if offset < data.len() {
-                let end = std::cmp::min(offset + block_size, data.len());
-                let slice = &data[offset..end];
-                chunk[..slice.len()].copy_from_slice(slice);
-                offset += slice.len();
+        let op = match kind {
+            InodeKind::File => "create",
+            InodeKind::Directory => "mkdir",
+            InodeKind::Symlink => "symlink",
+            _ => "mknod",
+        };
+        let result = self.transaction(|fs, txn| {
+            let new_id = fs
+                .txn_allocate_inode_id(txn)
+                .ok_or_else(|| crate::errors::QrfsError::NoSpace("sin inodos libres".into()))?;
+
+            let mut new_inode = Inode::new_at(new_id, kind, fs.superblock.timestamp());
+            new_inode.mode = mode;
+            new_inode.rdev = rdev;
+            // en un disco con clave maestra cada archivo nuevo lleva la suya;
+            // sin la frase no se crean archivos que quedarian sin cifrar
+            if new_inode.kind == InodeKind::File && !fs.superblock.key_slot.is_empty() {
+                let master = fs.next_key.as_ref().or(fs.master_key.as_ref()).ok_or_else(|| {
+                    crate::errors::QrfsError::NotPermitted(
+                        "el disco cifra los archivos nuevos; falta la frase".into(),
+                    )
+                })?;
+                new_inode.key = crypt::wrap_key(master, &crypt::random_key()?)?;
             }
 
-            self.storage.write_block(block_id, &chunk)?;
+            fs.txn_insert_inode(txn, new_inode.clone());
+            fs.txn_dir_insert(txn, dir_id, name, new_id, new_inode.kind.clone())?;
+            // un directorio nuevo sale vacio, con su bloque de . y ..
+            if new_inode.kind == InodeKind::Directory {
+                txn.touch_dir(new_id, None);
+                fs.dir_cache.insert(new_id, DirEntries::new());
+            }
+            if !data.is_empty() {
+                fs.write_range(txn, new_id, 0, data)?;
+                new_inode = fs.inodes[&new_id].clone();
+            }
+            Ok(new_inode)
+        });
+        match &result {
+            Ok(inode) => self.audit(op, inode.id, name, &format!("modo {:o}", mode)),
+            Err(e) => println!("error creando {}: {}", name, e),
         }
+        result
+    }
 
-        self.save_bitmap()?;
-
-        if let Some(root_inode) = self.inodes.get_mut(&root_id) {
-            root_inode.blocks = current_blocks;
-            root_inode.size = total_size;
-            root_inode.modified_at = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs();
+    // destino de un enlace simbolico
+    // por ahora solo la expone fuse
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub(crate) fn read_link(&self, id: u32) -> Result<Vec<u8>, crate::errors::QrfsError> {
+        match self.inodes.get(&id) {
+            Some(inode) if inode.kind == InodeKind::Symlink => self.read_data(id, 0, inode.size),
+            Some(_) => Err(crate::errors::QrfsError::Other(format!(
+                "el inodo {} no es un enlace simbolico",
+                id
+            ))),
+            None => Err(crate::errors::QrfsError::NotFound(format!("inodo {}", id))),
         }
+    }
 
-        self.save_inode_table()?;
+    // borra un archivo regular (o directorio) y libera sus bloques
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub(crate) fn remove_file(&mut self, name: &str) -> Result<(), crate::errors::QrfsError> {
+        self.remove_in(self.superblock.root_inode, name)
+    }
 
+    // lo mismo dentro de dir_id; un directorio con algo adentro no se borra
+    pub(crate) fn remove_in(&mut self, dir_id: u32, name: &str) -> Result<(), crate::errors::QrfsError> {
+        if let Some(id) = self.dir_lookup(dir_id, name)? {
+            self.check_flags(id, None)?;
+            if self.inodes.get(&id).is_some_and(|inode| inode.kind == InodeKind::Directory)
+                && !self.dir_entries(id)?.is_empty()
+            {
+                return Err(crate::errors::QrfsError::NotEmpty(name.to_string()));
+            }
+        }
+        let inode_id = self.transaction(|fs, txn| {
+            let inode_id = fs.unlink_entry(txn, dir_id, name)?;
+            fs.free_inode(txn, inode_id)?;
+            Ok(inode_id)
+        })?;
+        self.audit("unlink", inode_id, name, "");
         Ok(())
     }
 
-    // guarda toda la tabla de inodos de memoria al disco (qrs)
-    fn save_inode_table(&self) -> Result<(), crate::errors::QrfsError> {
-        let mut serialized_data = Vec::new();
-
-        for id in 0..self.superblock.inode_count {
-            let inode_to_write = if let Some(inode) = self.inodes.get(&id) {
-                inode.clone()
-            } else {
-                let mut empty = Inode::new(id, InodeKind::File);
-                empty.mode = 0;
-                empty
-            };
-
-            let bytes = bincode::serialize(&inode_to_write)
-                .map_err(|_| crate::errors::QrfsError::Other("error serializando inodo".into()))?;
-            serialized_data.extend_from_slice(&bytes);
+    // saca el nombre del directorio; el inodo sigue vivo
+    fn unlink_entry(
+        &mut self,
+        txn: &mut MetadataTransaction,
+        dir_id: u32,
+        name: &str,
+    ) -> Result<u32, crate::errors::QrfsError> {
+        let inode_id = self
+            .dir_lookup(dir_id, name)?
+            .ok_or_else(|| crate::errors::QrfsError::NotFound(name.to_string()))?;
+        if !self.inodes.contains_key(&inode_id) {
+            return Err(crate::errors::QrfsError::NotFound(name.to_string()));
         }
 
-        let block_size = self.superblock.block_size as usize;
-        let start_block = self.superblock.inode_table_start;
-        let num_blocks = self.superblock.inode_table_blocks;
+        self.txn_dir_remove(txn, dir_id, name)?;
+        Ok(inode_id)
+    }
 
-        let mut offset = 0;
-        for i in 0..num_blocks {
-            let block_id = start_block + i;
-            let mut chunk = vec![0u8; block_size];
+    // libera un inodo y todos sus bloques de datos (unlink, rmdir)
+    pub(crate) fn free_inode(
+        &mut self,
+        txn: &mut MetadataTransaction,
+        id: u32,
+    ) -> Result<(), crate::errors::QrfsError> {
+        if id == self.superblock.root_inode {
+            return Err(crate::errors::QrfsError::Other("no se puede liberar la raiz".into()));
+        }
 
-            if offset < serialized_data.len() {
-                let end = std::cmp::min(offset + block_size, serialized_data.len());
-                let slice = &serialized_data[offset..end];
-                chunk[..slice.len()].copy_from_slice(slice);
-                offset += slice.len();
+        let inode = self
+            .txn_remove_inode(txn, id)
+            .ok_or_else(|| crate::errors::QrfsError::NotFound(format!("inodo {}", id)))?;
+        match inode.blocks.split_last() {
+            Some((&shared, own)) if inode.flags & INODE_FLAG_TAIL != 0 => {
+                txn.freed.extend_from_slice(own);
+                self.release_shared_tail(txn, shared);
             }
-
-            self.storage.write_block(block_id, &chunk)?;
+            _ => txn.freed.extend_from_slice(&inode.blocks),
         }
 
+        // si era un directorio, su cache ya no vale (se restaura si hay rollback)
+        if self.dir_cache.contains_key(&id) {
+            txn.touch_dir(id, self.dir_cache.get(&id));
+            self.dir_cache.remove(&id);
+        }
         Ok(())
     }
 
-    // encuentra un id de inodo libre
-    fn find_free_inode_id(&self) -> Option<u32> {
-        for i in 2..self.superblock.inode_count {
-            if !self.inodes.contains_key(&i) {
-                return Some(i);
-            }
-        }
-        None
+    // renombra dentro de la raiz; si el destino existe se reemplaza en la misma
+    // transaccion (el directorio cambia los dos nombres en una sola escritura y
+    // el inodo reemplazado se libera despues, como en un unlink)
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub(crate) fn rename_entry(&mut self, from: &str, to: &str) -> Result<(), crate::errors::QrfsError> {
+        let root = self.superblock.root_inode;
+        self.move_entry(root, from, root, to)
     }
 
-    // guarda el bitmap al disco
-    fn save_bitmap(&self) -> Result<(), crate::errors::QrfsError> {
-        let block_size = self.superblock.block_size as usize;
-        let start_block = self.superblock.free_map_start;
-        let num_blocks = self.superblock.free_map_blocks;
-
-        let mut offset = 0;
-        for i in 0..num_blocks {
-            let block_id = start_block + i;
-            let mut chunk = vec![0u8; block_size];
-
-            if offset < self.bitmap.len() {
-                let end = std::cmp::min(offset + block_size, self.bitmap.len());
-                let slice = &self.bitmap[offset..end];
-                chunk[..slice.len()].copy_from_slice(slice);
-                offset += slice.len();
+    // lo mismo entre dos directorios cualquiera
+    pub(crate) fn move_entry(
+        &mut self,
+        from_dir: u32,
+        from: &str,
+        to_dir: u32,
+        to: &str,
+    ) -> Result<(), crate::errors::QrfsError> {
+        for (dir, name) in [(from_dir, from), (to_dir, to)] {
+            if let Some(id) = self.dir_lookup(dir, name)? {
+                self.check_flags(id, None)?;
             }
-
-            self.storage.write_block(block_id, &chunk)?;
         }
-
+        // un directorio no puede terminar adentro de si mismo
+        if let Some(id) = self.dir_lookup(from_dir, from)? {
+            let root = self.superblock.root_inode;
+            let mut dir = to_dir;
+            for _ in 0..=self.inodes.len() {
+                if dir == id {
+                    return Err(crate::errors::QrfsError::NotPermitted(format!("{} adentro de si mismo", from)));
+                }
+                if dir == root {
+                    break;
+                }
+                dir = self.parent_of(dir);
+            }
+        }
+        if from_dir == to_dir && from == to {
+            return match self.dir_lookup(from_dir, from)? {
+                Some(_) => Ok(()),
+                None => Err(crate::errors::QrfsError::NotFound(from.to_string())),
+            };
+        }
+        let inode_id = self.transaction(|fs, txn| {
+            let (inode_id, kind) = fs
+                .txn_dir_remove(txn, from_dir, from)?
+                .ok_or_else(|| crate::errors::QrfsError::NotFound(from.to_string()))?;
+            if let Some((replaced, _)) = fs.txn_dir_remove(txn, to_dir, to)? {
+                if replaced != inode_id && fs.inodes.contains_key(&replaced) {
+                    fs.free_inode(txn, replaced)?;
+                }
+            }
+            // un directorio que cambia de padre reescribe su '..'
+            if from_dir != to_dir && kind == InodeKind::Directory {
+                fs.dir_entries(inode_id)?;
+                txn.touch_dir(inode_id, fs.dir_cache.get(&inode_id));
+            }
+            fs.txn_dir_insert(txn, to_dir, to, inode_id, kind)?;
+            Ok(inode_id)
+        })?;
+        self.audit("rename", inode_id, from, to);
         Ok(())
     }
 
-    // busca un bit libre en el bitmap y lo marca como usado
-    fn allocate_block(&mut self) -> Option<u32> {
-        let total_blocks = self.superblock.total_blocks as usize;
-
-        for (byte_idx, byte) in self.bitmap.iter_mut().enumerate() {
-            if *byte == 0xFF {
-                continue;
-            }
+    // cambia el tamaño de un archivo, liberando los bloques que sobran
+    pub(crate) fn truncate(&mut self, id: u32, size: u64) -> Result<(), crate::errors::QrfsError> {
+        self.check_flags(id, None)?;
+        self.transaction(|fs, txn| {
+            fs.unpack_tail(txn, id)?;
 
-            for bit_idx in 0..8 {
-                let global_id = byte_idx * 8 + bit_idx;
+            let (block_size, old_size, mut blocks) = match fs.inodes.get(&id) {
+                Some(inode) => (fs.file_block_len(inode) as u64, inode.size, inode.blocks.clone()),
+                None => return Err(crate::errors::QrfsError::NotFound(format!("inodo {}", id))),
+            };
 
-                if global_id < self.superblock.data_block_start as usize {
-                    continue;
-                }
-                if global_id >= total_blocks {
-                    return None;
+            if size < old_size {
+                let keep = size.div_ceil(block_size) as usize;
+                if blocks.len() > keep {
+                    txn.freed.extend(blocks.split_off(keep));
                 }
 
-                if (*byte & (1 << bit_idx)) == 0 {
-                    *byte |= 1 << bit_idx;
-                    return Some(global_id as u32);
+                // limpiar la cola del ultimo bloque para que no reaparezca al crecer
+                let tail = (size % block_size) as usize;
+                if tail > 0 {
+                    if let Some(&last) = blocks.last() {
+                        let key = fs.file_key(id)?;
+                        let index = blocks.len() as u64 - 1;
+                        let mut data = fs.read_file_block(key.as_ref(), index, last)?;
+                        data[tail..].fill(0);
+                        fs.write_file_block(key.as_ref(), index, last, data)?;
+                    }
                 }
             }
+
+            let now = fs.superblock.timestamp();
+            if let Some(inode) = fs.txn_inode_mut(txn, id) {
+                inode.blocks = blocks;
+                inode.size = size;
+                inode.modified_at = now;
+            }
+            Ok(())
+        })?;
+        if self.audit.is_some() {
+            let name = self.cached_name(id);
+            self.audit("truncate", id, &name, &size.to_string());
         }
-        None
+        Ok(())
     }
 }
 
+// adaptador fuse (linux y macos); windows usa otro backend
+#[cfg(unix)]
 impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
+    // negociar escrituras y lecturas grandes (el default son 4 KiB)
+    fn init(
+        &mut self,
+        _req: &Request<'_>,
+        config: &mut fuser::KernelConfig,
+    ) -> Result<(), libc::c_int> {
+        if let Err(nearest) = config.set_max_write(MAX_WRITE) {
+            let _ = config.set_max_write(nearest);
+        }
+        if let Err(nearest) = config.set_max_readahead(MAX_READAHEAD) {
+            let _ = config.set_max_readahead(nearest);
+        }
+
+        if self.writeback_cache
+            && config
+                .add_capabilities(fuser::consts::FUSE_WRITEBACK_CACHE)
+                .is_err()
+        {
+            println!("debug: el kernel no soporta writeback cache, se sigue sin ella");
+        }
+
+        Ok(())
+    }
+
     // obtener metadatos (size, permisos, fecha)
     fn getattr(&mut self, _req: &Request, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
         std::io::stdout().flush().unwrap();
+        self.catch_up();
+        if Self::is_virtual_file(ino) || ino == META_INO {
+            match self.virtual_attr(ino) {
+                Some(attr) => reply.attr(&Duration::ZERO, &attr),
+                None => reply.error(ENOENT),
+            }
+            return;
+        }
         let target = if ino == 1 {
             self.superblock.root_inode
         } else {
@@ -333,10 +3089,7 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
         };
 
         if let Some(inode) = self.inodes.get(&target) {
-            let kind = match inode.kind {
-                InodeKind::Directory => FileType::Directory,
-                InodeKind::File => FileType::RegularFile,
-            };
+            let kind = file_type(&inode.kind);
 
             let attr = FileAttr {
                 ino,
@@ -345,7 +3098,8 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
                 atime: UNIX_EPOCH,
                 mtime: UNIX_EPOCH,
                 ctime: UNIX_EPOCH,
-                crtime: UNIX_EPOCH,
+                // finder muestra crtime como "creado"
+                crtime: UNIX_EPOCH + Duration::from_secs(inode.created_at),
                 kind,
                 perm: if kind == FileType::Directory {
                     0o755
@@ -355,8 +3109,8 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
                 nlink: 1,
                 uid: 1000,
                 gid: 1000,
-                rdev: 0,
-                flags: 0,
+                rdev: inode.rdev,
+                flags: Self::bsd_flags(inode),
                 blksize: 512,
             };
             reply.attr(&TTL, &attr);
@@ -374,26 +3128,60 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
         offset: i64,
         mut reply: ReplyDirectory,
     ) {
-        if ino != 1 {
-            reply.error(ENOENT);
+        self.recheck_metadata();
+        if ino == META_INO {
+            let mut entries = vec![
+                (META_INO, FileType::Directory, "."),
+                (1, FileType::Directory, ".."),
+                (HEALTH_INO, FileType::RegularFile, HEALTH_FILE),
+            ];
+            if self.superblock.label_block != 0 {
+                entries.push((LABEL_INO, FileType::RegularFile, LABEL_FILE));
+            }
+            for (i, entry) in entries.into_iter().enumerate().skip(offset as usize) {
+                if reply.add(entry.0, (i + 1) as i64, entry.1, entry.2) {
+                    break;
+                }
+            }
+            reply.ok();
             return;
         }
+        let dir_id = if ino == 1 {
+            self.superblock.root_inode
+        } else {
+            ino as u32
+        };
+        // el tipo sale de la entrada del directorio: no hace falta el inodo
+        let listed: Vec<(String, u32, FileType)> = match self.dir_entries(dir_id) {
+            Ok(dir) => dir
+                .iter()
+                .map(|(name, (id, kind))| (name.clone(), *id, file_type(kind)))
+                .collect(),
+            Err(e) => {
+                reply.error(e.errno());
+                return;
+            }
+        };
 
         let mut entries = vec![
-            (1, FileType::Directory, ".".to_string()),
+            (ino, FileType::Directory, ".".to_string()),
             (1, FileType::Directory, "..".to_string()),
         ];
-
-        for (name, &id) in &self.dir_cache {
-            let kind = if let Some(inode) = self.inodes.get(&id) {
-                match inode.kind {
-                    InodeKind::Directory => FileType::Directory,
-                    InodeKind::File => FileType::RegularFile,
+        if dir_id == self.superblock.root_inode {
+            for name in [STATUS_FILE, MISSING_FILE, VOLUME_INFO_FILE, META_DIR] {
+                if let Some(ino) = self.virtual_ino(name) {
+                    let kind = if ino == META_INO {
+                        FileType::Directory
+                    } else {
+                        FileType::RegularFile
+                    };
+                    entries.push((ino, kind, name.to_string()));
                 }
-            } else {
-                FileType::RegularFile
-            };
-            entries.push((id as u64, kind, name.clone()));
+            }
+        }
+
+        for (name, id, kind) in listed {
+            entries.push((id as u64, kind, name));
         }
 
         for (i, entry) in entries.into_iter().enumerate().skip(offset as usize) {
@@ -406,10 +3194,12 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
 
     // buscar archivo por nombre
     fn lookup(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
-        if parent != 1 {
-            reply.error(ENOENT);
-            return;
-        }
+        self.recheck_metadata();
+        let dir_id = if parent == 1 {
+            self.superblock.root_inode
+        } else {
+            parent as u32
+        };
 
         let name_str = match name.to_str() {
             Some(s) => s,
@@ -419,6 +3209,19 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
             }
         };
 
+        if parent == META_INO {
+            let ino = match name_str {
+                HEALTH_FILE => Some(HEALTH_INO),
+                LABEL_FILE => Some(LABEL_INO),
+                _ => None,
+            };
+            match ino.and_then(|ino| self.virtual_attr(ino)) {
+                Some(attr) => reply.entry(&Duration::ZERO, &attr, 0),
+                None => reply.error(ENOENT),
+            }
+            return;
+        }
+
         if name_str == "." || name_str == ".." {
             let attr = FileAttr {
                 ino: 1,
@@ -441,12 +3244,23 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
             return;
         }
 
-        if let Some(&inode_id) = self.dir_cache.get(name_str) {
+        if let Some(ino) = self.virtual_ino(name_str).filter(|_| dir_id == self.superblock.root_inode) {
+            if let Some(attr) = self.virtual_attr(ino) {
+                reply.entry(&Duration::ZERO, &attr, 0);
+                return;
+            }
+        }
+
+        let found = match self.dir_lookup(dir_id, name_str) {
+            Ok(found) => found,
+            Err(e) => {
+                reply.error(e.errno());
+                return;
+            }
+        };
+        if let Some(inode_id) = found {
             if let Some(inode) = self.inodes.get(&inode_id) {
-                let kind = match inode.kind {
-                    InodeKind::Directory => FileType::Directory,
-                    InodeKind::File => FileType::RegularFile,
-                };
+                let kind = file_type(&inode.kind);
 
                 let attr = FileAttr {
                     ino: inode_id as u64,
@@ -461,8 +3275,8 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
                     nlink: 1,
                     uid: 1000,
                     gid: 1000,
-                    rdev: 0,
-                    flags: 0,
+                    rdev: inode.rdev,
+                    flags: Self::bsd_flags(inode),
                     blksize: 512,
                 };
                 reply.entry(&TTL, &attr, 0);
@@ -480,24 +3294,11 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
 
     // obtener informacion del sistema de archivos
     fn statfs(&mut self, _req: &Request, _ino: u64, reply: fuser::ReplyStatfs) {
-        let total_blocks = self.superblock.total_blocks as u64;
-        let block_size = self.superblock.block_size as u32;
-
-        let mut free_blocks = 0;
-        for (bite_idx, byte) in self.bitmap.iter().enumerate() {
-            for bit in 0..8 {
-                let global_bit = bite_idx * 8 + bit;
-                if global_bit >= self.superblock.total_blocks as usize {
-                    break;
-                }
-                if (byte & (1 << bit)) == 0 {
-                    free_blocks += 1;
-                }
-            }
-        }
-
+        // solo cuenta el area de datos, la metadata nunca esta disponible
+        let total_blocks = self.data_block_count();
+        let block_size = self.superblock.block_size;
+        let (free_blocks, free_inodes) = self.free_counts();
         let total_inodes = self.superblock.inode_count as u64;
-        let free_inodes = total_inodes - self.inodes.len() as u64;
 
         reply.statfs(
             total_blocks,
@@ -511,7 +3312,17 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
         );
     }
 
-    fn open(&mut self, _req: &Request, ino: u64, _flags: i32, reply: fuser::ReplyOpen) {
+    fn open(&mut self, _req: &Request, ino: u64, _flags: i32, reply: fuser::ReplyOpen) {
+        self.catch_up();
+        if (ino == STATUS_INO && self.seal.is_some())
+            || (ino == MISSING_INO && self.degraded)
+            || matches!(ino, HEALTH_INO | LABEL_INO | VOLUME_INFO_INO)
+        {
+            // sin cache: el contenido cambia con cada falla o bloque que llega
+            reply.opened(0, fuser::consts::FOPEN_DIRECT_IO);
+            return;
+        }
+
         let target = if ino == 1 {
             self.superblock.root_inode
         } else {
@@ -527,33 +3338,69 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
 
     fn setattr(
         &mut self,
-        _req: &Request,
+        req: &Request,
         ino: u64,
         _mode: Option<u32>,
         _uid: Option<u32>,
         _gid: Option<u32>,
-        _size: Option<u64>,
+        size: Option<u64>,
         _atime: Option<fuser::TimeOrNow>,
         _mtime: Option<fuser::TimeOrNow>,
         _ctime: Option<std::time::SystemTime>,
         _fh: Option<u64>,
-        _crtime: Option<std::time::SystemTime>,
+        crtime: Option<std::time::SystemTime>,
         _chgtime: Option<std::time::SystemTime>,
         _bkuptime: Option<std::time::SystemTime>,
-        _flags: Option<u32>,
+        flags: Option<u32>,
         reply: ReplyAttr,
     ) {
+        self.caller_uid = req.uid();
         let target = if ino == 1 {
             self.superblock.root_inode
         } else {
             ino as u32
         };
 
+        // chflags de macos (linux no manda flags ni crtime)
+        if let Some(bsd) = flags {
+            let mut new_flags = 0;
+            if bsd & (UF_IMMUTABLE | SF_IMMUTABLE) != 0 {
+                new_flags |= INODE_FLAG_IMMUTABLE;
+            }
+            if bsd & (UF_APPEND | SF_APPEND) != 0 {
+                new_flags |= INODE_FLAG_APPEND;
+            }
+            let current = self.inode_flags(target).unwrap_or(0);
+            let system_change = bsd & (SF_IMMUTABLE | SF_APPEND) != 0;
+            if new_flags != current {
+                if system_change && req.uid() != 0 {
+                    reply.error(libc::EPERM);
+                    return;
+                }
+                if let Err(e) = self.set_inode_flags(target, new_flags) {
+                    reply.error(e.errno());
+                    return;
+                }
+            }
+        }
+        if let Some(time) = crtime {
+            let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+            if let Err(e) = self.set_created_at(target, secs) {
+                reply.error(e.errno());
+                return;
+            }
+        }
+
+        // truncate / ftruncate / apertura con O_TRUNC
+        if let Some(new_size) = size {
+            if let Err(e) = self.truncate(target, new_size) {
+                reply.error(e.errno());
+                return;
+            }
+        }
+
         if let Some(inode) = self.inodes.get(&target) {
-            let kind = match inode.kind {
-                InodeKind::Directory => FileType::Directory,
-                InodeKind::File => FileType::RegularFile,
-            };
+            let kind = file_type(&inode.kind);
 
             let attr = FileAttr {
                 ino,
@@ -568,8 +3415,8 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
                 nlink: 1,
                 uid: 1000,
                 gid: 1000,
-                rdev: 0,
-                flags: 0,
+                rdev: inode.rdev,
+                flags: Self::bsd_flags(inode),
                 blksize: 512,
             };
             reply.attr(&TTL, &attr);
@@ -580,7 +3427,7 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
 
     fn create(
         &mut self,
-        _req: &Request<'_>,
+        req: &Request<'_>,
         _parent: u64,
         name: &OsStr,
         mode: u32,
@@ -588,59 +3435,38 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
         _flags: i32,
         reply: fuser::ReplyCreate,
     ) {
+        self.caller_uid = req.uid();
         std::io::stdout().flush().unwrap();
 
-        let new_id = match self.find_free_inode_id() {
-            Some(id) => id,
+        let filename = match name.to_str() {
+            Some(s) => s,
             None => {
-                reply.error(libc::ENOSPC);
+                reply.error(libc::EINVAL);
                 return;
             }
         };
 
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-
-        let new_inode = Inode {
-            id: new_id,
-            kind: InodeKind::File,
-            size: 0,
-            blocks: Vec::new(),
-            mode: mode as u16,
-            created_at: now,
-            modified_at: now,
+        let new_inode = match self.create_file(filename, mode as u16) {
+            Ok(inode) => inode,
+            Err(e) => {
+                reply.error(e.errno());
+                return;
+            }
         };
 
-        self.inodes.insert(new_id, new_inode.clone());
-        if let Some(filename) = name.to_str() {
-            self.dir_cache.insert(filename.to_string(), new_id);
-        }
-
-        if let Err(e) = self.save_root_directory() {
-            println!("error: no se pudo persistir el directorio: {}", e);
-        }
-
-        if let Err(e) = self.save_inode_table() {
-            println!("error guardando inodo: {}", e);
-            reply.error(libc::EIO);
-            return;
-        }
-
         let attr = FileAttr {
-            ino: new_id as u64,
+            ino: new_inode.id as u64,
             size: 0,
             blocks: 0,
-            atime: UNIX_EPOCH + Duration::from_secs(now),
-            mtime: UNIX_EPOCH + Duration::from_secs(now),
-            ctime: UNIX_EPOCH + Duration::from_secs(now),
-            crtime: UNIX_EPOCH + Duration::from_secs(now),
+            atime: UNIX_EPOCH + Duration::from_secs(new_inode.modified_at),
+            mtime: UNIX_EPOCH + Duration::from_secs(new_inode.modified_at),
+            ctime: UNIX_EPOCH + Duration::from_secs(new_inode.created_at),
+            crtime: UNIX_EPOCH + Duration::from_secs(new_inode.created_at),
             kind: FileType::RegularFile,
             perm: mode as u16,
             nlink: 1,
-            uid: _req.uid(),
-            gid: _req.gid(),
+            uid: req.uid(),
+            gid: req.gid(),
             rdev: 0,
             flags: 0,
             blksize: 512,
@@ -650,10 +3476,62 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
         std::io::stdout().flush().unwrap();
     }
 
+    // fifos, sockets y dispositivos (rsync -a, cp -a, mkfifo)
+    fn mknod(
+        &mut self,
+        req: &Request<'_>,
+        _parent: u64,
+        name: &OsStr,
+        mode: u32,
+        _umask: u32,
+        rdev: u32,
+        reply: ReplyEntry,
+    ) {
+        self.caller_uid = req.uid();
+        let (Some(filename), Some(kind)) = (name.to_str(), node_kind(mode)) else {
+            reply.error(libc::EINVAL);
+            return;
+        };
+        match self.create_node(filename, kind, mode as u16, rdev) {
+            Ok(inode) => reply.entry(&TTL, &new_node_attr(&inode, req), 0),
+            Err(e) => reply.error(e.errno()),
+        }
+    }
+
+    fn symlink(
+        &mut self,
+        req: &Request<'_>,
+        _parent: u64,
+        link_name: &OsStr,
+        target: &Path,
+        reply: ReplyEntry,
+    ) {
+        self.caller_uid = req.uid();
+        let (Some(filename), Some(target)) = (link_name.to_str(), target.to_str()) else {
+            reply.error(libc::EINVAL);
+            return;
+        };
+        match self.create_symlink(filename, target) {
+            Ok(inode) => reply.entry(&TTL, &new_node_attr(&inode, req), 0),
+            Err(e) => reply.error(e.errno()),
+        }
+    }
+
+    fn readlink(&mut self, _req: &Request, ino: u64, reply: fuser::ReplyData) {
+        match self.inodes.get(&(ino as u32)) {
+            Some(inode) if inode.kind == InodeKind::Symlink => match self.read_link(ino as u32) {
+                Ok(target) => reply.data(&target),
+                Err(e) => reply.error(e.errno()),
+            },
+            Some(_) => reply.error(libc::EINVAL),
+            None => reply.error(ENOENT),
+        }
+    }
+
     // escribir datos dentro de un archivo
     fn write(
         &mut self,
-        _req: &Request,
+        req: &Request,
         ino: u64,
         _fh: u64,
         offset: i64,
@@ -663,6 +3541,7 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
         _lock_owner: Option<u64>,
         reply: fuser::ReplyWrite,
     ) {
+        self.caller_uid = req.uid();
         std::io::stdout().flush().unwrap();
 
         let target = if ino == 1 {
@@ -670,60 +3549,17 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
         } else {
             ino as u32
         };
-        let block_size = BLOCK_SIZE as u64;
-
-        let offset_in_block = (offset as u64) % block_size;
-        let needed_logical_idx = (offset as u64) / block_size;
-
-        let current_blocks = if let Some(inode) = self.inodes.get(&target) {
-            inode.blocks.clone()
-        } else {
-            reply.error(libc::ENOENT);
-            return;
-        };
-
-        let mut new_block_list = current_blocks;
-
-        while (new_block_list.len() as u64) <= needed_logical_idx {
-            if let Some(phys_id) = self.allocate_block() {
-                new_block_list.push(phys_id);
-                let _ = self.save_bitmap();
-            } else {
-                reply.error(libc::ENOSPC);
-                return;
-            }
-        }
-
-        let physical_block_id = new_block_list[needed_logical_idx as usize];
-
-        let mut block_data = match self.storage.read_block(physical_block_id) {
-            Ok(d) => d,
-            Err(_) => vec![0u8; BLOCK_SIZE],
-        };
 
-        let end_in_block = std::cmp::min(offset_in_block as usize + data.len(), BLOCK_SIZE);
-        let len_to_write = end_in_block - offset_in_block as usize;
+        // con max_write grande llegan muchos bloques por peticion
+        let result = self.write_all_data(target, offset as u64, data);
 
-        block_data[offset_in_block as usize..end_in_block].copy_from_slice(&data[..len_to_write]);
-
-        if let Err(e) = self.storage.write_block(physical_block_id, &block_data) {
-            println!("error escribiendo datos: {}", e);
-            reply.error(libc::EIO);
-            return;
-        }
-
-        if let Some(inode) = self.inodes.get_mut(&target) {
-            inode.blocks = new_block_list;
-
-            let new_end = offset as u64 + len_to_write as u64;
-            if new_end > inode.size {
-                inode.size = new_end;
+        match result {
+            Ok(written) => reply.written(written as u32),
+            Err(e) => {
+                println!("error escribiendo datos: {}", e);
+                reply.error(e.errno());
             }
-
-            let _ = self.save_inode_table();
         }
-
-        reply.written(len_to_write as u32);
         std::io::stdout().flush().unwrap();
     }
 
@@ -738,65 +3574,39 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
         _lock_owner: Option<u64>,
         reply: fuser::ReplyData,
     ) {
+        if Self::is_virtual_file(ino) {
+            match self.virtual_text(ino) {
+                Some(text) => {
+                    let start = (offset as usize).min(text.len());
+                    let end = (start + size as usize).min(text.len());
+                    reply.data(&text.as_bytes()[start..end]);
+                }
+                None => reply.error(ENOENT),
+            }
+            return;
+        }
+
         let target = if ino == 1 {
             self.superblock.root_inode
         } else {
             ino as u32
         };
-        let block_size = BLOCK_SIZE as u64;
-
-        if let Some(inode) = self.inodes.get(&target) {
-            if offset as u64 >= inode.size {
-                reply.data(&[]);
-                return;
-            }
-
-            let mut data_buffer = Vec::new();
-            let mut current_offset = offset as u64;
-            let end_offset = std::cmp::min(inode.size, offset as u64 + size as u64);
-
-            while current_offset < end_offset {
-                let logical_block_idx = current_offset / block_size;
-                let offset_in_block = (current_offset % block_size) as usize;
-
-                let remaining_in_file = end_offset - current_offset;
-                let remaining_in_block = (block_size as u64) - (offset_in_block as u64);
-                let len_to_read = std::cmp::min(remaining_in_file, remaining_in_block) as usize;
-
-                if (logical_block_idx as usize) < inode.blocks.len() {
-                    let phys_id = inode.blocks[logical_block_idx as usize];
-
-                    match self.storage.read_block(phys_id) {
-                        Ok(block_data) => {
-                            if block_data.len() >= offset_in_block + len_to_read {
-                                data_buffer.extend_from_slice(
-                                    &block_data[offset_in_block..offset_in_block + len_to_read],
-                                );
-                            } else {
-                                data_buffer.extend(vec![0u8; len_to_read]);
-                            }
-                        }
-                        Err(_) => {
-                            reply.error(libc::EIO);
-                            return;
-                        }
-                    }
-                } else {
-                    data_buffer.extend(vec![0u8; len_to_read]);
-                }
 
-                current_offset += len_to_read as u64;
-            }
+        #[cfg(feature = "fuse-lowlevel")]
+        if let Some(block) = self.whole_block(target, offset as u64, size as u64) {
+            reply.data(&block);
+            return;
+        }
 
-            reply.data(&data_buffer);
-        } else {
-            reply.error(libc::ENOENT);
+        match self.read_data(target, offset as u64, size as u64) {
+            Ok(data) => reply.data(&data),
+            Err(e) => reply.error(e.errno()),
         }
     }
 
     fn rename(
         &mut self,
-        _req: &Request,
+        req: &Request,
         parent: u64,
         name: &OsStr,
         _newparent: u64,
@@ -804,6 +3614,7 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
         _flags: u32,
         reply: fuser::ReplyEmpty,
     ) {
+        self.caller_uid = req.uid();
         if parent != 1 {
             reply.error(ENOENT);
             return;
@@ -812,40 +3623,34 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
         let name_str = name.to_str().unwrap().to_string();
         let new_name_str = newname.to_str().unwrap().to_string();
 
-        if let Some(inode_id) = self.dir_cache.remove(&name_str) {
-            self.dir_cache.insert(new_name_str, inode_id);
-            let _ = self.save_root_directory();
-            reply.ok();
-        } else {
-            reply.error(ENOENT);
+        match self.rename_entry(&name_str, &new_name_str) {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e.errno()),
         }
     }
 
-    fn rmdir(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
+    fn rmdir(&mut self, req: &Request, parent: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
+        self.caller_uid = req.uid();
         if parent != 1 {
             reply.error(ENOENT);
             return;
         }
+        if let Err(e) = self.check_writable() {
+            reply.error(e.errno());
+            return;
+        }
         let name_str = name.to_str().unwrap().to_string();
 
-        if let Some(inode_id) = self.dir_cache.remove(&name_str) {
-            if let Some(_inode) = self.inodes.remove(&inode_id) {
-                // nada que hacer con el inodo
-            }
-            if let Err(e) = self.save_root_directory() {
-                println!("error persistiendo rmdir: {}", e);
-            }
-
-            let _ = self.save_inode_table();
-
-            reply.ok();
-        } else {
-            reply.error(ENOENT);
+        // igual que unlink: tambien libera los bloques del directorio
+        match self.remove_file(&name_str) {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e.errno()),
         }
     }
 
     // borrar un archivo regular (rm file.txt)
-    fn unlink(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
+    fn unlink(&mut self, req: &Request, parent: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
+        self.caller_uid = req.uid();
         if parent != 1 {
             reply.error(ENOENT);
             return;
@@ -859,60 +3664,237 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
             }
         };
 
-        let inode_id_opt = self.dir_cache.get(&name_str).cloned();
+        match self.remove_file(&name_str) {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e.errno()),
+        }
+    }
 
-        if let Some(inode_id) = inode_id_opt {
-            if let Some(inode) = self.inodes.get(&inode_id) {
-                for &block_id in &inode.blocks {
-                    let byte_idx = (block_id as usize) / 8;
-                    let bit_idx = (block_id as usize) % 8;
+    // solo la reserva comun (mode 0) y FALLOC_FL_KEEP_SIZE; perforar o
+    // colapsar rangos no esta soportado
+    fn fallocate(
+        &mut self,
+        req: &Request,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        length: i64,
+        mode: i32,
+        reply: fuser::ReplyEmpty,
+    ) {
+        const FALLOC_FL_KEEP_SIZE: i32 = 0x01;
+        self.caller_uid = req.uid();
+        if mode & !FALLOC_FL_KEEP_SIZE != 0 {
+            reply.error(libc::EOPNOTSUPP);
+            return;
+        }
+        if offset < 0 || length <= 0 {
+            reply.error(libc::EINVAL);
+            return;
+        }
 
-                    if byte_idx < self.bitmap.len() {
-                        self.bitmap[byte_idx] &= !(1 << bit_idx);
-                    }
-                }
-            } else {
-                reply.error(ENOENT);
-                return;
+        let target = if ino == 1 {
+            self.superblock.root_inode
+        } else {
+            ino as u32
+        };
+        let keep_size = mode & FALLOC_FL_KEEP_SIZE != 0;
+        match self.allocate_range(target, offset as u64, length as u64, keep_size) {
+            Ok(()) => reply.ok(),
+            Err(e) => {
+                println!("error reservando bloques: {}", e);
+                reply.error(e.errno());
             }
+        }
+    }
 
-            self.inodes.remove(&inode_id);
-            self.dir_cache.remove(&name_str);
+    fn fsync(
+        &mut self,
+        _req: &Request,
+        _ino: u64,
+        _fh: u64,
+        _datasync: bool,
+        reply: fuser::ReplyEmpty,
+    ) {
+        match self.flush_data() {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e.errno()),
+        }
+    }
 
-            if let Err(e) = self.save_root_directory() {
-                println!("error al persistir directorio tras borrado: {}", e);
-            }
+    // XATTR_STORED y XATTR_RATIO (getfattr -d -m qrfs); size 0 pide el largo
+    fn getxattr(&mut self, _req: &Request, ino: u64, name: &OsStr, size: u32, reply: fuser::ReplyXattr) {
+        let target = if ino == 1 {
+            self.superblock.root_inode
+        } else {
+            ino as u32
+        };
+        let Some(value) = name.to_str().and_then(|name| self.stored_xattr(target, name)) else {
+            reply.error(NO_XATTR);
+            return;
+        };
+        if size == 0 {
+            reply.size(value.len() as u32);
+        } else if (size as usize) < value.len() {
+            reply.error(libc::ERANGE);
+        } else {
+            reply.data(value.as_bytes());
+        }
+    }
+
+    fn listxattr(&mut self, _req: &Request, ino: u64, size: u32, reply: fuser::ReplyXattr) {
+        let target = if ino == 1 {
+            self.superblock.root_inode
+        } else {
+            ino as u32
+        };
+        if !self.inodes.contains_key(&target) {
+            reply.error(ENOENT);
+            return;
+        }
+        let names = format!("{}\0{}\0", XATTR_STORED, XATTR_RATIO);
+        if size == 0 {
+            reply.size(names.len() as u32);
+        } else if (size as usize) < names.len() {
+            reply.error(libc::ERANGE);
+        } else {
+            reply.data(names.as_bytes());
+        }
+    }
+
+    // ioctl(fd, QRFS_IOC_REENCODE o QRFS_IOC_STORED) y los de chattr/lsattr
+    // sobre un archivo abierto
+    #[allow(clippy::too_many_arguments)]
+    #[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+    fn ioctl(
+        &mut self,
+        req: &Request,
+        ino: u64,
+        _fh: u64,
+        _flags: u32,
+        cmd: u32,
+        in_data: &[u8],
+        _out_size: u32,
+        reply: fuser::ReplyIoctl,
+    ) {
+        let target = if ino == 1 {
+            self.superblock.root_inode
+        } else {
+            ino as u32
+        };
+
+        // chattr/lsattr usan GETFLAGS/SETFLAGS; el kernel ademas pide
+        // FSGETXATTR antes de cambiar flags (y xfs_io usa FSSETXATTR)
+        #[cfg(target_os = "linux")]
+        let attr_bits = if FS_IOC_GETFLAGS.contains(&cmd) || FS_IOC_SETFLAGS.contains(&cmd) {
+            Some((FS_IMMUTABLE_FL, FS_APPEND_FL))
+        } else if cmd == FS_IOC_FSGETXATTR || cmd == FS_IOC_FSSETXATTR {
+            Some((FS_XFLAG_IMMUTABLE, FS_XFLAG_APPEND))
+        } else {
+            None
+        };
+
+        #[cfg(target_os = "linux")]
+        if let Some((immutable_bit, append_bit)) = attr_bits {
+            let Some(current) = self.inode_flags(target) else {
+                reply.error(ENOENT);
+                return;
+            };
 
-            if let Err(e) = self.save_bitmap() {
-                println!("error al guardar bitmap en unlink: {}", e);
-                reply.error(libc::EIO);
+            if FS_IOC_GETFLAGS.contains(&cmd) || cmd == FS_IOC_FSGETXATTR {
+                let mut bits = 0u32;
+                if current & INODE_FLAG_IMMUTABLE != 0 {
+                    bits |= immutable_bit;
+                }
+                if current & INODE_FLAG_APPEND != 0 {
+                    bits |= append_bit;
+                }
+                // GETFLAGS devuelve un int; fsxattr son 28 bytes con xflags primero
+                let mut out = bits.to_le_bytes().to_vec();
+                if cmd == FS_IOC_FSGETXATTR {
+                    out.resize(FSXATTR_SIZE, 0);
+                }
+                reply.ioctl(0, &out);
                 return;
             }
 
-            if let Err(e) = self.save_inode_table() {
-                println!("error al guardar tabla de inodos en unlink: {}", e);
-                reply.error(libc::EIO);
+            let Some(bytes) = in_data.get(..4) else {
+                reply.error(libc::EINVAL);
+                return;
+            };
+            let bits = u32::from_le_bytes(bytes.try_into().unwrap());
+            let mut flags = 0;
+            if bits & immutable_bit != 0 {
+                flags |= INODE_FLAG_IMMUTABLE;
+            }
+            if bits & append_bit != 0 {
+                flags |= INODE_FLAG_APPEND;
+            }
+            if flags == current & !INTERNAL_FLAGS {
+                reply.ioctl(0, &[]);
                 return;
             }
+            // como en linux, cambiar estos flags es cosa de root
+            if req.uid() != 0 {
+                reply.error(libc::EPERM);
+                return;
+            }
+            match self.set_inode_flags(target, flags) {
+                Ok(()) => reply.ioctl(0, &[]),
+                Err(e) => reply.error(e.errno()),
+            }
+            return;
+        }
 
-            reply.ok();
-        } else {
-            reply.error(ENOENT);
+        if cmd == QRFS_IOC_STORED {
+            let Some(inode) = self.inodes.get(&target) else {
+                reply.error(ENOENT);
+                return;
+            };
+            let allocated = inode.blocks.len() as u64 * self.superblock.block_size as u64;
+            let mut out = allocated.to_le_bytes().to_vec();
+            out.extend_from_slice(&self.stored_bytes(inode).to_le_bytes());
+            reply.ioctl(0, &out);
+            return;
+        }
+        if cmd != QRFS_IOC_REENCODE {
+            reply.error(libc::ENOTTY);
+            return;
+        }
+
+        match self.reencode_inode(target) {
+            Ok(count) => reply.ioctl(0, &(count as u32).to_le_bytes()),
+            Err(e) => reply.error(e.errno()),
         }
     }
 
-    fn fsync(
+    // close(): los datos buffereados pasan a qr
+    fn flush(
         &mut self,
         _req: &Request,
         _ino: u64,
         _fh: u64,
-        _datasync: bool,
+        _lock_owner: u64,
         reply: fuser::ReplyEmpty,
     ) {
-        reply.ok();
+        match self.flush_data() {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e.errno()),
+        }
+    }
+
+    fn destroy(&mut self) {
+        if let Err(e) = self.flush_data() {
+            eprintln!("qrfs: error guardando bloques pendientes al desmontar: {e}");
+        }
+        self.pack_tails_on_close();
     }
 
     fn opendir(&mut self, _req: &Request, ino: u64, _flags: i32, reply: fuser::ReplyOpen) {
+        if ino == META_INO {
+            reply.opened(0, 0);
+            return;
+        }
         let target = if ino == 1 {
             self.superblock.root_inode
         } else {
@@ -924,7 +3906,7 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
                 InodeKind::Directory => {
                     reply.opened(0, 0);
                 }
-                InodeKind::File => {
+                _ => {
                     reply.error(libc::ENOTDIR);
                 }
             }
@@ -932,4 +3914,92 @@ impl<B: BlockStorage + 'static> Filesystem for QrfsFilesystem<B> {
             reply.error(libc::ENOENT);
         }
     }
+
+    // macos pide la fecha de creacion aparte (FUSE_XTIMES)
+    #[cfg(target_os = "macos")]
+    fn getxtimes(&mut self, _req: &Request, ino: u64, reply: fuser::ReplyXTimes) {
+        let target = if ino == 1 {
+            self.superblock.root_inode
+        } else {
+            ino as u32
+        };
+        match self.inodes.get(&target) {
+            Some(inode) => {
+                let crtime = UNIX_EPOCH + Duration::from_secs(inode.created_at);
+                reply.xtimes(UNIX_EPOCH, crtime);
+            }
+            None => reply.error(ENOENT),
+        }
+    }
+}
+
+// uid del proceso, para la auditoria cuando no hay un pedido de fuse
+// tipo que ve el kernel para cada tipo de inodo
+#[cfg(unix)]
+fn file_type(kind: &InodeKind) -> FileType {
+    match kind {
+        InodeKind::File => FileType::RegularFile,
+        InodeKind::Directory => FileType::Directory,
+        InodeKind::Symlink => FileType::Symlink,
+        InodeKind::Fifo => FileType::NamedPipe,
+        InodeKind::Socket => FileType::Socket,
+        InodeKind::CharDevice => FileType::CharDevice,
+        InodeKind::BlockDevice => FileType::BlockDevice,
+    }
+}
+
+// atributos de un inodo recien creado por mknod o symlink
+#[cfg(unix)]
+fn new_node_attr(inode: &Inode, req: &Request) -> FileAttr {
+    FileAttr {
+        ino: inode.id as u64,
+        size: inode.size,
+        blocks: inode.blocks.len() as u64,
+        atime: UNIX_EPOCH + Duration::from_secs(inode.modified_at),
+        mtime: UNIX_EPOCH + Duration::from_secs(inode.modified_at),
+        ctime: UNIX_EPOCH + Duration::from_secs(inode.created_at),
+        crtime: UNIX_EPOCH + Duration::from_secs(inode.created_at),
+        kind: file_type(&inode.kind),
+        perm: inode.mode,
+        nlink: 1,
+        uid: req.uid(),
+        gid: req.gid(),
+        rdev: inode.rdev,
+        flags: 0,
+        blksize: 512,
+    }
+}
+
+// tipo de inodo que pide mknod en los bits S_IFMT del modo
+#[cfg(unix)]
+fn node_kind(mode: u32) -> Option<InodeKind> {
+    // mode_t es u32 en linux y u16 en macos
+    match mode as libc::mode_t & libc::S_IFMT {
+        libc::S_IFREG => Some(InodeKind::File),
+        libc::S_IFIFO => Some(InodeKind::Fifo),
+        libc::S_IFSOCK => Some(InodeKind::Socket),
+        libc::S_IFCHR => Some(InodeKind::CharDevice),
+        libc::S_IFBLK => Some(InodeKind::BlockDevice),
+        _ => None,
+    }
+}
+
+#[cfg(unix)]
+fn current_uid() -> u32 {
+    unsafe { libc::getuid() }
+}
+
+#[cfg(not(unix))]
+fn current_uid() -> u32 {
+    0
+}
+
+// lo que quede en el buffer se guarda al soltar el filesystem (api, server)
+impl<B: BlockStorage + 'static> Drop for QrfsFilesystem<B> {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush_data() {
+            eprintln!("qrfs: error guardando bloques pendientes: {e}");
+        }
+        self.pack_tails_on_close();
+    }
 }
@@ -1,9 +1,19 @@
-use crate::disk::{Inode, InodeKind, Superblock};
+use std::fmt;
+use std::io::{self, Read};
+
+use bincode::Options;
+
+use crate::disk::{
+    BlockId, DirBlockHeader, DirectoryEntry, DiskLabel, Inode, InodeBlockHeader, InodeKind, InodeV0,
+    InodeV1, InodeV3, Superblock, DIR_HEADER, DIR_MAGIC, INODE_BLOCK_HEADER, INODE_BLOCK_MAGIC,
+    INODE_EXT_HEADER, LABEL_BLOCK_MAGIC, SUPERBLOCK_MAX_BLOCKS, SUPERBLOCK_RESERVED_BYTES,
+};
 use crate::errors::QrfsError;
+use crate::storage::BlockStorage;
 
 // genera un vector de bytes representando el bitmap
 pub fn create_empty_bitmap(total_blocks: u32) -> Vec<u8> {
-    let bytes = (total_blocks as usize + 7) / 8;
+    let bytes = (total_blocks as usize).div_ceil(8);
     vec![0u8; bytes]
 }
 
@@ -13,8 +23,55 @@ pub fn serialize_superblock(sb: &Superblock) -> Result<Vec<u8>, QrfsError> {
     Ok(encoded)
 }
 
-// serializa la tabla de inodos inicial
-pub fn create_inode_table(count: u32) -> Result<Vec<u8>, QrfsError> {
+// reparte el superblock en los bloques de su region, completando con ceros
+pub fn encode_superblock(sb: &Superblock) -> Result<Vec<Vec<u8>>, QrfsError> {
+    let block_size = sb.block_size as usize;
+    let region = sb.region_blocks() as usize;
+    let mut bytes = serialize_superblock(sb)?;
+    if bytes.len() > region * block_size {
+        return Err(QrfsError::Other(format!(
+            "el superblock ocupa {} bytes y tiene {} reservados",
+            bytes.len(),
+            region * block_size
+        )));
+    }
+    bytes.resize(region * block_size, 0);
+    Ok(bytes.chunks(block_size).map(|chunk| chunk.to_vec()).collect())
+}
+
+// lee un superblock de lo que haya; los campos que no llegan quedan en 0
+// (asi se leen los campos agregados despues en discos viejos, y el bloque 0
+// solo alcanza para la firma y la geometria)
+pub fn decode_superblock(bytes: &[u8]) -> Result<Superblock, QrfsError> {
+    let mut padded = bytes.to_vec();
+    padded.resize(bytes.len().max(SUPERBLOCK_RESERVED_BYTES as usize), 0);
+    bincode::deserialize(&padded).map_err(|_| QrfsError::Other("superblock ilegible".into()))
+}
+
+// lee el bloque 0 y, si el superblock ocupa mas, el resto de su region.
+// no verifica la firma: cada llamador decide que hacer con uno invalido
+pub fn read_superblock<S: BlockStorage + ?Sized>(storage: &S) -> Result<Superblock, QrfsError> {
+    let mut bytes = storage.read_block(0)?;
+    let head = decode_superblock(&bytes)?;
+    let region = head.region_blocks();
+    if !head.is_valid() || region == 1 {
+        return Ok(head);
+    }
+    if region > SUPERBLOCK_MAX_BLOCKS {
+        return Err(QrfsError::Corrupt(format!(
+            "el superblock dice ocupar {} bloques (maximo {})",
+            region, SUPERBLOCK_MAX_BLOCKS
+        )));
+    }
+    for id in 1..region {
+        bytes.extend(storage.read_block(id)?);
+    }
+    decode_superblock(&bytes)
+}
+
+// serializa la tabla de inodos inicial en el formato pedido (INODE_FORMAT o 0),
+// con la hora now en todos los slots
+pub fn create_inode_table(count: u32, format: u32, now: u64) -> Result<Vec<u8>, QrfsError> {
     let mut inodes = Vec::new();
 
     for i in 0..count {
@@ -24,7 +81,7 @@ pub fn create_inode_table(count: u32) -> Result<Vec<u8>, QrfsError> {
             InodeKind::File
         };
 
-        let mut inode = Inode::new(i, kind);
+        let mut inode = Inode::new_at(i, kind, now);
 
         // si no es el inodo 0 (root), le ponemos modo 0
         // esto le indica al sistema que esta "libre"
@@ -32,9 +89,716 @@ pub fn create_inode_table(count: u32) -> Result<Vec<u8>, QrfsError> {
             inode.mode = 0; 
         }
 
-        let encoded = bincode::serialize(&inode)?;
+        let encoded = inode.encode(format)?;
         inodes.extend(encoded);
     }
 
     Ok(inodes)
-}
\ No newline at end of file
+}
+
+// escribe superblock, bitmap y tabla de inodos vacia sobre cualquier almacenamiento
+// (mkfs lo usa con los qr, las pruebas con InMemoryBlockStorage)
+pub fn format_storage<S: BlockStorage + ?Sized>(
+    storage: &S,
+    superblock: &Superblock,
+) -> Result<(), QrfsError> {
+    let block_size = superblock.block_size as usize;
+
+    // escribir superblock (desde el bloque 0 - la "firma" del inicio)
+    for (id, block) in encode_superblock(superblock)?.iter().enumerate() {
+        storage.write_block(id as BlockId, block)?;
+    }
+
+    // escribir bitmap
+    let mut bitmap = create_empty_bitmap(superblock.total_blocks);
+    // marcar bloques reservados como usados
+    for blk in 0..superblock.data_block_start {
+        let byte = (blk / 8) as usize;
+        let bit = (blk % 8) as u8;
+        if byte < bitmap.len() { bitmap[byte] |= 1 << bit; }
+    }
+    write_region(storage, superblock.free_map_start, superblock.free_map_blocks, &bitmap)?;
+
+    // escribir tabla de inodos
+    let inode_table_raw = create_inode_table(superblock.inode_count, superblock.inode_format, superblock.timestamp())?;
+    for (block_id, block) in encode_inode_table(superblock, &[], block_size, &inode_table_raw, 1)? {
+        storage.write_block(block_id, &block)?;
+    }
+
+    Ok(())
+}
+
+// reparte bytes en bloques consecutivos, rellenando con ceros
+fn write_region<S: BlockStorage + ?Sized>(
+    storage: &S,
+    start: u32,
+    blocks: u32,
+    bytes: &[u8],
+) -> Result<(), QrfsError> {
+    let block_size = storage.block_size();
+
+    let mut offset = 0;
+    for i in 0..blocks {
+        let mut blk_buf = vec![0u8; block_size];
+        if offset < bytes.len() {
+            let end = usize::min(offset + block_size, bytes.len());
+            blk_buf[..end - offset].copy_from_slice(&bytes[offset..end]);
+        }
+        storage.write_block(start + i, &blk_buf)?;
+        offset += block_size;
+    }
+
+    Ok(())
+}
+
+// bytes a escribir en los bloques de un directorio y el tamaño que va en el
+// inodo (las entradas serializadas, sin encabezados). en formato 0 es bincode
+// plano; en formato 1 cada bloque lleva un DirBlockHeader y el ultimo se rellena
+pub fn encode_directory(
+    inode_id: u32,
+    generation: u32,
+    entries: &[DirectoryEntry],
+    block_size: usize,
+    format: u32,
+) -> Result<(Vec<u8>, u64), QrfsError> {
+    let payload = bincode::serialize(entries)?;
+    let size = payload.len() as u64;
+    if format == 0 {
+        return Ok((payload, size));
+    }
+
+    let header = bincode::serialize(&DirBlockHeader {
+        magic: DIR_MAGIC,
+        inode_id,
+        generation,
+        entry_count: entries.len() as u32,
+        size: payload.len() as u32,
+        crc: crc32fast::hash(&payload),
+    })?;
+    let mut data = Vec::new();
+    for chunk in payload.chunks(block_size - DIR_HEADER) {
+        let start = data.len();
+        data.extend_from_slice(&header);
+        data.extend_from_slice(chunk);
+        data.resize(start + block_size, 0);
+    }
+    Ok((data, size))
+}
+
+// entradas de un directorio a partir de sus bloques (concatenados en raw, en
+// el orden de inode.blocks) y la generacion con la que se escribieron.
+// en formato 1 se rechaza cualquier bloque cuyo encabezado no coincida, y el
+// largo sale del encabezado (un size 0 en el inodo es un directorio sin escribir)
+pub fn decode_directory(
+    inode: &Inode,
+    raw: &[u8],
+    block_size: usize,
+    format: u32,
+) -> Result<(Vec<DirectoryEntry>, u32), QrfsError> {
+    if inode.size == 0 || raw.is_empty() {
+        return Ok((Vec::new(), 0));
+    }
+
+    if format == 0 {
+        let size = inode.size as usize;
+        let entries = raw
+            .get(..size)
+            .and_then(|data| bincode::deserialize(data).ok())
+            .ok_or_else(|| QrfsError::Other("error deserializando directorio".into()))?;
+        return Ok((entries, 0));
+    }
+
+    let bad = |msg: String| QrfsError::Other(format!("directorio {}: {}", inode.id, msg));
+    let head: DirBlockHeader = bincode::deserialize(&raw[..DIR_HEADER])?;
+    if head.magic != DIR_MAGIC {
+        let block_id = inode.blocks.first().copied().unwrap_or_default();
+        return Err(bad(format!("bloque {} sin encabezado de directorio", block_id)));
+    }
+    let size = head.size as usize;
+    // los bloques de mas (el directorio se achico) no se miran
+    let used = size.div_ceil(block_size - DIR_HEADER).max(1);
+    if raw.len() < used * block_size {
+        return Err(bad(format!("{} bytes no entran en {} bloques", size, inode.blocks.len())));
+    }
+
+    let mut payload = Vec::with_capacity(size);
+    for (i, block) in raw.chunks(block_size).take(used).enumerate() {
+        let block_id = inode.blocks.get(i).copied().unwrap_or_default();
+        let header: DirBlockHeader = bincode::deserialize(&block[..DIR_HEADER])?;
+        if header.magic != DIR_MAGIC {
+            return Err(bad(format!("bloque {} sin encabezado de directorio", block_id)));
+        }
+        if header.inode_id != inode.id {
+            return Err(bad(format!("bloque {} pertenece al inodo {}", block_id, header.inode_id)));
+        }
+        if header.generation != head.generation {
+            return Err(bad(format!(
+                "bloque {} con generacion {} (el primero tiene {})",
+                block_id, header.generation, head.generation
+            )));
+        }
+        if header != head {
+            return Err(bad(format!("bloque {} con encabezado distinto al resto", block_id)));
+        }
+        payload.extend_from_slice(&block[DIR_HEADER..]);
+    }
+    payload.truncate(size);
+
+    if crc32fast::hash(&payload) != head.crc {
+        return Err(bad("crc de las entradas no coincide".into()));
+    }
+    let entries: Vec<DirectoryEntry> = bincode::deserialize(&payload)
+        .map_err(|_| bad("error deserializando directorio".into()))?;
+    if entries.len() != head.entry_count as usize {
+        return Err(bad(format!(
+            "{} entradas pero el encabezado dice {}",
+            entries.len(),
+            head.entry_count
+        )));
+    }
+    Ok((entries, head.generation))
+}
+
+// lee y valida un directorio directamente del almacenamiento (fsck, herramientas)
+pub fn read_directory<S: BlockStorage + ?Sized>(
+    storage: &S,
+    sb: &Superblock,
+    inode: &Inode,
+) -> Result<(Vec<DirectoryEntry>, u32), QrfsError> {
+    let mut raw = Vec::with_capacity(inode.blocks.len() * storage.block_size());
+    for &block_id in &inode.blocks {
+        raw.extend_from_slice(&storage.read_block(block_id)?);
+    }
+    decode_directory(inode, &raw, storage.block_size(), sb.dir_format)
+}
+
+// error al decodificar un slot de la tabla de inodos
+#[derive(Debug)]
+pub struct InodeSlotError {
+    pub slot: u32,
+    pub error: QrfsError,
+}
+
+impl fmt::Display for InodeSlotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "slot {} de la tabla de inodos: {}", self.slot, self.error)
+    }
+}
+
+// bloques que forman la tabla de inodos, en orden, con el offset donde empiezan
+// los datos de la tabla: los fijos de mkfs enteros, los de la extension despues
+// del puntero al siguiente; en formato >= 2 ademas despues del encabezado
+pub fn inode_table_layout(sb: &Superblock, ext_blocks: &[BlockId]) -> Vec<(BlockId, usize)> {
+    let header = if sb.inode_format >= 2 { INODE_BLOCK_HEADER } else { 0 };
+    (sb.inode_table_start..sb.inode_table_start + sb.inode_table_blocks)
+        .map(|id| (id, header))
+        .chain(ext_blocks.iter().map(|&id| (id, INODE_EXT_HEADER + header)))
+        .collect()
+}
+
+// reparte la tabla serializada en sus bloques: punteros de la extension,
+// encabezado con la generacion y relleno con ceros. la tabla tiene que entrar
+pub fn encode_inode_table(
+    sb: &Superblock,
+    ext_blocks: &[BlockId],
+    block_size: usize,
+    data: &[u8],
+    generation: u32,
+) -> Result<Vec<(BlockId, Vec<u8>)>, QrfsError> {
+    let layout = inode_table_layout(sb, ext_blocks);
+    let mut blocks = Vec::with_capacity(layout.len());
+    let mut offset = 0;
+
+    for (i, &(block_id, start)) in layout.iter().enumerate() {
+        let mut chunk = vec![0u8; block_size];
+
+        if i >= sb.inode_table_blocks as usize {
+            let next = layout.get(i + 1).map(|&(id, _)| id).unwrap_or(0);
+            chunk[..INODE_EXT_HEADER].copy_from_slice(&next.to_le_bytes());
+        }
+
+        if offset < data.len() {
+            let end = usize::min(offset + block_size - start, data.len());
+            let slice = &data[offset..end];
+            chunk[start..start + slice.len()].copy_from_slice(slice);
+            offset += slice.len();
+        }
+
+        if sb.inode_format >= 2 {
+            let at = start - INODE_BLOCK_HEADER;
+            let header = bincode::serialize(&InodeBlockHeader {
+                magic: INODE_BLOCK_MAGIC,
+                generation,
+                crc: inode_block_crc(&chunk, at),
+            })?;
+            chunk[at..start].copy_from_slice(&header);
+        }
+        blocks.push((block_id, chunk));
+    }
+
+    if offset < data.len() {
+        return Err(QrfsError::NoSpace(format!(
+            "la tabla de inodos necesita {} bytes y entran {}",
+            data.len(),
+            offset
+        )));
+    }
+    Ok(blocks)
+}
+
+// crc de un bloque de la tabla sin contar el encabezado (que empieza en at)
+fn inode_block_crc(block: &[u8], at: usize) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&block[..at]);
+    hasher.update(&block[at + INODE_BLOCK_HEADER..]);
+    hasher.finalize()
+}
+
+// valida el encabezado de un bloque de la tabla (formato >= 2) cuyos datos
+// empiezan en start y devuelve su generacion
+pub fn check_inode_block(block_id: BlockId, block: &[u8], start: usize) -> Result<u32, QrfsError> {
+    let at = start - INODE_BLOCK_HEADER;
+    let header: InodeBlockHeader = bincode::deserialize(&block[at..start])?;
+    if header.magic != INODE_BLOCK_MAGIC {
+        return Err(QrfsError::Corrupt(format!(
+            "bloque {} de la tabla de inodos sin encabezado",
+            block_id
+        )));
+    }
+    if header.crc != inode_block_crc(block, at) {
+        return Err(QrfsError::Corrupt(format!(
+            "bloque {} de la tabla de inodos con crc invalido",
+            block_id
+        )));
+    }
+    Ok(header.generation)
+}
+
+// error si un bloque trae otra generacion que el primero de la tabla
+fn check_inode_generation(block_id: BlockId, generation: u32, first: u32) -> Result<(), QrfsError> {
+    if generation != first {
+        return Err(QrfsError::Corrupt(format!(
+            "escritura cortada de la tabla de inodos: bloque {} con generacion {} (el primero tiene {})",
+            block_id, generation, first
+        )));
+    }
+    Ok(())
+}
+
+// revisa los encabezados de todos los bloques de la tabla (tambien los que
+// todavia no tienen inodos) y devuelve la generacion; None en formatos sin encabezado
+pub fn check_inode_table<S: BlockStorage + ?Sized>(
+    storage: &S,
+    sb: &Superblock,
+) -> Result<Option<u32>, QrfsError> {
+    if sb.inode_format < 2 {
+        return Ok(None);
+    }
+    let ext_blocks = inode_ext_chain(storage, sb)?;
+    let mut buf = vec![0u8; storage.block_size()];
+
+    // primero los crc: un bloque roto no es lo mismo que uno viejo
+    let mut generations = Vec::new();
+    for (block_id, start) in inode_table_layout(sb, &ext_blocks) {
+        storage.read_block_into(block_id, &mut buf)?;
+        generations.push((block_id, check_inode_block(block_id, &buf, start)?));
+    }
+    let first = generations.first().map(|&(_, g)| g).unwrap_or(0);
+    for (block_id, generation) in generations {
+        check_inode_generation(block_id, generation, first)?;
+    }
+    Ok(Some(first))
+}
+
+// sigue la cadena de bloques de extension de la tabla de inodos desde el superblock
+pub fn inode_ext_chain_with<F>(
+    sb: &Superblock,
+    block_size: usize,
+    mut read_block: F,
+) -> Result<Vec<BlockId>, QrfsError>
+where
+    F: FnMut(BlockId, &mut [u8]) -> Result<(), QrfsError>,
+{
+    let mut chain = Vec::new();
+    let mut buf = vec![0u8; block_size];
+    let mut next = sb.inode_ext_head;
+
+    while next != 0 {
+        if next < sb.data_block_start || next >= sb.total_blocks {
+            return Err(QrfsError::Other(format!(
+                "extension de la tabla de inodos apunta a bloque invalido {}",
+                next
+            )));
+        }
+        if chain.contains(&next) {
+            return Err(QrfsError::Other(format!(
+                "extension de la tabla de inodos con ciclo en el bloque {}",
+                next
+            )));
+        }
+        read_block(next, &mut buf)?;
+        chain.push(next);
+        next = u32::from_le_bytes(buf[..INODE_EXT_HEADER].try_into().unwrap());
+    }
+
+    Ok(chain)
+}
+
+pub fn inode_ext_chain<S: BlockStorage + ?Sized>(
+    storage: &S,
+    sb: &Superblock,
+) -> Result<Vec<BlockId>, QrfsError> {
+    inode_ext_chain_with(sb, storage.block_size(), |id, buf| storage.read_block_into(id, buf))
+}
+
+// el rotulo en su bloque (con ceros hasta block_size)
+pub fn encode_label(label: &DiskLabel, block_size: usize) -> Result<Vec<u8>, QrfsError> {
+    let mut bytes = bincode::serialize(label)?;
+    if bytes.len() > block_size {
+        return Err(QrfsError::Other(format!("el rotulo no entra en un bloque de {} bytes", block_size)));
+    }
+    bytes.resize(block_size, 0);
+    Ok(bytes)
+}
+
+// rotulo del disco; Ok(None) si nunca se rotulo
+pub fn read_label<S: BlockStorage + ?Sized>(storage: &S, sb: &Superblock) -> Result<Option<DiskLabel>, QrfsError> {
+    let block = sb.label_block;
+    if block == 0 {
+        return Ok(None);
+    }
+    if block < sb.data_block_start || block >= sb.total_blocks {
+        return Err(QrfsError::Corrupt(format!("el rotulo apunta a bloque invalido {}", block)));
+    }
+    let label: DiskLabel = bincode::deserialize(&storage.read_block(block)?)
+        .map_err(|_| QrfsError::Corrupt(format!("bloque {} del rotulo ilegible", block)))?;
+    if label.magic != LABEL_BLOCK_MAGIC {
+        return Err(QrfsError::Corrupt(format!("el bloque {} no tiene el rotulo", block)));
+    }
+    Ok(Some(label))
+}
+
+// recorre la tabla de inodos slot por slot leyendo los bloques a medida que hacen falta
+// los inodos tienen largo variable (lista de bloques), asi que si un slot no se puede
+// decodificar no se sabe donde empieza el siguiente: se reporta el error y se termina
+pub struct InodeTableReader<F> {
+    source: BlockSource<F>,
+    slot: u32,
+    count: u32,
+    format: u32,
+    done: bool,
+}
+
+// lector de bytes sobre los bloques de la tabla (solo guarda el bloque actual)
+struct BlockSource<F> {
+    read_block: F,
+    blocks: Vec<(BlockId, usize)>,
+    next: usize,
+    buf: Vec<u8>,
+    pos: usize,
+    error: Option<QrfsError>,
+    // validar el encabezado de cada bloque al leerlo
+    headers: bool,
+    generation: Option<u32>,
+}
+
+impl<F> InodeTableReader<F>
+where
+    F: FnMut(BlockId, &mut [u8]) -> Result<(), QrfsError>,
+{
+    // read_block llena un buffer de block_size con el contenido del bloque
+    // ext_blocks es la cadena de extension (ver inode_ext_chain)
+    pub fn new(sb: &Superblock, block_size: usize, ext_blocks: &[BlockId], read_block: F) -> Self {
+        Self {
+            source: BlockSource {
+                read_block,
+                blocks: inode_table_layout(sb, ext_blocks),
+                next: 0,
+                buf: vec![0u8; block_size],
+                pos: block_size,
+                error: None,
+                headers: sb.inode_format >= 2,
+                generation: None,
+            },
+            slot: 0,
+            count: sb.inode_count,
+            format: sb.inode_format,
+            done: false,
+        }
+    }
+
+    // generacion de los bloques leidos hasta ahora (None sin encabezados)
+    pub fn generation(&self) -> Option<u32> {
+        self.source.generation
+    }
+}
+
+// lector de la tabla de inodos directamente sobre un almacenamiento
+// si la cadena de extension esta rota, los slots que caen ahi salen con error
+pub fn read_inode_table<'a, S: BlockStorage + ?Sized>(
+    storage: &'a S,
+    sb: &Superblock,
+) -> InodeTableReader<impl FnMut(BlockId, &mut [u8]) -> Result<(), QrfsError> + 'a> {
+    let ext_blocks = inode_ext_chain(storage, sb).unwrap_or_default();
+    InodeTableReader::new(sb, storage.block_size(), &ext_blocks, move |id, buf| {
+        storage.read_block_into(id, buf)
+    })
+}
+
+impl<F> Iterator for InodeTableReader<F>
+where
+    F: FnMut(BlockId, &mut [u8]) -> Result<(), QrfsError>,
+{
+    type Item = Result<Inode, InodeSlotError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.slot >= self.count {
+            return None;
+        }
+        let slot = self.slot;
+        self.slot += 1;
+
+        // el limite evita reservar memoria por un largo de lista corrupto
+        let options = bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .with_limit(self.source.remaining());
+        let decoded = match self.format {
+            0 => options
+                .deserialize_from::<_, InodeV0>(&mut self.source)
+                .map(Inode::from),
+            1 | 2 => options
+                .deserialize_from::<_, InodeV1>(&mut self.source)
+                .map(Inode::from),
+            3 => options
+                .deserialize_from::<_, InodeV3>(&mut self.source)
+                .map(Inode::from),
+            _ => options.deserialize_from::<_, Inode>(&mut self.source),
+        };
+
+        match decoded {
+            Ok(inode) if inode.id != slot => Some(Err(InodeSlotError {
+                slot,
+                error: QrfsError::Other(format!("el slot contiene el inodo {}", inode.id)),
+            })),
+            Ok(inode) => Some(Ok(inode)),
+            Err(e) => {
+                self.done = true;
+                let error = self.source.error.take().unwrap_or(QrfsError::Bincode(e));
+                Some(Err(InodeSlotError { slot, error }))
+            }
+        }
+    }
+}
+
+impl<F> BlockSource<F> {
+    // bytes que quedan en la tabla desde la posicion actual
+    fn remaining(&self) -> u64 {
+        let pending: usize = self.blocks[self.next..]
+            .iter()
+            .map(|&(_, start)| self.buf.len() - start)
+            .sum();
+        (self.buf.len() - self.pos + pending) as u64
+    }
+}
+
+impl<F> Read for BlockSource<F>
+where
+    F: FnMut(BlockId, &mut [u8]) -> Result<(), QrfsError>,
+{
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.buf.len() {
+            let Some(&(block_id, start)) = self.blocks.get(self.next) else {
+                return Ok(0);
+            };
+            let checked = (self.read_block)(block_id, &mut self.buf).and_then(|_| {
+                if self.headers {
+                    let generation = check_inode_block(block_id, &self.buf, start)?;
+                    let first = *self.generation.get_or_insert(generation);
+                    check_inode_generation(block_id, generation, first)?;
+                }
+                Ok(())
+            });
+            if let Err(e) = checked {
+                let msg = e.to_string();
+                self.error = Some(e);
+                return Err(io::Error::other(msg));
+            }
+            self.next += 1;
+            self.pos = start;
+        }
+
+        let n = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::BLOCK_SIZE;
+    use crate::storage::InMemoryBlockStorage;
+
+    fn formatted() -> (InMemoryBlockStorage, Superblock) {
+        let storage = InMemoryBlockStorage::new(200, BLOCK_SIZE);
+        let sb = Superblock::new(200, 64);
+        format_storage(&storage, &sb).unwrap();
+        (storage, sb)
+    }
+
+    #[test]
+    fn format_marks_exactly_the_metadata_blocks_as_used() {
+        let (storage, sb) = formatted();
+        let bitmap = storage.read_block(sb.free_map_start).unwrap();
+        for blk in 0..sb.total_blocks {
+            let used = bitmap[(blk / 8) as usize] & (1 << (blk % 8)) != 0;
+            assert_eq!(used, blk < sb.data_block_start, "bloque {}", blk);
+        }
+    }
+
+    #[test]
+    fn superblock_spans_its_reserved_region() {
+        let (storage, sb) = formatted();
+        assert_eq!(encode_superblock(&sb).unwrap().len(), 2);
+        assert_eq!(read_superblock(&storage).unwrap().data_block_start, sb.data_block_start);
+
+        // un disco viejo: todo el superblock en el bloque 0
+        let mut old = sb.clone();
+        old.free_map_start = 1;
+        let blocks = encode_superblock(&old).unwrap();
+        assert_eq!(blocks.len(), 1);
+        storage.write_block(0, &blocks[0]).unwrap();
+        assert_eq!(read_superblock(&storage).unwrap().free_map_start, 1);
+
+        // mas bloques de los que cualquier lector acepta
+        old.free_map_start = SUPERBLOCK_MAX_BLOCKS + 1;
+        storage.write_block(0, &encode_superblock(&old).unwrap()[0]).unwrap();
+        assert!(read_superblock(&storage).is_err());
+    }
+
+    #[test]
+    fn superblock_larger_than_its_region_is_an_error() {
+        let mut sb = Superblock::new(200, 64);
+        sb.block_size = 16;
+        sb.free_map_start = 1;
+        assert!(encode_superblock(&sb).is_err());
+        sb.free_map_start = 8;
+        assert_eq!(encode_superblock(&sb).unwrap().len(), 8);
+    }
+
+    #[test]
+    fn reader_yields_every_slot_of_a_fresh_table() {
+        let (storage, sb) = formatted();
+        let inodes: Vec<Inode> = read_inode_table(&storage, &sb)
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(inodes.len(), sb.inode_count as usize);
+        assert!(inodes.iter().enumerate().all(|(i, inode)| inode.id == i as u32));
+        assert_eq!(inodes[0].mode, 0o755);
+        assert!(inodes[1..].iter().all(|inode| inode.mode == 0));
+    }
+
+    #[test]
+    fn reader_reports_the_broken_slot_and_stops() {
+        // sin encabezados (formato 1) el bloque roto llega al decodificador de slots
+        let storage = InMemoryBlockStorage::new(200, BLOCK_SIZE);
+        let mut sb = Superblock::new(200, 64);
+        sb.inode_format = 1;
+        format_storage(&storage, &sb).unwrap();
+        let slot_len = Inode::new(0, InodeKind::File).encode(1).unwrap().len();
+
+        // largo de la lista de bloques del slot 3 absurdo
+        let offset = 3 * slot_len + 4 + 4 + 8;
+        let block = sb.inode_table_start + (offset / BLOCK_SIZE) as u32;
+        let mut data = storage.read_block(block).unwrap();
+        let at = offset % BLOCK_SIZE;
+        let end = (at + 8).min(BLOCK_SIZE);
+        data[at..end].fill(0xff);
+        storage.write_block(block, &data).unwrap();
+
+        let results: Vec<_> = read_inode_table(&storage, &sb).collect();
+        assert_eq!(results.len(), 4);
+        assert!(results[..3].iter().all(|r| r.is_ok()));
+        assert_eq!(results[3].as_ref().unwrap_err().slot, 3);
+    }
+
+    #[test]
+    fn inode_table_headers_tell_torn_writes_from_garbage() {
+        let (storage, sb) = formatted();
+        assert!(sb.inode_table_blocks > 1);
+        assert_eq!(check_inode_table(&storage, &sb).unwrap(), Some(1));
+
+        // una escritura nueva que solo llego al primer bloque
+        let table = create_inode_table(sb.inode_count, sb.inode_format, sb.timestamp()).unwrap();
+        let blocks = encode_inode_table(&sb, &[], BLOCK_SIZE, &table, 2).unwrap();
+        let (first, data) = &blocks[0];
+        let good = storage.read_block(*first).unwrap();
+        storage.write_block(*first, data).unwrap();
+        let err = check_inode_table(&storage, &sb).unwrap_err();
+        assert!(err.to_string().contains("cortada"), "{}", err);
+        let last = read_inode_table(&storage, &sb).last().unwrap().unwrap_err();
+        assert!(matches!(last.error, QrfsError::Corrupt(_)), "{}", last);
+
+        // un byte cambiado en un bloque que todavia no tiene inodos
+        storage.write_block(*first, &good).unwrap();
+        let (tail, _) = blocks[blocks.len() - 1];
+        let mut data = storage.read_block(tail).unwrap();
+        data[BLOCK_SIZE - 1] ^= 1;
+        storage.write_block(tail, &data).unwrap();
+        let err = check_inode_table(&storage, &sb).unwrap_err();
+        assert!(err.to_string().contains("crc"), "{}", err);
+    }
+
+    #[test]
+    fn directory_blocks_reject_stale_and_foreign_headers() {
+        let entries: Vec<DirectoryEntry> = (0..8)
+            .map(|i| DirectoryEntry {
+                name: format!("archivo_{}", i),
+                inode_id: i + 1,
+                kind: InodeKind::File,
+            })
+            .collect();
+        let decode = |inode: &Inode, raw: &[u8]| decode_directory(inode, raw, BLOCK_SIZE, 1);
+
+        let (gen1, size) = encode_directory(5, 1, &entries, BLOCK_SIZE, 1).unwrap();
+        let (gen2, _) = encode_directory(5, 2, &entries, BLOCK_SIZE, 1).unwrap();
+        assert!(gen1.len() > BLOCK_SIZE && gen1.len() % BLOCK_SIZE == 0);
+
+        let mut inode = Inode::new(5, InodeKind::Directory);
+        inode.size = size;
+        inode.blocks = (100..100 + (gen1.len() / BLOCK_SIZE) as u32).collect();
+        let (decoded, generation) = decode(&inode, &gen1).unwrap();
+        assert_eq!(generation, 1);
+        assert_eq!(decoded.len(), 8);
+        assert_eq!(decoded[7].name, "archivo_7");
+
+        // el segundo bloque quedo de la escritura anterior
+        let mut stale = gen2.clone();
+        stale[BLOCK_SIZE..2 * BLOCK_SIZE].copy_from_slice(&gen1[BLOCK_SIZE..2 * BLOCK_SIZE]);
+        let err = decode(&inode, &stale).unwrap_err();
+        assert!(err.to_string().contains("generacion"), "{}", err);
+
+        // el mismo bloque leido desde otro directorio
+        let mut other = inode.clone();
+        other.id = 6;
+        let err = decode(&other, &gen1).unwrap_err();
+        assert!(err.to_string().contains("inodo 5"), "{}", err);
+
+        // una entrada cambiada sin tocar el encabezado
+        let mut flipped = gen1.clone();
+        flipped[DIR_HEADER + 12] ^= 1;
+        let err = decode(&inode, &flipped).unwrap_err();
+        assert!(err.to_string().contains("crc"), "{}", err);
+
+        // los discos viejos guardan bincode plano
+        let (plain, size) = encode_directory(5, 0, &entries, BLOCK_SIZE, 0).unwrap();
+        inode.size = size;
+        let mut padded = plain.clone();
+        padded.resize(plain.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE, 0);
+        assert_eq!(decode_directory(&inode, &padded, BLOCK_SIZE, 0).unwrap().0.len(), 8);
+        assert!(decode(&inode, &padded).is_err());
+    }
+}
@@ -0,0 +1,666 @@
+// verificacion de un disco por pasos: la corren fsck.qrfs, el POST /fsck del
+// servidor y, con lo que ya tiene en memoria, la revision rapida al montar
+// (check_layout y check_links). cada paso anota lo que encuentra en el
+// reporte y el siguiente sigue si puede; el que llama decide si corta en el
+// primer problema (fsck.qrfs) o los muestra todos (el servidor)
+//
+//   let report = fsck::check(&storage, &FsckOptions { strict: true, ..Default::default() });
+//   if let Some(check) = report.first_problem() { ... }
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use serde::Serialize;
+
+use crate::crypt;
+use crate::disk::{
+    BlockId, DirectoryEntry, Inode, InodeKind, Superblock, VersionAccess, INODE_FLAG_TAIL, QRFS_MAGIC,
+};
+use crate::errors::QrfsError;
+use crate::fs_format::{
+    check_inode_table, encode_directory, inode_ext_chain, read_directory, read_inode_table, read_label,
+    read_superblock,
+};
+use crate::storage::BlockStorage;
+use crate::tail::parse_map;
+
+// nombres de los pasos, en el orden en que corren
+pub const CHECK_SUPERBLOCK: &str = "superblock";
+pub const CHECK_LAYOUT: &str = "layout";
+pub const CHECK_BITMAP: &str = "bitmap";
+pub const CHECK_INODES: &str = "inodes";
+pub const CHECK_LINKS: &str = "links";
+pub const CHECK_DIRECTORIES: &str = "directories";
+pub const CHECK_TREE: &str = "tree";
+pub const CHECK_DATA: &str = "data";
+
+#[derive(Debug, Clone, Default)]
+pub struct FsckOptions {
+    // lee cada bloque de datos comparando crc, id y largo del qr
+    pub strict: bool,
+    // reescribe los '.' y '..' mal apuntados y renombra los nombres repetidos
+    pub repair: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FsckCheck {
+    pub name: String,
+    pub ok: bool,
+    // los problemas; vacio si ok
+    pub details: Vec<String>,
+    // lo que vale la pena mostrar sin ser un problema ("6 colas en 1 bloques compartidos")
+    pub notes: Vec<String>,
+    // lo que arreglo --repair
+    pub repaired: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FsckReport {
+    pub clean: bool,
+    pub checks: Vec<FsckCheck>,
+    // bloques que usan los inodos, en orden (para ver cuales faltan escanear)
+    #[serde(skip)]
+    pub blocks_in_use: Vec<BlockId>,
+}
+
+impl FsckReport {
+    pub fn add(&mut self, name: &str, details: Vec<String>) -> &mut FsckCheck {
+        self.checks.push(FsckCheck {
+            name: name.to_string(),
+            ok: details.is_empty(),
+            details,
+            notes: Vec::new(),
+            repaired: Vec::new(),
+        });
+        self.clean = self.checks.iter().all(|c| c.ok);
+        self.checks.last_mut().unwrap()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&FsckCheck> {
+        self.checks.iter().find(|c| c.name == name)
+    }
+
+    pub fn first_problem(&self) -> Option<&FsckCheck> {
+        self.checks.iter().find(|c| !c.ok)
+    }
+}
+
+// corre todos los pasos sobre el disco. un paso que deja a los siguientes
+// sin nada confiable (superblock, layout, bitmap o tabla de inodos rotos)
+// corta ahi; los demas siguen aunque encuentren problemas
+pub fn check<S: BlockStorage + ?Sized>(storage: &S, options: &FsckOptions) -> FsckReport {
+    let mut report = FsckReport { clean: true, ..Default::default() };
+
+    // superblock: firma, version y rotulo
+    let sb = match read_superblock(storage) {
+        Ok(sb) => sb,
+        Err(e) => {
+            report.add(CHECK_SUPERBLOCK, vec![format!("superblock ilegible: {}", e)]);
+            return report;
+        }
+    };
+    let mut details = Vec::new();
+    let mut notes = vec![format!("Magic: {:X}", sb.magic)];
+    if sb.magic != QRFS_MAGIC {
+        report.add(CHECK_SUPERBLOCK, vec!["firma invalida (numero magico incorrecto)".to_string()]);
+        return report;
+    }
+    match sb.version_access() {
+        Ok(VersionAccess::ReadWrite) => {}
+        // misma politica que el montaje: una version mas nueva se revisa sin tocarla
+        Ok(VersionAccess::ReadOnly) => notes.push(format!("versión {}, más nueva: solo lectura", sb.version_string())),
+        Err(e) => {
+            report.add(CHECK_SUPERBLOCK, vec![e.to_string()]);
+            return report;
+        }
+    }
+    match read_label(storage, &sb) {
+        Ok(Some(label)) => notes.push(format!("rótulo '{}', uuid {}", label.label, label.uuid_string())),
+        Ok(None) => {}
+        Err(e) => details.push(e.to_string()),
+    }
+    report.add(CHECK_SUPERBLOCK, details).notes = notes;
+
+    let layout = check_layout(&sb, storage.block_size(), storage.total_blocks());
+    if !report.add(CHECK_LAYOUT, layout).ok {
+        return report;
+    }
+
+    let bitmap = match read_bitmap(storage, &sb) {
+        Ok(bitmap) => bitmap,
+        Err(e) => {
+            report.add(CHECK_BITMAP, vec![e.to_string()]);
+            return report;
+        }
+    };
+    report.add(CHECK_BITMAP, Vec::new());
+
+    // tabla de inodos: los encabezados separan una escritura cortada de un
+    // bloque corrupto
+    let mut details = Vec::new();
+    let generation = check_inode_table(storage, &sb).unwrap_or_else(|e| {
+        details.push(e.to_string());
+        None
+    });
+    let mut inodes = Vec::new();
+    for slot in read_inode_table(storage, &sb) {
+        match slot {
+            Ok(inode) if inode.id == sb.root_inode || inode.mode != 0 => inodes.push(inode),
+            Ok(_) => {}
+            Err(e) => details.push(e.to_string()),
+        }
+    }
+    let ext_blocks = inode_ext_chain(storage, &sb).unwrap_or_else(|e| {
+        details.push(e.to_string());
+        Vec::new()
+    });
+    let inodes_ok = details.is_empty();
+    let check = report.add(CHECK_INODES, details);
+    check.notes.push(format!("{} inodos activos", inodes.len()));
+    if let Some(generation) = generation {
+        check.notes.push(format!("generación {}", generation));
+    }
+    if !inodes_ok {
+        return report;
+    }
+    let mut in_use: Vec<BlockId> = inodes.iter().flat_map(|inode| inode.blocks.iter().copied()).collect();
+    in_use.sort();
+    in_use.dedup();
+    report.blocks_in_use = in_use;
+
+    // bitmap contra inodos y colas empaquetadas
+    let (mut details, orphans) = check_links(&sb, &bitmap, &inodes, &ext_blocks);
+    let (tails, shared) = check_tails(storage, &sb, &inodes).unwrap_or_else(|e| {
+        details.push(e.to_string());
+        (0, 0)
+    });
+    let check = report.add(CHECK_LINKS, details);
+    if orphans > 0 {
+        check.notes.push(format!("{} bloques huérfanos, se liberan al montar", orphans));
+    }
+    if tails > 0 {
+        check.notes.push(format!("{} colas en {} bloques compartidos", tails, shared));
+    }
+
+    // encabezados de los directorios, entradas a inodos libres y nombres repetidos
+    let (dirs, mut details, repeated) = check_directories(storage, &sb, &inodes);
+    let mut repaired = Vec::new();
+    if !repeated.is_empty() {
+        if options.repair {
+            let mut dir_ids: Vec<u32> = repeated.iter().map(|&(dir, _)| dir).collect();
+            dir_ids.dedup();
+            for dir in dir_ids {
+                let inode = inodes.iter().find(|inode| inode.id == dir).unwrap();
+                match check_repairable(&sb).and_then(|_| repair_duplicates(storage, &sb, inode, &inodes)) {
+                    Ok(changes) => repaired.extend(changes),
+                    Err(e) => details.push(e.to_string()),
+                }
+            }
+        } else {
+            let names: Vec<String> = repeated.iter().map(|(dir, name)| format!("'{}' en {}", name, dir)).collect();
+            details.push(format!(
+                "nombres repetidos: {} (--repair deja el más nuevo)",
+                names.join(", ")
+            ));
+        }
+    }
+    let check = report.add(CHECK_DIRECTORIES, details);
+    check.notes.push(format!("{} directorios", dirs));
+    check.repaired = repaired;
+
+    // el arbol desde la raiz: '.' y '..', ciclos y directorios sueltos
+    let mut repaired = Vec::new();
+    let details = match check_tree(storage, &sb, &inodes) {
+        Ok(wrong) if wrong.is_empty() => Vec::new(),
+        Ok(wrong) if options.repair => {
+            let mut details = Vec::new();
+            for (dir, parent) in wrong {
+                let inode = inodes.iter().find(|inode| inode.id == dir).unwrap();
+                match check_repairable(&sb).and_then(|_| repair_dot_entries(storage, &sb, inode, parent)) {
+                    Ok(()) => repaired.push(format!("directorio {}: '.' y '..' reescritos ('..' -> {})", dir, parent)),
+                    Err(e) => details.push(e.to_string()),
+                }
+            }
+            details
+        }
+        Ok(wrong) => {
+            let dirs: Vec<u32> = wrong.iter().map(|&(dir, _)| dir).collect();
+            vec![format!(
+                "'.' o '..' mal apuntados en los directorios {:?} (--repair los corrige)",
+                dirs
+            )]
+        }
+        Err(e) => vec![e.to_string()],
+    };
+    report.add(CHECK_TREE, details).repaired = repaired;
+
+    // cada bloque de datos se lee comparando crc, id y largo del qr
+    if options.strict {
+        let (blocks, details) = check_data_blocks(storage, &inodes);
+        report.add(CHECK_DATA, details).notes.push(format!("{} bloques", blocks));
+    }
+    report
+}
+
+// geometria del superblock contra el almacenamiento y orden de las zonas
+pub fn check_layout(sb: &Superblock, block_size: usize, total_blocks: u32) -> Vec<String> {
+    let mut problems = Vec::new();
+    if sb.block_size as usize != block_size {
+        problems.push(format!(
+            "el superblock usa bloques de {} bytes y el almacenamiento de {}",
+            sb.block_size, block_size
+        ));
+    }
+    if sb.total_blocks > total_blocks {
+        problems.push(format!(
+            "el superblock declara {} bloques pero el almacenamiento tiene {}",
+            sb.total_blocks, total_blocks
+        ));
+    }
+    if !(sb.free_map_start < sb.inode_table_start
+        && sb.inode_table_start < sb.data_block_start
+        && sb.data_block_start < sb.total_blocks)
+    {
+        problems.push("layout del superblock fuera de orden (inicio de datos fuera de rango)".to_string());
+    }
+    problems
+}
+
+// el bitmap entero, recortado a un bit por bloque del disco
+pub fn read_bitmap<S: BlockStorage + ?Sized>(storage: &S, sb: &Superblock) -> Result<Vec<u8>, QrfsError> {
+    let block_size = storage.block_size();
+    let mut bitmap = vec![0u8; sb.free_map_blocks as usize * block_size];
+    for (i, chunk) in bitmap.chunks_mut(block_size).enumerate() {
+        storage.read_block_into(sb.free_map_start + i as u32, chunk)?;
+    }
+    bitmap.truncate((sb.total_blocks as usize).div_ceil(8));
+    Ok(bitmap)
+}
+
+// bitmap contra lo que usan los inodos, la extension de la tabla y el rotulo:
+// reservados o en uso marcados como libres, bloques fuera del disco y
+// bloques de dos duenos (las colas empaquetadas si comparten el suyo).
+// devuelve los problemas y los bloques huerfanos (marcados sin dueño)
+pub fn check_links<'a>(
+    sb: &Superblock,
+    bitmap: &[u8],
+    inodes: impl IntoIterator<Item = &'a Inode>,
+    ext_blocks: &[BlockId],
+) -> (Vec<String>, u32) {
+    let is_used = |blk: BlockId| {
+        let byte = (blk / 8) as usize;
+        byte < bitmap.len() && bitmap[byte] & (1 << (blk % 8)) != 0
+    };
+    let mut problems = Vec::new();
+    if bitmap.len() < (sb.total_blocks as usize).div_ceil(8) {
+        problems.push("bitmap mas chico que el disco".to_string());
+    }
+    for blk in 0..sb.data_block_start {
+        if !is_used(blk) {
+            problems.push(format!("bloque reservado {} marcado como libre", blk));
+        }
+    }
+
+    let mut inodes: Vec<&Inode> = inodes.into_iter().collect();
+    inodes.sort_by_key(|inode| inode.id);
+    let mut owners: HashMap<BlockId, u32> = HashMap::new();
+    let mut tail_blocks: HashSet<BlockId> = HashSet::new();
+    for inode in inodes {
+        let per_block = crypt::payload_len(sb.block_size as usize, !inode.key.is_empty());
+        if inode.size > inode.blocks.len() as u64 * per_block as u64 {
+            problems.push(format!(
+                "inodo {} dice {} bytes pero tiene {} bloques",
+                inode.id,
+                inode.size,
+                inode.blocks.len()
+            ));
+        }
+        for (index, &blk) in inode.blocks.iter().enumerate() {
+            if blk < sb.data_block_start || blk >= sb.total_blocks {
+                problems.push(format!("inodo {} apunta a bloque invalido {}", inode.id, blk));
+                continue;
+            }
+            if !is_used(blk) {
+                problems.push(format!("bloque {} del inodo {} marcado como libre", blk, inode.id));
+            }
+            let tail = inode.flags & INODE_FLAG_TAIL != 0 && index + 1 == inode.blocks.len();
+            if tail && !tail_blocks.insert(blk) {
+                continue;
+            }
+            if let Some(other) = owners.insert(blk, inode.id) {
+                problems.push(format!("bloque {} compartido por los inodos {} y {}", blk, other, inode.id));
+            }
+        }
+    }
+
+    let mut claimed: HashSet<BlockId> = owners.keys().copied().collect();
+    let label = (sb.label_block != 0).then_some((sb.label_block, "el rotulo"));
+    for (blk, what) in ext_blocks.iter().map(|&blk| (blk, "la tabla de inodos")).chain(label) {
+        if !is_used(blk) {
+            problems.push(format!("bloque {} de {} marcado como libre", blk, what));
+        }
+        if let Some(other) = owners.get(&blk) {
+            problems.push(format!("bloque {} compartido por {} y el inodo {}", blk, what, other));
+        }
+        claimed.insert(blk);
+    }
+
+    // asignado en el bitmap pero sin dueño: espacio perdido, no corrupcion
+    let orphans = (sb.data_block_start..sb.total_blocks)
+        .filter(|&blk| is_used(blk) && !claimed.contains(&blk))
+        .count() as u32;
+    (problems, orphans)
+}
+
+// cada inodo con la cola empaquetada tiene que encontrarla en el mapa de su
+// ultimo bloque, con el largo que le falta al archivo despues de los bloques
+// propios; devuelve las colas y los bloques compartidos
+fn check_tails<S: BlockStorage + ?Sized>(
+    storage: &S,
+    sb: &Superblock,
+    inodes: &[Inode],
+) -> Result<(u32, u32), QrfsError> {
+    let block_size = sb.block_size as u64;
+    let mut tails = 0;
+    let mut shared = HashSet::new();
+    for inode in inodes {
+        if inode.flags & INODE_FLAG_TAIL == 0 {
+            continue;
+        }
+        let Some(&blk) = inode.blocks.last() else {
+            return Err(QrfsError::Corrupt(format!(
+                "el inodo {} tiene la cola empaquetada pero no tiene bloques",
+                inode.id
+            )));
+        };
+        let data = storage.read_block(blk)?;
+        let entry = parse_map(&data)
+            .map_err(|e| QrfsError::Corrupt(format!("bloque {}: {}", blk, e)))?
+            .into_iter()
+            .find(|entry| entry.inode == inode.id)
+            .ok_or_else(|| {
+                QrfsError::Corrupt(format!("el bloque {} no tiene la cola del inodo {}", blk, inode.id))
+            })?;
+        let expected = inode.size.saturating_sub((inode.blocks.len() as u64 - 1) * block_size);
+        if entry.len as u64 != expected {
+            return Err(QrfsError::Corrupt(format!(
+                "la cola del inodo {} mide {} bytes y al archivo le faltan {}",
+                inode.id, entry.len, expected
+            )));
+        }
+        tails += 1;
+        shared.insert(blk);
+    }
+    Ok((tails, shared.len() as u32))
+}
+
+// devuelve cuantos directorios hay, los problemas y los nombres repetidos
+// de cada uno (un rename cortado o la mezcla de dos escaneos); al montar
+// queda uno solo de cada nombre sin que se sepa cual
+fn check_directories<S: BlockStorage + ?Sized>(
+    storage: &S,
+    sb: &Superblock,
+    inodes: &[Inode],
+) -> (u32, Vec<String>, Vec<(u32, String)>) {
+    let active: HashSet<u32> = inodes.iter().map(|inode| inode.id).collect();
+    let mut dirs = 0;
+    let mut problems = Vec::new();
+    let mut repeated = Vec::new();
+
+    for inode in inodes {
+        if !matches!(inode.kind, InodeKind::Directory) {
+            continue;
+        }
+        dirs += 1;
+        let entries = match read_directory(storage, sb, inode) {
+            Ok((entries, _)) => entries,
+            Err(e) => {
+                problems.push(e.to_string());
+                continue;
+            }
+        };
+        let mut seen = HashSet::new();
+        for entry in entries {
+            if entry.name == "." || entry.name == ".." {
+                continue;
+            }
+            if !active.contains(&entry.inode_id) {
+                problems.push(format!(
+                    "'{}' del directorio {} apunta al inodo libre {}",
+                    entry.name, inode.id, entry.inode_id
+                ));
+            }
+            if !seen.insert(entry.name.clone()) && !repeated.contains(&(inode.id, entry.name.clone())) {
+                repeated.push((inode.id, entry.name));
+            }
+        }
+    }
+    (dirs, problems, repeated)
+}
+
+// un disco de una version mas nueva no se toca
+fn check_repairable(sb: &Superblock) -> Result<(), QrfsError> {
+    if sb.version_access()? != VersionAccess::ReadWrite {
+        return Err(QrfsError::ReadOnly(format!(
+            "el disco es de la version {}, no se repara",
+            sb.version_string()
+        )));
+    }
+    Ok(())
+}
+
+// de cada nombre repetido queda el del inodo modificado mas tarde (a igual
+// hora, el de id mas alto); otra entrada al mismo inodo se borra y la de
+// otro inodo se renombra a "<nombre>.dup<inodo>" para no perder el archivo.
+// devuelve lo que hizo, para mostrarlo
+fn repair_duplicates<S: BlockStorage + ?Sized>(
+    storage: &S,
+    sb: &Superblock,
+    dir: &Inode,
+    inodes: &[Inode],
+) -> Result<Vec<String>, QrfsError> {
+    let (entries, generation) = read_directory(storage, sb, dir)?;
+    let modified = |id: u32| inodes.iter().find(|inode| inode.id == id).map_or(0, |inode| inode.modified_at);
+
+    let mut keep: HashMap<String, u32> = HashMap::new();
+    for entry in entries.iter().filter(|entry| entry.name != "." && entry.name != "..") {
+        let newest = keep.entry(entry.name.clone()).or_insert(entry.inode_id);
+        if (modified(entry.inode_id), entry.inode_id) > (modified(*newest), *newest) {
+            *newest = entry.inode_id;
+        }
+    }
+
+    let mut names: HashSet<String> = keep.keys().cloned().collect();
+    let mut kept: HashSet<String> = HashSet::new();
+    let mut fixed = Vec::new();
+    let mut changes = Vec::new();
+    for entry in entries {
+        if entry.name == "." || entry.name == ".." {
+            fixed.push(entry);
+            continue;
+        }
+        let newest = keep[&entry.name];
+        if entry.inode_id == newest && kept.insert(entry.name.clone()) {
+            fixed.push(entry);
+        } else if entry.inode_id == newest {
+            changes.push(format!("'{}' (inodo {}): entrada repetida borrada", entry.name, entry.inode_id));
+        } else {
+            let mut name = format!("{}.dup{}", entry.name, entry.inode_id);
+            while names.contains(&name) {
+                name.push('_');
+            }
+            names.insert(name.clone());
+            changes.push(format!("'{}' (inodo {}) -> '{}'", entry.name, entry.inode_id, name));
+            fixed.push(DirectoryEntry { name, ..entry });
+        }
+    }
+    rewrite_directory(storage, sb, dir, generation, &fixed)?;
+    Ok(changes)
+}
+
+// recorre los directorios desde la raiz: cada uno cuelga de un solo padre,
+// no se llega dos veces al mismo (ciclo) y ninguno queda afuera del arbol.
+// devuelve los que tienen '.' o '..' mal, con el padre al que deberia apuntar
+// '..' (la raiz es su propio padre)
+fn check_tree<S: BlockStorage + ?Sized>(
+    storage: &S,
+    sb: &Superblock,
+    inodes: &[Inode],
+) -> Result<Vec<(u32, u32)>, QrfsError> {
+    let dirs: HashMap<u32, &Inode> = inodes
+        .iter()
+        .filter(|inode| matches!(inode.kind, InodeKind::Directory))
+        .map(|inode| (inode.id, inode))
+        .collect();
+    let root = sb.root_inode;
+    if !dirs.contains_key(&root) {
+        return Err(QrfsError::Corrupt("la raíz no es un directorio".into()));
+    }
+
+    let mut parents = HashMap::from([(root, root)]);
+    let mut queue = VecDeque::from([root]);
+    let mut wrong = Vec::new();
+    while let Some(dir) = queue.pop_front() {
+        let parent = parents[&dir];
+        let (entries, _) = read_directory(storage, sb, dirs[&dir])?;
+        // recien formateado: la raiz todavia no se escribio
+        if dirs[&dir].size == 0 {
+            continue;
+        }
+        let dot = entries.iter().find(|entry| entry.name == ".").map(|entry| entry.inode_id);
+        let dotdot = entries.iter().find(|entry| entry.name == "..").map(|entry| entry.inode_id);
+        if dot != Some(dir) || dotdot != Some(parent) {
+            wrong.push((dir, parent));
+        }
+        for entry in entries.iter().filter(|entry| entry.name != "." && entry.name != "..") {
+            // el tipo se toma del inodo, no de la entrada
+            if !dirs.contains_key(&entry.inode_id) {
+                continue;
+            }
+            if let Some(&other) = parents.get(&entry.inode_id) {
+                return Err(QrfsError::Corrupt(format!(
+                    "ciclo en el árbol: '{}' del directorio {} lleva al directorio {}, que ya cuelga de {}",
+                    entry.name, dir, entry.inode_id, other
+                )));
+            }
+            parents.insert(entry.inode_id, dir);
+            queue.push_back(entry.inode_id);
+        }
+    }
+
+    let mut unreachable: Vec<u32> = dirs.keys().filter(|id| !parents.contains_key(id)).copied().collect();
+    if !unreachable.is_empty() {
+        unreachable.sort();
+        return Err(QrfsError::Corrupt(format!(
+            "directorios que no se alcanzan desde la raíz: {:?}",
+            unreachable
+        )));
+    }
+    Ok(wrong)
+}
+
+// reescribe el directorio con '.' y '..' al principio y bien apuntados
+// (tiene que seguir entrando: los ids son de largo fijo)
+fn repair_dot_entries<S: BlockStorage + ?Sized>(
+    storage: &S,
+    sb: &Superblock,
+    inode: &Inode,
+    parent: u32,
+) -> Result<(), QrfsError> {
+    let (entries, generation) = read_directory(storage, sb, inode)?;
+    let mut fixed = vec![
+        DirectoryEntry { name: ".".to_string(), inode_id: inode.id, kind: InodeKind::Directory },
+        DirectoryEntry { name: "..".to_string(), inode_id: parent, kind: InodeKind::Directory },
+    ];
+    fixed.extend(entries.into_iter().filter(|entry| entry.name != "." && entry.name != ".."));
+    rewrite_directory(storage, sb, inode, generation, &fixed)
+}
+
+// escribe las entradas en los bloques que ya tiene el directorio, con la
+// generacion siguiente; falla si no entran
+fn rewrite_directory<S: BlockStorage + ?Sized>(
+    storage: &S,
+    sb: &Superblock,
+    inode: &Inode,
+    generation: u32,
+    entries: &[DirectoryEntry],
+) -> Result<(), QrfsError> {
+    let block_size = storage.block_size();
+    let (data, size) = encode_directory(inode.id, generation.wrapping_add(1), entries, block_size, sb.dir_format)?;
+    // en el formato viejo el largo sale del inodo, que aca no se reescribe
+    if data.len() > inode.blocks.len() * block_size || (sb.dir_format == 0 && size != inode.size) {
+        return Err(QrfsError::Other(format!(
+            "el directorio {} no se puede reparar en su lugar",
+            inode.id
+        )));
+    }
+    for (chunk, &block) in data.chunks(block_size).zip(&inode.blocks) {
+        let mut buf = chunk.to_vec();
+        buf.resize(block_size, 0);
+        storage.write_block(block, &buf)?;
+    }
+    Ok(())
+}
+
+// lee cada bloque de datos con read_verified; devuelve los leidos y los problemas
+fn check_data_blocks<S: BlockStorage + ?Sized>(storage: &S, inodes: &[Inode]) -> (u32, Vec<String>) {
+    let mut blocks = 0;
+    let mut problems = Vec::new();
+    for inode in inodes {
+        for &blk in &inode.blocks {
+            match storage.read_verified(blk) {
+                Ok(_) => blocks += 1,
+                Err(e) => problems.push(format!("bloque {} del inodo {}: {}", blk, inode.id, e)),
+            }
+        }
+    }
+    (blocks, problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::api::QrfsApi;
+    use crate::disk::BLOCK_SIZE;
+    use crate::fs_format::format_storage;
+    use crate::storage::InMemoryBlockStorage;
+
+    fn disk() -> (Arc<InMemoryBlockStorage>, Superblock) {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        let sb = Superblock::new(200, 64);
+        format_storage(storage.as_ref(), &sb).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        api.write("/a.bin", &[3u8; 500]).unwrap();
+        api.write("/b.txt", b"hola").unwrap();
+        drop(api);
+        (storage, sb)
+    }
+
+    #[test]
+    fn a_healthy_disk_passes_every_step() {
+        let (storage, _) = disk();
+        let report = check(storage.as_ref(), &FsckOptions { strict: true, repair: false });
+        assert!(report.clean, "{:?}", report.first_problem());
+        let names: Vec<&str> = report.checks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [CHECK_SUPERBLOCK, CHECK_LAYOUT, CHECK_BITMAP, CHECK_INODES, CHECK_LINKS, CHECK_DIRECTORIES, CHECK_TREE, CHECK_DATA]
+        );
+        assert_eq!(report.get(CHECK_INODES).unwrap().notes[0], "3 inodos activos");
+        assert_eq!(report.blocks_in_use.len(), 500usize.div_ceil(BLOCK_SIZE) + 2);
+    }
+
+    #[test]
+    fn a_cleared_bitmap_is_reported_and_the_later_steps_still_run() {
+        let (storage, sb) = disk();
+        storage.write_block(sb.free_map_start, &[0u8; BLOCK_SIZE]).unwrap();
+
+        let report = check(storage.as_ref(), &FsckOptions::default());
+        assert!(!report.clean);
+        let links = report.first_problem().unwrap();
+        assert_eq!(links.name, CHECK_LINKS);
+        assert!(links.details.iter().any(|d| d == "bloque reservado 0 marcado como libre"), "{:?}", links.details);
+        assert!(report.get(CHECK_TREE).unwrap().ok);
+    }
+}
@@ -0,0 +1,154 @@
+// salud del disco: cuantas veces fallo cada bloque al leerse (qr que no se
+// decodifica, crc o digest que no coincide) y cuantas se reparo (desde el
+// espejo). el storage anota lo que ve al leer; se consulta con
+// QrfsApi::health, el archivo virtual .qrfs_meta/health del montaje y el
+// /health del servidor. solo memoria: arranca vacio en cada montaje
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::disk::BlockId;
+
+// carpeta virtual de la raiz y el archivo con el informe
+pub const META_DIR: &str = ".qrfs_meta";
+pub const HEALTH_FILE: &str = "health";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthEvent {
+    // la imagen no abre o el qr no se decodifica
+    DecodeFailure,
+    // se decodifica pero el crc, el digest o el sello no coinciden
+    ChecksumMismatch,
+    // se volvio a escribir bien (desde el espejo)
+    Repaired,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BlockHealth {
+    pub block: BlockId,
+    pub decode_failures: u64,
+    pub checksum_mismatches: u64,
+    pub repairs: u64,
+    // el ultimo evento fue una reparacion: el bloque volvio a estar bien
+    pub repaired: bool,
+    pub last_error: String,
+    // segundos unix del ultimo evento
+    pub last_event: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    // "ok" sin fallas, "reparado" si todos los bloques que fallaron se
+    // repararon despues, "con fallas" si alguno sigue mal
+    pub status: &'static str,
+    pub decode_failures: u64,
+    pub checksum_mismatches: u64,
+    pub repairs: u64,
+    // los que tuvieron algun evento, por numero de bloque
+    pub blocks: Vec<BlockHealth>,
+}
+
+#[derive(Default)]
+pub struct HealthMonitor {
+    blocks: Mutex<BTreeMap<BlockId, BlockHealth>>,
+}
+
+impl HealthMonitor {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    pub fn record(&self, block: BlockId, event: HealthEvent, detail: &str) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let mut blocks = self.blocks.lock().unwrap();
+        let entry = blocks.entry(block).or_insert_with(|| BlockHealth {
+            block,
+            ..BlockHealth::default()
+        });
+        match event {
+            HealthEvent::DecodeFailure => entry.decode_failures += 1,
+            HealthEvent::ChecksumMismatch => entry.checksum_mismatches += 1,
+            HealthEvent::Repaired => entry.repairs += 1,
+        }
+        entry.repaired = event == HealthEvent::Repaired;
+        if event != HealthEvent::Repaired {
+            entry.last_error = detail.to_string();
+        }
+        entry.last_event = now;
+    }
+
+    pub fn report(&self) -> HealthReport {
+        let blocks: Vec<BlockHealth> = self.blocks.lock().unwrap().values().cloned().collect();
+        let status = if blocks.is_empty() {
+            "ok"
+        } else if blocks.iter().all(|b| b.repaired) {
+            "reparado"
+        } else {
+            "con fallas"
+        };
+        HealthReport {
+            status,
+            decode_failures: blocks.iter().map(|b| b.decode_failures).sum(),
+            checksum_mismatches: blocks.iter().map(|b| b.checksum_mismatches).sum(),
+            repairs: blocks.iter().map(|b| b.repairs).sum(),
+            blocks,
+        }
+    }
+}
+
+impl HealthReport {
+    // contenido de .qrfs_meta/health
+    pub fn text(&self) -> String {
+        let mut text = format!(
+            "estado: {}\nqr que no se decodifican: {}\nchecksums que no coinciden: {}\nreparaciones: {}\n",
+            self.status, self.decode_failures, self.checksum_mismatches, self.repairs
+        );
+        for block in &self.blocks {
+            text.push_str(&format!(
+                "\nbloque {}: {} sin decodificar, {} checksum, {} reparado{}\n",
+                block.block,
+                block.decode_failures,
+                block.checksum_mismatches,
+                block.repairs,
+                if block.repaired { " (ya esta bien)" } else { "" }
+            ));
+            if !block.last_error.is_empty() {
+                text.push_str(&format!("  ultimo error: {}\n", block.last_error));
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failures_are_counted_per_block_until_repaired() {
+        let health = HealthMonitor::new();
+        assert_eq!(health.report().status, "ok");
+
+        health.record(7, HealthEvent::DecodeFailure, "qr ilegible");
+        health.record(7, HealthEvent::DecodeFailure, "qr ilegible");
+        health.record(3, HealthEvent::ChecksumMismatch, "crc");
+        let report = health.report();
+        assert_eq!(report.status, "con fallas");
+        assert_eq!((report.decode_failures, report.checksum_mismatches), (2, 1));
+        assert_eq!(report.blocks.iter().map(|b| b.block).collect::<Vec<_>>(), [3, 7]);
+
+        health.record(7, HealthEvent::Repaired, "");
+        health.record(3, HealthEvent::Repaired, "");
+        let report = health.report();
+        assert_eq!(report.status, "reparado");
+        assert_eq!(report.repairs, 2);
+        assert!(report.text().contains("bloque 7: 2 sin decodificar, 0 checksum, 1 reparado (ya esta bien)"));
+        assert!(report.text().contains("ultimo error: qr ilegible"));
+    }
+}
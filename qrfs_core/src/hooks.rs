@@ -0,0 +1,194 @@
+// ganchos del ciclo de vida de los bloques: quien usa la biblioteca registra
+// un BlockHooks en un HookedStorage y se entera de cada bloque escrito,
+// leido, liberado o que no se pudo leer (para espejar a otro lado, avisar o
+// subir a la nube sin tocar este crate). los ganchos corren en el hilo de la
+// operacion y antes de que vuelva: lo que tarde se hace en otro hilo
+//
+//   let storage = HookedStorage::new(config.qr_storage(disk));
+//   storage.register(Arc::new(MiSubida::new()));
+//   let api = QrfsApi::open(Arc::new(storage))?;
+
+use std::sync::{Arc, RwLock};
+
+use crate::disk::BlockId;
+use crate::errors::QrfsError;
+use crate::health::HealthMonitor;
+use crate::storage::BlockStorage;
+
+// todos tienen una version vacia: se implementa solo lo que interesa
+pub trait BlockHooks: Send + Sync {
+    // el bloque ya esta escrito en el backend
+    fn on_block_written(&self, _id: BlockId, _data: &[u8]) {}
+
+    fn on_block_read(&self, _id: BlockId, _data: &[u8]) {}
+
+    // el filesystem libero el bloque (ver BlockStorage::block_freed)
+    fn on_block_freed(&self, _id: BlockId) {}
+
+    // la lectura fallo: el qr no se decodifica, no pasa el crc o falta
+    fn on_decode_error(&self, _id: BlockId, _error: &QrfsError) {}
+}
+
+pub struct HookedStorage<B: BlockStorage> {
+    inner: B,
+    hooks: RwLock<Vec<Arc<dyn BlockHooks>>>,
+}
+
+impl<B: BlockStorage> HookedStorage<B> {
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            hooks: RwLock::new(Vec::new()),
+        }
+    }
+
+    // se puede registrar con el disco ya montado; corren en el orden en que
+    // se registraron
+    pub fn register(&self, hooks: Arc<dyn BlockHooks>) {
+        self.hooks.write().unwrap().push(hooks);
+    }
+
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    fn each(&self, call: impl Fn(&dyn BlockHooks)) {
+        for hooks in self.hooks.read().unwrap().iter() {
+            call(hooks.as_ref());
+        }
+    }
+
+    fn after_read(&self, id: BlockId, result: Result<Vec<u8>, QrfsError>) -> Result<Vec<u8>, QrfsError> {
+        match &result {
+            Ok(data) => self.each(|hooks| hooks.on_block_read(id, data)),
+            Err(e) => self.each(|hooks| hooks.on_decode_error(id, e)),
+        }
+        result
+    }
+}
+
+impl<B: BlockStorage> BlockStorage for HookedStorage<B> {
+    fn block_size(&self) -> usize {
+        self.inner.block_size()
+    }
+
+    fn total_blocks(&self) -> u32 {
+        self.inner.total_blocks()
+    }
+
+    fn read_block(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        self.after_read(id, self.inner.read_block(id))
+    }
+
+    fn write_block(&self, id: BlockId, data: &[u8]) -> Result<(), QrfsError> {
+        self.inner.write_block(id, data)?;
+        self.each(|hooks| hooks.on_block_written(id, data));
+        Ok(())
+    }
+
+    fn read_block_into(&self, id: BlockId, buf: &mut [u8]) -> Result<(), QrfsError> {
+        match self.inner.read_block_into(id, buf) {
+            Ok(()) => {
+                self.each(|hooks| hooks.on_block_read(id, buf));
+                Ok(())
+            }
+            Err(e) => {
+                self.each(|hooks| hooks.on_decode_error(id, &e));
+                Err(e)
+            }
+        }
+    }
+
+    fn read_verified(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        self.after_read(id, self.inner.read_verified(id))
+    }
+
+    // se reescribe el mismo dato con otra imagen: para los ganchos no cambia nada
+    fn reencode_block(&self, id: BlockId) -> Result<(), QrfsError> {
+        self.inner.reencode_block(id)
+    }
+
+    fn has_block(&self, id: BlockId) -> bool {
+        self.inner.has_block(id)
+    }
+
+    fn host_bytes(&self, id: BlockId) -> u64 {
+        self.inner.host_bytes(id)
+    }
+
+    fn health(&self) -> Option<Arc<HealthMonitor>> {
+        self.inner.health()
+    }
+
+    fn block_freed(&self, id: BlockId) {
+        self.inner.block_freed(id);
+        self.each(|hooks| hooks.on_block_freed(id));
+    }
+
+    fn barrier(&self) -> Result<(), QrfsError> {
+        self.inner.barrier()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::QrfsApi;
+    use crate::disk::{Superblock, BLOCK_SIZE};
+    use crate::fs_format::format_storage;
+    use crate::storage::InMemoryBlockStorage;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder {
+        written: Mutex<Vec<BlockId>>,
+        read: Mutex<Vec<BlockId>>,
+        freed: Mutex<Vec<BlockId>>,
+        errors: Mutex<Vec<BlockId>>,
+    }
+
+    impl BlockHooks for Recorder {
+        fn on_block_written(&self, id: BlockId, _data: &[u8]) {
+            self.written.lock().unwrap().push(id);
+        }
+        fn on_block_read(&self, id: BlockId, _data: &[u8]) {
+            self.read.lock().unwrap().push(id);
+        }
+        fn on_block_freed(&self, id: BlockId) {
+            self.freed.lock().unwrap().push(id);
+        }
+        fn on_decode_error(&self, id: BlockId, _error: &QrfsError) {
+            self.errors.lock().unwrap().push(id);
+        }
+    }
+
+    #[test]
+    fn the_hooks_see_a_file_written_read_and_freed() {
+        let storage = HookedStorage::new(InMemoryBlockStorage::new(64, BLOCK_SIZE));
+        format_storage(&storage, &Superblock::new(64, 16)).unwrap();
+        let recorder = Arc::new(Recorder::default());
+        storage.register(recorder.clone());
+        let storage = Arc::new(storage);
+        let api = QrfsApi::open(storage.clone()).unwrap();
+
+        api.write("/a.txt", &[9u8; 300]).unwrap();
+        api.flush().unwrap();
+        let entry = api.walk().unwrap().into_iter().find(|e| e.path == "/a.txt").unwrap();
+        let mut blocks = entry.blocks;
+        blocks.sort();
+        let written = recorder.written.lock().unwrap().clone();
+        assert!(blocks.iter().all(|b| written.contains(b)), "{:?} {:?}", blocks, written);
+
+        recorder.read.lock().unwrap().clear();
+        storage.read_block(blocks[0]).unwrap();
+        assert_eq!(*recorder.read.lock().unwrap(), vec![blocks[0]]);
+
+        api.remove("/a.txt").unwrap();
+        let mut freed = recorder.freed.lock().unwrap().clone();
+        freed.sort();
+        assert_eq!(freed, blocks);
+
+        assert!(storage.read_block(1000).is_err());
+        assert_eq!(*recorder.errors.lock().unwrap(), vec![1000]);
+    }
+}
@@ -0,0 +1,278 @@
+// disco publicado en la web, solo lectura
+// un archivo qr exportado se puede subir tal cual a un hosting estatico y
+// montarlo desde ahi: cada bloque se baja cuando hace falta y nada se escribe.
+//   https://host/archivo/      carpeta de qr (000000.png, 000001.png...)
+//   https://host/disco.img     contenedor; cada bloque con un pedido Range
+// el cliente es ureq (http y https con rustls, hosts ipv6 entre corchetes).
+// se abre siempre envuelto en RetryingStorage (ver retry.rs)
+
+use std::io::{self, ErrorKind, Read};
+use std::num::NonZeroUsize;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use lru::LruCache;
+use ureq::Agent;
+
+use crate::disk::{BlockId, BLOCK_SIZE};
+use crate::errors::QrfsError;
+use crate::fs_format::decode_superblock;
+use crate::qr::{decode_qr_text, DecodedBlock};
+use crate::retry::{RetryPolicy, RetryingStorage};
+use crate::storage::BlockStorage;
+
+// un servidor que no contesta en este tiempo se da por caido (y se reintenta)
+const SOCKET_TIMEOUT: Duration = Duration::from_secs(20);
+
+// bloques bajados que se guardan en memoria; los de mas se vuelven a pedir
+const CACHE_BLOCKS: usize = 4096;
+
+pub fn is_remote(disk: &str) -> bool {
+    disk.starts_with("http://") || disk.starts_with("https://")
+}
+
+// un solo agente para todo el proceso: reusa conexiones y sesiones tls
+fn agent() -> &'static Agent {
+    static AGENT: OnceLock<Agent> = OnceLock::new();
+    AGENT.get_or_init(|| {
+        Agent::config_builder()
+            // los 404 y 5xx se miran aca
+            .http_status_as_error(false)
+            .timeout_global(Some(SOCKET_TIMEOUT))
+            .user_agent("qrfs")
+            .build()
+            .new_agent()
+    })
+}
+
+// los cortes de red quedan como io para que RetryingStorage los reintente
+fn network_error(url: &str, e: ureq::Error) -> QrfsError {
+    match e {
+        ureq::Error::Io(e) => QrfsError::Io(e),
+        ureq::Error::Timeout(_) => QrfsError::Io(io::Error::new(ErrorKind::TimedOut, format!("{}: {}", url, e))),
+        ureq::Error::ConnectionFailed | ureq::Error::Protocol(_) => {
+            QrfsError::Io(io::Error::new(ErrorKind::ConnectionAborted, format!("{}: {}", url, e)))
+        }
+        e => QrfsError::Other(format!("{}: {}", url, e)),
+    }
+}
+
+// GET de la url; None si no existe (404). range: bytes [inicio, fin)
+fn get(url: &str, range: Option<(u64, u64)>) -> Result<Option<Vec<u8>>, QrfsError> {
+    let mut request = agent().get(url);
+    if let Some((start, end)) = range {
+        request = request.header("Range", &format!("bytes={}-{}", start, end - 1));
+    }
+    let response = request.call().map_err(|e| network_error(url, e))?;
+    read_response(url, response, range)
+}
+
+// body: tipo de contenido y cuerpo (POST)
+fn post(url: &str, content_type: &str, body: &[u8]) -> Result<Option<Vec<u8>>, QrfsError> {
+    let response = agent()
+        .post(url)
+        .content_type(content_type)
+        .send(body)
+        .map_err(|e| network_error(url, e))?;
+    read_response(url, response, None)
+}
+
+fn read_response(
+    url: &str,
+    mut response: ureq::http::Response<ureq::Body>,
+    range: Option<(u64, u64)>,
+) -> Result<Option<Vec<u8>>, QrfsError> {
+    let status = response.status().as_u16();
+    match status {
+        200 | 206 => {}
+        404 => return Ok(None),
+        // el servidor esta ocupado o caido: se puede volver a pedir
+        500..=599 => {
+            return Err(QrfsError::Io(io::Error::new(
+                ErrorKind::ConnectionAborted,
+                format!("{} respondio {}", url, status),
+            )))
+        }
+        _ => return Err(QrfsError::Other(format!("{} respondio {}", url, status))),
+    }
+
+    let mut reader = response.body_mut().with_config().limit(u64::MAX).reader();
+    let mut body = Vec::new();
+    match (status, range) {
+        // un servidor sin soporte de Range manda el archivo entero: se salta
+        // hasta el bloque sin guardar lo anterior
+        (200, Some((start, end))) => {
+            io::copy(&mut (&mut reader).take(start), &mut io::sink())?;
+            reader.take(end - start).read_to_end(&mut body)?;
+        }
+        _ => {
+            reader.read_to_end(&mut body)?;
+        }
+    }
+    Ok(Some(body))
+}
+
+// GET de una url completa (sync entre servidores); None si da 404
+pub fn fetch_url(url: &str) -> Result<Option<Vec<u8>>, QrfsError> {
+    get(url, None)
+}
+
+// POST de un cuerpo a una url completa (cliente de blockrpc.rs)
+pub fn post_url(url: &str, content_type: &str, body: &[u8]) -> Result<Option<Vec<u8>>, QrfsError> {
+    post(url, content_type, body)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Layout {
+    QrFolder { shard_size: u32 },
+    Container,
+}
+
+pub struct HttpStorage {
+    url: String,
+    layout: Layout,
+    block_size: usize,
+    total_blocks: u32,
+    // lo publicado no cambia: un bloque se baja una vez mientras siga entre
+    // los ultimos CACHE_BLOCKS leidos
+    cache: Mutex<LruCache<BlockId, Vec<u8>>>,
+}
+
+impl HttpStorage {
+    // lee el superblock publicado para saber la geometria
+    pub fn open(url: &str) -> Result<Self, QrfsError> {
+        if !is_remote(url) {
+            return Err(QrfsError::Other(format!("'{}' no es una url http:// ni https://", url)));
+        }
+        // sin path la url es la raiz del sitio
+        let authority_end = url.find("://").map_or(0, |i| i + 3);
+        let parsed = if url[authority_end..].contains('/') {
+            url.to_string()
+        } else {
+            format!("{}/", url)
+        };
+        let layout = if parsed.ends_with('/') {
+            Layout::QrFolder { shard_size: 0 }
+        } else {
+            Layout::Container
+        };
+        let mut storage = Self {
+            url: parsed,
+            layout,
+            block_size: BLOCK_SIZE,
+            total_blocks: 1,
+            cache: Mutex::new(LruCache::new(NonZeroUsize::new(CACHE_BLOCKS).unwrap())),
+        };
+        let sb = decode_superblock(&storage.fetch(0)?)
+            .ok()
+            .filter(|sb| sb.is_valid())
+            .ok_or_else(|| QrfsError::NotFormatted(format!("{} no tiene un superblock qrfs", url)))?;
+        if sb.content_addressed {
+            return Err(QrfsError::Unimplemented(format!(
+                "{}: los discos cas no se pueden leer por http",
+                url
+            )));
+        }
+        storage.block_size = sb.block_size as usize;
+        storage.total_blocks = sb.total_blocks;
+        if let Layout::QrFolder { shard_size } = &mut storage.layout {
+            *shard_size = sb.shard_size;
+        }
+        storage.cache.lock().unwrap().clear();
+        Ok(storage)
+    }
+
+    // el backend listo para montar: los cortes de red se reintentan
+    pub fn open_remote(url: &str) -> Result<RetryingStorage<Self>, QrfsError> {
+        let policy = RetryPolicy::default();
+        let storage = policy.run(|| Self::open(url))?;
+        Ok(RetryingStorage::new(storage, policy))
+    }
+
+    // mismo nombre que en la carpeta local (QrStorageManager::block_path)
+    fn image_path(&self, id: BlockId, shard_size: u32) -> String {
+        let filename = format!("{:06}.png", id);
+        if id == 0 || shard_size == 0 {
+            format!("{}{}", self.url, filename)
+        } else {
+            format!("{}{:03}/{}", self.url, id / shard_size, filename)
+        }
+    }
+
+    fn fetch(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        let mut data = match self.layout {
+            // un png que no esta es un bloque en cero, como en la carpeta local
+            Layout::QrFolder { shard_size } => match get(&self.image_path(id, shard_size), None)? {
+                None => vec![0; self.block_size],
+                Some(png) => {
+                    let img = image::load_from_memory(&png)
+                        .map_err(|e| QrfsError::Other(format!("bloque {}: imagen invalida: {}", id, e)))?;
+                    let decoded = DecodedBlock::from_text(&decode_qr_text(&img)?)?;
+                    if !decoded.checksum_ok {
+                        return Err(QrfsError::Other(format!("checksum invalido en el bloque {}", id)));
+                    }
+                    decoded.data
+                }
+            },
+            Layout::Container => {
+                let start = id as u64 * self.block_size as u64;
+                get(&self.url, Some((start, start + self.block_size as u64)))?
+                    .ok_or_else(|| QrfsError::NotFound(format!("{} no existe", self.url)))?
+            }
+        };
+        data.resize(self.block_size, 0);
+        Ok(data)
+    }
+}
+
+impl BlockStorage for HttpStorage {
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn total_blocks(&self) -> u32 {
+        self.total_blocks
+    }
+
+    fn read_block(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        if id >= self.total_blocks {
+            return Err(QrfsError::Other(format!(
+                "block id {id} fuera de rango 0..{}",
+                self.total_blocks - 1
+            )));
+        }
+        if let Some(data) = self.cache.lock().unwrap().get(&id) {
+            return Ok(data.clone());
+        }
+        let data = self.fetch(id)?;
+        self.cache.lock().unwrap().put(id, data.clone());
+        Ok(data)
+    }
+
+    fn write_block(&self, id: BlockId, _data: &[u8]) -> Result<(), QrfsError> {
+        Err(QrfsError::ReadOnly(format!(
+            "bloque {}: el disco en {} es de solo lectura",
+            id, self.url
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_images_hang_from_the_published_folder() {
+        let storage = HttpStorage {
+            url: "https://[::1]:8443/fotos/".into(),
+            layout: Layout::QrFolder { shard_size: 100 },
+            block_size: BLOCK_SIZE,
+            total_blocks: 1000,
+            cache: Mutex::new(LruCache::new(NonZeroUsize::new(2).unwrap())),
+        };
+        assert_eq!(storage.image_path(0, 100), "https://[::1]:8443/fotos/000000.png");
+        assert_eq!(storage.image_path(250, 100), "https://[::1]:8443/fotos/002/000250.png");
+
+        assert!(matches!(HttpStorage::open("ftp://example.com/"), Err(QrfsError::Other(_))));
+    }
+}
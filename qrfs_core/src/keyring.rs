@@ -0,0 +1,259 @@
+// de donde sale la frase de un disco cifrado: un archivo de clave (montajes
+// desatendidos), QRFS_PASSPHRASE, el llavero del escritorio o la terminal.
+// el llavero es opcional: en linux va por secret-tool (libsecret), en macos
+// por security; si no esta se sigue con la terminal
+
+use std::fs;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::errors::QrfsError;
+
+// variables de entorno con la frase (y la nueva, para rekey), para los scripts
+pub const PASSPHRASE_ENV: &str = "QRFS_PASSPHRASE";
+pub const NEW_PASSPHRASE_ENV: &str = "QRFS_NEW_PASSPHRASE";
+
+// servicio con el que se guardan las frases en el llavero
+const KEYRING_SERVICE: &str = "qrfs";
+
+// frase del disco: de QRFS_PASSPHRASE o, si no esta, una linea de la entrada
+// estandar (se pide en stderr para no ensuciar la salida)
+pub fn read_passphrase(prompt: &str) -> Result<String, QrfsError> {
+    read_passphrase_from(PASSPHRASE_ENV, prompt)
+}
+
+pub fn read_passphrase_from(var: &str, prompt: &str) -> Result<String, QrfsError> {
+    if let Ok(passphrase) = std::env::var(var) {
+        return Ok(passphrase);
+    }
+    eprint!("{}", prompt);
+    let line = {
+        let _echo = EchoOff::new();
+        let mut line = String::new();
+        std::io::stdin().lock().read_line(&mut line)?;
+        line
+    };
+    let passphrase = line.trim_end_matches(['\r', '\n']).to_string();
+    if passphrase.is_empty() {
+        return Err(QrfsError::Other("frase vacia".into()));
+    }
+    Ok(passphrase)
+}
+
+// apaga el eco de la terminal mientras se escribe la frase; si la entrada no
+// es una terminal (un pipe) no hace nada
+struct EchoOff {
+    #[cfg(unix)]
+    saved: Option<libc::termios>,
+}
+
+impl EchoOff {
+    #[cfg(unix)]
+    fn new() -> Self {
+        unsafe {
+            let mut term: libc::termios = std::mem::zeroed();
+            if libc::isatty(libc::STDIN_FILENO) == 0 || libc::tcgetattr(libc::STDIN_FILENO, &mut term) != 0 {
+                return Self { saved: None };
+            }
+            let saved = term;
+            term.c_lflag &= !libc::ECHO;
+            term.c_lflag |= libc::ECHONL;
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &term);
+            Self { saved: Some(saved) }
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn new() -> Self {
+        Self {}
+    }
+}
+
+#[cfg(unix)]
+impl Drop for EchoOff {
+    fn drop(&mut self) {
+        if let Some(saved) = &self.saved {
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, saved);
+            }
+        }
+    }
+}
+
+// primera linea de un archivo de clave. avisa si otros usuarios pueden leerlo
+pub fn read_key_file(path: &Path) -> Result<String, QrfsError> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| QrfsError::Other(format!("no se pudo leer '{}': {}", path.display(), e)))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if fs::metadata(path)?.permissions().mode() & 0o077 != 0 {
+            eprintln!(
+                "qrfs: aviso: '{}' se puede leer por otros usuarios (chmod 600)",
+                path.display()
+            );
+        }
+    }
+    let passphrase = text.lines().next().unwrap_or("").to_string();
+    if passphrase.is_empty() {
+        return Err(QrfsError::Other(format!("'{}' no tiene una frase", path.display())));
+    }
+    Ok(passphrase)
+}
+
+// el disco se identifica en el llavero por su ruta absoluta
+fn keyring_account(disk: &str) -> String {
+    fs::canonicalize(disk)
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| disk.to_string())
+}
+
+// frase guardada en el llavero para este disco (None si no hay o no hay llavero)
+pub fn keyring_lookup(disk: &str) -> Option<String> {
+    let account = keyring_account(disk);
+    let output = if cfg!(target_os = "macos") {
+        Command::new("security")
+            .args(["find-generic-password", "-s", KEYRING_SERVICE, "-a", &account, "-w"])
+            .stderr(Stdio::null())
+            .output()
+    } else {
+        Command::new("secret-tool")
+            .args(["lookup", "service", KEYRING_SERVICE, "disk", &account])
+            .stderr(Stdio::null())
+            .output()
+    };
+    let output = output.ok().filter(|output| output.status.success())?;
+    let passphrase = String::from_utf8(output.stdout).ok()?;
+    let passphrase = passphrase.trim_end_matches(['\r', '\n']);
+    (!passphrase.is_empty()).then(|| passphrase.to_string())
+}
+
+pub fn keyring_store(disk: &str, passphrase: &str) -> Result<(), QrfsError> {
+    let account = keyring_account(disk);
+    let label = format!("qrfs {}", account);
+    let status = if cfg!(target_os = "macos") {
+        Command::new("security")
+            .args(["add-generic-password", "-U", "-s", KEYRING_SERVICE, "-a", &account, "-l", &label])
+            .args(["-w", passphrase])
+            .stderr(Stdio::null())
+            .status()
+    } else {
+        let mut child = Command::new("secret-tool")
+            .args(["store", "--label", &label, "service", KEYRING_SERVICE, "disk", &account])
+            .stdin(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| QrfsError::Other(format!("no se pudo ejecutar secret-tool: {}", e)))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            std::io::Write::write_all(&mut stdin, passphrase.as_bytes())?;
+        }
+        child.wait()
+    };
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(QrfsError::Other(format!("el llavero respondio {}", status))),
+        Err(e) => Err(QrfsError::Other(format!("no se pudo usar el llavero: {}", e))),
+    }
+}
+
+// opciones de linea de comandos sobre la frase: --key-file <f> y --keyring
+#[derive(Debug, Clone, Default)]
+pub struct PassphraseSource {
+    pub key_file: Option<PathBuf>,
+    pub keyring: bool,
+}
+
+impl PassphraseSource {
+    pub fn from_args(args: &[String]) -> Self {
+        let mut source = Self::default();
+        let mut i = 0;
+        while i < args.len() {
+            if args[i] == "--key-file" && i + 1 < args.len() {
+                source.key_file = Some(PathBuf::from(&args[i + 1]));
+                i += 1;
+            } else if args[i] == "--keyring" {
+                source.keyring = true;
+            }
+            i += 1;
+        }
+        source
+    }
+
+    // consigue la frase y se la pasa a unlock. del archivo o de la variable se
+    // usa tal cual; la del llavero, si ya no sirve, se vuelve a pedir; la que
+    // se pide en la terminal se guarda en el llavero (con --keyring) una vez
+    // que abrio el disco. devuelve la frase que funciono
+    pub fn unlock(
+        &self,
+        disk: &str,
+        prompt: &str,
+        mut unlock: impl FnMut(&str) -> Result<(), QrfsError>,
+    ) -> Result<String, QrfsError> {
+        if let Some(path) = &self.key_file {
+            let passphrase = read_key_file(path)?;
+            unlock(&passphrase)?;
+            return Ok(passphrase);
+        }
+        if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+            unlock(&passphrase)?;
+            return Ok(passphrase);
+        }
+        if self.keyring {
+            if let Some(passphrase) = keyring_lookup(disk) {
+                match unlock(&passphrase) {
+                    Ok(()) => return Ok(passphrase),
+                    Err(e) => eprintln!("qrfs: la frase del llavero no sirve ({}), se pide de nuevo", e),
+                }
+            }
+        }
+
+        let passphrase = read_passphrase_from(PASSPHRASE_ENV, prompt)?;
+        unlock(&passphrase)?;
+        if self.keyring {
+            if let Err(e) = keyring_store(disk, &passphrase) {
+                eprintln!("qrfs: aviso: la frase no se guardo en el llavero: {}", e);
+            }
+        }
+        Ok(passphrase)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_file_is_read_before_anything_else() {
+        let dir = std::env::temp_dir().join(format!("qrfs_keyfile_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("clave");
+        fs::write(&path, "frase del archivo\nresto ignorado\n").unwrap();
+
+        let args: Vec<String> = ["mount", "--key-file", path.to_str().unwrap(), "--keyring"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        let source = PassphraseSource::from_args(&args);
+        assert!(source.keyring);
+
+        let mut tried = Vec::new();
+        let passphrase = source
+            .unlock("disco", "frase: ", |p| {
+                tried.push(p.to_string());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(passphrase, "frase del archivo");
+        assert_eq!(tried, vec!["frase del archivo"]);
+
+        // una frase que no abre el disco es un error, sin preguntar
+        assert!(source
+            .unlock("disco", "frase: ", |_| Err(QrfsError::NotPermitted("frase incorrecta".into())))
+            .is_err());
+
+        fs::write(&path, "\n").unwrap();
+        assert!(read_key_file(&path).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
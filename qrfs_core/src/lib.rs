@@ -4,10 +4,54 @@ pub mod fs;
 pub mod errors;
 pub mod fs_format;
 pub mod qr;
+pub mod codec;
+pub mod api;
+pub mod decode_cache;
+pub mod transaction;
+pub mod cas;
+pub mod seal;
+pub mod snapshot;
+pub mod mirror;
+pub mod volume;
+pub mod health;
+pub mod retry;
+pub mod http;
+pub mod sync;
+pub mod blockrpc;
+pub mod camera;
+pub mod missing;
+pub mod sha256;
+pub mod crypt;
+pub mod shamir;
+pub mod keyring;
+pub mod tail;
+#[cfg(feature = "stego")]
+pub mod stego;
+#[cfg(feature = "audio")]
+pub mod audio;
+pub mod sheet;
+pub mod reprint;
+pub mod audit;
+pub mod backend;
+pub mod config;
+pub mod estimate;
+pub mod compat;
+pub mod hooks;
+pub mod events;
+pub mod zip;
+pub mod bundle;
+pub mod ordering;
+pub mod fsck;
 
 pub use crate::disk::{BlockId, Superblock, Inode, DirectoryEntry, InodeKind};
-pub use crate::storage::{BlockStorage, QrStorageManager, InMemoryBlockStorage};
+pub use crate::storage::{BlockStorage, ContainerStorage, QrStorageManager, InMemoryBlockStorage};
 pub use crate::fs::QrfsFilesystem;
+pub use crate::transaction::MetadataTransaction;
 pub use crate::errors::QrfsError;
 pub use crate::fs_format::*;
-pub use crate::qr::validate_qr_block;
\ No newline at end of file
+pub use crate::qr::{
+    decode_qr_bytes, decode_qr_reader, encode_block, encode_block_svg, validate_qr_block, BlockStatus,
+    DecodedBlock, EnvelopeVersion, QrParams,
+};
+pub use crate::api::{FileInfo, FragmentationReport, FsUsage, QrfsApi};
+pub use crate::config::QrfsConfig;
\ No newline at end of file
@@ -0,0 +1,182 @@
+// disco espejado: cada bloque se escribe en dos discos (dos carpetas de qr,
+// o dos contenedores) y se lee del principal. si un qr del principal no se
+// decodifica, no pasa el crc o falta su imagen, el bloque sale del espejo y
+// se reescribe en el principal en la misma lectura; recien si el espejo
+// tampoco lo tiene la lectura falla (EIO en fuse)
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::disk::BlockId;
+use crate::errors::QrfsError;
+use crate::health::{HealthEvent, HealthMonitor};
+use crate::storage::BlockStorage;
+
+pub struct MirrorStorage<P: BlockStorage, M: BlockStorage> {
+    primary: P,
+    mirror: M,
+    // bloques del principal reparados desde el espejo
+    repaired: AtomicU64,
+    // el del principal, que ya anota las fallas; aca se suman las reparaciones
+    health: Arc<HealthMonitor>,
+}
+
+impl<P: BlockStorage, M: BlockStorage> MirrorStorage<P, M> {
+    pub fn new(primary: P, mirror: M) -> Self {
+        let health = primary.health().unwrap_or_default();
+        Self {
+            primary,
+            mirror,
+            repaired: AtomicU64::new(0),
+            health,
+        }
+    }
+
+    pub fn repaired(&self) -> u64 {
+        self.repaired.load(Ordering::Relaxed)
+    }
+
+    // el bloque del principal no sirve: se lee del espejo y se reescribe su
+    // imagen. si la reescritura falla el dato igual se devuelve
+    fn repair(&self, id: BlockId, why: &str) -> Result<Vec<u8>, QrfsError> {
+        let data = self.mirror.read_verified(id).map_err(|e| {
+            QrfsError::Other(format!(
+                "bloque {} ilegible en el disco ({}) y en el espejo ({})",
+                id, why, e
+            ))
+        })?;
+        match self.primary.write_block(id, &data) {
+            Ok(()) => {
+                self.repaired.fetch_add(1, Ordering::Relaxed);
+                self.health.record(id, HealthEvent::Repaired, "");
+                eprintln!("qrfs: bloque {} reparado desde el espejo ({})", id, why);
+            }
+            Err(e) => eprintln!("qrfs: warning: bloque {} leido del espejo, no se pudo reescribir: {e}", id),
+        }
+        Ok(data)
+    }
+}
+
+impl<P: BlockStorage, M: BlockStorage> BlockStorage for MirrorStorage<P, M> {
+    fn block_size(&self) -> usize {
+        self.primary.block_size()
+    }
+
+    fn total_blocks(&self) -> u32 {
+        self.primary.total_blocks()
+    }
+
+    // sin imagen en el principal pero con una en el espejo, el qr se perdio
+    // (sin imagen en ninguno es un bloque en cero)
+    fn read_block(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        if !self.primary.has_block(id) && self.mirror.has_block(id) {
+            return self.repair(id, "falta la imagen");
+        }
+        match self.primary.read_verified(id) {
+            Ok(data) => Ok(data),
+            Err(e) => self.repair(id, &e.to_string()),
+        }
+    }
+
+    fn write_block(&self, id: BlockId, data: &[u8]) -> Result<(), QrfsError> {
+        self.primary.write_block(id, data)?;
+        self.mirror.write_block(id, data)
+    }
+
+    fn read_verified(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        self.read_block(id)
+    }
+
+    fn reencode_block(&self, id: BlockId) -> Result<(), QrfsError> {
+        // un principal ilegible se repara leyendolo
+        self.read_block(id)?;
+        self.primary.reencode_block(id)?;
+        self.mirror.reencode_block(id)
+    }
+
+    fn has_block(&self, id: BlockId) -> bool {
+        self.primary.has_block(id) || self.mirror.has_block(id)
+    }
+
+    fn host_bytes(&self, id: BlockId) -> u64 {
+        self.primary.host_bytes(id)
+    }
+
+    fn health(&self) -> Option<Arc<HealthMonitor>> {
+        Some(self.health.clone())
+    }
+
+    fn block_freed(&self, id: BlockId) {
+        self.primary.block_freed(id);
+        self.mirror.block_freed(id);
+    }
+
+    fn barrier(&self) -> Result<(), QrfsError> {
+        self.primary.barrier()?;
+        self.mirror.barrier()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::BLOCK_SIZE;
+    use crate::storage::InMemoryBlockStorage;
+
+    // principal con bloques que no se pueden leer, como un qr dañado
+    struct Damaged {
+        inner: InMemoryBlockStorage,
+        bad: std::sync::Mutex<Vec<BlockId>>,
+    }
+
+    impl BlockStorage for Damaged {
+        fn block_size(&self) -> usize {
+            self.inner.block_size()
+        }
+        fn total_blocks(&self) -> u32 {
+            self.inner.total_blocks()
+        }
+        fn read_block(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+            if self.bad.lock().unwrap().contains(&id) {
+                return Err(QrfsError::Other(format!("el qr del bloque {} no se decodifica", id)));
+            }
+            self.inner.read_block(id)
+        }
+        fn write_block(&self, id: BlockId, data: &[u8]) -> Result<(), QrfsError> {
+            self.bad.lock().unwrap().retain(|&b| b != id);
+            self.inner.write_block(id, data)
+        }
+    }
+
+    #[test]
+    fn unreadable_block_is_read_from_the_mirror_and_rewritten() {
+        let primary = Damaged {
+            inner: InMemoryBlockStorage::new(16, BLOCK_SIZE),
+            bad: std::sync::Mutex::new(Vec::new()),
+        };
+        let storage = MirrorStorage::new(primary, InMemoryBlockStorage::new(16, BLOCK_SIZE));
+        storage.write_block(3, &[7; BLOCK_SIZE]).unwrap();
+
+        storage.primary.bad.lock().unwrap().push(3);
+        assert_eq!(storage.read_block(3).unwrap(), [7; BLOCK_SIZE]);
+        assert_eq!(storage.repaired(), 1);
+        assert_eq!(storage.health().unwrap().report().repairs, 1);
+        // ya reescrito: la proxima lectura sale del principal
+        assert!(storage.primary.bad.lock().unwrap().is_empty());
+        assert_eq!(storage.primary.read_block(3).unwrap(), [7; BLOCK_SIZE]);
+
+        // sin copia buena en ninguno la lectura falla
+        let mirror_bad = MirrorStorage::new(
+            Damaged {
+                inner: InMemoryBlockStorage::new(16, BLOCK_SIZE),
+                bad: std::sync::Mutex::new(vec![5]),
+            },
+            Damaged {
+                inner: InMemoryBlockStorage::new(16, BLOCK_SIZE),
+                bad: std::sync::Mutex::new(vec![5]),
+            },
+        );
+        assert!(mirror_bad.read_block(5).is_err());
+        assert_eq!(mirror_bad.repaired(), 0);
+    }
+}
@@ -0,0 +1,252 @@
+// montaje degradado de un disco incompleto (escaneo a medias): un bloque de
+// datos sin qr se lee con EIO en vez de ceros y el archivo virtual
+// .qrfs_missing dice que falta escanear de cada archivo. se recalcula en cada
+// lectura: los bloques que van llegando dejan de figurar
+
+use std::collections::HashSet;
+
+use crate::disk::{BlockId, Inode, InodeKind, INODE_FLAG_PINNED};
+use crate::fs_format::{inode_ext_chain, read_inode_table, read_superblock};
+use crate::storage::BlockStorage;
+
+// archivo virtual en la raiz con el informe
+pub const MISSING_FILE: &str = ".qrfs_missing";
+
+pub struct MissingFile {
+    pub inode: u32,
+    pub name: String,
+    pub size: u64,
+    pub total_blocks: usize,
+    // (indice dentro del archivo, bloque del disco) de los que faltan
+    pub blocks: Vec<(u32, BlockId)>,
+}
+
+impl MissingFile {
+    // tramos de bytes del archivo que no se pueden leer
+    pub fn byte_ranges(&self, block_size: u64) -> Vec<(u64, u64)> {
+        let mut ranges: Vec<(u64, u64)> = Vec::new();
+        for &(index, _) in &self.blocks {
+            let start = index as u64 * block_size;
+            let end = (start + block_size).min(self.size);
+            match ranges.last_mut() {
+                Some(last) if last.1 == start => last.1 = end,
+                _ => ranges.push((start, end)),
+            }
+        }
+        ranges
+    }
+}
+
+// contenido del archivo de informe
+pub fn report_text(files: &[MissingFile], block_size: u64) -> String {
+    let blocks: usize = files.iter().map(|f| f.blocks.len()).sum();
+    let mut text = format!("bloques sin escanear: {} en {} archivos\n", blocks, files.len());
+    if !files.is_empty() {
+        // sin cas un bloque en cero tampoco tiene png
+        text.push_str("(un bloque que era todo ceros tambien figura)\n");
+    }
+    for file in files {
+        let name = if file.name.is_empty() { "?" } else { &file.name };
+        let ranges: Vec<String> = file
+            .byte_ranges(block_size)
+            .iter()
+            .map(|(start, end)| format!("{}..{}", start, end))
+            .collect();
+        let ids: Vec<String> = file.blocks.iter().map(|(_, id)| id.to_string()).collect();
+        text.push_str(&format!(
+            "\n{} (inodo {}): faltan {} de {} bloques\n  bytes: {}\n  bloques del disco: {}\n",
+            name,
+            file.inode,
+            file.blocks.len(),
+            file.total_blocks,
+            ranges.join(", "),
+            ids.join(", ")
+        ));
+    }
+    text
+}
+
+// que tan importante es escanear un bloque, de mas a menos: con la metadata
+// un escaneo a medias ya se monta (degradado) y se sabe que falta; cada
+// bloque de datos despues solo completa un pedazo de un archivo
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BlockRole {
+    Superblock,
+    Bitmap,
+    InodeTable,
+    Directory,
+    FileData,
+}
+
+impl BlockRole {
+    pub fn name(self) -> &'static str {
+        match self {
+            BlockRole::Superblock => "superblock",
+            BlockRole::Bitmap => "bitmap",
+            BlockRole::InodeTable => "inodos",
+            BlockRole::Directory => "directorio",
+            BlockRole::FileData => "datos",
+        }
+    }
+}
+
+// los bloques en uso del disco, del mas importante al menos (dentro de los
+// datos, los de archivos fijos primero). se arma con lo que se pueda leer:
+// sin superblock solo se sabe que falta el bloque 0, y sin la tabla de inodos
+// no se conocen directorios ni datos
+pub fn scan_order<S: BlockStorage + ?Sized>(storage: &S) -> Vec<(BlockId, BlockRole)> {
+    let sb = match read_superblock(storage) {
+        Ok(sb) if sb.is_valid() => sb,
+        _ => return vec![(0, BlockRole::Superblock)],
+    };
+    let mut order: Vec<(BlockId, BlockRole)> = Vec::new();
+    order.extend((0..sb.region_blocks()).map(|b| (b, BlockRole::Superblock)));
+    order.extend((sb.free_map_start..sb.free_map_start + sb.free_map_blocks).map(|b| (b, BlockRole::Bitmap)));
+    // el rotulo completa al superblock
+    if sb.label_block != 0 {
+        order.push((sb.label_block, BlockRole::Superblock));
+    }
+    order.extend(
+        (sb.inode_table_start..sb.inode_table_start + sb.inode_table_blocks).map(|b| (b, BlockRole::InodeTable)),
+    );
+    order.extend(
+        inode_ext_chain(storage, &sb)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|b| (b, BlockRole::InodeTable)),
+    );
+
+    let mut inodes: Vec<Inode> = read_inode_table(storage, &sb).filter_map(|slot| slot.ok()).collect();
+    inodes.sort_by_key(|inode| {
+        (
+            inode.kind != InodeKind::Directory,
+            inode.flags & INODE_FLAG_PINNED == 0,
+            inode.id,
+        )
+    });
+    // un bloque compartido (colas empaquetadas) figura una sola vez
+    let mut seen: HashSet<BlockId> = order.iter().map(|&(b, _)| b).collect();
+    for inode in &inodes {
+        let role = if inode.kind == InodeKind::Directory {
+            BlockRole::Directory
+        } else {
+            BlockRole::FileData
+        };
+        for &block in &inode.blocks {
+            if seen.insert(block) {
+                order.push((block, role));
+            }
+        }
+    }
+    order
+}
+
+// los limit bloques mas importantes que todavia no estan escaneados
+pub fn scan_next<S: BlockStorage + ?Sized>(storage: &S, limit: usize) -> Vec<(BlockId, BlockRole)> {
+    scan_order(storage)
+        .into_iter()
+        .filter(|&(block, _)| !storage.has_block(block))
+        .take(limit)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::api::QrfsApi;
+    use crate::disk::{Superblock, BLOCK_SIZE};
+    use crate::errors::QrfsError;
+    use crate::fs_format::format_storage;
+    use crate::storage::InMemoryBlockStorage;
+
+    #[test]
+    fn consecutive_missing_blocks_join_into_one_range() {
+        let file = MissingFile {
+            inode: 4,
+            name: "foto.jpg".into(),
+            size: 2500,
+            total_blocks: 5,
+            blocks: vec![(0, 20), (1, 21), (4, 30)],
+        };
+        assert_eq!(file.byte_ranges(512), vec![(0, 1024), (2048, 2500)]);
+
+        let text = report_text(&[file], 512);
+        assert!(text.starts_with("bloques sin escanear: 3 en 1 archivos"));
+        assert!(text.contains("foto.jpg (inodo 4): faltan 3 de 5 bloques"));
+        assert!(text.contains("bloques del disco: 20, 21, 30"));
+        assert_eq!(report_text(&[], 512), "bloques sin escanear: 0 en 0 archivos\n");
+    }
+
+    // un escaneo a medias: los bloques de `missing` no llegaron
+    struct PartialScan {
+        inner: InMemoryBlockStorage,
+        missing: HashSet<BlockId>,
+    }
+
+    impl BlockStorage for PartialScan {
+        fn block_size(&self) -> usize {
+            self.inner.block_size()
+        }
+        fn total_blocks(&self) -> u32 {
+            self.inner.total_blocks()
+        }
+        fn read_block(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+            if self.missing.contains(&id) {
+                return Err(QrfsError::NotFound(format!("bloque {}", id)));
+            }
+            self.inner.read_block(id)
+        }
+        fn write_block(&self, id: BlockId, data: &[u8]) -> Result<(), QrfsError> {
+            self.inner.write_block(id, data)
+        }
+        fn has_block(&self, id: BlockId) -> bool {
+            !self.missing.contains(&id)
+        }
+    }
+
+    #[test]
+    fn metadata_and_pinned_files_are_scanned_first() {
+        let storage = Arc::new(InMemoryBlockStorage::new(200, BLOCK_SIZE));
+        let mut sb = Superblock::new(200, 64);
+        sb.pinned_blocks = 8;
+        format_storage(storage.as_ref(), &sb).unwrap();
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        api.write("/comun.bin", &[1; 300]).unwrap();
+        api.write("/indice.txt", &[2; 200]).unwrap();
+        api.set_pinned("/indice.txt", true).unwrap();
+        let walk = api.walk().unwrap();
+        let pinned = walk.into_iter().find(|e| e.path == "/indice.txt").unwrap().blocks;
+        drop(api);
+
+        let order = scan_order(storage.as_ref());
+        assert_eq!(order[0], (0, BlockRole::Superblock));
+        assert!(order.windows(2).all(|w| w[0].1 <= w[1].1));
+        let data: Vec<BlockId> = order
+            .iter()
+            .filter(|&&(_, role)| role == BlockRole::FileData)
+            .map(|&(b, _)| b)
+            .collect();
+        assert_eq!(data.len(), 5);
+        assert_eq!(data[..2], pinned[..]);
+        assert!(order.iter().any(|&(_, role)| role == BlockRole::Directory));
+
+        // sin superblock solo se sabe que falta el 0; con la metadata lo que
+        // sigue son los datos que faltan, en orden
+        let inner = InMemoryBlockStorage::new(200, BLOCK_SIZE);
+        for id in 0..200 {
+            inner.write_block(id, &storage.read_block(id).unwrap()).unwrap();
+        }
+        let mut partial = PartialScan {
+            inner,
+            missing: HashSet::from([0, data[0], data[3]]),
+        };
+        assert_eq!(scan_next(&partial, 4), [(0, BlockRole::Superblock)]);
+        partial.missing.remove(&0);
+        assert_eq!(
+            scan_next(&partial, 4),
+            [(data[0], BlockRole::FileData), (data[3], BlockRole::FileData)]
+        );
+    }
+}
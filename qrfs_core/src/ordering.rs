@@ -0,0 +1,224 @@
+// orden de escritura para backends que no lo respetan solos (object storage,
+// colas asincronas): las escrituras entre dos barreras (BlockStorage::barrier)
+// forman una tanda; dentro de una tanda van en cualquier orden y en paralelo,
+// y una tanda no empieza a bajar hasta que termino la anterior. el commit del
+// filesystem pone una barrera entre cada paso, asi el superblock nunca llega
+// antes que la tabla de inodos, los directorios o el bitmap que nombra
+//
+//   let storage = QueuedStorage::new(backend_lento).with_workers(8);
+//   let api = QrfsApi::open(Arc::new(storage))?;
+//
+// lo encolado se lee desde la cola; al soltar el storage se baja lo que falte
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::disk::BlockId;
+use crate::errors::QrfsError;
+use crate::health::HealthMonitor;
+use crate::storage::BlockStorage;
+
+// una tanda mas grande que esto se empieza a bajar sin esperar la barrera
+// (adentro de la tanda el orden no importa)
+const MAX_QUEUED: usize = 1024;
+
+pub struct QueuedStorage<B: BlockStorage> {
+    inner: B,
+    // la ultima escritura de cada bloque de la tanda actual
+    queue: Mutex<HashMap<BlockId, Vec<u8>>>,
+    workers: usize,
+}
+
+impl<B: BlockStorage> QueuedStorage<B> {
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            queue: Mutex::new(HashMap::new()),
+            workers: 4,
+        }
+    }
+
+    // cuantas escrituras de una tanda van a la vez
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    pub fn queued(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+
+    fn queued_block(&self, id: BlockId) -> Option<Vec<u8>> {
+        self.queue.lock().unwrap().get(&id).cloned()
+    }
+
+    // baja la tanda; lo que falla vuelve a la cola para la proxima
+    fn drain(&self, queue: &mut HashMap<BlockId, Vec<u8>>) -> Result<(), QrfsError> {
+        let batch: Vec<(BlockId, Vec<u8>)> = queue.drain().collect();
+        let chunk = batch.len().div_ceil(self.workers).max(1);
+        let failed: Vec<(BlockId, Vec<u8>, QrfsError)> = thread::scope(|scope| {
+            let handles: Vec<_> = batch
+                .chunks(chunk)
+                .map(|part| {
+                    scope.spawn(move || {
+                        part.iter()
+                            .filter_map(|(id, data)| {
+                                self.inner.write_block(*id, data).err().map(|e| (*id, data.clone(), e))
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles.into_iter().flat_map(|h| h.join().unwrap()).collect()
+        });
+        let mut first = None;
+        for (id, data, e) in failed {
+            queue.insert(id, data);
+            first.get_or_insert(e);
+        }
+        first.map_or(Ok(()), Err)
+    }
+}
+
+impl<B: BlockStorage> BlockStorage for QueuedStorage<B> {
+    fn block_size(&self) -> usize {
+        self.inner.block_size()
+    }
+
+    fn total_blocks(&self) -> u32 {
+        self.inner.total_blocks()
+    }
+
+    fn read_block(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        match self.queued_block(id) {
+            Some(data) => Ok(data),
+            None => self.inner.read_block(id),
+        }
+    }
+
+    fn write_block(&self, id: BlockId, data: &[u8]) -> Result<(), QrfsError> {
+        if id >= self.inner.total_blocks() {
+            return Err(QrfsError::Other(format!("bloque {} fuera del disco", id)));
+        }
+        let mut queue = self.queue.lock().unwrap();
+        queue.insert(id, data.to_vec());
+        if queue.len() >= MAX_QUEUED {
+            self.drain(&mut queue)?;
+        }
+        Ok(())
+    }
+
+    fn read_verified(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        match self.queued_block(id) {
+            Some(data) => Ok(data),
+            None => self.inner.read_verified(id),
+        }
+    }
+
+    // uno encolado se va a escribir de nuevo igual
+    fn reencode_block(&self, id: BlockId) -> Result<(), QrfsError> {
+        if self.queue.lock().unwrap().contains_key(&id) {
+            return Ok(());
+        }
+        self.inner.reencode_block(id)
+    }
+
+    fn has_block(&self, id: BlockId) -> bool {
+        self.queue.lock().unwrap().contains_key(&id) || self.inner.has_block(id)
+    }
+
+    fn host_bytes(&self, id: BlockId) -> u64 {
+        self.inner.host_bytes(id)
+    }
+
+    fn health(&self) -> Option<Arc<HealthMonitor>> {
+        self.inner.health()
+    }
+
+    fn block_freed(&self, id: BlockId) {
+        self.inner.block_freed(id)
+    }
+
+    // la cola queda tomada mientras baja: lo que se escriba ahora es de la
+    // tanda siguiente
+    fn barrier(&self) -> Result<(), QrfsError> {
+        let mut queue = self.queue.lock().unwrap();
+        if !queue.is_empty() {
+            self.drain(&mut queue)?;
+        }
+        self.inner.barrier()
+    }
+}
+
+impl<B: BlockStorage> Drop for QueuedStorage<B> {
+    fn drop(&mut self) {
+        if let Err(e) = self.barrier() {
+            eprintln!("qrfs: warning: quedaron escrituras sin bajar: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::QrfsApi;
+    use crate::disk::{Superblock, BLOCK_SIZE};
+    use crate::fs_format::format_storage;
+    use crate::storage::InMemoryBlockStorage;
+
+    // anota las escrituras y las barreras que le llegan al backend
+    struct Recorder {
+        inner: InMemoryBlockStorage,
+        log: Mutex<Vec<Option<BlockId>>>,
+    }
+
+    impl BlockStorage for Recorder {
+        fn block_size(&self) -> usize {
+            self.inner.block_size()
+        }
+        fn total_blocks(&self) -> u32 {
+            self.inner.total_blocks()
+        }
+        fn read_block(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+            self.inner.read_block(id)
+        }
+        fn write_block(&self, id: BlockId, data: &[u8]) -> Result<(), QrfsError> {
+            self.log.lock().unwrap().push(Some(id));
+            self.inner.write_block(id, data)
+        }
+        fn barrier(&self) -> Result<(), QrfsError> {
+            self.log.lock().unwrap().push(None);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn the_superblock_reaches_the_backend_in_a_batch_after_the_metadata() {
+        let recorder = Recorder { inner: InMemoryBlockStorage::new(64, BLOCK_SIZE), log: Mutex::new(Vec::new()) };
+        format_storage(&recorder, &Superblock::new(64, 16)).unwrap();
+        let sb = crate::fs_format::read_superblock(&recorder).unwrap();
+        let storage = Arc::new(QueuedStorage::new(recorder).with_workers(3));
+        let api = QrfsApi::open(storage.clone()).unwrap();
+        storage.inner().log.lock().unwrap().clear();
+
+        api.write("/a.txt", &[7u8; 900]).unwrap();
+        api.flush().unwrap();
+        // leido desde la cola o desde el backend da lo mismo
+        assert_eq!(api.read("/a.txt").unwrap(), vec![7u8; 900]);
+        assert_eq!(storage.queued(), 0);
+
+        let log = storage.inner().log.lock().unwrap().clone();
+        let batches: Vec<&[Option<BlockId>]> = log.split(|entry| entry.is_none()).collect();
+        let superblock = batches.iter().position(|b| b.contains(&Some(0))).expect("no se escribio el superblock");
+        // la tanda del superblock no trae nada que el apunte
+        let region = sb.region_blocks();
+        assert!(batches[superblock].iter().flatten().all(|&id| id < region), "{:?}", log);
+        let earlier: Vec<BlockId> = batches[..superblock].iter().flat_map(|b| b.iter().flatten().copied()).collect();
+        assert!(earlier.contains(&sb.inode_table_start) && earlier.contains(&sb.free_map_start), "{:?}", log);
+    }
+}
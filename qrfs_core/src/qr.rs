@@ -1,17 +1,217 @@
 // modulo para operaciones de validacion de codigos qr
 
+use std::io::Read;
+
 use base64::{engine::general_purpose, Engine as _};
-use image::DynamicImage;
-use rqrr;
+use image::{DynamicImage, GrayImage, Luma};
+use qrcode::render::svg;
+use qrcode::{EcLevel, QrCode};
+use serde::{Deserialize, Serialize};
 
+use crate::codec::{BinaryCodec, BlockCodec, BINARY_PREFIX};
 use crate::errors::QrfsError;
 
-// valida que un bloque qr pueda ser decodificado correctamente
-// retorna el tamaño de los datos decodificados o error
-pub fn validate_qr_block(img: &DynamicImage) -> Result<usize, QrfsError> {
-    let img_gray = img.to_luma8();
+// sobre json que viaja dentro de cada qr
+// formato: {"block_id":X,"gen":N,"crc":C,"len":L,"data":"base64..."}
+// gen, crc y len son opcionales para poder leer qrs generados antes de agregarlos
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockEnvelope {
+    pub block_id: u32,
+    #[serde(default)]
+    pub gen: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crc: Option<u32>,
+    // largo real del payload (un bloque corto no se completa con ceros)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub len: Option<u32>,
+    pub data: String,
+}
+
+impl BlockEnvelope {
+    pub fn new(block_id: u32, gen: u64, payload: &[u8]) -> Self {
+        Self {
+            block_id,
+            gen,
+            crc: Some(block_checksum(payload)),
+            len: Some(payload.len() as u32),
+            data: general_purpose::STANDARD.encode(payload),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        // serializar un struct sin mapas no puede fallar
+        serde_json::to_string(self).unwrap()
+    }
+
+    // None si el texto no es un sobre json (ej: base64 directo del formato viejo)
+    pub fn from_json(content: &str) -> Option<Self> {
+        serde_json::from_str(content).ok()
+    }
 
-    let mut decoder = rqrr::PreparedImage::prepare(img_gray);
+    // decodifica el campo data
+    pub fn payload(&self) -> Result<Vec<u8>, QrfsError> {
+        general_purpose::STANDARD
+            .decode(&self.data)
+            .map_err(|e| QrfsError::Other(format!("error decodificando base64: {}", e)))
+    }
+
+    // true si no hay crc (formato viejo) o si coincide con el payload
+    pub fn checksum_matches(&self, payload: &[u8]) -> bool {
+        self.crc.is_none_or(|crc| crc == block_checksum(payload))
+    }
+}
+
+// checksum de los datos de un bloque (crc32)
+pub fn block_checksum(data: &[u8]) -> u32 {
+    crc32fast::hash(data)
+}
+
+// como se dibujan los qr: lado maximo en pixeles (los modulos son enteros,
+// puede quedar algo mas chico) y nivel de correccion de errores
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QrParams {
+    pub size: u32,
+    pub ec_level: EcLevel,
+}
+
+impl Default for QrParams {
+    fn default() -> Self {
+        Self {
+            size: 200,
+            ec_level: EcLevel::M,
+        }
+    }
+}
+
+fn qr_code(text: &str, params: QrParams) -> Result<QrCode, QrfsError> {
+    QrCode::with_error_correction_level(text, params.ec_level)
+        .map_err(|e| QrfsError::Other(format!("error generando qr: {}", e)))
+}
+
+// dibuja un texto ya armado (el sobre json de un bloque)
+pub fn encode_text(text: &str, params: QrParams) -> Result<GrayImage, QrfsError> {
+    Ok(qr_code(text, params)?
+        .render::<Luma<u8>>()
+        .min_dimensions(params.size, params.size)
+        .max_dimensions(params.size, params.size)
+        .build())
+}
+
+// imagen del bloque tal como la guarda el storage
+pub fn encode_block(id: u32, gen: u64, data: &[u8], params: QrParams) -> Result<GrayImage, QrfsError> {
+    encode_text(&BlockEnvelope::new(id, gen, data).to_json(), params)
+}
+
+// el mismo qr en svg (para imprimir a cualquier tamaño)
+pub fn encode_block_svg(id: u32, gen: u64, data: &[u8], params: QrParams) -> Result<String, QrfsError> {
+    Ok(qr_code(&BlockEnvelope::new(id, gen, data).to_json(), params)?
+        .render::<svg::Color>()
+        .min_dimensions(params.size, params.size)
+        .max_dimensions(params.size, params.size)
+        .build())
+}
+
+// formato del texto guardado en el qr
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvelopeVersion {
+    // base64 directo, sin metadata
+    Raw,
+    // {"block_id","data"}
+    Json,
+    // {"block_id","gen","crc","len","data"} (len desde que se agrego)
+    JsonChecked,
+    // binario compacto (ver codec)
+    Binary,
+}
+
+// que se pudo comprobar del contenido de un qr
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockStatus {
+    // el crc del sobre coincide con los datos
+    Verified,
+    // el qr no trae crc: no hay contra que comparar
+    Unchecked,
+    ChecksumMismatch,
+}
+
+// bloque leido de un qr, en cualquiera de los formatos
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedBlock {
+    // None en el formato viejo (base64 directo, sin metadata)
+    pub block_id: Option<u32>,
+    // 0 si el qr no trae generacion
+    pub generation: u64,
+    pub data: Vec<u8>,
+    // largo que declara el qr (None en los formatos que no lo guardan)
+    pub declared_len: Option<usize>,
+    pub envelope: EnvelopeVersion,
+    // un qr sin crc cuenta como valido
+    pub checksum_ok: bool,
+}
+
+impl DecodedBlock {
+    // interpreta el texto de un qr: sobre json, binario o base64 directo
+    pub fn from_text(content: &str) -> Result<Self, QrfsError> {
+        if content.starts_with(BINARY_PREFIX) {
+            return BinaryCodec.decode(content);
+        }
+        let Ok(serde_json::Value::Object(fields)) = serde_json::from_str(content) else {
+            return Ok(Self {
+                block_id: None,
+                generation: 0,
+                data: decode_base64(content)?,
+                declared_len: None,
+                envelope: EnvelopeVersion::Raw,
+                checksum_ok: true,
+            });
+        };
+
+        let data = fields
+            .get("data")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| QrfsError::Other("json invalido: falta campo 'data'".into()))?;
+        let data = decode_base64(data)?;
+        let crc = fields.get("crc").and_then(|v| v.as_u64());
+        Ok(Self {
+            block_id: fields.get("block_id").and_then(|v| v.as_u64()).map(|id| id as u32),
+            generation: fields.get("gen").and_then(|v| v.as_u64()).unwrap_or(0),
+            declared_len: fields.get("len").and_then(|v| v.as_u64()).map(|len| len as usize),
+            checksum_ok: crc.is_none_or(|crc| crc == block_checksum(&data) as u64),
+            envelope: if crc.is_some() { EnvelopeVersion::JsonChecked } else { EnvelopeVersion::Json },
+            data,
+        })
+    }
+
+    pub fn status(&self) -> BlockStatus {
+        match (self.envelope, self.checksum_ok) {
+            (_, false) => BlockStatus::ChecksumMismatch,
+            (EnvelopeVersion::JsonChecked | EnvelopeVersion::Binary, true) => BlockStatus::Verified,
+            _ => BlockStatus::Unchecked,
+        }
+    }
+
+    // el payload trae los bytes que el qr dice tener
+    pub fn length_matches(&self) -> bool {
+        self.declared_len.is_none_or(|len| len == self.data.len())
+    }
+
+    // true si el qr dice ser del bloque id (los qr sin metadata no lo dicen)
+    pub fn is_block(&self, id: u32) -> bool {
+        self.block_id.is_none_or(|block_id| block_id == id)
+    }
+}
+
+// base64 estandar, o la variante url sin relleno que mandan algunos lectores
+fn decode_base64(text: &str) -> Result<Vec<u8>, QrfsError> {
+    general_purpose::STANDARD
+        .decode(text)
+        .or_else(|_| general_purpose::URL_SAFE_NO_PAD.decode(text))
+        .map_err(|e| QrfsError::Other(format!("error decodificando base64: {}", e)))
+}
+
+// texto crudo del primer qr que se encuentre en la imagen
+pub fn decode_qr_text(img: &DynamicImage) -> Result<String, QrfsError> {
+    let mut decoder = rqrr::PreparedImage::prepare(img.to_luma8());
     let grids = decoder.detect_grids();
 
     if grids.is_empty() {
@@ -21,34 +221,47 @@ pub fn validate_qr_block(img: &DynamicImage) -> Result<usize, QrfsError> {
     let (_meta, content_string) = grids[0]
         .decode()
         .map_err(|e| QrfsError::Other(format!("error decodificando qr: {}", e)))?;
+    Ok(content_string)
+}
 
-    let data_size = if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&content_string) {
-        if let Some(data_str) = parsed.get("data").and_then(|v| v.as_str()) {
-            let decoded = general_purpose::STANDARD
-                .decode(data_str)
-                .map_err(|e| QrfsError::Other(format!("error decodificando base64: {}", e)))?;
-            decoded.len()
-        } else {
-            let decoded = general_purpose::STANDARD
-                .decode(&content_string)
-                .map_err(|e| QrfsError::Other(format!("error decodificando base64: {}", e)))?;
-            decoded.len()
-        }
-    } else {
-        let decoded = general_purpose::STANDARD
-            .decode(&content_string)
-            .map_err(|e| QrfsError::Other(format!("error decodificando base64: {}", e)))?;
-        decoded.len()
-    };
+// todos los qr que se lean en la imagen (un cuadro de camara puede tener
+// varios); los que se detectan pero no se decodifican se ignoran
+pub fn decode_all_qr_text(img: &DynamicImage) -> Vec<String> {
+    let mut decoder = rqrr::PreparedImage::prepare(img.to_luma8());
+    decoder
+        .detect_grids()
+        .iter()
+        .filter_map(|grid| grid.decode().ok().map(|(_meta, text)| text))
+        .collect()
+}
+
+pub fn decode_qr_image(img: &DynamicImage) -> Result<DecodedBlock, QrfsError> {
+    DecodedBlock::from_text(&decode_qr_text(img)?)
+}
+
+// imagen en cualquier formato que entienda image (png, jpeg, ...)
+pub fn decode_qr_bytes(bytes: &[u8]) -> Result<DecodedBlock, QrfsError> {
+    let img = image::load_from_memory(bytes)
+        .map_err(|e| QrfsError::Other(format!("error abriendo imagen: {}", e)))?;
+    decode_qr_image(&img)
+}
 
-    Ok(data_size)
+pub fn decode_qr_reader(mut reader: impl Read) -> Result<DecodedBlock, QrfsError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    decode_qr_bytes(&bytes)
+}
+
+// valida que un bloque qr pueda ser decodificado correctamente
+// retorna solo el tamaño de los datos; se mantiene por compatibilidad,
+// decode_qr_image devuelve tambien de que bloque es y si el crc coincide
+pub fn validate_qr_block(img: &DynamicImage) -> Result<usize, QrfsError> {
+    Ok(decode_qr_image(img)?.data.len())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use image::Luma;
-    use qrcode::QrCode;
 
     #[test]
     fn validate_qr_block_works() {
@@ -85,6 +298,87 @@ mod tests {
         assert_eq!(size, test_data.len());
     }
 
+    #[test]
+    fn envelope_roundtrip_keeps_generation_and_checksum() {
+        let payload = b"bloque con generacion";
+        let env = BlockEnvelope::new(7, 3, payload);
+
+        let parsed = BlockEnvelope::from_json(&env.to_json()).unwrap();
+        assert_eq!(parsed.block_id, 7);
+        assert_eq!(parsed.gen, 3);
+        assert_eq!(parsed.crc, Some(block_checksum(payload)));
+
+        let data = parsed.payload().unwrap();
+        assert_eq!(data, payload);
+        assert!(parsed.checksum_matches(&data));
+        assert!(!parsed.checksum_matches(b"otro contenido"));
+    }
+
+    #[test]
+    fn envelope_without_gen_or_crc_still_parses() {
+        let b64_string = general_purpose::STANDARD.encode(b"viejo");
+        let old = format!(r#"{{"block_id":4,"data":"{}"}}"#, b64_string);
+
+        let parsed = BlockEnvelope::from_json(&old).unwrap();
+        assert_eq!(parsed.gen, 0);
+        assert!(parsed.crc.is_none());
+        assert!(parsed.checksum_matches(b"viejo"));
+    }
+
+    #[test]
+    fn decode_png_bytes_reports_block_and_checksum() {
+        let payload = b"bloque en png";
+        let code = QrCode::new(BlockEnvelope::new(9, 4, payload).to_json()).unwrap();
+        let image = DynamicImage::ImageLuma8(code.render::<Luma<u8>>().min_dimensions(300, 300).build());
+        let mut png = Vec::new();
+        image
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+
+        let block = decode_qr_reader(png.as_slice()).unwrap();
+        assert_eq!(block.block_id, Some(9));
+        assert_eq!(block.generation, 4);
+        assert_eq!(block.data, payload);
+        assert_eq!(block.envelope, EnvelopeVersion::JsonChecked);
+        assert_eq!(block.status(), BlockStatus::Verified);
+        assert_eq!(block.declared_len, Some(payload.len()));
+        assert!(block.length_matches());
+        assert!(decode_qr_bytes(b"no es una imagen").is_err());
+    }
+
+    #[test]
+    fn decoded_text_flags_bad_checksum_and_old_format() {
+        let mut env = BlockEnvelope::new(2, 1, b"datos");
+        env.crc = Some(0);
+        let block = DecodedBlock::from_text(&env.to_json()).unwrap();
+        assert!(!block.checksum_ok);
+        assert_eq!(block.status(), BlockStatus::ChecksumMismatch);
+        assert!(block.is_block(2) && !block.is_block(3));
+
+        let old = DecodedBlock::from_text(&general_purpose::URL_SAFE_NO_PAD.encode(b"viejo?")).unwrap();
+        assert_eq!(old.block_id, None);
+        assert_eq!(old.data, b"viejo?");
+        assert_eq!(old.envelope, EnvelopeVersion::Raw);
+        assert_eq!(old.status(), BlockStatus::Unchecked);
+        assert!(old.is_block(5));
+    }
+
+    #[test]
+    fn encoded_block_decodes_back_and_svg_matches() {
+        let params = QrParams { size: 160, ec_level: EcLevel::L };
+        let image = encode_block(12, 2, b"mismo qr", params).unwrap();
+        assert!(image.width() <= 160);
+
+        let block = decode_qr_image(&DynamicImage::ImageLuma8(image)).unwrap();
+        assert_eq!(block.block_id, Some(12));
+        assert_eq!(block.generation, 2);
+        assert_eq!(block.data, b"mismo qr");
+
+        let svg = encode_block_svg(12, 2, b"mismo qr", params).unwrap();
+        assert!(svg.contains("<svg"));
+        assert!(encode_block_svg(12, 2, &[0u8; 4000], params).is_err());
+    }
+
     #[test]
     fn decode_invalid_image_fails() {
         let empty_img = DynamicImage::new_luma8(200, 200);
@@ -0,0 +1,329 @@
+// reimpresion diferencial: que qr hay que volver a imprimir despues de editar
+// en cada impresion se guarda un manifiesto con la generacion y el crc de cada
+// bloque impreso; la proxima vez se compara con el disco y solo sale lo que
+// cambio. lo que cuenta es el contenido: un bloque reescrito con los mismos
+// datos (la metadata se reescribe entera aunque cambie un solo inodo) o un qr
+// regenerado (reencode) no hace falta reimprimirlo, la hoja vieja da lo mismo
+//
+// las impresiones quedan anotadas en la carpeta del disco (PRINTS_FILE): que
+// bloques salieron en cada una, cuando y con que parametros. el papel es la
+// suma de todas (una reimpresion pisa las hojas de esos bloques), y fsck y
+// reprint_plan.qrfs dicen a que impresion corresponde el disco o cuanto se
+// aparto de ella
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::disk::BlockId;
+use crate::errors::QrfsError;
+use crate::qr::block_checksum;
+use crate::storage::{BlockStorage, QrStorageManager};
+
+// registro de impresiones, al lado de los png
+pub const PRINTS_FILE: &str = ".qrfs_prints.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrintedBlock {
+    pub gen: u64,
+    pub crc: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrintManifest {
+    pub blocks: BTreeMap<BlockId, PrintedBlock>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReprintReason {
+    // no estaba en la impresion anterior
+    New,
+    // se reescribio con otros datos
+    Rewritten { printed: u64, current: u64 },
+    // otros datos con la misma generacion (ej: copiado de otro disco)
+    Changed,
+}
+
+impl ReprintReason {
+    pub fn describe(self) -> String {
+        match self {
+            ReprintReason::New => "nuevo".to_string(),
+            ReprintReason::Rewritten { printed, current } => format!("gen {} -> {}", printed, current),
+            ReprintReason::Changed => "datos distintos".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ReprintPlan {
+    pub reprint: Vec<(BlockId, ReprintReason)>,
+    // impresos que ya no tienen qr en el disco: esas hojas se pueden tirar
+    pub obsolete: Vec<BlockId>,
+}
+
+impl PrintManifest {
+    // el estado actual de los qr de la carpeta; los ilegibles van aparte
+    pub fn capture(storage: &QrStorageManager) -> (Self, Vec<BlockId>) {
+        let mut manifest = Self::default();
+        let mut unreadable = Vec::new();
+        for id in 0..storage.total_blocks() {
+            if !storage.block_path(id).exists() {
+                continue;
+            }
+            let printed = storage
+                .read_envelope(id)
+                .ok()
+                .flatten()
+                .and_then(|envelope| Some((envelope.gen, envelope.payload().ok()?)));
+            match printed {
+                Some((gen, payload)) => {
+                    manifest.blocks.insert(id, PrintedBlock { gen, crc: block_checksum(&payload) });
+                }
+                None => unreadable.push(id),
+            }
+        }
+        (manifest, unreadable)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, QrfsError> {
+        let path = path.as_ref();
+        serde_json::from_str(&fs::read_to_string(path)?)
+            .map_err(|e| QrfsError::Other(format!("manifiesto de impresion invalido '{}': {}", path.display(), e)))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), QrfsError> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| QrfsError::Other(format!("no se pudo serializar el manifiesto: {}", e)))?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+
+    // lo que hay que imprimir para que las hojas de self queden como current
+    pub fn plan(&self, current: &PrintManifest) -> ReprintPlan {
+        let mut plan = ReprintPlan::default();
+        for (&id, now) in &current.blocks {
+            let reason = match self.blocks.get(&id) {
+                None => Some(ReprintReason::New),
+                Some(printed) if printed.crc == now.crc => None,
+                Some(printed) if printed.gen != now.gen => Some(ReprintReason::Rewritten {
+                    printed: printed.gen,
+                    current: now.gen,
+                }),
+                Some(_) => Some(ReprintReason::Changed),
+            };
+            if let Some(reason) = reason {
+                plan.reprint.push((id, reason));
+            }
+        }
+        plan.obsolete = self
+            .blocks
+            .keys()
+            .filter(|id| !current.blocks.contains_key(id))
+            .copied()
+            .collect();
+        plan
+    }
+}
+
+// como se imprimio: el qr (lado en pixeles y correccion) y la grilla de la hoja
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrintParams {
+    pub qr_size: u32,
+    pub ec_level: String,
+    pub cols: u32,
+    pub rows: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrintRun {
+    pub number: u32,
+    // segundos unix
+    pub timestamp: u64,
+    pub params: PrintParams,
+    // solo lo que salio en esta impresion
+    pub blocks: BTreeMap<BlockId, PrintedBlock>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrintLog {
+    pub runs: Vec<PrintRun>,
+}
+
+// a que papel corresponde el disco
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaperStatus {
+    NeverPrinted,
+    Current { run: u32, timestamp: u64 },
+    // changed: qr que habria que reimprimir
+    Diverged { run: u32, timestamp: u64, changed: usize },
+}
+
+impl PrintLog {
+    // sin archivo: nunca se imprimio
+    pub fn load(disk: impl AsRef<Path>) -> Result<Self, QrfsError> {
+        let path = disk.as_ref().join(PRINTS_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        serde_json::from_str(&fs::read_to_string(&path)?)
+            .map_err(|e| QrfsError::Other(format!("registro de impresiones invalido '{}': {}", path.display(), e)))
+    }
+
+    pub fn save(&self, disk: impl AsRef<Path>) -> Result<(), QrfsError> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| QrfsError::Other(format!("no se pudo serializar el registro de impresiones: {}", e)))?;
+        fs::write(disk.as_ref().join(PRINTS_FILE), json)?;
+        Ok(())
+    }
+
+    // lo que hay en papel: cada bloque como salio en su ultima impresion
+    pub fn paper(&self) -> PrintManifest {
+        let mut paper = PrintManifest::default();
+        for run in &self.runs {
+            paper.blocks.extend(run.blocks.iter().map(|(&id, &block)| (id, block)));
+        }
+        paper
+    }
+
+    // anota una impresion nueva con esos bloques
+    pub fn record(&mut self, blocks: BTreeMap<BlockId, PrintedBlock>, params: PrintParams) -> &PrintRun {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let number = self.runs.last().map(|run| run.number + 1).unwrap_or(1);
+        self.runs.push(PrintRun {
+            number,
+            timestamp,
+            params,
+            blocks,
+        });
+        self.runs.last().unwrap()
+    }
+
+    pub fn status(&self, current: &PrintManifest) -> PaperStatus {
+        let Some(last) = self.runs.last() else {
+            return PaperStatus::NeverPrinted;
+        };
+        let changed = self.paper().plan(current).reprint.len();
+        if changed == 0 {
+            PaperStatus::Current {
+                run: last.number,
+                timestamp: last.timestamp,
+            }
+        } else {
+            PaperStatus::Diverged {
+                run: last.number,
+                timestamp: last.timestamp,
+                changed,
+            }
+        }
+    }
+}
+
+impl fmt::Display for PaperStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            PaperStatus::NeverPrinted => write!(f, "nunca se imprimio"),
+            PaperStatus::Current { run, timestamp } => {
+                write!(f, "corresponde al papel de la impresion #{} ({})", run, utc_date(timestamp))
+            }
+            PaperStatus::Diverged { run, timestamp, changed } => write!(
+                f,
+                "{} qr cambiaron desde la impresion #{} ({})",
+                changed,
+                run,
+                utc_date(timestamp)
+            ),
+        }
+    }
+}
+
+// aaaa-mm-dd hh:mm utc (dias a fecha civil, como days_from_civil al reves)
+fn utc_date(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    let secs = timestamp % 86_400;
+    format!("{:04}-{:02}-{:02} {:02}:{:02} utc", year, month, day, secs / 3600, secs / 60 % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::BLOCK_SIZE;
+
+    #[test]
+    fn only_rewritten_and_new_blocks_are_reprinted() {
+        let dir = std::env::temp_dir().join(format!("qrfs_reprint_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let storage = QrStorageManager::new(&dir, BLOCK_SIZE, 16);
+        for id in 0..4 {
+            storage.write_block(id, &[id as u8; 20]).unwrap();
+        }
+        let (printed, unreadable) = PrintManifest::capture(&storage);
+        assert!(unreadable.is_empty());
+        let saved = dir.join("impresion.json");
+        printed.save(&saved).unwrap();
+        assert_eq!(PrintManifest::load(&saved).unwrap(), printed);
+
+        storage.write_block(1, &[9; 20]).unwrap();
+        storage.write_block(6, &[6; 20]).unwrap();
+        // mismo contenido con otra generacion: la hoja vieja sirve
+        storage.write_block(2, &[2; 20]).unwrap();
+        fs::remove_file(storage.block_path(3)).unwrap();
+
+        let (current, _) = PrintManifest::capture(&storage);
+        let plan = printed.plan(&current);
+        assert_eq!(
+            plan.reprint,
+            [
+                (1, ReprintReason::Rewritten { printed: 1, current: 2 }),
+                (6, ReprintReason::New),
+            ]
+        );
+        assert_eq!(plan.obsolete, [3]);
+        assert!(current.plan(&current).reprint.is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn the_log_knows_which_print_the_disk_matches() {
+        let block = |gen, crc| PrintedBlock { gen, crc };
+        let params = PrintParams {
+            qr_size: 200,
+            ec_level: "M".into(),
+            cols: 2,
+            rows: 3,
+        };
+        let mut log = PrintLog::default();
+        let mut current = PrintManifest::default();
+        current.blocks.extend([(0, block(1, 10)), (1, block(1, 11))]);
+        assert_eq!(log.status(&current), PaperStatus::NeverPrinted);
+
+        log.record(current.blocks.clone(), params.clone());
+        assert!(matches!(log.status(&current), PaperStatus::Current { run: 1, .. }));
+
+        current.blocks.insert(1, block(2, 12));
+        assert!(matches!(log.status(&current), PaperStatus::Diverged { run: 1, changed: 1, .. }));
+
+        // la reimpresion lleva solo el bloque 1; el 0 sigue siendo el de la #1
+        log.record([(1, block(2, 12))].into(), params);
+        assert_eq!(log.paper(), current);
+        assert!(matches!(log.status(&current), PaperStatus::Current { run: 2, .. }));
+
+        assert_eq!(utc_date(0), "1970-01-01 00:00 utc");
+        assert_eq!(utc_date(1_791_900_000), "2026-10-13 14:00 utc");
+    }
+}
@@ -0,0 +1,300 @@
+// reintentos para backends que fallan de a ratos
+// un backend remoto (red, object store) puede cortar una lectura o tardar
+// de mas sin que el bloque este mal. RetryingStorage envuelve cualquier
+// BlockStorage: repite las operaciones que fallaron por un error pasajero
+// (EINTR, timeout, conexion cortada) esperando cada vez el doble, y corta
+// las que tardan mas que el timeout. un error que no es pasajero (qr
+// ilegible, checksum, fuera de rango) sale en el primer intento.
+// los backends remotos se arman siempre envueltos con RetryPolicy::default();
+// QrStorageManager lo usa solo si el perfil lo pide ([retry] en qrfs.toml)
+
+use std::io::ErrorKind;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::disk::BlockId;
+use crate::errors::QrfsError;
+use crate::health::HealthMonitor;
+use crate::storage::BlockStorage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    // intentos en total (1 = sin reintentos)
+    pub attempts: u32,
+    // espera antes del primer reintento; se duplica en cada uno
+    pub backoff: Duration,
+    // tiempo maximo de cada intento (None = sin limite)
+    pub timeout: Option<Duration>,
+}
+
+// la de los backends remotos
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 4,
+            backoff: Duration::from_millis(50),
+            timeout: Some(Duration::from_secs(30)),
+        }
+    }
+}
+
+impl RetryPolicy {
+    // un solo intento: lo que hacia el backend sin envolver
+    pub const fn once() -> Self {
+        Self {
+            attempts: 1,
+            backoff: Duration::from_millis(50),
+            timeout: None,
+        }
+    }
+
+    // corre op hasta que salga bien, falle con un error que no es pasajero o
+    // se acaben los intentos (devuelve el ultimo error). sin timeout: op
+    // corre en el hilo del llamador
+    pub fn run<T>(&self, mut op: impl FnMut() -> Result<T, QrfsError>) -> Result<T, QrfsError> {
+        let mut wait = self.backoff;
+        let mut attempt = 1;
+        loop {
+            match op() {
+                Err(e) if attempt < self.attempts && is_transient(&e) => {
+                    thread::sleep(wait);
+                    wait = wait.saturating_mul(2);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+// errores de los que se vuelve: la llamada se interrumpio o la conexion se
+// corto, el bloque en si no tiene nada
+pub fn is_transient(e: &QrfsError) -> bool {
+    match e {
+        QrfsError::Io(e) => is_transient_io(e),
+        _ => false,
+    }
+}
+
+pub fn is_transient_io(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::TimedOut
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected
+            | ErrorKind::BrokenPipe
+    )
+}
+
+pub struct RetryingStorage<S: BlockStorage + 'static> {
+    inner: Arc<S>,
+    policy: RetryPolicy,
+    // intentos repetidos desde que se abrio
+    retried: AtomicU64,
+}
+
+impl<S: BlockStorage + 'static> RetryingStorage<S> {
+    pub fn new(inner: S, policy: RetryPolicy) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            policy,
+            retried: AtomicU64::new(0),
+        }
+    }
+
+    pub fn retried(&self) -> u64 {
+        self.retried.load(Ordering::Relaxed)
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    fn run<T: Send + 'static>(
+        &self,
+        op: impl Fn(&S) -> Result<T, QrfsError> + Send + Sync + 'static,
+    ) -> Result<T, QrfsError> {
+        let op = Arc::new(op);
+        let mut first = true;
+        self.policy.run(|| {
+            if !first {
+                self.retried.fetch_add(1, Ordering::Relaxed);
+            }
+            first = false;
+            self.attempt(op.clone())
+        })
+    }
+
+    // un intento; con timeout corre en otro hilo y si no termina a tiempo se
+    // abandona (el hilo sigue hasta que el backend responda, lo que traiga se
+    // descarta)
+    fn attempt<T: Send + 'static>(
+        &self,
+        op: Arc<impl Fn(&S) -> Result<T, QrfsError> + Send + Sync + 'static>,
+    ) -> Result<T, QrfsError> {
+        let Some(timeout) = self.policy.timeout else {
+            return op(&self.inner);
+        };
+        let (tx, rx) = mpsc::channel();
+        let inner = self.inner.clone();
+        thread::spawn(move || {
+            let _ = tx.send(op(&inner));
+        });
+        rx.recv_timeout(timeout).unwrap_or_else(|_| {
+            Err(QrfsError::Io(std::io::Error::new(
+                ErrorKind::TimedOut,
+                format!("el backend no respondio en {} ms", timeout.as_millis()),
+            )))
+        })
+    }
+}
+
+impl<S: BlockStorage + 'static> BlockStorage for RetryingStorage<S> {
+    fn block_size(&self) -> usize {
+        self.inner.block_size()
+    }
+
+    fn total_blocks(&self) -> u32 {
+        self.inner.total_blocks()
+    }
+
+    fn read_block(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        self.run(move |s| s.read_block(id))
+    }
+
+    fn write_block(&self, id: BlockId, data: &[u8]) -> Result<(), QrfsError> {
+        let data = data.to_vec();
+        self.run(move |s| s.write_block(id, &data))
+    }
+
+    fn read_verified(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        self.run(move |s| s.read_verified(id))
+    }
+
+    fn reencode_block(&self, id: BlockId) -> Result<(), QrfsError> {
+        self.run(move |s| s.reencode_block(id))
+    }
+
+    fn has_block(&self, id: BlockId) -> bool {
+        self.inner.has_block(id)
+    }
+
+    fn host_bytes(&self, id: BlockId) -> u64 {
+        self.inner.host_bytes(id)
+    }
+
+    fn health(&self) -> Option<Arc<HealthMonitor>> {
+        self.inner.health()
+    }
+
+    fn block_freed(&self, id: BlockId) {
+        self.inner.block_freed(id)
+    }
+
+    fn barrier(&self) -> Result<(), QrfsError> {
+        self.run(|s| s.barrier())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::BLOCK_SIZE;
+    use crate::storage::InMemoryBlockStorage;
+    use std::sync::atomic::AtomicU32;
+
+    // backend que falla las primeras `flaky` operaciones y tarda `delay` en cada una
+    struct Flaky {
+        inner: InMemoryBlockStorage,
+        flaky: AtomicU32,
+        kind: ErrorKind,
+        delay: Duration,
+    }
+
+    impl Flaky {
+        fn new(flaky: u32, kind: ErrorKind, delay: Duration) -> Self {
+            Self {
+                inner: InMemoryBlockStorage::new(16, BLOCK_SIZE),
+                flaky: AtomicU32::new(flaky),
+                kind,
+                delay,
+            }
+        }
+
+        fn fail(&self) -> Result<(), QrfsError> {
+            thread::sleep(self.delay);
+            if self.flaky.load(Ordering::SeqCst) > 0 {
+                self.flaky.fetch_sub(1, Ordering::SeqCst);
+                return Err(QrfsError::Io(std::io::Error::new(self.kind, "falla de prueba")));
+            }
+            Ok(())
+        }
+    }
+
+    impl BlockStorage for Flaky {
+        fn block_size(&self) -> usize {
+            self.inner.block_size()
+        }
+        fn total_blocks(&self) -> u32 {
+            self.inner.total_blocks()
+        }
+        fn read_block(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+            self.fail()?;
+            self.inner.read_block(id)
+        }
+        fn write_block(&self, id: BlockId, data: &[u8]) -> Result<(), QrfsError> {
+            self.fail()?;
+            self.inner.write_block(id, data)
+        }
+    }
+
+    fn policy(attempts: u32, timeout: Option<Duration>) -> RetryPolicy {
+        RetryPolicy {
+            attempts,
+            backoff: Duration::from_millis(1),
+            timeout,
+        }
+    }
+
+    #[test]
+    fn transient_errors_are_retried_and_permanent_ones_are_not() {
+        let storage = RetryingStorage::new(Flaky::new(2, ErrorKind::Interrupted, Duration::ZERO), policy(3, None));
+        storage.write_block(1, &[9; BLOCK_SIZE]).unwrap();
+        assert_eq!(storage.retried(), 2);
+        assert_eq!(storage.read_block(1).unwrap(), [9; BLOCK_SIZE]);
+
+        // se acaban los intentos: sale el ultimo error
+        let storage = RetryingStorage::new(Flaky::new(5, ErrorKind::ConnectionReset, Duration::ZERO), policy(3, None));
+        assert!(matches!(storage.read_block(1), Err(QrfsError::Io(e)) if e.kind() == ErrorKind::ConnectionReset));
+        assert_eq!(storage.retried(), 2);
+
+        // un permiso denegado no se arregla reintentando
+        let storage = RetryingStorage::new(Flaky::new(1, ErrorKind::PermissionDenied, Duration::ZERO), policy(3, None));
+        assert!(storage.read_block(1).is_err());
+        assert_eq!(storage.retried(), 0);
+        assert!(storage.read_block(1).is_ok());
+    }
+
+    #[test]
+    fn slow_operations_time_out_and_are_retried() {
+        let storage = RetryingStorage::new(
+            Flaky::new(0, ErrorKind::Interrupted, Duration::from_millis(200)),
+            policy(2, Some(Duration::from_millis(20))),
+        );
+        let err = storage.read_block(1).unwrap_err();
+        assert!(matches!(&err, QrfsError::Io(e) if e.kind() == ErrorKind::TimedOut), "{}", err);
+        assert_eq!(storage.retried(), 1);
+
+        let storage = RetryingStorage::new(
+            Flaky::new(0, ErrorKind::Interrupted, Duration::ZERO),
+            policy(2, Some(Duration::from_secs(5))),
+        );
+        assert_eq!(storage.read_block(1).unwrap(), [0; BLOCK_SIZE]);
+    }
+}
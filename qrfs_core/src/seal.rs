@@ -0,0 +1,93 @@
+// modo sellado (parecido a fsverity)
+// al montar se lee cada bloque en uso y se anota su checksum; despues toda
+// lectura tiene que dar el mismo checksum. un qr cambiado, reemplazado o
+// ilegible se corta con EIO y queda anotado para el archivo de estado
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::disk::BlockId;
+use crate::errors::QrfsError;
+use crate::qr::block_checksum;
+use crate::storage::BlockStorage;
+
+// archivo virtual en la raiz con el estado del sellado
+pub const STATUS_FILE: &str = ".qrfs_status";
+
+pub struct SealIndex {
+    measured: HashMap<BlockId, u32>,
+    failures: Mutex<Vec<String>>,
+}
+
+impl SealIndex {
+    // lee y mide los bloques; los que ya fallan al medir quedan como fallas
+    pub fn measure<S: BlockStorage + ?Sized>(storage: &S, blocks: &[BlockId]) -> Self {
+        let mut measured = HashMap::new();
+        let mut failures = Vec::new();
+        for &id in blocks {
+            match storage.read_verified(id) {
+                Ok(data) => {
+                    measured.insert(id, block_checksum(&data));
+                }
+                Err(e) => failures.push(format!("bloque {} al medir: {}", id, e)),
+            }
+        }
+        Self {
+            measured,
+            failures: Mutex::new(failures),
+        }
+    }
+
+    // error si el bloque no se midio o si su contenido cambio desde el montaje
+    pub fn verify(&self, id: BlockId, data: &[u8]) -> Result<(), QrfsError> {
+        let problem = match self.measured.get(&id) {
+            Some(&crc) if crc == block_checksum(data) => return Ok(()),
+            Some(_) => format!("bloque {} cambio desde el montaje", id),
+            None => format!("bloque {} no esta en el indice sellado", id),
+        };
+        self.record(problem.clone());
+        Err(QrfsError::Other(problem))
+    }
+
+    pub fn record(&self, problem: String) {
+        eprintln!("qrfs: sellado: {problem}");
+        self.failures.lock().unwrap().push(problem);
+    }
+
+    // contenido del archivo de estado
+    pub fn status_text(&self) -> String {
+        let failures = self.failures.lock().unwrap();
+        let mut text = format!(
+            "modo: sellado (solo lectura)\nbloques medidos: {}\nfallas: {}\n",
+            self.measured.len(),
+            failures.len()
+        );
+        for failure in failures.iter() {
+            text.push_str(failure);
+            text.push('\n');
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::InMemoryBlockStorage;
+
+    #[test]
+    fn changed_blocks_fail_and_show_up_in_status() {
+        let storage = InMemoryBlockStorage::new(8, 16);
+        storage.write_block(3, b"original").unwrap();
+        let seal = SealIndex::measure(&storage, &[3]);
+
+        assert!(seal.verify(3, &storage.read_block(3).unwrap()).is_ok());
+        assert!(seal.verify(3, b"cambiado").is_err());
+        assert!(seal.verify(5, &[0u8; 16]).is_err());
+
+        let status = seal.status_text();
+        assert!(status.contains("bloques medidos: 1"));
+        assert!(status.contains("fallas: 2"));
+        assert!(status.contains("bloque 3 cambio"));
+    }
+}
@@ -0,0 +1,278 @@
+// reparto de la clave maestra en fragmentos (shamir sobre gf(256)): de n
+// tarjetas qr hacen falta k para rearmarla, con menos no se sabe nada de
+// ella. sirve para recuperar un disco cifrado si se olvida la frase
+//
+// cada byte de la clave es el termino independiente de un polinomio al azar
+// de grado k-1; el fragmento x guarda el valor del polinomio en x. en texto:
+// qrfs-shard:<k>:<x>:<control en hex>:<valores en hex>. el control (hmac de
+// la maestra) lo llevan todos los fragmentos y detecta una mezcla de
+// tarjetas o una mal leida
+
+use std::path::{Path, PathBuf};
+
+use crate::crypt::{self, hmac_sha256, Key, KEY_LEN};
+use crate::errors::QrfsError;
+use crate::qr::{decode_qr_text, encode_text, QrParams};
+
+pub const SHARD_PREFIX: &str = "qrfs-shard:";
+
+const CHECK_LEN: usize = 8;
+
+// multiplicacion en gf(2^8) con el polinomio de aes (x^8 + x^4 + x^3 + x + 1)
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut out = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            out ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    out
+}
+
+// inverso: a^254 (el grupo multiplicativo tiene 255 elementos)
+fn gf_inv(a: u8) -> u8 {
+    let mut out = 1;
+    for _ in 0..254 {
+        out = gf_mul(out, a);
+    }
+    out
+}
+
+fn check_value(master: &Key) -> [u8; CHECK_LEN] {
+    hmac_sha256(master, &[b"qrfs-fragmento"])[..CHECK_LEN].try_into().unwrap()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyShard {
+    // fragmentos necesarios para rearmar la clave
+    pub threshold: u8,
+    // posicion del fragmento (1..=n)
+    pub x: u8,
+    pub check: [u8; CHECK_LEN],
+    pub share: Key,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(text: &str, out: &mut [u8]) -> Option<()> {
+    if text.len() != out.len() * 2 || !text.is_ascii() {
+        return None;
+    }
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&text[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(())
+}
+
+impl KeyShard {
+    pub fn to_text(&self) -> String {
+        format!(
+            "{}{}:{}:{}:{}",
+            SHARD_PREFIX,
+            self.threshold,
+            self.x,
+            hex(&self.check),
+            hex(&self.share)
+        )
+    }
+
+    pub fn parse(text: &str) -> Result<Self, QrfsError> {
+        let invalid = || QrfsError::Other(format!("fragmento de clave invalido: '{}'", text.trim()));
+        let body = text.trim().strip_prefix(SHARD_PREFIX).ok_or_else(invalid)?;
+        let parts: Vec<&str> = body.split(':').collect();
+        let [threshold, x, check, share] = parts[..] else {
+            return Err(invalid());
+        };
+        let threshold = threshold.parse().map_err(|_| invalid())?;
+        let x = x.parse().map_err(|_| invalid())?;
+        let mut shard = Self {
+            threshold,
+            x,
+            check: [0; CHECK_LEN],
+            share: [0; KEY_LEN],
+        };
+        from_hex(check, &mut shard.check).ok_or_else(invalid)?;
+        from_hex(share, &mut shard.share).ok_or_else(invalid)?;
+        if shard.x == 0 || shard.threshold < 2 {
+            return Err(invalid());
+        }
+        Ok(shard)
+    }
+}
+
+// tarjeta de un fragmento: un qr aparte de los del disco, con correccion
+// alta porque es chico y se imprime una sola vez
+pub fn shard_image(shard: &KeyShard) -> Result<image::GrayImage, QrfsError> {
+    encode_text(
+        &shard.to_text(),
+        QrParams {
+            size: 300,
+            ec_level: qrcode::EcLevel::H,
+        },
+    )
+}
+
+// fragmento de una foto o escaneo de la tarjeta (o de un .txt con el texto)
+pub fn read_shard(path: &Path) -> Result<KeyShard, QrfsError> {
+    let text = if path.extension().is_some_and(|ext| ext == "txt") {
+        std::fs::read_to_string(path)?
+    } else {
+        let img = image::open(path)
+            .map_err(|e| QrfsError::Other(format!("no se pudo abrir '{}': {}", path.display(), e)))?;
+        decode_qr_text(&img)?
+    };
+    KeyShard::parse(&text)
+}
+
+// --key-shards a.png b.png ...: las rutas que siguen hasta la proxima opcion
+pub fn key_shards_arg(args: &[String]) -> Vec<PathBuf> {
+    args.iter()
+        .skip_while(|arg| *arg != "--key-shards")
+        .skip(1)
+        .take_while(|arg| !arg.starts_with("--"))
+        .map(PathBuf::from)
+        .collect()
+}
+
+pub fn read_shards(paths: &[PathBuf]) -> Result<Vec<KeyShard>, QrfsError> {
+    paths
+        .iter()
+        .map(|path| {
+            read_shard(path).map_err(|e| QrfsError::Other(format!("{}: {}", path.display(), e)))
+        })
+        .collect()
+}
+
+// reparte la clave en count fragmentos de los que hacen falta threshold
+pub fn split(master: &Key, threshold: u8, count: u8) -> Result<Vec<KeyShard>, QrfsError> {
+    if threshold < 2 || threshold > count {
+        return Err(QrfsError::Other(format!(
+            "reparto invalido {}/{}: hacen falta al menos 2 fragmentos y no mas de los que hay",
+            threshold, count
+        )));
+    }
+    // coeficientes[i][j]: coeficiente de grado j+1 del polinomio del byte i
+    let mut coefficients = vec![0u8; KEY_LEN * (threshold as usize - 1)];
+    crypt::random_bytes(&mut coefficients)?;
+    let check = check_value(master);
+
+    Ok((1..=count)
+        .map(|x| {
+            let mut share = [0u8; KEY_LEN];
+            for (i, (value, &secret)) in share.iter_mut().zip(master).enumerate() {
+                let row = &coefficients[i * (threshold as usize - 1)..(i + 1) * (threshold as usize - 1)];
+                // horner: ((c_{k-1} x + c_{k-2}) x + ... ) x + secreto
+                let mut y = 0;
+                for &c in row.iter().rev() {
+                    y = gf_mul(y, x) ^ c;
+                }
+                *value = gf_mul(y, x) ^ secret;
+            }
+            KeyShard {
+                threshold,
+                x,
+                check,
+                share,
+            }
+        })
+        .collect())
+}
+
+// rearma la clave (interpolacion de lagrange en 0) y la compara con el control
+pub fn combine(shards: &[KeyShard]) -> Result<Key, QrfsError> {
+    let Some(first) = shards.first() else {
+        return Err(QrfsError::Other("no hay fragmentos de clave".into()));
+    };
+    // un mismo fragmento repetido (dos fotos de la misma tarjeta) cuenta una vez
+    let mut unique: Vec<&KeyShard> = Vec::new();
+    for shard in shards {
+        if shard.threshold != first.threshold || shard.check != first.check {
+            return Err(QrfsError::Other(format!(
+                "el fragmento {} es de otra clave o de otro reparto",
+                shard.x
+            )));
+        }
+        match unique.iter().find(|other| other.x == shard.x) {
+            Some(other) if other.share != shard.share => {
+                return Err(QrfsError::Other(format!("dos fragmentos distintos con el numero {}", shard.x)));
+            }
+            Some(_) => {}
+            None => unique.push(shard),
+        }
+    }
+    if unique.len() < first.threshold as usize {
+        return Err(QrfsError::Other(format!(
+            "hacen falta {} fragmentos distintos (hay {})",
+            first.threshold,
+            unique.len()
+        )));
+    }
+    let used = &unique[..first.threshold as usize];
+
+    let mut master = [0u8; KEY_LEN];
+    for (i, shard) in used.iter().enumerate() {
+        // base de lagrange en 0: prod x_j / (x_j - x_i); en gf(2^8) restar es xor
+        let mut basis = 1;
+        for (j, other) in used.iter().enumerate() {
+            if i != j {
+                basis = gf_mul(basis, gf_mul(other.x, gf_inv(other.x ^ shard.x)));
+            }
+        }
+        for (out, &y) in master.iter_mut().zip(&shard.share) {
+            *out ^= gf_mul(y, basis);
+        }
+    }
+    if check_value(&master) != first.check {
+        return Err(QrfsError::NotPermitted(
+            "los fragmentos no rearman la clave (alguno mal leido?)".into(),
+        ));
+    }
+    Ok(master)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn any_threshold_of_the_shards_rebuilds_the_key() {
+        let master = crypt::random_key().unwrap();
+        let shards = split(&master, 3, 5).unwrap();
+        assert_eq!(shards.len(), 5);
+        assert_eq!(gf_mul(0x57, 0x83), 0xc1);
+        assert_eq!(gf_mul(0x53, gf_inv(0x53)), 1);
+
+        for combo in [[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+            let picked: Vec<KeyShard> = combo.iter().map(|&i| shards[i].clone()).collect();
+            assert_eq!(combine(&picked).unwrap(), master);
+        }
+        // por texto, como sale de la tarjeta
+        let read: Vec<KeyShard> = shards[1..4]
+            .iter()
+            .map(|s| KeyShard::parse(&s.to_text()).unwrap())
+            .collect();
+        assert_eq!(combine(&read).unwrap(), master);
+
+        // dos no alcanzan, aunque uno este repetido
+        assert!(combine(&[shards[0].clone(), shards[1].clone(), shards[1].clone()]).is_err());
+        // un fragmento alterado no pasa el control
+        let mut bad = shards[..3].to_vec();
+        bad[2].share[5] ^= 1;
+        assert!(matches!(combine(&bad), Err(QrfsError::NotPermitted(_))));
+        assert!(split(&master, 4, 3).is_err());
+
+        // la tarjeta impresa se vuelve a leer
+        let path = std::env::temp_dir().join(format!("qrfs_fragmento_{}.png", std::process::id()));
+        shard_image(&shards[0]).unwrap().save(&path).unwrap();
+        assert_eq!(read_shard(&path).unwrap(), shards[0]);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,180 @@
+// instantanea de solo lectura de un disco que sigue recibiendo bloques (ej:
+// el servidor ingestando un escaneo a medias). la metadata se carga entera en
+// memoria al tomarla y el storage copia cada bloque a la instantanea antes de
+// pisarlo (copy on write), asi la vista queda fija en ese momento
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::disk::BlockId;
+use crate::errors::QrfsError;
+use crate::health::HealthMonitor;
+use crate::storage::BlockStorage;
+
+// contenido que tenian los bloques reescritos despues de tomar la instantanea
+#[derive(Default)]
+pub struct SnapshotBlocks {
+    blocks: Mutex<HashMap<BlockId, Vec<u8>>>,
+}
+
+impl SnapshotBlocks {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    // guarda lo que tiene el bloque ahora; solo la primera vez, las
+    // escrituras siguientes ya no cambian lo que ve la instantanea
+    pub fn preserve<S: BlockStorage + ?Sized>(&self, storage: &S, id: BlockId) -> Result<(), QrfsError> {
+        self.preserve_with(id, || storage.read_block(id))
+    }
+
+    // como preserve, para el storage que ya tiene el bloque tomado
+    pub fn preserve_with(
+        &self,
+        id: BlockId,
+        read: impl FnOnce() -> Result<Vec<u8>, QrfsError>,
+    ) -> Result<(), QrfsError> {
+        let mut blocks = self.blocks.lock().unwrap();
+        if let Entry::Vacant(entry) = blocks.entry(id) {
+            entry.insert(read()?);
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, id: BlockId) -> Option<Vec<u8>> {
+        self.blocks.lock().unwrap().get(&id).cloned()
+    }
+
+    pub fn contains(&self, id: BlockId) -> bool {
+        self.blocks.lock().unwrap().contains_key(&id)
+    }
+
+    // cuantos bloques se copiaron hasta ahora
+    pub fn len(&self) -> usize {
+        self.blocks.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// lee la copia si el bloque cambio y si no el disco; no se puede escribir
+pub struct SnapshotStorage<B: BlockStorage> {
+    inner: Arc<B>,
+    blocks: Arc<SnapshotBlocks>,
+}
+
+impl<B: BlockStorage> SnapshotStorage<B> {
+    // blocks ya tiene que estar registrado en el storage que escribe
+    // (QrStorageManager::register_snapshot) antes de leer nada
+    pub fn new(inner: Arc<B>, blocks: Arc<SnapshotBlocks>) -> Self {
+        Self { inner, blocks }
+    }
+
+    pub fn blocks(&self) -> &Arc<SnapshotBlocks> {
+        &self.blocks
+    }
+}
+
+impl<B: BlockStorage> BlockStorage for SnapshotStorage<B> {
+    fn block_size(&self) -> usize {
+        self.inner.block_size()
+    }
+
+    fn total_blocks(&self) -> u32 {
+        self.inner.total_blocks()
+    }
+
+    fn read_block(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        match self.blocks.get(id) {
+            Some(data) => Ok(data),
+            None => self.inner.read_block(id),
+        }
+    }
+
+    fn write_block(&self, id: BlockId, _data: &[u8]) -> Result<(), QrfsError> {
+        Err(QrfsError::Other(format!("bloque {}: la instantanea es de solo lectura", id)))
+    }
+
+    fn read_block_into(&self, id: BlockId, buf: &mut [u8]) -> Result<(), QrfsError> {
+        match self.blocks.get(id) {
+            Some(data) => {
+                let len = data.len().min(buf.len());
+                buf[..len].copy_from_slice(&data[..len]);
+                buf[len..].fill(0);
+                Ok(())
+            }
+            None => self.inner.read_block_into(id, buf),
+        }
+    }
+
+    // la copia ya no tiene qr contra el que verificarse
+    fn read_verified(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        match self.blocks.get(id) {
+            Some(data) => Ok(data),
+            None => self.inner.read_verified(id),
+        }
+    }
+
+    fn reencode_block(&self, id: BlockId) -> Result<(), QrfsError> {
+        self.write_block(id, &[])
+    }
+
+    fn has_block(&self, id: BlockId) -> bool {
+        self.blocks.contains(id) || self.inner.has_block(id)
+    }
+
+    fn host_bytes(&self, id: BlockId) -> u64 {
+        self.inner.host_bytes(id)
+    }
+
+    fn health(&self) -> Option<Arc<HealthMonitor>> {
+        self.inner.health()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::QrfsApi;
+    use crate::disk::{Superblock, BLOCK_SIZE};
+    use crate::fs::QrfsFilesystem;
+    use crate::fs_format::format_storage;
+    use crate::storage::QrStorageManager;
+
+    #[test]
+    fn snapshot_keeps_the_view_while_blocks_keep_arriving() {
+        let dir = std::env::temp_dir().join(format!("qrfs_snapshot_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let storage = Arc::new(Mutex::new(QrStorageManager::new(&dir, BLOCK_SIZE, 64)));
+        format_storage(storage.as_ref(), &Superblock::new(64, 16)).unwrap();
+        let writer = QrfsApi::open(storage.clone()).unwrap();
+        writer.write("/parcial.txt", b"primera parte").unwrap();
+        writer.flush().unwrap();
+
+        let blocks = SnapshotBlocks::new();
+        storage.lock().unwrap().register_snapshot(&blocks);
+        let snapshot = QrfsApi::new(
+            QrfsFilesystem::new(Arc::new(SnapshotStorage::new(storage.clone(), blocks.clone())))
+                .unwrap()
+                .with_snapshot()
+                .unwrap(),
+        );
+
+        // siguen llegando bloques: cambia el archivo y aparece otro
+        writer.write("/parcial.txt", b"ya completo").unwrap();
+        writer.write("/nuevo.txt", b"despues").unwrap();
+        writer.flush().unwrap();
+        assert!(!blocks.is_empty());
+
+        let names: Vec<String> = snapshot.list("/").unwrap().into_iter().map(|f| f.name).collect();
+        assert_eq!(names, vec!["parcial.txt".to_string()]);
+        assert_eq!(snapshot.read("/parcial.txt").unwrap(), b"primera parte");
+        assert!(snapshot.write("/otro.txt", b"x").is_err());
+        assert_eq!(writer.read("/parcial.txt").unwrap(), b"ya completo");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
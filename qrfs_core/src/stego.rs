@@ -0,0 +1,335 @@
+// modo portador (feature "stego"): en vez de un qr a la vista, cada bloque va
+// escondido en el bit bajo (lsb) de los canales rgb de una imagen de portada
+// que pone el usuario. la salida es siempre png: una compresion con perdida
+// (jpeg) borra esos bits, asi que una portada jpeg se guarda como png.
+// la carpeta del disco lleva CARRIER_FILE con las portadas y la geometria
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use image::RgbImage;
+use serde::{Deserialize, Serialize};
+
+use crate::disk::BlockId;
+use crate::errors::QrfsError;
+use crate::qr::block_checksum;
+use crate::storage::{BlockStorage, CARRIER_FILE};
+
+const MAGIC: &[u8; 4] = b"QRST";
+// magia + id + generacion + crc + largo
+const HEADER: usize = 24;
+
+#[derive(Serialize, Deserialize)]
+struct CarrierInfo {
+    covers: PathBuf,
+    block_size: usize,
+    total_blocks: u32,
+}
+
+pub struct CarrierStorage {
+    root_dir: PathBuf,
+    // portadas en orden de nombre; el bloque n usa la n % len
+    covers: Vec<PathBuf>,
+    block_size: usize,
+    total_blocks: u32,
+    generations: Mutex<HashMap<BlockId, u64>>,
+}
+
+// bytes que entran en una imagen: un bit por canal
+pub fn capacity(width: u32, height: u32) -> usize {
+    width as usize * height as usize * 3 / 8
+}
+
+// copia la portada con el payload en los bits bajos; cada canal cambia a lo
+// sumo en 1
+pub fn embed(cover: &RgbImage, payload: &[u8]) -> Result<RgbImage, QrfsError> {
+    let available = capacity(cover.width(), cover.height());
+    if payload.len() > available {
+        return Err(QrfsError::NoSpace(format!(
+            "la portada de {}x{} guarda {} bytes y el bloque necesita {}",
+            cover.width(),
+            cover.height(),
+            available,
+            payload.len()
+        )));
+    }
+    let mut image = cover.clone();
+    let bits = payload.iter().flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1));
+    for (channel, bit) in image.iter_mut().zip(bits) {
+        *channel = (*channel & !1) | bit;
+    }
+    Ok(image)
+}
+
+// lee len bytes desde el byte start de los bits bajos
+fn read_bits(image: &RgbImage, start: usize, len: usize) -> Vec<u8> {
+    image
+        .as_raw()
+        .iter()
+        .skip(start * 8)
+        .take(len * 8)
+        .map(|channel| channel & 1)
+        .collect::<Vec<u8>>()
+        .chunks(8)
+        .map(|bits| bits.iter().fold(0u8, |byte, &bit| (byte << 1) | bit))
+        .collect()
+}
+
+// (id, generacion, crc correcto, datos) de una imagen con un bloque adentro
+pub fn extract(image: &RgbImage, max_len: usize) -> Result<(BlockId, u64, bool, Vec<u8>), QrfsError> {
+    let available = capacity(image.width(), image.height());
+    if available < HEADER {
+        return Err(QrfsError::Corrupt("la imagen es muy chica para tener un bloque".into()));
+    }
+    let header = read_bits(image, 0, HEADER);
+    if &header[0..4] != MAGIC {
+        return Err(QrfsError::Corrupt("la imagen no tiene un bloque escondido".into()));
+    }
+    let id = u32::from_le_bytes(header[4..8].try_into().unwrap());
+    let generation = u64::from_le_bytes(header[8..16].try_into().unwrap());
+    let crc = u32::from_le_bytes(header[16..20].try_into().unwrap());
+    let len = u32::from_le_bytes(header[20..24].try_into().unwrap()) as usize;
+    if len > max_len || HEADER + len > available {
+        return Err(QrfsError::Corrupt(format!("el bloque {} declara {} bytes", id, len)));
+    }
+    let data = read_bits(image, HEADER, len);
+    Ok((id, generation, crc == block_checksum(&data), data))
+}
+
+fn payload(id: BlockId, generation: u64, data: &[u8]) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(HEADER + data.len());
+    raw.extend_from_slice(MAGIC);
+    raw.extend_from_slice(&id.to_le_bytes());
+    raw.extend_from_slice(&generation.to_le_bytes());
+    raw.extend_from_slice(&block_checksum(data).to_le_bytes());
+    raw.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    raw.extend_from_slice(data);
+    raw
+}
+
+impl CarrierStorage {
+    // mkfs: anota las portadas y la geometria en la carpeta del disco
+    pub fn create(
+        root_dir: impl Into<PathBuf>,
+        covers_dir: impl Into<PathBuf>,
+        block_size: usize,
+        total_blocks: u32,
+    ) -> Result<Self, QrfsError> {
+        let root_dir = root_dir.into();
+        // ruta absoluta: el montaje puede correr desde otra carpeta
+        let covers = covers_dir.into();
+        let info = CarrierInfo {
+            covers: fs::canonicalize(&covers).unwrap_or(covers),
+            block_size,
+            total_blocks,
+        };
+        let storage = Self::with_info(root_dir, &info)?;
+        fs::create_dir_all(&storage.root_dir)?;
+        let json = serde_json::to_string_pretty(&info)
+            .map_err(|e| QrfsError::Other(format!("error serializando {}: {}", CARRIER_FILE, e)))?;
+        fs::write(storage.root_dir.join(CARRIER_FILE), json)?;
+        Ok(storage)
+    }
+
+    pub fn open(root_dir: impl Into<PathBuf>) -> Result<Self, QrfsError> {
+        let root_dir = root_dir.into();
+        let text = fs::read_to_string(root_dir.join(CARRIER_FILE))?;
+        let info: CarrierInfo = serde_json::from_str(&text)
+            .map_err(|e| QrfsError::Corrupt(format!("{} invalido: {}", CARRIER_FILE, e)))?;
+        Self::with_info(root_dir, &info)
+    }
+
+    fn with_info(root_dir: PathBuf, info: &CarrierInfo) -> Result<Self, QrfsError> {
+        let mut covers: Vec<PathBuf> = fs::read_dir(&info.covers)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| matches!(ext.to_ascii_lowercase().as_str(), "png" | "jpg" | "jpeg" | "bmp"))
+            })
+            .collect();
+        covers.sort();
+        if covers.is_empty() {
+            return Err(QrfsError::Other(format!(
+                "no hay portadas (png, jpg o bmp) en {}",
+                info.covers.display()
+            )));
+        }
+        Ok(Self {
+            root_dir,
+            covers,
+            block_size: info.block_size,
+            total_blocks: info.total_blocks,
+            generations: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn block_path(&self, id: BlockId) -> PathBuf {
+        self.root_dir.join(format!("{:06}.png", id))
+    }
+
+    fn cover_for(&self, id: BlockId) -> &Path {
+        &self.covers[id as usize % self.covers.len()]
+    }
+
+    fn check_range(&self, id: BlockId) -> Result<(), QrfsError> {
+        if id >= self.total_blocks {
+            return Err(QrfsError::Other(format!(
+                "block id {id} fuera de rango 0..{}",
+                self.total_blocks - 1
+            )));
+        }
+        Ok(())
+    }
+
+    // None si el bloque no tiene imagen (se lee como ceros)
+    fn read_hidden(&self, id: BlockId) -> Result<Option<(u64, bool, Vec<u8>)>, QrfsError> {
+        self.check_range(id)?;
+        let path = self.block_path(id);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let image = image::open(&path)
+            .map_err(|e| QrfsError::Other(format!("error abriendo imagen: {}", e)))?
+            .to_rgb8();
+        let (stored_id, generation, checksum_ok, data) = extract(&image, self.block_size)
+            .map_err(|e| QrfsError::Corrupt(format!("{} ({})", e, path.display())))?;
+        if stored_id != id {
+            return Err(QrfsError::Corrupt(format!(
+                "{} tiene el bloque {} en vez del {}",
+                path.display(),
+                stored_id,
+                id
+            )));
+        }
+        self.generations.lock().unwrap().insert(id, generation);
+        Ok(Some((generation, checksum_ok, data)))
+    }
+}
+
+impl BlockStorage for CarrierStorage {
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn total_blocks(&self) -> u32 {
+        self.total_blocks
+    }
+
+    fn read_block(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        let mut data = match self.read_hidden(id)? {
+            Some((_, _, data)) => data,
+            None => Vec::new(),
+        };
+        data.resize(self.block_size, 0);
+        Ok(data)
+    }
+
+    fn read_verified(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        match self.read_hidden(id)? {
+            Some((_, false, _)) => Err(QrfsError::Corrupt(format!("bloque {}: crc distinto", id))),
+            Some((_, true, mut data)) => {
+                data.resize(self.block_size, 0);
+                Ok(data)
+            }
+            None => Ok(vec![0u8; self.block_size]),
+        }
+    }
+
+    fn write_block(&self, id: BlockId, data: &[u8]) -> Result<(), QrfsError> {
+        self.check_range(id)?;
+        if data.len() > self.block_size {
+            return Err(QrfsError::Other("datos muy grandes".to_string()));
+        }
+        let path = self.block_path(id);
+
+        // como en los qr, un bloque en cero no lleva imagen
+        if data.iter().all(|&b| b == 0) {
+            match fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+            return Ok(());
+        }
+
+        let known = self.generations.lock().unwrap().get(&id).copied();
+        let generation = match known {
+            Some(generation) => generation,
+            None => self.read_hidden(id).ok().flatten().map_or(0, |(generation, _, _)| generation),
+        } + 1;
+
+        let cover = image::open(self.cover_for(id))
+            .map_err(|e| QrfsError::Other(format!("error abriendo portada: {}", e)))?
+            .to_rgb8();
+        let image = embed(&cover, &payload(id, generation, data))
+            .map_err(|e| QrfsError::NoSpace(format!("{} ({})", e, self.cover_for(id).display())))?;
+        image
+            .save_with_format(&path, image::ImageFormat::Png)
+            .map_err(|e| QrfsError::Other(format!("error guardando imagen: {}", e)))?;
+        self.generations.lock().unwrap().insert(id, generation);
+        Ok(())
+    }
+
+    fn has_block(&self, id: BlockId) -> bool {
+        id < self.total_blocks && self.block_path(id).exists()
+    }
+
+    // la imagen de portada entera, que es lo que se guarda por cada bloque
+    fn host_bytes(&self, id: BlockId) -> u64 {
+        fs::metadata(self.block_path(id)).map(|meta| meta.len()).unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    fn cover(width: u32, height: u32) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, y| Rgb([(x * 7) as u8, (y * 5) as u8, ((x + y) * 3) as u8]))
+    }
+
+    #[test]
+    fn embedded_payload_comes_back_and_barely_touches_the_cover() {
+        let original = cover(64, 64);
+        let data: Vec<u8> = (0..500u32).map(|i| (i * 13) as u8).collect();
+        let image = embed(&original, &payload(9, 4, &data)).unwrap();
+
+        assert!(original
+            .as_raw()
+            .iter()
+            .zip(image.as_raw())
+            .all(|(a, b)| a.abs_diff(*b) <= 1));
+        let (id, generation, checksum_ok, extracted) = extract(&image, 1024).unwrap();
+        assert_eq!((id, generation, checksum_ok), (9, 4, true));
+        assert_eq!(extracted, data);
+
+        assert!(matches!(embed(&cover(8, 8), &data), Err(QrfsError::NoSpace(_))));
+        assert!(extract(&original, 1024).is_err());
+    }
+
+    #[test]
+    fn carrier_storage_roundtrips_blocks_through_cover_images() {
+        let dir = std::env::temp_dir().join(format!("qrfs_stego_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let covers = dir.join("portadas");
+        fs::create_dir_all(&covers).unwrap();
+        cover(48, 48).save(covers.join("a.png")).unwrap();
+        cover(50, 40).save(covers.join("b.png")).unwrap();
+
+        let storage = CarrierStorage::create(dir.join("disco"), &covers, 256, 16).unwrap();
+        storage.write_block(3, b"escondido").unwrap();
+        storage.write_block(3, b"otra vez").unwrap();
+        assert!(!storage.has_block(4));
+
+        let reopened = CarrierStorage::open(dir.join("disco")).unwrap();
+        assert_eq!(&reopened.read_verified(3).unwrap()[..8], b"otra vez");
+        assert_eq!(reopened.read_block(4).unwrap(), vec![0u8; 256]);
+        assert_eq!(reopened.read_hidden(3).unwrap().unwrap().0, 2);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,27 +1,217 @@
-use std::fs;
-use std::path::PathBuf;
-use std::sync::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak};
 
-use base64::{engine::general_purpose, Engine as _};
-use image::Luma;
-use qrcode::QrCode;
-use rqrr;
-use serde_json;
+use memmap2::Mmap;
 
-use crate::disk::BlockId;
+use qrcode::EcLevel;
+
+use crate::cas::{block_digest, CasIndex};
+use crate::codec::{codec_by_id, codecs, BlockCodec, DEFAULT_CODEC};
+use crate::decode_cache::DecodeCache;
+use crate::disk::{BlockId, Superblock, BLOCK_SIZE, QRFS_MAGIC, SUPERBLOCK_RESERVED_BYTES};
 use crate::errors::QrfsError;
+use crate::fs_format::decode_superblock;
+use crate::health::{HealthEvent, HealthMonitor};
+use crate::retry::{is_transient_io, RetryPolicy};
+use crate::snapshot::SnapshotBlocks;
+use crate::qr::{encode_block_svg, BlockEnvelope, DecodedBlock, QrParams};
 
 pub trait BlockStorage: Send + Sync {
     fn block_size(&self) -> usize;
     fn total_blocks(&self) -> u32;
     fn read_block(&self, id: BlockId) -> Result<Vec<u8>, QrfsError>;
     fn write_block(&self, id: BlockId, data: &[u8]) -> Result<(), QrfsError>;
+
+    // lee un bloque en un buffer del llamador (buf.len() == block_size)
+    // los backends que pueden evitan reservar memoria en cada llamada
+    fn read_block_into(&self, id: BlockId, buf: &mut [u8]) -> Result<(), QrfsError> {
+        let data = self.read_block(id)?;
+        let len = data.len().min(buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        buf[len..].fill(0);
+        Ok(())
+    }
+
+    // lee un bloque comprobando el checksum que guarde el backend, si tiene
+    fn read_verified(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        self.read_block(id)
+    }
+
+    // vuelve a codificar un bloque con la configuracion actual (ej: despues de
+    // cambiar parametros del qr o reparar una imagen); por defecto lo reescribe
+    fn reencode_block(&self, id: BlockId) -> Result<(), QrfsError> {
+        let data = self.read_block(id)?;
+        self.write_block(id, &data)
+    }
+
+    // false si el backend sabe que el bloque no esta (un qr sin escanear);
+    // los que no pueden saberlo dicen que todos estan
+    fn has_block(&self, _id: BlockId) -> bool {
+        true
+    }
+
+    // bytes que ocupa el bloque en el disco del host (el png de un qr pesa
+    // mucho mas que su payload); por defecto el bloque crudo
+    fn host_bytes(&self, _id: BlockId) -> u64 {
+        self.block_size() as u64
+    }
+
+    // donde anota el backend las fallas de lectura de cada bloque (ver
+    // health.rs); los que no decodifican nada no tienen
+    fn health(&self) -> Option<Arc<HealthMonitor>> {
+        None
+    }
+
+    // aviso del filesystem: el bloque quedo libre (ya en el bitmap). el qr
+    // sigue ahi hasta que se reuse; los envoltorios lo pasan para abajo y
+    // HookedStorage se lo cuenta a sus hooks
+    fn block_freed(&self, _id: BlockId) {}
+
+    // barrera de escritura: todo lo escrito antes queda en su lugar antes que
+    // cualquier cosa escrita despues. el filesystem pone una entre cada paso
+    // del commit (ver QrfsFilesystem::commit) y al final de flush_data, que
+    // es lo que hace fsync. los que terminan cada write_block antes de volver
+    // no tienen nada que hacer; uno que encola o sube en paralelo (ver
+    // ordering.rs) espera aca a que termine lo pendiente
+    fn barrier(&self) -> Result<(), QrfsError> {
+        Ok(())
+    }
+}
+
+// permite compartir un almacenamiento protegido por mutex (ej: servidor + montaje)
+impl<S: BlockStorage> BlockStorage for Mutex<S> {
+    fn block_size(&self) -> usize {
+        self.lock().unwrap().block_size()
+    }
+
+    fn total_blocks(&self) -> u32 {
+        self.lock().unwrap().total_blocks()
+    }
+
+    fn read_block(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        self.lock().unwrap().read_block(id)
+    }
+
+    fn write_block(&self, id: BlockId, data: &[u8]) -> Result<(), QrfsError> {
+        self.lock().unwrap().write_block(id, data)
+    }
+
+    fn read_block_into(&self, id: BlockId, buf: &mut [u8]) -> Result<(), QrfsError> {
+        self.lock().unwrap().read_block_into(id, buf)
+    }
+
+    fn read_verified(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        self.lock().unwrap().read_verified(id)
+    }
+
+    fn reencode_block(&self, id: BlockId) -> Result<(), QrfsError> {
+        self.lock().unwrap().reencode_block(id)
+    }
+
+    fn has_block(&self, id: BlockId) -> bool {
+        self.lock().unwrap().has_block(id)
+    }
+
+    fn host_bytes(&self, id: BlockId) -> u64 {
+        self.lock().unwrap().host_bytes(id)
+    }
+
+    fn health(&self) -> Option<Arc<HealthMonitor>> {
+        self.lock().unwrap().health()
+    }
+
+    fn block_freed(&self, id: BlockId) {
+        self.lock().unwrap().block_freed(id)
+    }
+
+    fn barrier(&self) -> Result<(), QrfsError> {
+        self.lock().unwrap().barrier()
+    }
+}
+
+// que se hizo con un qr escaneado (ver store_scanned)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanDecision {
+    // guardado con esta generacion
+    Saved(u64),
+    // ya estaba igual
+    Duplicate,
+    // de una generacion anterior a la guardada, no se guarda
+    Stale { scanned: u64, stored: u64 },
+    // misma generacion que la guardada pero otro contenido (dos hojas que
+    // salieron de copias distintas del disco): no se elige ninguna sola
+    Conflict(u64),
+}
+
+// candados por bloque repartidos en franjas: lecturas y escrituras de
+// bloques distintos van en paralelo (subidas del servidor, lecturas de
+// fuse), las del mismo bloque no ven un png a medio escribir ni pierden una
+// generacion
+const LOCK_STRIPES: usize = 64;
+
+struct BlockLocks(Vec<RwLock<()>>);
+
+impl BlockLocks {
+    fn new() -> Self {
+        Self((0..LOCK_STRIPES).map(|_| RwLock::new(())).collect())
+    }
+
+    // un hilo que entro en panico con el candado no deja el bloque tomado
+    fn read(&self, id: BlockId) -> RwLockReadGuard<'_, ()> {
+        self.0[id as usize % LOCK_STRIPES].read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self, id: BlockId) -> RwLockWriteGuard<'_, ()> {
+        self.0[id as usize % LOCK_STRIPES].write().unwrap_or_else(|e| e.into_inner())
+    }
 }
 
+// chunk de texto del png con la generacion del bloque (ver render_png)
+const GEN_CHUNK: &str = "qrfs-gen";
+
+// lee solo los chunks previos a la imagen, sin tocar el qr
+fn png_generation(path: &Path) -> Option<u64> {
+    let file = File::open(path).ok()?;
+    let reader = png::Decoder::new(io::BufReader::new(file)).read_info().ok()?;
+    let chunk = reader.info().uncompressed_latin1_text.iter().find(|c| c.keyword == GEN_CHUNK)?;
+    chunk.text.parse().ok()
+}
+
+// se comparte entre hilos sin envolverlo en un Mutex (ver BlockLocks)
 pub struct QrStorageManager {
     root_dir: PathBuf,
     block_size: usize,
     total_blocks: u32,
+    // ultima generacion conocida de cada bloque (evita decodificar el png en cada escritura)
+    generations: Mutex<HashMap<BlockId, u64>>,
+    // bloques que este proceso volvio a cero; se leen como ceros mientras
+    // siga sin aparecer su png (otro proceso o un escaneo puede traerlo)
+    zero_blocks: Mutex<HashSet<BlockId>>,
+    // qr ya decodificados, compartidos con otros procesos por un archivo en la carpeta
+    decode_cache: Option<DecodeCache>,
+    // bloques por subcarpeta (0 = sin subcarpetas), sale del superblock
+    shard_size: AtomicU32,
+    // con que codec se leen y escriben los qr, tambien del superblock
+    codec: AtomicU8,
+    // indice de bloques de datos por contenido, si el disco es cas
+    cas: Mutex<Option<CasIndex>>,
+    // como se dibujan los png que se escriban
+    qr_params: QrParams,
+    // un payload que no coincide con el largo declarado o no entra en el
+    // bloque es un error (si no, se corta o se completa con ceros)
+    strict_size: bool,
+    // instantaneas abiertas: antes de pisar un bloque se les copia lo que tenia
+    snapshots: Mutex<Vec<Weak<SnapshotBlocks>>>,
+    // qr que no se decodifican o no pasan el crc al leerlos
+    health: Arc<HealthMonitor>,
+    // lecturas y escrituras que fallan por un error pasajero del sistema de
+    // archivos (EINTR, un disco de red) se repiten; por defecto un intento
+    retry: RetryPolicy,
+    locks: BlockLocks,
 }
 
 impl QrStorageManager {
@@ -31,13 +221,166 @@ impl QrStorageManager {
             eprintln!("qrfs: warning: no se pudo crear el directorio raiz: {e}");
         }
 
+        let decode_cache = Some(DecodeCache::load(&root_dir));
+
+        // el esquema de subcarpetas lo dice el superblock; sin bloque 0 (mkfs,
+        // server antes de escanear) se arranca sin subcarpetas
+        let superblock = Self::probe_superblock(&root_dir).ok().flatten();
+        let shard_size = superblock.as_ref().map_or(0, |sb| sb.shard_size);
+        let codec = superblock.as_ref().map_or(DEFAULT_CODEC, |sb| sb.codec);
+        let cas = superblock
+            .filter(|sb| sb.content_addressed)
+            .and_then(|sb| match CasIndex::load(&root_dir, sb.data_block_start) {
+                Ok(index) => Some(index),
+                Err(e) => {
+                    eprintln!("qrfs: warning: {e}");
+                    None
+                }
+            });
+
         Self {
             root_dir,
             block_size,
             total_blocks,
+            generations: Mutex::new(HashMap::new()),
+            zero_blocks: Mutex::new(HashSet::new()),
+            decode_cache,
+            shard_size: AtomicU32::new(shard_size),
+            codec: AtomicU8::new(codec),
+            cas: Mutex::new(cas),
+            qr_params: QrParams::default(),
+            strict_size: false,
+            snapshots: Mutex::new(Vec::new()),
+            health: HealthMonitor::new(),
+            retry: RetryPolicy::once(),
+            locks: BlockLocks::new(),
         }
     }
 
+    // comparte el registro de fallas con otro storage (el espejo, el servidor)
+    pub fn with_health(mut self, health: Arc<HealthMonitor>) -> Self {
+        self.health = health;
+        self
+    }
+
+    // sin timeout: las operaciones de archivos no se pueden abandonar a mitad
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = RetryPolicy { timeout: None, ..policy };
+        self
+    }
+
+    // mkfs: repartir los pngs en subcarpetas de n bloques (000/000123.png)
+    pub fn with_shard_size(self, shard_size: u32) -> Self {
+        self.shard_size.store(shard_size, Ordering::Relaxed);
+        self
+    }
+
+    // mkfs: codec con el que se escriben los bloques (ver codec.rs)
+    pub fn with_codec(self, codec: u8) -> Self {
+        self.codec.store(codec, Ordering::Relaxed);
+        self
+    }
+
+    pub fn codec_id(&self) -> u8 {
+        self.codec.load(Ordering::Relaxed)
+    }
+
+    // tamano y correccion de errores de los qr que se escriban de aca en mas
+    // (los que ya estan se leen igual; reencode los pasa a la nueva forma)
+    pub fn with_qr_options(mut self, size: u32, ec_level: EcLevel) -> Self {
+        self.qr_params = QrParams { size, ec_level };
+        self
+    }
+
+    pub fn qr_params(&self) -> QrParams {
+        self.qr_params
+    }
+
+    // fsck: los tamaños que no coinciden se reportan en vez de ajustarse
+    pub fn with_strict_size(mut self, strict: bool) -> Self {
+        self.strict_size = strict;
+        self
+    }
+
+    pub fn shard_size(&self) -> u32 {
+        self.shard_size.load(Ordering::Relaxed)
+    }
+
+    pub fn is_content_addressed(&self) -> bool {
+        self.cas.lock().unwrap().is_some()
+    }
+
+    // bloques cas cuya imagen falta (recuperacion parcial)
+    pub fn missing_content(&self) -> Vec<BlockId> {
+        self.cas
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|index| index.missing())
+            .unwrap_or_default()
+    }
+
+    // sin cache todos los png se decodifican de verdad (para verificaciones)
+    pub fn with_decode_cache(mut self, enabled: bool) -> Self {
+        if !enabled {
+            self.decode_cache = None;
+        } else if self.decode_cache.is_none() {
+            self.decode_cache = Some(DecodeCache::load(&self.root_dir));
+        }
+        self
+    }
+
+    // lee el superblock (bloque 0) de una carpeta sin conocer su geometria
+    // Ok(None) si la carpeta todavia no tiene bloque 0
+    pub fn probe_superblock(root_dir: impl Into<PathBuf>) -> Result<Option<Superblock>, QrfsError> {
+        // el superblock cabe en el bloque por defecto, alcanza con mirar el bloque 0
+        let probe = Self {
+            root_dir: root_dir.into(),
+            block_size: BLOCK_SIZE,
+            total_blocks: 1,
+            generations: Mutex::new(HashMap::new()),
+            zero_blocks: Mutex::new(HashSet::new()),
+            decode_cache: None,
+            shard_size: AtomicU32::new(0),
+            codec: AtomicU8::new(DEFAULT_CODEC),
+            cas: Mutex::new(None),
+            qr_params: QrParams::default(),
+            strict_size: false,
+            snapshots: Mutex::new(Vec::new()),
+            health: HealthMonitor::new(),
+            retry: RetryPolicy::once(),
+            locks: BlockLocks::new(),
+        };
+
+        if !probe.block_path(0).exists() {
+            return Ok(None);
+        }
+
+        // todavia no se sabe el codec: se prueba con cada uno, el de siempre
+        // primero. la geometria y el reparto en carpetas estan en los
+        // primeros bytes: alcanza con el bloque 0 aunque el superblock ocupe mas
+        let mut read = probe.read_block(0);
+        for (id, _) in codecs().into_iter().skip(1) {
+            if read.is_ok() {
+                break;
+            }
+            probe.codec.store(id, Ordering::Relaxed);
+            read = probe.read_block(0);
+        }
+        let sb = decode_superblock(&read?)?;
+
+        if sb.magic != QRFS_MAGIC {
+            return Err(QrfsError::Other("firma invalida en bloque 0".into()));
+        }
+        // una version que no se puede leer no se confunde con un disco vacio
+        sb.version_access()?;
+        Ok(Some(sb))
+    }
+
+    pub fn root_dir(&self) -> &Path {
+        &self.root_dir
+    }
+
     pub fn init_empty_blocks(&self) -> Result<(), QrfsError> {
         let empty = vec![0u8; self.block_size];
         for id in 0..self.total_blocks {
@@ -47,8 +390,20 @@ impl QrStorageManager {
     }
 
     pub fn block_path(&self, id: BlockId) -> PathBuf {
+        if let Some(index) = self.cas.lock().unwrap().as_ref() {
+            if let Some(digest) = index.digest(id) {
+                return index.image_path(digest);
+            }
+        }
+
         let filename = format!("{:06}.png", id);
-        self.root_dir.join(filename)
+        let shard_size = self.shard_size();
+        if id == 0 || shard_size == 0 {
+            return self.root_dir.join(filename);
+        }
+        self.root_dir
+            .join(format!("{:03}", id / shard_size))
+            .join(filename)
     }
 
     fn check_range(&self, id: BlockId) -> Result<(), QrfsError> {
@@ -60,62 +415,511 @@ impl QrStorageManager {
         }
         Ok(())
     }
-}
 
-impl BlockStorage for QrStorageManager {
-    fn block_size(&self) -> usize {
-        self.block_size
+    // decodifica el qr del bloque y devuelve el texto crudo, None si el png no existe
+    fn decode_block_text(&self, id: BlockId) -> Result<Option<String>, QrfsError> {
+        let path = self.block_path(id);
+
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        if let Some(text) = self.decode_cache.as_ref().and_then(|c| c.get(&path)) {
+            return Ok(Some(text));
+        }
+
+        let img_dynamic = image::open(&path).map_err(|e| match e {
+            // un error pasajero se deja como io para que se pueda reintentar
+            image::ImageError::IoError(e) if is_transient_io(&e) => QrfsError::Io(e),
+            e => QrfsError::Other(format!("error abriendo imagen: {}", e)),
+        })?;
+        let content_string = self
+            .codec()?
+            .scan(&img_dynamic)
+            .map_err(|e| QrfsError::Other(format!("{} ({})", e, path.display())))?;
+
+        if let Some(cache) = &self.decode_cache {
+            cache.insert(&path, &content_string);
+        }
+
+        Ok(Some(content_string))
     }
 
-    fn total_blocks(&self) -> u32 {
-        self.total_blocks
+    // el codec del disco; uno que este programa no tiene registrado no se
+    // puede leer ni escribir
+    fn codec(&self) -> Result<Arc<dyn BlockCodec>, QrfsError> {
+        let id = self.codec_id();
+        codec_by_id(id).ok_or_else(|| {
+            QrfsError::Unimplemented(format!("el disco usa el codec {}, que no esta registrado", id))
+        })
     }
 
-    // leer bloque: decodifica qr desde png y extrae los datos binarios
-    fn read_block(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+    // el texto de un qr con el codec del disco, o en cualquiera de los
+    // formatos de siempre (lo que se escaneo de otro disco, el bloque 0)
+    fn decode_text(&self, text: &str) -> Result<DecodedBlock, QrfsError> {
+        self.codec()?.decode(text).or_else(|_| DecodedBlock::from_text(text))
+    }
+
+    // el bloque tal como esta en su imagen; None si no tiene
+    fn stored_block(&self, id: BlockId) -> Result<Option<DecodedBlock>, QrfsError> {
+        let Some(text) = self.decode_block_text(id)? else {
+            return Ok(None);
+        };
+        let decoded = self.decode_text(&text)?;
+        if decoded.block_id.is_some() {
+            self.remember_generation(id, decoded.generation);
+        }
+        Ok(Some(decoded))
+    }
+
+    // el sobre del qr: largo declarado, crc y que sea de este bloque
+    fn check_envelope(&self, id: BlockId) -> Result<(), QrfsError> {
+        if let Some(text) = self.decode_block_text(id)? {
+            let decoded = self.decode_text(&text)?;
+            self.check_size(id, &decoded)?;
+            if !decoded.checksum_ok {
+                return Err(QrfsError::Other(format!("checksum invalido en el bloque {}", id)));
+            }
+            // un png copiado o escaneado en el lugar equivocado decodifica bien
+            // pero es de otro bloque (en cas las imagenes se comparten)
+            if !decoded.is_block(id) && !self.is_content_addressed() {
+                return Err(QrfsError::Other(format!(
+                    "el qr del bloque {} es del bloque {}",
+                    id,
+                    decoded.block_id.unwrap_or(0)
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // payload tal como esta en el qr, sin cortar ni completar al tamaño del
+    // bloque; None si el bloque no tiene imagen (se lee como ceros)
+    pub fn read_payload(&self, id: BlockId) -> Result<Option<Vec<u8>>, QrfsError> {
         self.check_range(id)?;
-        let path = self.block_path(id);
+        let _lock = self.locks.read(id);
+        let Some(text) = self.decode_block_text(id)? else {
+            return Ok(None);
+        };
+        let decoded = self.decode_text(&text)?;
+        if !decoded.length_matches() {
+            return Err(self.size_error(id, &decoded));
+        }
+        Ok(Some(decoded.data))
+    }
 
-        if !path.exists() {
-            return Ok(vec![0u8; self.block_size]);
+    // el payload no tiene el largo declarado o no entra en el bloque
+    fn check_size(&self, id: BlockId, decoded: &DecodedBlock) -> Result<(), QrfsError> {
+        if decoded.length_matches() && decoded.data.len() <= self.block_size {
+            Ok(())
+        } else {
+            Err(self.size_error(id, decoded))
         }
+    }
 
-        let img_dynamic = image::open(&path)
-            .map_err(|e| QrfsError::Other(format!("error abriendo imagen: {}", e)))?;
-        let img_gray = img_dynamic.to_luma8();
+    fn size_error(&self, id: BlockId, decoded: &DecodedBlock) -> QrfsError {
+        match decoded.declared_len {
+            Some(len) if len != decoded.data.len() => QrfsError::Corrupt(format!(
+                "el qr del bloque {} declara {} bytes y trae {}",
+                id,
+                len,
+                decoded.data.len()
+            )),
+            _ => QrfsError::Corrupt(format!(
+                "el bloque {} trae {} bytes y el tamaño de bloque es {}",
+                id,
+                decoded.data.len(),
+                self.block_size
+            )),
+        }
+    }
 
-        let mut decoder = rqrr::PreparedImage::prepare(img_gray);
-        let grids = decoder.detect_grids();
-        if grids.is_empty() {
-            return Err(QrfsError::Other(format!(
-                "no se detecto qr en {}",
-                path.display()
-            )));
+    // lee el sobre guardado en un bloque
+    // None si el png no existe o si esta en formato base64 directo (sin metadata)
+    pub fn read_envelope(&self, id: BlockId) -> Result<Option<BlockEnvelope>, QrfsError> {
+        self.check_range(id)?;
+        let _lock = self.locks.read(id);
+
+        let envelope = self
+            .decode_block_text(id)?
+            .and_then(|text| BlockEnvelope::from_json(&text));
+
+        if let Some(env) = &envelope {
+            self.remember_generation(id, env.gen);
+        }
+        Ok(envelope)
+    }
+
+    // generacion del qr guardado; None si el bloque no tiene imagen, 0 si la
+    // imagen no se lee o no trae generacion
+    pub fn generation(&self, id: BlockId) -> Option<u64> {
+        let _lock = self.locks.read(id);
+        self.generation_locked(id)
+    }
+
+    fn generation_locked(&self, id: BlockId) -> Option<u64> {
+        if !self.has_block(id) {
+            return None;
+        }
+        Some(self.stored_generation(id))
+    }
+
+    // escribe con esa generacion salvo que la guardada sea mas nueva (o igual,
+    // sin replace_equal); false si no escribio. comparar y escribir van con
+    // el bloque tomado: dos copias que llegan a la vez no se pisan al reves
+    pub fn write_block_if_newer(
+        &self,
+        id: BlockId,
+        data: &[u8],
+        gen: u64,
+        replace_equal: bool,
+    ) -> Result<bool, QrfsError> {
+        self.check_range(id)?;
+        let _lock = self.locks.write(id);
+        // un bloque vuelto a cero tampoco acepta una copia vieja
+        match self.generation_locked(id).or_else(|| self.zeroed_generation(id)) {
+            Some(stored) if stored > gen || (stored == gen && !replace_equal) => Ok(false),
+            _ => self.write_locked(id, data, gen).map(|_| true),
+        }
+    }
+
+    // desde aca cada escritura copia primero el bloque viejo a la instantanea;
+    // se deja de copiar cuando se suelta el ultimo Arc
+    pub fn register_snapshot(&self, blocks: &Arc<SnapshotBlocks>) {
+        let mut snapshots = self.snapshots.lock().unwrap();
+        snapshots.retain(|s| s.strong_count() > 0);
+        snapshots.push(Arc::downgrade(blocks));
+    }
+
+    // un bloque que no se puede leer no se copia: la instantanea vera el nuevo.
+    // se llama con el bloque ya tomado para escribir
+    fn preserve_for_snapshots(&self, id: BlockId) {
+        let snapshots: Vec<_> = self.snapshots.lock().unwrap().iter().filter_map(Weak::upgrade).collect();
+        for blocks in snapshots {
+            if let Err(e) = blocks.preserve_with(id, || self.retry.run(|| self.read_block_once(id))) {
+                eprintln!("qrfs: warning: bloque {} no se pudo copiar a la instantanea: {e}", id);
+            }
+        }
+    }
+
+    // guarda un qr escaneado comparandolo con lo que ya hay: un re-escaneo no
+    // se reescribe y una hoja vieja no pisa una nueva. la generacion del qr
+    // se conserva para que las copias sean comparables. un png ilegible
+    // cuenta como ausente para poder repararlo
+    pub fn store_scanned(&self, block_id: BlockId, decoded: &DecodedBlock) -> Result<ScanDecision, QrfsError> {
+        self.check_range(block_id)?;
+        // dos copias del mismo qr escaneadas a la vez: una guarda, la otra es duplicada
+        let _lock = self.locks.write(block_id);
+        let stored = self.stored_block(block_id).ok().flatten().or_else(|| {
+            // sin png pero vuelto a cero: su generacion quedo en la lapida
+            self.zeroed_generation(block_id).map(|generation| DecodedBlock {
+                block_id: Some(block_id),
+                generation,
+                data: vec![0u8; decoded.data.len()],
+                declared_len: None,
+                envelope: decoded.envelope,
+                checksum_ok: true,
+            })
+        });
+
+        if let Some(stored) = &stored {
+            if stored.data == decoded.data {
+                return Ok(ScanDecision::Duplicate);
+            }
+            // gen 0 = qr sin generacion (formato viejo), no se puede comparar
+            if decoded.generation != 0 && decoded.generation < stored.generation {
+                return Ok(ScanDecision::Stale {
+                    scanned: decoded.generation,
+                    stored: stored.generation,
+                });
+            }
+            if decoded.generation != 0 && decoded.generation == stored.generation {
+                return Ok(ScanDecision::Conflict(stored.generation));
+            }
+        }
+
+        let gen = if decoded.generation != 0 {
+            decoded.generation
+        } else {
+            stored.map(|s| s.generation + 1).unwrap_or(1)
+        };
+        self.write_locked(block_id, &decoded.data, gen)?;
+        Ok(ScanDecision::Saved(gen))
+    }
+
+    // escribe un bloque con una generacion explicita (ej: al reingestar un qr escaneado)
+    pub fn write_block_with_gen(
+        &self,
+        id: BlockId,
+        data: &[u8],
+        gen: u64,
+    ) -> Result<(), QrfsError> {
+        self.check_range(id)?;
+        let _lock = self.locks.write(id);
+        self.write_locked(id, data, gen)
+    }
+
+    // write_block_with_gen con el bloque ya tomado para escribir
+    fn write_locked(&self, id: BlockId, data: &[u8], gen: u64) -> Result<(), QrfsError> {
+
+        if data.len() > self.block_size {
+            return Err(QrfsError::Other("datos muy grandes".to_string()));
         }
+        self.preserve_for_snapshots(id);
 
-        let (_meta, content_string) = grids[0]
-            .decode()
-            .map_err(|e| QrfsError::Other(format!("error decodificando qr (rqrr): {}", e)))?;
+        {
+            let mut cas = self.cas.lock().unwrap();
+            if let Some(index) = cas.as_mut().filter(|index| index.covers(id)) {
+                return self.write_content_block(index, id, data);
+            }
+        }
 
-        // intentar parsear como json con metadata, sino asumir base64 directo
-        let data = if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&content_string) {
-            if let Some(data_str) = parsed.get("data").and_then(|v| v.as_str()) {
-                general_purpose::STANDARD
-                    .decode(data_str)
-                    .map_err(|e| QrfsError::Other(format!("error decodificando base64 desde metadata: {}", e)))?
+        let path = self.block_path(id);
+
+        // un bloque en cero no se guarda: sin png se lee como ceros
+        if data.iter().all(|&b| b == 0) {
+            match fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+            if let Some(cache) = &self.decode_cache {
+                cache.remove(&path);
+            }
+            // sin png la generacion se perderia al reabrir y una hoja vieja
+            // volveria a guardarse: queda en una lapida. la primera (mkfs) no
+            // hace falta, cualquier hoja es igual o mas nueva
+            if gen > 1 {
+                fs::write(self.tombstone_path(id), gen.to_string())?;
             } else {
-                general_purpose::STANDARD
-                    .decode(&content_string)
-                    .map_err(|e| QrfsError::Other(format!("error decodificando base64: {}", e)))?
+                self.remove_tombstone(id)?;
             }
+            self.zero_blocks.lock().unwrap().insert(id);
+            self.remember_generation(id, gen);
+            return Ok(());
+        }
+
+        let text = self.render_png(id, gen, data, &path)?;
+        self.remove_tombstone(id)?;
+
+        // un superblock escrito (mkfs, ingesta del bloque 0) fija como se
+        // reparten y nombran los bloques que siguen
+        if id == 0 {
+            if let Ok(sb) = decode_superblock(data) {
+                if sb.is_valid() {
+                    self.shard_size.store(sb.shard_size, Ordering::Relaxed);
+                    self.codec.store(sb.codec, Ordering::Relaxed);
+                    let mut cas = self.cas.lock().unwrap();
+                    if sb.content_addressed && cas.is_none() {
+                        *cas = Some(CasIndex::load(&self.root_dir, sb.data_block_start)?);
+                    }
+                }
+            }
+        }
+        // lo que se acaba de codificar no hace falta volver a decodificarlo
+        if let Some(cache) = &self.decode_cache {
+            cache.insert(&path, &text);
+        }
+
+        self.zero_blocks.lock().unwrap().remove(&id);
+        self.remember_generation(id, gen);
+        Ok(())
+    }
+
+    // bloque de datos en modo cas: la imagen se nombra por el digest del
+    // contenido y solo se genera si no existe (otro bloque igual ya la tiene)
+    fn write_content_block(
+        &self,
+        index: &mut CasIndex,
+        id: BlockId,
+        data: &[u8],
+    ) -> Result<(), QrfsError> {
+        let mut block = data.to_vec();
+        block.resize(self.block_size, 0);
+
+        let unused = if block.iter().all(|&b| b == 0) {
+            self.zero_blocks.lock().unwrap().insert(id);
+            index.set(id, None)?
         } else {
-            general_purpose::STANDARD
-                .decode(&content_string)
-                .map_err(|e| QrfsError::Other(format!("error decodificando base64: {}", e)))?
+            let digest = block_digest(&block);
+            let path = index.image_path(&digest);
+            if !path.exists() {
+                // el id del sobre no significa nada aca: la imagen es compartida
+                let text = self.render_png(0, 0, &block, &path)?;
+                if let Some(cache) = &self.decode_cache {
+                    cache.insert(&path, &text);
+                }
+            }
+            self.zero_blocks.lock().unwrap().remove(&id);
+            index.set(id, Some(digest))?
         };
 
+        // nadie mas usa la imagen vieja
+        if let Some(old) = unused {
+            let path = index.image_path(&old);
+            match fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+            if let Some(cache) = &self.decode_cache {
+                cache.remove(&path);
+            }
+        }
+        Ok(())
+    }
+
+    // copia el png de un bloque a dest; si el bloque esta en cero genera su qr
+    pub fn export_block(&self, id: BlockId, dest: &Path) -> Result<(), QrfsError> {
+        self.check_range(id)?;
+        let _lock = self.locks.read(id);
+
+        let path = self.block_path(id);
+        if path.exists() {
+            fs::copy(&path, dest)?;
+            return Ok(());
+        }
+
+        let gen = self.generations.lock().unwrap().get(&id).copied().unwrap_or(0);
+        self.render_png(id, gen, &vec![0u8; self.block_size], dest)
+            .map(|_| ())
+    }
+
+    // como export_block pero en svg, dibujado con los mismos parametros que los png
+    pub fn export_block_svg(&self, id: BlockId, dest: &Path) -> Result<(), QrfsError> {
+        let data = self.read_block(id)?;
+        let gen = match self.read_envelope(id)? {
+            Some(envelope) => envelope.gen,
+            None => self.generations.lock().unwrap().get(&id).copied().unwrap_or(0),
+        };
+        fs::write(dest, encode_block_svg(id, gen, &data, self.qr_params)?)?;
+        Ok(())
+    }
+
+    // codifica el bloque con el codec del disco, lo guarda en path y devuelve el texto
+    fn render_png(&self, id: BlockId, gen: u64, data: &[u8], path: &Path) -> Result<String, QrfsError> {
+        let codec = self.codec()?;
+        let metadata = codec.encode(id, gen, data);
+
+        let image = codec.draw(&metadata, self.qr_params)?;
+
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+
+        // compresion y filtro fijos: el mismo qr da siempre el mismo png
+        // (discos reproducibles, ver Superblock::deterministic). la generacion
+        // va tambien en un chunk de texto para leerla sin decodificar el qr
+        let png_error = |e: png::EncodingError| QrfsError::Other(format!("error guardando imagen: {}", e));
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, image.width(), image.height());
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_compression(png::Compression::Balanced);
+        encoder.set_filter(png::Filter::Adaptive);
+        encoder.add_text_chunk(GEN_CHUNK.to_string(), gen.to_string()).map_err(png_error)?;
+        let mut writer = encoder.write_header().map_err(png_error)?;
+        writer.write_image_data(image.as_raw()).map_err(png_error)?;
+        writer.finish().map_err(png_error)?;
+        fs::write(path, png)?;
+
+        Ok(metadata)
+    }
+
+    // generacion que le toca a la proxima escritura del bloque
+    fn next_generation(&self, id: BlockId) -> u64 {
+        self.stored_generation(id) + 1
+    }
+
+    // generacion guardada sin decodificar el qr: la cacheada o la del chunk
+    // de texto del png. solo los png de antes del chunk se decodifican; uno
+    // ilegible o sin metadata cuenta como generacion 0
+    fn stored_generation(&self, id: BlockId) -> u64 {
+        if let Some(gen) = self.generations.lock().unwrap().get(&id) {
+            return *gen;
+        }
+        if let Some(gen) = png_generation(&self.block_path(id)).or_else(|| self.zeroed_generation(id)) {
+            self.remember_generation(id, gen);
+            return gen;
+        }
+        self.stored_block(id).ok().flatten().map_or(0, |block| block.generation)
+    }
+
+    fn remember_generation(&self, id: BlockId, gen: u64) {
+        self.generations.lock().unwrap().insert(id, gen);
+    }
+
+    // lapida de un bloque vuelto a cero: su generacion, al lado de donde
+    // iria el png (000123.gen)
+    fn tombstone_path(&self, id: BlockId) -> PathBuf {
+        self.block_path(id).with_extension("gen")
+    }
+
+    fn zeroed_generation(&self, id: BlockId) -> Option<u64> {
+        fs::read_to_string(self.tombstone_path(id)).ok()?.trim().parse().ok()
+    }
+
+    fn remove_tombstone(&self, id: BlockId) -> Result<(), QrfsError> {
+        match fs::remove_file(self.tombstone_path(id)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // una lectura, sin reintentos
+    fn read_block_once(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        self.check_range(id)?;
+
+        // el png pudo llegar despues de que este proceso lo puso en cero
+        if self.zero_blocks.lock().unwrap().contains(&id) {
+            if !self.block_path(id).exists() {
+                return Ok(vec![0u8; self.block_size]);
+            }
+            self.zero_blocks.lock().unwrap().remove(&id);
+        }
+
+        // en modo cas una imagen que falta es un error, no un bloque en cero
+        let digest = self
+            .cas
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|index| index.digest(id).map(|d| d.to_string()));
+        if let Some(digest) = &digest {
+            if !self.block_path(id).exists() {
+                return Err(QrfsError::Other(format!(
+                    "falta la imagen {} del bloque {}",
+                    digest, id
+                )));
+            }
+        }
+
+        // las fallas se anotan aca y no en decode_block_text: next_generation
+        // tambien decodifica, y un png ilegible que se va a reescribir no es
+        // una lectura fallida
+        let text = self.decode_block_text(id).inspect_err(|e| {
+            self.health.record(id, HealthEvent::DecodeFailure, &e.to_string());
+        })?;
+        let content_string = match text {
+            Some(text) => text,
+            // un png que falta no se recuerda: puede llegar en cualquier momento
+            None => return Ok(vec![0u8; self.block_size]),
+        };
+
+        // json con metadata o base64 directo
+        let decoded = self.decode_text(&content_string).inspect_err(|e| {
+            self.health.record(id, HealthEvent::DecodeFailure, &e.to_string());
+        })?;
+        if decoded.block_id.is_some() {
+            self.remember_generation(id, decoded.generation);
+        }
+        if self.strict_size {
+            self.check_size(id, &decoded)?;
+        }
+
         // ajustar tamaño del resultado al block_size esperado
-        let mut result = data;
+        let mut result = decoded.data;
         if result.len() > self.block_size {
             result.truncate(self.block_size);
         }
@@ -123,41 +927,102 @@ impl BlockStorage for QrStorageManager {
             result.resize(self.block_size, 0);
         }
 
+        // el nombre de la imagen es el digest: verifica el contenido
+        if let Some(digest) = digest {
+            if block_digest(&result) != digest {
+                let e = QrfsError::Other(format!("el bloque {} no coincide con su digest {}", id, digest));
+                self.health.record(id, HealthEvent::ChecksumMismatch, &e.to_string());
+                return Err(e);
+            }
+        }
+
         Ok(result)
     }
+}
 
-    // escribir bloque: codifica datos binarios en qr y guarda como png
-    fn write_block(&self, id: BlockId, data: &[u8]) -> Result<(), QrfsError> {
-        self.check_range(id)?;
+impl BlockStorage for QrStorageManager {
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
 
-        if data.len() > self.block_size {
-            return Err(QrfsError::Other(format!("datos muy grandes")));
+    // un bloque en cero tampoco tiene png: sin cas no se distingue de uno
+    // que falta escanear. no mira zero_blocks, puede haber llegado despues
+    fn has_block(&self, id: BlockId) -> bool {
+        if id >= self.total_blocks {
+            return false;
         }
+        {
+            let cas = self.cas.lock().unwrap();
+            if let Some(index) = cas.as_ref().filter(|index| index.covers(id)) {
+                return index.digest(id).is_none_or(|digest| index.image_path(digest).exists());
+            }
+        }
+        self.block_path(id).exists()
+    }
+
+    // con cas varios bloques iguales comparten imagen: cada uno la cuenta entera
+    fn host_bytes(&self, id: BlockId) -> u64 {
+        fs::metadata(self.block_path(id)).map(|meta| meta.len()).unwrap_or(0)
+    }
+
+    fn total_blocks(&self) -> u32 {
+        self.total_blocks
+    }
 
-        let b64_string = general_purpose::STANDARD.encode(data);
+    // leer bloque: decodifica qr desde png y extrae los datos binarios
+    fn read_block(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        let _lock = self.locks.read(id);
+        self.retry.run(|| self.read_block_once(id))
+    }
 
-        // formato json: {"block_id":X,"data":"base64..."}
-        let metadata = format!(r#"{{"block_id":{},"data":"{}"}}"#, id, b64_string);
+    // escribir bloque: codifica datos binarios en qr y guarda como png
+    fn write_block(&self, id: BlockId, data: &[u8]) -> Result<(), QrfsError> {
+        self.check_range(id)?;
+        let _lock = self.locks.write(id);
+        let gen = self.next_generation(id);
+        self.retry.run(|| self.write_locked(id, data, gen))
+    }
 
-        let code = QrCode::new(metadata)
-            .map_err(|e| QrfsError::Other(format!("error generando qr: {}", e)))?;
+    // ademas de leer, compara el payload con el crc y el largo del sobre del qr
+    fn read_verified(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        let _lock = self.locks.read(id);
+        let data = self.retry.run(|| self.read_block_once(id))?;
+        self.check_envelope(id).inspect_err(|e| {
+            self.health.record(id, HealthEvent::ChecksumMismatch, &e.to_string());
+        })?;
+        Ok(data)
+    }
 
-        let image = code
-            .render::<Luma<u8>>()
-            .min_dimensions(200, 200)
-            .max_dimensions(200, 200)
-            .build();
+    fn health(&self) -> Option<Arc<HealthMonitor>> {
+        Some(self.health.clone())
+    }
 
-        let path = self.block_path(id);
-        if let Some(parent) = path.parent() {
-            let _ = fs::create_dir_all(parent);
-        }
+    // genera el png de nuevo aunque el contenido no cambie: nueva generacion
+    // y checksum; en modo cas se reemplaza la imagen compartida
+    fn reencode_block(&self, id: BlockId) -> Result<(), QrfsError> {
+        self.check_range(id)?;
+        let _lock = self.locks.write(id);
+        let data = self.retry.run(|| self.read_block_once(id))?;
 
-        image
-            .save(&path)
-            .map_err(|e| QrfsError::Other(format!("error guardando imagen: {}", e)))?;
+        let cas_path = {
+            let cas = self.cas.lock().unwrap();
+            cas.as_ref()
+                .and_then(|index| index.digest(id).map(|d| index.image_path(d)))
+        };
+        if let Some(path) = cas_path {
+            let text = self.render_png(0, 0, &data, &path)?;
+            if let Some(cache) = &self.decode_cache {
+                cache.insert(&path, &text);
+            }
+            return Ok(());
+        }
 
-        Ok(())
+        // un bloque en cero no tiene png que regenerar
+        if !self.block_path(id).exists() {
+            return Ok(());
+        }
+        let gen = self.next_generation(id);
+        self.retry.run(|| self.write_locked(id, &data, gen))
     }
 }
 
@@ -206,4 +1071,533 @@ impl BlockStorage for InMemoryBlockStorage {
         memory[offset..offset + len].copy_from_slice(&data[..len]);
         Ok(())
     }
-}
\ No newline at end of file
+}
+// candado de un disco entre procesos que lo escriben (montaje, servidor)
+// es un candado del sistema sobre un archivo al lado de los bloques: .qrfs.lock dentro de la
+// carpeta de qr o disco.img.lock junto al contenedor. el sistema lo suelta
+// solo si el proceso muere, asi que el archivo puede quedar sin problema.
+// exclusivo por defecto; con --force-shared se toma compartido y solo convive
+// con otros compartidos, que revalidan el superblock antes de escribir
+#[derive(Debug)]
+pub struct DiskLock {
+    path: PathBuf,
+    shared: bool,
+    // mientras el archivo siga abierto el candado sigue tomado
+    _file: File,
+}
+
+pub const LOCK_FILE: &str = ".qrfs.lock";
+
+// marca de una carpeta en modo portador (stego.rs, feature "stego"): los
+// bloques estan escondidos en imagenes de portada en vez de qr
+pub const CARRIER_FILE: &str = ".qrfs_carrier";
+
+impl DiskLock {
+    pub fn acquire(disk: impl AsRef<Path>, owner: &str, shared: bool) -> Result<Self, QrfsError> {
+        let disk = disk.as_ref();
+        let path = if disk.is_file() {
+            let mut name = disk.as_os_str().to_owned();
+            name.push(".lock");
+            PathBuf::from(name)
+        } else {
+            disk.join(LOCK_FILE)
+        };
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        // flock en unix, LockFileEx en windows
+        let locked = if shared { file.try_lock_shared() } else { file.try_lock() };
+        if let Err(e) = locked {
+            if let TryLockError::Error(err) = e {
+                return Err(err.into());
+            }
+            let mut holder = String::new();
+            let _ = file.read_to_string(&mut holder);
+            let holder = holder.trim();
+            let holder = if holder.is_empty() { "otro proceso (compartido)" } else { holder };
+            return Err(QrfsError::Other(format!(
+                "{} esta en uso por {}; use --force-shared en los dos para compartirlo",
+                disk.display(),
+                holder
+            )));
+        }
+
+        // quien lo tiene, para el mensaje del que llegue despues (los
+        // compartidos no lo pisan: puede haber varios)
+        if !shared {
+            file.set_len(0)?;
+            file.write_all(format!("{} (pid {})\n", owner, std::process::id()).as_bytes())?;
+        }
+        Ok(Self { path, shared, _file: file })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_shared(&self) -> bool {
+        self.shared
+    }
+}
+
+// contenedor: todos los bloques seguidos en un solo archivo (volcado crudo)
+// las lecturas salen del archivo mapeado en memoria, sin copiar ni decodificar
+pub struct ContainerStorage {
+    path: PathBuf,
+    file: File,
+    map: Mmap,
+    block_size: usize,
+    total_blocks: u32,
+}
+
+impl ContainerStorage {
+    // crea (o reemplaza) un contenedor lleno de ceros
+    pub fn create(
+        path: impl Into<PathBuf>,
+        block_size: usize,
+        total_blocks: u32,
+    ) -> Result<Self, QrfsError> {
+        let path = path.into();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        file.set_len(total_blocks as u64 * block_size as u64)?;
+        Self::from_file(path, file, block_size)
+    }
+
+    // abre un contenedor existente; la cantidad de bloques sale del tamaño
+    pub fn open(path: impl Into<PathBuf>, block_size: usize) -> Result<Self, QrfsError> {
+        let path = path.into();
+        let file = OpenOptions::new().read(true).write(true).open(&path)?;
+        Self::from_file(path, file, block_size)
+    }
+
+    // abre un contenedor con el tamaño de bloque que dice su superblock; sin
+    // firma qrfs (sin formatear, roto) con el de por defecto, para que fsck
+    // o quien lo abra diga que le pasa
+    pub fn open_disk(path: impl Into<PathBuf>) -> Result<Self, QrfsError> {
+        let path = path.into();
+        let mut head = Vec::new();
+        File::open(&path)?
+            .take(SUPERBLOCK_RESERVED_BYTES as u64)
+            .read_to_end(&mut head)?;
+        let block_size = match decode_superblock(&head) {
+            Ok(sb) if sb.magic == QRFS_MAGIC && sb.block_size > 0 => sb.block_size as usize,
+            _ => BLOCK_SIZE,
+        };
+        Self::open(path, block_size)
+    }
+
+    fn from_file(path: PathBuf, file: File, block_size: usize) -> Result<Self, QrfsError> {
+        let len = file.metadata()?.len();
+        if len == 0 || len % block_size as u64 != 0 {
+            return Err(QrfsError::Other(format!(
+                "{}: tamaño {} no es multiplo del bloque ({})",
+                path.display(),
+                len,
+                block_size
+            )));
+        }
+
+        // las escrituras van por pwrite al mismo archivo; el mapa compartido las ve
+        let map = unsafe { Mmap::map(&file)? };
+
+        Ok(Self {
+            total_blocks: (len / block_size as u64) as u32,
+            path,
+            file,
+            map,
+            block_size,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // vista directa del bloque dentro del mapa, sin reservar memoria
+    pub fn block(&self, id: BlockId) -> Result<&[u8], QrfsError> {
+        if id >= self.total_blocks {
+            return Err(QrfsError::Other(format!(
+                "block id {id} fuera de rango 0..{}",
+                self.total_blocks - 1
+            )));
+        }
+        let offset = id as usize * self.block_size;
+        Ok(&self.map[offset..offset + self.block_size])
+    }
+}
+
+impl BlockStorage for ContainerStorage {
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn total_blocks(&self) -> u32 {
+        self.total_blocks
+    }
+
+    fn read_block(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        Ok(self.block(id)?.to_vec())
+    }
+
+    fn read_block_into(&self, id: BlockId, buf: &mut [u8]) -> Result<(), QrfsError> {
+        let block = self.block(id)?;
+        let len = block.len().min(buf.len());
+        buf[..len].copy_from_slice(&block[..len]);
+        buf[len..].fill(0);
+        Ok(())
+    }
+
+    fn write_block(&self, id: BlockId, data: &[u8]) -> Result<(), QrfsError> {
+        self.block(id)?;
+        if data.len() > self.block_size {
+            return Err(QrfsError::Other("datos muy grandes".to_string()));
+        }
+
+        // el bloque completo, rellenado con ceros, igual que los qr
+        let mut chunk = vec![0u8; self.block_size];
+        chunk[..data.len()].copy_from_slice(data);
+        write_all_at(&self.file, &chunk, id as u64 * self.block_size as u64)?;
+        Ok(())
+    }
+
+    // pwrite solo llega al cache del kernel, que lo baja en cualquier orden
+    fn barrier(&self) -> Result<(), QrfsError> {
+        self.file.sync_data()?;
+        Ok(())
+    }
+}
+
+// pwrite: no mueve el cursor, escrituras de bloques distintos van a la vez
+#[cfg(unix)]
+fn write_all_at(file: &File, data: &[u8], offset: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.write_all_at(data, offset)
+}
+
+// sin pwrite: seek y write seguidos, de a una escritura por vez para que
+// otra no mueva el cursor en el medio
+#[cfg(not(unix))]
+fn write_all_at(file: &File, data: &[u8], offset: u64) -> io::Result<()> {
+    use std::io::{Seek, SeekFrom};
+    static CURSOR: Mutex<()> = Mutex::new(());
+    let _cursor = CURSOR.lock().unwrap();
+    let mut file = file;
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qr::encode_text;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let unique = format!("qrfs_storage_{}_{}", name, std::process::id());
+        let dir = std::env::temp_dir().join(unique);
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn write_block_bumps_generation_and_stores_checksum() {
+        let dir = temp_dir("gen");
+        let storage = QrStorageManager::new(&dir, 128, 4);
+
+        storage.write_block(1, b"primera").unwrap();
+        storage.write_block(1, b"segunda").unwrap();
+
+        // una instancia nueva no tiene cache y debe leer la generacion del png
+        let fresh = QrStorageManager::new(&dir, 128, 4);
+        let env = fresh.read_envelope(1).unwrap().unwrap();
+        assert_eq!(env.block_id, 1);
+        assert_eq!(env.gen, 2);
+        assert!(env.checksum_matches(b"segunda"));
+
+        // la generacion sale del chunk del png, sin decodificar el qr
+        assert_eq!(png_generation(&fresh.block_path(1)), Some(2));
+        fresh.write_block(1, b"tercera").unwrap();
+        assert_eq!(fresh.read_envelope(1).unwrap().unwrap().gen, 3);
+        assert!(fresh.read_envelope(2).unwrap().is_none());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn threads_share_the_manager_without_an_outer_mutex() {
+        let dir = temp_dir("threads");
+        let storage = Arc::new(QrStorageManager::new(&dir, 128, 16));
+        let scanned = DecodedBlock {
+            block_id: Some(0),
+            generation: 5,
+            data: b"la misma hoja".to_vec(),
+            declared_len: None,
+            envelope: crate::qr::EnvelopeVersion::Raw,
+            checksum_ok: true,
+        };
+
+        let decisions: Vec<ScanDecision> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (1..9u32)
+                .map(|id| {
+                    let (storage, scanned) = (&storage, &scanned);
+                    scope.spawn(move || {
+                        // cada hilo con su bloque, y todos con la misma hoja del 0
+                        let data = vec![id as u8; 40];
+                        storage.write_block(id, &data).unwrap();
+                        assert_eq!(storage.read_block(id).unwrap()[..40], data[..]);
+                        storage.store_scanned(0, scanned).unwrap()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        assert_eq!(decisions.iter().filter(|d| **d == ScanDecision::Saved(5)).count(), 1);
+        assert_eq!(decisions.iter().filter(|d| **d == ScanDecision::Duplicate).count(), 7);
+        assert_eq!(storage.generation(0), Some(5));
+        // otra hoja con la misma generacion y otro contenido no se elige sola
+        let other = DecodedBlock { data: b"otra hoja".to_vec(), ..scanned.clone() };
+        assert_eq!(storage.store_scanned(0, &other).unwrap(), ScanDecision::Conflict(5));
+        assert_eq!(storage.read_block(0).unwrap()[..13], b"la misma hoja"[..]);
+        // una copia con la misma generacion no pisa salvo que se pida
+        assert!(!storage.write_block_if_newer(0, b"otra", 5, false).unwrap());
+        assert!(storage.write_block_if_newer(0, b"otra", 6, false).unwrap());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn probe_superblock_reads_geometry_from_block_zero() {
+        let dir = temp_dir("probe");
+        assert!(QrStorageManager::probe_superblock(&dir).unwrap().is_none());
+
+        let sb = Superblock::new(32, 16);
+        let storage = QrStorageManager::new(&dir, BLOCK_SIZE, sb.total_blocks);
+        storage
+            .write_block(0, &bincode::serialize(&sb).unwrap())
+            .unwrap();
+
+        let probed = QrStorageManager::probe_superblock(&dir).unwrap().unwrap();
+        assert_eq!(probed.total_blocks, 32);
+        assert_eq!(probed.inode_count, 16);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn sharded_blocks_go_to_subfolders_named_in_the_superblock() {
+        let dir = temp_dir("shard");
+        let mut sb = Superblock::new(32, 16);
+        sb.shard_size = 10;
+        let storage = QrStorageManager::new(&dir, BLOCK_SIZE, sb.total_blocks);
+        storage
+            .write_block(0, &bincode::serialize(&sb).unwrap())
+            .unwrap();
+        storage.write_block(23, b"datos").unwrap();
+
+        assert!(dir.join("000000.png").exists());
+        assert!(dir.join("002").join("000023.png").exists());
+
+        // otra herramienta que abre la carpeta resuelve la misma ruta
+        let fresh = QrStorageManager::new(&dir, BLOCK_SIZE, sb.total_blocks);
+        assert_eq!(fresh.shard_size(), 10);
+        assert_eq!(&fresh.read_block(23).unwrap()[..5], b"datos");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn content_addressed_blocks_share_images_and_verify_by_name() {
+        let dir = temp_dir("cas");
+        let mut sb = Superblock::new(64, 16);
+        sb.content_addressed = true;
+        let data_start = sb.data_block_start;
+        let storage = QrStorageManager::new(&dir, BLOCK_SIZE, sb.total_blocks);
+        storage
+            .write_block(0, &bincode::serialize(&sb).unwrap())
+            .unwrap();
+        assert!(storage.is_content_addressed());
+
+        // dos bloques iguales, una sola imagen
+        storage.write_block(data_start, b"igual").unwrap();
+        storage.write_block(data_start + 1, b"igual").unwrap();
+        storage.write_block(data_start + 2, b"otro").unwrap();
+        assert_eq!(fs::read_dir(dir.join(crate::cas::CAS_DIR)).unwrap().count(), 2);
+        assert_eq!(storage.block_path(data_start), storage.block_path(data_start + 1));
+
+        // la metadata sigue con nombre fijo
+        assert!(dir.join("000000.png").exists());
+
+        // si se pierde una imagen, solo fallan los bloques que la usaban
+        fs::remove_file(storage.block_path(data_start + 2)).unwrap();
+        let fresh = QrStorageManager::new(&dir, BLOCK_SIZE, sb.total_blocks);
+        assert_eq!(fresh.missing_content(), vec![data_start + 2]);
+        assert_eq!(&fresh.read_block(data_start + 1).unwrap()[..5], b"igual");
+        assert!(fresh.read_block(data_start + 2).is_err());
+
+        // reescribir un bloque compartido no borra la imagen del otro
+        fresh.write_block(data_start, b"nuevo").unwrap();
+        assert_eq!(&fresh.read_block(data_start + 1).unwrap()[..5], b"igual");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reencode_bumps_generation_and_keeps_data() {
+        let dir = temp_dir("reencode");
+        let storage = QrStorageManager::new(&dir, 128, 4);
+        storage.write_block(1, b"datos").unwrap();
+        storage.reencode_block(1).unwrap();
+        storage.reencode_block(2).unwrap();
+
+        let fresh = QrStorageManager::new(&dir, 128, 4).with_decode_cache(false);
+        let env = fresh.read_envelope(1).unwrap().unwrap();
+        assert_eq!(env.gen, 2);
+        assert_eq!(&fresh.read_block(1).unwrap()[..5], b"datos");
+        assert!(!fresh.block_path(2).exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn zero_blocks_are_not_stored_as_png() {
+        let dir = temp_dir("zero");
+        let storage = QrStorageManager::new(&dir, 128, 4);
+
+        storage.init_empty_blocks().unwrap();
+        assert!(!storage.block_path(0).exists());
+        assert_eq!(storage.read_block(2).unwrap(), vec![0u8; 128]);
+
+        storage.write_block(1, b"datos").unwrap();
+        assert!(storage.block_path(1).exists());
+
+        // volver a cero borra el png, una instancia nueva lee ceros
+        storage.write_block(1, &[0u8; 128]).unwrap();
+        assert!(!storage.block_path(1).exists());
+        let fresh = QrStorageManager::new(&dir, 128, 4);
+        assert_eq!(fresh.read_block(1).unwrap(), vec![0u8; 128]);
+
+        // exportar un bloque en cero genera un qr legible
+        let exported = dir.join("exportado.png");
+        storage.export_block(3, &exported).unwrap();
+        assert!(exported.exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_zeroed_block_keeps_its_generation_after_reopening() {
+        let dir = temp_dir("lapida");
+        let storage = QrStorageManager::new(&dir, 128, 4).with_decode_cache(false);
+        storage.write_block(1, b"v1").unwrap();
+        storage.write_block(1, b"v2").unwrap();
+        let old_sheet = storage.stored_block(1).unwrap().unwrap();
+        storage.write_block(1, &[0u8; 128]).unwrap();
+        assert!(!storage.block_path(1).exists());
+
+        // la hoja impresa antes de ponerlo en cero es vieja, tambien al reabrir
+        let fresh = QrStorageManager::new(&dir, 128, 4).with_decode_cache(false);
+        assert!(matches!(fresh.store_scanned(1, &old_sheet).unwrap(), ScanDecision::Stale { scanned: 2, stored: 3 }));
+        assert_eq!(fresh.read_block(1).unwrap(), vec![0u8; 128]);
+
+        // el png que escribe otro proceso se lee aunque este lo tuviera en cero
+        let other = QrStorageManager::new(&dir, 128, 4).with_decode_cache(false);
+        other.write_block(1, b"v4").unwrap();
+        assert_eq!(&storage.read_block(1).unwrap()[..2], b"v4");
+        assert!(!storage.tombstone_path(1).exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn strict_mode_reports_payloads_that_do_not_fit() {
+        let dir = temp_dir("strict");
+        let storage = QrStorageManager::new(&dir, 128, 4).with_decode_cache(false);
+        storage.write_block(1, b"corto").unwrap();
+        assert_eq!(storage.read_payload(1).unwrap().unwrap(), b"corto");
+        assert_eq!(storage.read_payload(3).unwrap(), None);
+
+        // un sobre que declara mas bytes de los que trae y uno que no entra en el bloque
+        let mut forged = BlockEnvelope::new(2, 1, b"cortado");
+        forged.len = Some(128);
+        encode_text(&forged.to_json(), QrParams::default())
+            .unwrap()
+            .save(storage.block_path(2))
+            .unwrap();
+        let oversized = BlockEnvelope::new(3, 1, &[7u8; 150]);
+        encode_text(&oversized.to_json(), QrParams { size: 300, ..QrParams::default() })
+            .unwrap()
+            .save(storage.block_path(3))
+            .unwrap();
+
+        // sin modo estricto se completa o se corta como antes
+        assert_eq!(&storage.read_block(2).unwrap()[..7], b"cortado");
+        assert_eq!(storage.read_block(3).unwrap(), vec![7u8; 128]);
+        assert!(storage.read_verified(2).is_err());
+        assert!(storage.read_payload(2).is_err());
+
+        let strict = QrStorageManager::new(&dir, 128, 4).with_decode_cache(false).with_strict_size(true);
+        assert_eq!(&strict.read_block(1).unwrap()[..5], b"corto");
+        assert!(matches!(strict.read_block(2), Err(QrfsError::Corrupt(_))));
+        assert!(matches!(strict.read_block(3), Err(QrfsError::Corrupt(_))));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn container_reads_come_from_the_mapped_file() {
+        let dir = temp_dir("container");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("disco.img");
+
+        let storage = ContainerStorage::create(&path, 128, 8).unwrap();
+        storage.write_block(3, b"contenedor").unwrap();
+        assert_eq!(&storage.block(3).unwrap()[..10], b"contenedor");
+
+        let reopened = ContainerStorage::open(&path, 128).unwrap();
+        assert_eq!(reopened.total_blocks(), 8);
+        let mut buf = vec![0xffu8; 128];
+        reopened.read_block_into(3, &mut buf).unwrap();
+        assert_eq!(&buf[..10], b"contenedor");
+        assert!(buf[10..].iter().all(|&b| b == 0));
+        assert!(reopened.read_block(8).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn disk_lock_is_exclusive_unless_both_sides_share() {
+        let dir = temp_dir("lock");
+        fs::create_dir_all(&dir).unwrap();
+
+        let first = DiskLock::acquire(&dir, "mount.qrfs", false).unwrap();
+        assert_eq!(first.path(), dir.join(LOCK_FILE));
+        let err = DiskLock::acquire(&dir, "server.qrfs", false).unwrap_err().to_string();
+        assert!(err.contains("mount.qrfs"), "{}", err);
+        assert!(DiskLock::acquire(&dir, "server.qrfs", true).is_err());
+        drop(first);
+
+        // compartido convive con compartido, no con exclusivo
+        let a = DiskLock::acquire(&dir, "mount.qrfs", true).unwrap();
+        let b = DiskLock::acquire(&dir, "server.qrfs", true).unwrap();
+        assert!(a.is_shared() && b.is_shared());
+        assert!(DiskLock::acquire(&dir, "fsck", false).is_err());
+        drop((a, b));
+
+        // el contenedor lleva el candado al lado del archivo
+        let path = dir.join("disco.img");
+        ContainerStorage::create(&path, 128, 8).unwrap();
+        let lock = DiskLock::acquire(&path, "mount.qrfs", false).unwrap();
+        assert_eq!(lock.path(), dir.join("disco.img.lock"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,168 @@
+// sincronizacion entre dos servidores con copias del mismo disco
+// cada servidor ingesta sus propios lotes de escaneos; para juntarlos uno le
+// pide al otro la lista de bloques que tiene (GET /sync/manifest, con la
+// generacion de cada qr) y se baja los que le faltan o tiene mas viejos
+// (GET /sync/block/{id}). es solo pull: para quedar iguales cada uno hace
+// pull del otro. un bloque con la misma generacion en los dos lados no se
+// toca aunque el contenido difiera (lo resuelve fsck, no el sync)
+
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+
+use crate::disk::BlockId;
+use crate::errors::QrfsError;
+use crate::http::fetch_url;
+use crate::storage::{BlockStorage, QrStorageManager};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncEntry {
+    pub block: BlockId,
+    pub gen: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncManifest {
+    pub block_size: usize,
+    pub total_blocks: u32,
+    // solo los bloques con imagen, en orden
+    pub blocks: Vec<SyncEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncBlock {
+    pub block: BlockId,
+    pub gen: u64,
+    // payload en base64
+    pub data: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SyncResult {
+    // bloques que el otro tenia y aca faltaban o eran mas viejos
+    pub pulled: Vec<BlockId>,
+    // los que no se pudieron bajar o guardar, con el motivo
+    pub failed: Vec<(BlockId, String)>,
+}
+
+pub fn manifest(storage: &QrStorageManager) -> SyncManifest {
+    SyncManifest {
+        block_size: storage.block_size(),
+        total_blocks: storage.total_blocks(),
+        blocks: (0..storage.total_blocks())
+            .filter_map(|block| storage.generation(block).map(|gen| SyncEntry { block, gen }))
+            .collect(),
+    }
+}
+
+// None si el bloque no tiene imagen
+pub fn export_block(storage: &QrStorageManager, id: BlockId) -> Result<Option<SyncBlock>, QrfsError> {
+    let Some(gen) = storage.generation(id) else {
+        return Ok(None);
+    };
+    let data = storage.read_verified(id)?;
+    Ok(Some(SyncBlock {
+        block: id,
+        gen,
+        data: general_purpose::STANDARD.encode(data),
+    }))
+}
+
+// bloques del otro que aca faltan o tienen una generacion anterior
+pub fn blocks_to_pull(local: &SyncManifest, remote: &SyncManifest) -> Result<Vec<BlockId>, QrfsError> {
+    if (local.block_size, local.total_blocks) != (remote.block_size, remote.total_blocks) {
+        return Err(QrfsError::Other(format!(
+            "los discos no son copias: {} bloques de {} contra {} de {}",
+            local.total_blocks, local.block_size, remote.total_blocks, remote.block_size
+        )));
+    }
+    let mut ours = local.blocks.iter().peekable();
+    let mut pull = Vec::new();
+    for theirs in &remote.blocks {
+        while ours.next_if(|e| e.block < theirs.block).is_some() {}
+        match ours.peek() {
+            Some(e) if e.block == theirs.block && e.gen >= theirs.gen => {}
+            _ => pull.push(theirs.block),
+        }
+    }
+    Ok(pull)
+}
+
+// guarda un bloque del otro si sigue siendo mas nuevo que el de aca (pudo
+// llegar un escaneo mientras se bajaba); false si no hizo falta
+pub fn import_block(storage: &QrStorageManager, block: &SyncBlock) -> Result<bool, QrfsError> {
+    let data = general_purpose::STANDARD
+        .decode(&block.data)
+        .map_err(|e| QrfsError::Other(format!("bloque {}: base64 invalido: {}", block.block, e)))?;
+    if data.len() > storage.block_size() {
+        return Err(QrfsError::Other(format!("bloque {}: payload muy grande", block.block)));
+    }
+    storage.write_block_if_newer(block.block, &data, block.gen, false)
+}
+
+// pull desde peer (http://host:puerto del otro servidor). cada bloque se
+// toma solo para compararlo y escribirlo, nunca mientras se espera la red:
+// el otro puede estar haciendo pull de este al mismo tiempo
+pub fn pull(storage: &QrStorageManager, peer: &str) -> Result<SyncResult, QrfsError> {
+    let peer = peer.trim_end_matches('/');
+    let remote: SyncManifest = fetch_json(&format!("{}/sync/manifest", peer))?
+        .ok_or_else(|| QrfsError::NotFound(format!("{} no tiene /sync/manifest", peer)))?;
+    let local = manifest(storage);
+
+    let mut result = SyncResult::default();
+    for id in blocks_to_pull(&local, &remote)? {
+        let block = match fetch_json::<SyncBlock>(&format!("{}/sync/block/{}", peer, id)) {
+            Ok(Some(block)) if block.block == id => block,
+            Ok(Some(block)) => {
+                result.failed.push((id, format!("llego el bloque {}", block.block)));
+                continue;
+            }
+            Ok(None) => {
+                result.failed.push((id, "el otro ya no lo tiene".into()));
+                continue;
+            }
+            Err(e) => {
+                result.failed.push((id, e.to_string()));
+                continue;
+            }
+        };
+        match import_block(storage, &block) {
+            Ok(true) => result.pulled.push(id),
+            Ok(false) => {}
+            Err(e) => result.failed.push((id, e.to_string())),
+        }
+    }
+    Ok(result)
+}
+
+fn fetch_json<T: serde::de::DeserializeOwned>(url: &str) -> Result<Option<T>, QrfsError> {
+    fetch_url(url)?
+        .map(|body| {
+            serde_json::from_slice(&body).map_err(|e| QrfsError::Other(format!("{}: respuesta invalida: {}", url, e)))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest_of(blocks: &[(BlockId, u64)]) -> SyncManifest {
+        SyncManifest {
+            block_size: 128,
+            total_blocks: 64,
+            blocks: blocks.iter().map(|&(block, gen)| SyncEntry { block, gen }).collect(),
+        }
+    }
+
+    #[test]
+    fn missing_and_older_blocks_are_pulled() {
+        let local = manifest_of(&[(0, 3), (2, 1), (5, 4), (9, 1)]);
+        let remote = manifest_of(&[(0, 3), (1, 1), (2, 2), (5, 1), (7, 1)]);
+        assert_eq!(blocks_to_pull(&local, &remote).unwrap(), [1, 2, 7]);
+        assert!(blocks_to_pull(&remote, &remote).unwrap().is_empty());
+
+        let mut other = manifest_of(&[]);
+        other.total_blocks = 400;
+        assert!(blocks_to_pull(&local, &other).is_err());
+    }
+}
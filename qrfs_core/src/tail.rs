@@ -0,0 +1,148 @@
+// empaquetado de colas: el ultimo bloque a medio llenar de varios archivos
+// chicos se guarda en un solo bloque compartido (un qr en vez de uno por
+// archivo). el inodo marca con INODE_FLAG_TAIL que su ultimo bloque es
+// compartido y busca su cola en el mapa del bloque por su numero de inodo
+//
+// formato: [magic 'T'][cantidad u8] y por cada cola (inodo u32, inicio u16,
+// largo u16, little endian); los datos siguen al mapa. una entrada de un
+// inodo que ya no apunta al bloque (borrado o desempaquetado) queda como
+// basura hasta que se libera el bloque: se busca solo la propia
+
+use crate::errors::QrfsError;
+
+pub const TAIL_MAGIC: u8 = b'T';
+
+const HEADER_LEN: usize = 2;
+// bytes de una entrada del mapa
+pub const ENTRY_LEN: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TailEntry {
+    pub inode: u32,
+    pub offset: u16,
+    pub len: u16,
+}
+
+// cola mas larga que se empaqueta: tienen que entrar al menos dos por bloque
+pub fn max_tail(block_size: usize) -> usize {
+    block_size.saturating_sub(HEADER_LEN + 2 * ENTRY_LEN) / 2
+}
+
+// mapa de un bloque compartido; error si el encabezado no es de colas o una
+// entrada se sale del bloque o pisa a otra
+pub fn parse_map(block: &[u8]) -> Result<Vec<TailEntry>, QrfsError> {
+    let invalid = |why: &str| QrfsError::Other(format!("bloque de colas invalido: {}", why));
+    if block.len() < HEADER_LEN || block[0] != TAIL_MAGIC {
+        return Err(invalid("sin encabezado"));
+    }
+    let count = block[1] as usize;
+    let data_start = HEADER_LEN + count * ENTRY_LEN;
+    if data_start > block.len() {
+        return Err(invalid("el mapa no entra en el bloque"));
+    }
+    let entries: Vec<TailEntry> = block[HEADER_LEN..data_start]
+        .chunks_exact(ENTRY_LEN)
+        .map(|raw| TailEntry {
+            inode: u32::from_le_bytes(raw[0..4].try_into().unwrap()),
+            offset: u16::from_le_bytes(raw[4..6].try_into().unwrap()),
+            len: u16::from_le_bytes(raw[6..8].try_into().unwrap()),
+        })
+        .collect();
+
+    let mut sorted = entries.clone();
+    sorted.sort_by_key(|e| e.offset);
+    let mut end = data_start;
+    for entry in &sorted {
+        if (entry.offset as usize) < end {
+            return Err(invalid(&format!("la cola del inodo {} pisa a otra", entry.inode)));
+        }
+        end = entry.offset as usize + entry.len as usize;
+        if end > block.len() {
+            return Err(invalid(&format!("la cola del inodo {} se sale del bloque", entry.inode)));
+        }
+    }
+    let mut seen = std::collections::HashSet::new();
+    if !entries.iter().all(|e| seen.insert(e.inode)) {
+        return Err(invalid("un inodo con dos colas"));
+    }
+    Ok(entries)
+}
+
+// la cola de un inodo, tal como estaba en su ultimo bloque
+pub fn tail_of(block: &[u8], inode: u32) -> Result<&[u8], QrfsError> {
+    let entry = parse_map(block)?
+        .into_iter()
+        .find(|e| e.inode == inode)
+        .ok_or_else(|| QrfsError::Other(format!("el bloque de colas no tiene la del inodo {}", inode)))?;
+    Ok(&block[entry.offset as usize..entry.offset as usize + entry.len as usize])
+}
+
+fn fits(block_size: usize, tails: &[(u32, Vec<u8>)], extra: usize) -> bool {
+    let data: usize = tails.iter().map(|(_, t)| t.len()).sum::<usize>() + extra;
+    tails.len() < u8::MAX as usize && HEADER_LEN + (tails.len() + 1) * ENTRY_LEN + data <= block_size
+}
+
+// arma un bloque compartido con las colas dadas (tienen que entrar)
+pub fn encode(block_size: usize, tails: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let mut block = vec![0u8; block_size];
+    block[0] = TAIL_MAGIC;
+    block[1] = tails.len() as u8;
+    let mut offset = HEADER_LEN + tails.len() * ENTRY_LEN;
+    for (i, (inode, data)) in tails.iter().enumerate() {
+        let entry = &mut block[HEADER_LEN + i * ENTRY_LEN..HEADER_LEN + (i + 1) * ENTRY_LEN];
+        entry[0..4].copy_from_slice(&inode.to_le_bytes());
+        entry[4..6].copy_from_slice(&(offset as u16).to_le_bytes());
+        entry[6..8].copy_from_slice(&(data.len() as u16).to_le_bytes());
+        block[offset..offset + data.len()].copy_from_slice(data);
+        offset += data.len();
+    }
+    block
+}
+
+// reparte las colas en bloques (primero las mas largas, cada una en el
+// primero donde entre); un bloque con una sola cola no ahorra nada y no se
+// devuelve
+pub fn pack(block_size: usize, mut tails: Vec<(u32, Vec<u8>)>) -> Vec<Vec<(u32, Vec<u8>)>> {
+    tails.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then(a.0.cmp(&b.0)));
+    let mut bins: Vec<Vec<(u32, Vec<u8>)>> = Vec::new();
+    for tail in tails {
+        match bins.iter_mut().find(|bin| fits(block_size, bin, tail.1.len())) {
+            Some(bin) => bin.push(tail),
+            None => bins.push(vec![tail]),
+        }
+    }
+    bins.retain(|bin| bin.len() > 1);
+    bins
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tails_are_packed_and_found_by_inode() {
+        let tails: Vec<(u32, Vec<u8>)> = (1..=9u32).map(|i| (i, vec![i as u8; 10 + i as usize])).collect();
+        let bins = pack(128, tails);
+        let packed: usize = bins.iter().map(|bin| bin.len()).sum();
+        assert_eq!(packed, 9);
+        assert!(bins.len() < 9);
+
+        for bin in &bins {
+            let block = encode(128, bin);
+            assert_eq!(parse_map(&block).unwrap().len(), bin.len());
+            for (inode, data) in bin {
+                assert_eq!(tail_of(&block, *inode).unwrap(), &data[..]);
+            }
+            assert!(tail_of(&block, 99).is_err());
+        }
+
+        // una sola cola no se empaqueta
+        assert!(pack(128, vec![(1, vec![1; 40])]).is_empty());
+
+        // un bloque comun o un mapa que se sale del bloque no se leen como colas
+        assert!(parse_map(&[0u8; 128]).is_err());
+        let mut block = encode(128, &[(1, vec![1; 20]), (2, vec![2; 20])]);
+        block[6] = 120;
+        assert!(parse_map(&block).is_err());
+    }
+}
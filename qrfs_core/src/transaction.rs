@@ -0,0 +1,74 @@
+// transaccion de metadata
+// junta los cambios de una operacion (inodos, bitmap, directorios) para que
+// QrfsFilesystem los escriba juntos y en un orden fijo al hacer commit.
+// si la operacion o el commit fallan, con lo guardado aca se vuelve al estado
+// en memoria de antes de empezar
+
+use std::collections::HashMap;
+
+use crate::disk::{BlockId, Inode, InodeKind, Superblock};
+
+// entradas en cache de un directorio: nombre -> (inodo, tipo)
+// el tipo sale de la entrada guardada, asi readdir no mira la tabla de inodos
+pub(crate) type DirEntries = HashMap<String, (u32, InodeKind)>;
+
+#[derive(Debug, Default)]
+pub struct MetadataTransaction {
+    // estado previo de cada cosa, guardado la primera vez que se toca
+    pub(crate) bitmap_before: Option<Vec<u8>>,
+    pub(crate) inodes_before: HashMap<u32, Option<Inode>>,
+    // cache de cada directorio tocado (None = no estaba en cache)
+    pub(crate) dirs_before: HashMap<u32, Option<DirEntries>>,
+    // superblock y extension de la tabla de inodos (cuando la tabla crece)
+    pub(crate) superblock_before: Option<(Superblock, Vec<BlockId>)>,
+
+    // hubo bloques reservados en el bitmap
+    pub(crate) allocated: bool,
+    // el commit ya escribio el bitmap con esas reservas
+    pub(crate) bitmap_written: bool,
+    // el commit ya empezo a escribir directorios o la tabla de inodos
+    pub(crate) metadata_written: bool,
+
+    // bloques a liberar: se marcan libres recien al final del commit, asi un
+    // corte a mitad deja bloques huerfanos (recuperables) y nunca compartidos
+    pub(crate) freed: Vec<BlockId>,
+}
+
+impl MetadataTransaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // no hay nada que escribir
+    pub fn is_empty(&self) -> bool {
+        !self.allocated
+            && self.freed.is_empty()
+            && self.inodes_before.is_empty()
+            && self.dirs_before.is_empty()
+            && self.superblock_before.is_none()
+    }
+
+    pub(crate) fn touch_bitmap(&mut self, bitmap: &[u8]) {
+        if self.bitmap_before.is_none() {
+            self.bitmap_before = Some(bitmap.to_vec());
+        }
+    }
+
+    pub(crate) fn touch_inode(&mut self, id: u32, current: Option<&Inode>) {
+        self.inodes_before
+            .entry(id)
+            .or_insert_with(|| current.cloned());
+    }
+
+    pub(crate) fn touch_dir(&mut self, dir_id: u32, current: Option<&DirEntries>) {
+        self.dirs_before
+            .entry(dir_id)
+            .or_insert_with(|| current.cloned());
+    }
+
+    pub(crate) fn touch_superblock(&mut self, sb: &Superblock, ext_blocks: &[BlockId]) {
+        if self.superblock_before.is_none() {
+            self.superblock_before = Some((sb.clone(), ext_blocks.to_vec()));
+        }
+    }
+}
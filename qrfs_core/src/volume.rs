@@ -0,0 +1,431 @@
+// disco repartido en varias carpetas de qr ("volumen 2 de 3"): el
+// superblock guarda el rango de bloques de cada volumen y cada carpeta una
+// marca con el numero del juego y su lugar en el. el primer volumen lleva
+// toda la metadata (superblock, bitmap, tabla de inodos y zona fija), asi el
+// superblock se lee sin los demas
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::crypt::disk_bytes;
+use crate::disk::{BlockId, Superblock, VolumeRecord, VolumeSet, MAX_VOLUMES};
+use crate::errors::QrfsError;
+use crate::fs_format::read_superblock;
+use crate::health::HealthMonitor;
+use crate::storage::{BlockStorage, QrStorageManager};
+
+// marca de cada carpeta del juego
+pub const VOLUME_FILE: &str = ".qrfs_volume";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VolumeMarker {
+    // el id del volume_set del superblock
+    pub set: u64,
+    // desde 1, como en la etiqueta
+    pub index: u32,
+    pub count: u32,
+    pub first: BlockId,
+    pub blocks: u32,
+}
+
+impl VolumeMarker {
+    // Ok(None) si la carpeta no tiene marca
+    pub fn load(dir: impl AsRef<Path>) -> Result<Option<Self>, QrfsError> {
+        let path = dir.as_ref().join(VOLUME_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        serde_json::from_str(&fs::read_to_string(&path)?)
+            .map(Some)
+            .map_err(|e| QrfsError::Other(format!("marca de volumen invalida '{}': {}", path.display(), e)))
+    }
+
+    pub fn save(&self, dir: impl AsRef<Path>) -> Result<(), QrfsError> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| QrfsError::Other(format!("no se pudo serializar la marca de volumen: {}", e)))?;
+        fs::write(dir.as_ref().join(VOLUME_FILE), json)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for VolumeMarker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "volumen {} de {} (bloques {} a {})",
+            self.index,
+            self.count,
+            self.first,
+            self.first + self.blocks.saturating_sub(1)
+        )
+    }
+}
+
+// reparte los bloques del disco en count rangos seguidos y sortea el numero
+// del juego. el primero crece hasta tener la metadata y la zona fija
+pub fn split_volumes(sb: &mut Superblock, count: u32) -> Result<(), QrfsError> {
+    if count < 2 || count as usize > MAX_VOLUMES {
+        return Err(QrfsError::Other(format!(
+            "un disco se reparte en 2 a {} volumenes, no en {}",
+            MAX_VOLUMES, count
+        )));
+    }
+    let first = sb.total_blocks.div_ceil(count).max(sb.data_block_start + sb.pinned_blocks);
+    let rest = sb.total_blocks.saturating_sub(first);
+    let others = count - 1;
+    if rest < others {
+        return Err(QrfsError::Other(format!(
+            "{} bloques no alcanzan para {} volumenes (la metadata ocupa {})",
+            sb.total_blocks, count, first
+        )));
+    }
+
+    let mut volumes = vec![VolumeRecord { first: 0, count: first }];
+    let mut next = first;
+    for n in 0..others {
+        // el resto de la division va a los primeros
+        let blocks = rest / others + u32::from(n < rest % others);
+        volumes.push(VolumeRecord { first: next, count: blocks });
+        next += blocks;
+    }
+
+    let mut set = [0u8; 8];
+    disk_bytes(sb.deterministic, "volumes", &mut set)?;
+    sb.volume_set = Some(VolumeSet { id: u64::from_le_bytes(set), volumes });
+    Ok(())
+}
+
+// carpetas por defecto: la del disco y a su lado disco.vol2, disco.vol3...
+pub fn default_paths(disk: &str, count: usize) -> Vec<PathBuf> {
+    let base = disk.trim_end_matches('/');
+    (1..=count)
+        .map(|n| match n {
+            1 => PathBuf::from(disk),
+            n => PathBuf::from(format!("{}.vol{}", base, n)),
+        })
+        .collect()
+}
+
+// --volume-dirs disco.vol2 disco.vol3 ...: las carpetas de los volumenes 2
+// en adelante, hasta la proxima opcion
+pub fn volume_dirs_arg(args: &[String]) -> Vec<PathBuf> {
+    args.iter()
+        .skip_while(|arg| *arg != "--volume-dirs")
+        .skip(1)
+        .take_while(|arg| !arg.starts_with("--"))
+        .map(PathBuf::from)
+        .collect()
+}
+
+// las carpetas del disco en orden: la del disco y las de --volume-dirs, o
+// sin ellas las de al lado (default_paths)
+pub fn volume_paths(disk: &str, sb: &Superblock, dirs: Vec<PathBuf>) -> Vec<PathBuf> {
+    if dirs.is_empty() {
+        return default_paths(disk, sb.volumes().len());
+    }
+    std::iter::once(PathBuf::from(disk)).chain(dirs).collect()
+}
+
+// superblock entero (con los registros de volumen, que pueden no entrar en
+// el bloque 0) de la primera carpeta de un disco repartido; None si no tiene
+// bloque 0 o es de una sola carpeta
+pub fn probe_volumes(disk: &str) -> Result<Option<Superblock>, QrfsError> {
+    let Some(head) = QrStorageManager::probe_superblock(disk)? else {
+        return Ok(None);
+    };
+    let storage = QrStorageManager::new(disk, head.block_size as usize, head.total_blocks)
+        .with_shard_size(head.shard_size)
+        .with_codec(head.codec);
+    let sb = read_superblock(&storage)?;
+    Ok(Some(sb).filter(|sb| sb.volume_set.is_some()))
+}
+
+// problemas del juego de carpetas contra el superblock: cantidad, marcas que
+// faltan o son de otro disco o de otro lugar, y qr fuera del rango de su
+// carpeta. vacio si esta todo bien
+pub fn check_volumes(sb: &Superblock, paths: &[PathBuf]) -> Vec<String> {
+    let mut problems = Vec::new();
+    if paths.len() != sb.volumes().len() {
+        problems.push(format!(
+            "el disco tiene {} volumenes y se dieron {} carpetas",
+            sb.volumes().len(),
+            paths.len()
+        ));
+    }
+    let set = sb.volume_set.as_ref().map_or(0, |set| set.id);
+    let count = sb.volumes().len() as u32;
+    for (n, (record, path)) in sb.volumes().iter().zip(paths).enumerate() {
+        let index = n as u32 + 1;
+        let shown = path.display();
+        match VolumeMarker::load(path) {
+            Err(e) => problems.push(e.to_string()),
+            Ok(None) => problems.push(format!("'{}' no tiene marca de volumen ({})", shown, VOLUME_FILE)),
+            Ok(Some(marker)) if marker.set != set => problems.push(format!(
+                "'{}' es de otro disco (juego {:016x}, se esperaba {:016x})",
+                shown, marker.set, set
+            )),
+            Ok(Some(marker)) if marker.index != index || marker.count != count => problems.push(format!(
+                "'{}' es el volumen {} de {}, en ese lugar va el {} de {}",
+                shown, marker.index, marker.count, index, count
+            )),
+            Ok(Some(marker)) if (marker.first, marker.blocks) != (record.first, record.count) => {
+                problems.push(format!(
+                    "'{}' dice tener {} bloques desde el {} y el superblock {} desde el {}",
+                    shown, marker.blocks, marker.first, record.count, record.first
+                ))
+            }
+            Ok(Some(_)) => {}
+        }
+        let mut found = Vec::new();
+        block_images(path, &mut found);
+        found.sort_unstable();
+        for id in found.into_iter().filter(|&id| sb.volume_of(id) != Some(n)) {
+            match sb.volume_of(id) {
+                Some(other) => problems.push(format!(
+                    "'{}' tiene el qr del bloque {}, que va en el volumen {}",
+                    shown,
+                    id,
+                    other + 1
+                )),
+                None => problems.push(format!("'{}' tiene el qr del bloque {}, fuera del disco", shown, id)),
+            }
+        }
+    }
+    problems
+}
+
+// ids de los png de bloque (000123.png) de la carpeta y sus subcarpetas
+fn block_images(dir: &Path, found: &mut Vec<BlockId>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            block_images(&path, found);
+            continue;
+        }
+        let id = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(".png"))
+            .filter(|stem| stem.len() == 6)
+            .and_then(|stem| stem.parse().ok());
+        if let Some(id) = id {
+            found.push(id);
+        }
+    }
+}
+
+// el disco armado con todas sus carpetas: cada bloque va a la de su volumen
+pub struct VolumeStorage {
+    block_size: usize,
+    total_blocks: u32,
+    volumes: Vec<(VolumeRecord, QrStorageManager)>,
+    // compartido por todas las carpetas
+    health: Arc<HealthMonitor>,
+}
+
+impl VolumeStorage {
+    // abre un juego existente con las carpetas en orden; configure agrega a
+    // cada una las opciones del perfil (qr, cache, reintentos)
+    pub fn open(
+        sb: &Superblock,
+        paths: &[PathBuf],
+        configure: impl Fn(QrStorageManager) -> QrStorageManager,
+    ) -> Result<Self, QrfsError> {
+        let problems = check_volumes(sb, paths);
+        if !problems.is_empty() {
+            return Err(QrfsError::Other(format!("juego de volumenes invalido: {}", problems.join("; "))));
+        }
+        Ok(Self::assemble(sb, paths, configure))
+    }
+
+    // mkfs: crea las carpetas y deja la marca en cada una
+    pub fn create(
+        sb: &Superblock,
+        paths: &[PathBuf],
+        configure: impl Fn(QrStorageManager) -> QrStorageManager,
+    ) -> Result<Self, QrfsError> {
+        if paths.len() != sb.volumes().len() {
+            return Err(QrfsError::Other(format!(
+                "el disco tiene {} volumenes y se dieron {} carpetas",
+                sb.volumes().len(),
+                paths.len()
+            )));
+        }
+        let count = sb.volumes().len() as u32;
+        for (n, (record, path)) in sb.volumes().iter().zip(paths).enumerate() {
+            fs::create_dir_all(path)?;
+            let marker = VolumeMarker {
+                set: sb.volume_set.as_ref().map_or(0, |set| set.id),
+                index: n as u32 + 1,
+                count,
+                first: record.first,
+                blocks: record.count,
+            };
+            marker.save(path)?;
+        }
+        Ok(Self::assemble(sb, paths, configure))
+    }
+
+    fn assemble(
+        sb: &Superblock,
+        paths: &[PathBuf],
+        configure: impl Fn(QrStorageManager) -> QrStorageManager,
+    ) -> Self {
+        let health = HealthMonitor::new();
+        let volumes = sb
+            .volumes()
+            .iter()
+            .zip(paths)
+            .map(|(&record, path)| {
+                // las carpetas sin bloque 0 no pueden leer el reparto en
+                // subcarpetas ni el codec
+                let storage = QrStorageManager::new(path, sb.block_size as usize, sb.total_blocks)
+                    .with_shard_size(sb.shard_size)
+                    .with_codec(sb.codec);
+                (record, configure(storage).with_health(health.clone()))
+            })
+            .collect();
+        Self {
+            block_size: sb.block_size as usize,
+            total_blocks: sb.total_blocks,
+            volumes,
+            health,
+        }
+    }
+
+    pub fn volumes(&self) -> impl Iterator<Item = (&VolumeRecord, &QrStorageManager)> {
+        self.volumes.iter().map(|(record, storage)| (record, storage))
+    }
+
+    fn volume(&self, id: BlockId) -> Result<&QrStorageManager, QrfsError> {
+        self.volumes
+            .iter()
+            .find(|(record, _)| id >= record.first && id - record.first < record.count)
+            .map(|(_, storage)| storage)
+            .ok_or_else(|| QrfsError::Other(format!("bloque {} fuera de los volumenes del disco", id)))
+    }
+}
+
+impl BlockStorage for VolumeStorage {
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn total_blocks(&self) -> u32 {
+        self.total_blocks
+    }
+
+    fn read_block(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        self.volume(id)?.read_block(id)
+    }
+
+    fn write_block(&self, id: BlockId, data: &[u8]) -> Result<(), QrfsError> {
+        self.volume(id)?.write_block(id, data)
+    }
+
+    fn read_block_into(&self, id: BlockId, buf: &mut [u8]) -> Result<(), QrfsError> {
+        self.volume(id)?.read_block_into(id, buf)
+    }
+
+    fn read_verified(&self, id: BlockId) -> Result<Vec<u8>, QrfsError> {
+        self.volume(id)?.read_verified(id)
+    }
+
+    fn reencode_block(&self, id: BlockId) -> Result<(), QrfsError> {
+        self.volume(id)?.reencode_block(id)
+    }
+
+    fn has_block(&self, id: BlockId) -> bool {
+        self.volume(id).is_ok_and(|storage| storage.has_block(id))
+    }
+
+    fn host_bytes(&self, id: BlockId) -> u64 {
+        self.volume(id).map_or(0, |storage| storage.host_bytes(id))
+    }
+
+    fn health(&self) -> Option<Arc<HealthMonitor>> {
+        Some(self.health.clone())
+    }
+
+    fn block_freed(&self, id: BlockId) {
+        if let Ok(storage) = self.volume(id) {
+            storage.block_freed(id);
+        }
+    }
+
+    fn barrier(&self) -> Result<(), QrfsError> {
+        for (_, storage) in &self.volumes {
+            storage.barrier()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("qrfs_volume_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn the_first_volume_keeps_the_metadata_and_the_rest_is_shared_out() {
+        let mut sb = Superblock::new(100, 64);
+        sb.pinned_blocks = 40;
+        split_volumes(&mut sb, 3).unwrap();
+
+        let meta = sb.data_block_start + sb.pinned_blocks;
+        assert_eq!(sb.volumes()[0], VolumeRecord { first: 0, count: meta });
+        assert_eq!(sb.volumes().iter().map(|v| v.count).sum::<u32>(), 100);
+        assert_eq!(sb.volumes()[1].first, meta);
+        assert_eq!(sb.volumes()[2].first, sb.volumes()[1].first + sb.volumes()[1].count);
+        assert_eq!(sb.volume_of(99), Some(2));
+        assert_eq!(sb.volume_of(100), None);
+        assert!(split_volumes(&mut sb, 1).is_err());
+        assert!(split_volumes(&mut Superblock::new(20, 64), 8).is_err());
+    }
+
+    #[test]
+    fn blocks_land_in_their_folder_and_a_swapped_set_is_refused() {
+        let dir = temp_dir("juego");
+        let mut sb = Superblock::new(32, 16);
+        split_volumes(&mut sb, 2).unwrap();
+        let paths = default_paths(dir.join("disco").to_str().unwrap(), 2);
+        let storage = VolumeStorage::create(&sb, &paths, |s| s).unwrap();
+
+        let last = sb.total_blocks - 1;
+        storage.write_block(last, &[9; 40]).unwrap();
+        assert!(paths[1].join(format!("{:06}.png", last)).exists());
+        assert!(!paths[0].join(format!("{:06}.png", last)).exists());
+        assert_eq!(&storage.read_block(last).unwrap()[..40], &[9; 40]);
+        assert!(storage.write_block(sb.total_blocks, &[1]).is_err());
+
+        assert!(check_volumes(&sb, &paths).is_empty());
+        assert!(VolumeStorage::open(&sb, &paths, |s| s).is_ok());
+        let swapped = vec![paths[1].clone(), paths[0].clone()];
+        assert!(VolumeStorage::open(&sb, &swapped, |s| s).is_err());
+        assert!(VolumeStorage::open(&sb, &paths[..1], |s| s).is_err());
+
+        // un qr que quedo en la carpeta equivocada
+        fs::copy(
+            paths[1].join(format!("{:06}.png", last)),
+            paths[0].join(format!("{:06}.png", last)),
+        )
+        .unwrap();
+        let problems = check_volumes(&sb, &paths);
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert!(problems[0].contains("va en el volumen 2"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
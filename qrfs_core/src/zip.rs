@@ -0,0 +1,141 @@
+// zip minimo para empaquetar los qr de un archivo (qr_extract --zip, publish)
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::errors::QrfsError;
+
+// zip minimo sin compresion (los png ya vienen comprimidos): encabezado local
+// + datos por entrada y al final el directorio central
+pub struct ZipWriter {
+    out: BufWriter<File>,
+    offset: u64,
+    // nombre, crc, largo y posicion del encabezado local de cada entrada
+    entries: Vec<(String, u32, u32, u32)>,
+}
+
+// 1980-01-01 00:00, la fecha mas vieja que entiende el formato
+const ZIP_DOS_DATE: u16 = 0x21;
+
+// bit 11: los nombres van en utf-8 (si no, cada programa los lee en su
+// pagina de codigos y los acentos salen rotos)
+const ZIP_FLAG_UTF8: u16 = 0x0800;
+
+// sin zip64 cada posicion del archivo va en 32 bits
+const ZIP_MAX_OFFSET: u64 = u32::MAX as u64;
+
+impl ZipWriter {
+    pub fn create(path: &Path) -> Result<Self, QrfsError> {
+        Ok(Self {
+            out: BufWriter::new(File::create(path)?),
+            offset: 0,
+            entries: Vec::new(),
+        })
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), QrfsError> {
+        self.out.write_all(bytes)?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+
+    // sin zip64: hasta 4 GiB y 65535 entradas
+    pub fn add(&mut self, name: &str, data: &[u8]) -> Result<(), QrfsError> {
+        let end = self.offset + 30 + name.len() as u64 + data.len() as u64;
+        if end > ZIP_MAX_OFFSET || self.entries.len() >= u16::MAX as usize || name.len() > u16::MAX as usize {
+            return Err(too_big());
+        }
+        let mut crc = flate2::Crc::new();
+        crc.update(data);
+        let crc = crc.sum();
+        let header_offset = self.offset as u32;
+
+        let mut header = Vec::with_capacity(30 + name.len());
+        header.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        header.extend_from_slice(&20u16.to_le_bytes()); // version necesaria
+        header.extend_from_slice(&ZIP_FLAG_UTF8.to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes()); // sin compresion
+        header.extend_from_slice(&0u16.to_le_bytes()); // hora
+        header.extend_from_slice(&ZIP_DOS_DATE.to_le_bytes());
+        header.extend_from_slice(&crc.to_le_bytes());
+        header.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes()); // extra
+        header.extend_from_slice(name.as_bytes());
+        self.write(&header)?;
+        self.write(data)?;
+
+        self.entries.push((name.to_string(), crc, data.len() as u32, header_offset));
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), QrfsError> {
+        // el directorio central entero tiene que quedar antes de los 4 GiB
+        let entries = std::mem::take(&mut self.entries);
+        let directory_len: u64 = entries.iter().map(|(name, ..)| 46 + name.len() as u64).sum();
+        if self.offset + directory_len > ZIP_MAX_OFFSET {
+            return Err(too_big());
+        }
+        let directory_offset = self.offset as u32;
+        for (name, crc, len, header_offset) in &entries {
+            let mut record = Vec::with_capacity(46 + name.len());
+            record.extend_from_slice(&0x02014b50u32.to_le_bytes());
+            record.extend_from_slice(&20u16.to_le_bytes()); // hecho por
+            record.extend_from_slice(&20u16.to_le_bytes()); // version necesaria
+            record.extend_from_slice(&ZIP_FLAG_UTF8.to_le_bytes());
+            record.extend_from_slice(&0u16.to_le_bytes()); // sin compresion
+            record.extend_from_slice(&0u16.to_le_bytes()); // hora
+            record.extend_from_slice(&ZIP_DOS_DATE.to_le_bytes());
+            record.extend_from_slice(&crc.to_le_bytes());
+            record.extend_from_slice(&len.to_le_bytes());
+            record.extend_from_slice(&len.to_le_bytes());
+            record.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            // extra, comentario, disco, atributos internos y externos
+            record.extend_from_slice(&[0u8; 12]);
+            record.extend_from_slice(&header_offset.to_le_bytes());
+            record.extend_from_slice(name.as_bytes());
+            self.write(&record)?;
+        }
+        let directory_size = self.offset as u32 - directory_offset;
+
+        let mut end = Vec::with_capacity(22);
+        end.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes()); // este disco
+        end.extend_from_slice(&0u16.to_le_bytes()); // disco del directorio
+        end.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        end.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        end.extend_from_slice(&directory_size.to_le_bytes());
+        end.extend_from_slice(&directory_offset.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes()); // comentario
+        self.write(&end)?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+fn too_big() -> QrfsError {
+    QrfsError::Other("el zip supera los limites del formato sin zip64".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_flagged_as_utf8_in_both_headers() {
+        let path = std::env::temp_dir().join(format!("qrfs_zip_test_{}.zip", std::process::id()));
+        let mut zip = ZipWriter::create(&path).unwrap();
+        zip.add("año/señal.png", b"datos").unwrap();
+        zip.finish().unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let flags = |at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+        assert_eq!(&bytes[..4], &0x04034b50u32.to_le_bytes());
+        assert_eq!(flags(6), ZIP_FLAG_UTF8);
+        let central = bytes.windows(4).position(|w| w == 0x02014b50u32.to_le_bytes()).unwrap();
+        assert_eq!(flags(central + 8), ZIP_FLAG_UTF8);
+    }
+}